@@ -0,0 +1,33 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use delve_rs::{bench, filters::Filters, query, search_backend::SearchConfig};
+
+/// Replays the query corpus against the fixture dump in `tests/fixtures/dump`,
+/// or against the database at `$DELVE_RS_BENCH_DB` when it's set.
+fn queries(c: &mut Criterion) {
+    let (_dir, db, cache, index) = match std::env::var("DELVE_RS_BENCH_DB") {
+        Ok(path) => {
+            let (db, cache, index) =
+                delve_rs::open(path, &SearchConfig::default()).expect("error opening database");
+            cache.refresh_blocking().expect("error loading cache");
+            (None, db, cache, index)
+        }
+        Err(_) => {
+            let (dir, db, cache, index) =
+                bench::import_fixture(&SearchConfig::default()).expect("error importing fixture");
+            (Some(dir), db, cache, index)
+        }
+    };
+    let queries = bench::load_queries("benches/queries.txt").expect("error loading queries");
+
+    let mut group = c.benchmark_group("query");
+    for q in &queries {
+        let (terms, filters) = Filters::parse(q);
+        group.bench_function(q, |b| {
+            b.iter(|| query(&terms, &filters, &db, &cache, &index).expect("query failed"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
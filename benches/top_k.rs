@@ -0,0 +1,68 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use delve_rs::top_k::TopK;
+
+/// The number of results kept by `query()`.
+const RESULTS: usize = 1_000;
+
+/// Compares selecting the best results of a broad query, which scores tens of
+/// thousands of crates, with a [`TopK`] and with the sorted insertion
+/// `query()` used before it.
+fn select(c: &mut Criterion) {
+    let mut group = c.benchmark_group("top_k");
+    for candidates in [10_000, 100_000] {
+        let scores = scores(candidates);
+        group.bench_with_input(
+            BenchmarkId::new("heap", candidates),
+            &scores,
+            |b, scores| {
+                b.iter(|| {
+                    let mut top = TopK::new(RESULTS);
+                    for (id, score) in scores.iter().enumerate() {
+                        top.push(*score, id as u64);
+                    }
+                    black_box(top.into_sorted_vec())
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sorted_insert", candidates),
+            &scores,
+            |b, scores| b.iter(|| black_box(sorted_insert(scores))),
+        );
+    }
+    group.finish();
+}
+
+fn sorted_insert(scores: &[f32]) -> Vec<(f32, u64)> {
+    let mut results = Vec::<(f32, u64)>::with_capacity(RESULTS);
+    for (id, score) in scores.iter().enumerate() {
+        let insert_at = match results.binary_search_by(|(other, _)| score.total_cmp(other)) {
+            Ok(insert_at) | Err(insert_at) => insert_at,
+        };
+        if insert_at < RESULTS {
+            results.insert(insert_at, (*score, id as u64));
+            results.truncate(RESULTS);
+        }
+    }
+    results
+}
+
+/// Returns `count` scores spread like a broad query's: mostly low, with a long
+/// tail of strong matches, in no particular order.
+fn scores(count: usize) -> Vec<f32> {
+    // SplitMix64, so that every run benchmarks the same scores.
+    let mut state = 0_u64;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut value = state;
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            let uniform = (value ^ (value >> 31)) as f32 / u64::MAX as f32;
+            uniform.powi(4) * 20.
+        })
+        .collect()
+}
+
+criterion_group!(benches, select);
+criterion_main!(benches);
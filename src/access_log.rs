@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::mpsc::{self, SyncSender, TrySendError},
+    time::Instant,
+};
+
+use axum::{
+    extract::State,
+    http::{Request, Uri},
+    middleware::Next,
+    response::Response,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// The maximum number of characters of a search query that are logged.
+const MAX_LOGGED_QUERY: usize = 200;
+
+/// The number of lines that can be waiting to be written before further
+/// requests go unlogged.
+const QUEUED_LINES: usize = 10_000;
+
+/// A file that receives one tab-separated line per request. Lines are written
+/// by a dedicated thread, so that requests never wait on the disk.
+#[derive(Clone, Debug)]
+pub(crate) struct AccessLog(SyncSender<String>);
+
+impl AccessLog {
+    /// Opens the access log at `path`, appending to it if it exists, and
+    /// starts the thread that writes to it.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_LINES);
+        std::thread::Builder::new()
+            .name(String::from("access-log"))
+            .spawn(move || write_lines(file, receiver))?;
+        Ok(Self(sender))
+    }
+
+    fn record(&self, line: String) {
+        match self.0.try_send(line) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => tracing::warn!("access log is behind, dropping a line"),
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// Writes each line received to `file` until every [`AccessLog`] is dropped.
+fn write_lines(mut file: File, lines: mpsc::Receiver<String>) {
+    for line in lines {
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            tracing::warn!("error writing access log: {err}");
+        }
+    }
+}
+
+/// Middleware that records every request in an [`AccessLog`].
+pub(crate) async fn log_access<B>(
+    State(log): State<AccessLog>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let query = sanitized_query(request.uri());
+    let request_id = request_id(&request);
+
+    let response = next.run(request).await;
+
+    let timestamp = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+    log.record(format!(
+        "{timestamp}\t{request_id}\t{method}\t{path}\t{}\t{}\t{query}\n",
+        response.status().as_u16(),
+        start.elapsed().as_micros(),
+    ));
+
+    response
+}
+
+/// Returns the id assigned to `request` with control characters removed, or
+/// `-` if it has none. Header values can contain tabs, which would otherwise
+/// shift the columns of the access log.
+pub(crate) fn request_id<B>(request: &Request<B>) -> String {
+    request
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .map(|id| id.chars().filter(|ch| !ch.is_control()).collect())
+        .unwrap_or_else(|| String::from("-"))
+}
+
+/// Returns the search text in `uri`'s `q` parameter with control characters
+/// removed, so that it can't forge extra log lines.
+pub(crate) fn sanitized_query(uri: &Uri) -> String {
+    let Some(query) = uri.query() else { return String::new() };
+    serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+        .ok()
+        .and_then(|params| params.into_iter().find(|(key, _)| key == "q"))
+        .map(|(_, q)| {
+            q.chars()
+                .filter(|ch| !ch.is_control())
+                .take(MAX_LOGGED_QUERY)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn request(request_id: Option<&[u8]>, uri: &str) -> Request<()> {
+        let mut request = Request::builder().uri(uri);
+        if let Some(request_id) = request_id {
+            request = request.header("x-request-id", request_id);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn request_ids_cant_add_columns() {
+        assert_eq!(request_id(&request(Some(b"abc-123"), "/")), "abc-123");
+        assert_eq!(request_id(&request(Some(b"abc\t200\t1"), "/")), "abc2001");
+        assert_eq!(request_id(&request(None, "/")), "-");
+    }
+
+    #[test]
+    fn queries_cant_add_lines() {
+        let uri = "/search?q=serde%0Ajson%09x&page=2".parse().unwrap();
+        assert_eq!(sanitized_query(&uri), "serdejsonx");
+        assert_eq!(sanitized_query(&"/search".parse().unwrap()), "");
+    }
+
+    #[test]
+    fn lines_are_appended() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("access.log");
+        std::fs::write(&path, "earlier\n").unwrap();
+
+        let log = AccessLog::open(&path).unwrap();
+        log.record(String::from("first\n"));
+        log.record(String::from("second\n"));
+        drop(log);
+
+        // The lines are written by another thread.
+        let mut contents = String::new();
+        for _ in 0..100 {
+            contents = std::fs::read_to_string(&path).unwrap();
+            if contents.lines().count() == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(contents, "earlier\nfirst\nsecond\n");
+    }
+}
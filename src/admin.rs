@@ -0,0 +1,738 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        Request, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Extension, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bonsaidb::core::schema::SerializedCollection;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    aliases,
+    analytics::QueryReport,
+    api,
+    api_keys::{self, KeySummary},
+    blocklist,
+    config::AdminConfig,
+    curation,
+    dump::{self, Importer},
+    experiments::Report,
+    maintenance::{CompactionReport, Compactor},
+    name_collisions::{self, Collision},
+    schema::{
+        AliasSource, BlockedCrate, Crate, CrateAlias, CrateSuccessor, Curation, CurationAction,
+        CurationEvent, ImportState, PublishWave, Webhook, CRATES_IO,
+    },
+    webserver::{internal_error, AppState},
+};
+
+/// Routes for managing delve-rs. Every route requires the credentials
+/// configured in `config`, and the routes are disabled entirely when none are
+/// configured.
+pub(super) fn router(
+    config: &AdminConfig,
+    importer: Importer,
+    compactor: Compactor,
+) -> axum::Router<AppState> {
+    let credentials = Credentials {
+        token: config.token(),
+        basic: config.username.clone().zip(config.password.clone()),
+    };
+    axum::Router::new()
+        .route("/stats", get(stats))
+        .route("/reindex", post(reindex))
+        .route("/cache/refresh", post(refresh_cache))
+        .route("/import", post(start_import))
+        .route("/compact", post(start_compaction))
+        .route("/aliases", get(list_aliases))
+        .route("/aliases/:name", put(set_alias).delete(delete_alias))
+        .route("/successors", get(list_successors))
+        .route(
+            "/successors/:name",
+            put(set_successors).delete(delete_successors),
+        )
+        .route("/blocklist", get(list_blocked))
+        .route("/blocklist/:name", put(block_crate).delete(unblock_crate))
+        .route("/curations", get(list_curations).post(create_curation))
+        .route("/curations/history", get(curation_history))
+        .route("/curations/:id", delete(delete_curation))
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/:id", delete(delete_webhook))
+        .route("/experiments/:name", get(experiment_report))
+        .route("/queries", get(query_report))
+        .route("/name-collisions", get(name_collision_report))
+        .route("/publish-waves", get(list_publish_waves))
+        .route("/api-keys", get(list_api_keys))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(credentials),
+            require_credentials,
+        ))
+        .layer(Extension(importer))
+        .layer(Extension(compactor))
+}
+
+/// The credentials accepted by the admin API.
+#[derive(Debug)]
+struct Credentials {
+    token: Option<String>,
+    /// The username and password accepted by HTTP basic authentication.
+    basic: Option<(String, String)>,
+}
+
+/// The administrator a request was authorized for, which is recorded with
+/// the changes they make: the username they signed in with, or `token` for
+/// the bearer token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Administrator(String);
+
+impl Credentials {
+    /// Returns the administrator `authorization` identifies, or None if it
+    /// isn't valid.
+    fn authorize(&self, authorization: &str) -> Option<Administrator> {
+        if let (Some(token), Some(provided)) = (&self.token, authorization.strip_prefix("Bearer "))
+        {
+            return constant_time_eq(provided, token).then(|| Administrator(String::from("token")));
+        }
+
+        if let (Some((username, password)), Some(encoded)) =
+            (&self.basic, authorization.strip_prefix("Basic "))
+        {
+            return STANDARD
+                .decode(encoded.trim())
+                .ok()
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .map_or(false, |decoded| {
+                    decoded.split_once(':').map_or(
+                        false,
+                        |(provided_username, provided_password)| {
+                            // Both are always compared, so the time taken doesn't
+                            // reveal whether the username was right.
+                            constant_time_eq(provided_username, username)
+                                & constant_time_eq(provided_password, password)
+                        },
+                    )
+                })
+                .then(|| Administrator(username.clone()));
+        }
+
+        None
+    }
+}
+
+/// Returns whether `provided` equals `expected` in a time that doesn't depend
+/// on where they differ or on `expected`'s length. Their MACs are compared
+/// rather than the values themselves, and `verify_slice` compares in constant
+/// time.
+fn constant_time_eq(provided: &str, expected: &str) -> bool {
+    let mac = |value: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"delve-rs admin credentials")
+            .expect("HMAC accepts keys of any size");
+        mac.update(value.as_bytes());
+        mac
+    };
+    mac(provided)
+        .verify_slice(&mac(expected).finalize().into_bytes())
+        .is_ok()
+}
+
+async fn require_credentials<B>(
+    State(credentials): State<Arc<Credentials>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if credentials.token.is_none() && credentials.basic.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let administrator = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| credentials.authorize(value));
+    if let Some(administrator) = administrator {
+        request.extensions_mut().insert(administrator);
+        next.run(request).await
+    } else if credentials.basic.is_some() {
+        // Prompts browsers for the username and password.
+        (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, "Basic realm=\"delve-rs admin\"")],
+        )
+            .into_response()
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Stats {
+    crates: usize,
+    likely_placeholders: usize,
+    deprecated: usize,
+    crates_by_registry: BTreeMap<String, usize>,
+    /// The number of crates in the search index.
+    indexed: u64,
+    last_dump_imported: Option<String>,
+    /// The dump most recently imported from each alternate registry.
+    registries_imported: HashMap<String, String>,
+    import_running: bool,
+    compaction_running: bool,
+    /// The last compaction since the server started.
+    last_compaction: Option<CompactionReport>,
+}
+
+async fn stats(
+    State((db, cache, index)): State<AppState>,
+    Extension(importer): Extension<Importer>,
+    Extension(compactor): Extension<Compactor>,
+) -> Result<Json<Stats>, StatusCode> {
+    let crates = cache.crates().map_err(internal_error)?;
+    let mut crates_by_registry = BTreeMap::<String, usize>::new();
+    let mut likely_placeholders = 0;
+    let mut deprecated = 0;
+    for krate in crates.iter() {
+        *crates_by_registry
+            .entry(krate.registry().to_string())
+            .or_default() += 1;
+        likely_placeholders += usize::from(krate.likely_placeholder());
+        deprecated += usize::from(krate.deprecation().is_some());
+    }
+    let state = ImportState::get(&(), &db)
+        .map_err(internal_error)?
+        .map(|state| state.contents)
+        .unwrap_or_default();
+
+    Ok(Json(Stats {
+        crates: crates.len(),
+        likely_placeholders,
+        deprecated,
+        crates_by_registry,
+        indexed: index.num_docs(),
+        last_dump_imported: state.last_dump_imported,
+        registries_imported: state.registries,
+        import_running: importer.is_running(),
+        compaction_running: compactor.is_running(),
+        last_compaction: compactor.last_report(),
+    }))
+}
+
+#[derive(Serialize, Debug)]
+struct Reindexed {
+    indexed: usize,
+}
+
+/// Rebuilds the search index from the database. Searches use the previous
+/// index until the rebuild finishes.
+async fn reindex(
+    State((db, _, index)): State<AppState>,
+    Extension(importer): Extension<Importer>,
+) -> Result<Json<Reindexed>, StatusCode> {
+    // The import's changes would be written to the index being replaced.
+    if importer.is_running() {
+        return Err(StatusCode::CONFLICT);
+    }
+    let indexed = tokio::task::spawn_blocking(move || index.rebuild(&db))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+    Ok(Json(Reindexed { indexed }))
+}
+
+async fn refresh_cache(State((_, cache, _)): State<AppState>) -> Result<StatusCode, StatusCode> {
+    cache.refresh().map_err(internal_error)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Starts an import in the background, unless one is already running.
+async fn start_import(Extension(importer): Extension<Importer>) -> StatusCode {
+    if importer.start() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::CONFLICT
+    }
+}
+
+/// Starts compacting the database in the background, unless a compaction is
+/// already running.
+async fn start_compaction(Extension(compactor): Extension<Compactor>) -> StatusCode {
+    if compactor.start() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::CONFLICT
+    }
+}
+
+async fn list_aliases(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<String, CrateAlias>>, StatusCode> {
+    Ok(Json(
+        CrateAlias::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct AliasRequest {
+    replacements: Vec<String>,
+    note: String,
+}
+
+async fn set_alias(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<AliasRequest>,
+) -> Result<StatusCode, StatusCode> {
+    CrateAlias {
+        replacements: request.replacements,
+        note: request.note,
+        source: AliasSource::Manual,
+    }
+    .overwrite_into(&Crate::normalized_name(&name), &db)
+    .map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_alias(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let alias = CrateAlias::get(&Crate::normalized_name(&name), &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    alias.delete(&db).map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_successors(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<String, CrateSuccessor>>, StatusCode> {
+    Ok(Json(
+        CrateSuccessor::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct SuccessorsRequest {
+    successors: Vec<String>,
+}
+
+/// Links a crate to its successors, replacing any detected forks. Curated
+/// successors are suggested whether or not the crate appears abandoned.
+///
+/// Responds with 404 if the crate doesn't exist, and 422 unless every
+/// successor is another crate that exists.
+async fn set_successors(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<SuccessorsRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let Some(id) = api::find_crate(&cache, &name).map_err(internal_error)?
+        else { return Err(StatusCode::NOT_FOUND) };
+    if request.successors.is_empty() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    for successor in &request.successors {
+        let known = aliases::crate_name_token(successor) == Some(successor.as_str())
+            && api::find_crate(&cache, successor)
+                .map_err(internal_error)?
+                .map_or(false, |successor| successor != id);
+        if !known {
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+    CrateSuccessor {
+        successors: request.successors,
+        source: AliasSource::Manual,
+    }
+    .overwrite_into(&Crate::normalized_name(&name), &db)
+    .map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_successors(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let successors = CrateSuccessor::get(&Crate::normalized_name(&name), &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    successors.delete(&db).map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_blocked(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<String, BlockedCrate>>, StatusCode> {
+    Ok(Json(
+        BlockedCrate::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct BlockRequest {
+    reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlockedRegistry {
+    /// The registry of the crate being blocked or unblocked, which is
+    /// crates.io by default.
+    registry: Option<String>,
+}
+
+impl BlockedRegistry {
+    fn key(&self, name: &str) -> String {
+        blocklist::key(self.registry.as_deref().unwrap_or(CRATES_IO), name)
+    }
+}
+
+/// Blocks a crate, replacing any block by an advisory so that withdrawing the
+/// advisory doesn't unblock the crate.
+async fn block_crate(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(registry): Query<BlockedRegistry>,
+    Json(request): Json<BlockRequest>,
+) -> Result<StatusCode, StatusCode> {
+    BlockedCrate {
+        reason: request.reason,
+        advisory: None,
+        blocked_at: dump::timestamp(time::OffsetDateTime::now_utc()),
+    }
+    .overwrite_into(&registry.key(&name), &db)
+    .map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unblock_crate(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(registry): Query<BlockedRegistry>,
+) -> Result<StatusCode, StatusCode> {
+    let blocked = BlockedCrate::get(&registry.key(&name), &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    blocked.delete(&db).map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_curations(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<u64, Curation>>, StatusCode> {
+    Ok(Json(
+        Curation::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct CurationRequest {
+    query: String,
+    #[serde(rename = "crate")]
+    crate_name: String,
+    action: CurationAction,
+    /// Why the curation is being made, which is kept in the audit trail.
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CreatedCuration {
+    id: u64,
+}
+
+async fn create_curation(
+    State((db, cache, _)): State<AppState>,
+    Extension(administrator): Extension<Administrator>,
+    Json(request): Json<CurationRequest>,
+) -> Result<(StatusCode, Json<CreatedCuration>), StatusCode> {
+    let known_crate = api::find_crate(&cache, &request.crate_name)
+        .map_err(internal_error)?
+        .is_some();
+    if request.query.trim().is_empty() || !known_crate {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let curation = Curation {
+        query: request.query,
+        crate_name: request.crate_name,
+        action: request.action,
+    };
+    let id =
+        curation::add(&db, curation, request.reason, administrator.0).map_err(internal_error)?;
+    cache.refresh().map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(CreatedCuration { id })))
+}
+
+#[derive(Deserialize, Debug)]
+struct DeleteCurationRequest {
+    #[serde(default)]
+    reason: String,
+}
+
+async fn delete_curation(
+    State((db, cache, _)): State<AppState>,
+    Extension(administrator): Extension<Administrator>,
+    Path(id): Path<u64>,
+    Query(request): Query<DeleteCurationRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if !curation::remove(&db, id, request.reason, administrator.0).map_err(internal_error)? {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    cache.refresh().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists every change made to the curations, newest first.
+async fn curation_history(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<Vec<CurationEvent>>, StatusCode> {
+    let mut events = CurationEvent::all(&db)
+        .query()
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|d| d.contents)
+        .collect::<Vec<_>>();
+    events.reverse();
+    Ok(Json(events))
+}
+
+/// A webhook as listed by the admin API, which omits its secret.
+#[derive(Serialize, Debug)]
+struct WebhookSummary {
+    url: String,
+    crates: Vec<String>,
+    keywords: Vec<String>,
+}
+
+async fn list_webhooks(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<u64, WebhookSummary>>, StatusCode> {
+    Ok(Json(
+        Webhook::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| {
+                (
+                    d.header.id,
+                    WebhookSummary {
+                        url: d.contents.url,
+                        crates: d.contents.crates,
+                        keywords: d.contents.keywords,
+                    },
+                )
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, Debug)]
+struct CreatedWebhook {
+    id: u64,
+}
+
+async fn create_webhook(
+    State((db, _, _)): State<AppState>,
+    Json(webhook): Json<Webhook>,
+) -> Result<(StatusCode, Json<CreatedWebhook>), StatusCode> {
+    if reqwest::Url::parse(&webhook.url).is_err() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let webhook = webhook.push_into(&db).map_err(internal_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedWebhook {
+            id: webhook.header.id,
+        }),
+    ))
+}
+
+async fn delete_webhook(
+    State((db, _, _)): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, StatusCode> {
+    let webhook = Webhook::get(&id, &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    webhook.delete(&db).map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn experiment_report(
+    State((db, _, _)): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Report>, StatusCode> {
+    Ok(Json(Report::load(&db, &name).map_err(internal_error)?))
+}
+
+#[derive(Deserialize, Debug)]
+struct QueryReportRequest {
+    limit: Option<u32>,
+}
+
+async fn query_report(
+    State((db, _, _)): State<AppState>,
+    Query(request): Query<QueryReportRequest>,
+) -> Result<Json<QueryReport>, StatusCode> {
+    let limit = request.limit.unwrap_or(50).min(500);
+    Ok(Json(QueryReport::load(&db, limit).map_err(internal_error)?))
+}
+
+#[derive(Deserialize, Debug)]
+struct NameCollisionRequest {
+    days: Option<u32>,
+}
+
+impl NameCollisionRequest {
+    /// Returns the first day of the requested period ending `today`, or an
+    /// error if it would start before the earliest representable date.
+    fn since(&self, today: time::Date) -> Result<time::Date, StatusCode> {
+        let days = self.days.unwrap_or(7);
+        today
+            .checked_sub(time::Duration::days(i64::from(days)))
+            .ok_or(StatusCode::BAD_REQUEST)
+    }
+}
+
+/// Lists the crates sharing a normalized name with another crate, where one of
+/// them was first published in the last week, or in the last `days`.
+async fn name_collision_report(
+    State((_, cache, _)): State<AppState>,
+    Query(request): Query<NameCollisionRequest>,
+) -> Result<Json<Vec<Collision>>, StatusCode> {
+    let since = request.since(time::OffsetDateTime::now_utc().date())?;
+    Ok(Json(
+        name_collisions::report(&cache, since).map_err(internal_error)?,
+    ))
+}
+
+/// Lists the owners flagged for publishing waves of near-identical crates,
+/// keyed by their lowercase login.
+async fn list_publish_waves(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<BTreeMap<String, PublishWave>>, StatusCode> {
+    Ok(Json(
+        PublishWave::all(&db)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect(),
+    ))
+}
+
+/// Lists the issued API keys and how much each has been used.
+async fn list_api_keys(
+    State((db, _, _)): State<AppState>,
+) -> Result<Json<Vec<KeySummary>>, StatusCode> {
+    Ok(Json(api_keys::list(&db).map_err(internal_error)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            token: Some(String::from("secret-token")),
+            basic: Some((String::from("admin"), String::from("hunter2"))),
+        }
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!("Basic {}", STANDARD.encode(credentials))
+    }
+
+    #[test]
+    fn bearer_tokens() {
+        let credentials = credentials();
+        assert_eq!(
+            credentials.authorize("Bearer secret-token"),
+            Some(Administrator(String::from("token")))
+        );
+        assert!(credentials.authorize("Bearer secret-toke").is_none());
+        assert!(credentials.authorize("Bearer secret-token2").is_none());
+        assert!(credentials.authorize("Bearer ").is_none());
+    }
+
+    #[test]
+    fn basic_authentication() {
+        let credentials = credentials();
+        assert_eq!(
+            credentials.authorize(&basic("admin:hunter2")),
+            Some(Administrator(String::from("admin")))
+        );
+        assert!(credentials.authorize(&basic("admin:hunter3")).is_none());
+        assert!(credentials.authorize(&basic("root:hunter2")).is_none());
+        assert!(credentials.authorize(&basic("admin")).is_none());
+        assert!(credentials.authorize("Basic not base64!").is_none());
+    }
+
+    #[test]
+    fn missing_credentials() {
+        let credentials = Credentials {
+            token: None,
+            basic: None,
+        };
+        assert!(credentials.authorize("Bearer ").is_none());
+        assert!(credentials.authorize(&basic(":")).is_none());
+    }
+
+    #[test]
+    fn name_collision_periods() {
+        let date = |year, month, day| time::Date::from_calendar_date(year, month, day).unwrap();
+        let today = date(2023, time::Month::March, 20);
+        let since = |days| NameCollisionRequest { days }.since(today);
+        assert_eq!(since(None), Ok(date(2023, time::Month::March, 13)));
+        assert_eq!(since(Some(0)), Ok(today));
+        assert_eq!(since(Some(365)), Ok(date(2022, time::Month::March, 20)));
+        assert_eq!(since(Some(u32::MAX)), Err(StatusCode::BAD_REQUEST));
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use bonsaidb::{
+    core::transaction::{Operation, Transaction},
+    local::Database,
+};
+use serde::Deserialize;
+
+use crate::{archive, blocklist, schema};
+
+const ADVISORY_DB_ARCHIVE: &str =
+    "https://github.com/rustsec/advisory-db/archive/refs/heads/main.tar.gz";
+
+/// Downloads the RustSec advisory database into `data_dir` and imports every
+/// advisory into the `Advisory` collection.
+pub(super) async fn import(database: &Database, data_dir: &Path) -> anyhow::Result<()> {
+    println!("Downloading advisory-db.");
+    let response = reqwest::get(ADVISORY_DB_ARCHIVE)
+        .await?
+        .error_for_status()?;
+    archive::download_and_extract(response, &data_dir.join("advisory-db.tar.gz"), data_dir).await?;
+
+    let database = database.clone();
+    let advisory_db = data_dir.join("advisory-db-main");
+    tokio::task::spawn_blocking(move || import_advisories(&advisory_db, &database)).await?
+}
+
+fn import_advisories(advisory_db: &Path, db: &Database) -> anyhow::Result<()> {
+    println!("Parsing advisories.");
+    let mut tx = Transaction::new();
+    for crate_folder in std::fs::read_dir(advisory_db.join("crates"))? {
+        let crate_folder = crate_folder?;
+        if !crate_folder.file_type()?.is_dir() {
+            continue;
+        }
+
+        for advisory in std::fs::read_dir(crate_folder.path())? {
+            let path = advisory?.path();
+            if path.extension().map_or(true, |ext| ext != "md") {
+                continue;
+            }
+
+            let contents = std::fs::read_to_string(&path)?;
+            let (id, advisory) = parse_advisory(&contents)
+                .map_err(|err| anyhow::anyhow!("error parsing {}: {err}", path.display()))?;
+            tx.operations
+                .push(Operation::overwrite_serialized::<schema::Advisory, _>(
+                    &id, &advisory,
+                )?);
+        }
+    }
+
+    println!("Committing {} advisories", tx.operations.len());
+    tx.apply(db)?;
+
+    blocklist::update(db)
+}
+
+/// Parses an advisory-db markdown file, which begins with a fenced TOML block
+/// followed by the advisory's markdown description.
+fn parse_advisory(contents: &str) -> anyhow::Result<(String, schema::Advisory)> {
+    let contents = contents
+        .strip_prefix("```toml")
+        .ok_or_else(|| anyhow::anyhow!("missing toml front matter"))?;
+    let (front_matter, description) = contents
+        .split_once("```")
+        .ok_or_else(|| anyhow::anyhow!("unterminated toml front matter"))?;
+    let front_matter: FrontMatter = toml::from_str(front_matter)?;
+    let title = description
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    Ok((
+        front_matter.advisory.id,
+        schema::Advisory {
+            crate_name: front_matter.advisory.package,
+            title,
+            date: front_matter.advisory.date,
+            url: front_matter.advisory.url,
+            informational: front_matter.advisory.informational,
+            withdrawn: front_matter.advisory.withdrawn,
+            categories: front_matter.advisory.categories,
+            patched: front_matter.versions.patched,
+            unaffected: front_matter.versions.unaffected,
+        },
+    ))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct FrontMatter {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    versions: AffectedVersions,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    date: String,
+    url: Option<String>,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    categories: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+struct AffectedVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
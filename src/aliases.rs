@@ -0,0 +1,123 @@
+use crate::schema::{AliasSource, Crate, CrateAlias};
+
+/// Well-known deprecations that are seeded as manual aliases during import if
+/// no alias exists for the crate yet. Each is only seeded once, so deleting
+/// one through the admin API removes it for good.
+const KNOWN_ALIASES: &[(&str, &[&str], &str)] = &[
+    (
+        "failure",
+        &["anyhow", "thiserror"],
+        "failure is deprecated. anyhow and thiserror are its recommended successors.",
+    ),
+    (
+        "error-chain",
+        &["anyhow", "thiserror"],
+        "error-chain is no longer maintained.",
+    ),
+    (
+        "tempdir",
+        &["tempfile"],
+        "tempdir has been merged into tempfile.",
+    ),
+    (
+        "rustc-serialize",
+        &["serde"],
+        "rustc-serialize is deprecated in favor of serde.",
+    ),
+    (
+        "structopt",
+        &["clap"],
+        "structopt is in maintenance mode. Its derive API is now part of clap.",
+    ),
+];
+
+/// Returns the built-in aliases, keyed by normalized crate name.
+pub fn known_aliases() -> impl Iterator<Item = (String, CrateAlias)> {
+    KNOWN_ALIASES.iter().map(|(name, replacements, note)| {
+        (
+            Crate::normalized_name(name),
+            CrateAlias {
+                replacements: replacements.iter().map(|r| r.to_string()).collect(),
+                note: note.to_string(),
+                source: AliasSource::Manual,
+            },
+        )
+    })
+}
+
+/// Phrases in a description that introduce the name of a replacement crate.
+const REPLACEMENT_PHRASES: &[&str] = &[
+    "renamed to",
+    "moved to",
+    "superseded by",
+    "replaced by",
+    "in favor of",
+    "in favour of",
+    "use",
+];
+
+/// Attempts to detect whether `krate` has been deprecated or renamed based on
+/// its description.
+pub fn detect(krate: &Crate) -> Option<CrateAlias> {
+    let description = krate.description.trim();
+    let lowercase = description.to_ascii_lowercase();
+    if ![
+        "deprecated",
+        "renamed",
+        "superseded",
+        "no longer maintained",
+    ]
+    .iter()
+    .any(|marker| lowercase.contains(marker))
+    {
+        return None;
+    }
+
+    let words = lowercase.split_ascii_whitespace().collect::<Vec<_>>();
+    let mut replacements = Vec::new();
+    for phrase in REPLACEMENT_PHRASES {
+        let phrase = phrase.split(' ').collect::<Vec<_>>();
+        for (index, window) in words.windows(phrase.len() + 1).enumerate() {
+            if window[..phrase.len()] != phrase[..] {
+                continue;
+            }
+
+            // "use X instead" is only a replacement if followed by "instead".
+            if phrase == ["use"]
+                && words
+                    .get(index + 2)
+                    .map_or(true, |w| !w.starts_with("instead"))
+            {
+                continue;
+            }
+
+            if let Some(name) = crate_name_token(window[phrase.len()]) {
+                if Crate::normalized_name(name) != Crate::normalized_name(&krate.name)
+                    && !replacements.iter().any(|r| r == name)
+                {
+                    replacements.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    Some(CrateAlias {
+        replacements,
+        note: description.to_string(),
+        source: AliasSource::Heuristic,
+    })
+}
+
+/// Strips surrounding punctuation and markdown from `token`, returning it if
+/// what remains looks like a crate name.
+pub(crate) fn crate_name_token(token: &str) -> Option<&str> {
+    let token =
+        token.trim_matches(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'));
+    let valid = !token.is_empty()
+        && token.len() <= 64
+        && token.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && token
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+    valid.then_some(token)
+}
@@ -0,0 +1,193 @@
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    dump, experiments, replication,
+    schema::{QueriesByCount, QueryLog, ResultClick, ZeroResultQueriesByCount},
+};
+
+/// The number of bytes of a query's digest kept by [`query_hash`].
+const QUERY_HASH_BYTES: usize = 8;
+
+/// The maximum number of characters of a query stored in the [`QueryLog`].
+const MAX_LOGGED_QUERY: usize = 100;
+
+/// The number of clicks deleted in each transaction by [`prune`].
+const PRUNE_BATCH: u32 = 10_000;
+
+/// Returns `query` in lowercase with its whitespace collapsed.
+pub(crate) fn normalize_query(query: &str) -> String {
+    query
+        .split_ascii_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns a short hash identifying `query`, which keys both the clicks on a
+/// query's results and the query's [`QueryLog`] entry. It isn't salted, so it
+/// doesn't hide the query from anyone who can read the log. Queries differing
+/// only in case or whitespace have the same hash.
+pub(crate) fn query_hash(query: &str) -> String {
+    let digest = Sha256::digest(normalize_query(query).as_bytes());
+
+    let mut hash = String::with_capacity(QUERY_HASH_BYTES * 2);
+    for byte in &digest[..QUERY_HASH_BYTES] {
+        hash.push_str(&format!("{byte:02x}"));
+    }
+    hash
+}
+
+/// Records that the search result for `crate_id` at `position`, starting from
+/// 1, was clicked after searching for `query`.
+pub(crate) fn record_click(db: &Database, crate_id: u64, position: u32, query: &str) {
+    if replication::is_read_only() {
+        return;
+    }
+    let click = ResultClick {
+        crate_id,
+        position,
+        query_hash: query_hash(query),
+        at: dump::timestamp(OffsetDateTime::now_utc()),
+    };
+    // Failing to record a click shouldn't prevent the redirect.
+    if let Err(err) = click.push_into(db) {
+        tracing::warn!("error recording result click: {err}");
+    }
+}
+
+/// Counts a search for `query` in the [`QueryLog`], noting whether it
+/// returned any results.
+pub(crate) fn record_query(db: &Database, query: &str, results: usize) {
+    if replication::is_read_only() {
+        return;
+    }
+    if let Err(err) = try_record_query(db, query, results) {
+        tracing::warn!("error recording query: {err}");
+    }
+}
+
+fn try_record_query(db: &Database, query: &str, results: usize) -> anyhow::Result<()> {
+    let normalized = normalize_query(query);
+    if normalized.is_empty() {
+        return Ok(());
+    }
+    let hash = query_hash(&normalized);
+    let zero_results = u64::from(results == 0);
+    let now = dump::timestamp(OffsetDateTime::now_utc());
+
+    if let Some(mut entry) = QueryLog::get(&hash, db)? {
+        entry.modify(db, |entry| {
+            entry.contents.count += 1;
+            entry.contents.zero_results += zero_results;
+            entry.contents.last_searched = now.clone();
+        })?;
+    } else {
+        QueryLog {
+            query: normalized
+                .chars()
+                .filter(|ch| !ch.is_control())
+                .take(MAX_LOGGED_QUERY)
+                .collect(),
+            count: 1,
+            zero_results,
+            last_searched: now,
+        }
+        .insert_into(&hash, db)?;
+    }
+    Ok(())
+}
+
+/// Deletes the clicks and experiment events recorded more than
+/// `retention_days` days ago, and the queries that haven't been searched for
+/// since then, returning how many were deleted.
+pub(crate) fn prune(db: &Database, retention_days: u64) -> anyhow::Result<usize> {
+    let retention = Duration::days(i64::try_from(retention_days).unwrap_or(i64::MAX));
+    let cutoff = match OffsetDateTime::now_utc().checked_sub(retention) {
+        // Timestamps sort chronologically as strings.
+        Some(cutoff) => dump::timestamp(cutoff),
+        None => return Ok(0),
+    };
+    Ok(prune_clicks(db, &cutoff)? + prune_queries(db, &cutoff)? + experiments::prune(db, &cutoff)?)
+}
+
+/// Deletes the clicks recorded before `cutoff`. Clicks are pushed with
+/// increasing ids, so the oldest are listed first.
+fn prune_clicks(db: &Database, cutoff: &str) -> anyhow::Result<usize> {
+    let mut deleted = 0;
+    loop {
+        let clicks = ResultClick::all(db).limit(PRUNE_BATCH).query()?;
+        let mut tx = Transaction::new();
+        for click in clicks
+            .into_iter()
+            .take_while(|click| click.contents.at.as_str() < cutoff)
+        {
+            tx.operations.push(Operation::delete(
+                ResultClick::collection_name(),
+                Header::try_from(click.header)?,
+            ));
+        }
+        if tx.operations.is_empty() {
+            return Ok(deleted);
+        }
+        deleted += tx.operations.len();
+        tx.apply(db)?;
+    }
+}
+
+/// Deletes the queries last searched for before `cutoff`.
+fn prune_queries(db: &Database, cutoff: &str) -> anyhow::Result<usize> {
+    let mut tx = Transaction::new();
+    for entry in QueryLog::all(db).query()? {
+        if entry.contents.last_searched.as_str() < cutoff {
+            tx.operations.push(Operation::delete(
+                QueryLog::collection_name(),
+                Header::try_from(entry.header)?,
+            ));
+        }
+    }
+    let deleted = tx.operations.len();
+    if deleted > 0 {
+        tx.apply(db)?;
+    }
+    Ok(deleted)
+}
+
+/// The most frequent queries, and the most frequent queries that returned no
+/// results.
+#[derive(Serialize, Debug)]
+pub(crate) struct QueryReport {
+    pub top: Vec<QueryLog>,
+    pub zero_results: Vec<QueryLog>,
+}
+
+impl QueryReport {
+    /// Loads up to `limit` queries for each list.
+    pub fn load(db: &Database, limit: u32) -> anyhow::Result<Self> {
+        Ok(Self {
+            top: QueriesByCount::entries(db)
+                .descending()
+                .limit(limit)
+                .query()?
+                .into_iter()
+                .map(|mapping| mapping.value)
+                .collect(),
+            zero_results: ZeroResultQueriesByCount::entries(db)
+                .descending()
+                .limit(limit)
+                .query()?
+                .into_iter()
+                .map(|mapping| mapping.value)
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,1198 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json,
+};
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analytics,
+    api_keys::{self, Meter},
+    blocklist,
+    cache::Cache,
+    categories,
+    dependency_graph::{DependencyGraph, MAX_GRAPH_DEPTH},
+    embeddings::Embedder,
+    filters::{Filters, PlaceholderMode, QueryTimeout},
+    i18n,
+    name_collisions::{self, CollidingCrate},
+    not_found::{self, ApiError},
+    ownership::{self, OwnershipEntry},
+    pagination::{Cursor, Page, PageQuery},
+    ranking::{self, Recency},
+    recommendations, review_report, reviews, rollup,
+    schema::{
+        self, AdvisoriesByCrate, CalendarDate, CratesByCategory, DependenciesByVersion,
+        DependencyKind, DependentsByCrate, DownloadsByCrateAndDate, UnsafeIndicator,
+        VersionsByCrate, CRATES_IO,
+    },
+    snippets,
+    version_info::VersionInfo,
+    version_links::{self, VersionLink},
+    watchlists,
+    webserver::{internal_error, AppState},
+    QueryResults, MAX_QUERY_LEN,
+};
+
+pub(super) fn router(recency: Recency, meter: Meter) -> axum::Router<AppState> {
+    // The routes the site's pages call from scripts, which stay anonymous and
+    // unlimited like the pages themselves.
+    let site = axum::Router::new()
+        .route("/crates/:name/depgraph", get(dependency_graph))
+        .route("/keywords/suggest", get(suggest_keywords))
+        .route("/categories/suggest", get(suggest_categories));
+
+    axum::Router::new()
+        .route("/search", get(search))
+        .route("/search/pages", get(search_pages))
+        .route("/quick", get(quick_search))
+        .route("/crates/:name", get(crate_info))
+        .route("/crates/:name/dependents", get(dependents))
+        .route("/crates/:name/versions/resolve", get(resolve_version))
+        .route("/audit", post(audit))
+        .route("/recommendations", post(recommendations::recommend))
+        .route("/report", post(review_report::generate))
+        .route("/categories/:slug/crates", get(category_crates))
+        .nest("/watchlists", watchlists::router())
+        .route_layer(middleware::from_fn_with_state(meter, api_keys::meter))
+        .merge(site)
+        .layer(Extension(recency))
+        .layer(middleware::from_fn(json_errors))
+}
+
+/// Gives the API's error responses that have no body, such as the 404 of an
+/// unknown route, a JSON [`ApiError`] body.
+async fn json_errors<B>(request: Request<B>, next: Next<B>) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error())
+        || response.headers().contains_key(CONTENT_TYPE)
+    {
+        return response;
+    }
+    let body = ApiError {
+        error: status
+            .canonical_reason()
+            .unwrap_or("error")
+            .to_ascii_lowercase(),
+        suggestions: None,
+    };
+    let (parts, _) = response.into_parts();
+    let mut json = (status, Json(body)).into_response();
+    // Keeps headers such as `Retry-After`.
+    json.headers_mut().extend(parts.headers);
+    json
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchQuery {
+    q: String,
+    #[serde(default)]
+    hide_placeholders: bool,
+    ranker: Option<String>,
+}
+
+/// The key of a search result page. Results are ordered by score rather than
+/// by a stored key, so a page continues after the position of the last crate
+/// returned.
+#[derive(Serialize, Deserialize, Debug)]
+struct SearchPosition {
+    /// The id of the last crate returned.
+    id: u64,
+    /// The number of results returned so far.
+    offset: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct SearchResult {
+    name: String,
+    description: String,
+    downloads: u64,
+    recent_downloads: u64,
+    confidence: f32,
+    popularity: f32,
+    likely_placeholder: bool,
+    /// True if the crate appears to be unmaintained.
+    unmaintained: bool,
+    /// True if the crate appears to support WebAssembly.
+    wasm: bool,
+    /// True if the crate appears to be a procedural macro.
+    proc_macro: bool,
+    /// True if the crate appears to have a build script.
+    build_script: bool,
+    /// The number of unsafe items compiled by the crate's scanned version,
+    /// or None if it hasn't been scanned.
+    unsafe_items: Option<u64>,
+    /// True if the crate's scanned version forbids unsafe code.
+    forbids_unsafe: bool,
+    /// The number of community reviews of any version of the crate.
+    reviews: u32,
+    /// True if a cargo-vet audit or a positive crev review certifies a
+    /// version of the crate.
+    audited: bool,
+    registry: String,
+    /// The ISO 639-1 code of the language the crate is documented in.
+    language: Option<String>,
+    /// The number of crates the latest version requires, directly or
+    /// indirectly.
+    transitive_dependencies: Option<u32>,
+    /// The number of steps from the crate to its furthest dependency.
+    dependency_depth: Option<u32>,
+    /// The date the crate's first version was published.
+    first_published: Option<String>,
+    /// An HTML excerpt of the description or readme with the matching terms
+    /// wrapped in `<b>` tags.
+    snippet: Option<String>,
+    keywords: Vec<String>,
+    /// Names of lower-ranked crates that appear to be forks or copies of this
+    /// crate.
+    similar: Vec<String>,
+    /// Names of the crates suggested in place of this crate, such as its
+    /// maintained forks.
+    successors: Vec<String>,
+}
+
+/// Returns the first `limit` results of a search as an array, which is how
+/// searches responded before they were paginated.
+async fn search(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+    Query(limit): Query<LimitQuery>,
+    Extension(recency): Extension<Recency>,
+    Extension(embedder): Extension<Embedder>,
+    Extension(timeout): Extension<QueryTimeout>,
+) -> Result<Json<Vec<SearchResult>>, StatusCode> {
+    let paging = PageQuery {
+        cursor: None,
+        limit: limit.limit,
+    };
+    let page = search_page(state, query, paging, recency, embedder, timeout).await?;
+    Ok(Json(page.page.items))
+}
+
+#[derive(Deserialize, Debug)]
+struct LimitQuery {
+    limit: Option<usize>,
+}
+
+/// Returns a page of a search's results.
+async fn search_pages(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+    Query(paging): Query<PageQuery>,
+    Extension(recency): Extension<Recency>,
+    Extension(embedder): Extension<Embedder>,
+    Extension(timeout): Extension<QueryTimeout>,
+) -> Result<Json<SearchPage>, StatusCode> {
+    search_page(state, query, paging, recency, embedder, timeout)
+        .await
+        .map(Json)
+}
+
+async fn search_page(
+    (db, cache, search_index): AppState,
+    query: SearchQuery,
+    paging: PageQuery,
+    recency: Recency,
+    embedder: Embedder,
+    timeout: QueryTimeout,
+) -> Result<SearchPage, StatusCode> {
+    if query.q.len() > MAX_QUERY_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let cursor = paging.cursor::<SearchPosition>()?;
+    let (terms, mut filters) = Filters::parse(&query.q);
+    filters.semantic = embedder.matches(&cache, &terms).await;
+    filters.recency = recency;
+    if query.hide_placeholders {
+        filters.placeholders = PlaceholderMode::Hide;
+    }
+    if let Some(ranker) = &query.ranker {
+        filters.ranker = Some(ranking::by_name(ranker).ok_or(StatusCode::BAD_REQUEST)?);
+    }
+    let generation = cache.crates().map_err(internal_error)?.generation();
+    filters.deadline = timeout.deadline();
+    let QueryResults { results, partial } = crate::spawn_query(
+        terms.clone(),
+        filters,
+        db.clone(),
+        cache,
+        search_index.clone(),
+    )
+    .await
+    .map_err(internal_error)?;
+    // Only the first page is a new search.
+    if cursor.is_none() {
+        analytics::record_query(&db, &query.q, results.len());
+    }
+
+    // Within the same generation the results are identical, so the offset is
+    // exact. Otherwise the page continues after wherever the last crate moved.
+    let start = match &cursor {
+        None => 0,
+        Some(cursor) if cursor.generation == generation => cursor.last.offset,
+        Some(cursor) => results
+            .iter()
+            .position(|result| result.result.id() == cursor.last.id)
+            .map_or(cursor.last.offset, |position| position + 1),
+    };
+    let mut page = Page::new(
+        results
+            .into_iter()
+            .enumerate()
+            .skip(start)
+            .map(|(index, result)| {
+                let position = SearchPosition {
+                    id: result.result.id(),
+                    offset: index + 1,
+                };
+                (position, result)
+            }),
+        paging.limit(),
+        generation,
+        cursor.as_ref(),
+    );
+    snippets::highlight(&terms, &db, &search_index, &mut page.items).map_err(internal_error)?;
+    let page = page.map(|result| SearchResult {
+        name: result.result.name().to_string(),
+        description: result.result.description().to_string(),
+        downloads: result.result.downloads(),
+        recent_downloads: result.result.recent_downloads(),
+        confidence: result.confidence,
+        popularity: result.popularity,
+        likely_placeholder: result.result.likely_placeholder(),
+        unmaintained: result.result.unmaintained(),
+        wasm: result.result.profile().wasm,
+        proc_macro: result.result.profile().proc_macro,
+        build_script: result.result.profile().build_script,
+        unsafe_items: result.result.unsafe_usage().map(|usage| usage.count()),
+        forbids_unsafe: result.result.unsafe_usage() == Some(UnsafeIndicator::Forbidden),
+        reviews: result.result.reviews().total,
+        audited: result.result.audited(),
+        registry: result.result.registry().to_string(),
+        language: result.result.language().map(String::from),
+        transitive_dependencies: result
+            .result
+            .dependencies()
+            .map(|metrics| metrics.transitive),
+        dependency_depth: result.result.dependencies().map(|metrics| metrics.depth),
+        first_published: result.result.first_published().map(|date| date.to_string()),
+        snippet: result.snippet,
+        keywords: result
+            .result
+            .keyword_names()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        successors: result
+            .result
+            .successors()
+            .iter()
+            .map(|successor| successor.name().to_string())
+            .collect(),
+        similar: result
+            .similar
+            .into_iter()
+            .map(|similar| similar.result.name().to_string())
+            .collect(),
+    });
+    Ok(SearchPage { page, partial })
+}
+
+#[derive(Serialize, Debug)]
+struct SearchPage {
+    #[serde(flatten)]
+    page: Page<SearchResult>,
+    /// True if scoring the results took too long, in which case they're the
+    /// search index's matches in the index's order.
+    partial: bool,
+}
+
+/// The number of results returned by a quick search.
+const QUICK_RESULTS: usize = 5;
+
+/// How long scoring a quick search may take, which keeps completion popups
+/// responsive while typing.
+const QUICK_BUDGET: Duration = Duration::from_millis(50);
+
+/// How long a quick search waits for its results, including the search index
+/// lookup that [`QUICK_BUDGET`] doesn't interrupt, before responding without
+/// any.
+const QUICK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The most search index matches a quick search fetches.
+const QUICK_INDEX_LIMIT: usize = 100;
+
+#[derive(Deserialize, Debug)]
+struct QuickQuery {
+    q: String,
+}
+
+#[derive(Serialize, Debug)]
+struct QuickResults {
+    items: Vec<QuickResult>,
+    /// True if scoring the results ran out of time, in which case they're the
+    /// search index's matches in the index's order, or if the search didn't
+    /// finish in time, in which case there are none.
+    partial: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct QuickResult {
+    name: String,
+    description: String,
+    /// The latest version.
+    version: Option<String>,
+}
+
+/// Returns the top few results of a search with only the fields needed for
+/// completion, such as by editor plugins. Quick searches skip semantic
+/// matching, snippets, and query statistics, hide placeholders, stop scoring
+/// after [`QUICK_BUDGET`], and respond with no results after
+/// [`QUICK_TIMEOUT`].
+async fn quick_search(
+    State((db, cache, search_index)): State<AppState>,
+    Query(query): Query<QuickQuery>,
+    Extension(recency): Extension<Recency>,
+    Extension(timeout): Extension<QueryTimeout>,
+) -> Result<Json<QuickResults>, StatusCode> {
+    if query.q.len() > MAX_QUERY_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (terms, mut filters) = Filters::parse(&query.q);
+    filters.recency = recency;
+    filters.placeholders = PlaceholderMode::Hide;
+    filters.index_limit = Some(QUICK_INDEX_LIMIT);
+    let budget = Instant::now() + QUICK_BUDGET;
+    filters.deadline = Some(
+        timeout
+            .deadline()
+            .map_or(budget, |deadline| deadline.min(budget)),
+    );
+    let search = crate::spawn_query(terms, filters, db, cache, search_index);
+    // The search keeps running on its blocking thread after a timeout, but
+    // the completion popup isn't kept waiting for it.
+    let Ok(results) = tokio::time::timeout(QUICK_TIMEOUT, search).await else {
+        return Ok(Json(QuickResults {
+            items: Vec::new(),
+            partial: true,
+        }));
+    };
+    let QueryResults { results, partial } = results.map_err(internal_error)?;
+    let items = results
+        .into_iter()
+        .take(QUICK_RESULTS)
+        .map(|result| QuickResult {
+            name: result.result.name().to_string(),
+            description: result.result.description().to_string(),
+            version: result.result.latest_version().map(String::from),
+        })
+        .collect();
+    Ok(Json(QuickResults { items, partial }))
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct CrateInfo {
+    pub name: String,
+    pub description: String,
+    pub homepage: String,
+    pub documentation: String,
+    pub repository: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub downloads: u64,
+    pub recent_downloads: u64,
+    /// Downloads in the last 365 days.
+    pub yearly_downloads: u64,
+    /// Downloads counted from the daily download records, which only cover
+    /// the days since they were first imported.
+    pub recorded_downloads: u64,
+    pub latest_version: Option<String>,
+    /// The latest version's license expression, if it has one.
+    pub license: Option<String>,
+    /// The command that adds the crate to a project, or None if the crate is
+    /// blocked.
+    pub install: Option<String>,
+    /// Why the crate is on the blocklist, if it is.
+    pub blocked: Option<schema::BlockedCrate>,
+    /// Links to the latest version's `.crate` file, documentation, and
+    /// source, leaving out links found to be broken. Blocked crates aren't
+    /// linked to.
+    pub links: Vec<VersionLink>,
+    pub rust_version: Option<String>,
+    pub deprecation: Option<schema::CrateAlias>,
+    /// The crates suggested in place of this crate, such as its maintained
+    /// forks.
+    pub successors: Vec<String>,
+    pub likely_placeholder: bool,
+    /// True if the crate hasn't been released in over two years and either
+    /// its repository is archived or an open advisory says it's
+    /// unmaintained.
+    pub unmaintained: bool,
+    /// True if the crate appears to support WebAssembly.
+    pub wasm: bool,
+    /// The platforms the crate has code specific to.
+    pub platforms: schema::Platforms,
+    /// True if the crate appears to be a procedural macro.
+    pub proc_macro: bool,
+    /// True if the crate appears to have a build script.
+    pub build_script: bool,
+    /// The number of required crates that appear to be procedural macros.
+    pub dependency_proc_macros: u32,
+    /// The number of required crates that appear to have build scripts.
+    pub dependency_build_scripts: u32,
+    /// The unsafe code in the most recently scanned version, if any version
+    /// has been scanned.
+    pub unsafe_usage: Option<schema::UnsafeUsage>,
+    /// Community reviews of every version of the crate, favorable reviews
+    /// first.
+    pub reviews: Vec<schema::Review>,
+    /// The organizations and crev repositories with favorable reviews of the
+    /// crate.
+    pub audited_by: Vec<String>,
+    /// The ISO 639-1 code of the language the crate is documented in.
+    pub language: Option<String>,
+    /// Other crates whose names Cargo treats as the same as this crate's.
+    pub name_collisions: Vec<CollidingCrate>,
+    /// The crate whose README this crate's README appears to be copied from.
+    pub duplicate_of: Option<DuplicateOf>,
+    pub versions: Vec<schema::VersionSummary>,
+    /// The latest version's dependencies, grouped by kind.
+    pub dependencies: Vec<DependencyGroup>,
+    /// Recent downloads grouped by semver-compatible releases, newest first.
+    pub download_split: Vec<VersionGroupDownloads>,
+    pub advisories: Vec<AdvisorySummary>,
+    pub ownership_changes: Vec<OwnershipEntry>,
+    /// The crates most often depended on together with this crate.
+    pub often_used_with: Vec<OftenUsedWith>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DependencyGroup {
+    pub kind: DependencyKind,
+    pub dependencies: Vec<DependencyInfo>,
+}
+
+impl DependencyGroup {
+    /// Returns the group's heading in the current response's language.
+    pub fn title(&self) -> String {
+        i18n::t(match self.kind {
+            DependencyKind::Normal => "dependencies-normal",
+            DependencyKind::Build => "dependencies-build",
+            DependencyKind::Dev => "dependencies-dev",
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DependencyInfo {
+    pub name: String,
+    pub req: String,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    /// The `cfg` or target triple the dependency is limited to.
+    pub target: Option<String>,
+    /// The name the dependency is renamed to in the manifest.
+    pub rename: Option<String>,
+    /// The dependency's latest release.
+    pub latest: Option<String>,
+    /// True if `req` doesn't allow `latest`, leaving the dependency behind its
+    /// latest release.
+    pub stale: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct OftenUsedWith {
+    pub name: String,
+    pub description: String,
+    /// The number of crates depending on both crates.
+    pub shared: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DuplicateOf {
+    pub name: String,
+    /// The estimated fraction of the two READMEs' text that they share.
+    pub similarity: f32,
+}
+
+impl DuplicateOf {
+    pub fn percent(&self) -> String {
+        format!("{:.0}%", self.similarity * 100.)
+    }
+}
+
+/// The most crates listed in [`CrateInfo::often_used_with`].
+const OFTEN_USED_WITH: usize = 10;
+
+/// The number of days of downloads included in [`CrateInfo::download_split`].
+const DOWNLOAD_SPLIT_DAYS: u32 = 90;
+
+#[derive(Serialize, Debug)]
+pub(crate) struct VersionGroupDownloads {
+    /// The semver-compatible releases in the group, such as `1.x`, `0.3.x`, or
+    /// `0.0.4`.
+    pub versions: String,
+    pub downloads: u64,
+    /// The group's fraction of the crate's downloads.
+    pub share: f32,
+}
+
+impl VersionGroupDownloads {
+    pub fn percent(&self) -> String {
+        format!("{:.1}%", self.share * 100.)
+    }
+}
+
+impl CrateInfo {
+    /// Loads the information about the crate named `name`, returning None if
+    /// no crate is found.
+    pub fn load(db: &Database, cache: &Cache, name: &str) -> anyhow::Result<Option<Self>> {
+        let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+        let Some(cached) = cache.crates()?.get(id) else { return Ok(None) };
+        let Some(krate) = schema::Crate::get(&id, db)? else { return Ok(None) };
+        let krate = krate.contents;
+
+        let mut latest_version_id = None;
+        let mut versions = Vec::new();
+        let mut version_numbers = HashMap::new();
+        for mapping in VersionsByCrate::entries(db).with_key(&id).query()? {
+            let version_id = mapping.source.id.deserialize::<u64>()?;
+            if Some(mapping.value.version.as_str()) == cached.latest_version() {
+                latest_version_id = Some(version_id);
+            }
+            if let Ok(version) = semver::Version::parse(&mapping.value.version) {
+                version_numbers.insert(version_id, version);
+            }
+            versions.push(mapping.value);
+        }
+        // Sort newest first, leaving unparseable versions at the end.
+        versions.sort_by_cached_key(|v| std::cmp::Reverse(semver::Version::parse(&v.version).ok()));
+        let (dependencies, license) = match latest_version_id {
+            Some(version_id) => (
+                dependency_groups(db, cache, version_id)?,
+                schema::Version::get(&version_id, db)?
+                    .map(|version| version.contents.license)
+                    .filter(|license| !license.is_empty()),
+            ),
+            None => (Vec::new(), None),
+        };
+        let download_split = download_split(db, id, &version_numbers)?;
+        let today = time::OffsetDateTime::now_utc().date();
+        let yearly_downloads =
+            rollup::crate_downloads(db, id, today - time::Duration::days(364), today)?;
+        let recorded_downloads = rollup::recorded_downloads(db, id)?;
+        let name_collisions = name_collisions::for_crate(cache, id)?;
+        let unsafe_usage = schema::UnsafeUsage::get(&id, db)?.map(|usage| usage.contents);
+        let reviews = reviews::for_crate(db, &krate.name)?;
+        let audited_by = reviews::audited_by(&reviews);
+
+        let advisories = advisories_for(db, &krate.name)?
+            .into_iter()
+            .map(|(id, advisory)| AdvisorySummary::new(id, advisory))
+            .collect();
+        let ownership_changes = ownership::history(db, id, &krate.name)?;
+        let blocked = if cached.blocked() {
+            schema::BlockedCrate::get(&blocklist::key(cached.registry(), &krate.name), db)?
+                .map(|blocked| blocked.contents)
+        } else {
+            None
+        };
+        let install = blocked.is_none().then(|| match cached.registry() {
+            CRATES_IO => format!("cargo add {}", krate.name),
+            registry => format!("cargo add {} --registry {registry}", krate.name),
+        });
+        let links = match cached.latest_version() {
+            Some(version) if blocked.is_none() => {
+                version_links::for_version(&krate.name, version, cached.registry())
+            }
+            _ => Vec::new(),
+        };
+        let often_used_with = often_used_with(db, cache, id)?;
+        let duplicate_of = match schema::ReadmeDuplicate::get(&id, db)? {
+            Some(duplicate) => cache
+                .crates()?
+                .get(duplicate.contents.original)
+                .map(|original| DuplicateOf {
+                    name: original.name().to_string(),
+                    similarity: duplicate.contents.similarity,
+                }),
+            None => None,
+        };
+
+        Ok(Some(Self {
+            name: krate.name,
+            description: krate.description,
+            homepage: krate.homepage,
+            documentation: krate.documentation,
+            repository: krate.repository,
+            created_at: krate.created_at,
+            updated_at: krate.updated_at,
+            downloads: krate.downloads.unwrap_or(0),
+            recent_downloads: cached.recent_downloads(),
+            yearly_downloads,
+            recorded_downloads,
+            latest_version: cached.latest_version().map(String::from),
+            license,
+            install,
+            blocked,
+            links,
+            rust_version: cached.rust_version().map(|v| v.to_string()),
+            deprecation: cached.deprecation().cloned(),
+            successors: cached
+                .successors()
+                .iter()
+                .map(|successor| successor.name().to_string())
+                .collect(),
+            likely_placeholder: cached.likely_placeholder(),
+            unmaintained: cached.unmaintained(),
+            wasm: cached.profile().wasm,
+            platforms: cached.profile().platforms,
+            proc_macro: cached.profile().proc_macro,
+            build_script: cached.profile().build_script,
+            dependency_proc_macros: cached.profile().dependency_proc_macros,
+            dependency_build_scripts: cached.profile().dependency_build_scripts,
+            unsafe_usage,
+            reviews,
+            audited_by,
+            language: cached.language().map(String::from),
+            name_collisions,
+            duplicate_of,
+            versions,
+            dependencies,
+            download_split,
+            advisories,
+            ownership_changes,
+            often_used_with,
+        }))
+    }
+
+    /// Returns the warnings about the crate, most serious first, in the
+    /// order the exported summaries list them.
+    pub fn warnings(&self) -> Vec<CrateWarning<'_>> {
+        let mut warnings = Vec::new();
+        if let Some(blocked) = &self.blocked {
+            warnings.push(CrateWarning::Blocked(blocked));
+        }
+        if self.likely_placeholder {
+            warnings.push(CrateWarning::Placeholder);
+        }
+        if self.unmaintained {
+            warnings.push(CrateWarning::Unmaintained);
+        }
+        if let Some(deprecation) = &self.deprecation {
+            warnings.push(CrateWarning::Deprecated(&deprecation.replacements));
+        }
+        if !self.successors.is_empty() {
+            warnings.push(CrateWarning::Successors(&self.successors));
+        }
+        if !self.name_collisions.is_empty() {
+            warnings.push(CrateWarning::NameCollisions(&self.name_collisions));
+        }
+        if let Some(duplicate_of) = &self.duplicate_of {
+            warnings.push(CrateWarning::Duplicate(duplicate_of));
+        }
+        warnings
+    }
+}
+
+/// A warning at the top of a crate's exported summaries, whose text is
+/// shared by `crate_warning.txt`.
+#[derive(Debug)]
+pub(crate) enum CrateWarning<'a> {
+    Blocked(&'a schema::BlockedCrate),
+    Placeholder,
+    Unmaintained,
+    /// The crates to use instead, if any.
+    Deprecated(&'a [String]),
+    Successors(&'a [String]),
+    NameCollisions(&'a [CollidingCrate]),
+    Duplicate(&'a DuplicateOf),
+}
+
+impl CrateWarning<'_> {
+    /// Returns the class the printable summary styles the warning with.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::Blocked(_) => "blocked",
+            Self::Placeholder => "placeholder",
+            Self::Unmaintained => "unmaintained",
+            Self::Deprecated(_) => "deprecated",
+            Self::Successors(_) => "successors",
+            Self::NameCollisions(_) => "name-collision",
+            Self::Duplicate(_) => "duplicate",
+        }
+    }
+}
+
+/// Returns the crates most often depended on together with `id`, skipping
+/// deprecated and blocked crates.
+fn often_used_with(db: &Database, cache: &Cache, id: u64) -> anyhow::Result<Vec<OftenUsedWith>> {
+    let Some(cooccurrence) = schema::CrateCooccurrence::get(&id, db)?
+        else { return Ok(Vec::new()) };
+    let crates = cache.crates()?;
+    Ok(cooccurrence
+        .contents
+        .used_with
+        .into_iter()
+        .filter_map(|used_with| {
+            let krate = crates.get(used_with.crate_id)?;
+            (krate.deprecation().is_none() && !krate.blocked()).then(|| OftenUsedWith {
+                name: krate.name().to_string(),
+                description: krate.description().to_string(),
+                shared: used_with.shared,
+            })
+        })
+        .take(OFTEN_USED_WITH)
+        .collect())
+}
+
+/// Returns the dependencies of `version_id`, grouped by kind and sorted by
+/// name.
+pub(crate) fn dependency_groups(
+    db: &Database,
+    cache: &Cache,
+    version_id: u64,
+) -> anyhow::Result<Vec<DependencyGroup>> {
+    let crates = cache.crates()?;
+    let mut groups = BTreeMap::<DependencyKind, Vec<DependencyInfo>>::new();
+    for mapping in DependenciesByVersion::entries(db)
+        .with_key(&version_id)
+        .query()?
+    {
+        let dependency = mapping.value;
+        let krate = crates.get(dependency.crate_id);
+        let name = match &krate {
+            Some(krate) => krate.name().to_string(),
+            None => format!("#{}", dependency.crate_id),
+        };
+        let latest = krate.and_then(|krate| krate.latest_version().map(String::from));
+        let stale = latest
+            .as_deref()
+            .map_or(false, |latest| is_stale(&dependency.req, latest));
+        groups
+            .entry(dependency.kind)
+            .or_default()
+            .push(DependencyInfo {
+                name,
+                req: dependency.req,
+                optional: dependency.optional,
+                default_features: dependency.default_features,
+                features: dependency.features,
+                target: dependency.target,
+                rename: dependency.explicit_name,
+                latest,
+                stale,
+            });
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(kind, mut dependencies)| {
+            dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+            DependencyGroup { kind, dependencies }
+        })
+        .collect())
+}
+
+/// Returns true if the requirement `req` doesn't match `latest`. Prereleases
+/// are only the latest release of crates without stable releases, and
+/// requirements only match them when they name the prerelease, so they never
+/// count as stale.
+fn is_stale(req: &str, latest: &str) -> bool {
+    let (Ok(req), Ok(latest)) = (
+        semver::VersionReq::parse(req),
+        semver::Version::parse(latest),
+    ) else {
+        return false;
+    };
+    latest.pre.is_empty() && !req.matches(&latest)
+}
+
+/// Sums the recent downloads of `crate_id`'s versions by the group of
+/// semver-compatible releases each belongs to, so that adoption of a new major
+/// release can be compared to the previous ones.
+fn download_split(
+    db: &Database,
+    crate_id: u64,
+    versions: &HashMap<u64, semver::Version>,
+) -> anyhow::Result<Vec<VersionGroupDownloads>> {
+    let today = CalendarDate::from(time::OffsetDateTime::now_utc().date());
+    let mut groups = BTreeMap::<(u64, u64, u64), u64>::new();
+    for mapping in DownloadsByCrateAndDate::entries(db)
+        .with_key_range((crate_id, today - DOWNLOAD_SPLIT_DAYS)..=(crate_id, today))
+        .query()?
+    {
+        let (version_id, downloads) = mapping.value;
+        let Some(version) = versions.get(&version_id) else { continue };
+        // Cargo considers 0.x releases compatible only within the same minor
+        // version, and 0.0.x releases only with themselves.
+        let group = match (version.major, version.minor) {
+            (0, 0) => (0, 0, version.patch),
+            (0, minor) => (0, minor, 0),
+            (major, _) => (major, 0, 0),
+        };
+        *groups.entry(group).or_default() += downloads;
+    }
+
+    let total = groups.values().sum::<u64>().max(1) as f32;
+    Ok(groups
+        .into_iter()
+        .rev()
+        .map(|((major, minor, patch), downloads)| VersionGroupDownloads {
+            versions: match (major, minor) {
+                (0, 0) => format!("0.0.{patch}"),
+                (0, minor) => format!("0.{minor}.x"),
+                (major, _) => format!("{major}.x"),
+            },
+            downloads,
+            share: downloads as f32 / total,
+        })
+        .collect())
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct AdvisorySummary {
+    pub id: String,
+    pub title: String,
+    pub date: String,
+    pub url: Option<String>,
+    pub informational: Option<String>,
+}
+
+impl AdvisorySummary {
+    pub fn new(id: String, advisory: schema::Advisory) -> Self {
+        Self {
+            id,
+            title: advisory.title,
+            date: advisory.date,
+            url: advisory.url,
+            informational: advisory.informational,
+        }
+    }
+}
+
+async fn crate_info(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Response, StatusCode> {
+    match CrateInfo::load(&db, &cache, &name).map_err(internal_error)? {
+        Some(info) => Ok(Json(info).into_response()),
+        None => not_found::crate_json(&cache, &name).map_err(internal_error),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ResolveQuery {
+    /// A Cargo version requirement, such as `^1.2` or `~0.3`.
+    req: String,
+}
+
+/// Returns the version Cargo would choose for a requirement, with its
+/// features and dependencies, for tools completing or describing
+/// `Cargo.toml` dependencies.
+async fn resolve_version(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<ResolveQuery>,
+) -> Result<Json<VersionInfo>, StatusCode> {
+    let req = semver::VersionReq::parse(query.req.trim()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let version = VersionInfo::resolve_requirement(&db, &cache, &name, &req)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    VersionInfo::load(&db, &cache, &name, &version)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize, Debug)]
+struct DependencyGraphQuery {
+    depth: Option<u32>,
+    /// `json` or `dot`. Defaults to `json`.
+    format: Option<String>,
+}
+
+/// Returns the required dependencies of a crate's latest version, followed up
+/// to `depth` steps away, as JSON nodes and edges or as a Graphviz graph.
+async fn dependency_graph(
+    State((_, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<DependencyGraphQuery>,
+) -> Result<Response, StatusCode> {
+    let depth = query.depth.unwrap_or(2).min(MAX_GRAPH_DEPTH);
+    let dot = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("dot") => true,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let graph = DependencyGraph::load(&cache, &name, depth)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if dot {
+        Ok(([(CONTENT_TYPE, "text/vnd.graphviz")], graph.to_dot()).into_response())
+    } else {
+        Ok(Json(graph).into_response())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Dependent {
+    name: String,
+    /// The number of the dependent's versions that depend on the crate.
+    versions: u64,
+}
+
+/// Lists the crates with a published version that depends on a crate, in the
+/// order of their ids.
+async fn dependents(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(paging): Query<PageQuery>,
+) -> Result<Response, StatusCode> {
+    let cursor = paging.cursor::<u64>()?;
+    let Some(id) = find_crate(&cache, &name).map_err(internal_error)?
+        else { return not_found::crate_json(&cache, &name).map_err(internal_error) };
+    let crates = cache.crates().map_err(internal_error)?;
+    let after = cursor.as_ref().map_or(Ok(0), Cursor::next_key)?;
+    let dependents = DependentsByCrate::entries(&db)
+        .with_key_range((id, after)..(id + 1, 0))
+        .reduce_grouped()
+        .map_err(internal_error)?;
+
+    Ok(Json(Page::new(
+        dependents.into_iter().filter_map(|mapping| {
+            let dependent = crates.get(mapping.key.1)?;
+            Some((
+                mapping.key.1,
+                Dependent {
+                    name: dependent.name().to_string(),
+                    versions: mapping.value,
+                },
+            ))
+        }),
+        paging.limit(),
+        crates.generation(),
+        cursor.as_ref(),
+    ))
+    .into_response())
+}
+
+#[derive(Deserialize, Debug)]
+struct SuggestQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+impl SuggestQuery {
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(10).min(50)
+    }
+}
+
+/// Returns the keywords starting with `q`, in alphabetical order.
+async fn suggest_keywords(
+    State((db, _, _)): State<AppState>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let prefix = query.q.trim().to_ascii_lowercase();
+    if prefix.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    Ok(Json(
+        schema::Keywords::entries(&db)
+            .with_key_prefix(&prefix)
+            .limit(u32::try_from(query.limit()).unwrap_or(u32::MAX))
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|mapping| mapping.key)
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, Debug)]
+struct CategorySuggestion {
+    slug: String,
+    category: String,
+}
+
+/// Returns the categories whose slug or name starts with `q`, or that have a
+/// subcategory starting with `q`.
+async fn suggest_categories(
+    State((db, _, _)): State<AppState>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<Vec<CategorySuggestion>>, StatusCode> {
+    let prefix = query.q.trim().to_ascii_lowercase();
+    let mut suggestions = schema::Category::all(&db)
+        .query()
+        .map_err(internal_error)?
+        .into_iter()
+        .filter(|category| {
+            let category = &category.contents;
+            category.slug.starts_with(&prefix)
+                || category
+                    .category
+                    .split("::")
+                    .any(|name| name.trim().to_ascii_lowercase().starts_with(&prefix))
+        })
+        .map(|category| CategorySuggestion {
+            slug: category.contents.slug,
+            category: category.contents.category,
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|a, b| a.slug.cmp(&b.slug));
+    suggestions.truncate(query.limit());
+
+    Ok(Json(suggestions))
+}
+
+#[derive(Serialize, Debug)]
+struct CategoryCrate {
+    name: String,
+    description: String,
+    downloads: u64,
+}
+
+/// Lists the crates in a category or its subcategories, in the order of their
+/// ids.
+async fn category_crates(
+    State((db, cache, _)): State<AppState>,
+    Path(slug): Path<String>,
+    Query(paging): Query<PageQuery>,
+) -> Result<Json<Page<CategoryCrate>>, StatusCode> {
+    let cursor = paging.cursor::<u64>()?;
+    let categories = schema::Category::all(&db).query().map_err(internal_error)?;
+    let category_ids = categories::with_descendants(&categories, &slug);
+    if category_ids.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut crate_ids = BTreeSet::new();
+    for category_id in category_ids {
+        for mapping in CratesByCategory::entries(&db)
+            .with_key(&category_id)
+            .query()
+            .map_err(internal_error)?
+        {
+            crate_ids.insert(
+                mapping
+                    .source
+                    .id
+                    .deserialize::<u64>()
+                    .map_err(internal_error)?,
+            );
+        }
+    }
+
+    let crates = cache.crates().map_err(internal_error)?;
+    let after = cursor.as_ref().map_or(Ok(0), Cursor::next_key)?;
+    Ok(Json(Page::new(
+        crate_ids.range(after..).filter_map(|id| {
+            let krate = crates.get(*id)?;
+            Some((
+                *id,
+                CategoryCrate {
+                    name: krate.name().to_string(),
+                    description: krate.description().to_string(),
+                    downloads: krate.downloads(),
+                },
+            ))
+        }),
+        paging.limit(),
+        crates.generation(),
+        cursor.as_ref(),
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+struct AuditRequest {
+    packages: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LockedPackage {
+    name: String,
+    version: String,
+}
+
+#[derive(Serialize, Debug)]
+struct AuditFinding {
+    name: String,
+    version: String,
+    yanked: bool,
+    advisories: Vec<AdvisorySummary>,
+}
+
+/// Reports yanked versions and applicable advisories for a list of locked
+/// packages. Packages without any findings are omitted from the response.
+async fn audit(
+    State((db, cache, _)): State<AppState>,
+    Json(request): Json<AuditRequest>,
+) -> Result<Json<Vec<AuditFinding>>, StatusCode> {
+    let mut findings = Vec::new();
+    for package in request.packages {
+        let Ok(version) = semver::Version::parse(&package.version) else { continue };
+        let Some(id) = find_crate(&cache, &package.name).map_err(internal_error)? else { continue };
+
+        let yanked = VersionsByCrate::entries(&db)
+            .with_key(&id)
+            .query()
+            .map_err(internal_error)?
+            .into_iter()
+            .any(|mapping| mapping.value.version == package.version && mapping.value.yanked);
+        let advisories = advisories_for(&db, &package.name)
+            .map_err(internal_error)?
+            .into_iter()
+            .filter(|(_, advisory)| advisory.affects(&version))
+            .map(|(id, advisory)| AdvisorySummary::new(id, advisory))
+            .collect::<Vec<_>>();
+
+        if yanked || !advisories.is_empty() {
+            findings.push(AuditFinding {
+                name: package.name,
+                version: package.version,
+                yanked,
+                advisories,
+            });
+        }
+    }
+
+    Ok(Json(findings))
+}
+
+pub(crate) fn find_crate(cache: &Cache, name: &str) -> anyhow::Result<Option<u64>> {
+    Ok(cache.names()?.get(&schema::Crate::normalized_name(name)))
+}
+
+pub(crate) fn advisories_for(
+    db: &Database,
+    crate_name: &str,
+) -> anyhow::Result<Vec<(String, schema::Advisory)>> {
+    let mut advisories = Vec::new();
+    for mapping in AdvisoriesByCrate::entries(db)
+        .with_key(crate_name)
+        .query()?
+    {
+        let id = mapping.source.id.deserialize::<String>()?;
+        if let Some(advisory) = schema::Advisory::get(&id, db)? {
+            advisories.push((id, advisory.contents));
+        }
+    }
+    Ok(advisories)
+}
@@ -0,0 +1,576 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    dump, replication,
+    schema::{ApiKey, ApiUsage},
+};
+
+/// Starts every issued key, which makes keys easy to recognize when they're
+/// leaked.
+const KEY_PREFIX: &str = "drs_";
+
+/// The number of hex digits of a key's hash shown to identify the key.
+const KEY_ID_LENGTH: usize = 12;
+
+/// The header clients can send their key in instead of `Authorization`.
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// How often the requests counted in memory are written to the database.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How keys are required and limited on the public API.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiKeysConfig {
+    /// Whether every request to the metered routes must include a key. When
+    /// false, requests without a key are served without limits, and requests
+    /// with one count against its quota.
+    pub required: bool,
+    /// The most requests per UTC day made with a key issued without its own
+    /// quota. 0 means unlimited.
+    pub daily_quota: u64,
+    /// Days that each key's daily usage is kept. Totals are kept with the
+    /// key. 0 keeps daily usage forever.
+    pub usage_retention_days: u64,
+}
+
+impl Default for ApiKeysConfig {
+    fn default() -> Self {
+        Self {
+            required: false,
+            daily_quota: 10_000,
+            usage_retention_days: 90,
+        }
+    }
+}
+
+/// An issued key and how much it has been used, as listed by the `api-keys`
+/// command and the admin API.
+#[derive(Serialize, Debug)]
+pub struct KeySummary {
+    /// The start of the key's hash, which identifies the key without
+    /// revealing it.
+    pub id: String,
+    pub name: String,
+    pub daily_quota: Option<u64>,
+    pub created_at: String,
+    pub revoked: bool,
+    /// The requests made with the key today, in UTC.
+    pub requests_today: u64,
+    pub requests_total: u64,
+    /// The requests refused because the key's quota was used up.
+    pub rejected_total: u64,
+}
+
+/// Issues a key for `name`, which is limited to `daily_quota` requests per
+/// day, or the configured default when None. Only the key's hash is stored,
+/// so the returned key can't be shown again.
+pub fn create(db: &Database, name: &str, daily_quota: Option<u64>) -> anyhow::Result<String> {
+    let key = format!(
+        "{KEY_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    ApiKey {
+        name: name.to_string(),
+        daily_quota,
+        created_at: dump::timestamp(OffsetDateTime::now_utc()),
+        revoked: false,
+        requests_total: 0,
+        rejected_total: 0,
+    }
+    .insert_into(&hash(&key), db)?;
+    Ok(key)
+}
+
+/// Lists every issued key, including revoked keys, oldest first. Requests
+/// the server hasn't written to the database yet aren't included.
+pub fn list(db: &Database) -> anyhow::Result<Vec<KeySummary>> {
+    let keys = ApiKey::all(db).query()?;
+    let today = today();
+    let usage_ids = keys
+        .iter()
+        .map(|key| usage_id(&key.header.id, &today))
+        .collect::<Vec<_>>();
+    let requests_today = ApiUsage::get_multiple(&usage_ids, db)?
+        .into_iter()
+        .map(|usage| (usage.contents.key, usage.contents.requests))
+        .collect::<HashMap<_, _>>();
+
+    let mut keys = keys
+        .into_iter()
+        .map(|key| KeySummary {
+            id: key.header.id[..KEY_ID_LENGTH].to_string(),
+            requests_today: requests_today
+                .get(&key.header.id)
+                .copied()
+                .unwrap_or_default(),
+            name: key.contents.name,
+            daily_quota: key.contents.daily_quota,
+            created_at: key.contents.created_at,
+            revoked: key.contents.revoked,
+            requests_total: key.contents.requests_total,
+            rejected_total: key.contents.rejected_total,
+        })
+        .collect::<Vec<_>>();
+    keys.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(keys)
+}
+
+/// Revokes the key whose id, as shown by [`list`], starts with `id`,
+/// returning the key's name. Usage of revoked keys is kept.
+pub fn revoke(db: &Database, id: &str) -> anyhow::Result<String> {
+    let mut matching = ApiKey::all(db)
+        .query()?
+        .into_iter()
+        .filter(|key| !id.is_empty() && key.header.id.starts_with(id))
+        .collect::<Vec<_>>();
+    let mut key = match matching.len() {
+        0 => anyhow::bail!("no API key has the id {id}"),
+        1 => matching.remove(0),
+        _ => anyhow::bail!("more than one API key has an id starting with {id}"),
+    };
+    key.modify(db, |key| key.contents.revoked = true)?;
+    Ok(key.contents.name)
+}
+
+/// Deletes the daily usage recorded more than `retention_days` days ago,
+/// returning how many days were deleted. Each key's totals are kept.
+pub(crate) fn prune(db: &Database, retention_days: u64) -> anyhow::Result<usize> {
+    let retention = time::Duration::days(i64::try_from(retention_days).unwrap_or(i64::MAX));
+    let Some(cutoff) = OffsetDateTime::now_utc().date().checked_sub(retention)
+        else { return Ok(0) };
+    // Dates sort chronologically as strings.
+    let cutoff = cutoff.to_string();
+    let mut tx = Transaction::new();
+    for usage in ApiUsage::all(db).query()? {
+        if usage.contents.date < cutoff {
+            tx.operations.push(Operation::delete(
+                ApiUsage::collection_name(),
+                Header::try_from(usage.header)?,
+            ));
+        }
+    }
+    let deleted = tx.operations.len();
+    if deleted > 0 {
+        tx.apply(db)?;
+    }
+    Ok(deleted)
+}
+
+/// The state of the [`meter`] middleware.
+///
+/// Requests are counted in memory and written to the database every
+/// [`FLUSH_INTERVAL`], so that serving a request doesn't wait for a write.
+/// Requests counted since the last write are lost if the server stops.
+#[derive(Clone, Debug)]
+pub(crate) struct Meter {
+    database: Database,
+    config: ApiKeysConfig,
+    /// Each key's requests, by the key's hash and the day.
+    counters: Arc<Mutex<HashMap<(String, String), Counter>>>,
+}
+
+impl Meter {
+    /// Returns a meter that writes its counts to `database` from a spawned
+    /// task, which also prunes old usage once a day.
+    pub fn new(database: Database, config: ApiKeysConfig) -> Self {
+        let meter = Self {
+            database,
+            config,
+            counters: Arc::default(),
+        };
+        tokio::spawn(meter.clone().flush_periodically());
+        meter
+    }
+
+    async fn flush_periodically(self) {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        let mut last_pruned = None;
+        loop {
+            interval.tick().await;
+            if replication::is_read_only() {
+                continue;
+            }
+            let meter = self.clone();
+            let prune = last_pruned.as_ref() != Some(&today());
+            let flushed = tokio::task::spawn_blocking(move || {
+                meter.flush()?;
+                if prune && meter.config.usage_retention_days > 0 {
+                    let deleted = self::prune(&meter.database, meter.config.usage_retention_days)?;
+                    tracing::info!("deleted {deleted} days of expired API key usage");
+                }
+                anyhow::Ok(())
+            });
+            match flushed.await {
+                Ok(Ok(())) => {
+                    if prune {
+                        last_pruned = Some(today());
+                    }
+                }
+                Ok(Err(err)) => tracing::error!("error recording API key usage: {err}"),
+                Err(err) => tracing::error!("error recording API key usage: {err}"),
+            }
+        }
+    }
+
+    /// Writes the requests counted since the last flush to the database, and
+    /// forgets the counters of past days once they're written.
+    fn flush(&self) -> anyhow::Result<()> {
+        let pending = self
+            .counters
+            .lock()
+            .expect("poisoned")
+            .iter()
+            .filter(|(_, counter)| counter.is_pending())
+            .map(|(day, counter)| (day.clone(), *counter))
+            .collect::<Vec<_>>();
+        for ((hash, date), counter) in pending {
+            record(&self.database, &hash, &date, counter)?;
+            if let Some(current) = self
+                .counters
+                .lock()
+                .expect("poisoned")
+                .get_mut(&(hash, date))
+            {
+                current.written(counter);
+            }
+        }
+
+        let today = today();
+        self.counters
+            .lock()
+            .expect("poisoned")
+            .retain(|(_, date), counter| *date == today || counter.is_pending());
+        Ok(())
+    }
+
+    /// Counts a request made with `key`, returning whether it may be served.
+    fn admit(&self, key: &str) -> anyhow::Result<Admission> {
+        let hash = hash(key);
+        let Some(api_key) = ApiKey::get(&hash, &self.database)?
+            else { return Ok(Admission::InvalidKey) };
+        if api_key.contents.revoked {
+            return Ok(Admission::InvalidKey);
+        }
+        let quota = api_key
+            .contents
+            .daily_quota
+            .unwrap_or(self.config.daily_quota);
+
+        let day = (hash, today());
+        let counted = self.counters.lock().expect("poisoned").contains_key(&day);
+        // The day's first request picks up the requests written before the
+        // server started.
+        let stored = if counted {
+            0
+        } else {
+            ApiUsage::get(&usage_id(&day.0, &day.1), &self.database)?
+                .map_or(0, |usage| usage.contents.requests)
+        };
+        let allowed = self
+            .counters
+            .lock()
+            .expect("poisoned")
+            .entry(day)
+            .or_insert_with(|| Counter::new(stored))
+            .admit(quota);
+        Ok(if allowed {
+            Admission::Allowed
+        } else {
+            Admission::OverQuota
+        })
+    }
+}
+
+/// A key's requests on one day.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counter {
+    /// The requests already written to the database.
+    stored: u64,
+    /// The requests, and the requests refused, not yet written.
+    requests: u64,
+    rejected: u64,
+}
+
+impl Counter {
+    fn new(stored: u64) -> Self {
+        Self {
+            stored,
+            ..Self::default()
+        }
+    }
+
+    /// Counts a request against `quota`, where 0 is unlimited, returning
+    /// whether it's allowed.
+    fn admit(&mut self, quota: u64) -> bool {
+        let allowed = quota == 0 || self.stored + self.requests < quota;
+        if allowed {
+            self.requests += 1;
+        } else {
+            self.rejected += 1;
+        }
+        allowed
+    }
+
+    fn is_pending(&self) -> bool {
+        self.requests > 0 || self.rejected > 0
+    }
+
+    /// Moves the counts of `flushed`, a copy of this counter taken before it
+    /// was written, from pending to stored. Requests counted during the
+    /// write stay pending.
+    fn written(&mut self, flushed: Counter) {
+        self.stored += flushed.requests;
+        self.requests -= flushed.requests;
+        self.rejected -= flushed.rejected;
+    }
+}
+
+/// Adds the pending counts of `counter` to the usage of the key with `hash`
+/// on `date` and to the key's totals, in one transaction.
+fn record(db: &Database, hash: &str, date: &str, counter: Counter) -> anyhow::Result<()> {
+    let id = usage_id(hash, date);
+    let mut tx = Transaction::new();
+    tx.operations.push(match ApiUsage::get(&id, db)? {
+        Some(mut usage) => {
+            usage.contents.requests += counter.requests;
+            usage.contents.rejected += counter.rejected;
+            Operation::update_serialized::<ApiUsage>(usage.header, &usage.contents)?
+        }
+        None => Operation::insert_serialized::<ApiUsage>(
+            Some(&id),
+            &ApiUsage {
+                key: hash.to_string(),
+                date: date.to_string(),
+                requests: counter.requests,
+                rejected: counter.rejected,
+            },
+        )?,
+    });
+    if let Some(mut key) = ApiKey::get(hash, db)? {
+        key.contents.requests_total += counter.requests;
+        key.contents.rejected_total += counter.rejected;
+        tx.operations.push(Operation::update_serialized::<ApiKey>(
+            key.header,
+            &key.contents,
+        )?);
+    }
+    tx.apply(db)?;
+    Ok(())
+}
+
+/// Returns the id of the usage of the key with `hash` on `date`.
+fn usage_id(hash: &str, date: &str) -> String {
+    format!("{hash}/{date}")
+}
+
+enum Admission {
+    Allowed,
+    InvalidKey,
+    OverQuota,
+}
+
+/// Counts each request against the daily quota of the key it was made with,
+/// refusing requests with unknown or revoked keys, requests over quota, and,
+/// when keys are required, requests without a key.
+pub(crate) async fn meter<B>(
+    State(meter): State<Meter>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(key) = provided_key(&request) else {
+        return if meter.config.required {
+            (StatusCode::UNAUTHORIZED, "an API key is required").into_response()
+        } else {
+            next.run(request).await
+        };
+    };
+
+    // Usage is counted in the primary's database, so that quotas apply
+    // across every process.
+    if replication::is_read_only() {
+        return replication::misdirected();
+    }
+    match meter.admit(&key) {
+        Ok(Admission::Allowed) => next.run(request).await,
+        Ok(Admission::InvalidKey) => {
+            (StatusCode::UNAUTHORIZED, "the API key is invalid").into_response()
+        }
+        Ok(Admission::OverQuota) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, seconds_until_tomorrow().to_string())],
+            "the API key's daily quota is used up",
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("error metering API request: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Returns the key sent as `Authorization: Bearer <key>` or in the
+/// `X-Api-Key` header.
+fn provided_key<B>(request: &Request<B>) -> Option<String> {
+    let headers = request.headers();
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let header = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    bearer
+        .or(header)
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+}
+
+/// Returns the hex-encoded SHA-256 hash of `key`, which identifies it in the
+/// database.
+fn hash(key: &str) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    let mut hash = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        hash.push_str(&format!("{byte:02x}"));
+    }
+    hash
+}
+
+/// Returns today's date in UTC, which quotas are counted by.
+fn today() -> String {
+    OffsetDateTime::now_utc().date().to_string()
+}
+
+/// Returns the seconds until quotas are reset at midnight UTC.
+fn seconds_until_tomorrow() -> i64 {
+    let now = OffsetDateTime::now_utc();
+    now.date().next_day().map_or(1, |tomorrow| {
+        (tomorrow.midnight().assume_utc() - now)
+            .whole_seconds()
+            .max(1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotas_refuse_requests_once_used_up() {
+        let mut counter = Counter::new(0);
+        assert!(counter.admit(2));
+        assert!(counter.admit(2));
+        assert!(!counter.admit(2));
+        assert!(!counter.admit(2));
+        assert_eq!(counter.requests, 2);
+        assert_eq!(counter.rejected, 2);
+    }
+
+    #[test]
+    fn zero_quotas_are_unlimited() {
+        let mut counter = Counter::new(u64::MAX - 1);
+        assert!(counter.admit(0));
+        assert!(counter.admit(0));
+    }
+
+    #[test]
+    fn stored_requests_count_against_the_quota() {
+        let mut counter = Counter::new(9);
+        assert!(counter.admit(10));
+        assert!(!counter.admit(10));
+    }
+
+    #[test]
+    fn requests_counted_during_a_write_stay_pending() {
+        let mut counter = Counter::new(5);
+        counter.admit(0);
+        let flushed = counter;
+        counter.admit(0);
+        counter.admit(1);
+        counter.written(flushed);
+        assert_eq!(
+            counter,
+            Counter {
+                stored: 6,
+                requests: 1,
+                rejected: 1,
+            }
+        );
+        assert!(counter.is_pending());
+    }
+
+    #[test]
+    fn flushed_usage_is_listed_and_pruned() {
+        let (_dir, db) = crate::test_database();
+        let key = create(&db, "test", Some(2)).unwrap();
+        let meter = Meter {
+            database: db.clone(),
+            config: ApiKeysConfig::default(),
+            counters: Arc::default(),
+        };
+        for _ in 0..3 {
+            meter.admit(&key).unwrap();
+        }
+        assert!(matches!(
+            meter.admit("drs_unknown").unwrap(),
+            Admission::InvalidKey
+        ));
+        meter.flush().unwrap();
+
+        let summary = list(&db).unwrap().remove(0);
+        assert_eq!(summary.requests_today, 2);
+        assert_eq!(summary.requests_total, 2);
+        assert_eq!(summary.rejected_total, 1);
+
+        // A restarted server picks up the day's written requests.
+        let restarted = Meter {
+            counters: Arc::default(),
+            ..meter
+        };
+        assert!(matches!(
+            restarted.admit(&key).unwrap(),
+            Admission::OverQuota
+        ));
+
+        ApiUsage {
+            key: hash(&key),
+            date: String::from("2000-01-01"),
+            requests: 7,
+            rejected: 0,
+        }
+        .insert_into(&usage_id(&hash(&key), "2000-01-01"), &db)
+        .unwrap();
+        assert_eq!(prune(&db, 90).unwrap(), 1);
+        assert_eq!(prune(&db, 90).unwrap(), 0);
+        assert_eq!(list(&db).unwrap()[0].requests_today, 2);
+    }
+}
@@ -0,0 +1,86 @@
+//! Downloading and extracting the gzipped tarballs that crates.io dumps, the
+//! advisory database, and crev proof repositories are published as.
+
+use std::{fs::File, path::Path};
+
+use flate2::read::GzDecoder;
+use tokio::io::AsyncWriteExt;
+
+/// Saves the body of `response` to `archive`, then extracts the archive into
+/// `destination`, which is created if it doesn't exist.
+pub(crate) async fn download_and_extract(
+    mut response: reqwest::Response,
+    archive: &Path,
+    destination: &Path,
+) -> anyhow::Result<()> {
+    let mut file = tokio::fs::File::create(archive).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let archive = archive.to_path_buf();
+    let destination = destination.to_path_buf();
+    tokio::task::spawn_blocking(move || extract(&archive, &destination)).await?
+}
+
+/// Extracts the gzipped tarball `archive` into `destination`. Entries that
+/// would be written outside of `destination` are skipped.
+pub(crate) fn extract(archive: &Path, destination: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    let file = File::open(archive)?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(destination)
+        .map_err(|err| anyhow::anyhow!("error extracting {}: {err}", archive.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn write_archive(path: &Path, files: &[(&str, &str)]) {
+        let file = File::create(path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::fast()));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn extracts_into_the_destination() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("dump.tar.gz");
+        write_archive(
+            &archive,
+            &[
+                ("2023-03-20-020030/data/crates.csv", "id,name\n1,serde\n"),
+                ("2023-03-20-020030/metadata.json", "{}"),
+            ],
+        );
+
+        let destination = directory.path().join("data");
+        extract(&archive, &destination).unwrap();
+        let crates =
+            std::fs::read_to_string(destination.join("2023-03-20-020030/data/crates.csv")).unwrap();
+        assert_eq!(crates, "id,name\n1,serde\n");
+        assert!(destination.join("2023-03-20-020030/metadata.json").exists());
+    }
+
+    #[test]
+    fn corrupt_archives_are_errors() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("corrupt.tar.gz");
+        std::fs::write(&archive, b"not a gzip file").unwrap();
+        assert!(extract(&archive, &directory.path().join("data")).is_err());
+    }
+}
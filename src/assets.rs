@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    path::{Component, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use axum::{
+    extract::Path,
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use include_dir::{include_dir, Dir, File};
+use once_cell::sync::Lazy;
+
+static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/assets");
+
+static MANIFEST: Lazy<Manifest> = Lazy::new(Manifest::build);
+
+/// When set, assets are read from the source tree on every request instead of
+/// being served from the copies embedded at compile time.
+static DEV_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Fingerprinted names never change contents, so they can be cached forever.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Maps each embedded asset to a name containing a hash of its contents.
+struct Manifest {
+    /// Fingerprinted URLs keyed by the asset's path.
+    urls: HashMap<&'static str, String>,
+    /// Assets keyed by their fingerprinted paths.
+    files: HashMap<String, &'static File<'static>>,
+}
+
+impl Manifest {
+    fn build() -> Self {
+        let mut manifest = Self {
+            urls: HashMap::new(),
+            files: HashMap::new(),
+        };
+        manifest.add_dir(&ASSETS);
+        manifest
+    }
+
+    fn add_dir(&mut self, dir: &'static Dir<'static>) {
+        for file in dir.files() {
+            let Some(path) = file.path().to_str() else { continue };
+            let hash = format!("{:016x}", fnv1a(file.contents()));
+            let fingerprinted = match path.rsplit_once('.') {
+                Some((stem, extension)) => format!("{stem}.{}.{extension}", &hash[..8]),
+                None => format!("{path}.{}", &hash[..8]),
+            };
+            self.urls.insert(path, format!("/assets/{fingerprinted}"));
+            self.files.insert(fingerprinted, file);
+        }
+        for dir in dir.dirs() {
+            self.add_dir(dir);
+        }
+    }
+}
+
+/// Serves assets from `src/assets` on disk, so that edits show up without
+/// rebuilding. Templates are still compiled into the binary by askama, so
+/// changing them requires a rebuild.
+pub fn enable_dev_mode() {
+    DEV_MODE.store(true, Ordering::Relaxed);
+}
+
+/// Returns the fingerprinted URL of the asset at `path`, relative to
+/// `src/assets`. In dev mode the URL isn't fingerprinted, since the file can
+/// change while the server is running.
+pub fn url(path: &str) -> String {
+    if DEV_MODE.load(Ordering::Relaxed) {
+        return format!("/assets/{path}");
+    }
+
+    MANIFEST
+        .urls
+        .get(path)
+        .cloned()
+        .unwrap_or_else(|| format!("/assets/{path}"))
+}
+
+/// Serves an embedded asset. Fingerprinted paths are cached indefinitely,
+/// while unfingerprinted paths must be revalidated.
+pub(crate) async fn serve(Path(path): Path<String>) -> Response {
+    if DEV_MODE.load(Ordering::Relaxed) {
+        return serve_from_disk(&path).await;
+    }
+
+    let (file, cache_control) = if let Some(file) = MANIFEST.files.get(&path) {
+        (*file, IMMUTABLE)
+    } else if let Some(file) = ASSETS.get_file(&path) {
+        (file, "no-cache")
+    } else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    (
+        [
+            (CONTENT_TYPE, content_type(&path)),
+            (CACHE_CONTROL, cache_control),
+        ],
+        file.contents(),
+    )
+        .into_response()
+}
+
+async fn serve_from_disk(path: &str) -> Response {
+    let Some(full_path) = disk_path(path) else { return StatusCode::NOT_FOUND.into_response() };
+    match tokio::fs::read(full_path).await {
+        Ok(contents) => (
+            [
+                (CONTENT_TYPE, content_type(path)),
+                (CACHE_CONTROL, "no-store"),
+            ],
+            contents,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Returns the path of the asset at `path` in `src/assets`, or None if `path`
+/// could refer to a file outside of it. Absolute paths, which the path
+/// parameter decodes `%2F` into, would replace the directory when joined.
+fn disk_path(path: &str) -> Option<PathBuf> {
+    let relative = std::path::Path::new(path);
+    let mut components = relative.components().peekable();
+    components.peek()?;
+    if !components.all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    Some(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/assets")
+            .join(relative),
+    )
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A 64-bit FNV-1a hash, which is stable across builds and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_paths_stay_in_the_assets_directory() {
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/assets");
+        assert_eq!(disk_path("style.css"), Some(assets.join("style.css")));
+        assert_eq!(
+            disk_path("icons/crate.svg"),
+            Some(assets.join("icons/crate.svg"))
+        );
+    }
+
+    #[test]
+    fn absolute_disk_paths_are_rejected() {
+        assert_eq!(disk_path("/etc/passwd"), None);
+        assert_eq!(disk_path("//etc/passwd"), None);
+    }
+
+    #[test]
+    fn parent_disk_paths_are_rejected() {
+        assert_eq!(disk_path(".."), None);
+        assert_eq!(disk_path("../Cargo.toml"), None);
+        assert_eq!(disk_path("icons/../../../Cargo.toml"), None);
+        assert_eq!(disk_path("./style.css"), None);
+        assert_eq!(disk_path(""), None);
+    }
+}
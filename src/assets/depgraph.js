@@ -0,0 +1,60 @@
+// Renders the dependency graph of a crate as a collapsible tree. The graph is
+// loaded from `/api/v1/crates/:name/depgraph` into any element with a
+// `data-depgraph` attribute naming the crate.
+document.addEventListener("DOMContentLoaded", () => {
+    for (const container of document.querySelectorAll("[data-depgraph]")) {
+        const name = container.dataset.depgraph;
+        fetch(`/api/v1/crates/${encodeURIComponent(name)}/depgraph?depth=3`)
+            .then((response) => response.json())
+            .then((graph) => render(container, graph))
+            .catch(() => { container.textContent = "The dependency graph could not be loaded."; });
+    }
+});
+
+function render(container, graph) {
+    const children = new Map();
+    for (const edge of graph.edges) {
+        if (!children.has(edge.from)) {
+            children.set(edge.from, []);
+        }
+        children.get(edge.from).push(edge);
+    }
+
+    container.replaceChildren(subtree(graph.root, children, new Set([graph.root])));
+}
+
+// Builds the list of `name`'s dependencies. `ancestors` contains `name` and
+// every crate above it in the tree. Each dependency's own list is only built
+// when it's first expanded, since the same crates appear under many others.
+function subtree(name, children, ancestors) {
+    const list = document.createElement("ul");
+    for (const edge of children.get(name) || []) {
+        const item = document.createElement("li");
+        const label = document.createElement("span");
+        const link = document.createElement("a");
+        link.href = `/crates/${encodeURIComponent(edge.to)}`;
+        link.textContent = edge.to;
+        label.append(link, ` ${edge.req}`);
+        if (edge.kind === "Build") {
+            label.append(" (build)");
+        }
+
+        // Expanding a crate that depends on itself would repeat forever.
+        if (children.has(edge.to) && !ancestors.has(edge.to)) {
+            const details = document.createElement("details");
+            const summary = document.createElement("summary");
+            summary.append(label);
+            details.append(summary);
+            details.addEventListener("toggle", () => {
+                if (details.open && details.childElementCount === 1) {
+                    details.append(subtree(edge.to, children, new Set(ancestors).add(edge.to)));
+                }
+            });
+            item.append(details);
+        } else {
+            item.append(label);
+        }
+        list.append(item);
+    }
+    return list;
+}
@@ -0,0 +1,49 @@
+// Embeds a delve-rs search box in place of every element with a
+// `data-delve-search` attribute on the page that loads this script. The
+// element's `data-keyword` and `data-category` attributes scope the search,
+// and `data-links="message"` keeps result links from opening the crate's
+// page. Clicked results are dispatched from the element as a
+// `delve-rs:result` event whose detail contains the crate's `name` and `url`.
+(() => {
+    const origin = new URL(document.currentScript.src).origin;
+    const elements = new Map();
+
+    const embed = () => {
+        for (const element of document.querySelectorAll("[data-delve-search]")) {
+            const params = new URLSearchParams();
+            for (const name of ["q", "keyword", "category", "links"]) {
+                if (element.dataset[name]) {
+                    params.set(name, element.dataset[name]);
+                }
+            }
+            const frame = document.createElement("iframe");
+            frame.src = `${origin}/embed/search?${params}`;
+            frame.title = element.dataset.title || "Search Rust crates";
+            frame.style.border = "0";
+            frame.style.width = "100%";
+            element.replaceChildren(frame);
+            elements.set(frame.contentWindow, { element, frame });
+        }
+    };
+
+    window.addEventListener("message", (event) => {
+        const embedded = elements.get(event.source);
+        if (event.origin !== origin || !embedded || typeof event.data !== "object") {
+            return;
+        }
+        if (event.data.type === "delve-rs:resize") {
+            embedded.frame.style.height = `${event.data.height}px`;
+        } else if (event.data.type === "delve-rs:result") {
+            embedded.element.dispatchEvent(new CustomEvent("delve-rs:result", {
+                bubbles: true,
+                detail: { name: event.data.name, url: event.data.url },
+            }));
+        }
+    });
+
+    if (document.readyState === "loading") {
+        document.addEventListener("DOMContentLoaded", embed);
+    } else {
+        embed();
+    }
+})();
@@ -0,0 +1,20 @@
+// Runs inside the embedded search box served by `/embed/search`, telling the
+// embedding page about clicked results and the frame's height so that
+// `embed.js` can size the frame to fit.
+document.addEventListener("DOMContentLoaded", () => {
+    const post = (message) => window.parent.postMessage(message, "*");
+
+    new ResizeObserver(() => {
+        post({ type: "delve-rs:resize", height: document.documentElement.scrollHeight });
+    }).observe(document.body);
+
+    for (const link of document.querySelectorAll("a[data-crate]")) {
+        link.addEventListener("click", (event) => {
+            // Only the embedding page handles the click when it asked to.
+            if (document.body.dataset.links === "message") {
+                event.preventDefault();
+            }
+            post({ type: "delve-rs:result", name: link.dataset.crate, url: link.href });
+        });
+    }
+});
@@ -0,0 +1,52 @@
+// Keyboard shortcuts for searching without a mouse: `/` focuses the search
+// box, `j` and `k` move between results, and Enter opens the focused result.
+// Escape leaves the search box so that the other shortcuts can be used.
+function resultLinks() {
+    return Array.from(document.querySelectorAll("#results a.result-link"));
+}
+
+function isTyping(target) {
+    return target instanceof HTMLInputElement
+        || target instanceof HTMLTextAreaElement
+        || target instanceof HTMLSelectElement
+        || (target instanceof HTMLElement && target.isContentEditable);
+}
+
+document.addEventListener("keydown", (event) => {
+    if (event.ctrlKey || event.metaKey || event.altKey) {
+        return;
+    }
+
+    if (isTyping(event.target)) {
+        if (event.key === "Escape") {
+            event.target.blur();
+        }
+        return;
+    }
+
+    if (event.key === "/") {
+        const search = document.querySelector("input[type=search]");
+        if (search) {
+            event.preventDefault();
+            search.focus();
+            search.select();
+        }
+    } else if (event.key === "j" || event.key === "k") {
+        const links = resultLinks();
+        if (links.length === 0) {
+            return;
+        }
+        event.preventDefault();
+        const current = links.indexOf(document.activeElement);
+        let next;
+        if (current === -1) {
+            next = event.key === "j" ? 0 : links.length - 1;
+        } else {
+            next = event.key === "j"
+                ? Math.min(current + 1, links.length - 1)
+                : Math.max(current - 1, 0);
+        }
+        links[next].focus();
+        links[next].closest("tr").scrollIntoView({ block: "nearest" });
+    }
+});
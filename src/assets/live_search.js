@@ -0,0 +1,46 @@
+// Updates the search results while typing. Inputs marked with
+// `data-live-search` replace the contents of `#results` with the results of
+// their form's query, fetched from `/search/fragment`. Typing is debounced by
+// the number of milliseconds in the attribute's value, if any.
+document.addEventListener("DOMContentLoaded", () => {
+    const results = document.getElementById("results");
+    if (!results) {
+        return;
+    }
+
+    let latest = null;
+    const search = (form) => {
+        const query = new URLSearchParams(new FormData(form)).toString();
+        if (query === latest) {
+            return;
+        }
+        latest = query;
+        fetch(`/search/fragment?${query}`)
+            .then((response) => (response.ok ? response.text() : Promise.reject(response)))
+            .then((html) => {
+                // A later search may have finished first.
+                if (query === latest) {
+                    results.innerHTML = html;
+                }
+            })
+            .catch(() => {
+                // Let the same query be retried, unless a later one is
+                // already running.
+                if (query === latest) {
+                    latest = null;
+                }
+            });
+    };
+
+    for (const input of document.querySelectorAll("[data-live-search]")) {
+        const delay = Number(input.dataset.liveSearch) || 0;
+        let pending = null;
+        const schedule = () => {
+            clearTimeout(pending);
+            pending = setTimeout(() => search(input.form), delay);
+        };
+        input.addEventListener(input.type === "checkbox" ? "change" : "input", schedule);
+        // Clearing a search box with its clear button fires `search`.
+        input.addEventListener("search", () => search(input.form));
+    }
+});
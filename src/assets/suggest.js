@@ -0,0 +1,49 @@
+// Suggests keywords and categories while the last word typed into a search
+// box starts with `keyword:` or `category:`. Suggestions are offered through a
+// datalist, whose options are the whole query with the last word completed.
+const FACETS = {
+    "keyword:": (prefix) => `/api/v1/keywords/suggest?q=${encodeURIComponent(prefix)}`,
+    "category:": (prefix) => `/api/v1/categories/suggest?q=${encodeURIComponent(prefix)}`,
+};
+
+document.addEventListener("DOMContentLoaded", () => {
+    for (const input of document.querySelectorAll("input[data-suggest]")) {
+        const list = document.getElementById(input.dataset.suggest);
+        let pending = null;
+        input.addEventListener("input", () => {
+            clearTimeout(pending);
+            pending = setTimeout(() => suggest(input, list), 150);
+        });
+    }
+});
+
+function suggest(input, list) {
+    const query = input.value;
+    const start = query.search(/\S*$/);
+    const word = query.slice(start);
+    const facet = Object.keys(FACETS).find((facet) => word.startsWith(facet));
+    if (!facet) {
+        list.replaceChildren();
+        return;
+    }
+
+    const before = query.slice(0, start);
+    fetch(FACETS[facet](word.slice(facet.length)))
+        .then((response) => response.json())
+        .then((suggestions) => {
+            // The query may have changed while the suggestions were loading.
+            if (input.value !== query) {
+                return;
+            }
+            list.replaceChildren(...suggestions.map((suggestion) => {
+                const option = document.createElement("option");
+                const value = typeof suggestion === "string" ? suggestion : suggestion.slug;
+                option.value = `${before}${facet}${value} `;
+                if (typeof suggestion !== "string") {
+                    option.label = suggestion.category;
+                }
+                return option;
+            }));
+        })
+        .catch(() => list.replaceChildren());
+}
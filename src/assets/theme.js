@@ -0,0 +1,33 @@
+// Applies the theme chosen with the theme toggle, which is remembered in the
+// `theme` cookie. Without a choice, the stylesheet follows the system's color
+// scheme. This script is loaded without `defer` so that the theme is applied
+// before the page is first painted.
+const THEME_COOKIE = "theme";
+
+function savedTheme() {
+    const cookie = document.cookie
+        .split("; ")
+        .find((cookie) => cookie.startsWith(`${THEME_COOKIE}=`));
+    const theme = cookie && cookie.substring(THEME_COOKIE.length + 1);
+    return theme === "light" || theme === "dark" ? theme : null;
+}
+
+function currentTheme() {
+    return savedTheme()
+        || (window.matchMedia("(prefers-color-scheme: light)").matches ? "light" : "dark");
+}
+
+const initialTheme = savedTheme();
+if (initialTheme) {
+    document.documentElement.dataset.theme = initialTheme;
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+    for (const toggle of document.querySelectorAll("[data-theme-toggle]")) {
+        toggle.addEventListener("click", () => {
+            const theme = currentTheme() === "light" ? "dark" : "light";
+            document.documentElement.dataset.theme = theme;
+            document.cookie = `${THEME_COOKIE}=${theme}; path=/; max-age=31536000; samesite=lax`;
+        });
+    }
+});
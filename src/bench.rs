@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use bonsaidb::local::Database;
+use serde::Deserialize;
+use tempfile::TempDir;
+
+use crate::{
+    cache::Cache, dump, filters::Filters, query, search_backend::SearchConfig, SearchIndex,
+};
+
+/// The number of results considered when computing NDCG.
+const NDCG_DEPTH: usize = 10;
+
+/// The small dump that benchmarks and relevance tests import.
+pub const FIXTURE_DUMP: &str = "tests/fixtures/dump";
+
+/// Imports [`FIXTURE_DUMP`] into a new database in a temporary directory and
+/// loads the cache, so that benchmarks need no setup and are comparable
+/// between machines. The returned directory must be kept alive for as long
+/// as the database is in use.
+pub fn import_fixture(
+    search: &SearchConfig,
+) -> anyhow::Result<(TempDir, Database, Cache, SearchIndex)> {
+    let dir = tempfile::tempdir()?;
+    let (db, cache, index) = crate::open(dir.path().join("delve-rs.bonsaidb"), search)?;
+    dump::import_local_dump(FIXTURE_DUMP, &db, &index)?;
+    cache.refresh_blocking()?;
+    Ok((dir, db, cache, index))
+}
+
+/// Loads a query corpus: one query per line, ignoring blank lines and lines
+/// beginning with `#`.
+pub fn load_queries(path: impl AsRef<Path>) -> anyhow::Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// A query with hand-assigned relevance grades for the crates that should be
+/// returned.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LabeledQuery {
+    pub q: String,
+    /// Relevance grades keyed by crate name. Higher is more relevant, and
+    /// unlisted crates have a grade of 0.
+    pub relevant: HashMap<String, u8>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Labels {
+    query: Vec<LabeledQuery>,
+}
+
+/// Loads a TOML file containing `[[query]]` tables of [`LabeledQuery`].
+pub fn load_labels(path: impl AsRef<Path>) -> anyhow::Result<Vec<LabeledQuery>> {
+    let labels: Labels = toml::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(labels.query)
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub samples: usize,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// The slowest queries, ordered by their median latency.
+    pub slowest: Vec<(String, Duration)>,
+    /// The mean NDCG@10 across all labeled queries.
+    pub mean_ndcg: Option<f32>,
+    pub ndcg_by_query: Vec<(String, f32)>,
+}
+
+/// Executes each query in `queries` `iterations` times, measuring latency, and
+/// scores each labeled query's results.
+pub fn run(
+    queries: &[String],
+    labels: &[LabeledQuery],
+    iterations: usize,
+    db: &Database,
+    cache: &Cache,
+    index: &SearchIndex,
+) -> anyhow::Result<Report> {
+    let mut samples = Vec::with_capacity(queries.len() * iterations);
+    let mut medians = Vec::with_capacity(queries.len());
+    for q in queries {
+        let (terms, filters) = Filters::parse(q);
+        let mut query_samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            query(&terms, &filters, db, cache, index)?;
+            query_samples.push(start.elapsed());
+        }
+        query_samples.sort_unstable();
+        medians.push((q.clone(), percentile(&query_samples, 50)));
+        samples.extend(query_samples);
+    }
+    samples.sort_unstable();
+    medians.sort_by(|a, b| b.1.cmp(&a.1));
+    medians.truncate(5);
+
+    let mut ndcg_by_query = Vec::with_capacity(labels.len());
+    for label in labels {
+        let (terms, filters) = Filters::parse(&label.q);
+        let results = query(&terms, &filters, db, cache, index)?.results;
+        let names = results
+            .iter()
+            .map(|result| result.result.name())
+            .collect::<Vec<_>>();
+        ndcg_by_query.push((label.q.clone(), ndcg(&names, &label.relevant)));
+    }
+    let mean_ndcg = (!ndcg_by_query.is_empty()).then(|| {
+        ndcg_by_query.iter().map(|(_, score)| score).sum::<f32>() / ndcg_by_query.len() as f32
+    });
+
+    Ok(Report {
+        samples: samples.len(),
+        p50: percentile(&samples, 50),
+        p99: percentile(&samples, 99),
+        max: samples.last().copied().unwrap_or_default(),
+        slowest: medians,
+        mean_ndcg,
+        ndcg_by_query,
+    })
+}
+
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = (sorted.len() * percentile / 100).min(sorted.len() - 1);
+    sorted[index]
+}
+
+/// Computes the normalized discounted cumulative gain of the first
+/// [`NDCG_DEPTH`] `results`.
+pub fn ndcg(results: &[&str], relevant: &HashMap<String, u8>) -> f32 {
+    fn dcg(grades: impl Iterator<Item = u8>) -> f32 {
+        grades
+            .take(NDCG_DEPTH)
+            .enumerate()
+            .map(|(rank, grade)| (2_f32.powi(i32::from(grade)) - 1.) / (rank as f32 + 2.).log2())
+            .sum()
+    }
+
+    let mut ideal = relevant.values().copied().collect::<Vec<_>>();
+    ideal.sort_unstable_by(|a, b| b.cmp(a));
+    let ideal = dcg(ideal.into_iter());
+    if ideal == 0. {
+        return 0.;
+    }
+
+    dcg(results
+        .iter()
+        .map(|name| relevant.get(*name).copied().unwrap_or(0)))
+        / ideal
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} samples", self.samples)?;
+        writeln!(f, "p50: {}us", self.p50.as_micros())?;
+        writeln!(f, "p99: {}us", self.p99.as_micros())?;
+        writeln!(f, "max: {}us", self.max.as_micros())?;
+        writeln!(f, "slowest queries (median):")?;
+        for (q, median) in &self.slowest {
+            writeln!(f, "  {}us  {q}", median.as_micros())?;
+        }
+        if let Some(mean_ndcg) = self.mean_ndcg {
+            writeln!(f, "NDCG@{NDCG_DEPTH}: {mean_ndcg:.3}")?;
+            for (q, score) in &self.ndcg_by_query {
+                writeln!(f, "  {score:.3}  {q}")?;
+            }
+        }
+        Ok(())
+    }
+}
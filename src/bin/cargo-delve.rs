@@ -0,0 +1,163 @@
+//! A cargo subcommand for querying a delve-rs server.
+//!
+//! ```text
+//! cargo delve [--server <url>] search <query>...
+//! cargo delve [--server <url>] info <crate>
+//! cargo delve [--server <url>] audit [path/to/Cargo.lock]
+//! ```
+//!
+//! The server defaults to `$DELVE_RS_SERVER`, falling back to a local
+//! instance.
+
+use delve_rs::client::{Client, LockedPackage};
+use serde::Deserialize;
+
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).peekable();
+    // When invoked as `cargo delve`, cargo passes "delve" as the first argument.
+    if args.peek().map_or(false, |arg| arg == "delve") {
+        args.next();
+    }
+
+    let mut server =
+        std::env::var("DELVE_RS_SERVER").unwrap_or_else(|_| DEFAULT_SERVER.to_string());
+    if args.peek().map_or(false, |arg| arg == "--server") {
+        args.next();
+        server = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("--server requires a url"))?;
+    }
+    let client = Client::new(&server);
+
+    match args.next().as_deref() {
+        Some("search") => {
+            let query = args.collect::<Vec<_>>().join(" ");
+            if query.is_empty() {
+                anyhow::bail!("usage: cargo delve search <query>");
+            }
+            search(&client, &query).await
+        }
+        Some("info") => {
+            let name = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("usage: cargo delve info <crate>"))?;
+            info(&client, &name).await
+        }
+        Some("audit") => {
+            let lockfile = args.next().unwrap_or_else(|| String::from("Cargo.lock"));
+            audit(&client, &lockfile).await
+        }
+        _ => {
+            anyhow::bail!("usage: cargo delve [--server <url>] <search|info|audit> [args]")
+        }
+    }
+}
+
+async fn search(client: &Client, query: &str) -> anyhow::Result<()> {
+    let results = client.search(query, None).await?.items;
+
+    if results.is_empty() {
+        println!("No crates found for \"{query}\".");
+    }
+    for result in results {
+        println!("{} ({} downloads)", result.name, result.downloads);
+        if !result.description.is_empty() {
+            println!("    {}", result.description.trim());
+        }
+    }
+
+    Ok(())
+}
+
+async fn info(client: &Client, name: &str) -> anyhow::Result<()> {
+    let info = client
+        .crate_info(name)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("crate not found: {name}"))?;
+
+    println!("{}", info.name);
+    if !info.description.is_empty() {
+        println!("{}", info.description.trim());
+    }
+    println!();
+    for (label, value) in [
+        ("homepage", &info.homepage),
+        ("documentation", &info.documentation),
+        ("repository", &info.repository),
+    ] {
+        if !value.is_empty() {
+            println!("{label}: {value}");
+        }
+    }
+    println!(
+        "downloads: {} ({} recent)",
+        info.downloads, info.recent_downloads
+    );
+    if let Some(latest) = info
+        .versions
+        .iter()
+        .filter(|v| !v.yanked)
+        .filter_map(|v| semver::Version::parse(&v.version).ok())
+        .max()
+    {
+        println!("latest version: {latest}");
+    }
+    for advisory in &info.advisories {
+        println!("advisory {}: {}", advisory.id, advisory.title);
+    }
+
+    Ok(())
+}
+
+async fn audit(client: &Client, lockfile: &str) -> anyhow::Result<()> {
+    let lockfile: Lockfile = toml::from_str(&std::fs::read_to_string(lockfile)?)?;
+    let packages = lockfile
+        .package
+        .into_iter()
+        .filter(|package| {
+            package
+                .source
+                .as_deref()
+                .map_or(false, |source| source.starts_with("registry+"))
+        })
+        .map(|package| LockedPackage {
+            name: package.name,
+            version: package.version,
+        })
+        .collect::<Vec<_>>();
+    let findings = client.audit(&packages).await?;
+
+    if findings.is_empty() {
+        println!("No issues found in {} packages.", packages.len());
+        return Ok(());
+    }
+
+    for finding in &findings {
+        if finding.yanked {
+            println!("{} {}: version is yanked", finding.name, finding.version);
+        }
+        for advisory in &finding.advisories {
+            println!(
+                "{} {}: {} {}",
+                finding.name, finding.version, advisory.id, advisory.title
+            );
+        }
+    }
+    anyhow::bail!("{} packages have issues", findings.len())
+}
+
+#[derive(Deserialize, Debug)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockfilePackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LockfilePackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    dump,
+    schema::{Advisory, BlockedCrate, Crate, CRATES_IO},
+};
+
+/// Returns the key of the block on the crate named `name` from `registry`.
+/// Crates.io crates are keyed by their normalized name, and other registries'
+/// crates by the registry's name and theirs, so that blocking a crate doesn't
+/// block a crate of the same name from another registry.
+pub(crate) fn key(registry: &str, name: &str) -> String {
+    let name = Crate::normalized_name(name);
+    if registry == CRATES_IO {
+        name
+    } else {
+        format!("{}/{name}", registry.to_ascii_lowercase())
+    }
+}
+
+/// Blocks the crates that open RustSec advisories report as malicious, and
+/// unblocks the crates whose advisories were withdrawn. Crates blocked by an
+/// administrator are left as they are. Advisories only block crates.io
+/// crates.
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    let mut malicious = HashMap::new();
+    for advisory in Advisory::all(db).query()? {
+        if advisory.contents.is_malicious() {
+            malicious.insert(
+                key(CRATES_IO, &advisory.contents.crate_name),
+                (advisory.header.id, advisory.contents.title),
+            );
+        }
+    }
+
+    let mut existing = BlockedCrate::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id.clone(), d))
+        .collect::<HashMap<_, _>>();
+    let now = dump::timestamp(OffsetDateTime::now_utc());
+    let mut tx = Transaction::new();
+    for (name, (advisory, title)) in malicious {
+        if existing.remove(&name).is_some() {
+            continue;
+        }
+        tx.operations
+            .push(Operation::insert_serialized::<BlockedCrate>(
+                Some(&name),
+                &BlockedCrate {
+                    reason: title,
+                    advisory: Some(advisory),
+                    blocked_at: now.clone(),
+                },
+            )?);
+    }
+    for unflagged in existing
+        .into_values()
+        .filter(|blocked| blocked.contents.advisory.is_some())
+    {
+        tx.operations.push(Operation::delete(
+            BlockedCrate::collection_name(),
+            Header::try_from(unflagged.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        println!("Updating {} blocklist entries", tx.operations.len());
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert_eq!(key(CRATES_IO, "Evil-Crate"), "evil_crate");
+        assert_eq!(key("Internal", "Evil-Crate"), "internal/evil_crate");
+    }
+
+    fn malicious(crate_name: &str) -> Advisory {
+        Advisory {
+            crate_name: crate_name.to_string(),
+            title: format!("{crate_name} is malware"),
+            date: String::from("2023-03-20"),
+            url: None,
+            informational: Some(String::from("malicious")),
+            withdrawn: None,
+            patched: Vec::new(),
+            unaffected: Vec::new(),
+            categories: Vec::new(),
+        }
+    }
+
+    fn blocked(db: &Database) -> Vec<(String, Option<String>)> {
+        let mut blocked = BlockedCrate::all(db)
+            .query()
+            .unwrap()
+            .into_iter()
+            .map(|blocked| (blocked.header.id, blocked.contents.advisory))
+            .collect::<Vec<_>>();
+        blocked.sort();
+        blocked
+    }
+
+    #[test]
+    fn advisories_block_crates() {
+        let (_dir, db) = crate::test_database();
+        malicious("Evil-Crate")
+            .insert_into(&String::from("RUSTSEC-2023-0001"), &db)
+            .unwrap();
+        malicious("withdrawn")
+            .insert_into(&String::from("RUSTSEC-2023-0002"), &db)
+            .unwrap();
+        BlockedCrate {
+            reason: String::from("reported"),
+            advisory: None,
+            blocked_at: String::from("2023-03-20 00:00:00"),
+        }
+        .insert_into(&key("internal", "sneaky"), &db)
+        .unwrap();
+
+        update(&db).unwrap();
+        assert_eq!(
+            blocked(&db),
+            [
+                (
+                    String::from("evil_crate"),
+                    Some(String::from("RUSTSEC-2023-0001"))
+                ),
+                (String::from("internal/sneaky"), None),
+                (
+                    String::from("withdrawn"),
+                    Some(String::from("RUSTSEC-2023-0002"))
+                ),
+            ]
+        );
+
+        // Withdrawing an advisory unblocks its crate, but administrators'
+        // blocks stay.
+        let mut advisory = Advisory::get(&String::from("RUSTSEC-2023-0002"), &db)
+            .unwrap()
+            .unwrap();
+        advisory.contents.withdrawn = Some(String::from("2023-03-21"));
+        advisory.update(&db).unwrap();
+        update(&db).unwrap();
+        assert_eq!(
+            blocked(&db),
+            [
+                (
+                    String::from("evil_crate"),
+                    Some(String::from("RUSTSEC-2023-0001"))
+                ),
+                (String::from("internal/sneaky"), None),
+            ]
+        );
+    }
+}
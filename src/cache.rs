@@ -1,10 +1,42 @@
-use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock, RwLockReadGuard, Weak};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, Weak};
 
-use bonsaidb::core::schema::SerializedView;
+use bonsaidb::core::schema::{SerializedCollection, SerializedView};
 use bonsaidb::local::Database;
+use time::Date;
 
-use crate::schema::{CalendarDate, CratesByNormalizedName, DownloadsByDate};
+use crate::analytics;
+use crate::blocklist;
+use crate::categories::{self, CategoryEntry};
+use crate::dashboard::Dashboard;
+use crate::dependency_graph;
+use crate::dump;
+use crate::embeddings::SemanticIndex;
+use crate::forks;
+use crate::name_index::NameIndex;
+use crate::schema::{
+    Advisory, AliasSource, BlockedCrate, CalendarDate, Crate, CrateAlias, CrateProfile,
+    CrateSuccessor, CratesByNormalizedName, Curation, CurationAction, Dependency,
+    DependencyMetrics, DownloadsByDate, Keyword, PublishWave, ReadmeDuplicate, ReviewsByCrate,
+    RustVersion, UnsafeIndicator, UnsafeUsage, VersionsByCrate, CRATES_IO,
+};
+use crate::spelling::Spelling;
+use crate::strings::{Span, StringArena};
+use crate::SearchIndex;
+use crate::{placeholder, unmaintained};
+
+/// The fewest crates whose descriptions and READMEs must use a word for it to
+/// be in the spelling dictionary.
+const MIN_SPELLING_TERM_CRATES: u32 = 20;
+
+/// The fewest crates that must use a keyword for it to be in the spelling
+/// dictionary, since keywords used once are often misspelled themselves.
+const MIN_SPELLING_KEYWORD_CRATES: u64 = 2;
+
+/// The most versions whose dependencies are kept by
+/// [`Cache::required_dependencies`] until the next refresh.
+const MAX_CACHED_DEPENDENCIES: usize = 20_000;
 
 #[derive(Debug, Clone)]
 pub struct Cache {
@@ -13,15 +45,22 @@ pub struct Cache {
 }
 
 impl Cache {
-    pub fn new(database: Database) -> anyhow::Result<Self> {
+    /// Returns a cache of `database`, which refreshes in the background.
+    /// `index` is only read for the spelling dictionary.
+    pub fn new(database: Database, index: SearchIndex) -> anyhow::Result<Self> {
         let (sender, receiver) = flume::unbounded();
         sender.send(Command::Refresh)?;
         let cache = Self {
             thread: sender,
             data: Arc::new(Data {
                 database,
+                index,
                 crates: RwLock::default(),
-                crates_by_name: RwLock::default(),
+                names: RwLock::default(),
+                categories: RwLock::default(),
+                dashboard: RwLock::default(),
+                spelling: RwLock::default(),
+                semantic: RwLock::default(),
             }),
         };
 
@@ -37,26 +76,102 @@ impl Cache {
         Ok(self.thread.send(Command::Refresh)?)
     }
 
-    pub fn crates(&self) -> anyhow::Result<RwLockReadGuard<'_, HashMap<u64, CachedCrate>>> {
+    /// Refreshes the cache on the current thread, returning once the cache
+    /// reflects the current database contents.
+    pub fn refresh_blocking(&self) -> anyhow::Result<()> {
+        self.data.refresh_crates()
+    }
+
+    /// Returns a snapshot of the cached crates. The snapshot isn't affected by
+    /// later refreshes.
+    pub fn crates(&self) -> anyhow::Result<Arc<CrateTable>> {
         self.data
             .crates
             .read()
+            .map(|crates| crates.clone())
             .map_err(|_| anyhow::anyhow!("crates rwlock poisoned"))
     }
 
-    pub fn crates_by_name(&self) -> anyhow::Result<RwLockReadGuard<'_, HashMap<String, u64>>> {
+    /// Returns the required dependencies of `krate`'s latest version. They're
+    /// loaded from the database the first time they're needed, and kept until
+    /// the next refresh.
+    pub(crate) fn required_dependencies(
+        &self,
+        krate: &CachedCrate,
+    ) -> anyhow::Result<Arc<[Dependency]>> {
+        let Some(version_id) = krate.row().latest_version_id
+            else { return Ok(Arc::from(Vec::new())) };
+        let cached = &krate.table.dependencies;
+        if let Some(dependencies) = cached.lock().expect("poisoned").get(&version_id) {
+            return Ok(dependencies.clone());
+        }
+
+        let dependencies = Arc::<[Dependency]>::from(dependency_graph::required_dependencies(
+            &self.data.database,
+            version_id,
+        )?);
+        let mut cached = cached.lock().expect("poisoned");
+        if cached.len() < MAX_CACHED_DEPENDENCIES {
+            cached.insert(version_id, dependencies.clone());
+        }
+        Ok(dependencies)
+    }
+
+    pub fn names(&self) -> anyhow::Result<RwLockReadGuard<'_, NameIndex>> {
         self.data
-            .crates_by_name
+            .names
             .read()
-            .map_err(|_| anyhow::anyhow!("crates_by_name rwlock poisoned"))
+            .map_err(|_| anyhow::anyhow!("names rwlock poisoned"))
+    }
+
+    /// Returns the category tree, including the number of crates in each
+    /// category.
+    pub(crate) fn categories(&self) -> anyhow::Result<Arc<Vec<CategoryEntry>>> {
+        self.data
+            .categories
+            .read()
+            .map(|categories| categories.clone())
+            .map_err(|_| anyhow::anyhow!("categories rwlock poisoned"))
+    }
+
+    /// Returns the registry statistics shown on the homepage.
+    pub(crate) fn dashboard(&self) -> anyhow::Result<Arc<Dashboard>> {
+        self.data
+            .dashboard
+            .read()
+            .map(|dashboard| dashboard.clone())
+            .map_err(|_| anyhow::anyhow!("dashboard rwlock poisoned"))
+    }
+
+    /// Returns the dictionary used to correct misspelled search terms.
+    pub(crate) fn spelling(&self) -> anyhow::Result<Arc<Spelling>> {
+        self.data
+            .spelling
+            .read()
+            .map(|spelling| spelling.clone())
+            .map_err(|_| anyhow::anyhow!("spelling rwlock poisoned"))
+    }
+
+    /// Returns the index of crate embeddings used for semantic search.
+    pub(crate) fn semantic_index(&self) -> anyhow::Result<Arc<SemanticIndex>> {
+        self.data
+            .semantic
+            .read()
+            .map(|semantic| semantic.clone())
+            .map_err(|_| anyhow::anyhow!("semantic rwlock poisoned"))
     }
 }
 
 #[derive(Debug)]
 struct Data {
     database: Database,
-    crates: RwLock<HashMap<u64, CachedCrate>>,
-    crates_by_name: RwLock<HashMap<String, u64>>,
+    index: SearchIndex,
+    crates: RwLock<Arc<CrateTable>>,
+    names: RwLock<NameIndex>,
+    categories: RwLock<Arc<Vec<CategoryEntry>>>,
+    dashboard: RwLock<Arc<Dashboard>>,
+    spelling: RwLock<Arc<Spelling>>,
+    semantic: RwLock<Arc<SemanticIndex>>,
 }
 
 impl Data {
@@ -75,52 +190,729 @@ impl Data {
             *crate_downloads += mapping.value;
         }
 
-        let (crates, crates_by_name) = crates_by_name
+        // Count each crate's versions and find the latest non-yanked version,
+        // preferring stable releases over pre-releases.
+        let mut version_stats = HashMap::<u64, VersionStats>::with_capacity(crates_by_name.len());
+        for mapping in VersionsByCrate::entries(&self.database).query()? {
+            let stats = version_stats
+                .entry(mapping.key)
+                .or_insert_with(VersionStats::default);
+            stats.count += 1;
+            if !mapping.value.created_at.is_empty()
+                && stats
+                    .first_published
+                    .as_ref()
+                    .map_or(true, |first| first > &mapping.value.created_at)
+            {
+                stats.first_published = Some(mapping.value.created_at.clone());
+            }
+            if stats
+                .last_published
+                .as_ref()
+                .map_or(true, |last| last < &mapping.value.created_at)
+            {
+                stats.last_published = Some(mapping.value.created_at.clone());
+            }
+            if mapping.value.yanked {
+                continue;
+            }
+            let Ok(version) = semver::Version::parse(&mapping.value.version) else { continue };
+            let stable = version.pre.is_empty();
+            if stats.latest.as_ref().map_or(true, |latest| {
+                (stable, &version) > (latest.stable, &latest.version)
+            }) {
+                stats.latest = Some(LatestVersion {
+                    id: mapping.source.id.deserialize()?,
+                    stable,
+                    version,
+                    rust_version: mapping
+                        .value
+                        .rust_version
+                        .as_deref()
+                        .and_then(|v| v.parse().ok()),
+                });
+            }
+        }
+
+        let mut aliases = CrateAlias::all(&self.database)
+            .query()?
             .into_iter()
-            .map(|mapping| {
-                let id = mapping.source.id.deserialize().expect("invalid id");
-                let recent_downloads = recent_downloads_by_crate.get(&id).copied().unwrap_or(0);
-                (
-                    (
-                        id,
-                        CachedCrate {
-                            name: mapping.value.name,
-                            description: mapping.value.description,
-                            downloads: mapping.value.downloads,
-                            keywords: mapping.value.keywords,
-                            recent_downloads,
-                        },
-                    ),
-                    (mapping.key, id),
-                )
+            .map(|d| (d.header.id, d.contents))
+            .collect::<HashMap<_, _>>();
+
+        let mut dependency_metrics = DependencyMetrics::all(&self.database)
+            .query()?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect::<HashMap<_, _>>();
+
+        let mut profiles = CrateProfile::all(&self.database)
+            .query()?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents))
+            .collect::<HashMap<_, _>>();
+
+        let mut unsafe_usage = UnsafeUsage::all(&self.database)
+            .query()?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents.indicator()))
+            .collect::<HashMap<_, _>>();
+
+        let mut duplicates = ReadmeDuplicate::all(&self.database)
+            .query()?
+            .into_iter()
+            .map(|d| (d.header.id, d.contents.original))
+            .collect::<HashMap<_, _>>();
+
+        let mut reviews = HashMap::<String, ReviewCounts>::new();
+        for mapping in ReviewsByCrate::entries(&self.database).query()? {
+            let counts = reviews.entry(mapping.key).or_default();
+            counts.total += 1;
+            counts.favorable += u32::from(mapping.value);
+        }
+
+        let advisories = Advisory::all(&self.database).query()?;
+        // Crates with an open advisory saying they're unmaintained, by
+        // normalized name. Advisories only cover crates.io crates.
+        let unmaintained_advisories = advisories
+            .iter()
+            .filter(|advisory| {
+                advisory.contents.withdrawn.is_none()
+                    && advisory.contents.informational.as_deref() == Some("unmaintained")
             })
-            .unzip();
+            .map(|advisory| Crate::normalized_name(&advisory.contents.crate_name))
+            .collect::<HashSet<_>>();
+
+        // The keys of the blocked crates.
+        let blocked_keys = BlockedCrate::all(&self.database)
+            .query()?
+            .into_iter()
+            .map(|blocked| blocked.header.id)
+            .collect::<HashSet<_>>();
+
+        // The crates published in waves of near-identical crates.
+        let wave_crates = PublishWave::all(&self.database)
+            .query()?
+            .into_iter()
+            .flat_map(|wave| wave.contents.crate_ids)
+            .collect::<HashSet<_>>();
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let mut table = CrateTable::with_capacity(crates_by_name.len());
+        // The time of the refresh identifies the snapshot, including across
+        // restarts.
+        table.generation = time::OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        for keyword in Keyword::all(&self.database).query()? {
+            let name = table.text.push(&keyword.contents.keyword);
+            table.keyword_names.insert(keyword.header.id, name);
+            table
+                .keyword_ids
+                .insert(keyword.contents.keyword, keyword.header.id);
+        }
+
+        let mut names = Vec::with_capacity(crates_by_name.len());
+        for mapping in crates_by_name {
+            let id = mapping.source.id.deserialize()?;
+            let stats = version_stats.remove(&id).unwrap_or_default();
+            let latest = stats.latest.as_ref();
+            let likely_placeholder = placeholder::is_likely_placeholder(
+                &mapping.value,
+                stats.count,
+                latest.map(|latest| &latest.version),
+            );
+            let keywords_start = table.keywords.len();
+            table.keywords.extend(&mapping.value.keywords);
+            for keyword in &mapping.value.keywords {
+                table
+                    .crates_by_keyword
+                    .entry(*keyword)
+                    .or_default()
+                    .push(id);
+            }
+            let last_published = stats
+                .last_published
+                .as_deref()
+                .and_then(|timestamp| dump::parse_timestamp(timestamp).ok())
+                .map(|timestamp| timestamp.date());
+            let unmaintained = unmaintained::is_unmaintained(
+                last_published,
+                mapping.value.archive_notice,
+                mapping.value.registry.is_none() && unmaintained_advisories.contains(&mapping.key),
+                today,
+            );
+            let blocked = blocked_keys.contains(&blocklist::key(
+                mapping.value.registry.as_deref().unwrap_or(CRATES_IO),
+                &mapping.key,
+            ));
+            let deprecation = aliases.remove(&mapping.key).map(|alias| {
+                table.aliases.push(alias);
+                table.aliases.len() - 1
+            });
+
+            table.rows_by_id.insert(id, table.rows.len());
+            table.rows.push(Row {
+                id,
+                name: table.text.push(&mapping.value.name),
+                description: table.text.push(&mapping.value.description),
+                latest_version: latest.map(|latest| table.text.push(&latest.version.to_string())),
+                latest_version_id: latest.map(|latest| latest.id),
+                keywords: keywords_start..table.keywords.len(),
+                downloads: mapping.value.downloads,
+                recent_downloads: recent_downloads_by_crate.get(&id).copied().unwrap_or(0),
+                downloads_percentile: 0.,
+                recent_downloads_percentile: 0.,
+                rust_version: latest.and_then(|latest| latest.rust_version),
+                deprecation,
+                likely_placeholder,
+                unmaintained,
+                blocked,
+                in_publish_wave: wave_crates.contains(&id),
+                registry: mapping
+                    .value
+                    .registry
+                    .as_ref()
+                    .map(|registry| table.text.push(registry)),
+                language: mapping
+                    .value
+                    .language
+                    .as_ref()
+                    .map(|language| table.text.push(language)),
+                first_published: stats
+                    .first_published
+                    .as_deref()
+                    .and_then(|timestamp| dump::parse_timestamp(timestamp).ok())
+                    .map(|timestamp| timestamp.date()),
+                last_published,
+                dependencies: dependency_metrics.remove(&id),
+                profile: profiles.remove(&id).unwrap_or_default(),
+                unsafe_usage: unsafe_usage.remove(&id),
+                duplicate_of: duplicates.remove(&id),
+                reviews: reviews.remove(&mapping.key).unwrap_or_default(),
+            });
+            names.push((mapping.key, id));
+        }
 
+        // Detected forks are only suggested in place of crates that appear
+        // abandoned, and only if they've been released since and don't appear
+        // abandoned themselves.
+        let ids_by_name = names
+            .iter()
+            .map(|(name, id)| (name.as_str(), *id))
+            .collect::<HashMap<_, _>>();
+        for record in CrateSuccessor::all(&self.database).query()? {
+            let Some(&original) = ids_by_name.get(record.header.id.as_str()) else { continue };
+            let row = &table.rows[table.rows_by_id[&original]];
+            let curated = record.contents.source == AliasSource::Manual;
+            if !curated
+                && !forks::is_abandoned(
+                    row.last_published,
+                    row.unmaintained,
+                    row.deprecation.is_some(),
+                    today,
+                )
+            {
+                continue;
+            }
+            let successors = record
+                .contents
+                .successors
+                .iter()
+                .filter_map(|name| ids_by_name.get(Crate::normalized_name(name).as_str()))
+                .copied()
+                .filter(|id| {
+                    let successor = &table.rows[table.rows_by_id[id]];
+                    *id != original
+                        && (curated
+                            || (!successor.likely_placeholder
+                                && successor.last_published > row.last_published
+                                && !forks::is_abandoned(
+                                    successor.last_published,
+                                    successor.unmaintained,
+                                    successor.deprecation.is_some(),
+                                    today,
+                                )))
+                })
+                .collect::<Vec<_>>();
+            if !successors.is_empty() {
+                table.successors.insert(original, successors);
+            }
+        }
+
+        // Curations are listed in the order they were made, which is the
+        // order pinned crates are listed in.
+        for curation in Curation::all(&self.database).query()? {
+            let name = Crate::normalized_name(&curation.contents.crate_name);
+            let Some(&id) = ids_by_name.get(name.as_str()) else { continue };
+            table
+                .curations
+                .entry(analytics::normalize_query(&curation.contents.query))
+                .or_default()
+                .push((id, curation.contents.action));
+        }
+
+        let downloads_percentiles = percentile_ranks(&table.rows, |row| row.downloads);
+        let recent_downloads_percentiles =
+            percentile_ranks(&table.rows, |row| row.recent_downloads);
+        for (row, (downloads, recent_downloads)) in table.rows.iter_mut().zip(
+            downloads_percentiles
+                .into_iter()
+                .zip(recent_downloads_percentiles),
+        ) {
+            row.downloads_percentile = downloads;
+            row.recent_downloads_percentile = recent_downloads;
+        }
+        table.text.shrink_to_fit();
+        let spelling = self.spelling_dictionary(&table)?;
+        let names = NameIndex::new(names);
+
+        let table = Arc::new(table);
         let mut cached_crates = self
             .crates
             .write()
             .map_err(|_| anyhow::anyhow!("crates rwlock poisoned"))?;
-        *cached_crates = crates;
+        *cached_crates = table.clone();
         drop(cached_crates);
 
-        let mut cached_crates = self
-            .crates_by_name
+        let mut cached_names = self
+            .names
             .write()
-            .map_err(|_| anyhow::anyhow!("crates_by_name rwlock poisoned"))?;
-        *cached_crates = crates_by_name;
-        drop(cached_crates);
+            .map_err(|_| anyhow::anyhow!("names rwlock poisoned"))?;
+        *cached_names = names;
+        drop(cached_names);
+
+        let categories = categories::tree(&self.database)?;
+        let dashboard = Dashboard::new(&table, &categories, &advisories, today);
+        let mut cached_categories = self
+            .categories
+            .write()
+            .map_err(|_| anyhow::anyhow!("categories rwlock poisoned"))?;
+        *cached_categories = Arc::new(categories);
+        drop(cached_categories);
+
+        let mut cached_dashboard = self
+            .dashboard
+            .write()
+            .map_err(|_| anyhow::anyhow!("dashboard rwlock poisoned"))?;
+        *cached_dashboard = Arc::new(dashboard);
+        drop(cached_dashboard);
+
+        let mut cached_spelling = self
+            .spelling
+            .write()
+            .map_err(|_| anyhow::anyhow!("spelling rwlock poisoned"))?;
+        *cached_spelling = Arc::new(spelling);
+        drop(cached_spelling);
+
+        let previous_semantic = self
+            .semantic
+            .read()
+            .map(|semantic| semantic.clone())
+            .map_err(|_| anyhow::anyhow!("semantic rwlock poisoned"))?;
+        let semantic = SemanticIndex::build(&self.database, &previous_semantic)?;
+        drop(previous_semantic);
+        let mut cached_semantic = self
+            .semantic
+            .write()
+            .map_err(|_| anyhow::anyhow!("semantic rwlock poisoned"))?;
+        *cached_semantic = semantic;
+        drop(cached_semantic);
 
         Ok(())
     }
+
+    /// Builds the spelling dictionary from the names and keywords of the
+    /// crates in `table`, and the words many crates' descriptions and READMEs
+    /// use. Names are more common the more descriptions and READMEs mention
+    /// them, which favors popular crates when correcting a misspelled name.
+    fn spelling_dictionary(&self, table: &CrateTable) -> anyhow::Result<Spelling> {
+        let terms = self.index.common_terms(MIN_SPELLING_TERM_CRATES)?;
+        let mentions = |word: &str| terms.get(&word.to_lowercase()).copied().unwrap_or(0);
+
+        let mut keyword_crates = HashMap::<u64, u64>::new();
+        for keyword in &table.keywords {
+            *keyword_crates.entry(*keyword).or_default() += 1;
+        }
+        let names = table.rows.iter().map(|row| {
+            let name = table.text.get(row.name);
+            (name.to_string(), 1 + mentions(name))
+        });
+        let keywords = keyword_crates
+            .into_iter()
+            .filter(|(_, crates)| *crates >= MIN_SPELLING_KEYWORD_CRATES)
+            .filter_map(|(id, crates)| table.keyword(id).map(|name| (name, crates)))
+            .map(|(name, crates)| (name.to_string(), crates + mentions(name)));
+        let mut words = names.chain(keywords).collect::<Vec<_>>();
+        words.extend(terms);
+        Ok(Spelling::new(words))
+    }
+}
+
+/// Every cached crate, stored compactly. Strings are packed into a single
+/// buffer and keywords into a single list, so refreshing the cache makes a
+/// handful of large allocations instead of several per crate.
+#[derive(Debug, Default)]
+pub struct CrateTable {
+    generation: u64,
+    text: StringArena,
+    keywords: Vec<u64>,
+    keyword_names: HashMap<u64, Span>,
+    /// Sorted, so that keywords can be looked up by prefix.
+    keyword_ids: BTreeMap<String, u64>,
+    /// The ids of the crates with each keyword, keyed by keyword id.
+    crates_by_keyword: HashMap<u64, Vec<u64>>,
+    aliases: Vec<CrateAlias>,
+    /// The ids of the crates suggested in place of each abandoned crate,
+    /// keyed by the abandoned crate's id.
+    successors: HashMap<u64, Vec<u64>>,
+    /// The crates curated for each search, keyed by the normalized search
+    /// terms.
+    curations: HashMap<String, Vec<(u64, CurationAction)>>,
+    rows: Vec<Row>,
+    rows_by_id: HashMap<u64, usize>,
+    /// The required dependencies of the latest versions loaded so far, keyed
+    /// by version id.
+    dependencies: Mutex<HashMap<u64, Arc<[Dependency]>>>,
+}
+
+impl CrateTable {
+    fn with_capacity(crates: usize) -> Self {
+        Self {
+            rows: Vec::with_capacity(crates),
+            rows_by_id: HashMap::with_capacity(crates),
+            ..Self::default()
+        }
+    }
+
+    /// Identifies this snapshot of the cache. Each refresh produces a new
+    /// generation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns a handle to the crate with `id`, if it is cached.
+    pub fn get(self: &Arc<Self>, id: u64) -> Option<CachedCrate> {
+        self.rows_by_id.get(&id).map(|row| CachedCrate {
+            table: self.clone(),
+            row: *row,
+        })
+    }
+
+    /// Returns the crates curated for the search `terms`, in the order the
+    /// curations were made.
+    pub fn curations(&self, terms: &str) -> &[(u64, CurationAction)] {
+        if self.curations.is_empty() {
+            return &[];
+        }
+        self.curations
+            .get(&analytics::normalize_query(terms))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the name of the keyword with `id`.
+    pub fn keyword(&self, id: u64) -> Option<&str> {
+        self.keyword_names.get(&id).map(|name| self.text.get(*name))
+    }
+
+    /// Returns the id of the keyword named `name`.
+    pub fn keyword_id(&self, name: &str) -> Option<u64> {
+        self.keyword_ids.get(name).copied()
+    }
+
+    /// Returns the names and ids of the keywords starting with `prefix`, in
+    /// alphabetical order.
+    pub fn keywords_with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, u64)> + 'a {
+        self.keyword_ids
+            .range::<str, _>(prefix..)
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .map(|(name, id)| (name.as_str(), *id))
+    }
+
+    /// Returns the ids of the crates with the keyword `id`.
+    pub fn crates_with_keyword(&self, id: u64) -> &[u64] {
+        self.crates_by_keyword.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The number of cached crates.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns a handle to every cached crate.
+    pub fn iter(self: &Arc<Self>) -> impl Iterator<Item = CachedCrate> + '_ {
+        (0..self.rows.len()).map(|row| CachedCrate {
+            table: self.clone(),
+            row,
+        })
+    }
 }
 
+#[derive(Debug)]
+struct Row {
+    id: u64,
+    name: Span,
+    description: Span,
+    latest_version: Option<Span>,
+    latest_version_id: Option<u64>,
+    keywords: Range<usize>,
+    downloads: u64,
+    recent_downloads: u64,
+    downloads_percentile: f32,
+    recent_downloads_percentile: f32,
+    rust_version: Option<RustVersion>,
+    deprecation: Option<usize>,
+    likely_placeholder: bool,
+    /// True if the crate hasn't been released in years and appears to be
+    /// abandoned.
+    unmaintained: bool,
+    /// True if the crate is on the blocklist.
+    blocked: bool,
+    /// True if the crate was published in a wave of near-identical crates.
+    in_publish_wave: bool,
+    /// The registry the crate was imported from, or None for crates.io.
+    registry: Option<Span>,
+    language: Option<Span>,
+    first_published: Option<Date>,
+    last_published: Option<Date>,
+    dependencies: Option<DependencyMetrics>,
+    profile: CrateProfile,
+    unsafe_usage: Option<UnsafeIndicator>,
+    /// The crate whose README this crate's README appears to be copied from.
+    duplicate_of: Option<u64>,
+    reviews: ReviewCounts,
+}
+
+/// The number of community reviews of any version of a crate.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ReviewCounts {
+    pub total: u32,
+    /// Reviews that certify the crate, which excludes crev reviews without a
+    /// positive rating.
+    pub favorable: u32,
+}
+
+/// A handle to a crate in a [`CrateTable`]. Cloning a handle doesn't copy any
+/// of the crate's data.
 #[derive(Debug, Clone)]
 pub struct CachedCrate {
-    pub name: String,
-    pub description: String,
-    pub keywords: HashSet<u64>,
-    pub downloads: u64,
-    pub recent_downloads: u64,
+    table: Arc<CrateTable>,
+    row: usize,
+}
+
+impl CachedCrate {
+    fn row(&self) -> &Row {
+        &self.table.rows[self.row]
+    }
+
+    pub fn id(&self) -> u64 {
+        self.row().id
+    }
+
+    pub fn name(&self) -> &str {
+        self.table.text.get(self.row().name)
+    }
+
+    pub fn description(&self) -> &str {
+        self.table.text.get(self.row().description)
+    }
+
+    pub fn keywords(&self) -> &[u64] {
+        &self.table.keywords[self.row().keywords.clone()]
+    }
+
+    /// The names of the crate's keywords, in alphabetical order.
+    pub fn keyword_names(&self) -> Vec<&str> {
+        let mut names = self
+            .keywords()
+            .iter()
+            .filter_map(|id| self.table.keyword(*id))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Returns true if the crate has the keyword named `name`.
+    pub fn has_keyword(&self, name: &str) -> bool {
+        self.table
+            .keyword_id(name)
+            .map_or(false, |id| self.keywords().contains(&id))
+    }
+
+    pub fn downloads(&self) -> u64 {
+        self.row().downloads
+    }
+
+    pub fn recent_downloads(&self) -> u64 {
+        self.row().recent_downloads
+    }
+
+    /// The fraction of all crates with fewer all-time downloads than this one.
+    pub fn downloads_percentile(&self) -> f32 {
+        self.row().downloads_percentile
+    }
+
+    /// The fraction of all crates with fewer downloads in the last 30 days
+    /// than this one.
+    pub fn recent_downloads_percentile(&self) -> f32 {
+        self.row().recent_downloads_percentile
+    }
+
+    pub fn latest_version(&self) -> Option<&str> {
+        self.row()
+            .latest_version
+            .map(|version| self.table.text.get(version))
+    }
+
+    pub fn rust_version(&self) -> Option<RustVersion> {
+        self.row().rust_version
+    }
+
+    pub fn deprecation(&self) -> Option<&CrateAlias> {
+        self.row()
+            .deprecation
+            .map(|alias| &self.table.aliases[alias])
+    }
+
+    pub fn likely_placeholder(&self) -> bool {
+        self.row().likely_placeholder
+    }
+
+    /// True if the crate has been flagged as malware, by a RustSec advisory
+    /// or an administrator.
+    pub fn blocked(&self) -> bool {
+        self.row().blocked
+    }
+
+    /// True if the crate was published in a wave of near-identical crates,
+    /// such as hundreds of name reservations, which demotes it.
+    pub fn in_publish_wave(&self) -> bool {
+        self.row().in_publish_wave
+    }
+
+    /// The crates suggested in place of this crate, such as its maintained
+    /// forks. Detected forks are only suggested while this crate appears
+    /// abandoned.
+    pub fn successors(&self) -> Vec<CachedCrate> {
+        self.table
+            .successors
+            .get(&self.row().id)
+            .map_or_else(Vec::new, |ids| {
+                ids.iter().filter_map(|id| self.table.get(*id)).collect()
+            })
+    }
+
+    /// True if the crate hasn't been released in over two years and either
+    /// its repository is archived or an open advisory says it's unmaintained.
+    pub fn unmaintained(&self) -> bool {
+        self.row().unmaintained
+    }
+
+    /// The date the crate's first version was published.
+    pub fn first_published(&self) -> Option<Date> {
+        self.row().first_published
+    }
+
+    /// The date the crate's most recent version was published.
+    pub fn last_published(&self) -> Option<Date> {
+        self.row().last_published
+    }
+
+    /// The size of the dependency tree of the crate's latest version, if it
+    /// has been measured.
+    pub fn dependencies(&self) -> Option<DependencyMetrics> {
+        self.row().dependencies
+    }
+
+    /// What the crate's latest version supports. Crates that haven't been
+    /// profiled yet have the default profile.
+    pub fn profile(&self) -> CrateProfile {
+        self.row().profile
+    }
+
+    /// How much unsafe code the crate contains, if a version of it has been
+    /// scanned.
+    pub fn unsafe_usage(&self) -> Option<UnsafeIndicator> {
+        self.row().unsafe_usage
+    }
+
+    /// The id of the crate whose README this crate's README appears to be
+    /// copied from, if any.
+    pub fn duplicate_of(&self) -> Option<u64> {
+        self.row().duplicate_of
+    }
+
+    pub fn reviews(&self) -> ReviewCounts {
+        self.row().reviews
+    }
+
+    /// True if a cargo-vet audit or a positive crev review certifies a
+    /// version of the crate.
+    pub fn audited(&self) -> bool {
+        self.row().reviews.favorable > 0
+    }
+
+    /// The name of the registry the crate was imported from.
+    pub fn registry(&self) -> &str {
+        self.row()
+            .registry
+            .map_or(CRATES_IO, |registry| self.table.text.get(registry))
+    }
+
+    /// The ISO 639-1 code of the language the crate is documented in, if it
+    /// was detected.
+    pub fn language(&self) -> Option<&str> {
+        self.row()
+            .language
+            .map(|language| self.table.text.get(language))
+    }
+}
+
+/// Returns the fraction of the other rows that have a lower `value` than each
+/// row, in the same order as `rows`.
+fn percentile_ranks(rows: &[Row], value: impl Fn(&Row) -> u64) -> Vec<f32> {
+    let mut values = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| (value(row), index))
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+
+    let others = values.len().saturating_sub(1).max(1) as f32;
+    let mut ranks = vec![0.; values.len()];
+    let mut lower = 0;
+    for (rank, (value, index)) in values.iter().enumerate() {
+        // Rows with the same value share the rank of the first of them.
+        if rank > 0 && values[rank - 1].0 < *value {
+            lower = rank;
+        }
+        ranks[*index] = lower as f32 / others;
+    }
+    ranks
+}
+
+#[derive(Default)]
+struct VersionStats {
+    count: usize,
+    latest: Option<LatestVersion>,
+    /// The timestamp of the first published version, including yanked
+    /// versions.
+    first_published: Option<String>,
+    /// The timestamp of the most recently published version, including yanked
+    /// versions.
+    last_published: Option<String>,
+}
+
+struct LatestVersion {
+    id: u64,
+    stable: bool,
+    version: semver::Version,
+    rust_version: Option<RustVersion>,
 }
 
 enum Command {
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
+use bonsaidb::{
+    core::{
+        document::CollectionDocument,
+        schema::{SerializedCollection, SerializedView},
+    },
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::schema::{Category, CratesByCategory};
+
+/// A category as listed on the categories page, in the order of a depth-first
+/// walk of the category tree.
+#[derive(Serialize, Debug)]
+pub(crate) struct CategoryEntry {
+    pub slug: String,
+    /// The last segment of the category's name, such as `Robotics` for
+    /// `Science::Robotics`.
+    pub name: String,
+    pub description: String,
+    /// The number of crates in this category, not counting its subcategories.
+    pub crates: u64,
+    pub depth: usize,
+    /// True if the next entry is this category's first child.
+    pub has_children: bool,
+    /// The number of ancestor levels that end after this entry.
+    pub closes: usize,
+}
+
+/// Returns every category in a depth-first walk of the category tree, with
+/// siblings sorted by name.
+pub(crate) fn tree(db: &Database) -> anyhow::Result<Vec<CategoryEntry>> {
+    let categories = Category::all(db).query()?;
+    let counts = CratesByCategory::entries(db)
+        .reduce_grouped()?
+        .into_iter()
+        .map(|mapping| (mapping.key, mapping.value))
+        .collect::<HashMap<_, _>>();
+    let mut children = HashMap::<Option<u64>, Vec<&CollectionDocument<Category>>>::new();
+    for category in &categories {
+        // Categories whose parent is missing are shown at the top level.
+        let parent = category
+            .contents
+            .parent_id
+            .filter(|parent| categories.iter().any(|other| other.header.id == *parent));
+        children.entry(parent).or_default().push(category);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.contents.category.cmp(&b.contents.category));
+    }
+
+    let mut entries = Vec::with_capacity(categories.len());
+    walk(
+        &children,
+        &counts,
+        None,
+        0,
+        &mut HashSet::new(),
+        &mut entries,
+    );
+
+    for index in 0..entries.len() {
+        let next_depth = entries.get(index + 1).map_or(0, |next| next.depth);
+        let entry = &mut entries[index];
+        entry.has_children = next_depth > entry.depth;
+        entry.closes = entry.depth.saturating_sub(next_depth);
+    }
+
+    Ok(entries)
+}
+
+fn walk(
+    children: &HashMap<Option<u64>, Vec<&CollectionDocument<Category>>>,
+    counts: &HashMap<u64, u64>,
+    parent: Option<u64>,
+    depth: usize,
+    visited: &mut HashSet<u64>,
+    entries: &mut Vec<CategoryEntry>,
+) {
+    let Some(siblings) = children.get(&parent) else { return };
+    for category in siblings {
+        // Guards against cycles in malformed paths.
+        if !visited.insert(category.header.id) {
+            continue;
+        }
+        entries.push(CategoryEntry {
+            slug: category.contents.slug.clone(),
+            name: category.contents.name().to_string(),
+            description: category.contents.description.clone(),
+            crates: counts.get(&category.header.id).copied().unwrap_or_default(),
+            depth,
+            has_children: false,
+            closes: 0,
+        });
+        walk(
+            children,
+            counts,
+            Some(category.header.id),
+            depth + 1,
+            visited,
+            entries,
+        );
+    }
+}
+
+/// Returns the ids of the categories whose slug is `slug`, along with the ids
+/// of all of their descendants.
+pub(crate) fn with_descendants(
+    categories: &[CollectionDocument<Category>],
+    slug: &str,
+) -> Vec<u64> {
+    let mut ids = categories
+        .iter()
+        .filter(|category| category.contents.slug.eq_ignore_ascii_case(slug))
+        .map(|category| category.header.id)
+        .collect::<Vec<_>>();
+    let mut included = ids.iter().copied().collect::<HashSet<_>>();
+    let mut index = 0;
+    while index < ids.len() {
+        let parent = ids[index];
+        for category in categories {
+            if category.contents.parent_id == Some(parent) && included.insert(category.header.id) {
+                ids.push(category.header.id);
+            }
+        }
+        index += 1;
+    }
+    ids
+}
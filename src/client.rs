@@ -0,0 +1,294 @@
+//! A typed client for the delve-rs JSON API.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! let client = delve_rs::client::Client::new("http://localhost:3000");
+//! let page = client.search("async runtime", None).await?;
+//! for result in page.items {
+//!     println!("{}: {}", result.name, result.description);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A connection to a delve-rs server.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    server: String,
+}
+
+impl Client {
+    /// Returns a client for the server at `server`, such as
+    /// `https://delve.rs`.
+    pub fn new(server: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server: server.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Searches for crates matching `query`, which can use the same syntax as
+    /// the search box. Pass a page's `next_cursor` to fetch the next page.
+    pub async fn search(
+        &self,
+        query: &str,
+        cursor: Option<&str>,
+    ) -> anyhow::Result<Page<SearchResult>> {
+        self.get(
+            "/api/v1/search/pages",
+            &[("q", Some(query)), ("cursor", cursor)],
+        )
+        .await
+    }
+
+    /// Returns the top 5 results of `query` with only their names,
+    /// descriptions, and latest versions, for completing a search as it's
+    /// typed.
+    pub async fn quick_search(&self, query: &str) -> anyhow::Result<QuickResults> {
+        self.get("/api/v1/quick", &[("q", Some(query))]).await
+    }
+
+    /// Returns information about the crate named `name`, or None if it
+    /// doesn't exist.
+    pub async fn crate_info(&self, name: &str) -> anyhow::Result<Option<CrateInfo>> {
+        self.get_optional(&format!("/api/v1/crates/{name}")).await
+    }
+
+    /// Returns the newest version of the crate named `name` that Cargo would
+    /// choose for the requirement `req`, such as `^1.2`, or None if the crate
+    /// doesn't exist or no version matches.
+    pub async fn resolve_version(
+        &self,
+        name: &str,
+        req: &str,
+    ) -> anyhow::Result<Option<VersionInfo>> {
+        let response = self
+            .http
+            .get(format!(
+                "{}/api/v1/crates/{name}/versions/resolve",
+                self.server
+            ))
+            .query(&[("req", req)])
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    /// Lists the crates that depend on the crate named `name`.
+    pub async fn dependents(
+        &self,
+        name: &str,
+        cursor: Option<&str>,
+    ) -> anyhow::Result<Page<Dependent>> {
+        self.get(
+            &format!("/api/v1/crates/{name}/dependents"),
+            &[("cursor", cursor)],
+        )
+        .await
+    }
+
+    /// Lists the crates in the category with `slug` and its subcategories.
+    pub async fn category_crates(
+        &self,
+        slug: &str,
+        cursor: Option<&str>,
+    ) -> anyhow::Result<Page<CategoryCrate>> {
+        self.get(
+            &format!("/api/v1/categories/{slug}/crates"),
+            &[("cursor", cursor)],
+        )
+        .await
+    }
+
+    /// Returns the keywords starting with `prefix`.
+    pub async fn suggest_keywords(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        self.get("/api/v1/keywords/suggest", &[("q", Some(prefix))])
+            .await
+    }
+
+    /// Checks `packages` for yanked versions and security advisories,
+    /// returning only the packages with issues.
+    pub async fn audit(&self, packages: &[LockedPackage]) -> anyhow::Result<Vec<AuditFinding>> {
+        Ok(self
+            .http
+            .post(format!("{}/api/v1/audit", self.server))
+            .json(&AuditRequest { packages })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, Option<&str>)],
+    ) -> anyhow::Result<T> {
+        let query = query
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| (*name, value)))
+            .collect::<Vec<_>>();
+        Ok(self
+            .http
+            .get(format!("{}{path}", self.server))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn get_optional<T: DeserializeOwned>(&self, path: &str) -> anyhow::Result<Option<T>> {
+        let response = self
+            .http
+            .get(format!("{}{path}", self.server))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+}
+
+/// One page of a paginated list.
+#[derive(Deserialize, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Passed to the same method to fetch the next page. None on the last
+    /// page.
+    pub next_cursor: Option<String>,
+    /// True if the server's data changed since the previous page.
+    pub stale: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuickResults {
+    pub items: Vec<QuickResult>,
+    /// True if the server ran out of time to rank the results fully.
+    pub partial: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuickResult {
+    pub name: String,
+    pub description: String,
+    /// The latest version.
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResult {
+    pub name: String,
+    pub description: String,
+    pub downloads: u64,
+    pub recent_downloads: u64,
+    pub likely_placeholder: bool,
+    pub unmaintained: bool,
+    pub registry: String,
+    pub first_published: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CrateInfo {
+    pub name: String,
+    pub description: String,
+    pub homepage: String,
+    pub documentation: String,
+    pub repository: String,
+    pub downloads: u64,
+    pub recent_downloads: u64,
+    pub latest_version: Option<String>,
+    pub rust_version: Option<String>,
+    pub likely_placeholder: bool,
+    pub unmaintained: bool,
+    pub versions: Vec<VersionSummary>,
+    pub advisories: Vec<AdvisorySummary>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    pub yanked: bool,
+    pub rust_version: Option<String>,
+    /// Each feature and the features and dependencies it enables.
+    pub features: BTreeMap<String, Vec<String>>,
+    /// The version's dependencies, grouped by kind.
+    pub dependencies: Vec<DependencyGroup>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DependencyGroup {
+    /// `Normal`, `Build`, or `Dev`.
+    pub kind: String,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Dependency {
+    pub name: String,
+    pub req: String,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    pub target: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VersionSummary {
+    pub version: String,
+    pub yanked: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AdvisorySummary {
+    pub id: String,
+    pub title: String,
+    pub date: String,
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Dependent {
+    pub name: String,
+    pub versions: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CategoryCrate {
+    pub name: String,
+    pub description: String,
+    pub downloads: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct AuditRequest<'a> {
+    packages: &'a [LockedPackage],
+}
+
+/// A package from a `Cargo.lock` to audit.
+#[derive(Serialize, Debug)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuditFinding {
+    pub name: String,
+    pub version: String,
+    pub yanked: bool,
+    pub advisories: Vec<AdvisorySummary>,
+}
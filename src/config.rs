@@ -0,0 +1,295 @@
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+    api_keys::ApiKeysConfig,
+    embeddings::EmbeddingsConfig,
+    maintenance::CompactionConfig,
+    ranking::{self, Recency},
+    replication::ReplicationConfig,
+    reviews::ReviewsConfig,
+    schema::{Registry, CRATES_IO},
+    search_backend::SearchConfig,
+    throttle::ThrottleConfig,
+};
+
+/// The configuration file used when `DELVE_RS_CONFIG` isn't set.
+const DEFAULT_PATH: &str = "delve-rs.toml";
+
+/// Settings loaded from the configuration file. Every setting is optional.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The address the web server listens on. Ignored when `listeners` isn't
+    /// empty.
+    pub listen: SocketAddr,
+    /// When set, the web server serves HTTPS instead of HTTP. Ignored when
+    /// `listeners` isn't empty.
+    pub tls: Option<TlsConfig>,
+    /// The sockets the web server listens on, each serving the public site,
+    /// the admin API, or both.
+    #[serde(rename = "listener")]
+    pub listeners: Vec<ListenerConfig>,
+    /// Where Cargo downloads `.crate` files from when using the sparse index
+    /// served at `/index/`. Air-gapped mirrors should point this at a local
+    /// copy of the crate files.
+    pub download_url: String,
+    /// Where the crates.io dump, the advisory database, and crev proof
+    /// repositories are downloaded and extracted.
+    pub data_dir: PathBuf,
+    /// Registries whose dumps are imported alongside crates.io's.
+    #[serde(rename = "registry")]
+    pub registries: Vec<RegistryConfig>,
+    /// A ranking experiment that splits search traffic between rankers.
+    pub experiment: Option<ExperimentConfig>,
+    /// The credentials that grant access to the admin API.
+    pub admin: AdminConfig,
+    /// How search results are adjusted by the age of each crate.
+    pub recency: Recency,
+    /// The URL the site is publicly served at, such as `https://delve.rs`.
+    /// Canonical links and link previews use relative URLs when unset.
+    pub public_url: Option<String>,
+    /// Signs the cookies that remember the crates each visitor viewed and
+    /// their preferences. When unset, a random secret is used until the
+    /// server restarts, which forgets every visitor's history and
+    /// preferences.
+    pub cookie_secret: Option<String>,
+    /// When the database is compacted in the background.
+    pub compaction: CompactionConfig,
+    /// cargo-geiger JSON reports imported after each import, each a URL, a
+    /// report file, or a directory of `.json` reports.
+    pub unsafe_reports: Vec<String>,
+    /// Where community reviews of crates are imported from.
+    pub reviews: ReviewsConfig,
+    /// How keys are required and limited on the public API.
+    pub api_keys: ApiKeysConfig,
+    /// Where crate and query embeddings are computed for semantic search.
+    pub embeddings: EmbeddingsConfig,
+    /// How many milliseconds scoring a search's results may take before the
+    /// search returns the search index's matches alone, marked as partial.
+    /// 0 places no limit on searches.
+    pub query_timeout_ms: u64,
+    /// Which search backend finds the crates whose text matches a search.
+    pub search: SearchConfig,
+    /// Where snapshots for read-only replicas are published.
+    pub replication: ReplicationConfig,
+    /// How imports are slowed down to leave disk I/O for searches.
+    pub import_throttle: ThrottleConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            tls: None,
+            listeners: Vec::new(),
+            download_url: String::from("https://static.crates.io/crates"),
+            data_dir: PathBuf::from("."),
+            registries: Vec::new(),
+            experiment: None,
+            admin: AdminConfig::default(),
+            recency: Recency::default(),
+            public_url: None,
+            cookie_secret: None,
+            compaction: CompactionConfig::default(),
+            unsafe_reports: Vec::new(),
+            reviews: ReviewsConfig::default(),
+            api_keys: ApiKeysConfig::default(),
+            embeddings: EmbeddingsConfig::default(),
+            query_timeout_ms: 500,
+            search: SearchConfig::default(),
+            replication: ReplicationConfig::default(),
+            import_throttle: ThrottleConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// A PEM file containing the certificate chain.
+    pub certificate: PathBuf,
+    /// A PEM file containing the certificate's private key.
+    pub private_key: PathBuf,
+}
+
+/// Credentials for the admin API. Requests may authenticate with either the
+/// bearer token or HTTP basic authentication. The admin API is disabled when
+/// neither is configured.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Sent as `Authorization: Bearer <token>`. `DELVE_RS_ADMIN_TOKEN`
+    /// overrides this setting.
+    pub token: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl AdminConfig {
+    /// Returns the bearer token, preferring `DELVE_RS_ADMIN_TOKEN`.
+    pub fn token(&self) -> Option<String> {
+        std::env::var("DELVE_RS_ADMIN_TOKEN")
+            .ok()
+            .or_else(|| self.token.clone())
+            .filter(|token| !token.is_empty())
+    }
+}
+
+/// A socket the web server listens on.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub address: SocketAddr,
+    /// Whether the site and public API are served.
+    #[serde(default = "default_true")]
+    pub public: bool,
+    /// Whether the admin API is served.
+    #[serde(default)]
+    pub admin: bool,
+    /// When set, this listener serves HTTPS instead of HTTP.
+    pub tls: Option<TlsConfig>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// An alternate registry that publishes a dump in the same format as
+/// crates.io, such as a private registry.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// The name used to filter searches by this registry.
+    pub name: String,
+    /// A number from 1 to 65535 identifying this registry's records in the
+    /// database. It must not change once the registry has been imported.
+    pub id: u16,
+    /// The directory the registry's dump is extracted into, which contains the
+    /// dump's `data` directory.
+    pub dump_path: PathBuf,
+}
+
+impl RegistryConfig {
+    pub fn registry(&self) -> Registry {
+        Registry {
+            id: self.id,
+            name: self.name.clone(),
+        }
+    }
+}
+
+/// An A/B test comparing rankers on real searches. Each session is assigned
+/// one of the variants, and the results it's shown and clicks are recorded.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+    /// The name the experiment's events are recorded under. Changing the name
+    /// starts a new experiment.
+    pub name: String,
+    /// The names of the rankers being compared.
+    pub variants: Vec<String>,
+}
+
+impl Config {
+    /// Loads the file named by `DELVE_RS_CONFIG`, or `delve-rs.toml` if the
+    /// variable isn't set. If `delve-rs.toml` doesn't exist, the default
+    /// configuration is returned.
+    pub fn load() -> anyhow::Result<Self> {
+        match std::env::var_os("DELVE_RS_CONFIG") {
+            Some(path) => Self::load_from(path),
+            None if Path::new(DEFAULT_PATH).exists() => Self::load_from(DEFAULT_PATH),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Returns the listeners to serve. Without any `[[listener]]` tables, a
+    /// single listener on `listen` serves everything.
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        if self.listeners.is_empty() {
+            vec![ListenerConfig {
+                address: self.listen,
+                public: true,
+                admin: true,
+                tls: self.tls.clone(),
+            }]
+        } else {
+            self.listeners.clone()
+        }
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("error reading {}", path.display()))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("error parsing {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("invalid configuration in {}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match (&self.admin.username, &self.admin.password) {
+            (Some(username), Some(password)) if username.is_empty() || password.is_empty() => {
+                anyhow::bail!("the admin username and password must not be empty");
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                anyhow::bail!("the admin username and password must be set together");
+            }
+            _ => {}
+        }
+
+        let mut addresses = HashSet::new();
+        for listener in &self.listeners {
+            if !listener.public && !listener.admin {
+                anyhow::bail!("listener {} serves nothing", listener.address);
+            } else if !addresses.insert(listener.address) {
+                anyhow::bail!("listener {} is configured more than once", listener.address);
+            }
+        }
+
+        let mut ids = HashSet::new();
+        let mut names = HashSet::new();
+        for registry in &self.registries {
+            if registry.id == 0 {
+                anyhow::bail!("registry id 0 is reserved for crates.io");
+            } else if registry.name == CRATES_IO {
+                anyhow::bail!("registry name {CRATES_IO} is reserved");
+            } else if !ids.insert(registry.id) {
+                anyhow::bail!("registry id {} is used more than once", registry.id);
+            } else if !names.insert(&registry.name) {
+                anyhow::bail!("registry name {} is used more than once", registry.name);
+            }
+        }
+
+        if let Some(url) = &self.public_url {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                anyhow::bail!("the public URL must start with http:// or https://");
+            }
+        }
+
+        if !self.recency.weight.is_finite() || self.recency.weight < -1. {
+            anyhow::bail!("the recency weight must be a finite number of at least -1");
+        }
+
+        if let Some(experiment) = &self.experiment {
+            if experiment.variants.is_empty() {
+                anyhow::bail!("experiment {} has no variants", experiment.name);
+            }
+            for variant in &experiment.variants {
+                if ranking::by_name(variant).is_none() {
+                    anyhow::bail!("experiment variant {variant} is not a known ranker");
+                }
+            }
+        }
+        Ok(())
+    }
+}
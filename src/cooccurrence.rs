@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+
+use crate::{
+    dependency_graph::{self, Graph},
+    schema::{CrateCooccurrence, UsedWith},
+};
+
+/// The most crates stored as used with each crate.
+const MAX_USED_WITH: usize = 30;
+
+/// The fewest crates that must depend on two crates for the two to be
+/// considered used together.
+const MIN_SHARED_DEPENDENTS: u64 = 3;
+
+/// Crates with more dependencies than this aren't counted. They're mostly
+/// applications and collections of re-exports, whose dependencies say little
+/// about which crates go together, and counting their pairs is slow.
+const MAX_DEPENDENCIES: usize = 100;
+
+/// Recomputes the [`CrateCooccurrence`] of every crate with dependents,
+/// storing the ones that changed. Every kind of dependency of each crate's
+/// latest version counts, including optional and dev-dependencies.
+///
+/// The crates used with a crate are ordered by the Jaccard index of the two
+/// crates' dependents, rather than by how many dependents they share, so that
+/// crates used with nearly everything, such as `serde`, don't crowd out the
+/// crates used with this crate in particular.
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    println!("Counting crates used together");
+    let graph = dependency_graph::latest_dependencies(db, |_| true)?;
+    let cooccurrences = cooccurrences(&graph);
+
+    let mut existing = CrateCooccurrence::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+
+    let mut tx = Transaction::new();
+    for (crate_id, cooccurrence) in cooccurrences {
+        let operation = match existing.remove(&crate_id) {
+            Some(existing) if existing.contents == cooccurrence => continue,
+            Some(existing) => {
+                Operation::update_serialized::<CrateCooccurrence>(existing.header, &cooccurrence)?
+            }
+            None => {
+                Operation::insert_serialized::<CrateCooccurrence>(Some(&crate_id), &cooccurrence)?
+            }
+        };
+        tx.operations.push(operation);
+        if tx.operations.len() >= 100_000 {
+            tx.apply(db)?;
+            tx = Transaction::new();
+        }
+    }
+    // Crates that lost their last dependent.
+    for stale in existing.into_values() {
+        tx.operations.push(Operation::delete(
+            CrateCooccurrence::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the [`CrateCooccurrence`] of every crate with dependents in
+/// `graph`.
+fn cooccurrences(graph: &Graph) -> HashMap<u64, CrateCooccurrence> {
+    let mut dependents = HashMap::<u64, Vec<u64>>::new();
+    for (dependent, dependencies) in graph {
+        if dependencies.len() > MAX_DEPENDENCIES {
+            continue;
+        }
+        for dependency in dependencies {
+            dependents.entry(*dependency).or_default().push(*dependent);
+        }
+    }
+
+    let mut cooccurrences = HashMap::with_capacity(dependents.len());
+    let mut shared = HashMap::<u64, u64>::new();
+    for (crate_id, crate_dependents) in &dependents {
+        shared.clear();
+        for dependent in crate_dependents {
+            for other in &graph[dependent] {
+                if other != crate_id {
+                    *shared.entry(*other).or_default() += 1;
+                }
+            }
+        }
+
+        let total = crate_dependents.len() as u64;
+        let mut used_with = shared
+            .iter()
+            .filter(|(_, count)| **count >= MIN_SHARED_DEPENDENTS)
+            .map(|(other, count)| {
+                // Every crate depended on by a counted crate has dependents.
+                let other_total = dependents[other].len() as u64;
+                let similarity = *count as f64 / (total + other_total - count) as f64;
+                (
+                    similarity,
+                    UsedWith {
+                        crate_id: *other,
+                        shared: *count,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        used_with.sort_by(|a, b| {
+            b.0.total_cmp(&a.0)
+                .then(b.1.shared.cmp(&a.1.shared))
+                .then(a.1.crate_id.cmp(&b.1.crate_id))
+        });
+        cooccurrences.insert(
+            *crate_id,
+            CrateCooccurrence {
+                dependents: total,
+                used_with: used_with
+                    .into_iter()
+                    .take(MAX_USED_WITH)
+                    .map(|(_, used_with)| used_with)
+                    .collect(),
+            },
+        );
+    }
+
+    cooccurrences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn used_with(cooccurrence: &CrateCooccurrence) -> Vec<(u64, u64)> {
+        cooccurrence
+            .used_with
+            .iter()
+            .map(|used_with| (used_with.crate_id, used_with.shared))
+            .collect()
+    }
+
+    #[test]
+    fn crates_are_ordered_by_similarity() {
+        // 1 and 2 are used together most often, and 3 is used with both.
+        let graph = Graph::from([
+            (10, vec![1, 2, 3, 4]),
+            (11, vec![1, 2, 3]),
+            (12, vec![1, 2, 3]),
+            (13, vec![1, 2]),
+            (14, vec![1]),
+        ]);
+        let cooccurrences = cooccurrences(&graph);
+
+        assert_eq!(cooccurrences[&1].dependents, 5);
+        assert_eq!(used_with(&cooccurrences[&1]), [(2, 4), (3, 3)]);
+        assert_eq!(used_with(&cooccurrences[&2]), [(1, 4), (3, 3)]);
+        // 3 shares as many dependents with 1 as with 2, but 2 has fewer
+        // dependents that don't use 3.
+        assert_eq!(used_with(&cooccurrences[&3]), [(2, 3), (1, 3)]);
+        // 4 shares too few dependents with anything.
+        assert_eq!(cooccurrences[&4].dependents, 1);
+        assert!(cooccurrences[&4].used_with.is_empty());
+        // Crates without dependents aren't included.
+        assert!(!cooccurrences.contains_key(&10));
+    }
+
+    #[test]
+    fn crates_with_many_dependencies_are_skipped() {
+        let mut graph = Graph::from([(10, vec![1, 2]), (11, vec![1, 2]), (12, vec![1, 2])]);
+        graph.insert(20, (1..=MAX_DEPENDENCIES as u64 + 1).collect());
+        let cooccurrences = cooccurrences(&graph);
+
+        assert_eq!(cooccurrences[&1].dependents, 3);
+        assert_eq!(used_with(&cooccurrences[&1]), [(2, 3)]);
+        assert!(!cooccurrences.contains_key(&3));
+    }
+
+    #[test]
+    fn stale_cooccurrences_are_removed() {
+        let (_dir, db) = crate::test_database();
+        CrateCooccurrence {
+            dependents: 3,
+            used_with: vec![UsedWith {
+                crate_id: 2,
+                shared: 3,
+            }],
+        }
+        .insert_into(&1, &db)
+        .unwrap();
+
+        update(&db).unwrap();
+        assert!(CrateCooccurrence::get(&1, &db).unwrap().is_none());
+    }
+}
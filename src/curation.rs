@@ -0,0 +1,320 @@
+//! Administrators' overrides of search results, which pin, boost, demote, or
+//! hide crates in the results of specific searches.
+//!
+//! Curations are cached with the crates, keyed by their normalized query, and
+//! applied by [`query`](crate::query) after the results are ranked. Every
+//! change is recorded as a [`CurationEvent`] in the same transaction.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    cache::CrateTable,
+    dump,
+    filters::Filters,
+    schema::{Curation, CurationAction, CurationChange, CurationEvent},
+    CrateResult,
+};
+
+/// Returns the multiplier curations apply to the score of the crate with
+/// `id`, or None if the crate is hidden.
+pub(crate) fn multiplier(curations: &[(u64, CurationAction)], id: u64) -> Option<f32> {
+    let mut multiplier = 1.;
+    for (_, action) in curations.iter().filter(|(curated, _)| *curated == id) {
+        match action {
+            CurationAction::Hide => return None,
+            CurationAction::Boost => multiplier *= CurationAction::BOOST,
+            CurationAction::Demote => multiplier *= CurationAction::DEMOTE,
+            CurationAction::Pin => {}
+        }
+    }
+    Some(multiplier)
+}
+
+/// Moves the pinned crates to the front of `results`, adding those that
+/// didn't match the search as long as they satisfy the filters.
+pub(crate) fn pin(
+    curations: &[(u64, CurationAction)],
+    results: &mut Vec<CrateResult>,
+    filters: &Filters,
+    crates: &Arc<CrateTable>,
+    category_crates: Option<&HashSet<u64>>,
+) {
+    let pinned = curations
+        .iter()
+        .filter(|(_, action)| *action == CurationAction::Pin)
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for id in pinned.into_iter().rev() {
+        let result = match results.iter().position(|result| result.result.id() == id) {
+            Some(position) => results.remove(position),
+            None => {
+                let Some(krate) = crates.get(id) else { continue };
+                if !filters.matches(&krate)
+                    || category_crates.map_or(false, |crates| !crates.contains(&id))
+                {
+                    continue;
+                }
+                CrateResult {
+                    confidence: 1.,
+                    popularity: crate::popularity(&krate),
+                    result: krate,
+                    similar: Vec::new(),
+                    snippet: None,
+                }
+            }
+        };
+        results.insert(0, result);
+    }
+}
+
+/// Held while a curation is added, so that the id chosen for it isn't taken
+/// before it's written.
+static ADDING: Mutex<()> = Mutex::new(());
+
+/// Adds `curation` on behalf of `admin`, returning its id.
+///
+/// The id is chosen before the curation is written, so that the curation and
+/// its event are written together. Ids aren't reused, even after the
+/// curation they belonged to is removed, so each id in the audit trail names
+/// one curation.
+pub(crate) fn add(
+    db: &Database,
+    curation: Curation,
+    reason: String,
+    admin: String,
+) -> anyhow::Result<u64> {
+    let _adding = ADDING.lock().expect("poisoned");
+    let latest = Curation::all(db).descending().limit(1).query()?.pop();
+    let id = CurationEvent::all(db)
+        .query()?
+        .into_iter()
+        .map(|event| event.contents.curation_id)
+        .chain(latest.map(|latest| latest.header.id))
+        .max()
+        .map_or(1, |id| id + 1);
+
+    let mut tx = Transaction::new();
+    tx.operations.push(Operation::insert_serialized::<Curation>(
+        Some(&id),
+        &curation,
+    )?);
+    tx.operations
+        .push(event(CurationChange::Added, id, curation, reason, admin)?);
+    tx.apply(db)?;
+    Ok(id)
+}
+
+/// Removes the curation with `id` on behalf of `admin`, returning false if
+/// there is no such curation.
+pub(crate) fn remove(
+    db: &Database,
+    id: u64,
+    reason: String,
+    admin: String,
+) -> anyhow::Result<bool> {
+    let Some(curation) = Curation::get(&id, db)? else { return Ok(false) };
+
+    let mut tx = Transaction::new();
+    tx.operations.push(Operation::delete(
+        Curation::collection_name(),
+        Header::try_from(curation.header)?,
+    ));
+    tx.operations.push(event(
+        CurationChange::Removed,
+        id,
+        curation.contents,
+        reason,
+        admin,
+    )?);
+    tx.apply(db)?;
+    Ok(true)
+}
+
+/// Returns the operation recording `change` to the curation with `id` in the
+/// audit trail.
+fn event(
+    change: CurationChange,
+    id: u64,
+    curation: Curation,
+    reason: String,
+    admin: String,
+) -> anyhow::Result<Operation> {
+    Ok(Operation::push_serialized::<CurationEvent>(
+        &CurationEvent {
+            at: dump::timestamp(OffsetDateTime::now_utc()),
+            change,
+            curation_id: id,
+            curation,
+            reason,
+            admin,
+        },
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multipliers() {
+        let curations = [
+            (1, CurationAction::Boost),
+            (2, CurationAction::Demote),
+            (3, CurationAction::Hide),
+            (4, CurationAction::Pin),
+            (5, CurationAction::Boost),
+            (5, CurationAction::Demote),
+        ];
+        assert_eq!(multiplier(&curations, 1), Some(CurationAction::BOOST));
+        assert_eq!(multiplier(&curations, 2), Some(CurationAction::DEMOTE));
+        assert_eq!(multiplier(&curations, 3), None);
+        assert_eq!(multiplier(&curations, 4), Some(1.));
+        assert_eq!(
+            multiplier(&curations, 5),
+            Some(CurationAction::BOOST * CurationAction::DEMOTE)
+        );
+        assert_eq!(multiplier(&curations, 6), Some(1.));
+    }
+
+    /// Returns the ids of four crates, the last from another registry, and a
+    /// table of them.
+    fn crates() -> (tempfile::TempDir, [u64; 4], Arc<CrateTable>) {
+        let (dir, db) = crate::test_database();
+        let mut ids = [0; 4];
+        for (id, name) in ids.iter_mut().zip(["alpha", "beta", "gamma", "delta"]) {
+            let mut krate = crate::test_crate(name);
+            if name == "delta" {
+                krate.registry = Some(String::from("internal"));
+            }
+            *id = krate.push_into(&db).unwrap().header.id;
+        }
+        let table = crate::test_cache(&db).crates().unwrap();
+        (dir, ids, table)
+    }
+
+    fn result(crates: &Arc<CrateTable>, id: u64) -> CrateResult {
+        CrateResult {
+            confidence: 0.5,
+            popularity: 0.,
+            result: crates.get(id).unwrap(),
+            similar: Vec::new(),
+            snippet: None,
+        }
+    }
+
+    fn ids(results: &[CrateResult]) -> Vec<u64> {
+        results.iter().map(|result| result.result.id()).collect()
+    }
+
+    #[test]
+    fn pinned_results_are_moved_first() {
+        let (_dir, [alpha, beta, gamma, _], crates) = crates();
+        let mut results = vec![
+            result(&crates, alpha),
+            result(&crates, beta),
+            result(&crates, gamma),
+        ];
+        let curations = [(gamma, CurationAction::Pin), (beta, CurationAction::Pin)];
+        pin(&curations, &mut results, &Filters::default(), &crates, None);
+        assert_eq!(ids(&results), [gamma, beta, alpha]);
+        // Pinned crates that matched keep their confidence.
+        assert_eq!(results[0].confidence, 0.5);
+    }
+
+    #[test]
+    fn pinned_crates_are_added() {
+        let (_dir, [alpha, beta, gamma, _], crates) = crates();
+        let mut results = vec![result(&crates, alpha)];
+        let curations = [(gamma, CurationAction::Pin), (beta, CurationAction::Boost)];
+        pin(&curations, &mut results, &Filters::default(), &crates, None);
+        assert_eq!(ids(&results), [gamma, alpha]);
+        assert_eq!(results[0].confidence, 1.);
+    }
+
+    #[test]
+    fn pinned_crates_must_match_the_filters() {
+        let (_dir, [alpha, beta, gamma, delta], crates) = crates();
+        let mut results = vec![result(&crates, alpha)];
+        let curations = [
+            (delta, CurationAction::Pin),
+            (beta, CurationAction::Pin),
+            (gamma, CurationAction::Pin),
+        ];
+        let (_, filters) = Filters::parse("registry:crates-io");
+        let category = HashSet::from([alpha, gamma]);
+        pin(&curations, &mut results, &filters, &crates, Some(&category));
+        assert_eq!(ids(&results), [gamma, alpha]);
+    }
+
+    #[test]
+    fn changes_are_recorded() {
+        let (_dir, db) = crate::test_database();
+        let curation = Curation {
+            query: String::from("parsing"),
+            crate_name: String::from("nom"),
+            action: CurationAction::Pin,
+        };
+        let first = add(
+            &db,
+            curation.clone(),
+            String::from("why"),
+            String::from("admin"),
+        )
+        .unwrap();
+        assert!(remove(&db, first, String::new(), String::from("token")).unwrap());
+        assert!(!remove(&db, first, String::new(), String::from("token")).unwrap());
+        // Ids aren't reused once their curation is removed.
+        let second = add(&db, curation.clone(), String::new(), String::from("admin")).unwrap();
+        assert!(second > first);
+        assert_eq!(
+            Curation::get(&second, &db).unwrap().unwrap().contents,
+            curation
+        );
+
+        let events = CurationEvent::all(&db)
+            .query()
+            .unwrap()
+            .into_iter()
+            .map(|event| {
+                let event = event.contents;
+                (event.change, event.curation_id, event.reason, event.admin)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (
+                    CurationChange::Added,
+                    first,
+                    String::from("why"),
+                    String::from("admin")
+                ),
+                (
+                    CurationChange::Removed,
+                    first,
+                    String::new(),
+                    String::from("token")
+                ),
+                (
+                    CurationChange::Added,
+                    second,
+                    String::new(),
+                    String::from("admin")
+                ),
+            ]
+        );
+    }
+}
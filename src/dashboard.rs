@@ -0,0 +1,143 @@
+//! The registry statistics shown on the homepage, which are computed each
+//! time the cache is refreshed.
+
+use std::sync::Arc;
+
+use bonsaidb::core::document::CollectionDocument;
+use time::{Date, Duration};
+
+use crate::{
+    cache::{CachedCrate, CrateTable},
+    categories::CategoryEntry,
+    schema::Advisory,
+};
+
+/// Crates first published within this many days count as new.
+const NEW_CRATE_DAYS: i64 = 7;
+
+/// The fewest downloads in the last 30 days a crate needs to be trending, so
+/// that a brand new crate with a few downloads isn't.
+const MIN_TRENDING_DOWNLOADS: u64 = 10_000;
+
+/// The number of trending crates shown.
+const TRENDING_CRATES: usize = 10;
+
+/// The number of categories shown.
+const TOP_CATEGORIES: usize = 12;
+
+/// The number of advisories shown.
+const RECENT_ADVISORIES: usize = 5;
+
+#[derive(Debug, Default)]
+pub(crate) struct Dashboard {
+    /// The number of searchable crates.
+    pub total_crates: usize,
+    /// The number of searchable crates first published in the last week.
+    pub new_this_week: usize,
+    /// The ids of the crates with the largest share of their downloads in the
+    /// last 30 days, most trending first.
+    pub trending: Vec<u64>,
+    /// The top-level categories with the most crates, most crates first.
+    pub top_categories: Vec<TopCategory>,
+    /// The most recently published advisories that haven't been withdrawn,
+    /// newest first.
+    pub advisories: Vec<RecentAdvisory>,
+}
+
+#[derive(Debug)]
+pub(crate) struct TopCategory {
+    pub slug: String,
+    pub name: String,
+    pub crates: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct RecentAdvisory {
+    pub id: String,
+    pub crate_name: String,
+    pub title: String,
+    pub date: String,
+    /// The kind of informational advisory, such as `unmaintained`, or None
+    /// for vulnerabilities.
+    pub informational: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new(
+        crates: &Arc<CrateTable>,
+        categories: &[CategoryEntry],
+        advisories: &[CollectionDocument<Advisory>],
+        today: Date,
+    ) -> Self {
+        let new_since = today - Duration::days(NEW_CRATE_DAYS);
+        let mut dashboard = Self::default();
+        let mut trending = Vec::new();
+        for krate in crates.iter().filter(|krate| !krate.blocked()) {
+            dashboard.total_crates += 1;
+            if krate
+                .first_published()
+                .map_or(false, |published| published >= new_since)
+            {
+                dashboard.new_this_week += 1;
+            }
+            if let Some(share) = trending_share(&krate) {
+                trending.push((share, krate.id()));
+            }
+        }
+        trending.sort_unstable_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        dashboard.trending = trending
+            .into_iter()
+            .take(TRENDING_CRATES)
+            .map(|(_, id)| id)
+            .collect();
+
+        let mut top_categories = categories
+            .iter()
+            .filter(|category| category.depth == 0)
+            .collect::<Vec<_>>();
+        top_categories.sort_by(|a, b| b.crates.cmp(&a.crates).then(a.name.cmp(&b.name)));
+        dashboard.top_categories = top_categories
+            .into_iter()
+            .take(TOP_CATEGORIES)
+            .map(|category| TopCategory {
+                slug: category.slug.clone(),
+                name: category.name.clone(),
+                crates: category.crates,
+            })
+            .collect();
+
+        let mut recent = advisories
+            .iter()
+            .filter(|advisory| advisory.contents.withdrawn.is_none())
+            .collect::<Vec<_>>();
+        recent.sort_by(|a, b| b.contents.date.cmp(&a.contents.date));
+        dashboard.advisories = recent
+            .into_iter()
+            .take(RECENT_ADVISORIES)
+            .map(|advisory| RecentAdvisory {
+                id: advisory.header.id.clone(),
+                crate_name: advisory.contents.crate_name.clone(),
+                title: advisory.contents.title.clone(),
+                date: advisory.contents.date.clone(),
+                informational: advisory.contents.informational.clone(),
+            })
+            .collect();
+
+        dashboard
+    }
+}
+
+/// Returns the share of the crate's downloads that were in the last 30 days,
+/// or None if it can't be trending.
+fn trending_share(krate: &CachedCrate) -> Option<f64> {
+    if krate.recent_downloads() < MIN_TRENDING_DOWNLOADS
+        || krate.likely_placeholder()
+        || krate.deprecation().is_some()
+        || krate.in_publish_wave()
+    {
+        return None;
+    }
+    // Download counts are imported separately from the recent downloads, so
+    // the total can briefly lag behind.
+    Some(krate.recent_downloads() as f64 / krate.downloads().max(krate.recent_downloads()) as f64)
+}
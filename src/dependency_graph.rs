@@ -0,0 +1,331 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use bonsaidb::{
+    core::{
+        schema::{SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::{
+    api::find_crate,
+    cache::Cache,
+    schema::{
+        DependenciesByVersion, Dependency, DependencyKind, DependencyMetrics, VersionSummary,
+        VersionsByCrate,
+    },
+};
+
+/// The deepest [`DependencyGraph`] that can be requested.
+pub(crate) const MAX_GRAPH_DEPTH: u32 = 5;
+
+/// The direct dependencies of each crate's latest version, keyed by crate id.
+pub(crate) type Graph = HashMap<u64, Vec<u64>>;
+
+/// Returns true if `dependency` is always built when its dependent is used as
+/// a dependency itself. Dev-dependencies are only built for the dependent's
+/// own tests, and optional dependencies depend on the features enabled.
+pub(crate) fn is_required(dependency: &Dependency) -> bool {
+    dependency.kind != DependencyKind::Dev && !dependency.optional
+}
+
+/// The latest non-yanked version of a crate, preferring stable releases over
+/// pre-releases.
+#[derive(Default)]
+struct LatestVersion(Option<(bool, semver::Version, u64)>);
+
+impl LatestVersion {
+    /// Considers the version `version_id`, described by `summary`.
+    fn consider(
+        &mut self,
+        summary: &VersionSummary,
+        version_id: impl FnOnce() -> anyhow::Result<u64>,
+    ) -> anyhow::Result<()> {
+        if summary.yanked {
+            return Ok(());
+        }
+        let Ok(version) = semver::Version::parse(&summary.version) else { return Ok(()) };
+        let stable = version.pre.is_empty();
+        if self.0.as_ref().map_or(true, |(latest_stable, latest, _)| {
+            (stable, &version) > (*latest_stable, latest)
+        }) {
+            self.0 = Some((stable, version, version_id()?));
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> Option<u64> {
+        self.0.as_ref().map(|(_, _, id)| *id)
+    }
+}
+
+/// Returns the id of every crate's latest version, keyed by crate id. Crates
+/// without a non-yanked version are omitted.
+pub(crate) fn latest_versions(db: &Database) -> anyhow::Result<HashMap<u64, u64>> {
+    let mut latest = HashMap::<u64, LatestVersion>::new();
+    for mapping in VersionsByCrate::entries(db).query()? {
+        latest
+            .entry(mapping.key)
+            .or_default()
+            .consider(&mapping.value, || Ok(mapping.source.id.deserialize()?))?;
+    }
+    Ok(latest
+        .into_iter()
+        .filter_map(|(crate_id, latest)| Some((crate_id, latest.id()?)))
+        .collect())
+}
+
+/// Returns the required dependencies of `version_id`.
+pub(crate) fn required_dependencies(
+    db: &Database,
+    version_id: u64,
+) -> anyhow::Result<Vec<Dependency>> {
+    Ok(DependenciesByVersion::entries(db)
+        .with_key(&version_id)
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.value)
+        .filter(is_required)
+        .collect())
+}
+
+/// Builds the graph of every crate's dependencies that `include` accepts,
+/// such as [`is_required`].
+///
+/// Each dependency is resolved to the dependency's latest version rather than
+/// the newest version matching its requirement. This keeps the graph to one
+/// node per crate, at the cost of measuring the latest versions' dependencies
+/// even when an older major version is required.
+pub(crate) fn latest_dependencies(
+    db: &Database,
+    include: impl Fn(&Dependency) -> bool,
+) -> anyhow::Result<Graph> {
+    let latest = latest_versions(db)?;
+    let mut graph = Graph::with_capacity(latest.len());
+    for (crate_id, version_id) in latest {
+        let mut dependencies = DependenciesByVersion::entries(db)
+            .with_key(&version_id)
+            .query()?
+            .into_iter()
+            .filter(|mapping| include(&mapping.value))
+            .map(|mapping| mapping.value.crate_id)
+            .collect::<Vec<_>>();
+        // The same crate can be listed once per target.
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        graph.insert(crate_id, dependencies);
+    }
+
+    Ok(graph)
+}
+
+/// Walks the dependencies of `crate_id` breadth-first, counting the distinct
+/// crates reached and the number of steps needed to reach the furthest one.
+fn measure(graph: &Graph, crate_id: u64) -> DependencyMetrics {
+    let mut visited = HashSet::from([crate_id]);
+    let mut frontier = vec![crate_id];
+    let mut depth = 0;
+    loop {
+        let mut next = Vec::new();
+        for id in frontier {
+            for dependency in graph.get(&id).into_iter().flatten() {
+                if visited.insert(*dependency) {
+                    next.push(*dependency);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        depth += 1;
+        frontier = next;
+    }
+
+    DependencyMetrics {
+        transitive: u32::try_from(visited.len() - 1).unwrap_or(u32::MAX),
+        depth,
+    }
+}
+
+/// Returns the crates `crate_id` requires, directly or indirectly, not
+/// including itself.
+pub(crate) fn reachable(graph: &Graph, crate_id: u64) -> HashSet<u64> {
+    let mut visited = HashSet::from([crate_id]);
+    let mut pending = vec![crate_id];
+    while let Some(id) = pending.pop() {
+        for dependency in graph.get(&id).into_iter().flatten() {
+            if visited.insert(*dependency) {
+                pending.push(*dependency);
+            }
+        }
+    }
+    visited.remove(&crate_id);
+    visited
+}
+
+/// Recomputes the [`DependencyMetrics`] of every crate, storing the ones that
+/// changed.
+pub(crate) fn update_metrics(db: &Database) -> anyhow::Result<()> {
+    println!("Measuring dependency graph");
+    let graph = latest_dependencies(db, is_required)?;
+    let mut existing = DependencyMetrics::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+
+    let mut tx = Transaction::new();
+    for crate_id in graph.keys() {
+        let metrics = measure(&graph, *crate_id);
+        let operation = match existing.remove(crate_id) {
+            Some(existing) if existing.contents == metrics => continue,
+            Some(existing) => {
+                Operation::update_serialized::<DependencyMetrics>(existing.header, &metrics)?
+            }
+            None => Operation::insert_serialized::<DependencyMetrics>(Some(crate_id), &metrics)?,
+        };
+        tx.operations.push(operation);
+        if tx.operations.len() >= 100_000 {
+            tx.apply(db)?;
+            tx = Transaction::new();
+        }
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+/// The required dependencies of a crate, followed to a limited depth.
+#[derive(Serialize, Debug)]
+pub(crate) struct DependencyGraph {
+    pub root: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GraphNode {
+    pub name: String,
+    /// The latest version of the crate.
+    pub version: Option<String>,
+    /// The number of steps from the root crate.
+    pub depth: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub req: String,
+    pub kind: DependencyKind,
+    /// The `cfg` or target triple the dependency is limited to.
+    pub target: Option<String>,
+}
+
+impl DependencyGraph {
+    /// Loads the graph of the crate named `name`, following dependencies up to
+    /// `depth` steps away. Returns None if the crate isn't found.
+    /// Dependencies are read through the cache, so that crates shared by
+    /// many graphs aren't queried again for each one.
+    pub fn load(cache: &Cache, name: &str, depth: u32) -> anyhow::Result<Option<Self>> {
+        let Some(root_id) = find_crate(cache, name)? else { return Ok(None) };
+        let crates = cache.crates()?;
+        let Some(root) = crates.get(root_id) else { return Ok(None) };
+        let crate_name = |id: u64| match crates.get(id) {
+            Some(krate) => krate.name().to_string(),
+            None => format!("#{id}"),
+        };
+
+        let mut graph = Self {
+            root: root.name().to_string(),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        let mut visited = HashSet::from([root_id]);
+        let mut frontier = vec![root_id];
+        for level in 0..=depth {
+            let mut next = Vec::new();
+            for crate_id in frontier {
+                let krate = crates.get(crate_id);
+                let name = crate_name(crate_id);
+                graph.nodes.push(GraphNode {
+                    name: name.clone(),
+                    version: krate
+                        .as_ref()
+                        .and_then(|krate| krate.latest_version().map(String::from)),
+                    depth: level,
+                });
+                // The dependencies of the deepest level aren't shown.
+                if level == depth {
+                    continue;
+                }
+
+                let Some(krate) = krate else { continue };
+                for dependency in cache.required_dependencies(&krate)?.iter() {
+                    graph.edges.push(GraphEdge {
+                        from: name.clone(),
+                        to: crate_name(dependency.crate_id),
+                        req: dependency.req.clone(),
+                        kind: dependency.kind,
+                        target: dependency.target.clone(),
+                    });
+                    if visited.insert(dependency.crate_id) {
+                        next.push(dependency.crate_id);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        Ok(Some(graph))
+    }
+
+    /// Formats the graph in Graphviz's DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("digraph {} {{\n", quoted(&self.root));
+        for node in &self.nodes {
+            let label = match &node.version {
+                Some(version) => format!("{} {version}", node.name),
+                None => node.name.clone(),
+            };
+            let _ = writeln!(
+                dot,
+                "    {} [label={}];",
+                quoted(&node.name),
+                quoted(&label)
+            );
+        }
+        for edge in &self.edges {
+            let label = match &edge.target {
+                Some(target) => format!("{} ({target})", edge.req),
+                None => edge.req.clone(),
+            };
+            let style = if edge.kind == DependencyKind::Build {
+                " style=dashed"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label={}{style}];",
+                quoted(&edge.from),
+                quoted(&edge.to),
+                quoted(&label)
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes `id` as a DOT identifier.
+fn quoted(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
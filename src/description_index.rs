@@ -0,0 +1,344 @@
+//! A [`SearchBackend`] that keeps an inverted index of crates' names and
+//! descriptions in memory, for deployments without room for tantivy.
+//!
+//! Only the words' counts are stored, not the text, so the index is a fraction
+//! of the size of the crate cache's descriptions. Matches are scored with
+//! BM25, like tantivy's, so that the query scoring weighs them the same way.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use bonsaidb::{core::schema::SerializedView, local::Database};
+
+use crate::{
+    schema::{Crate, CratesByNormalizedName},
+    search_backend::{Highlighter, IndexWriter, SearchBackend},
+    snippets::WordHighlighter,
+    syntax::{self, Expr, Field, Term},
+    top_k::TopK,
+    PHRASE_BOOST,
+};
+
+/// How quickly repeating a word stops increasing a crate's score.
+const K1: f32 = 1.2;
+
+/// How much a crate's score is reduced by the length of its name and
+/// description.
+const B: f32 = 0.75;
+
+#[derive(Debug, Default)]
+pub struct DescriptionIndex {
+    contents: Arc<RwLock<Contents>>,
+}
+
+impl DescriptionIndex {
+    /// Builds the index from every crate in `db`.
+    pub fn open(db: &Database) -> anyhow::Result<Self> {
+        let index = Self::default();
+        index.rebuild(db)?;
+        Ok(index)
+    }
+}
+
+impl SearchBackend for DescriptionIndex {
+    fn search(&self, terms: &str, limit: usize) -> anyhow::Result<Vec<(f32, u64)>> {
+        let Some(expr) = syntax::parse(terms) else { return Ok(Vec::new()) };
+        let contents = self
+            .contents
+            .read()
+            .map_err(|_| anyhow::anyhow!("description index rwlock poisoned"))?;
+
+        let mut term_scores = HashMap::new();
+        let mut candidates = HashSet::new();
+        for (term, negated) in expr.terms() {
+            let scores = term_scores
+                .entry(term)
+                .or_insert_with(|| contents.term_scores(&term));
+            if !negated {
+                candidates.extend(scores.keys().copied());
+            }
+        }
+
+        let mut results = TopK::new(limit);
+        for id in candidates {
+            if let Some(score) = score(&expr, id, &term_scores) {
+                results.push(score, id);
+            }
+        }
+        Ok(results.into_sorted_vec())
+    }
+
+    fn writer(&self) -> anyhow::Result<Box<dyn IndexWriter>> {
+        Ok(Box::new(DescriptionWriter {
+            contents: self.contents.clone(),
+            crates: HashMap::new(),
+        }))
+    }
+
+    fn rebuild(&self, db: &Database) -> anyhow::Result<usize> {
+        let mut contents = Contents::default();
+        for mapping in CratesByNormalizedName::entries(db).query()? {
+            contents.add(
+                mapping.source.id.deserialize::<u64>()?,
+                &mapping.value.name,
+                &mapping.value.description,
+            );
+        }
+        let indexed = contents.lengths.len();
+        *self
+            .contents
+            .write()
+            .map_err(|_| anyhow::anyhow!("description index rwlock poisoned"))? = contents;
+        Ok(indexed)
+    }
+
+    /// Does nothing, since the index is built when it's opened.
+    fn warm_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Counts the words of descriptions, since READMEs aren't indexed.
+    fn common_terms(&self, min_crates: u32) -> anyhow::Result<HashMap<String, u64>> {
+        let contents = self
+            .contents
+            .read()
+            .map_err(|_| anyhow::anyhow!("description index rwlock poisoned"))?;
+        Ok(contents
+            .postings
+            .iter()
+            .filter_map(|(word, postings)| {
+                let crates = postings
+                    .iter()
+                    .filter(|posting| posting.description > 0)
+                    .count() as u64;
+                (crates >= u64::from(min_crates)).then(|| (word.clone(), crates))
+            })
+            .collect())
+    }
+
+    fn num_docs(&self) -> u64 {
+        self.contents
+            .read()
+            .map_or(0, |contents| contents.lengths.len() as u64)
+    }
+
+    fn highlighter(&self, terms: &str) -> anyhow::Result<Option<Box<dyn Highlighter>>> {
+        Ok(syntax::parse(terms)
+            .map(|expr| Box::new(WordHighlighter::new(&expr)) as Box<dyn Highlighter>))
+    }
+
+    /// The index is rebuilt from the database when opened.
+    fn snapshot(&self, _destination: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Contents {
+    /// The crates containing each lowercased word.
+    postings: HashMap<String, Vec<Posting>>,
+    /// The number of words in each crate's name and description.
+    lengths: HashMap<u64, u32>,
+    total_length: u64,
+}
+
+/// How many times a word appears in a crate's name and description.
+#[derive(Debug, Clone, Copy)]
+struct Posting {
+    id: u64,
+    name: u16,
+    description: u16,
+}
+
+impl Contents {
+    fn add(&mut self, id: u64, name: &str, description: &str) {
+        let mut counts = HashMap::<String, Posting>::new();
+        let new_posting = || Posting {
+            id,
+            name: 0,
+            description: 0,
+        };
+        for (_, word) in words(name) {
+            let posting = counts
+                .entry(word.to_lowercase())
+                .or_insert_with(new_posting);
+            posting.name = posting.name.saturating_add(1);
+        }
+        for (_, word) in words(description) {
+            let posting = counts
+                .entry(word.to_lowercase())
+                .or_insert_with(new_posting);
+            posting.description = posting.description.saturating_add(1);
+        }
+
+        let length = counts
+            .values()
+            .map(|posting| u32::from(posting.name) + u32::from(posting.description))
+            .sum::<u32>();
+        self.lengths.insert(id, length);
+        self.total_length += u64::from(length);
+        for (word, posting) in counts {
+            self.postings.entry(word).or_default().push(posting);
+        }
+    }
+
+    fn remove(&mut self, ids: &HashSet<u64>) {
+        for id in ids {
+            if let Some(length) = self.lengths.remove(id) {
+                self.total_length -= u64::from(length);
+            }
+        }
+        self.postings.retain(|_, postings| {
+            postings.retain(|posting| !ids.contains(&posting.id));
+            !postings.is_empty()
+        });
+    }
+
+    /// Returns the BM25 score of each crate that `term` matches. A term of
+    /// several words, such as a phrase, only matches crates containing all of
+    /// them.
+    fn term_scores(&self, term: &Term<'_>) -> HashMap<u64, f32> {
+        if term.field == Some(Field::Readme) {
+            return HashMap::new();
+        }
+
+        let crates = self.lengths.len() as f32;
+        let average_length = self.total_length as f32 / crates.max(1.);
+        let mut scores = None::<HashMap<u64, f32>>;
+        for (_, word) in words(term.text) {
+            let Some(postings) = self.postings.get(&word.to_lowercase())
+                else { return HashMap::new() };
+            let matches = postings.len() as f32;
+            let idf = (1. + (crates - matches + 0.5) / (matches + 0.5)).ln();
+            let word_scores = postings.iter().filter_map(|posting| {
+                let count = f32::from(match term.field {
+                    Some(Field::Name) => posting.name,
+                    Some(Field::Description) => posting.description,
+                    _ => posting.name.saturating_add(posting.description),
+                });
+                let length = self.lengths.get(&posting.id).copied().unwrap_or(0) as f32;
+                let normalization = K1 * (1. - B + B * length / average_length);
+                (count > 0.).then(|| {
+                    (
+                        posting.id,
+                        idf * count * (K1 + 1.) / (count + normalization),
+                    )
+                })
+            });
+            scores = Some(match scores {
+                None => word_scores.collect(),
+                Some(mut scores) => {
+                    let word_scores = word_scores.collect::<HashMap<_, _>>();
+                    scores.retain(|id, score| match word_scores.get(id) {
+                        Some(word_score) => {
+                            *score += word_score;
+                            true
+                        }
+                        None => false,
+                    });
+                    scores
+                }
+            });
+        }
+
+        let mut scores = scores.unwrap_or_default();
+        if term.phrase {
+            for score in scores.values_mut() {
+                *score *= PHRASE_BOOST;
+            }
+        }
+        scores
+    }
+}
+
+/// Returns the score of crate `id` for `expr`, or None if it doesn't match.
+///
+/// This matches the same crates as the tantivy backend's queries: phrases must
+/// match, other words need only match when there are no phrases, and a `NOT`
+/// is only meaningful alongside other terms.
+fn score(
+    expr: &Expr<'_>,
+    id: u64,
+    term_scores: &HashMap<Term<'_>, HashMap<u64, f32>>,
+) -> Option<f32> {
+    match expr {
+        Expr::Terms(terms) => {
+            let mut total = 0.;
+            let mut matched = terms.iter().any(|term| term.phrase);
+            for term in terms {
+                let score = term_scores[term].get(&id).copied();
+                if term.phrase {
+                    total += score?;
+                } else if let Some(score) = score {
+                    total += score;
+                    matched = true;
+                }
+            }
+            matched.then_some(total)
+        }
+        Expr::And(exprs) => {
+            let mut total = None::<f32>;
+            for expr in exprs {
+                match expr {
+                    Expr::Not(excluded) => {
+                        if score(excluded, id, term_scores).is_some() {
+                            return None;
+                        }
+                    }
+                    expr => *total.get_or_insert(0.) += score(expr, id, term_scores)?,
+                }
+            }
+            total
+        }
+        Expr::Or(exprs) => exprs
+            .iter()
+            .filter_map(|expr| score(expr, id, term_scores))
+            .reduce(|a, b| a + b),
+        Expr::Not(_) => None,
+    }
+}
+
+/// Returns each word of `text` and the byte offset it starts at. Words are
+/// split on anything that isn't alphanumeric, like tantivy's default
+/// tokenizer.
+pub(crate) fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut chars = text.char_indices();
+    std::iter::from_fn(move || {
+        let (start, _) = chars.find(|(_, ch)| ch.is_alphanumeric())?;
+        let end = chars
+            .find(|(_, ch)| !ch.is_alphanumeric())
+            .map_or(text.len(), |(end, _)| end);
+        Some((start, &text[start..end]))
+    })
+}
+
+/// Collects the crates to index until they're committed, since updating the
+/// index requires scanning every word's postings.
+struct DescriptionWriter {
+    contents: Arc<RwLock<Contents>>,
+    /// Each crate's name and description, by id.
+    crates: HashMap<u64, (String, String)>,
+}
+
+impl IndexWriter for DescriptionWriter {
+    fn replace(&mut self, id: u64, krate: &Crate) -> anyhow::Result<()> {
+        self.crates
+            .insert(id, (krate.name.clone(), krate.description.clone()));
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<()> {
+        let mut contents = self
+            .contents
+            .write()
+            .map_err(|_| anyhow::anyhow!("description index rwlock poisoned"))?;
+        contents.remove(&self.crates.keys().copied().collect());
+        for (id, (name, description)) in &self.crates {
+            contents.add(*id, name, description);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::{
+    api::find_crate,
+    cache::Cache,
+    schema::{self, DependenciesByVersion, DependencyKind, VersionsByCrate},
+};
+
+/// A summary of the metadata that changed between two versions of a crate.
+#[derive(Serialize, Debug)]
+pub(crate) struct VersionDiff {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub dependencies: Vec<DependencyChange>,
+    pub features: Vec<FeatureChange>,
+    pub license: Option<Change>,
+    /// The change to the minimum supported Rust version.
+    pub rust_version: Option<Change>,
+    /// The change in the size of the packaged crate, in bytes.
+    pub size_delta: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DependencyChange {
+    pub name: String,
+    pub kind: DependencyKind,
+    pub target: Option<String>,
+    /// The name the dependency is renamed to in the manifest, if any.
+    pub explicit_name: Option<String>,
+    pub change: Change,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct FeatureChange {
+    pub name: String,
+    pub change: Change,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub(crate) enum Change {
+    Added(String),
+    Removed(String),
+    Changed { from: String, to: String },
+}
+
+impl Change {
+    fn between(from: Option<String>, to: Option<String>) -> Option<Self> {
+        match (from, to) {
+            (Some(from), Some(to)) if from == to => None,
+            (Some(from), Some(to)) => Some(Self::Changed { from, to }),
+            (Some(from), None) => Some(Self::Removed(from)),
+            (None, Some(to)) => Some(Self::Added(to)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added(value) => write!(f, "added {value}"),
+            Change::Removed(value) => write!(f, "removed {value}"),
+            Change::Changed { from, to } => write!(f, "{from} -> {to}"),
+        }
+    }
+}
+
+impl VersionDiff {
+    /// Loads the differences between versions `from` and `to` of the crate
+    /// named `name`, returning None if the crate or either version can't be
+    /// found.
+    pub fn load(
+        db: &Database,
+        cache: &Cache,
+        name: &str,
+        from: &str,
+        to: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+        let Some(krate) = cache.crates()?.get(id) else { return Ok(None) };
+
+        let mut from_id = None;
+        let mut to_id = None;
+        for mapping in VersionsByCrate::entries(db).with_key(&id).query()? {
+            if mapping.value.version == from {
+                from_id = Some(mapping.source.id.deserialize::<u64>()?);
+            }
+            if mapping.value.version == to {
+                to_id = Some(mapping.source.id.deserialize::<u64>()?);
+            }
+        }
+        let (Some(from_id), Some(to_id)) = (from_id, to_id) else { return Ok(None) };
+        let Some(old) = schema::Version::get(&from_id, db)? else { return Ok(None) };
+        let Some(new) = schema::Version::get(&to_id, db)? else { return Ok(None) };
+        let (old, new) = (old.contents, new.contents);
+
+        Ok(Some(Self {
+            name: krate.name().to_string(),
+            from: old.version.clone(),
+            to: new.version.clone(),
+            dependencies: dependency_changes(db, cache, from_id, to_id)?,
+            features: feature_changes(&old.features, &new.features)?,
+            license: Change::between(
+                Some(old.license).filter(|license| !license.is_empty()),
+                Some(new.license).filter(|license| !license.is_empty()),
+            ),
+            rust_version: Change::between(old.rust_version, new.rust_version),
+            size_delta: old
+                .crate_size
+                .zip(new.crate_size)
+                .map(|(old, new)| new as i64 - old as i64),
+        }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dependencies.is_empty()
+            && self.features.is_empty()
+            && self.license.is_none()
+            && self.rust_version.is_none()
+            && self.size_delta.map_or(true, |delta| delta == 0)
+    }
+}
+
+/// Dependencies are matched between versions by their name, kind, target, and
+/// rename, so moving a dependency to `[dev-dependencies]` shows up as a
+/// removal and an addition. The rename tells apart two versions of the same
+/// crate depended on at once.
+type DependencyKey = (String, DependencyKind, Option<String>, Option<String>);
+
+fn dependency_changes(
+    db: &Database,
+    cache: &Cache,
+    from_id: u64,
+    to_id: u64,
+) -> anyhow::Result<Vec<DependencyChange>> {
+    let old = dependencies(db, cache, from_id)?;
+    let mut new = dependencies(db, cache, to_id)?;
+
+    let mut changes = Vec::new();
+    for (key, old_req) in old {
+        let change = match new.remove(&key) {
+            Some(new_req) if new_req == old_req => continue,
+            Some(new_req) => Change::Changed {
+                from: old_req,
+                to: new_req,
+            },
+            None => Change::Removed(old_req),
+        };
+        changes.push((key, change));
+    }
+    changes.extend(new.into_iter().map(|(key, req)| (key, Change::Added(req))));
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(changes
+        .into_iter()
+        .map(
+            |((name, kind, target, explicit_name), change)| DependencyChange {
+                name,
+                kind,
+                target,
+                explicit_name,
+                change,
+            },
+        )
+        .collect())
+}
+
+/// Returns the version requirement of each dependency of `version_id`.
+fn dependencies(
+    db: &Database,
+    cache: &Cache,
+    version_id: u64,
+) -> anyhow::Result<BTreeMap<DependencyKey, String>> {
+    let crates = cache.crates()?;
+    let mut dependencies = BTreeMap::new();
+    for mapping in DependenciesByVersion::entries(db)
+        .with_key(&version_id)
+        .query()?
+    {
+        let dependency = mapping.value;
+        let name = match crates.get(dependency.crate_id) {
+            Some(krate) => krate.name().to_string(),
+            None => format!("#{}", dependency.crate_id),
+        };
+        dependencies.insert(
+            (
+                name,
+                dependency.kind,
+                dependency.target,
+                dependency.explicit_name,
+            ),
+            dependency.req,
+        );
+    }
+    Ok(dependencies)
+}
+
+/// Compares two versions' feature tables, which are stored as the JSON
+/// object from the crates.io dump.
+fn feature_changes(old: &str, new: &str) -> anyhow::Result<Vec<FeatureChange>> {
+    let old = parse_features(old)?;
+    let mut new = parse_features(new)?;
+
+    let mut changes = Vec::new();
+    for (name, old_enables) in old {
+        let change = match new.remove(&name) {
+            Some(new_enables) if new_enables == old_enables => continue,
+            Some(new_enables) => Change::Changed {
+                from: join(&old_enables),
+                to: join(&new_enables),
+            },
+            None => Change::Removed(join(&old_enables)),
+        };
+        changes.push(FeatureChange { name, change });
+    }
+    changes.extend(new.into_iter().map(|(name, enables)| FeatureChange {
+        name,
+        change: Change::Added(join(&enables)),
+    }));
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(changes)
+}
+
+pub(crate) fn parse_features(features: &str) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    if features.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_json::from_str(features)?)
+}
+
+fn join(enables: &BTreeSet<String>) -> String {
+    enables
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_backend::{Backend, SearchConfig};
+
+    fn added(value: &str) -> Change {
+        Change::Added(value.to_string())
+    }
+
+    fn changed(from: &str, to: &str) -> Change {
+        Change::Changed {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn changes_between_values() {
+        let value = |value: &str| Some(value.to_string());
+        assert_eq!(Change::between(value("MIT"), value("MIT")), None);
+        assert_eq!(Change::between(None, None), None);
+        assert_eq!(
+            Change::between(value("MIT"), value("Apache-2.0")),
+            Some(changed("MIT", "Apache-2.0"))
+        );
+        assert_eq!(
+            Change::between(value("1.60"), None),
+            Some(Change::Removed(String::from("1.60")))
+        );
+        assert_eq!(Change::between(None, value("1.60")), Some(added("1.60")));
+        assert_eq!(changed("^0.14", "^1").to_string(), "^0.14 -> ^1");
+    }
+
+    #[test]
+    fn feature_tables_are_compared() {
+        let changes = feature_changes(
+            r#"{"default": ["std"], "std": [], "old": []}"#,
+            r#"{"default": ["std", "derive"], "std": [], "derive": ["dep:serde_derive"]}"#,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|feature| (feature.name, feature.change))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                (String::from("default"), changed("std", "derive, std")),
+                (String::from("derive"), added("dep:serde_derive")),
+                (String::from("old"), Change::Removed(String::new())),
+            ]
+        );
+        assert!(feature_changes("", "{}").unwrap().is_empty());
+        assert!(feature_changes("not json", "{}").is_err());
+    }
+
+    #[test]
+    fn versions_in_the_fixture() {
+        let (_dir, db, cache, _index) = crate::bench::import_fixture(&SearchConfig {
+            backend: Backend::Descriptions,
+            ..SearchConfig::default()
+        })
+        .unwrap();
+
+        let diff = VersionDiff::load(&db, &cache, "axum", "0.9.0", "1.0.0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(diff.license, None);
+        assert_eq!(diff.rust_version, Some(added("1.60")));
+        assert_eq!(diff.size_delta, Some(0));
+        assert!(diff.features.is_empty());
+        let dependencies = diff
+            .dependencies
+            .into_iter()
+            .map(|dependency| {
+                (
+                    dependency.name,
+                    dependency.kind,
+                    dependency.explicit_name,
+                    dependency.change,
+                )
+            })
+            .collect::<Vec<_>>();
+        // The renamed rand is told apart from the other one, which is
+        // unchanged.
+        assert_eq!(
+            dependencies,
+            [
+                (
+                    String::from("hyper"),
+                    DependencyKind::Normal,
+                    None,
+                    changed("^0.14", "^1")
+                ),
+                (
+                    String::from("rand"),
+                    DependencyKind::Normal,
+                    Some(String::from("rand07")),
+                    added("^0.7")
+                ),
+                (
+                    String::from("serde_json"),
+                    DependencyKind::Normal,
+                    None,
+                    Change::Removed(String::from("^1"))
+                ),
+                (
+                    String::from("tokio"),
+                    DependencyKind::Dev,
+                    None,
+                    added("^1")
+                ),
+            ]
+        );
+
+        assert!(VersionDiff::load(&db, &cache, "axum", "0.9.0", "2.0.0")
+            .unwrap()
+            .is_none());
+        assert!(VersionDiff::load(&db, &cache, "missing", "0.9.0", "1.0.0")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use time::{Date, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::cache::{CachedCrate, CrateTable};
+
+/// The downloads percentile that discovery favors most. Crates become less
+/// likely to be picked the further their percentile is from it, and the most
+/// downloaded crates are never picked since most visitors already know them.
+const IDEAL_PERCENTILE: f32 = 0.65;
+
+/// How far a crate's downloads percentile can be from [`IDEAL_PERCENTILE`]
+/// and still be picked.
+const PERCENTILE_RANGE: f32 = 0.35;
+
+/// Crates without a release in this many days are never picked.
+const MAX_DAYS_SINCE_RELEASE: i64 = 365;
+
+/// The crate picked for the last day [`crate_of_the_day`] was called, and the
+/// generation of the crate table it was picked from.
+static TODAY: Mutex<Option<(Date, u64, Option<u64>)>> = Mutex::new(None);
+
+/// Returns today's featured crate. The pick only changes daily, or when an
+/// import changes which crates are eligible.
+pub(crate) fn crate_of_the_day(crates: &Arc<CrateTable>) -> Option<CachedCrate> {
+    let today = OffsetDateTime::now_utc().date();
+    let mut picked = TODAY.lock().expect("poisoned");
+    let id = match *picked {
+        Some((date, generation, id)) if date == today && generation == crates.generation() => id,
+        _ => {
+            let seed = u64::try_from(today.to_julian_day()).unwrap_or_default();
+            let id = pick(crates, today, seed).map(|krate| krate.id());
+            *picked = Some((today, crates.generation(), id));
+            id
+        }
+    };
+    id.and_then(|id| crates.get(id))
+}
+
+/// Returns a random crate, favoring the same crates as
+/// [`crate_of_the_day`].
+pub(crate) fn random(crates: &Arc<CrateTable>) -> Option<CachedCrate> {
+    let seed = Uuid::new_v4().as_u64_pair().0;
+    pick(crates, OffsetDateTime::now_utc().date(), seed)
+}
+
+/// Picks a crate with a probability proportional to its [`weight`], using
+/// `seed` as the source of randomness. The same seed always picks the same
+/// crate from the same table, regardless of the order crates are stored in.
+fn pick(crates: &Arc<CrateTable>, today: Date, seed: u64) -> Option<CachedCrate> {
+    // Weighted sampling by keeping the crate with the largest `u^(1/w)`,
+    // where `u` is a uniform random number derived from the crate's id.
+    crates
+        .iter()
+        .filter_map(|krate| {
+            let weight = weight(&krate, today)?;
+            let uniform = (mix(seed ^ mix(krate.id())) >> 11) as f64 / (1_u64 << 53) as f64;
+            Some((uniform.powf(1. / weight), krate))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, krate)| krate)
+}
+
+/// Returns how strongly `krate` should be favored, or None if it shouldn't be
+/// picked at all. Healthy crates that are moderately downloaded and recently
+/// released are favored.
+fn weight(krate: &CachedCrate, today: Date) -> Option<f64> {
+    if krate.likely_placeholder()
+        || krate.blocked()
+        || krate.deprecation().is_some()
+        || krate.description().trim().is_empty()
+    {
+        return None;
+    }
+
+    let distance = (krate.downloads_percentile() - IDEAL_PERCENTILE).abs();
+    let popularity = 1. - distance / PERCENTILE_RANGE;
+    let days = (today - krate.last_published()?).whole_days();
+    let freshness = 1. - days as f32 / MAX_DAYS_SINCE_RELEASE as f32;
+    let weight = popularity * freshness.min(1.);
+    (weight > 0.).then_some(f64::from(weight))
+}
+
+/// The SplitMix64 finalizer, which scrambles `value` so that similar inputs
+/// produce unrelated outputs.
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::{schema::Crate, CrateResult};
+
+/// Suffixes added to a crate's normalized name when publishing a fork of it.
+/// Only affixes that unambiguously mark a fork are stripped, since others,
+/// such as `-fixed` in `bytes-fixed`, are often part of an unrelated crate's
+/// name.
+const FORK_SUFFIXES: &[&str] = &["_fork", "_patched"];
+
+/// Prefixes added to a crate's normalized name when publishing a fork of it.
+const FORK_PREFIXES: &[&str] = &["forked_"];
+
+/// Descriptions shorter than this are too generic to identify copies with.
+const MIN_DESCRIPTION_LENGTH: usize = 32;
+
+/// Collapses results that look like forks or copies of a higher-ranked result
+/// into that result's `similar` list, preserving the order of the remaining
+/// results.
+///
+/// A result is considered similar to an earlier one if its normalized name is
+/// the earlier result's name with a fork affix added (e.g. `serde-json-fork`),
+/// or if both have the same description.
+pub fn collapse_similar(results: Vec<CrateResult>) -> Vec<CrateResult> {
+    let mut collapsed = Vec::<CrateResult>::with_capacity(results.len());
+    let mut leaders_by_name = HashMap::<String, usize>::new();
+    let mut leaders_by_description = HashMap::<String, usize>::new();
+    for result in results {
+        let name = Crate::normalized_name(result.result.name());
+        let base_name = fork_base_name(&name);
+        let description = normalized_description(result.result.description());
+
+        let leader = leaders_by_name
+            .get(base_name)
+            .or_else(|| {
+                description
+                    .as_ref()
+                    .and_then(|description| leaders_by_description.get(description))
+            })
+            .copied();
+        if let Some(leader) = leader {
+            collapsed[leader].similar.push(result);
+        } else {
+            let index = collapsed.len();
+            leaders_by_name
+                .entry(base_name.to_string())
+                .or_insert(index);
+            leaders_by_name.entry(name).or_insert(index);
+            if let Some(description) = description {
+                leaders_by_description.entry(description).or_insert(index);
+            }
+            collapsed.push(result);
+        }
+    }
+
+    collapsed
+}
+
+/// Returns the normalized `name` with any fork affix removed.
+fn fork_base_name(name: &str) -> &str {
+    let suffixed = FORK_SUFFIXES
+        .iter()
+        .filter_map(|suffix| name.strip_suffix(suffix));
+    let prefixed = FORK_PREFIXES
+        .iter()
+        .filter_map(|prefix| name.strip_prefix(prefix));
+    suffixed
+        .chain(prefixed)
+        .find(|base| !base.is_empty())
+        .unwrap_or(name)
+}
+
+fn normalized_description(description: &str) -> Option<String> {
+    let description = description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (description.len() >= MIN_DESCRIPTION_LENGTH).then_some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_name(name: &str) -> String {
+        fork_base_name(&Crate::normalized_name(name)).to_string()
+    }
+
+    #[test]
+    fn forks_are_grouped_with_the_original() {
+        for fork in ["serde-json-fork", "serde_json_patched", "forked-serde-json"] {
+            assert_eq!(base_name(fork), "serde_json", "{fork}");
+        }
+    }
+
+    #[test]
+    fn other_affixes_are_kept() {
+        for name in [
+            "bytes-fixed",
+            "rustls-mirror",
+            "tokio-hotfix",
+            "unofficial-discord",
+            "serde-json-forked",
+            "fork-union",
+            "patched-serde",
+            "pitchfork",
+        ] {
+            assert_eq!(base_name(name), Crate::normalized_name(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn bare_affixes_are_kept() {
+        for name in ["fork", "patched", "forked", "_fork", "forked_"] {
+            assert_eq!(fork_base_name(name), name);
+        }
+    }
+}
@@ -1,101 +1,422 @@
 use std::{
-    collections::{HashMap, HashSet},
-    path::Path,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
 use bonsaidb::{
     core::{
         connection::Connection,
-        schema::SerializedCollection,
+        document::{CollectionDocument, Header},
+        schema::{Collection, SerializedCollection, SerializedView},
         transaction::{Operation, Transaction},
     },
     local::Database,
 };
 use reqwest::header::LAST_MODIFIED;
 use serde::Deserialize;
-use tantivy::{doc, IndexWriter, Term};
 use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time};
-use tokio::{io::AsyncWriteExt, process::Command};
 
 use crate::{
+    advisories, aliases, archive,
     cache::Cache,
-    schema::{self, CalendarDate, ImportState, OwnerId, VersionDownloadKey},
-    SearchIndex,
+    config::RegistryConfig,
+    cooccurrence, dependency_graph, duplicates,
+    embeddings::{self, EmbeddingsConfig},
+    forks, keyword_trends, language, profile, publish_waves,
+    replication::Publisher,
+    reviews::{self, ReviewsConfig},
+    rollup,
+    schema::{
+        self, CalendarDate, DownloadsByDate, ImportState, OwnerId, Registry, VersionDownloadKey,
+    },
+    search_backend::IndexWriter,
+    throttle::Throttle,
+    unsafe_usage, webhooks, SearchIndex,
 };
 
-// TODO this reference to cache means it won't ever drop because this task never exits.
-pub(super) async fn import_continuously(
+/// Where an [`Importer`] reads dumps from.
+#[derive(Clone, Debug)]
+pub enum ImportSource {
+    /// Downloads the latest crates.io dump, then imports each alternate
+    /// registry's local dump.
+    Download {
+        /// Where the dump is downloaded and extracted.
+        data_dir: PathBuf,
+        registries: Vec<RegistryConfig>,
+        unsafe_reports: Vec<String>,
+        reviews: ReviewsConfig,
+        embeddings: EmbeddingsConfig,
+    },
+    /// Imports dumps that have already been extracted, without accessing the
+    /// network.
+    Offline {
+        dump_path: String,
+        registries: Vec<RegistryConfig>,
+        unsafe_reports: Vec<String>,
+    },
+}
+
+/// Runs imports from an [`ImportSource`], ensuring that only one import runs
+/// at a time.
+#[derive(Clone, Debug)]
+pub struct Importer {
     database: Database,
     cache: Cache,
     index: SearchIndex,
-) -> anyhow::Result<()> {
-    // loop {
-    if let Some(latest_dump) = download_new_dump(&database).await? {
-        let (sender, receiver) = std::sync::mpsc::sync_channel(100_000);
+    source: Arc<ImportSource>,
+    running: Arc<AtomicBool>,
+    /// Publishes a snapshot for replicas after each import.
+    publisher: Option<Publisher>,
+}
 
-        let index_writer = index.index.writer(4 * 1024 * 1024)?;
-        let importer = tokio::task::spawn_blocking({
-            let database = database.clone();
-            let index = index.clone();
+impl Importer {
+    pub fn new(database: Database, cache: Cache, index: SearchIndex, source: ImportSource) -> Self {
+        Self {
+            database,
+            cache,
+            index,
+            source: Arc::new(source),
+            running: Arc::default(),
+            publisher: None,
+        }
+    }
 
-            move || import_dump(latest_dump, &database, sender, index_writer, index)
-        });
+    /// Publishes a snapshot with `publisher` after each successful import.
+    pub fn with_publisher(mut self, publisher: Option<Publisher>) -> Self {
+        self.publisher = publisher;
+        self
+    }
 
-        let mut tx = Transaction::new();
-        let mut op_count = 0;
-        let mut uncompacted_operations = 0;
-        while let Ok(operation) = receiver.recv() {
-            tx.operations.push(operation);
-            if tx.operations.len() >= 100_000 {
-                let new_count = op_count + tx.operations.len();
-                uncompacted_operations += tx.operations.len();
-                println!("Committing {op_count}:{new_count} changes");
-                tx.apply(&database)?;
-                tx = Transaction::new();
-                op_count = new_count;
-
-                // Load new data into the cache during a long import.
-                cache.refresh()?;
-            }
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
 
-            if uncompacted_operations > 2_000_000 {
-                // Keep disk space down by compacting frequently.
-                database.compact()?;
-                uncompacted_operations = 0;
+    /// Imports from the source, returning an error if an import is already
+    /// running.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let Some(_running) = self.claim() else { anyhow::bail!("an import is already running") };
+        self.import().await
+    }
+
+    /// Starts an import in the background, returning false if an import is
+    /// already running.
+    pub fn start(&self) -> bool {
+        let Some(running) = self.claim() else { return false };
+        let importer = self.clone();
+        tokio::spawn(async move {
+            let _running = running;
+            if let Err(err) = importer.import().await {
+                tracing::error!("error importing: {err}");
             }
+        });
+        true
+    }
+
+    fn claim(&self) -> Option<RunningImport> {
+        (!self.running.swap(true, Ordering::AcqRel)).then(|| RunningImport(self.running.clone()))
+    }
+
+    async fn import(&self) -> anyhow::Result<()> {
+        self.import_source().await?;
+        if let Some(publisher) = self.publisher.clone() {
+            tokio::task::spawn_blocking(move || publisher.publish()).await??;
         }
-        drop(receiver);
+        Ok(())
+    }
 
-        if !tx.operations.is_empty() {
-            let new_count = op_count + tx.operations.len();
-            uncompacted_operations += tx.operations.len();
-            println!("Committing {op_count}:{new_count} changes");
-            tx.apply(&database)?;
-            op_count = new_count;
-            cache.refresh()?;
+    async fn import_source(&self) -> anyhow::Result<()> {
+        match &*self.source {
+            ImportSource::Download {
+                data_dir,
+                registries,
+                unsafe_reports,
+                reviews,
+                embeddings,
+            } => {
+                import_continuously(
+                    self.database.clone(),
+                    self.cache.clone(),
+                    self.index.clone(),
+                    data_dir.clone(),
+                    registries.clone(),
+                    unsafe_reports.clone(),
+                    reviews.clone(),
+                    embeddings.clone(),
+                )
+                .await
+            }
+            ImportSource::Offline {
+                dump_path,
+                registries,
+                unsafe_reports,
+            } => {
+                let dump_path = dump_path.clone();
+                let registries = registries.clone();
+                let unsafe_reports = unsafe_reports.clone();
+                let importer = self.clone();
+                tokio::task::spawn_blocking(move || {
+                    import_offline(
+                        &dump_path,
+                        &registries,
+                        &unsafe_reports,
+                        &importer.database,
+                        &importer.cache,
+                        &importer.index,
+                    )
+                })
+                .await?
+            }
         }
+    }
+}
 
-        importer.await??;
+/// Marks an import as running until dropped, even if the import panics.
+struct RunningImport(Arc<AtomicBool>);
 
-        // This cleans up the database once per day-ish.
-        if op_count > 0 && uncompacted_operations > 0 {
-            println!("Compacting.");
-            database.compact()?;
-        }
+impl Drop for RunningImport {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
 
-        println!("Done importing.");
+// TODO this reference to cache means it won't ever drop because this task never exits.
+pub async fn import_continuously(
+    database: Database,
+    cache: Cache,
+    index: SearchIndex,
+    data_dir: PathBuf,
+    registries: Vec<RegistryConfig>,
+    unsafe_reports: Vec<String>,
+    reviews: ReviewsConfig,
+    embeddings: EmbeddingsConfig,
+) -> anyhow::Result<()> {
+    // loop {
+    if let Some(latest_dump) = download_new_dump(&database, &data_dir).await? {
+        import(
+            data_dir.join(latest_dump).to_string_lossy().into_owned(),
+            Registry::crates_io(),
+            &database,
+            &cache,
+            &index,
+        )
+        .await?;
     } else {
         println!("No new data dumps are available.");
     }
+
+    // Alternate registries are imported from local dumps, which are only
+    // re-imported once they've been replaced.
+    for registry in registries {
+        if !registry_dump_changed(&database, &registry)? {
+            println!("Registry {}'s dump hasn't changed.", registry.name);
+            continue;
+        }
+        println!("Importing registry {}.", registry.name);
+        let dump_path = registry.dump_path.to_string_lossy().into_owned();
+        import(dump_path, registry.registry(), &database, &cache, &index).await?;
+    }
+
+    dependency_graph::update_metrics(&database)?;
+    cooccurrence::update(&database)?;
+    duplicates::update(&database)?;
+    forks::update(&database)?;
+    publish_waves::update(&database)?;
+    keyword_trends::record(&database)?;
+    profile::update(&database)?;
+    unsafe_usage::import(&database, &unsafe_reports).await?;
+    reviews::import(&database, &reviews, &data_dir).await?;
+    embeddings::update(&database, &embeddings).await?;
+    rollup::roll_up(&database)?;
+    cache.refresh()?;
+
+    advisories::import(&database, &data_dir).await?;
+    // Advisories flag unmaintained and malicious crates.
+    cache.refresh()?;
     //     // Check for new dumps every hour.
     //     tokio::time::sleep(std::time::Duration::from_secs(60 * 60)).await;
     // }
     Ok(())
 }
 
-async fn download(client: reqwest::Client) -> anyhow::Result<(String, String)> {
+/// Imports the dump extracted at `dump_path` from `registry`.
+async fn import(
+    dump_path: String,
+    registry: Registry,
+    database: &Database,
+    cache: &Cache,
+    index: &SearchIndex,
+) -> anyhow::Result<()> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(100_000);
+
+    let index_writer = index.writer()?;
+    let importer = tokio::task::spawn_blocking({
+        let database = database.clone();
+
+        move || import_dump(dump_path, &registry, &database, sender, index_writer)
+    });
+
+    // Throttled batches sleep between commits, which mustn't block the
+    // runtime's workers.
+    tokio::task::spawn_blocking({
+        let database = database.clone();
+        let cache = cache.clone();
+        move || apply_operations(receiver, &database, Some(&cache))
+    })
+    .await??;
+
+    let new_releases = importer.await??;
+    webhooks::deliver(database, &new_releases);
+
+    println!("Done importing.");
+    Ok(())
+}
+
+/// Imports the dump extracted at `dump_path`, blocking until it completes,
+/// without downloading anything. This is used to load fixture dumps.
+pub fn import_local_dump(
+    dump_path: &str,
+    database: &Database,
+    index: &SearchIndex,
+) -> anyhow::Result<()> {
+    import_blocking(
+        dump_path.to_string(),
+        Registry::crates_io(),
+        database,
+        None,
+        index,
+    )?;
+    dependency_graph::update_metrics(database)?;
+    profile::update(database)
+}
+
+/// Imports the crates.io dump extracted at `dump_path` and each alternate
+/// registry's dump without accessing the network, blocking until the imports
+/// complete. Unsafe usage reports are imported from the local files in
+/// `unsafe_reports`.
+pub fn import_offline(
+    dump_path: &str,
+    registries: &[RegistryConfig],
+    unsafe_reports: &[String],
+    database: &Database,
+    cache: &Cache,
+    index: &SearchIndex,
+) -> anyhow::Result<()> {
+    import_blocking(
+        dump_path.to_string(),
+        Registry::crates_io(),
+        database,
+        Some(cache),
+        index,
+    )?;
+    for registry in registries {
+        println!("Importing registry {}.", registry.name);
+        import_blocking(
+            registry.dump_path.to_string_lossy().into_owned(),
+            registry.registry(),
+            database,
+            Some(cache),
+            index,
+        )?;
+    }
+
+    dependency_graph::update_metrics(database)?;
+    cooccurrence::update(database)?;
+    duplicates::update(database)?;
+    forks::update(database)?;
+    publish_waves::update(database)?;
+    keyword_trends::record(database)?;
+    profile::update(database)?;
+    unsafe_usage::import_offline(database, unsafe_reports)?;
+    rollup::roll_up(database)?;
+    cache.refresh()?;
+
+    println!("Done importing.");
+    Ok(())
+}
+
+fn import_blocking(
+    dump_path: String,
+    registry: Registry,
+    database: &Database,
+    cache: Option<&Cache>,
+    index: &SearchIndex,
+) -> anyhow::Result<()> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(100_000);
+
+    let index_writer = index.writer()?;
+    let importer = std::thread::spawn({
+        let database = database.clone();
+
+        move || import_dump(dump_path, &registry, &database, sender, index_writer)
+    });
+
+    apply_operations(receiver, database, cache)?;
+
+    importer
+        .join()
+        .map_err(|_| anyhow::anyhow!("dump importer panicked"))??;
+    Ok(())
+}
+
+/// The number of operations an import commits before compacting the database.
+const MAX_UNCOMPACTED_OPERATIONS: usize = 2_000_000;
+
+/// Commits the operations produced by an importer in batches, returning the
+/// total number of operations applied. The database is compacted after every
+/// [`MAX_UNCOMPACTED_OPERATIONS`], and the cache is refreshed once every batch
+/// is committed, since refreshing reloads every crate.
+fn apply_operations(
+    receiver: std::sync::mpsc::Receiver<Operation>,
+    database: &Database,
+    cache: Option<&Cache>,
+) -> anyhow::Result<usize> {
+    let mut throttle = Throttle::new();
+    let mut tx = Transaction::new();
+    let mut op_count = 0;
+    let mut uncompacted_operations = 0;
+    while let Ok(operation) = receiver.recv() {
+        tx.operations.push(operation);
+        if tx.operations.len() >= throttle.batch_size() {
+            let new_count = op_count + tx.operations.len();
+            println!("Committing {op_count}:{new_count} changes");
+            tx.apply(database)?;
+            tx = Transaction::new();
+            let batch_size = new_count - op_count;
+            op_count = new_count;
+            uncompacted_operations += batch_size;
+            throttle.pause(batch_size);
+        }
+
+        if uncompacted_operations > MAX_UNCOMPACTED_OPERATIONS {
+            // Keep disk space down during large imports, rather than waiting
+            // for the scheduled compaction.
+            println!("Compacting.");
+            database.compact()?;
+            uncompacted_operations = 0;
+        }
+    }
+    drop(receiver);
+
+    if !tx.operations.is_empty() {
+        let new_count = op_count + tx.operations.len();
+        println!("Committing {op_count}:{new_count} changes");
+        tx.apply(database)?;
+        op_count = new_count;
+    }
+    if let Some(cache) = cache {
+        cache.refresh()?;
+    }
+
+    Ok(op_count)
+}
+
+async fn download(client: reqwest::Client, data_dir: &Path) -> anyhow::Result<(String, String)> {
     println!("Downloading new dump.");
-    let mut response = client
+    let response = client
         .get("https://static.crates.io/db-dump.tar.gz")
         .send()
         .await?;
@@ -106,36 +427,20 @@ async fn download(client: reqwest::Client) -> anyhow::Result<(String, String)> {
         .to_str()?
         .to_string();
 
-    let mut file = tokio::fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open("db-dump.tar.gz")
-        .await?;
-    while let Some(chunk) = response.chunk().await? {
-        file.write_all(&chunk).await?;
-    }
-    drop(file);
-
-    if !Command::new("/usr/bin/tar")
-        .arg("-xzf")
-        .arg("db-dump.tar.gz")
-        .status()
-        .await?
-        .success()
-    {
-        anyhow::bail!("error extracting database dump");
-    }
+    archive::download_and_extract(response, &data_dir.join("db-dump.tar.gz"), data_dir).await?;
 
-    let latest_dump = find_latest_dump(true)
+    let latest_dump = find_latest_dump(data_dir, true)
         .await?
         .ok_or_else(|| anyhow::anyhow!("archive contained stale export"))?;
 
     Ok((latest_dump, last_modified))
 }
 
-async fn find_latest_dump(allow_stale: bool) -> anyhow::Result<Option<String>> {
-    let mut entries = tokio::fs::read_dir(".").await?;
+/// Returns the name of the newest dump extracted in `data_dir`, deleting the
+/// dumps that are stale unless `allow_stale` is true.
+async fn find_latest_dump(data_dir: &Path, allow_stale: bool) -> anyhow::Result<Option<String>> {
+    tokio::fs::create_dir_all(data_dir).await?;
+    let mut entries = tokio::fs::read_dir(data_dir).await?;
     let now = OffsetDateTime::now_utc();
     let mut latest_date = None;
     while let Some(entry) = entries.next_entry().await? {
@@ -149,14 +454,16 @@ async fn find_latest_dump(allow_stale: bool) -> anyhow::Result<Option<String>> {
             latest_date = latest_date.max(Some(file_name.to_string()));
         } else {
             // Delete this folder, because it's stale.
-            tokio::fs::remove_dir_all(&file_name).await?;
+            tokio::fs::remove_dir_all(entry.path()).await?;
         }
     }
 
     Ok(latest_date)
 }
 
-async fn download_new_dump(db: &Database) -> anyhow::Result<Option<String>> {
+/// Returns the name of the dump in `data_dir` to import, downloading a new
+/// dump if one has been published.
+async fn download_new_dump(db: &Database, data_dir: &Path) -> anyhow::Result<Option<String>> {
     let mut state = ImportState::get(&(), db)?
         .map(|d| d.contents)
         .unwrap_or_default();
@@ -180,7 +487,7 @@ async fn download_new_dump(db: &Database) -> anyhow::Result<Option<String>> {
         });
     drop(response);
 
-    let latest_date = find_latest_dump(!new_dump_available).await?;
+    let latest_date = find_latest_dump(data_dir, !new_dump_available).await?;
 
     if let Some(latest_date) = latest_date {
         if state
@@ -195,7 +502,7 @@ async fn download_new_dump(db: &Database) -> anyhow::Result<Option<String>> {
             Ok(None)
         }
     } else {
-        let (path, new_last_modified) = download(http).await?;
+        let (path, new_last_modified) = download(http, data_dir).await?;
 
         state.downloaded_last_modified = Some(new_last_modified);
         state.overwrite_into(&(), db)?;
@@ -204,6 +511,34 @@ async fn download_new_dump(db: &Database) -> anyhow::Result<Option<String>> {
     }
 }
 
+/// Identifies the contents of the dump extracted at `dump_path`: the export
+/// time recorded in its `metadata.json`, or, for dumps without one, when its
+/// crates were last written.
+fn dump_version(dump_path: &Path) -> anyhow::Result<String> {
+    #[derive(Deserialize)]
+    struct Metadata {
+        timestamp: String,
+    }
+
+    if let Ok(metadata) = std::fs::read_to_string(dump_path.join("metadata.json")) {
+        if let Ok(metadata) = serde_json::from_str::<Metadata>(&metadata) {
+            return Ok(metadata.timestamp);
+        }
+    }
+    let modified = std::fs::metadata(dump_path.join("data").join("crates.csv"))?.modified()?;
+    Ok(timestamp(OffsetDateTime::from(modified)))
+}
+
+/// Returns whether `registry`'s dump differs from the one it was last
+/// imported from.
+fn registry_dump_changed(db: &Database, registry: &RegistryConfig) -> anyhow::Result<bool> {
+    let state = ImportState::get(&(), db)?
+        .map(|d| d.contents)
+        .unwrap_or_default();
+    let version = dump_version(&registry.dump_path)?;
+    Ok(state.registries.get(&registry.name) != Some(&version))
+}
+
 fn parse_folder_date(file_name: &str) -> Option<OffsetDateTime> {
     let (date, hms) = file_name.rsplit_once('-')?;
     let date = parse_iso_date(date).ok()?;
@@ -221,52 +556,149 @@ fn parse_folder_date(file_name: &str) -> Option<OffsetDateTime> {
 
 fn import_dump(
     dump_date: String,
+    registry: &Registry,
     db: &Database,
     tx_sender: std::sync::mpsc::SyncSender<Operation>,
-    index_writer: IndexWriter,
-    index: SearchIndex,
-) -> anyhow::Result<()> {
+    index_writer: Box<dyn IndexWriter>,
+) -> anyhow::Result<NewReleases> {
     let path = Path::new(&dump_date);
     let data_folder = path.join("data");
+    // Read before importing, so that a dump replaced during the import is
+    // imported again.
+    let version = (!registry.is_crates_io())
+        .then(|| dump_version(path))
+        .transpose()?;
+
+    // Everything is new during the first import, so nothing is tracked.
+    let mut state = ImportState::get(&(), db)?
+        .map(|d| d.contents)
+        .unwrap_or_default();
+    let first_import = if registry.is_crates_io() {
+        state.last_dump_imported.is_none()
+    } else {
+        !state.registries.contains_key(&registry.name)
+    };
+    let mut new_releases = NewReleases::default();
+    let mut tracked = (!first_import).then_some(&mut new_releases);
 
     // Now we can import the crates structure.
 
-    apply_crate_changes(&data_folder, &tx_sender, db, index_writer, index)?;
-    // apply_keyword_changes(&data_folder, &tx_sender, db)?;
-    // apply_category_changes(&data_folder, &tx_sender, db)?;
-    // let version_crates = apply_version_changes(&data_folder, &tx_sender, db)?;
-    // apply_version_download_changes(&data_folder, &tx_sender, db, &version_crates)?;
-
-    let mut state = ImportState::get(&(), db)?.expect("downloading inserts state");
-    state.contents.last_dump_imported = Some(dump_date);
-    tx_sender.send(Operation::update_serialized::<ImportState>(
-        state.header,
-        &state.contents,
+    apply_crate_changes(
+        &data_folder,
+        registry,
+        &tx_sender,
+        db,
+        index_writer,
+        tracked.as_deref_mut(),
+        &mut state.seeded_aliases,
+    )?;
+    apply_keyword_changes(&data_folder, registry, &tx_sender, db)?;
+    apply_category_changes(&data_folder, registry, &tx_sender, db)?;
+    let version_crates = apply_version_changes(
+        &data_folder,
+        registry,
+        &tx_sender,
+        db,
+        tracked.as_deref_mut(),
+    )?;
+    apply_dependency_changes(&data_folder, registry, &tx_sender, db, &version_crates)?;
+    let now = OffsetDateTime::now_utc();
+    let reconcile = state
+        .downloads_reconciled
+        .get(&registry.name)
+        .and_then(|reconciled| parse_timestamp(reconciled).ok())
+        .map_or(true, |reconciled| {
+            now - reconciled >= RECONCILIATION_INTERVAL
+        });
+    apply_version_download_changes(
+        &data_folder,
+        registry,
+        &tx_sender,
+        db,
+        &version_crates,
+        reconcile,
+    )?;
+    if reconcile {
+        state
+            .downloads_reconciled
+            .insert(registry.name.clone(), timestamp(now));
+    }
+
+    if registry.is_crates_io() {
+        // Only the dump's directory name is kept, which is compared with the
+        // names of newly downloaded dumps.
+        let name = path.file_name().map_or_else(
+            || dump_date.clone(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        state.last_dump_imported = Some(name);
+    } else if let Some(version) = version {
+        state.registries.insert(registry.name.clone(), version);
+    }
+    tx_sender.send(Operation::overwrite_serialized::<ImportState, _>(
+        &(),
+        &state,
     )?)?;
 
-    Ok(())
+    Ok(new_releases)
+}
+
+/// The crates and versions that were published since the previous import.
+#[derive(Debug, Default)]
+pub struct NewReleases {
+    pub crates: Vec<u64>,
+    /// The crate id and version number of each new version.
+    pub versions: Vec<(u64, String)>,
 }
 
 fn apply_crate_changes(
     data_folder: &Path,
+    registry: &Registry,
     tx: &std::sync::mpsc::SyncSender<Operation>,
     db: &Database,
-    mut index_writer: IndexWriter,
-    index: SearchIndex,
+    mut index_writer: Box<dyn IndexWriter>,
+    mut new_releases: Option<&mut NewReleases>,
+    seeded_aliases: &mut HashSet<String>,
 ) -> anyhow::Result<()> {
     // Gather the keywords and categories for the crates
     println!("Parsing crate keywords.");
-    let mut keyword_ids_by_crate = load_crate_keywords(data_folder)?;
+    let mut keyword_ids_by_crate = load_crate_keywords(data_folder, registry)?;
     println!("Parsing crate categories.");
-    let mut category_ids_by_crate = load_crate_categories(data_folder)?;
+    let mut category_ids_by_crate = load_crate_categories(data_folder, registry)?;
     println!("Parsing crate owners.");
-    let mut owners = load_crate_owners(data_folder)?;
+    let mut owners = load_crate_owners(data_folder, registry)?;
+    let owner_logins = load_owner_logins(data_folder, registry)?;
+    let imported_at = timestamp(OffsetDateTime::now_utc());
+    let existing_aliases = schema::CrateAlias::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id.clone(), d))
+        .collect::<HashMap<_, _>>();
+    let mut manual_aliases = existing_aliases
+        .values()
+        .filter(|d| d.contents.source == schema::AliasSource::Manual)
+        .map(|d| d.header.id.clone())
+        .collect::<HashSet<_>>();
+    // The known aliases refer to crates on crates.io. Each is only seeded
+    // once, so that an admin can delete it.
+    for (name, alias) in aliases::known_aliases().filter(|_| registry.is_crates_io()) {
+        if seeded_aliases.insert(name.clone()) && !existing_aliases.contains_key(&name) {
+            tx.send(Operation::overwrite_serialized::<schema::CrateAlias, _>(
+                &name, &alias,
+            )?)?;
+            manual_aliases.insert(name);
+        }
+    }
+
+    let mut crates_by_owner = HashMap::<OwnerId, Vec<u64>>::new();
 
     println!("Parsing crates.");
     let mut crates = csv::Reader::from_reader(std::fs::File::open(data_folder.join("crates.csv"))?);
     for row in crates.deserialize() {
         let cr: Crate = row?;
-        let id = cr.id;
+        let id = registry.key(cr.id)?;
+        let owners_added_at = owners.remove(&cr.id).unwrap_or_default();
+        let language = language::detect(&cr.description, &cr.readme).map(String::from);
         let cr = schema::Crate {
             created_at: cr.created_at,
             description: cr.description,
@@ -280,23 +712,35 @@ fn apply_crate_changes(
             updated_at: cr.updated_at,
             keywords: keyword_ids_by_crate.remove(&cr.id).unwrap_or_default(),
             category_ids: category_ids_by_crate.remove(&cr.id).unwrap_or_default(),
-            owners: owners.remove(&cr.id).unwrap_or_default(),
+            owners: owners_added_at.keys().copied().collect(),
+            registry: (!registry.is_crates_io()).then(|| registry.name.clone()),
+            language,
         };
 
+        if registry.is_crates_io() {
+            apply_alias_changes(&cr, &existing_aliases, &manual_aliases, tx)?;
+            for owner in &cr.owners {
+                crates_by_owner.entry(*owner).or_default().push(id);
+            }
+        }
+
         if let Some(existing) = schema::Crate::get(&id, db)? {
+            apply_ownership_changes(
+                id,
+                &existing.contents.owners,
+                &owners_added_at,
+                &owner_logins,
+                &imported_at,
+                tx,
+            )?;
             if existing.contents == cr {
                 continue;
-            } else {
-                index_writer.delete_term(Term::from_field_u64(index.id, id));
             }
+        } else if let Some(new_releases) = &mut new_releases {
+            new_releases.crates.push(id);
         }
 
-        index_writer.add_document(doc! {
-            index.id => id,
-            index.name => cr.name.clone(),
-            index.description => cr.description.clone(),
-            index.readme => cr.readme.clone(),
-        })?;
+        index_writer.replace(id, &cr)?;
 
         tx.send(Operation::overwrite_serialized::<schema::Crate, _>(
             &id, &cr,
@@ -305,10 +749,123 @@ fn apply_crate_changes(
 
     index_writer.commit()?;
 
+    if registry.is_crates_io() {
+        apply_owner_changes(crates_by_owner, &owner_logins, db, tx)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the [`schema::Owner`] of each login with the crates it owns in
+/// the dump, deleting owners that no longer own any crates.
+fn apply_owner_changes(
+    crates_by_owner: HashMap<OwnerId, Vec<u64>>,
+    owner_logins: &HashMap<OwnerId, String>,
+    db: &Database,
+    tx: &std::sync::mpsc::SyncSender<Operation>,
+) -> anyhow::Result<()> {
+    let mut existing = schema::Owner::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id.clone(), d))
+        .collect::<HashMap<_, _>>();
+    for (owner, mut crates) in crates_by_owner {
+        let Some(login) = owner_logins.get(&owner) else { continue };
+        crates.sort_unstable();
+        let key = login.to_lowercase();
+        let owner = schema::Owner {
+            login: login.clone(),
+            crates,
+        };
+        if existing
+            .remove(&key)
+            .map_or(true, |existing| existing.contents != owner)
+        {
+            tx.send(Operation::overwrite_serialized::<schema::Owner, _>(
+                &key, &owner,
+            )?)?;
+        }
+    }
+    for stale in existing.into_values() {
+        tx.send(Operation::delete(
+            schema::Owner::collection_name(),
+            Header::try_from(stale.header)?,
+        ))?;
+    }
+    Ok(())
+}
+
+/// Records or clears a heuristically detected deprecation for `cr`. Manually
+/// entered aliases are left untouched.
+fn apply_alias_changes(
+    cr: &schema::Crate,
+    existing_aliases: &HashMap<String, CollectionDocument<schema::CrateAlias>>,
+    manual_aliases: &HashSet<String>,
+    tx: &std::sync::mpsc::SyncSender<Operation>,
+) -> anyhow::Result<()> {
+    let name = schema::Crate::normalized_name(&cr.name);
+    if manual_aliases.contains(&name) {
+        return Ok(());
+    }
+
+    let existing = existing_aliases.get(&name);
+    match (aliases::detect(cr), existing) {
+        (Some(alias), existing) if existing.map_or(true, |e| e.contents != alias) => {
+            tx.send(Operation::overwrite_serialized::<schema::CrateAlias, _>(
+                &name, &alias,
+            )?)?;
+        }
+        (None, Some(existing)) => {
+            tx.send(Operation::delete(
+                schema::CrateAlias::collection_name(),
+                Header::try_from(existing.header.clone())?,
+            ))?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Records an [`schema::OwnershipEvent`] for each owner added to or removed
+/// from a crate since the previous import.
+fn apply_ownership_changes(
+    crate_id: u64,
+    previous_owners: &HashSet<OwnerId>,
+    owners_added_at: &HashMap<OwnerId, String>,
+    owner_logins: &HashMap<OwnerId, String>,
+    imported_at: &str,
+    tx: &std::sync::mpsc::SyncSender<Operation>,
+) -> anyhow::Result<()> {
+    let added = owners_added_at
+        .iter()
+        .filter(|(owner, _)| !previous_owners.contains(owner))
+        .map(|(owner, added_at)| (*owner, true, added_at.as_str()));
+    // The dump doesn't include when an owner was removed, so the time of the
+    // import is used instead.
+    let removed = previous_owners
+        .iter()
+        .filter(|owner| !owners_added_at.contains_key(owner))
+        .map(|owner| (*owner, false, imported_at));
+    for (owner, added, at) in added.chain(removed) {
+        tx.send(Operation::push_serialized::<schema::OwnershipEvent>(
+            &schema::OwnershipEvent {
+                crate_id,
+                owner,
+                login: owner_logins.get(&owner).cloned(),
+                added,
+                at: at.to_string(),
+            },
+        )?)?;
+    }
+
     Ok(())
 }
 
-fn load_crate_keywords(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<u64>>> {
+fn load_crate_keywords(
+    path: &Path,
+    registry: &Registry,
+) -> anyhow::Result<HashMap<u64, HashSet<u64>>> {
     let mut crate_keywords =
         csv::Reader::from_reader(std::fs::File::open(path.join("crates_keywords.csv"))?);
     let mut keyword_ids_by_crate = HashMap::new();
@@ -317,12 +874,15 @@ fn load_crate_keywords(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<u64>>
         let keywords = keyword_ids_by_crate
             .entry(row.crate_id)
             .or_insert_with(HashSet::default);
-        keywords.insert(row.keyword_id);
+        keywords.insert(registry.key(row.keyword_id)?);
     }
     Ok(keyword_ids_by_crate)
 }
 
-fn load_crate_categories(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<u64>>> {
+fn load_crate_categories(
+    path: &Path,
+    registry: &Registry,
+) -> anyhow::Result<HashMap<u64, HashSet<u64>>> {
     let mut crate_categories =
         csv::Reader::from_reader(std::fs::File::open(path.join("crates_categories.csv"))?);
     let mut category_ids_by_crate = HashMap::new();
@@ -331,12 +891,16 @@ fn load_crate_categories(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<u64
         let categories = category_ids_by_crate
             .entry(row.crate_id)
             .or_insert_with(HashSet::default);
-        categories.insert(row.category_id);
+        categories.insert(registry.key(row.category_id)?);
     }
     Ok(category_ids_by_crate)
 }
 
-fn load_crate_owners(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<OwnerId>>> {
+/// Loads each crate's owners, along with when they became an owner.
+fn load_crate_owners(
+    path: &Path,
+    registry: &Registry,
+) -> anyhow::Result<HashMap<u64, HashMap<OwnerId, String>>> {
     let mut crate_categories =
         csv::Reader::from_reader(std::fs::File::open(path.join("crate_owners.csv"))?);
     let mut owners_by_crate = HashMap::new();
@@ -344,22 +908,40 @@ fn load_crate_owners(path: &Path) -> anyhow::Result<HashMap<u64, HashSet<OwnerId
         let row: CrateOwners = row?;
         let categories = owners_by_crate
             .entry(row.crate_id)
-            .or_insert_with(HashSet::default);
+            .or_insert_with(HashMap::default);
         let owner = match row.owner_kind {
-            0 => OwnerId::User(row.owner_id),
-            1 => OwnerId::Team(row.owner_id),
+            0 => OwnerId::User(registry.key(row.owner_id)?),
+            1 => OwnerId::Team(registry.key(row.owner_id)?),
             _ => anyhow::bail!("expected owner kind: {}", row.owner_kind),
         };
-        categories.insert(owner);
+        categories.insert(owner, row.created_at);
     }
     Ok(owners_by_crate)
 }
 
+/// Loads the GitHub login of every user and team.
+fn load_owner_logins(path: &Path, registry: &Registry) -> anyhow::Result<HashMap<OwnerId, String>> {
+    let mut logins = HashMap::new();
+    let mut users = csv::Reader::from_reader(std::fs::File::open(path.join("users.csv"))?);
+    for row in users.deserialize() {
+        let row: Users = row?;
+        logins.insert(OwnerId::User(registry.key(row.id)?), row.gh_login);
+    }
+    let mut teams = csv::Reader::from_reader(std::fs::File::open(path.join("teams.csv"))?);
+    for row in teams.deserialize() {
+        let row: Teams = row?;
+        logins.insert(OwnerId::Team(registry.key(row.id)?), row.login);
+    }
+    Ok(logins)
+}
+
 fn apply_keyword_changes(
     data_folder: &Path,
+    registry: &Registry,
     tx: &std::sync::mpsc::SyncSender<Operation>,
     db: &Database,
 ) -> anyhow::Result<()> {
+    println!("Parsing keywords");
     let mut existing_keywords = schema::Keyword::all(db)
         .query()?
         .into_iter()
@@ -369,10 +951,11 @@ fn apply_keyword_changes(
         csv::Reader::from_reader(std::fs::File::open(data_folder.join("keywords.csv"))?);
     for row in keywords.deserialize() {
         let row: Keywords = row?;
+        let id = registry.key(row.id)?;
         let new = schema::Keyword {
             keyword: row.keyword,
         };
-        if let Some(existing) = existing_keywords.remove(&row.id) {
+        if let Some(existing) = existing_keywords.remove(&id) {
             if existing.contents != new {
                 tx.send(Operation::update_serialized::<schema::Keyword>(
                     existing.header,
@@ -381,7 +964,7 @@ fn apply_keyword_changes(
             }
         } else {
             tx.send(Operation::insert_serialized::<schema::Keyword>(
-                Some(&row.id),
+                Some(&id),
                 &new,
             )?)?;
         }
@@ -392,26 +975,42 @@ fn apply_keyword_changes(
 
 fn apply_category_changes(
     data_folder: &Path,
+    registry: &Registry,
     tx: &std::sync::mpsc::SyncSender<Operation>,
     db: &Database,
 ) -> anyhow::Result<()> {
+    println!("Parsing categories");
     let mut existing_categories = schema::Category::all(db)
         .query()?
         .into_iter()
         .map(|d| (d.header.id, d))
         .collect::<HashMap<_, _>>();
-    let mut keywords =
+    let mut categories =
         csv::Reader::from_reader(std::fs::File::open(data_folder.join("categories.csv"))?);
-    for row in keywords.deserialize() {
-        let row: Categories = row?;
-        let new = schema::Category {
+    let rows = categories
+        .deserialize()
+        .collect::<Result<Vec<Categories>, _>>()?;
+    // Parents are identified by their path, so every row is needed before
+    // any category's parent can be found.
+    let ids_by_path = rows
+        .iter()
+        .map(|row| Ok((row.path.clone(), registry.key(row.id)?)))
+        .collect::<anyhow::Result<HashMap<_, _>>>()?;
+    for row in rows {
+        let id = registry.key(row.id)?;
+        let mut new = schema::Category {
             category: row.category,
             created_at: row.created_at,
             description: row.description,
             path: row.path,
             slug: row.slug,
+            parent_id: None,
         };
-        if let Some(existing) = existing_categories.remove(&row.id) {
+        new.parent_id = new
+            .parent_path()
+            .and_then(|path| ids_by_path.get(path))
+            .copied();
+        if let Some(existing) = existing_categories.remove(&id) {
             if existing.contents != new {
                 tx.send(Operation::update_serialized::<schema::Category>(
                     existing.header,
@@ -420,7 +1019,7 @@ fn apply_category_changes(
             }
         } else {
             tx.send(Operation::insert_serialized::<schema::Category>(
-                Some(&row.id),
+                Some(&id),
                 &new,
             )?)?;
         }
@@ -433,8 +1032,10 @@ fn apply_category_changes(
 /// crate id.
 fn apply_version_changes(
     data_folder: &Path,
+    registry: &Registry,
     tx: &std::sync::mpsc::SyncSender<Operation>,
     db: &Database,
+    mut new_releases: Option<&mut NewReleases>,
 ) -> anyhow::Result<HashMap<u64, u64>> {
     println!("Parsing versions");
     let mut existing_versions = schema::Version::all(db)
@@ -447,9 +1048,11 @@ fn apply_version_changes(
         csv::Reader::from_reader(std::fs::File::open(data_folder.join("versions.csv"))?);
     for row in versions.deserialize() {
         let row: Versions = row?;
-        version_id_to_crate.insert(row.id, row.crate_id);
+        let id = registry.key(row.id)?;
+        let crate_id = registry.key(row.crate_id)?;
+        version_id_to_crate.insert(id, crate_id);
         let new = schema::Version {
-            crate_id: row.crate_id,
+            crate_id,
             checksum: row.checksum,
             created_at: row.created_at,
             updated_at: row.updated_at,
@@ -461,8 +1064,20 @@ fn apply_version_changes(
             version: row.num,
             published_by: row.published_by,
             yanked: row.yanked == Some('t'),
+            rust_version: row.rust_version,
         };
-        if let Some(existing) = existing_versions.remove(&row.id) {
+        if let Some(existing) = existing_versions.remove(&id) {
+            if existing.contents.yanked != new.yanked {
+                tx.send(Operation::push_serialized::<schema::YankEvent>(
+                    &schema::YankEvent {
+                        crate_id: new.crate_id,
+                        version_id: id,
+                        version: new.version.clone(),
+                        yanked: new.yanked,
+                        at: new.updated_at.clone(),
+                    },
+                )?)?;
+            }
             if existing.contents != new {
                 tx.send(Operation::update_serialized::<schema::Version>(
                     existing.header,
@@ -470,8 +1085,13 @@ fn apply_version_changes(
                 )?)?;
             }
         } else {
+            if let Some(new_releases) = &mut new_releases {
+                new_releases
+                    .versions
+                    .push((new.crate_id, new.version.clone()));
+            }
             tx.send(Operation::insert_serialized::<schema::Version>(
-                Some(&row.id),
+                Some(&id),
                 &new,
             )?)?;
         }
@@ -480,55 +1100,188 @@ fn apply_version_changes(
     Ok(version_id_to_crate)
 }
 
-fn apply_version_download_changes(
+fn apply_dependency_changes(
     data_folder: &Path,
+    registry: &Registry,
     tx: &std::sync::mpsc::SyncSender<Operation>,
     db: &Database,
     version_crates: &HashMap<u64, u64>,
 ) -> anyhow::Result<()> {
-    println!("Parsing version downloads");
-    // We only want to import the most recent download numbers. We re-import the previous 7 days to adjust for any changes to download numbers.
-    let last_imported = schema::VersionDownloads::all(db)
-        .limit(1)
-        .descending()
+    println!("Parsing dependencies");
+    let mut existing_dependencies = schema::Dependency::all(db)
         .query()?
         .into_iter()
-        .next()
-        .map(|dl| dl.header.id.date - 7);
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+    let mut dependencies =
+        csv::Reader::from_reader(std::fs::File::open(data_folder.join("dependencies.csv"))?);
+    for row in dependencies.deserialize() {
+        let row: Dependencies = row?;
+        let id = registry.key(row.id)?;
+        let version_id = registry.key(row.version_id)?;
+        let new = schema::Dependency {
+            version_id,
+            crate_id: registry.key(row.crate_id)?,
+            req: row.req,
+            kind: match row.kind {
+                0 => schema::DependencyKind::Normal,
+                1 => schema::DependencyKind::Build,
+                2 => schema::DependencyKind::Dev,
+                _ => anyhow::bail!("unexpected dependency kind: {}", row.kind),
+            },
+            optional: row.optional == 't',
+            default_features: row.default_features == 't',
+            features: parse_array(&row.features),
+            target: Some(row.target).filter(|target| !target.is_empty()),
+            explicit_name: Some(row.explicit_name).filter(|name| !name.is_empty()),
+            dependent_crate_id: version_crates.get(&version_id).copied(),
+        };
+        if let Some(existing) = existing_dependencies.remove(&id) {
+            if existing.contents != new {
+                tx.send(Operation::update_serialized::<schema::Dependency>(
+                    existing.header,
+                    &new,
+                )?)?;
+            }
+        } else {
+            tx.send(Operation::insert_serialized::<schema::Dependency>(
+                Some(&id),
+                &new,
+            )?)?;
+        }
+    }
 
-    let mut downloads = csv::Reader::from_reader(std::fs::File::open(
-        data_folder.join("version_downloads.csv"),
-    )?);
+    Ok(())
+}
+
+/// Parses a PostgreSQL array literal, such as `{serde,std}`.
+fn parse_array(array: &str) -> Vec<String> {
+    let array = array.trim_start_matches('{').trim_end_matches('}');
+    array
+        .split(',')
+        .map(|value| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// How often every row of a dump's version downloads is compared against the
+/// database, rather than only the most recent days.
+const RECONCILIATION_INTERVAL: Duration = Duration::days(30);
+
+/// The number of rows whose stored downloads are looked up at once while
+/// reconciling.
+const RECONCILIATION_BATCH: usize = 10_000;
+
+/// Schedules a full reconciliation of every registry's version downloads
+/// during the next import, instead of waiting for [`RECONCILIATION_INTERVAL`]
+/// to pass.
+pub fn request_download_reconciliation(db: &Database) -> anyhow::Result<()> {
+    let mut state = ImportState::get(&(), db)?
+        .map(|d| d.contents)
+        .unwrap_or_default();
+    state.downloads_reconciled.clear();
+    state.overwrite_into(&(), db)?;
+    Ok(())
+}
+
+/// Imports the daily downloads of each version.
+///
+/// Normally only the days since the previous import are written, along with
+/// the 7 days before them to pick up late adjustments. When `reconcile` is
+/// true, every row in the dump is compared with the database instead, and
+/// only the rows whose downloads changed are rewritten, so corrections to
+/// older days aren't missed.
+fn apply_version_download_changes(
+    data_folder: &Path,
+    registry: &Registry,
+    tx: &std::sync::mpsc::SyncSender<Operation>,
+    db: &Database,
+    version_crates: &HashMap<u64, u64>,
+    reconcile: bool,
+) -> anyhow::Result<()> {
+    let path = data_folder.join("version_downloads.csv");
+    if !path.exists() {
+        // Alternate registries aren't required to publish downloads.
+        return Ok(());
+    }
+
+    println!("Parsing version downloads");
+    let last_imported = if reconcile {
+        None
+    } else {
+        DownloadsByDate::entries(db)
+            .descending()
+            .limit(1)
+            .query()?
+            .into_iter()
+            .next()
+            .map(|mapping| mapping.key.0 - 7)
+    };
+
+    let mut batch = Vec::with_capacity(RECONCILIATION_BATCH);
+    let mut corrected = 0;
+    let mut downloads = csv::Reader::from_reader(std::fs::File::open(path)?);
     for row in downloads.deserialize() {
         let row: VersionDownloads = row?;
-        let date = parse_iso_date(&row.date)?;
-        // 365 requires 9 bits.
-        let date = CalendarDate::from(date);
+        let date = CalendarDate::from(parse_iso_date(&row.date)?);
         if last_imported.map_or(false, |last_imported| date < last_imported) {
             continue;
         }
 
-        let key = VersionDownloadKey {
-            date,
-            version_id: row.version_id,
+        let version_id = registry.key(row.version_id)?;
+        let key = VersionDownloadKey { date, version_id };
+        let downloads = schema::VersionDownloads {
+            crate_id: *version_crates
+                .get(&version_id)
+                .ok_or_else(|| anyhow::anyhow!("invalid version download: unknown version_id"))?,
+            downloads: row.downloads,
         };
-        tx.send(Operation::overwrite_serialized::<
-            schema::VersionDownloads,
-            _,
-        >(
-            &key,
-            &schema::VersionDownloads {
-                crate_id: *version_crates.get(&row.version_id).ok_or_else(|| {
-                    anyhow::anyhow!("invalid version download: unknown version_id")
-                })?,
-                downloads: row.downloads,
-            },
-        )?)?;
+        if reconcile {
+            batch.push((key, downloads));
+            if batch.len() == RECONCILIATION_BATCH {
+                corrected += reconcile_downloads(&mut batch, tx, db)?;
+            }
+        } else {
+            tx.send(Operation::overwrite_serialized::<
+                schema::VersionDownloads,
+                _,
+            >(&key, &downloads)?)?;
+        }
+    }
+
+    if reconcile {
+        corrected += reconcile_downloads(&mut batch, tx, db)?;
+        println!("Reconciled version downloads, correcting {corrected} rows.");
     }
 
     Ok(())
 }
 
+/// Writes the rows of `batch` that are missing from the database or whose
+/// downloads differ from the stored value, returning how many were written.
+fn reconcile_downloads(
+    batch: &mut Vec<(VersionDownloadKey, schema::VersionDownloads)>,
+    tx: &std::sync::mpsc::SyncSender<Operation>,
+    db: &Database,
+) -> anyhow::Result<usize> {
+    let stored = schema::VersionDownloads::get_multiple(batch.iter().map(|(key, _)| key), db)?
+        .into_iter()
+        .map(|document| (document.header.id, document.contents))
+        .collect::<BTreeMap<_, _>>();
+    let mut corrected = 0;
+    for (key, downloads) in batch.drain(..) {
+        if stored.get(&key) != Some(&downloads) {
+            tx.send(Operation::overwrite_serialized::<
+                schema::VersionDownloads,
+                _,
+            >(&key, &downloads)?)?;
+            corrected += 1;
+        }
+    }
+    Ok(corrected)
+}
+
 fn parse_iso_date(date: &str) -> anyhow::Result<time::Date> {
     let mut parts = date.split('-');
     let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next())
@@ -540,6 +1293,33 @@ fn parse_iso_date(date: &str) -> anyhow::Result<time::Date> {
     Ok(Date::from_calendar_date(year, month, day)?)
 }
 
+/// Parses a timestamp from the dump, such as `2023-03-15 02:00:46.123456`.
+/// Timestamps are in UTC, and a date without a time is treated as midnight.
+pub(crate) fn parse_timestamp(timestamp: &str) -> anyhow::Result<OffsetDateTime> {
+    let (date, time) = timestamp.split_once(' ').unwrap_or((timestamp, "00:00:00"));
+    let date = parse_iso_date(date)?;
+    // Fractional seconds are ignored.
+    let time = time.split_once('.').map_or(time, |(time, _)| time);
+    let mut parts = time.split(':');
+    let (Some(hours), Some(minutes), Some(seconds)) = (parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("invalid time format")
+    };
+    let time = Time::from_hms(hours.parse()?, minutes.parse()?, seconds.parse()?)?;
+    Ok(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+/// Formats `time` in the same format as the dump's timestamps.
+pub(crate) fn timestamp(time: OffsetDateTime) -> String {
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        time.date(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct Crate {
     created_at: String,
@@ -617,12 +1397,28 @@ pub struct Versions {
     published_by: Option<u64>,
     updated_at: String,
     yanked: Option<char>,
+    #[serde(default)]
+    rust_version: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Dependencies {
+    crate_id: u64,
+    default_features: char,
+    explicit_name: String,
+    features: String,
+    id: u64,
+    kind: u8,
+    optional: char,
+    req: String,
+    target: String,
+    version_id: u64,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Users {
     gh_avatar: String,
-    gh_id: u64,
+    gh_id: i64,
     gh_login: String,
     id: u64,
     name: String,
@@ -637,3 +1433,30 @@ pub struct Teams {
     name: String,
     org_id: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_versions_prefer_the_export_time() {
+        let dump = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dump.path().join("data")).unwrap();
+        std::fs::write(dump.path().join("data").join("crates.csv"), "id,name\n").unwrap();
+        let modified = dump_version(dump.path()).unwrap();
+        assert!(parse_timestamp(&modified).is_ok());
+
+        std::fs::write(
+            dump.path().join("metadata.json"),
+            r#"{"timestamp": "2023-03-20T02:00:30Z", "crates_io_commit": "abc"}"#,
+        )
+        .unwrap();
+        assert_eq!(dump_version(dump.path()).unwrap(), "2023-03-20T02:00:30Z");
+    }
+
+    #[test]
+    fn dumps_without_crates_have_no_version() {
+        let dump = tempfile::tempdir().unwrap();
+        assert!(dump_version(dump.path()).is_err());
+    }
+}
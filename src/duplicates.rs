@@ -0,0 +1,391 @@
+//! Detection of crates whose READMEs are near-exact copies of other crates'
+//! READMEs, which is common among spam and typosquatting crates.
+//!
+//! Each README's prose is split into overlapping runs of words, called
+//! shingles, and summarized by a MinHash signature: the smallest hash of its
+//! shingles under each of several hash functions. The fraction of values two
+//! signatures agree on estimates the fraction of shingles the two READMEs
+//! share. Signatures are bucketed by bands of their values, so that only
+//! READMEs likely to be similar are compared.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+
+use crate::{
+    language,
+    schema::{Crate, CratesByNormalizedName, ReadmeDuplicate},
+};
+
+/// The number of words in each shingle.
+const SHINGLE_WORDS: usize = 5;
+
+/// The most words of a README that are compared. Copies are almost always
+/// recognizable from their beginning.
+const MAX_WORDS: usize = 500;
+
+/// The fewest shingles a README must have to be compared. Shorter READMEs are
+/// often the same boilerplate written independently.
+const MIN_SHINGLES: usize = 20;
+
+/// The number of hash functions in each signature.
+const SIGNATURE_LENGTH: usize = 64;
+
+/// The number of signature values in each band. Two READMEs sharing 80% of
+/// their shingles share at least one of the 16 bands 99.9% of the time.
+const BAND_ROWS: usize = 4;
+
+/// The estimated fraction of shingles two READMEs must share to be copies.
+const MIN_SIMILARITY: f32 = 0.8;
+
+/// The most earlier crates in a bucket each crate is compared with, which
+/// keeps buckets of thousands of identical READMEs from being compared
+/// pairwise.
+const MAX_BUCKET_COMPARISONS: usize = 20;
+
+const BATCH_SIZE: usize = 1_000;
+
+/// The most bytes of a README that are searched for prose.
+const MAX_README_LENGTH: usize = 16 * 1024;
+
+type Signature = [u32; SIGNATURE_LENGTH];
+
+struct Candidate {
+    id: u64,
+    registry: Option<String>,
+    repository: String,
+    created_at: String,
+    signature: Signature,
+}
+
+impl Candidate {
+    /// Orders crates by how likely they are to be the original of a cluster:
+    /// the earliest published first. Crates without a publication time come
+    /// last.
+    fn age_key(&self) -> (bool, &str, u64) {
+        (self.created_at.is_empty(), &self.created_at, self.id)
+    }
+
+    /// Returns whether the crates' READMEs are similar enough to be copies.
+    /// Crates with the same repository are never copies of each other, and
+    /// neither are crates from different registries.
+    fn is_copy_of(&self, other: &Self) -> bool {
+        self.registry == other.registry
+            && (self.repository.is_empty() || self.repository != other.repository)
+            && similarity(&self.signature, &other.signature) >= MIN_SIMILARITY
+    }
+}
+
+/// Clusters the crates whose READMEs are near-exact copies of each other,
+/// storing a [`ReadmeDuplicate`] for every crate in a cluster that is a copy
+/// of the cluster's earliest published crate.
+///
+/// Crates with the same repository are never considered copies of each
+/// other, since the crates of a workspace often share a README, and neither
+/// are crates from different registries, which may mirror each other.
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    println!("Finding copied READMEs");
+    let permutations = permutations();
+    let ids = CratesByNormalizedName::entries(db)
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.source.id.deserialize())
+        .collect::<Result<Vec<u64>, _>>()?;
+    let mut candidates = Vec::new();
+    for batch in ids.chunks(BATCH_SIZE) {
+        for krate in Crate::get_multiple(batch, db)? {
+            let Some(signature) = signature(&krate.contents.readme, &permutations)
+                else { continue };
+            candidates.push(Candidate {
+                id: krate.header.id,
+                registry: krate.contents.registry,
+                repository: krate
+                    .contents
+                    .repository
+                    .trim()
+                    .trim_end_matches('/')
+                    .to_string(),
+                created_at: krate.contents.created_at,
+                signature,
+            });
+        }
+    }
+
+    let duplicates = find_duplicates(&candidates);
+    let mut existing = ReadmeDuplicate::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+    let mut tx = Transaction::new();
+    for (crate_id, duplicate) in &duplicates {
+        let operation = match existing.remove(crate_id) {
+            Some(existing) if existing.contents == *duplicate => continue,
+            Some(existing) => {
+                Operation::update_serialized::<ReadmeDuplicate>(existing.header, duplicate)?
+            }
+            None => Operation::insert_serialized::<ReadmeDuplicate>(Some(crate_id), duplicate)?,
+        };
+        tx.operations.push(operation);
+        if tx.operations.len() >= 100_000 {
+            tx.apply(db)?;
+            tx = Transaction::new();
+        }
+    }
+    // Crates whose READMEs are no longer copies.
+    for stale in existing.into_values() {
+        tx.operations.push(Operation::delete(
+            ReadmeDuplicate::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    println!("Found {} copied READMEs", duplicates.len());
+    Ok(())
+}
+
+/// Returns the crates whose READMEs are copies, keyed by their ids.
+///
+/// Copies are clustered transitively, so a cluster can contain crates that
+/// aren't similar to each other. Each crate is only stored as a copy of its
+/// cluster's original if it is a copy of the original itself.
+fn find_duplicates(candidates: &[Candidate]) -> HashMap<u64, ReadmeDuplicate> {
+    let mut buckets = HashMap::<u64, Vec<usize>>::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        for (band, values) in candidate.signature.chunks(BAND_ROWS).enumerate() {
+            let mut hasher = DefaultHasher::new();
+            (band, &candidate.registry, values).hash(&mut hasher);
+            buckets.entry(hasher.finish()).or_default().push(index);
+        }
+    }
+
+    let mut clusters = Clusters::new(candidates.len());
+    for members in buckets.values().filter(|members| members.len() > 1) {
+        for (position, &member) in members.iter().enumerate().skip(1) {
+            for &earlier in members[..position].iter().take(MAX_BUCKET_COMPARISONS) {
+                if clusters.root(member) != clusters.root(earlier)
+                    && candidates[member].is_copy_of(&candidates[earlier])
+                {
+                    clusters.join(member, earlier);
+                }
+            }
+        }
+    }
+
+    let mut originals = HashMap::<usize, usize>::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let original = originals.entry(clusters.root(index)).or_insert(index);
+        if candidate.age_key() < candidates[*original].age_key() {
+            *original = index;
+        }
+    }
+    let mut duplicates = HashMap::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let original = &candidates[originals[&clusters.root(index)]];
+        if original.id != candidate.id && candidate.is_copy_of(original) {
+            duplicates.insert(
+                candidate.id,
+                ReadmeDuplicate {
+                    original: original.id,
+                    similarity: similarity(&candidate.signature, &original.signature),
+                },
+            );
+        }
+    }
+    duplicates
+}
+
+/// Returns the MinHash signature of `readme`'s prose, or None if it's too
+/// short to compare.
+fn signature(readme: &str, permutations: &[(u64, u64); SIGNATURE_LENGTH]) -> Option<Signature> {
+    let mut end = readme.len().min(MAX_README_LENGTH);
+    while !readme.is_char_boundary(end) {
+        end -= 1;
+    }
+    let prose = language::prose(&readme[..end]).to_lowercase();
+    let words = prose
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(MAX_WORDS)
+        .collect::<Vec<_>>();
+    let shingles = words.windows(SHINGLE_WORDS);
+    if shingles.len() < MIN_SHINGLES {
+        return None;
+    }
+
+    let mut signature = [u32::MAX; SIGNATURE_LENGTH];
+    for shingle in shingles {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let hash = hasher.finish();
+        for (min, (multiplier, increment)) in signature.iter_mut().zip(permutations) {
+            // Multiply-shift hashing, which keeps the high bits.
+            let value = (hash.wrapping_mul(*multiplier).wrapping_add(*increment) >> 32) as u32;
+            *min = (*min).min(value);
+        }
+    }
+    Some(signature)
+}
+
+/// Returns the parameters of each of the signature's hash functions. The
+/// parameters come from a fixed seed, so that each import finds the same
+/// clusters.
+fn permutations() -> [(u64, u64); SIGNATURE_LENGTH] {
+    // SplitMix64.
+    let mut state = 0_u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    };
+    std::array::from_fn(|_| (next() | 1, next()))
+}
+
+/// Returns the estimated fraction of shingles shared by the READMEs with
+/// signatures `a` and `b`.
+fn similarity(a: &Signature, b: &Signature) -> f32 {
+    let matching = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    matching as f32 / SIGNATURE_LENGTH as f32
+}
+
+/// Disjoint sets of crates, identified by their indexes.
+struct Clusters {
+    parents: Vec<usize>,
+}
+
+impl Clusters {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    /// Returns the index identifying the cluster `index` is in.
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        self.parents[a] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const README: &str = "Fast and friendly parsing of command line arguments. The parser \
+        reads the arguments your program was started with, checks them against the options \
+        you declared, and fills in a struct with the values it found. Errors explain which \
+        option was missing or malformed and suggest the closest valid spelling. Help text is \
+        generated from the documentation comments of each field, and shell completions can \
+        be written for bash, zsh, and fish at build time.";
+
+    const UNRELATED: &str = "An asynchronous client for a popular key value store. It keeps \
+        a pool of connections open, pipelines commands sent from many tasks at once, and \
+        reconnects with exponential backoff when the server goes away. Every command returns \
+        a typed response, and scripts can be loaded once and then called by their digest. \
+        Cluster mode routes each key to the node that owns its slot and follows redirections \
+        transparently while slots migrate between nodes.";
+
+    fn candidate(id: u64, repository: &str, signature: Signature) -> Candidate {
+        Candidate {
+            id,
+            registry: None,
+            repository: repository.to_string(),
+            created_at: format!("2020-01-{id:02} 00:00:00"),
+            signature,
+        }
+    }
+
+    /// Returns a signature sharing `SIGNATURE_LENGTH - changed.len()` values
+    /// with `base`.
+    fn changed(base: Signature, changed: impl IntoIterator<Item = usize>) -> Signature {
+        let mut signature = base;
+        for index in changed {
+            signature[index] = u32::MAX - index as u32;
+        }
+        signature
+    }
+
+    #[test]
+    fn near_duplicates_are_similar() {
+        let permutations = permutations();
+        let original = signature(README, &permutations).expect("long enough");
+        let edited = README.replace("friendly", "ergonomic");
+        let copy = signature(&edited, &permutations).expect("long enough");
+        let html = format!("<p>{README}</p><pre><code>let x = 1;</code></pre>");
+        let rendered = signature(&html, &permutations).expect("long enough");
+
+        assert!(similarity(&original, &copy) >= MIN_SIMILARITY);
+        assert!(similarity(&original, &copy) < 1.);
+        assert_eq!(similarity(&original, &rendered), 1.);
+    }
+
+    #[test]
+    fn unrelated_readmes_are_not_similar() {
+        let permutations = permutations();
+        let a = signature(README, &permutations).expect("long enough");
+        let b = signature(UNRELATED, &permutations).expect("long enough");
+        assert!(similarity(&a, &b) < 0.2);
+    }
+
+    #[test]
+    fn short_readmes_are_not_compared() {
+        assert!(signature("A small crate that does one thing well.", &permutations()).is_none());
+    }
+
+    #[test]
+    fn members_must_be_copies_of_the_original() {
+        // B is a copy of A and C is a copy of B, but C isn't a copy of A.
+        let a = [0; SIGNATURE_LENGTH];
+        let b = changed(a, 0..12);
+        let c = changed(b, 12..24);
+        let candidates = [
+            candidate(1, "", a),
+            candidate(2, "", b),
+            candidate(3, "", c),
+        ];
+
+        let duplicates = find_duplicates(&candidates);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[&2].original, 1);
+    }
+
+    #[test]
+    fn crates_of_the_same_repository_are_not_copies() {
+        // A and B share a repository, and both are similar to C.
+        let a = [0; SIGNATURE_LENGTH];
+        let b = changed(a, 0..4);
+        let c = changed(a, 4..8);
+        let candidates = [
+            candidate(1, "https://github.com/a/a", a),
+            candidate(2, "https://github.com/a/a", b),
+            candidate(3, "https://github.com/c/c", c),
+        ];
+
+        let duplicates = find_duplicates(&candidates);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[&3].original, 1);
+    }
+}
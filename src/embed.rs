@@ -0,0 +1,94 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::header::CACHE_CONTROL,
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+
+use crate::{
+    filters::{Filters, PlaceholderMode, QueryTimeout},
+    security_headers::Framable,
+    webserver::{AppError, AppState},
+    CrateResult, MAX_QUERY_LEN,
+};
+
+/// The most results shown in an embedded search box.
+const MAX_RESULTS: usize = 10;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct EmbedQuery {
+    q: String,
+    /// Limits results to crates with this keyword.
+    keyword: Option<String>,
+    /// Limits results to crates in this category or its subcategories.
+    category: Option<String>,
+    /// When `message`, clicking a result only notifies the embedding page
+    /// instead of also opening the crate's page.
+    links: Option<String>,
+}
+
+impl EmbedQuery {
+    /// Returns the search query with the embed's scope added as filters.
+    fn scoped_query(&self) -> String {
+        let mut query = self.q.clone();
+        for (filter, value) in [("keyword", &self.keyword), ("category", &self.category)] {
+            // Only the first word is used, so that the scope can't add other
+            // filters or terms.
+            if let Some(value) = value.as_deref().and_then(|v| v.split_whitespace().next()) {
+                query.push_str(&format!(" {filter}:{value}"));
+            }
+        }
+        query
+    }
+}
+
+#[derive(Template, Debug)]
+#[template(path = "embed.html")]
+struct EmbeddedSearch {
+    query: EmbedQuery,
+    results: Vec<CrateResult>,
+}
+
+/// A search box for other sites to embed in an iframe, optionally scoped to a
+/// keyword or category. The embedding page is told about clicked results and
+/// the frame's height through `postMessage` by the frame's script, which
+/// `/assets/embed.js` listens for.
+pub(crate) async fn search(
+    State((db, cache, search_index)): State<AppState>,
+    Query(query): Query<EmbedQuery>,
+    Extension(timeout): Extension<QueryTimeout>,
+) -> Result<Response, AppError> {
+    let scoped_query = query.scoped_query();
+    // The scope counts towards the limit, since it's parsed with the query.
+    if scoped_query.len() > MAX_QUERY_LEN {
+        return Err(AppError::BadRequest(format!(
+            "search queries can be at most {MAX_QUERY_LEN} bytes long"
+        )));
+    }
+
+    let results = if query.q.trim().is_empty() {
+        Vec::new()
+    } else {
+        let (terms, mut filters) = Filters::parse(&scoped_query);
+        filters.placeholders = PlaceholderMode::Hide;
+        filters.deadline = timeout.deadline();
+        let mut results = crate::spawn_query(terms, filters, db, cache, search_index)
+            .await?
+            .results;
+        results.truncate(MAX_RESULTS);
+        results
+    };
+
+    let page = EmbeddedSearch { query, results }.render()?;
+    Ok((
+        [(CACHE_CONTROL, "no-cache")],
+        // Unlike the rest of the site, this page is meant to be framed by any
+        // site.
+        Extension(Framable),
+        Html(page),
+    )
+        .into_response())
+}
@@ -0,0 +1,436 @@
+//! Semantic search using text embeddings.
+//!
+//! Each crate's name, description, and README are embedded by an
+//! OpenAI-compatible embeddings endpoint after each import, which can be a
+//! hosted API or a small model served locally. Natural-language queries, such
+//! as "read large CSV quickly", are embedded the same way, and the crates
+//! whose embeddings are most similar are blended into the search results,
+//! even when they share no words with the query.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bonsaidb::{
+    core::{
+        schema::{SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::Cache,
+    hnsw::Hnsw,
+    language,
+    schema::{Crate, CrateEmbedding, CratesByNormalizedName},
+    syntax::{self, Token},
+};
+
+/// The most bytes of a crate's text that are embedded. Most models only read
+/// the first few hundred words of a text anyway.
+const MAX_TEXT_LENGTH: usize = 2_000;
+
+/// The most bytes of a README that are searched for prose to embed.
+const MAX_README_LENGTH: usize = 16 * 1024;
+
+/// The fewest words a query must have to be searched semantically. Shorter
+/// queries are usually names or keywords, which the search index matches
+/// better.
+const MIN_QUERY_WORDS: usize = 3;
+
+/// How many of the most similar crates are blended into a query's results.
+const SEMANTIC_CANDIDATES: usize = 100;
+
+/// How similar a crate must be to the query, relative to the most similar
+/// crate, to be blended into the results.
+const MIN_RELATIVE_SIMILARITY: f32 = 0.8;
+
+/// The most query embeddings kept in memory.
+const MAX_CACHED_QUERIES: usize = 1_000;
+
+/// How long embedding a query may take before the query is searched without
+/// it.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long queries are searched without embeddings after the endpoint
+/// fails, so that an outage doesn't slow every search.
+const QUERY_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Where embeddings are computed, and how much they affect search results.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingsConfig {
+    /// The URL of an OpenAI-compatible embeddings endpoint, such as
+    /// `https://api.openai.com/v1/embeddings`. Semantic search is disabled
+    /// when unset.
+    pub url: Option<String>,
+    /// The model requested from the endpoint. Changing the model embeds every
+    /// crate again.
+    pub model: String,
+    /// Sent as `Authorization: Bearer <key>`. `DELVE_RS_EMBEDDINGS_KEY`
+    /// overrides this setting.
+    pub api_key: Option<String>,
+    /// The number of dimensions requested, for models that can shorten their
+    /// embeddings. Fewer dimensions use less memory.
+    pub dimensions: Option<usize>,
+    /// How much similarity counts compared to matching the query's words. The
+    /// most similar crate scores `weight` times the best word match.
+    pub weight: f32,
+    /// The most crates embedded by each request during imports.
+    pub batch_size: usize,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            model: String::from("text-embedding-3-small"),
+            api_key: None,
+            dimensions: None,
+            weight: 0.5,
+            batch_size: 64,
+        }
+    }
+}
+
+impl EmbeddingsConfig {
+    /// Returns the API key, preferring `DELVE_RS_EMBEDDINGS_KEY`.
+    fn api_key(&self) -> Option<String> {
+        std::env::var("DELVE_RS_EMBEDDINGS_KEY")
+            .ok()
+            .or_else(|| self.api_key.clone())
+            .filter(|key| !key.is_empty())
+    }
+}
+
+/// Embeds every crate whose text changed or that hasn't been embedded by the
+/// configured model. Crates are embedded in batches, and when the endpoint
+/// fails the remaining crates are left for the next import.
+pub(crate) async fn update(db: &Database, config: &EmbeddingsConfig) -> anyhow::Result<()> {
+    let Some(url) = &config.url else { return Ok(()) };
+    println!("Embedding crates.");
+    let client = reqwest::Client::builder().timeout(IMPORT_TIMEOUT).build()?;
+    let ids = CratesByNormalizedName::entries(db)
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.source.id.deserialize())
+        .collect::<Result<Vec<u64>, _>>()?;
+
+    let mut embedded = 0;
+    for batch in ids.chunks(config.batch_size.max(1)) {
+        let existing = CrateEmbedding::get_multiple(batch, db)?
+            .into_iter()
+            .map(|embedding| (embedding.header.id, embedding.contents))
+            .collect::<HashMap<_, _>>();
+        let mut pending = Vec::new();
+        for krate in Crate::get_multiple(batch, db)? {
+            let text = text(&krate.contents);
+            let text_hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+            let current = existing.get(&krate.header.id).map_or(false, |embedding| {
+                embedding.model == config.model && embedding.text_hash == text_hash
+            });
+            if !current {
+                pending.push((krate.header.id, text, text_hash));
+            }
+        }
+        if pending.is_empty() {
+            continue;
+        }
+
+        let texts = pending
+            .iter()
+            .map(|(_, text, _)| text.as_str())
+            .collect::<Vec<_>>();
+        let vectors = match embed(&client, config, url, &texts).await {
+            Ok(vectors) => vectors,
+            Err(err) => {
+                tracing::error!("error embedding crates: {err:#}");
+                break;
+            }
+        };
+        let mut tx = Transaction::new();
+        for ((id, _, text_hash), vector) in pending.into_iter().zip(vectors) {
+            tx.operations
+                .push(Operation::overwrite_serialized::<CrateEmbedding, _>(
+                    &id,
+                    &CrateEmbedding {
+                        model: config.model.clone(),
+                        text_hash,
+                        vector,
+                    },
+                )?);
+        }
+        embedded += tx.operations.len();
+        tx.apply(db)?;
+    }
+
+    println!("Embedded {embedded} crates");
+    Ok(())
+}
+
+/// Returns the text embedded for `krate`: its name and description, followed
+/// by as much of its README's prose as fits.
+fn text(krate: &Crate) -> String {
+    let mut readme = krate.readme.as_str();
+    if readme.len() > MAX_README_LENGTH {
+        let mut end = MAX_README_LENGTH;
+        while !readme.is_char_boundary(end) {
+            end -= 1;
+        }
+        readme = &readme[..end];
+    }
+
+    let mut text = format!("{}: {}\n", krate.name, krate.description.trim());
+    for word in language::prose(readme).split_whitespace() {
+        if text.len() + word.len() >= MAX_TEXT_LENGTH {
+            break;
+        }
+        text.push_str(word);
+        text.push(' ');
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+#[derive(Serialize, Debug)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingResponse {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Embedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Returns the embedding of each of `texts`, in order.
+async fn embed(
+    client: &reqwest::Client,
+    config: &EmbeddingsConfig,
+    url: &str,
+    texts: &[&str],
+) -> anyhow::Result<Vec<Vec<f32>>> {
+    let mut request = client.post(url).json(&EmbeddingRequest {
+        model: &config.model,
+        input: texts,
+        dimensions: config.dimensions,
+    });
+    if let Some(key) = config.api_key() {
+        request = request.bearer_auth(key);
+    }
+    let mut response = request
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<EmbeddingResponse>()
+        .await?;
+    anyhow::ensure!(
+        response.data.len() == texts.len(),
+        "expected {} embeddings, received {}",
+        texts.len(),
+        response.data.len()
+    );
+    response.data.sort_by_key(|embedding| embedding.index);
+    Ok(response
+        .data
+        .into_iter()
+        .map(|embedding| embedding.embedding)
+        .collect())
+}
+
+/// The stored embeddings of one model, indexed for similarity searches.
+#[derive(Debug, Default)]
+pub struct SemanticIndex {
+    model: String,
+    /// Identifies the embeddings the index was built from.
+    fingerprint: u64,
+    vectors: Hnsw,
+}
+
+impl SemanticIndex {
+    /// Indexes the stored embeddings of the model the most crates are
+    /// embedded with. Building the index is slow, so `previous` is returned
+    /// when no embedding has changed since it was built.
+    pub(crate) fn build(
+        db: &Database,
+        previous: &Arc<SemanticIndex>,
+    ) -> anyhow::Result<Arc<SemanticIndex>> {
+        let embeddings = CrateEmbedding::all(db).query()?;
+        let mut models = HashMap::<&str, usize>::new();
+        for embedding in &embeddings {
+            *models.entry(embedding.contents.model.as_str()).or_default() += 1;
+        }
+        let Some((model, _)) = models.into_iter().max_by_key(|(_, count)| *count)
+            else { return Ok(Arc::default()) };
+        let embeddings = embeddings
+            .iter()
+            .filter(|embedding| embedding.contents.model == model)
+            .collect::<Vec<_>>();
+
+        let mut hasher = DefaultHasher::new();
+        model.hash(&mut hasher);
+        for embedding in &embeddings {
+            embedding.header.id.hash(&mut hasher);
+            embedding.contents.text_hash.hash(&mut hasher);
+        }
+        let fingerprint = hasher.finish();
+        if previous.model == model && previous.fingerprint == fingerprint {
+            return Ok(previous.clone());
+        }
+
+        let start = Instant::now();
+        let mut vectors = Hnsw::new(embeddings[0].contents.vector.len());
+        for embedding in embeddings {
+            vectors.insert(embedding.header.id, &embedding.contents.vector);
+        }
+        tracing::info!(
+            "indexed {} embeddings in {}ms",
+            vectors.len(),
+            start.elapsed().as_millis()
+        );
+        Ok(Arc::new(SemanticIndex {
+            model: model.to_string(),
+            fingerprint,
+            vectors,
+        }))
+    }
+}
+
+/// The crates most similar to a natural-language query, which are blended
+/// into its results.
+#[derive(Debug)]
+pub struct SemanticMatches {
+    /// How much similarity counts compared to matching the query's words.
+    pub(crate) weight: f32,
+    /// The similarity of each crate to the query, relative to the most
+    /// similar crate's.
+    pub(crate) similarity: HashMap<u64, f32>,
+}
+
+impl SemanticMatches {
+    /// Returns matches with the given similarities, keyed by crate id, for
+    /// callers that compute similarities themselves.
+    pub fn new(weight: f32, similarity: HashMap<u64, f32>) -> Self {
+        Self { weight, similarity }
+    }
+}
+
+/// Embeds search queries. Query embeddings are cached, since the same
+/// queries are searched repeatedly as results are paged through.
+#[derive(Clone, Debug)]
+pub(crate) struct Embedder {
+    config: Arc<EmbeddingsConfig>,
+    client: reqwest::Client,
+    queries: Arc<Mutex<QueryCache>>,
+}
+
+#[derive(Debug, Default)]
+struct QueryCache {
+    vectors: HashMap<String, Arc<Vec<f32>>>,
+    /// When queries may be embedded again after the endpoint failed.
+    unavailable_until: Option<Instant>,
+}
+
+impl Embedder {
+    pub fn new(config: EmbeddingsConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            config: Arc::new(config),
+            client: reqwest::Client::builder().timeout(QUERY_TIMEOUT).build()?,
+            queries: Arc::default(),
+        })
+    }
+
+    /// Returns the crates most similar to `terms`, if semantic search is
+    /// enabled and `terms` reads like a description rather than names or
+    /// keywords. `terms` must already have had its filters removed.
+    pub async fn matches(&self, cache: &Cache, terms: &str) -> Option<Arc<SemanticMatches>> {
+        if self.config.url.is_none() || !is_natural_language(terms) {
+            return None;
+        }
+        let index = cache.semantic_index().ok()?;
+        if index.vectors.is_empty() || index.model != self.config.model {
+            return None;
+        }
+        let query = self.query_vector(terms).await?;
+
+        let nearest = index.vectors.search(&query, SEMANTIC_CANDIDATES);
+        let best = nearest.first()?.1;
+        if best <= 0. {
+            return None;
+        }
+        let similarity = nearest
+            .into_iter()
+            .filter(|(_, similarity)| *similarity >= best * MIN_RELATIVE_SIMILARITY)
+            .map(|(id, similarity)| (id, similarity / best))
+            .collect();
+        Some(Arc::new(SemanticMatches {
+            weight: self.config.weight,
+            similarity,
+        }))
+    }
+
+    async fn query_vector(&self, terms: &str) -> Option<Arc<Vec<f32>>> {
+        let key = terms
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        {
+            let queries = self.queries.lock().ok()?;
+            if let Some(vector) = queries.vectors.get(&key) {
+                return Some(vector.clone());
+            }
+            if queries
+                .unavailable_until
+                .map_or(false, |until| Instant::now() < until)
+            {
+                return None;
+            }
+        }
+
+        let url = self.config.url.as_deref()?;
+        match embed(&self.client, &self.config, url, &[&key]).await {
+            Ok(mut vectors) => {
+                let vector = Arc::new(vectors.pop()?);
+                let mut queries = self.queries.lock().ok()?;
+                if queries.vectors.len() >= MAX_CACHED_QUERIES {
+                    queries.vectors.clear();
+                }
+                queries.vectors.insert(key, vector.clone());
+                Some(vector)
+            }
+            Err(err) => {
+                tracing::warn!("error embedding query: {err:#}");
+                self.queries.lock().ok()?.unavailable_until =
+                    Some(Instant::now() + QUERY_RETRY_DELAY);
+                None
+            }
+        }
+    }
+}
+
+/// Returns true if `terms` is several plain words, without phrases, field
+/// prefixes, or operators.
+fn is_natural_language(terms: &str) -> bool {
+    let tokens = syntax::tokens(terms);
+    tokens.len() >= MIN_QUERY_WORDS
+        && tokens
+            .iter()
+            .all(|token| matches!(token, Token::Term(term) if !term.phrase && term.field.is_none()))
+}
@@ -0,0 +1,283 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{COOKIE, SET_COOKIE},
+        HeaderMap, HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response,
+};
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    config::ExperimentConfig,
+    dump,
+    ranking::{self, Ranker},
+    replication,
+    schema::{ExperimentEvent, ExperimentEventKind, ExperimentEventsByExperiment},
+};
+
+/// The cookie identifying a browser session, which determines the variant
+/// each session is assigned.
+const SESSION_COOKIE: &str = "delve_session";
+
+/// How long a session cookie lasts, in seconds.
+const SESSION_MAX_AGE: u32 = 60 * 60 * 24 * 90;
+
+/// The number of result positions included in a [`Report`].
+const REPORT_POSITIONS: u32 = 10;
+
+/// The number of events deleted in each transaction by [`prune`].
+const PRUNE_BATCH: u32 = 10_000;
+
+/// A running ranking experiment.
+#[derive(Debug)]
+pub(crate) struct Experiment {
+    pub name: String,
+    variants: Vec<&'static dyn Ranker>,
+}
+
+impl Experiment {
+    pub fn new(config: &ExperimentConfig) -> anyhow::Result<Self> {
+        let variants = config
+            .variants
+            .iter()
+            .map(|variant| {
+                ranking::by_name(variant).ok_or_else(|| anyhow::anyhow!("unknown ranker {variant}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        anyhow::ensure!(!variants.is_empty(), "experiment has no variants");
+
+        Ok(Self {
+            name: config.name.clone(),
+            variants,
+        })
+    }
+
+    /// Returns the variant `session` is assigned. The same session is always
+    /// assigned the same variant of an experiment, while different
+    /// experiments split sessions independently.
+    fn assign(&self, session: &str) -> &'static dyn Ranker {
+        let digest = Sha256::new()
+            .chain_update(&self.name)
+            .chain_update([0])
+            .chain_update(session)
+            .finalize();
+        let bucket = u64::from_le_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+        self.variants[(bucket % self.variants.len() as u64) as usize]
+    }
+}
+
+/// The variant of an experiment that a request's session is assigned.
+#[derive(Clone, Debug)]
+pub(crate) struct Assignment {
+    pub experiment: Arc<Experiment>,
+    pub variant: &'static dyn Ranker,
+}
+
+impl Assignment {
+    /// Returns whether results linked with `experiment` and `variant` were
+    /// ordered by this assignment, so that a click on them can be attributed
+    /// to it.
+    pub fn ordered(&self, experiment: &str, variant: &str) -> bool {
+        self.experiment.name == experiment && self.variant.name() == variant
+    }
+
+    /// Records that a page of `results` results ordered by this variant was
+    /// shown.
+    pub fn record_impression(&self, db: &Database, results: usize) {
+        self.record(
+            db,
+            ExperimentEventKind::Impression {
+                results: u32::try_from(results).unwrap_or(u32::MAX),
+            },
+        );
+    }
+
+    /// Records that the result at `position`, starting from 1, was clicked.
+    pub fn record_click(&self, db: &Database, position: u32) {
+        self.record(db, ExperimentEventKind::Click { position });
+    }
+
+    fn record(&self, db: &Database, kind: ExperimentEventKind) {
+        if replication::is_read_only() {
+            return;
+        }
+        let event = ExperimentEvent {
+            experiment: self.experiment.name.clone(),
+            variant: self.variant.name().to_string(),
+            kind,
+            at: dump::timestamp(OffsetDateTime::now_utc()),
+        };
+        // Failing to record an event shouldn't fail the search.
+        if let Err(err) = event.push_into(db) {
+            tracing::warn!("error recording experiment event: {err}");
+        }
+    }
+}
+
+/// Middleware that assigns each request's session a variant of `experiment`,
+/// storing it as an [`Assignment`] in the request's extensions. Sessions are
+/// identified by a random cookie that is set on the first request.
+pub(crate) async fn assign_session<B>(
+    State(experiment): State<Arc<Experiment>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let existing = session_cookie(request.headers());
+    let session = existing
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    let variant = experiment.assign(&session);
+    request.extensions_mut().insert(Assignment {
+        experiment,
+        variant,
+    });
+
+    let mut response = next.run(request).await;
+    if existing.is_none() {
+        let cookie = format!(
+            "{SESSION_COOKIE}={session}; Path=/; Max-Age={SESSION_MAX_AGE}; HttpOnly; SameSite=Lax"
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+/// Deletes the experiment events recorded before `cutoff`, returning how many
+/// were deleted. Events are pushed with increasing ids, so the oldest are
+/// listed first.
+pub(crate) fn prune(db: &Database, cutoff: &str) -> anyhow::Result<usize> {
+    let mut deleted = 0;
+    loop {
+        let events = ExperimentEvent::all(db).limit(PRUNE_BATCH).query()?;
+        let mut tx = Transaction::new();
+        for event in events
+            .into_iter()
+            .take_while(|event| event.contents.at.as_str() < cutoff)
+        {
+            tx.operations.push(Operation::delete(
+                ExperimentEvent::collection_name(),
+                Header::try_from(event.header)?,
+            ));
+        }
+        if tx.operations.is_empty() {
+            return Ok(deleted);
+        }
+        deleted += tx.operations.len();
+        tx.apply(db)?;
+    }
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == SESSION_COOKIE && !value.is_empty()).then(|| value.to_string())
+        })
+}
+
+/// The click-through rate of each variant of an experiment.
+#[derive(Serialize, Debug)]
+pub(crate) struct Report {
+    pub experiment: String,
+    pub variants: Vec<VariantReport>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct VariantReport {
+    pub variant: String,
+    pub impressions: u64,
+    pub clicks: u64,
+    pub positions: Vec<PositionReport>,
+}
+
+impl VariantReport {
+    fn new(variant: &str) -> Self {
+        Self {
+            variant: variant.to_string(),
+            positions: (1..=REPORT_POSITIONS)
+                .map(|position| PositionReport {
+                    position,
+                    ..PositionReport::default()
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+}
+
+/// How often the result at `position` was clicked when it was shown.
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct PositionReport {
+    pub position: u32,
+    pub impressions: u64,
+    pub clicks: u64,
+    pub ctr: f64,
+}
+
+impl Report {
+    /// Summarizes the events recorded for the experiment named `experiment`.
+    pub fn load(db: &Database, experiment: &str) -> anyhow::Result<Self> {
+        let mut variants = BTreeMap::<String, VariantReport>::new();
+        for mapping in ExperimentEventsByExperiment::entries(db)
+            .with_key(experiment)
+            .query()?
+        {
+            let event = mapping.value;
+            let report = variants
+                .entry(event.variant)
+                .or_insert_with_key(|variant| VariantReport::new(variant));
+            match event.kind {
+                ExperimentEventKind::Impression { results } => {
+                    report.impressions += 1;
+                    // A page with 3 results is an impression of positions 1-3.
+                    for position in report.positions.iter_mut().take(results as usize) {
+                        position.impressions += 1;
+                    }
+                }
+                ExperimentEventKind::Click { position } => {
+                    report.clicks += 1;
+                    if let Some(position) = position
+                        .checked_sub(1)
+                        .and_then(|index| report.positions.get_mut(index as usize))
+                    {
+                        position.clicks += 1;
+                    }
+                }
+            }
+        }
+
+        for report in variants.values_mut() {
+            for position in &mut report.positions {
+                if position.impressions > 0 {
+                    position.ctr = position.clicks as f64 / position.impressions as f64;
+                }
+            }
+        }
+
+        Ok(Self {
+            experiment: experiment.to_string(),
+            variants: variants.into_values().collect(),
+        })
+    }
+}
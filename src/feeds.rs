@@ -0,0 +1,259 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension,
+};
+use time::{
+    format_description::well_known::{Rfc2822, Rfc3339},
+    OffsetDateTime,
+};
+
+use crate::{
+    dump,
+    ownership::{self, OwnerAlerts},
+    seo::SiteUrl,
+    watchlists::WatchlistActivity,
+    webserver::{AppError, AppState},
+    yanks,
+};
+
+/// RSS feeds of events detected while importing dumps.
+pub(super) fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/yanks.xml", get(yank_feed))
+        .route("/ownership.xml", get(ownership_feed))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "feed.xml")]
+pub(crate) struct Feed {
+    pub title: String,
+    pub link: String,
+    pub description: String,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Debug)]
+pub(crate) struct FeedItem {
+    pub title: String,
+    pub link: String,
+    /// A unique identifier of the event, so that readers don't show it twice.
+    pub guid: String,
+    /// The publication date in RFC 2822 format.
+    pub published: Option<String>,
+    pub description: String,
+}
+
+impl Feed {
+    /// Renders the feed, making its links absolute first, since RSS
+    /// requires them to be.
+    fn respond(mut self, site: &SiteUrl) -> Result<Response, AppError> {
+        self.link = absolute(site, self.link);
+        for item in &mut self.items {
+            item.link = absolute(site, std::mem::take(&mut item.link));
+        }
+        Ok(([(CONTENT_TYPE, "application/rss+xml")], self.render()?).into_response())
+    }
+}
+
+/// Returns the absolute URL of `link`. Links to other sites, such as
+/// advisories, are returned unchanged.
+fn absolute(site: &SiteUrl, link: String) -> String {
+    if link.starts_with('/') {
+        site.absolute(&link)
+    } else {
+        link
+    }
+}
+
+/// Converts a timestamp from the dump to the format used by RSS.
+pub(crate) fn rss_date(timestamp: &str) -> Option<String> {
+    dump::parse_timestamp(timestamp).ok()?.format(&Rfc2822).ok()
+}
+
+/// An Atom feed, for readers that need absolute links and stable entry ids.
+#[derive(Template, Debug)]
+#[template(path = "atom.xml")]
+pub(crate) struct AtomFeed {
+    /// A permanent identifier of the feed.
+    pub id: String,
+    pub title: String,
+    pub subtitle: String,
+    /// The feed's own URL.
+    pub self_link: String,
+    /// When the newest entry was published, in RFC 3339 format.
+    pub updated: String,
+    pub entries: Vec<AtomEntry>,
+}
+
+#[derive(Debug)]
+pub(crate) struct AtomEntry {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    /// The publication date in RFC 3339 format.
+    pub updated: String,
+}
+
+/// Converts a timestamp from the dump to the format used by Atom.
+fn atom_date(timestamp: &str) -> Option<String> {
+    dump::parse_timestamp(timestamp).ok()?.format(&Rfc3339).ok()
+}
+
+async fn yank_feed(
+    State((db, cache, _)): State<AppState>,
+    Extension(site): Extension<SiteUrl>,
+) -> Result<Response, AppError> {
+    let items = yanks::recent(&db, &cache)?
+        .into_iter()
+        .map(|entry| {
+            let action = if entry.yanked { "yanked" } else { "unyanked" };
+            FeedItem {
+                title: format!("{} {} was {action}", entry.name, entry.version),
+                link: format!("/crates/{}", entry.name),
+                guid: format!("yank-{}", entry.id),
+                published: rss_date(&entry.at),
+                description: format!("Version {} of {} was {action}.", entry.version, entry.name),
+            }
+        })
+        .collect();
+
+    Feed {
+        title: String::from("delve.rs: yanked versions"),
+        link: String::from("/yanks"),
+        description: String::from("Versions of crates that were recently yanked or unyanked."),
+        items,
+    }
+    .respond(&site)
+}
+
+async fn ownership_feed(
+    State((db, cache, _)): State<AppState>,
+    Extension(site): Extension<SiteUrl>,
+) -> Result<Response, AppError> {
+    let items = ownership::recent(&db, &cache)?
+        .into_iter()
+        .map(|entry| {
+            let owner = if entry.team {
+                format!("team {}", entry.owner)
+            } else {
+                entry.owner
+            };
+            let (action, preposition) = if entry.added {
+                ("added", "to")
+            } else {
+                ("removed", "from")
+            };
+            FeedItem {
+                title: format!("{owner} was {action} as an owner of {}", entry.name),
+                link: format!("/crates/{}", entry.name),
+                guid: format!("ownership-{}", entry.id),
+                published: rss_date(&entry.at),
+                description: format!(
+                    "{owner} was {action} {preposition} the owners of {}.",
+                    entry.name
+                ),
+            }
+        })
+        .collect();
+
+    Feed {
+        title: String::from("delve.rs: ownership changes"),
+        link: String::from("/ownership"),
+        description: String::from("Owners recently added to or removed from crates."),
+        items,
+    }
+    .respond(&site)
+}
+
+/// Advisories, yanks, and suspected impersonations of the crates owned by a
+/// user or team.
+pub(crate) async fn owner_feed(
+    State((db, cache, _)): State<AppState>,
+    Path(login): Path<String>,
+    Extension(site): Extension<SiteUrl>,
+) -> Result<Response, AppError> {
+    let owner = OwnerAlerts::load(&db, &cache, &login)?.ok_or(AppError::NotFound)?;
+    let entries = owner
+        .alerts
+        .into_iter()
+        .filter_map(|alert| {
+            Some(AtomEntry {
+                updated: atom_date(&alert.at)?,
+                id: format!("urn:delve-rs:{}", alert.guid),
+                title: alert.title,
+                link: absolute(&site, alert.link),
+            })
+        })
+        .collect::<Vec<_>>();
+    let updated = match entries.first() {
+        Some(entry) => entry.updated.clone(),
+        None => OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default(),
+    };
+
+    let feed = AtomFeed {
+        id: format!("urn:delve-rs:owner:{}", owner.login.to_lowercase()),
+        title: format!("delve.rs: alerts for {}", owner.login),
+        subtitle: format!(
+            "Advisories, yanks, and suspected impersonations of crates owned by {}.",
+            owner.login
+        ),
+        self_link: site.absolute(&format!("/users/{}/advisories.xml", owner.login)),
+        updated,
+        entries,
+    };
+    Ok(([(CONTENT_TYPE, "application/atom+xml")], feed.render()?).into_response())
+}
+
+pub(crate) async fn watchlist_feed(
+    State((db, cache, _)): State<AppState>,
+    Path(id): Path<String>,
+    Extension(site): Extension<SiteUrl>,
+) -> Result<Response, AppError> {
+    let activity = WatchlistActivity::load(&db, &cache, &id)?.ok_or(AppError::NotFound)?;
+    let items = activity
+        .events
+        .into_iter()
+        .map(|event| FeedItem {
+            description: event.title.clone(),
+            title: event.title,
+            link: event.link,
+            guid: event.guid,
+            published: rss_date(&event.at),
+        })
+        .collect();
+
+    Feed {
+        title: format!("delve.rs: {}", activity.name),
+        link: format!("/watchlists/{}", activity.id),
+        description: format!("Recent activity of the crates in {}.", activity.name),
+        items,
+    }
+    .respond(&site)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn site_links_are_made_absolute() {
+        let site = SiteUrl::new(Some("https://delve.rs/"));
+        assert_eq!(
+            absolute(&site, String::from("/crates/serde")),
+            "https://delve.rs/crates/serde"
+        );
+        assert_eq!(
+            absolute(
+                &site,
+                String::from("https://rustsec.org/advisories/RUSTSEC-2023-0001")
+            ),
+            "https://rustsec.org/advisories/RUSTSEC-2023-0001"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+
+use crate::{
+    cache::CachedCrate,
+    categories,
+    embeddings::SemanticMatches,
+    profile::Platform,
+    ranking::{self, Ranker, Recency},
+    schema::{Category, CratesByCategory, RustVersion, UnsafeIndicator},
+    syntax::{self, Token},
+};
+
+/// Restrictions on a search's result set, parsed out of the query string.
+/// Crates on the blocklist never match, whatever the filters.
+///
+/// Supported filters:
+///
+/// - `msrv<=1.70`: the latest version's `rust-version` must satisfy the
+///   comparison. `<`, `<=`, `=`, `>=`, and `>` are supported. Crates that do
+///   not declare a `rust-version` are excluded.
+/// - `placeholders:hide` or `placeholders:include`: controls how crates that
+///   appear to be name reservations are treated. By default they are demoted.
+/// - `registry:crates-io`: only crates imported from the named registry are
+///   included.
+/// - `ranker:popularity`: orders the results using the named
+///   [`Ranker`](crate::ranking::Ranker) instead of the default.
+/// - `keyword:async`: only crates with the keyword are included.
+/// - `category:database`: only crates in the category with this slug, or one
+///   of its subcategories, are included.
+/// - `lang:de`: only crates whose description and README were detected to be
+///   written in the language with this ISO 639-1 code are included.
+/// - `wasm:true` or `wasm:false`: only crates that appear to support
+///   WebAssembly, or that don't, are included.
+/// - `platform:windows`: only crates with code specific to the platform are
+///   included. `windows`, `macos`, `linux`, `wasm32`, and `embedded` are
+///   supported.
+/// - `proc-macro:false` or `proc-macro:true`: only crates whose build doesn't
+///   run a procedural macro, or does, are included. Macros the crate requires
+///   count, as do crates that are macros themselves.
+/// - `build-script:false` or `build-script:true`: the same, for build scripts.
+/// - `unsafe:forbid`, `unsafe:none`, or `unsafe:some`: only crates whose
+///   scanned version forbids unsafe code, compiles none, or compiles some are
+///   included. `unsafe:none` includes crates that forbid it. Crates that
+///   haven't been scanned are excluded.
+/// - `audited:true` or `audited:false`: only crates that a cargo-vet audit or
+///   a positive crev review certifies, or that none does, are included.
+/// - `unmaintained:false` or `unmaintained:true`: only crates that don't
+///   appear to be unmaintained, or that do, are included.
+#[derive(Default, Debug, Clone)]
+pub struct Filters {
+    msrv: Option<(Comparison, RustVersion)>,
+    pub placeholders: PlaceholderMode,
+    registry: Option<String>,
+    keywords: Vec<String>,
+    categories: Vec<String>,
+    language: Option<String>,
+    wasm: Option<bool>,
+    platforms: Vec<Platform>,
+    proc_macro: Option<bool>,
+    build_script: Option<bool>,
+    unsafe_usage: Option<UnsafeFilter>,
+    audited: Option<bool>,
+    unmaintained: Option<bool>,
+    pub ranker: Option<&'static dyn Ranker>,
+    pub recency: Recency,
+    /// The ISO 639-1 codes of the languages the searcher reads. When set,
+    /// crates detected to be documented in other languages are demoted.
+    pub readable_languages: Vec<String>,
+    /// The crates most similar to the query's embedding, which are included
+    /// in the results even if they don't match the query's words.
+    pub semantic: Option<Arc<SemanticMatches>>,
+    /// When the search must finish by. Searches that run past it while
+    /// scoring return the search index's matches alone, and searches whose
+    /// search index lookup runs past it return nothing.
+    pub deadline: Option<Instant>,
+    /// The most matches to fetch from the search index, if fewer than usual,
+    /// which keeps the lookup short for searches with a tight deadline.
+    pub index_limit: Option<usize>,
+}
+
+/// How long scoring a search's results may take, shared with the handlers
+/// that search as an extension. None places no limit on searches.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryTimeout(pub Option<Duration>);
+
+impl QueryTimeout {
+    /// Returns `milliseconds` as a timeout, where 0 is no limit.
+    pub fn from_millis(milliseconds: u64) -> Self {
+        Self((milliseconds > 0).then(|| Duration::from_millis(milliseconds)))
+    }
+
+    /// Returns the deadline of a search starting now.
+    pub fn deadline(self) -> Option<Instant> {
+        self.0.map(|timeout| Instant::now() + timeout)
+    }
+}
+
+/// How likely placeholder crates are treated in search results.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PlaceholderMode {
+    /// Placeholders are included, but ranked below other matches.
+    #[default]
+    Demote,
+    /// Placeholders are excluded from the results.
+    Hide,
+    /// Placeholders are ranked like any other crate.
+    Include,
+}
+
+/// How much unsafe code a crate must contain to match the `unsafe:` filter.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum UnsafeFilter {
+    Forbid,
+    None,
+    Some,
+}
+
+impl UnsafeFilter {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "forbid" => Some(Self::Forbid),
+            "none" => Some(Self::None),
+            "some" => Some(Self::Some),
+            _ => None,
+        }
+    }
+
+    fn matches(self, indicator: UnsafeIndicator) -> bool {
+        match self {
+            Self::Forbid => indicator == UnsafeIndicator::Forbidden,
+            Self::None => !matches!(indicator, UnsafeIndicator::Used(_)),
+            Self::Some => matches!(indicator, UnsafeIndicator::Used(_)),
+        }
+    }
+}
+
+/// The multiplier applied to the score of demoted placeholder crates.
+const PLACEHOLDER_PENALTY: f32 = 0.1;
+
+/// The multiplier applied to the score of crates whose READMEs appear to be
+/// copied from another crate.
+const DUPLICATE_PENALTY: f32 = 0.25;
+
+/// The multiplier applied to the score of every crate whose owner published a
+/// wave of near-identical crates.
+const PUBLISH_WAVE_PENALTY: f32 = 0.1;
+
+/// The multiplier applied to the score of crates documented in a language
+/// the searcher doesn't read.
+const LANGUAGE_PENALTY: f32 = 0.5;
+
+impl Filters {
+    /// Splits `query` into its search terms and filters. Quoted phrases are
+    /// kept in the search terms, even if they look like filters, as are
+    /// operators and parentheses.
+    pub fn parse(query: &str) -> (String, Self) {
+        let mut filters = Self::default();
+        let mut terms = Vec::new();
+        for token in syntax::tokens(query) {
+            if let Token::Term(term) = token {
+                if !term.phrase && term.field.is_none() && filters.parse_filter(term.text) {
+                    continue;
+                }
+            }
+            terms.push(token.to_string());
+        }
+
+        (terms.join(" "), filters)
+    }
+
+    fn parse_filter(&mut self, word: &str) -> bool {
+        if let Some(requirement) = word.strip_prefix("msrv") {
+            if let Some((comparison, version)) = Comparison::parse(requirement) {
+                if let Ok(version) = version.parse() {
+                    self.msrv = Some((comparison, version));
+                    return true;
+                }
+            }
+        } else if let Some(mode) = word.strip_prefix("placeholders:") {
+            match mode {
+                "hide" | "false" => self.placeholders = PlaceholderMode::Hide,
+                "include" | "true" => self.placeholders = PlaceholderMode::Include,
+                "demote" => self.placeholders = PlaceholderMode::Demote,
+                _ => return false,
+            }
+            return true;
+        } else if let Some(registry) = word.strip_prefix("registry:") {
+            if !registry.is_empty() {
+                self.registry = Some(registry.to_string());
+                return true;
+            }
+        } else if let Some(name) = word.strip_prefix("ranker:") {
+            if let Some(ranker) = ranking::by_name(name) {
+                self.ranker = Some(ranker);
+                return true;
+            }
+        } else if let Some(keyword) = word.strip_prefix("keyword:") {
+            if !keyword.is_empty() {
+                self.keywords.push(keyword.to_ascii_lowercase());
+                return true;
+            }
+        } else if let Some(slug) = word.strip_prefix("category:") {
+            if !slug.is_empty() {
+                self.categories.push(slug.to_ascii_lowercase());
+                return true;
+            }
+        } else if let Some(wasm) = word.strip_prefix("wasm:") {
+            if let Ok(wasm) = wasm.parse() {
+                self.wasm = Some(wasm);
+                return true;
+            }
+        } else if let Some(proc_macro) = word.strip_prefix("proc-macro:") {
+            if let Ok(proc_macro) = proc_macro.parse() {
+                self.proc_macro = Some(proc_macro);
+                return true;
+            }
+        } else if let Some(build_script) = word.strip_prefix("build-script:") {
+            if let Ok(build_script) = build_script.parse() {
+                self.build_script = Some(build_script);
+                return true;
+            }
+        } else if let Some(unsafe_usage) = word.strip_prefix("unsafe:") {
+            if let Some(filter) = UnsafeFilter::parse(unsafe_usage) {
+                self.unsafe_usage = Some(filter);
+                return true;
+            }
+        } else if let Some(audited) = word.strip_prefix("audited:") {
+            if let Ok(audited) = audited.parse() {
+                self.audited = Some(audited);
+                return true;
+            }
+        } else if let Some(unmaintained) = word.strip_prefix("unmaintained:") {
+            if let Ok(unmaintained) = unmaintained.parse() {
+                self.unmaintained = Some(unmaintained);
+                return true;
+            }
+        } else if let Some(platform) = word.strip_prefix("platform:") {
+            if let Some(platform) = Platform::parse(platform) {
+                self.platforms.push(platform);
+                return true;
+            }
+        } else if let Some(language) = word.strip_prefix("lang:") {
+            if !language.is_empty() {
+                self.language = Some(language.to_ascii_lowercase());
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns true if `krate` satisfies every filter.
+    pub fn matches(&self, krate: &CachedCrate) -> bool {
+        if krate.blocked() {
+            return false;
+        }
+
+        if let Some((comparison, msrv)) = self.msrv {
+            let Some(rust_version) = krate.rust_version() else { return false };
+            if !comparison.compare(rust_version, msrv) {
+                return false;
+            }
+        }
+
+        if krate.likely_placeholder() && self.placeholders == PlaceholderMode::Hide {
+            return false;
+        }
+
+        if let Some(registry) = &self.registry {
+            if !krate.registry().eq_ignore_ascii_case(registry) {
+                return false;
+            }
+        }
+
+        if !self
+            .keywords
+            .iter()
+            .all(|keyword| krate.has_keyword(keyword))
+        {
+            return false;
+        }
+
+        if let Some(language) = &self.language {
+            if krate.language() != Some(language.as_str()) {
+                return false;
+            }
+        }
+
+        if self.wasm.map_or(false, |wasm| krate.profile().wasm != wasm) {
+            return false;
+        }
+
+        let profile = krate.profile();
+        if !self
+            .platforms
+            .iter()
+            .all(|platform| platform.supported_by(&profile))
+        {
+            return false;
+        }
+
+        if self
+            .proc_macro
+            .map_or(false, |proc_macro| profile.runs_proc_macros() != proc_macro)
+            || self.build_script.map_or(false, |build_script| {
+                profile.runs_build_scripts() != build_script
+            })
+        {
+            return false;
+        }
+
+        if let Some(filter) = self.unsafe_usage {
+            let Some(indicator) = krate.unsafe_usage() else { return false };
+            if !filter.matches(indicator) {
+                return false;
+            }
+        }
+
+        if self
+            .audited
+            .map_or(false, |audited| krate.audited() != audited)
+            || self
+                .unmaintained
+                .map_or(false, |unmaintained| krate.unmaintained() != unmaintained)
+        {
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns the ids of the crates in every required category or one of its
+    /// subcategories, or None if no categories are required.
+    pub fn category_crates(&self, db: &Database) -> anyhow::Result<Option<HashSet<u64>>> {
+        if self.categories.is_empty() {
+            return Ok(None);
+        }
+
+        let categories = Category::all(db).query()?;
+        let mut required = Vec::with_capacity(self.categories.len());
+        for slug in &self.categories {
+            let mut crates = HashSet::new();
+            for category_id in categories::with_descendants(&categories, slug) {
+                for mapping in CratesByCategory::entries(db)
+                    .with_key(&category_id)
+                    .query()?
+                {
+                    crates.insert(mapping.source.id.deserialize::<u64>()?);
+                }
+            }
+            required.push(crates);
+        }
+
+        let mut required = required.into_iter();
+        Ok(required.next().map(|first| {
+            required.fold(first, |crates, other| {
+                crates.intersection(&other).copied().collect()
+            })
+        }))
+    }
+
+    /// Returns the ranker that orders the results.
+    pub fn ranker(&self) -> &'static dyn Ranker {
+        self.ranker.unwrap_or_else(ranking::default_ranker)
+    }
+
+    /// Returns the multiplier to apply to `krate`'s relevance score.
+    pub fn score_multiplier(&self, krate: &CachedCrate) -> f32 {
+        let mut multiplier = 1.;
+        if krate.likely_placeholder() && self.placeholders == PlaceholderMode::Demote {
+            multiplier *= PLACEHOLDER_PENALTY;
+        }
+        if krate.duplicate_of().is_some() {
+            multiplier *= DUPLICATE_PENALTY;
+        }
+        if krate.in_publish_wave() {
+            multiplier *= PUBLISH_WAVE_PENALTY;
+        }
+        if let Some(language) = krate.language() {
+            if !self.readable_languages.is_empty()
+                && !self
+                    .readable_languages
+                    .iter()
+                    .any(|readable| readable == language)
+            {
+                multiplier *= LANGUAGE_PENALTY;
+            }
+        }
+        multiplier
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Comparison {
+    LessThan,
+    LessThanOrEqual,
+    Equal,
+    GreaterThanOrEqual,
+    GreaterThan,
+}
+
+impl Comparison {
+    fn parse(requirement: &str) -> Option<(Self, &str)> {
+        if let Some(value) = requirement.strip_prefix("<=") {
+            Some((Self::LessThanOrEqual, value))
+        } else if let Some(value) = requirement.strip_prefix(">=") {
+            Some((Self::GreaterThanOrEqual, value))
+        } else if let Some(value) = requirement.strip_prefix('<') {
+            Some((Self::LessThan, value))
+        } else if let Some(value) = requirement.strip_prefix('>') {
+            Some((Self::GreaterThan, value))
+        } else if let Some(value) = requirement.strip_prefix('=') {
+            Some((Self::Equal, value))
+        } else {
+            requirement
+                .strip_prefix(':')
+                .map(|value| (Self::Equal, value))
+        }
+    }
+
+    fn compare<T: Ord>(self, value: T, target: T) -> bool {
+        match self {
+            Comparison::LessThan => value < target,
+            Comparison::LessThanOrEqual => value <= target,
+            Comparison::Equal => value == target,
+            Comparison::GreaterThanOrEqual => value >= target,
+            Comparison::GreaterThan => value > target,
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Detection of crates that continue another crate under a new name, so that
+//! searches for an abandoned crate can suggest its maintained fork.
+//!
+//! A crate is taken to be a fork of another when its description says so,
+//! such as "A maintained fork of foo", or when its repository has the same
+//! name as the other crate's repository under a different owner, as forked
+//! repositories do on GitHub and GitLab, and its crate name extends the
+//! other's by a `-` or `_` separated word, such as `foo-ng` or `new_foo`.
+//! Administrators can also link crates to their successors, and their links
+//! are never overwritten.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use time::Date;
+
+use crate::{
+    aliases,
+    schema::{AliasSource, Crate, CrateSuccessor, CratesByNormalizedName},
+    unmaintained,
+};
+
+/// Phrases in a description that introduce the name of the crate it forks.
+const FORK_PHRASES: &[&str] = &["fork of", "forked from", "fork from"];
+
+/// The most crates sharing a repository name that are compared with each
+/// other. Generic names such as `rust` are shared by too many unrelated
+/// repositories to say anything.
+const MAX_REPOSITORY_GROUP: usize = 50;
+
+const BATCH_SIZE: usize = 1_000;
+
+/// A crate's repository, split into the parts that identify a fork.
+#[derive(Debug)]
+struct RepositoryPath {
+    host: String,
+    owner: String,
+    name: String,
+}
+
+impl RepositoryPath {
+    /// Parses URLs such as `https://github.com/owner/name.git`, ignoring any
+    /// path after the repository's name.
+    fn parse(url: &str) -> Option<Self> {
+        let url = url.trim().to_ascii_lowercase();
+        let url = url.strip_prefix("git+").unwrap_or(&url);
+        let (_, rest) = url.split_once("://")?;
+        let mut parts = rest.split('/').filter(|part| !part.is_empty());
+        let host = parts.next()?.trim_start_matches("www.").to_string();
+        let owner = parts.next()?.to_string();
+        let name = parts.next()?.trim_end_matches(".git").to_string();
+        (!name.is_empty()).then_some(Self { host, owner, name })
+    }
+}
+
+#[derive(Debug)]
+struct Candidate {
+    name: String,
+    normalized: String,
+    registry: Option<String>,
+    created_at: String,
+    description: String,
+    repository: Option<RepositoryPath>,
+}
+
+/// Detects forks among every crate, replacing the previously detected
+/// successors of each crate whose successors weren't entered by an
+/// administrator.
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    println!("Finding forks");
+    let ids = CratesByNormalizedName::entries(db)
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.source.id.deserialize())
+        .collect::<Result<Vec<u64>, _>>()?;
+    let mut candidates = Vec::with_capacity(ids.len());
+    for batch in ids.chunks(BATCH_SIZE) {
+        for krate in Crate::get_multiple(batch, db)? {
+            candidates.push(Candidate {
+                normalized: Crate::normalized_name(&krate.contents.name),
+                name: krate.contents.name,
+                registry: krate.contents.registry,
+                created_at: krate.contents.created_at,
+                repository: RepositoryPath::parse(&krate.contents.repository),
+                description: krate.contents.description,
+            });
+        }
+    }
+
+    let detected = detect(&candidates);
+
+    let mut existing = CrateSuccessor::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id.clone(), d))
+        .collect::<HashMap<_, _>>();
+    let mut tx = Transaction::new();
+    for (original, forks) in detected {
+        let successor = CrateSuccessor {
+            successors: forks.into_iter().collect(),
+            source: AliasSource::Heuristic,
+        };
+        let operation = match existing.remove(&original) {
+            Some(existing)
+                if existing.contents.source == AliasSource::Manual
+                    || existing.contents == successor =>
+            {
+                continue
+            }
+            Some(existing) => {
+                Operation::update_serialized::<CrateSuccessor>(existing.header, &successor)?
+            }
+            None => Operation::insert_serialized::<CrateSuccessor>(Some(&original), &successor)?,
+        };
+        tx.operations.push(operation);
+        if tx.operations.len() >= 100_000 {
+            tx.apply(db)?;
+            tx = Transaction::new();
+        }
+    }
+    // Detected forks that no longer look like forks.
+    for stale in existing
+        .into_values()
+        .filter(|stale| stale.contents.source == AliasSource::Heuristic)
+    {
+        tx.operations.push(Operation::delete(
+            CrateSuccessor::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the names of the forks of each crate with any, keyed by the
+/// forked crate's normalized name. Crates are only forks of crates from the
+/// same registry.
+fn detect(candidates: &[Candidate]) -> BTreeMap<String, BTreeSet<String>> {
+    let mut forks = BTreeMap::<String, BTreeSet<String>>::new();
+    let names = candidates
+        .iter()
+        .map(|candidate| (&candidate.registry, candidate.normalized.as_str()))
+        .collect::<HashSet<_>>();
+    for candidate in candidates {
+        for original in described_originals(&candidate.description) {
+            let original = Crate::normalized_name(original);
+            if original != candidate.normalized
+                && names.contains(&(&candidate.registry, original.as_str()))
+            {
+                forks
+                    .entry(original)
+                    .or_default()
+                    .insert(candidate.name.clone());
+            }
+        }
+    }
+
+    let mut by_repository = HashMap::<_, Vec<(&str, &Candidate)>>::new();
+    for candidate in candidates {
+        if let Some(repository) = &candidate.repository {
+            by_repository
+                .entry((&candidate.registry, &repository.host, &repository.name))
+                .or_default()
+                .push((&repository.owner, candidate));
+        }
+    }
+    for group in by_repository
+        .values()
+        .filter(|group| (2..=MAX_REPOSITORY_GROUP).contains(&group.len()))
+    {
+        for (fork_owner, fork) in group {
+            for (original_owner, original) in group {
+                if fork_owner != original_owner
+                    && fork.created_at > original.created_at
+                    && extends(&fork.normalized, &original.normalized)
+                {
+                    forks
+                        .entry(original.normalized.clone())
+                        .or_default()
+                        .insert(fork.name.clone());
+                }
+            }
+        }
+    }
+
+    forks
+}
+
+/// Returns true if the normalized name `fork` is `original` with words added
+/// before or after it, so that `foo_ng` extends `foo` but `foobar` doesn't.
+fn extends(fork: &str, original: &str) -> bool {
+    fork.strip_prefix(original)
+        .map_or(false, |rest| rest.len() > 1 && rest.starts_with('_'))
+        || fork
+            .strip_suffix(original)
+            .map_or(false, |rest| rest.len() > 1 && rest.ends_with('_'))
+}
+
+/// Returns the names of the crates `description` says it's a fork of.
+fn described_originals(description: &str) -> Vec<&str> {
+    let lowercase = description.to_ascii_lowercase();
+    let words = description.split_ascii_whitespace().collect::<Vec<_>>();
+    let lowercase_words = lowercase.split_ascii_whitespace().collect::<Vec<_>>();
+    let mut originals = Vec::new();
+    for phrase in FORK_PHRASES {
+        let phrase = phrase.split(' ').collect::<Vec<_>>();
+        for (index, window) in lowercase_words.windows(phrase.len() + 1).enumerate() {
+            if window[..phrase.len()] == phrase[..] {
+                if let Some(name) = aliases::crate_name_token(words[index + phrase.len()]) {
+                    originals.push(name);
+                }
+            }
+        }
+    }
+    originals
+}
+
+/// Returns true if a crate appears to be abandoned, so that its detected
+/// forks are worth suggesting in its place.
+pub(crate) fn is_abandoned(
+    last_published: Option<Date>,
+    unmaintained: bool,
+    deprecated: bool,
+    today: Date,
+) -> bool {
+    unmaintained
+        || deprecated
+        || last_published.map_or(true, |published| {
+            today - published > unmaintained::RELEASE_GAP
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, created_at: &str, description: &str, repository: &str) -> Candidate {
+        Candidate {
+            name: name.to_string(),
+            normalized: Crate::normalized_name(name),
+            registry: None,
+            created_at: created_at.to_string(),
+            description: description.to_string(),
+            repository: RepositoryPath::parse(repository),
+        }
+    }
+
+    fn forks_of<'a>(
+        detected: &'a BTreeMap<String, BTreeSet<String>>,
+        original: &str,
+    ) -> Vec<&'a str> {
+        detected
+            .get(original)
+            .map(|forks| forks.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn repository_paths() {
+        let path = RepositoryPath::parse("git+https://www.GitHub.com/Owner/Name.git").unwrap();
+        assert_eq!(
+            (path.host.as_str(), path.owner.as_str(), path.name.as_str()),
+            ("github.com", "owner", "name")
+        );
+        let path =
+            RepositoryPath::parse("https://gitlab.com/owner/name/-/tree/main/crate").unwrap();
+        assert_eq!(
+            (path.host.as_str(), path.owner.as_str(), path.name.as_str()),
+            ("gitlab.com", "owner", "name")
+        );
+        assert!(RepositoryPath::parse("").is_none());
+        assert!(RepositoryPath::parse("github.com/owner/name").is_none());
+        assert!(RepositoryPath::parse("https://github.com/owner").is_none());
+        assert!(RepositoryPath::parse("https://github.com/owner/.git").is_none());
+    }
+
+    #[test]
+    fn originals_named_in_descriptions() {
+        assert_eq!(described_originals("A maintained fork of foo."), ["foo"]);
+        assert_eq!(
+            described_originals("Forked from `bar-rs` to add async support"),
+            ["bar-rs"]
+        );
+        assert!(described_originals("A fork-free allocator").is_empty());
+        assert!(described_originals("Fork of").is_empty());
+    }
+
+    #[test]
+    fn forks_named_in_descriptions() {
+        let candidates = [
+            candidate("foo", "2018", "", ""),
+            candidate("better-foo", "2021", "A maintained fork of foo.", ""),
+            candidate("baz", "2021", "A fork of unknown.", ""),
+            candidate("self", "2021", "A fork of self.", ""),
+        ];
+        let detected = detect(&candidates);
+        assert_eq!(forks_of(&detected, "foo"), ["better-foo"]);
+        assert_eq!(detected.len(), 1);
+    }
+
+    #[test]
+    fn forks_sharing_a_repository_name() {
+        let candidates = [
+            candidate("foo", "2018", "", "https://github.com/alice/foo"),
+            candidate("foo-ng", "2021", "", "https://github.com/bob/foo"),
+            candidate("new_foo", "2021", "", "https://github.com/carol/foo"),
+            candidate("foobar", "2021", "", "https://github.com/dave/foo"),
+            // The original's owner publishing another crate isn't a fork.
+            candidate("foo-derive", "2021", "", "https://github.com/alice/foo"),
+            // Nor is a crate from a repository hosted elsewhere.
+            candidate("foo-gl", "2021", "", "https://gitlab.com/erin/foo"),
+            // Nor is an older crate.
+            candidate("foo-old", "2015", "", "https://github.com/frank/foo"),
+        ];
+        let detected = detect(&candidates);
+        assert_eq!(forks_of(&detected, "foo"), ["foo-ng", "new_foo"]);
+        assert_eq!(detected.len(), 1);
+    }
+
+    #[test]
+    fn forks_are_from_the_same_registry() {
+        let mut fork = candidate(
+            "foo-ng",
+            "2021",
+            "A fork of foo",
+            "https://github.com/bob/foo",
+        );
+        fork.registry = Some(String::from("internal"));
+        let candidates = [
+            candidate("foo", "2018", "", "https://github.com/alice/foo"),
+            fork,
+        ];
+        assert!(detect(&candidates).is_empty());
+    }
+
+    #[test]
+    fn extended_names() {
+        assert!(extends("foo_ng", "foo"));
+        assert!(extends("new_foo", "foo"));
+        assert!(!extends("foobar", "foo"));
+        assert!(!extends("barfoo", "foo"));
+        assert!(!extends("foo", "foo"));
+        assert!(!extends("foo_", "foo"));
+    }
+}
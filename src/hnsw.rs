@@ -0,0 +1,272 @@
+//! An approximate nearest neighbor index of embedding vectors.
+//!
+//! This is a hierarchical navigable small world graph: each vector is linked
+//! to its nearest neighbors on level 0, and a shrinking random subset of the
+//! vectors is linked again on each higher level. A search descends greedily
+//! from the sparse top level, then explores level 0 around the closest vector
+//! found, examining a small fraction of the vectors.
+//!
+//! Vectors are normalized when inserted, so the distance between two vectors
+//! is one minus their cosine similarity.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+};
+
+/// The most neighbors linked to each vector above level 0.
+const MAX_NEIGHBORS: usize = 16;
+
+/// The most neighbors linked to each vector on level 0, which every search
+/// ends on.
+const MAX_LEVEL_0_NEIGHBORS: usize = MAX_NEIGHBORS * 2;
+
+/// How many candidates are considered when linking a new vector. Larger
+/// values build a more accurate graph more slowly.
+const CONSTRUCTION_CANDIDATES: usize = 100;
+
+/// How many candidates are considered by each search, at least.
+const SEARCH_CANDIDATES: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct Hnsw {
+    dimensions: usize,
+    /// Every vector, normalized, one after another.
+    vectors: Vec<f32>,
+    /// The crate id of each vector.
+    ids: Vec<u64>,
+    /// The neighbors of each vector on each level it's on, from level 0 up.
+    links: Vec<Vec<Vec<u32>>>,
+    /// The vector that searches start from, which is on the top level.
+    entry: Option<u32>,
+}
+
+impl Hnsw {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Adds the embedding of the crate with `id`. Vectors with the wrong
+    /// number of dimensions or without a direction are ignored.
+    pub fn insert(&mut self, id: u64, vector: &[f32]) {
+        let Some(vector) = normalized(vector).filter(|v| v.len() == self.dimensions)
+            else { return };
+        let node = u32::try_from(self.ids.len()).expect("too many vectors");
+        let level = level_of(id);
+        self.vectors.extend_from_slice(&vector);
+        self.ids.push(id);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+        let top_level = self.level(entry);
+        for search_level in (level + 1..=top_level).rev() {
+            entry = self.closest(&vector, entry, search_level);
+        }
+        for link_level in (0..=level.min(top_level)).rev() {
+            let candidates = self.search_level(&vector, entry, CONSTRUCTION_CANDIDATES, link_level);
+            let max_neighbors = max_neighbors(link_level);
+            let neighbors = candidates
+                .iter()
+                .take(max_neighbors)
+                .map(|(_, neighbor)| *neighbor)
+                .collect::<Vec<_>>();
+            for &neighbor in &neighbors {
+                self.link(neighbor, node, link_level);
+            }
+            self.links[node as usize][link_level] = neighbors;
+            entry = candidates[0].1;
+        }
+        if level > top_level {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Returns the ids of up to `count` crates whose embeddings are most
+    /// similar to `query`, with their cosine similarity, most similar first.
+    pub fn search(&self, query: &[f32], count: usize) -> Vec<(u64, f32)> {
+        let (Some(mut entry), Some(query)) = (self.entry, normalized(query))
+            else { return Vec::new() };
+        if query.len() != self.dimensions {
+            return Vec::new();
+        }
+        for level in (1..=self.level(entry)).rev() {
+            entry = self.closest(&query, entry, level);
+        }
+        self.search_level(&query, entry, count.max(SEARCH_CANDIDATES), 0)
+            .into_iter()
+            .take(count)
+            .map(|(distance, node)| (self.ids[node as usize], 1. - distance))
+            .collect()
+    }
+
+    fn vector(&self, node: u32) -> &[f32] {
+        let start = node as usize * self.dimensions;
+        &self.vectors[start..start + self.dimensions]
+    }
+
+    fn distance(&self, query: &[f32], node: u32) -> f32 {
+        let similarity = query
+            .iter()
+            .zip(self.vector(node))
+            .map(|(a, b)| a * b)
+            .sum::<f32>();
+        1. - similarity
+    }
+
+    fn level(&self, node: u32) -> usize {
+        self.links[node as usize].len() - 1
+    }
+
+    /// Follows links on `level` from `entry` while they lead closer to
+    /// `query`, returning the closest node reached.
+    fn closest(&self, query: &[f32], mut entry: u32, level: usize) -> u32 {
+        let mut distance = self.distance(query, entry);
+        loop {
+            let mut improved = false;
+            for &neighbor in &self.links[entry as usize][level] {
+                let neighbor_distance = self.distance(query, neighbor);
+                if neighbor_distance < distance {
+                    distance = neighbor_distance;
+                    entry = neighbor;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return entry;
+            }
+        }
+    }
+
+    /// Returns up to `candidates` nodes on `level` near `query`, found by
+    /// exploring outward from `entry`, closest first.
+    fn search_level(
+        &self,
+        query: &[f32],
+        entry: u32,
+        candidates: usize,
+        level: usize,
+    ) -> Vec<(f32, u32)> {
+        let entry_distance = Scored(self.distance(query, entry), entry);
+        let mut visited = HashSet::from([entry]);
+        let mut to_visit = BinaryHeap::from([Reverse(entry_distance)]);
+        let mut nearest = BinaryHeap::from([entry_distance]);
+
+        while let Some(Reverse(Scored(distance, node))) = to_visit.pop() {
+            let farthest = nearest
+                .peek()
+                .map_or(f32::MAX, |Scored(distance, _)| *distance);
+            if distance > farthest && nearest.len() >= candidates {
+                break;
+            }
+            for &neighbor in &self.links[node as usize][level] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let neighbor = Scored(self.distance(query, neighbor), neighbor);
+                let farthest = nearest
+                    .peek()
+                    .map_or(f32::MAX, |Scored(distance, _)| *distance);
+                if nearest.len() < candidates || neighbor.0 < farthest {
+                    to_visit.push(Reverse(neighbor));
+                    nearest.push(neighbor);
+                    if nearest.len() > candidates {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+
+        nearest
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Scored(distance, node)| (distance, node))
+            .collect()
+    }
+
+    /// Links `node` to `new_neighbor` on `level`, dropping its farthest
+    /// neighbor if it has too many.
+    fn link(&mut self, node: u32, new_neighbor: u32, level: usize) {
+        self.links[node as usize][level].push(new_neighbor);
+        if self.links[node as usize][level].len() <= max_neighbors(level) {
+            return;
+        }
+        let vector = self.vector(node).to_vec();
+        let mut neighbors = std::mem::take(&mut self.links[node as usize][level])
+            .into_iter()
+            .map(|neighbor| Scored(self.distance(&vector, neighbor), neighbor))
+            .collect::<Vec<_>>();
+        neighbors.sort_unstable();
+        neighbors.truncate(max_neighbors(level));
+        self.links[node as usize][level] = neighbors
+            .into_iter()
+            .map(|Scored(_, neighbor)| neighbor)
+            .collect();
+    }
+}
+
+fn max_neighbors(level: usize) -> usize {
+    if level == 0 {
+        MAX_LEVEL_0_NEIGHBORS
+    } else {
+        MAX_NEIGHBORS
+    }
+}
+
+/// Picks the highest level the crate with `id` is on. Each level has about
+/// 1 in [`MAX_NEIGHBORS`] of the vectors on the level below it. The level is
+/// derived from the id rather than chosen randomly, so that rebuilding the
+/// index produces the same graph.
+fn level_of(id: u64) -> usize {
+    // SplitMix64, which spreads sequential ids evenly.
+    let mut hash = id.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    // A uniform value in (0, 1].
+    let uniform = ((hash >> 11) as f64 + 1.) / (1_u64 << 53) as f64;
+    (-uniform.ln() / (MAX_NEIGHBORS as f64).ln()) as usize
+}
+
+/// Returns `vector` scaled to a length of 1, or None if it has no length.
+fn normalized(vector: &[f32]) -> Option<Vec<f32>> {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    (length > 0. && length.is_finite()).then(|| vector.iter().map(|x| x / length).collect())
+}
+
+/// A node and its distance from a query, ordered by the distance.
+#[derive(Clone, Copy, Debug)]
+struct Scored(f32, u32);
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
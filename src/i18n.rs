@@ -0,0 +1,299 @@
+//! Translations of the site's interface.
+//!
+//! Each locale is a [Fluent](https://projectfluent.org) file in `src/locales`,
+//! named by its language tag. The files are embedded at compile time and
+//! formatted with `fluent-bundle`, which picks plural categories by the
+//! locale's own rules.
+//!
+//! A locale whose file can't be parsed is logged and left out, so its
+//! visitors see the default locale. Tests check that every file parses and
+//! translates every message of the default locale.
+
+use std::{collections::HashMap, fmt::Display};
+
+use axum::{
+    http::{
+        header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, COOKIE, VARY},
+        HeaderMap, HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response,
+};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use include_dir::{include_dir, Dir};
+use once_cell::sync::Lazy;
+use unic_langid::LanguageIdentifier;
+
+static LOCALES: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/locales");
+
+/// The locale used when the request doesn't accept any translated locale, and
+/// for messages missing from another locale's file.
+const DEFAULT_LOCALE: &str = "en";
+
+/// The cookie that overrides the request's `Accept-Language` header.
+const LANGUAGE_COOKIE: &str = "lang";
+
+type Bundle = FluentBundle<FluentResource>;
+
+static BUNDLES: Lazy<HashMap<&'static str, Bundle>> = Lazy::new(|| {
+    LOCALES
+        .files()
+        .filter_map(|file| {
+            let path = file.path().to_str()?;
+            let language = path.strip_suffix(".ftl")?;
+            let source = file.contents_utf8()?;
+            match bundle(language, source) {
+                Ok(bundle) => Some((language, bundle)),
+                Err(err) => {
+                    tracing::error!("error parsing {path}: {err}");
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+fn bundle(language: &str, source: &str) -> Result<Bundle, String> {
+    let language = language
+        .parse::<LanguageIdentifier>()
+        .map_err(|err| format!("invalid language tag: {err}"))?;
+    let resource =
+        FluentResource::try_new(source.to_string()).map_err(|(_, errors)| format!("{errors:?}"))?;
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // The isolation marks Fluent adds around variables would show up in
+    // attributes and plain text emails.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .map_err(|errors| format!("{errors:?}"))?;
+    Ok(bundle)
+}
+
+tokio::task_local! {
+    static LANGUAGE: &'static str;
+}
+
+/// Middleware that picks the language of the response from the `lang` cookie
+/// or the `Accept-Language` header. Templates rendered while handling the
+/// request are translated into it by [`t`].
+pub(crate) async fn negotiate<B>(request: Request<B>, next: Next<B>) -> Response {
+    let language = negotiated_language(request.headers());
+    let mut response = LANGUAGE.scope(language, next.run(request)).await;
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_LANGUAGE, HeaderValue::from_static(language));
+    headers.append(VARY, HeaderValue::from_static("accept-language"));
+    response
+}
+
+fn negotiated_language(headers: &HeaderMap) -> &'static str {
+    let cookie = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == LANGUAGE_COOKIE).then_some(value)
+        });
+    if let Some(language) = cookie.and_then(supported) {
+        return language;
+    }
+
+    let mut best = None;
+    for value in headers.get_all(ACCEPT_LANGUAGE) {
+        let Ok(value) = value.to_str() else { continue };
+        for range in value.split(',') {
+            let mut parameters = range.split(';');
+            let tag = parameters.next().unwrap_or_default().trim();
+            let quality = parameters
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.);
+            let Some(language) = supported(tag) else { continue };
+            if quality > 0. && best.map_or(true, |(_, best)| quality > best) {
+                best = Some((language, quality));
+            }
+        }
+    }
+    best.map_or(DEFAULT_LOCALE, |(language, _)| language)
+}
+
+/// Returns the primary language of every language the request's
+/// `Accept-Language` header accepts, such as `de` for `de-AT`, along with
+/// English, which nearly every crate is documented in. Returns nothing if the
+/// request doesn't say which languages it accepts.
+pub(crate) fn readable_languages(headers: &HeaderMap) -> Vec<String> {
+    let mut languages = Vec::new();
+    for value in headers.get_all(ACCEPT_LANGUAGE) {
+        let Ok(value) = value.to_str() else { continue };
+        for range in value.split(',') {
+            let mut parameters = range.split(';');
+            let tag = parameters.next().unwrap_or_default().trim();
+            let rejected = parameters
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .any(|quality| {
+                    quality
+                        .parse::<f32>()
+                        .map_or(false, |quality| quality <= 0.)
+                });
+            let primary = tag
+                .split('-')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if !rejected && !primary.is_empty() && primary != "*" && !languages.contains(&primary) {
+                languages.push(primary);
+            }
+        }
+    }
+    if !languages.is_empty() && !languages.iter().any(|language| language == DEFAULT_LOCALE) {
+        languages.push(String::from(DEFAULT_LOCALE));
+    }
+    languages
+}
+
+/// Returns the supported locale matching `tag`, either exactly or by its
+/// primary language, so that `de-AT` matches `de`.
+fn supported(tag: &str) -> Option<&'static str> {
+    let primary = tag.split('-').next().unwrap_or_default();
+    [tag, primary].into_iter().find_map(|tag| {
+        BUNDLES
+            .keys()
+            .find(|language| language.eq_ignore_ascii_case(tag))
+            .copied()
+    })
+}
+
+/// Returns the language of the current response, such as `en`.
+pub fn language() -> &'static str {
+    LANGUAGE
+        .try_with(|language| *language)
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Returns the message `id` in the current response's language.
+pub fn t(id: &str) -> String {
+    translate(id, &[])
+}
+
+/// Returns the message `id` in the current response's language, with the
+/// variable `$name` set to `value`.
+pub fn t_arg(id: &str, name: &str, value: impl Display) -> String {
+    translate(id, &[(name, value.to_string())])
+}
+
+/// Returns the message `id` in the current response's language, with each of
+/// `args` setting a variable.
+pub fn t_args(id: &str, args: &[(&str, String)]) -> String {
+    translate(id, args)
+}
+
+fn translate(id: &str, args: &[(&str, String)]) -> String {
+    let Some((bundle, pattern)) = [language(), DEFAULT_LOCALE]
+        .into_iter()
+        .find_map(|language| {
+            let bundle = BUNDLES.get(language)?;
+            Some((bundle, bundle.get_message(id)?.value()?))
+        })
+    else {
+        tracing::warn!("missing translation for {id}");
+        return id.to_string();
+    };
+
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        // Numbers select plural variants, so pass anything that parses as one
+        // as a number.
+        fluent_args.set(*name, FluentValue::try_number(value.as_str()));
+    }
+    let mut errors = Vec::new();
+    let output = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    if !errors.is_empty() {
+        tracing::warn!("error formatting {id}: {errors:?}");
+    }
+    output.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use fluent_syntax::ast::Entry;
+
+    use super::*;
+
+    fn parse_all() -> Vec<(&'static str, FluentResource)> {
+        LOCALES
+            .files()
+            .map(|file| {
+                let path = file.path().to_str().expect("locale paths are UTF-8");
+                let source = file.contents_utf8().expect("locale files are UTF-8");
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|(_, errors)| panic!("error parsing {path}: {errors:?}"));
+                (path, resource)
+            })
+            .collect()
+    }
+
+    fn message_ids(resource: &FluentResource) -> BTreeSet<&str> {
+        resource
+            .entries()
+            .filter_map(|entry| match entry {
+                Entry::Message(message) => Some(message.id.name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_locale_parses() {
+        let resources = parse_all();
+        assert!(resources
+            .iter()
+            .any(|(path, _)| *path == format!("{DEFAULT_LOCALE}.ftl")));
+        assert_eq!(BUNDLES.len(), resources.len());
+    }
+
+    #[test]
+    fn every_locale_has_every_default_message() {
+        let resources = parse_all();
+        let default = message_ids(
+            &resources
+                .iter()
+                .find(|(path, _)| *path == format!("{DEFAULT_LOCALE}.ftl"))
+                .expect("the default locale exists")
+                .1,
+        );
+        for (path, resource) in &resources {
+            let ids = message_ids(resource);
+            let missing = default.difference(&ids).collect::<BTreeSet<_>>();
+            assert!(missing.is_empty(), "{path} is missing {missing:?}");
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        assert!(bundle("en", "  indented = first").is_err());
+        assert!(bundle("en", "no-equals-sign").is_err());
+        assert!(bundle("en", "unclosed = { $count").is_err());
+        assert!(bundle("en", "no-default = { $count ->\n    [one] one\n }").is_err());
+        assert!(bundle("not a tag", "message = value").is_err());
+    }
+
+    #[test]
+    fn plurals_follow_the_locale() {
+        let source = "items = { $count ->\n    [one] one item\n   *[other] { $count } items\n }\n";
+        let bundle = bundle("en", source).unwrap();
+        let pattern = bundle.get_message("items").unwrap().value().unwrap();
+        let format = |count: &str| {
+            let mut args = FluentArgs::new();
+            args.set("count", FluentValue::try_number(count));
+            let mut errors = Vec::new();
+            bundle
+                .format_pattern(pattern, Some(&args), &mut errors)
+                .into_owned()
+        };
+        assert_eq!(format("1"), "one item");
+        assert_eq!(format("3"), "3 items");
+    }
+}
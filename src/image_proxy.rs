@@ -0,0 +1,422 @@
+//! A proxy for the images referenced by crates' READMEs.
+//!
+//! The site doesn't render READMEs on its own pages yet, so none of them link
+//! to the proxy. It's for clients that display the imported README HTML, such
+//! as embedders, which rewrite each image's `src` to
+//! `/crates/:name/readme/image?url=...` so that their visitors' browsers
+//! never contact the image's host. Pages should do the same once READMEs are
+//! rendered.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+use once_cell::sync::Lazy;
+use reqwest::{redirect, Url};
+use serde::Deserialize;
+
+use crate::{api::find_crate, cache::Cache, schema, webserver::AppState};
+
+/// The largest image the proxy will download.
+const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+
+/// The most bytes of images and URLs kept in memory. The least recently
+/// fetched images are evicted first.
+const MAX_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// How long a downloaded image is served from memory before it's fetched
+/// again.
+const IMAGE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// How long a failed download is remembered before it's retried.
+const FAILURE_TTL: Duration = Duration::from_secs(60 * 10);
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MAX_REDIRECTS: usize = 3;
+
+/// The image types the proxy serves. Other responses are treated as broken
+/// images.
+const ALLOWED_TYPES: [&str; 6] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/avif",
+    "image/svg+xml",
+];
+
+/// Served in place of images that can't be downloaded.
+const BROKEN_IMAGE: &str = concat!(
+    r##"<svg xmlns="http://www.w3.org/2000/svg" width="120" height="24">"##,
+    r##"<rect width="120" height="24" fill="#888" opacity="0.2"/>"##,
+    r##"<text x="60" y="16" font-family="sans-serif" font-size="11" text-anchor="middle" "##,
+    r##"fill="#888">image unavailable</text></svg>"##,
+);
+
+static CACHE: Lazy<Mutex<ImageCache>> = Lazy::new(Mutex::default);
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ImageQuery {
+    url: String,
+}
+
+/// Serves an image referenced by a crate's README, so that visitors'
+/// browsers never contact the image's host. Only URLs that appear in the
+/// crate's README are fetched, which keeps the proxy from being used to
+/// fetch arbitrary URLs.
+pub(crate) async fn serve(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    Query(ImageQuery { url }): Query<ImageQuery>,
+) -> Response {
+    let cached = CACHE.lock().expect("poisoned").get(&url);
+    if let Some(image) = cached {
+        return respond(image);
+    }
+
+    match referenced_by_readme(&db, &cache, &name, &url) {
+        Ok(true) => {}
+        Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            tracing::error!("error loading readme of {name}: {err:?}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let image = match fetch(&url).await {
+        Ok(image) => Some(image),
+        Err(err) => {
+            tracing::debug!("error proxying {url}: {err:#}");
+            None
+        }
+    };
+    CACHE.lock().expect("poisoned").insert(url, image.clone());
+    respond(image)
+}
+
+fn respond(image: Option<Image>) -> Response {
+    match image {
+        Some(image) => (
+            [
+                (CONTENT_TYPE, image.content_type),
+                (CACHE_CONTROL, "public, max-age=86400"),
+                // Scripts in SVG images must not run if the image is opened
+                // directly.
+                (
+                    CONTENT_SECURITY_POLICY,
+                    "default-src 'none'; style-src 'unsafe-inline'; sandbox",
+                ),
+            ],
+            image.bytes,
+        )
+            .into_response(),
+        None => (
+            [
+                (CONTENT_TYPE, "image/svg+xml"),
+                (CACHE_CONTROL, "public, max-age=600"),
+            ],
+            BROKEN_IMAGE,
+        )
+            .into_response(),
+    }
+}
+
+fn referenced_by_readme(
+    db: &Database,
+    cache: &Cache,
+    name: &str,
+    url: &str,
+) -> anyhow::Result<bool> {
+    let Some(id) = find_crate(cache, name)? else { return Ok(false) };
+    let Some(krate) = schema::Crate::get(&id, db)? else { return Ok(false) };
+    // URLs in HTML image tags have their ampersands escaped.
+    let readme = &krate.contents.readme;
+    Ok(readme.contains(url) || readme.contains(&url.replace('&', "&amp;")))
+}
+
+#[derive(Clone, Debug)]
+struct Image {
+    content_type: &'static str,
+    bytes: Bytes,
+}
+
+/// Downloads the image at `url`, following redirects to other public hosts.
+async fn fetch(url: &str) -> anyhow::Result<Image> {
+    let mut url = Url::parse(url)?;
+    for _ in 0..=MAX_REDIRECTS {
+        let mut response = pinned_client(&url).await?.get(url.clone()).send().await?;
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .context("redirect without a location")?
+                .to_str()?;
+            url = url.join(location)?;
+            continue;
+        }
+
+        response = response.error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .and_then(|value| {
+                ALLOWED_TYPES
+                    .iter()
+                    .find(|allowed| allowed.eq_ignore_ascii_case(value.trim()))
+                    .copied()
+            })
+            .context("not a supported image type")?;
+        if response
+            .content_length()
+            .map_or(false, |length| length > MAX_IMAGE_SIZE as u64)
+        {
+            anyhow::bail!("image is too large");
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > MAX_IMAGE_SIZE {
+                anyhow::bail!("image is too large");
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok(Image {
+            content_type,
+            bytes: Bytes::from(bytes),
+        });
+    }
+    anyhow::bail!("too many redirects")
+}
+
+/// Returns a client that connects to `url`'s host at an address resolved
+/// ahead of time, after checking that it's on the public internet. Resolving
+/// first keeps READMEs from making the server request its own network.
+async fn pinned_client(url: &Url) -> anyhow::Result<reqwest::Client> {
+    anyhow::ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "unsupported scheme"
+    );
+    let host = url.host_str().context("missing host")?;
+    let port = url.port_or_known_default().context("missing port")?;
+    let addresses = tokio::net::lookup_host((host, port))
+        .await?
+        .collect::<Vec<SocketAddr>>();
+    anyhow::ensure!(
+        !addresses.is_empty() && addresses.iter().all(|address| is_public(address.ip())),
+        "{host} doesn't resolve to a public address"
+    );
+    Ok(reqwest::Client::builder()
+        .resolve(host, addresses[0])
+        .redirect(redirect::Policy::none())
+        .timeout(FETCH_TIMEOUT)
+        .build()?)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || first == 0
+                // Reserved for future use, including the broadcast address.
+                || first >= 240
+                // Shared address space used by carrier-grade NATs.
+                || (first == 100 && (64..128).contains(&second))
+                // Benchmarking networks.
+                || (first == 198 && (18..20).contains(&second)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local and link-local addresses.
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                // NAT64 prefixes, which can translate to private IPv4
+                // addresses.
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                || segments[..3] == [0x64, 0xff9b, 1])
+        }
+    }
+}
+
+/// Recently proxied images, including failed downloads so that broken links
+/// aren't retried on every page view.
+#[derive(Debug)]
+struct ImageCache {
+    entries: HashMap<String, (Instant, Option<Image>)>,
+    /// The cached URLs, from the least to the most recently fetched.
+    order: VecDeque<String>,
+    /// The total [`cost`](Self::cost) of the entries.
+    size: usize,
+    /// The largest total cost kept before the least recently fetched entries
+    /// are evicted.
+    capacity: usize,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::with_capacity(MAX_CACHE_SIZE)
+    }
+}
+
+impl ImageCache {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            size: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the cached download of `url`, where None inside the result
+    /// means the download failed.
+    fn get(&self, url: &str) -> Option<Option<Image>> {
+        let (fetched, image) = self.entries.get(url)?;
+        let ttl = if image.is_some() {
+            IMAGE_TTL
+        } else {
+            FAILURE_TTL
+        };
+        (fetched.elapsed() < ttl).then(|| image.clone())
+    }
+
+    fn insert(&mut self, url: String, image: Option<Image>) {
+        self.remove(&url);
+        self.size += Self::cost(&url, image.as_ref());
+        self.order.push_back(url.clone());
+        self.entries.insert(url, (Instant::now(), image));
+        while self.size > self.capacity {
+            let Some(oldest) = self.order.front().cloned() else { break };
+            self.remove(&oldest);
+        }
+    }
+
+    /// The memory used by an entry, which is mostly the image itself.
+    fn cost(url: &str, image: Option<&Image>) -> usize {
+        url.len() + image.map_or(0, |image| image.bytes.len())
+    }
+
+    fn remove(&mut self, url: &str) {
+        if let Some((_, image)) = self.entries.remove(url) {
+            self.size -= Self::cost(url, image.as_ref());
+            self.order.retain(|cached| cached != url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(address: &str) -> bool {
+        is_public(address.parse().expect("invalid address"))
+    }
+
+    fn image(size: usize) -> Option<Image> {
+        Some(Image {
+            content_type: "image/png",
+            bytes: Bytes::from(vec![0; size]),
+        })
+    }
+
+    #[test]
+    fn public_addresses() {
+        for address in [
+            "8.8.8.8",
+            "198.20.0.1",
+            "223.255.255.254",
+            "2606:4700::1111",
+        ] {
+            assert!(public(address), "{address} should be public");
+        }
+    }
+
+    #[test]
+    fn non_public_addresses() {
+        for address in [
+            "0.0.0.0",
+            "10.0.0.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "172.16.0.1",
+            "192.168.1.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "203.0.113.1",
+            "224.0.0.1",
+            "239.255.255.250",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:10.0.0.1",
+            "fc00::1",
+            "fe80::1",
+            "ff02::1",
+            "64:ff9b::a00:1",
+            "64:ff9b:1::1",
+        ] {
+            assert!(!public(address), "{address} should not be public");
+        }
+    }
+
+    #[test]
+    fn cache_remembers_images_and_failures() {
+        let mut cache = ImageCache::with_capacity(1024);
+        assert!(cache.get("https://example.com/a.png").is_none());
+
+        cache.insert(String::from("https://example.com/a.png"), image(10));
+        cache.insert(String::from("https://example.com/b.png"), None);
+        let cached = cache.get("https://example.com/a.png").expect("a is cached");
+        assert_eq!(cached.expect("a was downloaded").bytes.len(), 10);
+        assert!(matches!(cache.get("https://example.com/b.png"), Some(None)));
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_fetched() {
+        // Each entry costs its URL's length plus 100 bytes.
+        let url = |name: &str| format!("https://example.com/{name}.png");
+        let mut cache = ImageCache::with_capacity(3 * (url("a").len() + 100));
+        cache.insert(url("a"), image(100));
+        cache.insert(url("b"), image(100));
+        cache.insert(url("c"), image(100));
+        // Fetching `a` again makes it the most recently fetched.
+        cache.insert(url("a"), image(100));
+        cache.insert(url("d"), image(100));
+
+        assert!(cache.get(&url("b")).is_none());
+        for name in ["a", "c", "d"] {
+            assert!(cache.get(&url(name)).is_some(), "{name} was evicted");
+        }
+        assert_eq!(cache.size, 3 * (url("a").len() + 100));
+        assert_eq!(cache.order.len(), 3);
+    }
+}
@@ -0,0 +1,297 @@
+//! Keyword trends, which show the topics of the ecosystem that are growing or
+//! declining.
+//!
+//! After each import, the number of crates using each keyword is recorded in
+//! a [`KeywordStats`] snapshot. Trends compare the latest snapshot with the
+//! one recorded [`TREND_DAYS`] before it, or the oldest one when there isn't
+//! that much history yet. Snapshots older than the compared one are deleted,
+//! since no trend will compare with them again.
+
+use std::collections::BTreeMap;
+
+use bonsaidb::{
+    core::{
+        document::{CollectionDocument, Header},
+        schema::{Collection, SerializedCollection},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Serialize;
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::{
+    dump,
+    schema::{CratesByKeyword, Keyword, KeywordStats},
+};
+
+/// How far apart the compared snapshots are.
+const TREND_DAYS: i64 = 90;
+
+/// The fewest crates a keyword must have been used by, in either snapshot,
+/// to be listed. Keywords of a handful of crates swing wildly.
+const MIN_CRATES: u64 = 10;
+
+/// The number of keywords listed as growing and as declining.
+const LISTED_KEYWORDS: usize = 50;
+
+/// Records the number of crates using each keyword under today's date,
+/// replacing the snapshot of an earlier import on the same day, and prunes
+/// the snapshots that are no longer needed.
+pub(crate) fn record(db: &Database) -> anyhow::Result<()> {
+    println!("Recording keyword usage");
+    let names = Keyword::all(db)
+        .query()?
+        .into_iter()
+        .map(|keyword| (keyword.header.id, keyword.contents.keyword))
+        .collect::<BTreeMap<_, _>>();
+    let mut crates = BTreeMap::new();
+    for mapping in CratesByKeyword::entries(db).reduce_grouped()? {
+        if let Some(name) = names.get(&mapping.key) {
+            // Keywords of different registries can share a name.
+            *crates.entry(name.clone()).or_default() += mapping.value;
+        }
+    }
+
+    let today = OffsetDateTime::now_utc().date();
+    KeywordStats { crates }.overwrite_into(&today.to_string(), db)?;
+    prune(db, today)
+}
+
+/// Deletes the snapshots older than the one trends compare with the snapshot
+/// of `latest`. Trends only move forward, so they won't be compared again.
+fn prune(db: &Database, latest: Date) -> anyhow::Result<()> {
+    let Some(earlier) = snapshot_before(db, latest)? else { return Ok(()) };
+    let mut tx = Transaction::new();
+    for stale in KeywordStats::list(String::new()..earlier.header.id, db).query()? {
+        tx.operations.push(Operation::delete(
+            KeywordStats::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+    Ok(())
+}
+
+/// Returns the newest snapshot recorded at least [`TREND_DAYS`] before
+/// `latest`.
+fn snapshot_before(
+    db: &Database,
+    latest: Date,
+) -> anyhow::Result<Option<CollectionDocument<KeywordStats>>> {
+    let cutoff = latest - Duration::days(TREND_DAYS);
+    Ok(KeywordStats::list(String::new()..=cutoff.to_string(), db)
+        .descending()
+        .limit(1)
+        .query()?
+        .pop())
+}
+
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct KeywordTrends {
+    /// The dates of the compared snapshots, or None if fewer than two have
+    /// been recorded.
+    pub period: Option<(String, String)>,
+    /// The keywords whose usage grew the most, fastest growing first.
+    pub growing: Vec<KeywordTrend>,
+    /// The keywords whose usage declined the most, fastest declining first.
+    pub declining: Vec<KeywordTrend>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct KeywordTrend {
+    pub keyword: String,
+    /// The number of crates using the keyword at the start of the period.
+    pub before: u64,
+    /// The number of crates using the keyword at the end of the period.
+    pub after: u64,
+}
+
+impl KeywordTrend {
+    /// Returns the change in usage relative to the usage at the start of the
+    /// period. Keywords that were used by fewer than [`MIN_CRATES`] crates
+    /// are compared to [`MIN_CRATES`], so new keywords don't all grow
+    /// infinitely.
+    fn growth(&self) -> f64 {
+        (self.after as f64 - self.before as f64) / self.before.max(MIN_CRATES) as f64
+    }
+
+    /// Describes the change in usage, such as `+12%`.
+    pub fn change(&self) -> String {
+        format!("{:+.0}%", self.growth() * 100.)
+    }
+}
+
+/// Returns the keywords that grew and declined the most between the latest
+/// snapshot and the one [`TREND_DAYS`] before it.
+pub(crate) fn trends(db: &Database) -> anyhow::Result<KeywordTrends> {
+    let Some(latest) = KeywordStats::all(db).descending().limit(1).query()?.pop()
+        else { return Ok(KeywordTrends::default()) };
+    let latest_date = dump::parse_timestamp(&latest.header.id)?.date();
+    let earlier = match snapshot_before(db, latest_date)? {
+        Some(earlier) => earlier,
+        None => match KeywordStats::all(db).limit(1).query()?.pop() {
+            Some(oldest) if oldest.header.id != latest.header.id => oldest,
+            _ => return Ok(KeywordTrends::default()),
+        },
+    };
+
+    let (mut growing, mut declining): (Vec<_>, Vec<_>) = latest
+        .contents
+        .crates
+        .iter()
+        .map(|(keyword, after)| KeywordTrend {
+            keyword: keyword.clone(),
+            before: earlier.contents.crates.get(keyword).copied().unwrap_or(0),
+            after: *after,
+        })
+        .chain(
+            earlier
+                .contents
+                .crates
+                .iter()
+                .filter(|(keyword, _)| !latest.contents.crates.contains_key(*keyword))
+                .map(|(keyword, before)| KeywordTrend {
+                    keyword: keyword.clone(),
+                    before: *before,
+                    after: 0,
+                }),
+        )
+        .filter(|trend| trend.before.max(trend.after) >= MIN_CRATES && trend.before != trend.after)
+        .partition(|trend| trend.after > trend.before);
+    growing.sort_by(|a, b| b.growth().total_cmp(&a.growth()));
+    growing.truncate(LISTED_KEYWORDS);
+    declining.sort_by(|a, b| a.growth().total_cmp(&b.growth()));
+    declining.truncate(LISTED_KEYWORDS);
+
+    Ok(KeywordTrends {
+        period: Some((earlier.header.id, latest.header.id)),
+        growing,
+        declining,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(db: &Database, date: &str, crates: &[(&str, u64)]) {
+        KeywordStats {
+            crates: crates
+                .iter()
+                .map(|(keyword, crates)| (keyword.to_string(), *crates))
+                .collect(),
+        }
+        .overwrite_into(&date.to_string(), db)
+        .unwrap();
+    }
+
+    fn keywords(trends: &[KeywordTrend]) -> Vec<(&str, u64, u64)> {
+        trends
+            .iter()
+            .map(|trend| (trend.keyword.as_str(), trend.before, trend.after))
+            .collect()
+    }
+
+    fn dates(db: &Database) -> Vec<String> {
+        KeywordStats::all(db)
+            .query()
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.header.id)
+            .collect()
+    }
+
+    #[test]
+    fn growth() {
+        let trend = |before, after| KeywordTrend {
+            keyword: String::from("async"),
+            before,
+            after,
+        };
+        assert_eq!(trend(100, 150).change(), "+50%");
+        assert_eq!(trend(100, 25).change(), "-75%");
+        assert_eq!(trend(20, 0).change(), "-100%");
+        // New keywords are compared with MIN_CRATES.
+        assert_eq!(trend(0, 30).change(), "+300%");
+        assert_eq!(trend(5, 15).change(), "+100%");
+    }
+
+    #[test]
+    fn trends_need_two_snapshots() {
+        let (_dir, db) = crate::test_database();
+        assert!(trends(&db).unwrap().period.is_none());
+        snapshot(&db, "2023-03-20", &[("async", 100)]);
+        assert!(trends(&db).unwrap().period.is_none());
+    }
+
+    #[test]
+    fn trends_compare_with_the_snapshot_before_the_period() {
+        let (_dir, db) = crate::test_database();
+        snapshot(&db, "2022-12-01", &[("async", 1)]);
+        snapshot(
+            &db,
+            "2022-12-20",
+            &[("async", 100), ("gui", 50), ("tiny", 2)],
+        );
+        snapshot(&db, "2023-01-01", &[("async", 10)]);
+        snapshot(
+            &db,
+            "2023-03-20",
+            &[("async", 150), ("wasm", 40), ("tiny", 9), ("gui", 45)],
+        );
+
+        let trends = trends(&db).unwrap();
+        assert_eq!(
+            trends.period,
+            Some((String::from("2022-12-20"), String::from("2023-03-20")))
+        );
+        assert_eq!(
+            keywords(&trends.growing),
+            [("wasm", 0, 40), ("async", 100, 150)]
+        );
+        assert_eq!(keywords(&trends.declining), [("gui", 50, 45)]);
+    }
+
+    #[test]
+    fn trends_fall_back_to_the_oldest_snapshot() {
+        let (_dir, db) = crate::test_database();
+        snapshot(&db, "2023-03-01", &[("async", 100), ("gui", 20)]);
+        snapshot(&db, "2023-03-10", &[("async", 10)]);
+        snapshot(&db, "2023-03-20", &[("async", 120)]);
+
+        let trends = trends(&db).unwrap();
+        assert_eq!(
+            trends.period,
+            Some((String::from("2023-03-01"), String::from("2023-03-20")))
+        );
+        assert_eq!(keywords(&trends.growing), [("async", 100, 120)]);
+        assert_eq!(keywords(&trends.declining), [("gui", 20, 0)]);
+    }
+
+    #[test]
+    fn snapshots_before_the_compared_one_are_pruned() {
+        let (_dir, db) = crate::test_database();
+        for date in [
+            "2022-11-01",
+            "2022-12-01",
+            "2022-12-20",
+            "2023-01-01",
+            "2023-03-20",
+        ] {
+            snapshot(&db, date, &[("async", 100)]);
+        }
+        let date = |month, day| Date::from_calendar_date(2023, month, day).unwrap();
+
+        prune(&db, date(time::Month::March, 20)).unwrap();
+        assert_eq!(dates(&db), ["2022-12-20", "2023-01-01", "2023-03-20"]);
+        // Without a snapshot old enough to compare with, nothing is pruned.
+        let (_dir, db) = crate::test_database();
+        snapshot(&db, "2023-03-01", &[("async", 100)]);
+        snapshot(&db, "2023-03-20", &[("async", 100)]);
+        prune(&db, date(time::Month::March, 20)).unwrap();
+        assert_eq!(dates(&db), ["2023-03-01", "2023-03-20"]);
+    }
+}
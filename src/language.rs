@@ -0,0 +1,254 @@
+//! Detection of the natural language crates are documented in.
+//!
+//! Languages written in their own script are recognized by their characters.
+//! Languages written in the Latin script are told apart by counting common
+//! words that are rare in the other languages checked, which is reliable for
+//! a paragraph of prose but not for a single short sentence. Text that can't
+//! be told apart is left undetected rather than guessed.
+
+/// The least number of common words a text must contain for a Latin-script
+/// language to be detected.
+const MIN_COMMON_WORDS: usize = 2;
+
+/// The fraction of a text's letters that must be in a script for the text to
+/// be detected as that script's language. Code and crate names in READMEs
+/// add Latin letters to text in any language.
+const MIN_SCRIPT_SHARE: f32 = 0.2;
+
+/// The most bytes of a README examined.
+const MAX_README_LENGTH: usize = 8 * 1024;
+
+/// Common words of each detected language written in the Latin script, keyed
+/// by its ISO 639-1 code.
+const COMMON_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "for", "with", "that", "this", "are", "it", "on",
+            "from", "by", "be", "as", "or", "you", "your", "can", "which", "an",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "für", "mit", "ein", "eine", "nicht", "von", "zu",
+            "den", "dem", "auf", "sich", "auch", "werden", "wird", "oder",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "et", "est", "pour", "avec", "des", "une", "du", "dans", "qui", "sur",
+            "pas", "ce", "cette", "sont", "ou", "au", "aux",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "para", "con", "una", "del", "por", "como", "su", "más",
+            "pero", "este", "esta", "al",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "é", "com", "uma", "um", "da", "não", "em", "dos", "das", "mais", "ao", "também",
+            "você", "isso",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "è", "per", "gli", "della", "che", "di", "non", "sono", "questo", "nel", "dei",
+            "anche", "questa", "delle",
+        ],
+    ),
+];
+
+/// Returns the ISO 639-1 code of the language `description` and `readme` are
+/// written in, or None if it can't be detected. `readme` may be HTML, in
+/// which case tags and code blocks are ignored.
+pub fn detect(description: &str, readme: &str) -> Option<&'static str> {
+    let mut readme = readme;
+    if readme.len() > MAX_README_LENGTH {
+        let mut end = MAX_README_LENGTH;
+        while !readme.is_char_boundary(end) {
+            end -= 1;
+        }
+        readme = &readme[..end];
+    }
+    let mut text = String::from(description);
+    text.push('\n');
+    text.push_str(&prose(readme));
+
+    by_script(&text).or_else(|| by_common_words(&text))
+}
+
+/// Detects languages that are written in their own script.
+fn by_script(text: &str) -> Option<&'static str> {
+    let mut letters = 0;
+    let mut kana = 0;
+    let mut hangul = 0;
+    let mut han = 0;
+    let mut cyrillic = 0;
+    let mut ukrainian = 0;
+    for ch in text.chars().filter(|ch| ch.is_alphabetic()) {
+        letters += 1;
+        match ch {
+            '\u{3040}'..='\u{30ff}' => kana += 1,
+            '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => hangul += 1,
+            '\u{4e00}'..='\u{9fff}' => han += 1,
+            '\u{0400}'..='\u{04ff}' => {
+                cyrillic += 1;
+                // Letters used in Ukrainian but not in Russian.
+                if matches!(ch, 'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ') {
+                    ukrainian += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let share = |count: usize| letters > 0 && count as f32 / letters as f32 >= MIN_SCRIPT_SHARE;
+    if share(kana + han) && kana > 0 {
+        // Japanese mixes kana with Chinese characters.
+        Some("ja")
+    } else if share(hangul) {
+        Some("ko")
+    } else if share(han) {
+        Some("zh")
+    } else if share(cyrillic) {
+        Some(if ukrainian > 0 { "uk" } else { "ru" })
+    } else {
+        None
+    }
+}
+
+/// Detects languages written in the Latin script by their common words.
+fn by_common_words(text: &str) -> Option<&'static str> {
+    let mut counts = vec![0_usize; COMMON_WORDS.len()];
+    for word in text
+        .split(|ch: char| !ch.is_alphabetic())
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        for (count, (_, words)) in counts.iter_mut().zip(COMMON_WORDS) {
+            if words.contains(&word.as_str()) {
+                *count += 1;
+            }
+        }
+    }
+
+    let mut ranked = counts.iter().copied().zip(COMMON_WORDS).collect::<Vec<_>>();
+    ranked.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let (best, (language, _)) = ranked[0];
+    let runner_up = ranked.get(1).map_or(0, |(count, _)| *count);
+    (best >= MIN_COMMON_WORDS && best > runner_up).then_some(*language)
+}
+
+/// Returns the text of `html` outside of tags and code blocks. Plain text is
+/// returned unchanged, except for anything that looks like a tag.
+pub(crate) fn prose(html: &str) -> String {
+    let mut prose = String::with_capacity(html.len());
+    let mut code_depth = 0_usize;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if code_depth == 0 {
+            prose.push_str(&rest[..start]);
+        }
+        let Some(end) = rest[start..].find('>') else { return prose };
+        let tag = rest[start + 1..start + end].trim().to_ascii_lowercase();
+        let (closing, name) = match tag.strip_prefix('/') {
+            Some(name) => (true, name),
+            None => (false, tag.as_str()),
+        };
+        let name = name.split_whitespace().next().unwrap_or_default();
+        if name == "pre" || name == "code" {
+            if closing {
+                code_depth = code_depth.saturating_sub(1);
+            } else {
+                code_depth += 1;
+            }
+        }
+        prose.push(' ');
+        rest = &rest[start + end + 1..];
+    }
+    if code_depth == 0 {
+        prose.push_str(rest);
+    }
+    prose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin_script_languages() {
+        assert_eq!(
+            detect(
+                "A fast JSON parser.",
+                "This crate parses JSON and is built for speed. You can use it with serde, \
+                 or on its own. Do you need help? Read the docs."
+            ),
+            Some("en")
+        );
+        assert_eq!(
+            detect(
+                "Ein schneller JSON-Parser.",
+                "Diese Bibliothek ist für die Verarbeitung von JSON und wird mit serde \
+                 verwendet. Sie ist auch ohne serde nutzbar."
+            ),
+            Some("de")
+        );
+        assert_eq!(
+            detect(
+                "Um analisador de JSON rápido.",
+                "Esta biblioteca é usada com serde para ler dos arquivos. Também funciona \
+                 sem serde, e não tem dependências."
+            ),
+            Some("pt")
+        );
+    }
+
+    #[test]
+    fn english_do_is_not_portuguese() {
+        assert_eq!(
+            detect("", "Do this, then do that, and do it with care."),
+            Some("en")
+        );
+    }
+
+    #[test]
+    fn short_or_ambiguous_text_is_undetected() {
+        assert_eq!(detect("JSON parser", ""), None);
+        assert_eq!(detect("", ""), None);
+        // One common word from each of two languages.
+        assert_eq!(detect("the der", ""), None);
+    }
+
+    #[test]
+    fn script_languages() {
+        assert_eq!(detect("高速なJSONパーサーです。", ""), Some("ja"));
+        assert_eq!(detect("빠른 JSON 파서입니다.", ""), Some("ko"));
+        assert_eq!(detect("一个快速的JSON解析器。", ""), Some("zh"));
+        assert_eq!(detect("Быстрый парсер JSON.", ""), Some("ru"));
+        assert_eq!(
+            detect("Швидкий парсер JSON, який їсть дані.", ""),
+            Some("uk")
+        );
+    }
+
+    #[test]
+    fn code_in_readmes_is_ignored() {
+        let readme = "<p>Ein Parser für JSON, der auch mit serde und ohne serde läuft.</p>\
+            <pre><code>// the parser is for the data and the types\nlet x = 1;</code></pre>";
+        assert_eq!(detect("", readme), Some("de"));
+        assert_eq!(
+            prose("<p>a <code>b</code> c</p>")
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+    }
+}
@@ -0,0 +1,686 @@
+// The OpenAPI document is written with a single `json!` invocation.
+#![recursion_limit = "256"]
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    path::Path,
+    sync::{mpsc::RecvTimeoutError, Arc},
+    time::Instant,
+};
+
+use bonsaidb::{
+    core::{connection::StorageConnection, key::Key},
+    local::{
+        config::{Builder, StorageConfiguration},
+        Database, Storage,
+    },
+};
+
+use crate::{
+    cache::{Cache, CachedCrate, CrateTable},
+    description_index::DescriptionIndex,
+    filters::Filters,
+    meilisearch::Meilisearch,
+    ranking::Signals,
+    schema::CurationAction,
+    search_backend::{Backend, SearchBackend, SearchConfig},
+    syntax::Term,
+    tantivy_index::TantivyIndex,
+    top_k::TopK,
+};
+
+/// The most results a search returns.
+const MAX_RESULTS: usize = 1_000;
+
+/// The longest search query accepted, in bytes.
+pub const MAX_QUERY_LEN: usize = 512;
+
+/// How much more a quoted phrase's matches are weighted than a single word's.
+const PHRASE_BOOST: f32 = 2.;
+
+mod access_log;
+mod admin;
+mod advisories;
+mod aliases;
+mod analytics;
+mod api;
+pub mod api_keys;
+mod archive;
+pub mod assets;
+pub mod bench;
+mod blocklist;
+pub mod cache;
+mod categories;
+pub mod client;
+pub mod config;
+mod cooccurrence;
+mod curation;
+mod dashboard;
+mod dependency_graph;
+mod description_index;
+mod diff;
+mod discover;
+mod diversify;
+pub mod dump;
+mod duplicates;
+mod embed;
+pub mod embeddings;
+mod experiments;
+mod feeds;
+pub mod filters;
+mod forks;
+mod hnsw;
+mod i18n;
+mod image_proxy;
+mod keyword_trends;
+mod language;
+pub mod maintenance;
+mod markdown;
+pub mod meilisearch;
+mod name_collisions;
+pub mod name_index;
+mod not_found;
+mod openapi;
+mod ownership;
+mod pagination;
+mod placeholder;
+mod preferences;
+mod profile;
+mod publish_waves;
+pub mod ranking;
+mod recently_viewed;
+mod recommendations;
+pub mod replication;
+mod review_report;
+pub mod reviews;
+mod rollup;
+pub mod schema;
+pub mod search_backend;
+mod security_headers;
+mod seo;
+mod signed_cookies;
+mod snippets;
+mod sparse_index;
+mod spelling;
+mod strings;
+mod syntax;
+mod tantivy_index;
+pub mod throttle;
+pub mod top_k;
+mod unmaintained;
+pub mod unsafe_usage;
+mod version_info;
+mod version_links;
+mod watchlists;
+mod webhooks;
+pub mod webserver;
+mod yanks;
+
+/// Opens the database stored in `path` and the search backend in `search`,
+/// creating the database and tantivy index if they do not exist.
+pub fn open(
+    path: impl AsRef<Path>,
+    search: &SearchConfig,
+) -> anyhow::Result<(Database, Cache, SearchIndex)> {
+    let path = path.as_ref();
+    let storage = open_storage(path)?;
+    let db = storage.create_database::<schema::CrateIndex>("delve", true)?;
+    let index = match search.backend {
+        Backend::Tantivy => SearchIndex::new(TantivyIndex::open(path)?),
+        Backend::Descriptions => SearchIndex::new(DescriptionIndex::open(&db)?),
+        Backend::Meilisearch => SearchIndex::new(Meilisearch::connect(&search.meilisearch)?),
+    };
+    let cache = Cache::new(db.clone(), index.clone())?;
+
+    Ok((db, cache, index))
+}
+
+/// Opens the storage in `path` with the database's schema registered, without
+/// creating the database.
+fn open_storage(path: &Path) -> anyhow::Result<Storage> {
+    Ok(Storage::open(
+        StorageConfiguration::default()
+            .path(path)
+            .with_schema::<schema::CrateIndex>()?,
+    )?)
+}
+
+/// Creates an empty database in a temporary directory for unit tests. The
+/// returned directory must be kept alive for as long as the database is in
+/// use.
+#[cfg(test)]
+pub(crate) fn test_database() -> (tempfile::TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = open_storage(dir.path()).unwrap();
+    let db = storage
+        .create_database::<schema::CrateIndex>("delve", true)
+        .unwrap();
+    (dir, db)
+}
+
+/// Returns a crate named `name` with no other metadata, for unit tests.
+#[cfg(test)]
+pub(crate) fn test_crate(name: &str) -> schema::Crate {
+    schema::Crate {
+        created_at: String::from("2023-01-01 00:00:00"),
+        description: String::new(),
+        documentation: String::new(),
+        downloads: None,
+        homepage: String::new(),
+        max_upload_size: None,
+        name: name.to_string(),
+        readme: String::new(),
+        repository: String::new(),
+        updated_at: String::from("2023-01-01 00:00:00"),
+        keywords: HashSet::new(),
+        category_ids: HashSet::new(),
+        owners: HashSet::new(),
+        registry: None,
+        language: None,
+    }
+}
+
+/// Returns a cache of `db` that reflects its current contents, for unit
+/// tests.
+#[cfg(test)]
+pub(crate) fn test_cache(db: &Database) -> Cache {
+    let index = SearchIndex::new(DescriptionIndex::open(db).unwrap());
+    let cache = Cache::new(db.clone(), index).unwrap();
+    cache.refresh_blocking().unwrap();
+    cache
+}
+
+/// Loads everything the first searches would otherwise wait on: the crate
+/// cache, including the download percentiles used for popularity, and the
+/// search index's segments.
+pub fn warm_up(cache: &Cache, index: &SearchIndex) -> anyhow::Result<()> {
+    let start = std::time::Instant::now();
+    cache.refresh_blocking()?;
+    index.warm_up()?;
+    tracing::info!(
+        "warmed up {} crates in {}ms",
+        cache.crates()?.len(),
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// The search backend shared by the web server, importer, and cache.
+#[derive(Clone, Debug)]
+pub struct SearchIndex(Arc<dyn SearchBackend>);
+
+impl SearchIndex {
+    pub fn new(backend: impl SearchBackend + 'static) -> Self {
+        Self(Arc::new(backend))
+    }
+}
+
+impl Deref for SearchIndex {
+    type Target = dyn SearchBackend;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+#[derive(Key, Debug, Clone)]
+struct Foo<'k> {
+    string: Cow<'k, str>,
+}
+
+#[derive(Debug)]
+pub struct CrateResult {
+    pub confidence: f32,
+    pub popularity: f32,
+    pub result: CachedCrate,
+    /// Lower-ranked results that appear to be forks or copies of this crate.
+    pub similar: Vec<CrateResult>,
+    /// An HTML excerpt of the crate's description or readme with the search
+    /// terms highlighted, filled in by [`snippets::highlight`].
+    pub snippet: Option<String>,
+}
+
+/// The results of a search, most relevant first.
+#[derive(Debug, Default)]
+pub struct QueryResults {
+    pub results: Vec<CrateResult>,
+    /// True if the search ran past the [`Filters::deadline`], in which case
+    /// the results are the search index's matches in the index's order, or
+    /// only the pinned crates if the search index itself was too slow.
+    pub partial: bool,
+}
+
+/// Runs [`query`] on a thread where blocking is allowed. Searching reads the
+/// database and the search index, and the Meilisearch backend waits for HTTP
+/// responses, none of which should stall the async runtime's workers.
+pub async fn spawn_query(
+    terms: String,
+    filters: Filters,
+    db: Database,
+    cache: Cache,
+    index: SearchIndex,
+) -> anyhow::Result<QueryResults> {
+    tokio::task::spawn_blocking(move || query(&terms, &filters, &db, &cache, &index)).await?
+}
+
+/// Searches for crates matching `query`, which must already have had its
+/// filters removed by [`Filters::parse`].
+pub fn query(
+    query: &str,
+    filters: &Filters,
+    db: &Database,
+    cache: &Cache,
+    index: &SearchIndex,
+) -> anyhow::Result<QueryResults> {
+    let _timer = throttle::QueryTimer::start();
+    let Some(expr) = syntax::parse(query) else { return Ok(QueryResults::default()) };
+    let crates = cache.crates()?;
+    let category_crates = filters.category_crates(db)?;
+    let curations = crates.curations(query);
+
+    // Search for crates that contain this word in their description/readme.
+    // These matches are also the results if scoring runs out of time.
+    let index_limit = filters
+        .index_limit
+        .map_or(MAX_RESULTS, |limit| limit.min(MAX_RESULTS));
+    let index_matches = match filters.deadline {
+        Some(deadline) => search_until(index, query, index_limit, deadline)?,
+        None => Some(index.search(query, index_limit)?),
+    };
+    let index_timed_out = index_matches.is_none();
+    let index_matches = index_matches.unwrap_or_default();
+    let out_of_time = || {
+        filters
+            .deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+    };
+    let partial_results = || {
+        tracing::warn!("searching {query:?} ran out of time");
+        QueryResults {
+            results: index_results(
+                &index_matches,
+                filters,
+                &crates,
+                category_crates.as_ref(),
+                curations,
+            ),
+            partial: true,
+        }
+    };
+
+    if index_timed_out {
+        return Ok(partial_results());
+    }
+
+    let terms = expr.terms();
+    let interned = InternedTerms::new(&terms);
+    let mut crate_scores = HashMap::<u64, QueryScore>::new();
+    for (term, negated) in terms {
+        if out_of_time() {
+            return Ok(partial_results());
+        }
+        let term_index = interned.index(&term);
+        let (normalized_query, lowercase_query) = if term.phrase {
+            // Names and keywords can't contain spaces, so phrases match the
+            // names and keywords that contain their words joined together.
+            let words = term.text.split_ascii_whitespace().collect::<Vec<_>>();
+            (
+                schema::Crate::normalized_name(&words.join("_")),
+                words.join("-").to_ascii_lowercase(),
+            )
+        } else {
+            (
+                schema::Crate::normalized_name(term.text),
+                term.text.to_ascii_lowercase(),
+            )
+        };
+
+        // Build matches based on the crate names
+        if term.matches_names() {
+            let names = cache.names()?;
+            for (normalized_name, crate_id) in names.candidates(&normalized_query) {
+                // A phrase must appear in its entirety.
+                if term.phrase && !normalized_name.contains(normalized_query.as_str()) {
+                    continue;
+                }
+                if let Some(name_score) = TextScore::score(&normalized_query, normalized_name) {
+                    let score = crate_scores
+                        .entry(crate_id)
+                        .or_insert_with(QueryScore::default);
+                    // Negated terms are only matched so they can be excluded.
+                    if !negated {
+                        score.name_score += name_score.calculated_score();
+                    }
+                    score.matched_terms.insert(term_index);
+                }
+            }
+        }
+
+        // Adjust matches based on keyword matches.
+        if term.matches_keywords() {
+            for (keyword, keyword_id) in crates.keywords_with_prefix(&lowercase_query) {
+                if out_of_time() {
+                    return Ok(partial_results());
+                }
+                if let Some(keyword_score) = TextScore::score(&lowercase_query, keyword) {
+                    for crate_id in crates.crates_with_keyword(keyword_id) {
+                        let score = crate_scores
+                            .entry(*crate_id)
+                            .or_insert_with(QueryScore::default);
+                        if !negated {
+                            score.keyword_score += keyword_score.calculated_score();
+                        }
+                        score.matched_terms.insert(term_index);
+                    }
+                }
+            }
+        }
+    }
+
+    for (search_score, crate_id) in &index_matches {
+        crate_scores
+            .entry(*crate_id)
+            .or_insert_with(QueryScore::default)
+            .index_score = Some(*search_score);
+    }
+
+    // Blend in the crates whose embeddings are most similar to the query's.
+    // The most similar crate scores `weight` times the best index match, so
+    // that similarity is comparable to the index's scores.
+    let mut semantic_scale = 0.;
+    if let Some(semantic) = &filters.semantic {
+        let best_index_score = crate_scores
+            .values()
+            .filter_map(|score| score.index_score)
+            .reduce(f32::max)
+            .unwrap_or(1.);
+        semantic_scale = semantic.weight * best_index_score;
+        for (id, similarity) in &semantic.similarity {
+            crate_scores
+                .entry(*id)
+                .or_insert_with(QueryScore::default)
+                .semantic = Some(*similarity);
+        }
+    }
+    // for mapping in schema::CratesByWord::entries(db).with_key(word).query()? {
+    //     let score = crate_scores
+    //         .entry(mapping.source.id.deserialize::<u64>()?)
+    //         .or_insert_with(QueryScore::default);
+    //     score.word_locations += mapping.value;
+    //     score.matched_words.insert(word);
+    // }
+
+    // Sort the result set and get rid of everything that didn't match all
+    // search terms or filters. Crates found only by their embeddings can't
+    // be checked against `AND` and `NOT`, so they're only results of queries
+    // without them.
+    let semantic_only_allowed = !expr.has_constraints();
+    let mut results = TopK::new(MAX_RESULTS);
+    for (position, (id, score)) in crate_scores.iter().enumerate() {
+        // Checking the time for every crate would slow down broad queries.
+        if position % 1024 == 0 && out_of_time() {
+            return Ok(partial_results());
+        }
+        if category_crates
+            .as_ref()
+            .map_or(false, |crates| !crates.contains(id))
+        {
+            continue;
+        }
+        let Some(c) = crates.get(*id) else { continue };
+        let matched =
+            expr.matches(&|term: &Term<'_>| score.matched_terms.contains(interned.index(term)));
+        let searched = matched
+            || score.index_score.is_some()
+            || (semantic_only_allowed && score.semantic.is_some());
+        if searched && filters.matches(&c) {
+            let calculated = score.calculated_score(semantic_scale) * filters.score_multiplier(&c);
+            results.push(calculated, *id);
+        }
+    }
+
+    if results.is_empty() && curations.is_empty() {
+        return Ok(QueryResults::default());
+    }
+    let results = results.into_sorted_vec();
+
+    // Build a confidence score
+    let maximum_confidence = results.first().map_or(1., |(confidence, _)| *confidence);
+    let ranker = filters.ranker();
+    let today = time::OffsetDateTime::now_utc().date();
+    let mut ranked = Vec::with_capacity(results.len());
+    for (confidence, id) in results {
+        let Some(c) = crates.get(id) else { continue };
+        let Some(curated) = curation::multiplier(curations, id) else { continue };
+        let score = &crate_scores[&id];
+
+        // Adjust confidence to be a percentage of the highest crate
+        let confidence = confidence / maximum_confidence;
+
+        let popularity = popularity(&c);
+        let signals = Signals {
+            relevance: confidence,
+            index_score: score.index_score.unwrap_or(0.),
+            name_score: score.name_score,
+            keyword_score: score.keyword_score,
+            popularity,
+            days_since_release: c
+                .last_published()
+                .map(|published| (today - published).whole_days()),
+            days_since_first_release: c
+                .first_published()
+                .map(|published| (today - published).whole_days()),
+            transitive_dependencies: c.dependencies().map(|metrics| metrics.transitive),
+            krate: &c,
+        };
+        let rank = ranker.score(&signals) * filters.recency.factor(&signals) * curated;
+        ranked.push((
+            rank,
+            CrateResult {
+                confidence,
+                popularity,
+                result: c,
+                similar: Vec::new(),
+                snippet: None,
+            },
+        ));
+    }
+
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut final_results = ranked.into_iter().map(|(_, result)| result).collect();
+    curation::pin(
+        curations,
+        &mut final_results,
+        filters,
+        &crates,
+        category_crates.as_ref(),
+    );
+
+    Ok(QueryResults {
+        results: diversify::collapse_similar(final_results),
+        partial: false,
+    })
+}
+
+/// Returns up to `limit` of the search index's matches for `query`, or None
+/// if they aren't found by `deadline`. Search backends can't be interrupted,
+/// so the lookup runs on a thread of its own, which is left to finish in the
+/// background when it runs late.
+fn search_until(
+    index: &SearchIndex,
+    query: &str,
+    limit: usize,
+    deadline: Instant,
+) -> anyhow::Result<Option<Vec<(f32, u64)>>> {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    if timeout.is_zero() {
+        return Ok(None);
+    }
+
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+    let index = index.clone();
+    let query = query.to_string();
+    std::thread::spawn(move || {
+        // The receiver is gone if the search ran late.
+        let _ = sender.send(index.search(&query, limit));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(matches) => matches.map(Some),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        Err(RecvTimeoutError::Disconnected) => anyhow::bail!("searching the index panicked"),
+    }
+}
+
+/// Returns how popular `krate` is, favoring recent downloads. The percentiles
+/// are relative to every crate, so popularity is comparable across queries.
+fn popularity(krate: &CachedCrate) -> f32 {
+    (krate.recent_downloads_percentile() * 4. + krate.downloads_percentile()) / 5.
+}
+
+/// Returns the crates among the search index's `matches` that satisfy the
+/// filters, in the index's order.
+fn index_results(
+    matches: &[(f32, u64)],
+    filters: &Filters,
+    crates: &Arc<CrateTable>,
+    category_crates: Option<&HashSet<u64>>,
+    curations: &[(u64, CurationAction)],
+) -> Vec<CrateResult> {
+    let maximum_score = matches.first().map_or(1., |(score, _)| *score);
+    let mut results = matches
+        .iter()
+        .filter(|(_, id)| category_crates.map_or(true, |crates| crates.contains(id)))
+        .filter(|(_, id)| curation::multiplier(curations, *id).is_some())
+        .filter_map(|(score, id)| {
+            let c = crates.get(*id)?;
+            filters.matches(&c).then(|| CrateResult {
+                confidence: score / maximum_score,
+                popularity: popularity(&c),
+                result: c,
+                similar: Vec::new(),
+                snippet: None,
+            })
+        })
+        .collect();
+    curation::pin(curations, &mut results, filters, crates, category_crates);
+    diversify::collapse_similar(results)
+}
+
+/// How a crate matched a query. Scores are summed as they're found, so that
+/// queries matching tens of thousands of crates don't allocate for each one.
+#[derive(Default, Debug, Clone, Copy)]
+struct QueryScore {
+    /// The terms matched by the crate's name or keywords.
+    matched_terms: TermSet,
+    index_score: Option<f32>,
+    /// The similarity of the crate's embedding to the query's, relative to
+    /// the most similar crate.
+    semantic: Option<f32>,
+    /// The sum of the [`TextScore`]s of the crate's name matches.
+    name_score: f32,
+    /// The sum of the [`TextScore`]s of the crate's keyword matches.
+    keyword_score: f32,
+}
+
+impl QueryScore {
+    fn calculated_score(&self, semantic_scale: f32) -> f32 {
+        self.index_score.unwrap_or(0.) + self.semantic.unwrap_or(0.) * semantic_scale
+    }
+}
+
+/// The distinct terms of a query, numbered so that the terms each crate
+/// matches can be stored in a [`TermSet`].
+struct InternedTerms<'a>(Vec<Term<'a>>);
+
+impl<'a> InternedTerms<'a> {
+    /// Numbers the distinct terms in `terms`, which [`syntax::parse`] limits
+    /// to [`syntax::MAX_TERMS`] so that every term fits in a [`TermSet`].
+    fn new(terms: &[(Term<'a>, bool)]) -> Self {
+        let mut distinct = Vec::new();
+        for (term, _) in terms {
+            if !distinct.contains(term) {
+                distinct.push(*term);
+            }
+        }
+        Self(distinct)
+    }
+
+    fn index(&self, term: &Term<'a>) -> Option<usize> {
+        self.0.iter().position(|interned| interned == term)
+    }
+}
+
+// Every distinct term of a parsed query has to fit in a `TermSet`.
+const _: () = assert!(syntax::MAX_TERMS <= TermSet::CAPACITY);
+
+/// A set of the terms numbered by [`InternedTerms`], stored as a bit per
+/// term.
+#[derive(Default, Debug, Clone, Copy)]
+struct TermSet(u64);
+
+impl TermSet {
+    const CAPACITY: usize = u64::BITS as usize;
+
+    fn insert(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            self.0 |= 1 << index;
+        }
+    }
+
+    fn contains(self, index: Option<usize>) -> bool {
+        index.map_or(false, |index| self.0 & (1 << index) != 0)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum TextScore {
+    ExactMatch,
+    StartsWith { match_percent: f32 },
+    EndsWith { match_percent: f32 },
+    Contains { match_percent: f32 },
+}
+
+impl TextScore {
+    pub fn score(needle: &str, haystack: &str) -> Option<Self> {
+        let same_length = needle.len() == haystack.len();
+        haystack
+            .find(needle)
+            .map(|offset| Self::score_offset(offset, same_length, haystack.len(), needle.len()))
+            .or_else(|| {
+                needle.find(haystack).map(|offset| {
+                    Self::score_offset(offset, same_length, needle.len(), haystack.len())
+                })
+            })
+    }
+
+    fn score_offset(
+        offset: usize,
+        same_length: bool,
+        haystack_len: usize,
+        needle_len: usize,
+    ) -> Self {
+        let match_percent = needle_len as f32 / haystack_len as f32;
+        if offset == 0 {
+            if same_length {
+                Self::ExactMatch
+            } else {
+                Self::StartsWith { match_percent }
+            }
+        } else if offset == haystack_len - needle_len {
+            Self::EndsWith { match_percent }
+        } else {
+            Self::Contains { match_percent }
+        }
+    }
+
+    fn calculated_score(&self) -> f32 {
+        match self {
+            TextScore::ExactMatch => 100.,
+            TextScore::StartsWith { match_percent } => 10. * match_percent * match_percent,
+            TextScore::EndsWith { match_percent } => 10. * match_percent * match_percent,
+            TextScore::Contains { match_percent } => *match_percent * *match_percent,
+        }
+    }
+}
@@ -0,0 +1,262 @@
+# The German interface.
+
+site-description = Rust-Crates nach Name, Beschreibung, Schlagwort und Kategorie durchsuchen.
+theme-toggle = Farbschema wechseln
+
+## Search
+
+search-label = Crates durchsuchen
+search-button = Suchen
+keyboard-shortcuts = Drücke / zum Suchen, j und k zum Wechseln zwischen Ergebnissen und Enter zum Öffnen.
+results-label = Suchergebnisse
+hide-placeholders = Platzhalter-Crates ausblenden
+recently-viewed = Zuletzt angesehen
+crate-of-the-day = Crate des Tages
+random-crate = Zeig mir ein zufälliges Crate
+categories-heading = Kategorien
+all-categories = Alle Kategorien
+dashboard-crates = { $count ->
+    [one] 1 Crate
+   *[other] { $count } Crates
+    }
+dashboard-new-crates = { $count ->
+    [one] 1 neues diese Woche
+   *[other] { $count } neue diese Woche
+    }
+trending-heading = Im Trend
+recent-advisories = Aktuelle Sicherheitsmeldungen
+results-heading = Ergebnisse für { $query }
+no-results = Keine Crates entsprechen deiner Suche.
+showing-results-for = Ergebnisse für { $query }.
+search-instead-for = Stattdessen nach { $query } suchen
+partial-results = Diese Suche hat zu lange gedauert, daher sind diese Ergebnisse nur nach Übereinstimmung sortiert.
+previous-page = Vorherige Seite
+next-page = Nächste Seite
+column-crate = Crate
+column-confidence = Relevanz
+column-popularity = Beliebtheit
+column-dependencies = Abhängigkeiten
+placeholder-badge = Platzhalter
+wasm-badge = wasm
+wasm-badge-title = Scheint WebAssembly zu unterstützen
+proc-macro-badge = proc-macro
+proc-macro-badge-title = Scheint ein prozedurales Makro zu sein
+build-script-badge = Build-Skript
+build-script-badge-title = Scheint beim Kompilieren ein Build-Skript auszuführen
+audited-badge = geprüft
+audited-badge-title = { $count ->
+    [one] Durch ein cargo-vet-Audit oder crev-Review bestätigt
+   *[other] Durch { $count } cargo-vet-Audits oder crev-Reviews bestätigt
+    }
+unsafe-forbidden-badge = verbietet unsafe
+unsafe-forbidden-badge-title = Deklariert #![forbid(unsafe_code)]
+unsafe-none-badge = kein unsafe
+unsafe-none-badge-title = Die gescannte Version kompiliert keinen unsafe-Code
+unsafe-badge = unsafe: { $count }
+unsafe-badge-title = Unsafe-Funktionen, -Ausdrücke, -Impls, -Traits und -Methoden, die die gescannte Version kompiliert
+first-published = erstmals veröffentlicht { $age }
+deprecated = Veraltet
+deprecated-consider = Veraltet, stattdessen
+successors-consider = Fortgeführt von
+dependency-depth = { $depth ->
+    [one] 1 Ebene tief
+   *[other] { $depth } Ebenen tief
+    }
+lightweight = leichtgewichtig
+heavyweight = schwergewichtig
+similar-crates = { $count ->
+    [one] 1 ähnliches Crate
+   *[other] { $count } ähnliche Crates
+    }
+
+## Ages
+
+age-today = heute
+age-days = { $count ->
+    [one] vor 1 Tag
+   *[other] vor { $count } Tagen
+    }
+age-months = { $count ->
+    [one] vor 1 Monat
+   *[other] vor { $count } Monaten
+    }
+age-years = { $count ->
+    [one] vor 1 Jahr
+   *[other] vor { $count } Jahren
+    }
+
+## Crates
+
+crate-blocked = Installiere dieses Crate nicht.
+crate-blocked-reason = Es wurde als Schadsoftware gemeldet:
+crate-blocked-advice = Falls es in deinem Abhängigkeitsbaum ist, entferne es und überprüfe die Rechner, die es gebaut haben.
+crate-placeholder = Dieses Crate scheint ein Platzhalter zu sein, der nur seinen Namen reserviert.
+crate-unmaintained = Dieses Crate scheint nicht mehr gepflegt zu werden. Seit über zwei Jahren gab es
+    keine Veröffentlichung, und sein Repository ist archiviert oder eine Sicherheitsmeldung besagt, dass es nicht mehr gepflegt wird.
+crate-successors = Dieses Crate wird fortgeführt von
+crate-name-collision = Cargo behandelt den Namen dieses Crates als gleich wie
+crate-name-collision-registry = aus { $registry }
+crate-name-collision-published = (zuerst veröffentlicht { $date })
+crate-name-collision-advice = Prüfe, aus welcher Registry deine Abhängigkeit darauf stammt.
+crate-duplicate = Die README dieses Crates ist zu { $percent } ähnlich zu der von
+crate-duplicate-advice = Es könnte eine Kopie dieses Crates sein.
+crate-deprecated = Dieses Crate ist veraltet.
+crate-deprecated-consider = Dieses Crate ist veraltet, ziehe
+crate-deprecated-instead = stattdessen in Betracht.
+crate-install = Installation
+crate-homepage = Webseite
+crate-documentation = Dokumentation
+crate-repository = Repository
+crate-version = Version { $version }
+crate-downloads = Downloads
+crate-downloads-recent = ({ $recent } in den letzten 30 Tagen, { $yearly } im letzten Jahr)
+crate-msrv = Minimal unterstützte Rust-Version
+crate-compile-time = Code zur Kompilierzeit
+crate-compile-proc-macro = Scheint ein prozedurales Makro zu sein.
+crate-compile-build-script = Scheint ein Build-Skript zu haben.
+crate-compile-dependencies = Benötigte prozedurale Makros: { $macros }, Crates mit Build-Skripten: { $scripts }.
+crate-reviews = Bewertungen
+crate-review-count = { $count ->
+    [one] 1 Bewertung
+   *[other] { $count } Bewertungen
+    }
+crate-audited-by = geprüft von { $auditors }
+crate-unsafe = Verwendung von unsafe
+crate-unsafe-forbidden = Verbietet unsafe-Code
+crate-unsafe-compiled = { $count ->
+    [one] 1 unsafe-Element kompiliert
+   *[other] { $count } unsafe-Elemente kompiliert
+    }
+crate-unsafe-uncompiled = { $count } weitere in Code, der nicht kompiliert wurde
+crate-unsafe-scanned = (untersuchte Version { $version }).
+crate-export = Export
+crate-export-print = Druckbare Zusammenfassung
+crate-export-markdown = Markdown
+crate-download-split = Downloads nach Version
+crate-download-split-description = Downloads der letzten 90 Tage, gruppiert nach semver-kompatiblen Veröffentlichungen.
+crate-platforms = Plattformen
+crate-platforms-description = Plattformen, für die das Crate spezifische Abhängigkeiten, Schlagwörter oder Kategorien hat.
+    Crates ohne plattformspezifischen Code lassen sich meist überall bauen, wo ihre Abhängigkeiten es tun.
+crate-platform-embedded = Eingebettet
+crate-depgraph = Abhängigkeitsgraph
+crate-depgraph-available = Verfügbar als
+crate-depgraph-or = oder
+crate-often-used-with = Oft verwendet mit
+crate-used-together = { $count ->
+    [one] 1 Crate verwendet beide
+   *[other] { $count } Crates verwenden beide
+    }
+crate-advisories = Sicherheitsmeldungen
+crate-ownership-changes = Änderungen der Besitzer
+ownership-added = { $owner } wurde hinzugefügt
+ownership-removed = { $owner } wurde entfernt
+ownership-team-added = Team { $owner } wurde hinzugefügt
+ownership-team-removed = Team { $owner } wurde entfernt
+crate-versions = Versionen
+column-version = Version
+column-msrv = MSRV
+column-verdict = Urteil
+column-source = Quelle
+column-reviewer = Prüfer
+version-yanked = zurückgezogen
+version-unyanked = wiederhergestellt
+version-diff = Vergleich
+link-download = .crate-Datei
+link-docs = docs.rs
+link-source = Quellcode
+
+## Dependencies
+
+dependencies-normal = Abhängigkeiten
+dependencies-build = Build-Abhängigkeiten
+dependencies-dev = Entwicklungsabhängigkeiten
+column-requirement = Anforderung
+column-notes = Hinweise
+dependency-rename = als { $name }
+dependency-stale = Erlaubt nicht die neueste Veröffentlichung
+dependency-latest = (neueste { $version })
+dependency-optional = optional
+dependency-no-default-features = keine Standard-Features
+dependency-features = Features: { $features }
+
+## Versions
+
+version-yanked-notice = Diese Version wurde zurückgezogen.
+version-not-latest = Dies ist nicht die neueste Version von { $name }.
+version-view-latest = Neueste Version ansehen.
+version-published = Veröffentlicht
+version-license = Lizenz
+version-size = Größe
+version-size-bytes = { $size ->
+    [one] 1 Byte
+   *[other] { $size } Bytes
+    }
+version-checksum = Prüfsumme
+version-features = Features
+column-feature = Feature
+column-enables = Aktiviert
+
+## Categories
+
+categories-empty = Es wurden noch keine Kategorien importiert.
+
+## Watchlists
+
+watchlist-watching = Beobachtet
+watchlist-subscribe = Diese Beobachtungsliste abonnieren.
+watchlist-missing = Nicht gefunden:
+watchlist-no-activity = Es gab noch keine Aktivität.
+watchlist-published = { $name } { $version } wurde veröffentlicht
+watchlist-yanked = { $name } { $version } wurde zurückgezogen
+watchlist-unyanked = { $name } { $version } wurde wiederhergestellt
+column-when = Wann
+column-event = Ereignis
+
+## Yanks
+
+yanks-heading = Zurückgezogene Versionen
+yanks-description = Versionen, die seit dem Import des vorherigen crates.io-Dumps zurückgezogen oder wiederhergestellt wurden.
+yanks-subscribe = Diesen Feed abonnieren.
+yanks-empty = Es wurden noch keine zurückgezogenen Versionen erkannt.
+column-updated = Aktualisiert
+
+## Keyword trends
+
+keyword-trends-heading = Schlagwort-Trends
+keyword-trends-description = Die Schlagwörter, deren Anzahl an Crates sich zwischen den Importen vom { $from } und { $to } am stärksten verändert hat.
+keyword-trends-growing = Wachsend
+keyword-trends-none-growing = Kein Schlagwort ist gewachsen.
+keyword-trends-declining = Schrumpfend
+keyword-trends-none-declining = Kein Schlagwort ist geschrumpft.
+keyword-trends-pending = Trends erscheinen, sobald die Verwendung von Schlagwörtern von zwei Importen an verschiedenen Tagen erfasst wurde.
+column-keyword = Schlagwort
+column-change = Änderung
+
+## Settings
+
+settings-link = Einstellungen
+settings-heading = Einstellungen
+settings-per-page = Ergebnisse pro Seite
+settings-per-page-all = Alle auf einer Seite
+settings-sort = Ergebnisse sortieren nach
+settings-sort-default = Standard
+settings-theme = Farbschema
+settings-theme-system = Wie das System
+settings-theme-light = Hell
+settings-theme-dark = Dunkel
+settings-hide-placeholders = Platzhalter-Crates standardmäßig ausblenden
+settings-save = Speichern
+settings-storage = Einstellungen werden in einem Cookie in deinem Browser gespeichert.
+
+## Embedded search
+
+embed-powered-by = Suche bereitgestellt von delve.rs
+
+## Errors
+
+error-not-found = Die angeforderte Seite wurde nicht gefunden.
+error-internal = Ein unerwarteter Fehler ist aufgetreten. Bitte versuche es später erneut.
+back-to-search = Zurück zur Suche
+crate-not-found = Es gibt kein Crate namens { $name }.
+similar-names = Crates mit ähnlichen Namen
+search-for = Nach { $query } suchen
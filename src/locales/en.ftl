@@ -0,0 +1,263 @@
+# The English interface, which is used for any message missing from another
+# locale.
+
+site-description = Search for Rust crates by name, description, keyword, and category.
+theme-toggle = Toggle theme
+
+## Search
+
+search-label = Search crates
+search-button = Search
+keyboard-shortcuts = Press / to search, j and k to move between results, and Enter to open one.
+results-label = Search results
+hide-placeholders = Hide placeholder crates
+recently-viewed = Recently viewed
+crate-of-the-day = Crate of the day
+random-crate = Show me a random crate
+categories-heading = Categories
+all-categories = All categories
+dashboard-crates = { $count ->
+    [one] 1 crate
+   *[other] { $count } crates
+    }
+dashboard-new-crates = { $count ->
+    [one] 1 new this week
+   *[other] { $count } new this week
+    }
+trending-heading = Trending
+recent-advisories = Recent advisories
+results-heading = Results for { $query }
+no-results = No crates matched your search.
+showing-results-for = Showing results for { $query }.
+search-instead-for = Search instead for { $query }
+partial-results = This search took too long, so these results are only ranked by how well they match.
+previous-page = Previous page
+next-page = Next page
+column-crate = Crate
+column-confidence = Confidence
+column-popularity = Popularity
+column-dependencies = Dependencies
+placeholder-badge = placeholder
+wasm-badge = wasm
+wasm-badge-title = Appears to support WebAssembly
+proc-macro-badge = proc-macro
+proc-macro-badge-title = Appears to be a procedural macro
+build-script-badge = build script
+build-script-badge-title = Appears to run a build script when compiled
+audited-badge = audited
+audited-badge-title = { $count ->
+    [one] Certified by a cargo-vet audit or crev review
+   *[other] Certified by { $count } cargo-vet audits or crev reviews
+    }
+unsafe-forbidden-badge = forbids unsafe
+unsafe-forbidden-badge-title = Declares #![forbid(unsafe_code)]
+unsafe-none-badge = no unsafe
+unsafe-none-badge-title = The scanned version compiles no unsafe code
+unsafe-badge = unsafe: { $count }
+unsafe-badge-title = Unsafe functions, expressions, impls, traits, and methods compiled by the scanned version
+first-published = first published { $age }
+deprecated = Deprecated
+deprecated-consider = Deprecated, consider
+successors-consider = Continued by
+dependency-depth = { $depth ->
+    [one] 1 level deep
+   *[other] { $depth } levels deep
+    }
+lightweight = lightweight
+heavyweight = heavyweight
+similar-crates = { $count ->
+    [one] 1 similar crate
+   *[other] { $count } similar crates
+    }
+
+## Ages
+
+age-today = today
+age-days = { $count ->
+    [one] 1 day ago
+   *[other] { $count } days ago
+    }
+age-months = { $count ->
+    [one] 1 month ago
+   *[other] { $count } months ago
+    }
+age-years = { $count ->
+    [one] 1 year ago
+   *[other] { $count } years ago
+    }
+
+## Crates
+
+crate-blocked = Do not install this crate.
+crate-blocked-reason = It has been flagged as malware:
+crate-blocked-advice = If it's in your dependency tree, remove it and check the machines that built it.
+crate-placeholder = This crate appears to be a placeholder that only reserves its name.
+crate-unmaintained = This crate appears to be unmaintained. It hasn't had a release in over two
+    years, and its repository is archived or an advisory says it's no longer maintained.
+crate-successors = This crate is continued by
+crate-name-collision = Cargo treats this crate's name as the same as
+crate-name-collision-registry = from { $registry }
+crate-name-collision-published = (first published { $date })
+crate-name-collision-advice = Check which registry your dependency on it comes from.
+crate-duplicate = This crate's README is { $percent } similar to that of
+crate-duplicate-advice = It may be a copy of that crate.
+crate-deprecated = This crate is deprecated.
+crate-deprecated-consider = This crate is deprecated, consider
+crate-deprecated-instead = instead.
+crate-install = Install
+crate-homepage = Homepage
+crate-documentation = Documentation
+crate-repository = Repository
+crate-version = Version { $version }
+crate-downloads = Downloads
+crate-downloads-recent = ({ $recent } in the last 30 days, { $yearly } in the last year)
+crate-msrv = Minimum Supported Rust Version
+crate-compile-time = Compile-time code
+crate-compile-proc-macro = Appears to be a procedural macro.
+crate-compile-build-script = Appears to have a build script.
+crate-compile-dependencies = Required procedural macros: { $macros }, crates with build scripts: { $scripts }.
+crate-reviews = Reviews
+crate-review-count = { $count ->
+    [one] 1 review
+   *[other] { $count } reviews
+    }
+crate-audited-by = audited by { $auditors }
+crate-unsafe = Unsafe usage
+crate-unsafe-forbidden = Forbids unsafe code
+crate-unsafe-compiled = { $count ->
+    [one] 1 unsafe item compiled
+   *[other] { $count } unsafe items compiled
+    }
+crate-unsafe-uncompiled = { $count } more in code that wasn't compiled
+crate-unsafe-scanned = (scanned version { $version }).
+crate-export = Export
+crate-export-print = Printable summary
+crate-export-markdown = Markdown
+crate-download-split = Downloads by version
+crate-download-split-description = Downloads in the last 90 days, grouped by semver-compatible releases.
+crate-platforms = Platforms
+crate-platforms-description = Platforms the crate has dependencies, keywords, or categories specific to.
+    Crates without platform-specific code usually build everywhere their dependencies do.
+crate-platform-embedded = Embedded
+crate-depgraph = Dependency graph
+crate-depgraph-available = Available as
+crate-depgraph-or = or
+crate-often-used-with = Often used with
+crate-used-together = { $count ->
+    [one] 1 crate uses both
+   *[other] { $count } crates use both
+    }
+crate-advisories = Advisories
+crate-ownership-changes = Ownership changes
+ownership-added = { $owner } was added
+ownership-removed = { $owner } was removed
+ownership-team-added = team { $owner } was added
+ownership-team-removed = team { $owner } was removed
+crate-versions = Versions
+column-version = Version
+column-msrv = MSRV
+column-verdict = Verdict
+column-source = Source
+column-reviewer = Reviewer
+version-yanked = yanked
+version-unyanked = unyanked
+version-diff = diff
+link-download = .crate file
+link-docs = docs.rs
+link-source = Source
+
+## Dependencies
+
+dependencies-normal = Dependencies
+dependencies-build = Build dependencies
+dependencies-dev = Dev dependencies
+column-requirement = Requirement
+column-notes = Notes
+dependency-rename = as { $name }
+dependency-stale = Doesn't allow the latest release
+dependency-latest = (latest { $version })
+dependency-optional = optional
+dependency-no-default-features = no default features
+dependency-features = features: { $features }
+
+## Versions
+
+version-yanked-notice = This version has been yanked.
+version-not-latest = This is not the latest version of { $name }.
+version-view-latest = View the latest version.
+version-published = Published
+version-license = License
+version-size = Size
+version-size-bytes = { $size ->
+    [one] 1 byte
+   *[other] { $size } bytes
+    }
+version-checksum = Checksum
+version-features = Features
+column-feature = Feature
+column-enables = Enables
+
+## Categories
+
+categories-empty = No categories have been imported yet.
+
+## Watchlists
+
+watchlist-watching = Watching
+watchlist-subscribe = Subscribe to this watchlist.
+watchlist-missing = Not found:
+watchlist-no-activity = There hasn't been any activity yet.
+watchlist-published = { $name } { $version } was published
+watchlist-yanked = { $name } { $version } was yanked
+watchlist-unyanked = { $name } { $version } was unyanked
+column-when = When
+column-event = Event
+
+## Yanks
+
+yanks-heading = Yanked versions
+yanks-description = Versions that were yanked or unyanked since the previous crates.io dump was imported.
+yanks-subscribe = Subscribe to this feed.
+yanks-empty = No yanks have been detected yet.
+column-updated = Updated
+
+## Keyword trends
+
+keyword-trends-heading = Keyword trends
+keyword-trends-description = The keywords whose number of crates changed the most between the imports of { $from } and { $to }.
+keyword-trends-growing = Growing
+keyword-trends-none-growing = No keywords grew.
+keyword-trends-declining = Declining
+keyword-trends-none-declining = No keywords declined.
+keyword-trends-pending = Trends appear once keyword usage has been recorded by two imports on different days.
+column-keyword = Keyword
+column-change = Change
+
+## Settings
+
+settings-link = Settings
+settings-heading = Settings
+settings-per-page = Results per page
+settings-per-page-all = All on one page
+settings-sort = Sort results by
+settings-sort-default = Default
+settings-theme = Theme
+settings-theme-system = Same as system
+settings-theme-light = Light
+settings-theme-dark = Dark
+settings-hide-placeholders = Hide placeholder crates by default
+settings-save = Save
+settings-storage = Settings are stored in a cookie in your browser.
+
+## Embedded search
+
+embed-powered-by = Search powered by delve.rs
+
+## Errors
+
+error-not-found = The page you requested could not be found.
+error-internal = An unexpected error occurred. Please try again later.
+back-to-search = Back to search
+crate-not-found = No crate is named { $name }.
+similar-names = Crates with similar names
+search-for = Search for { $query }
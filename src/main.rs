@@ -1,333 +1,243 @@
-use std::{
-    borrow::Cow,
-    cmp::Ordering,
-    collections::{HashMap, HashSet},
-    time::Instant,
+use std::{path::Path, time::Instant};
+
+use bonsaidb::local::Database;
+use delve_rs::{
+    api_keys, assets, bench,
+    config::Config,
+    dump::{self, ImportSource, Importer},
+    filters::Filters,
+    maintenance::Compactor,
+    query,
+    replication::{self, Publisher},
+    search_backend::Backend,
+    throttle, unsafe_usage, webserver,
 };
+use tracing_subscriber::EnvFilter;
 
-use bonsaidb::{
-    core::{connection::StorageConnection, key::Key, schema::SerializedView},
-    local::{
-        config::{Builder, StorageConfiguration},
-        Database, Storage,
-    },
-};
-use tantivy::{
-    collector::TopDocs,
-    query::QueryParser,
-    schema::{Field, Schema, Value, FAST, INDEXED, STORED, TEXT},
-    Index,
-};
+/// Where the database and search index are stored.
+const DATABASE_PATH: &str = "delve-rs.bonsaidb";
 
-use crate::cache::{Cache, CachedCrate};
-
-mod cache;
-mod dump;
-mod schema;
-mod webserver;
+/// Where a read-only replica copies the snapshot it serves.
+const REPLICA_PATH: &str = "delve-rs-replica.bonsaidb";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let storage = Storage::open(
-        StorageConfiguration::default()
-            .path("delve-rs.bonsaidb")
-            .with_schema::<schema::CrateIndex>()?,
-    )?;
-    let db = storage.create_database::<schema::CrateIndex>("delve", true)?;
-    let cache = Cache::new(db.clone())?;
-
-    let mut search_schema = tantivy::schema::Schema::builder();
-    let id = search_schema.add_u64_field("id", INDEXED | STORED);
-    let name = search_schema.add_text_field("name", TEXT);
-    let description = search_schema.add_text_field("description", TEXT);
-    let readme = search_schema.add_text_field("readme", TEXT);
-    let search_schema = search_schema.build();
-
-    std::fs::create_dir("delve-rs.bonsaidb/tantivy")?;
-    let index = SearchIndex {
-        index: Index::create_in_dir("delve-rs.bonsaidb/tantivy", search_schema.clone())?,
-        id,
-        name,
-        description,
-        readme,
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("delve_rs=info,tower_http=info")),
+        )
+        .init();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let config = Config::load()?;
+    let mut search = config.search.clone();
+    // Searching names and descriptions in memory leaves out READMEs, but
+    // needs far less memory than tantivy.
+    if take_flag(&mut args, "--no-tantivy") {
+        search.backend = Backend::Descriptions;
+    }
+    // A replica serves a copy of the newest snapshot published by the process
+    // that imports, and never imports itself.
+    let replica = if take_flag(&mut args, "--replica") {
+        replication::enable_read_only();
+        Some(replication::load_latest(
+            &config.replication,
+            Path::new(REPLICA_PATH),
+        )?)
+    } else {
+        None
     };
-
-    if std::env::args().len() <= 1 {
-        dump::import_continuously(db, cache, index).await?;
-        println!("About to exit.");
-        // webserver::run(db, cache, index).await?;
+    let database_path = if replica.is_some() {
+        REPLICA_PATH
     } else {
-        let q = std::env::args().nth(1).expect("length checked");
-        let start = Instant::now();
-        query(&q, &db, &cache, &index)?;
-        println!("Query executed in {}us", start.elapsed().as_micros());
+        DATABASE_PATH
+    };
+    let (db, cache, index) = delve_rs::open(database_path, &search)?;
+    let compactor = Compactor::new(db.clone(), database_path);
+    let publisher = Publisher::new(db.clone(), index.clone(), &config.replication);
+    throttle::configure(config.import_throttle.clone());
+
+    let dev = take_flag(&mut args, "--dev");
+    if dev {
+        assets::enable_dev_mode();
     }
-
-    Ok(())
-}
-
-#[derive(Clone, Debug)]
-struct SearchIndex {
-    pub index: Index,
-    pub id: Field,
-    pub name: Field,
-    pub description: Field,
-    pub readme: Field,
-}
-
-#[derive(Key, Debug, Clone)]
-struct Foo<'k> {
-    string: Cow<'k, str>,
-}
-
-#[derive(Debug)]
-struct CrateResult {
-    confidence: f32,
-    popularity: f32,
-    result: CachedCrate,
-}
-
-fn query(
-    query: &str,
-    db: &Database,
-    cache: &Cache,
-    index: &SearchIndex,
-) -> anyhow::Result<Vec<CrateResult>> {
-    let mut crate_scores = HashMap::new();
-
-    let mut total_words = 0;
-    for word in query.split_ascii_whitespace() {
-        if word.is_empty() {
-            continue;
-        }
-
-        total_words += 1;
-        let normalized_query = schema::Crate::normalized_name(word);
-        let lowercase_query = word.to_ascii_lowercase();
-
-        // Build matches based on the crate names
-        let crates_by_name = cache.crates_by_name()?;
-        for (normalized_name, crate_id) in crates_by_name.iter() {
-            if let Some(name_score) = TextScore::score(&normalized_query, normalized_name) {
-                let score = crate_scores
-                    .entry(*crate_id)
-                    .or_insert_with(QueryScore::default);
-                score.name.push(name_score);
-                score.matched_words.insert(word);
-            }
+    // Skipping the warm-up starts the server sooner, at the cost of slower
+    // first searches.
+    let warm_up = !take_flag(&mut args, "--no-warmup") && !dev;
+    if take_flag(&mut args, "--reconcile-downloads") {
+        dump::request_download_reconciliation(&db)?;
+    }
+    let mut args = args.into_iter();
+    if let Some(loaded) = replica {
+        if args.next().is_some() {
+            anyhow::bail!("--replica only serves searches");
         }
-
-        // Adjust matches based on keyword matches.
-        for mapping in schema::Keywords::entries(db)
-            .with_key_prefix(&lowercase_query)
-            .query()?
-        {
-            if let Some(keyword_score) = TextScore::score(word, &mapping.key) {
-                for crate_with_keyword in schema::CratesByKeyword::entries(db)
-                    .with_key(&mapping.source.id.deserialize::<u64>()?)
-                    .query()?
-                {
-                    let score = crate_scores
-                        .entry(crate_with_keyword.source.id.deserialize::<u64>()?)
-                        .or_insert_with(QueryScore::default);
-                    score.keywords.push(keyword_score);
-                    score.matched_words.insert(word);
-                }
-            }
+        if warm_up {
+            delve_rs::warm_up(&cache, &index)?;
         }
+        tokio::spawn(replication::watch(config.replication.clone(), loaded));
+        return webserver::run(&config, db, cache, index, None).await;
     }
-
-    // Search for crates that contain this word in their description/readme
-    let search_index = index.index.reader()?;
-    let searcher = search_index.searcher();
-    let query_parser = QueryParser::for_index(
-        &index.index,
-        vec![index.name, index.description, index.readme],
-    );
-    if let Ok(query) = query_parser.parse_query(query) {
-        for (search_score, doc) in search_index
-            .searcher()
-            .search(&query, &TopDocs::with_limit(1_000))?
-        {
-            if let Ok(doc) = searcher.doc(doc) {
-                if let Some(Value::U64(crate_id)) = doc.get_first(index.id) {
-                    let score = crate_scores
-                        .entry(*crate_id)
-                        .or_insert_with(QueryScore::default);
-                    score.index_score = Some(search_score);
-                }
+    match args.next() {
+        None => {
+            if warm_up {
+                delve_rs::warm_up(&cache, &index)?;
             }
+            let importer = Importer::new(
+                db.clone(),
+                cache.clone(),
+                index.clone(),
+                ImportSource::Download {
+                    data_dir: config.data_dir.clone(),
+                    registries: config.registries.clone(),
+                    unsafe_reports: config.unsafe_reports.clone(),
+                    reviews: config.reviews.clone(),
+                    embeddings: config.embeddings.clone(),
+                },
+            )
+            .with_publisher(publisher);
+            let initial_import = tokio::spawn({
+                let importer = importer.clone();
+                async move { importer.run().await }
+            });
+            tokio::spawn(
+                compactor
+                    .clone()
+                    .run_on_schedule(config.compaction, importer.clone()),
+            );
+            webserver::run(&config, db, cache, index, Some((importer, compactor))).await?;
+            initial_import.await??;
         }
-    }
-    // for mapping in schema::CratesByWord::entries(db).with_key(word).query()? {
-    //     let score = crate_scores
-    //         .entry(mapping.source.id.deserialize::<u64>()?)
-    //         .or_insert_with(QueryScore::default);
-    //     score.word_locations += mapping.value;
-    //     score.matched_words.insert(word);
-    // }
-
-    // Sort the result set and get rid of everything that didn't match all
-    // search terms.
-    let mut results = Vec::<(f32, f32, u64)>::with_capacity(crate_scores.len().max(1000));
-    for (id, score) in &crate_scores {
-        if score.matched_words.len() == total_words || score.index_score.is_some() {
-            let calculated = score.calculated_score();
-            let insert_at =
-                match results.binary_search_by(|(ascore, _, _)| calculated.total_cmp(ascore)) {
-                    Ok(insert_at) => insert_at,
-                    Err(insert_at) => insert_at,
-                };
-            if insert_at < 1000 {
-                results.insert(insert_at, (calculated, 0.0, *id));
-                if results.len() > 1000 {
-                    results.truncate(1000);
-                }
+        Some(flag) if flag == "--offline" => {
+            let dump_path = match (args.next(), args.next()) {
+                (Some(flag), Some(dump_path)) if flag == "--dump-path" => dump_path,
+                _ => anyhow::bail!("usage: delve-rs --offline --dump-path <path>"),
+            };
+            if warm_up {
+                delve_rs::warm_up(&cache, &index)?;
             }
+            let importer = Importer::new(
+                db.clone(),
+                cache.clone(),
+                index.clone(),
+                ImportSource::Offline {
+                    dump_path,
+                    registries: config.registries.clone(),
+                    unsafe_reports: config.unsafe_reports.clone(),
+                },
+            )
+            .with_publisher(publisher);
+            let initial_import = tokio::spawn({
+                let importer = importer.clone();
+                async move { importer.run().await }
+            });
+            tokio::spawn(
+                compactor
+                    .clone()
+                    .run_on_schedule(config.compaction, importer.clone()),
+            );
+            webserver::run(&config, db, cache, index, Some((importer, compactor))).await?;
+            initial_import.await??;
         }
-    }
-
-    if results.is_empty() {
-        return Ok(Vec::new());
-    }
-
-    // Build a confidence score
-    let maximum_confidence = results.first().expect("at least one result").0;
-    let mut total_downloads = 0;
-    let mut total_recent_downloads = 0;
-    let mut all_crates = HashMap::with_capacity(results.len());
-    let crates = cache.crates()?;
-    for (_, _, crate_id) in &results {
-        if let Some(c) = crates.get(crate_id) {
-            total_downloads += c.downloads;
-            total_recent_downloads += c.recent_downloads;
-
-            all_crates.insert(*crate_id, c.clone());
+        Some(command) if command == "compact" => {
+            let report = compactor.run().await?;
+            println!(
+                "Compacted in {}ms: {} bytes before, {} bytes after, {} bytes reclaimed",
+                report.duration_ms, report.size_before, report.size_after, report.reclaimed
+            );
         }
-    }
-
-    // Adjust the scores based on percentage of downloads across these search results.
-    for (confidence, popularity, id) in &mut results {
-        let Some(c) = all_crates.get(id) else { continue };
-
-        // Adjust confidence to be a percentage of the highest crate
-        *confidence /= maximum_confidence;
-
-        // Prioritize crates that have more recent downloads
-        let all_time_downloads_percent = c.downloads as f32 / total_downloads as f32;
-        let recent_downloads_percent = c.recent_downloads as f32 / total_recent_downloads as f32;
-        *popularity = (recent_downloads_percent * 4. + all_time_downloads_percent) / 5.;
-    }
-
-    let maximum_popularity = results
-        .iter()
-        .map(|(_, popularity, _)| *popularity)
-        .reduce(|a, b| {
-            if a.total_cmp(&b) == Ordering::Greater {
-                a
-            } else {
-                b
+        Some(command) if command == "import-unsafe" => {
+            let sources = args.collect::<Vec<_>>();
+            if sources.is_empty() {
+                anyhow::bail!("usage: delve-rs import-unsafe <report>...");
             }
-        })
-        .unwrap_or(1.);
-
-    results.sort_by(|a, b| {
-        (b.0 * (b.1 / maximum_popularity)).total_cmp(&(a.0 * (a.1 / maximum_popularity)))
-    });
-
-    let mut final_results = Vec::with_capacity(results.len());
-    for (confidence, popularity, id) in results {
-        let Some(c) = all_crates.remove(&id) else { continue };
-        final_results.push(CrateResult {
-            confidence,
-            popularity,
-            result: c,
-        });
-    }
-
-    Ok(final_results)
-}
-
-#[derive(Default, Debug)]
-struct QueryScore<'a> {
-    matched_words: HashSet<&'a str>,
-    index_score: Option<f32>,
-    name: Vec<TextScore>,
-    keywords: Vec<TextScore>,
-    category: Vec<TextScore>,
-}
-
-impl<'a> QueryScore<'a> {
-    fn calculated_score(&self) -> f32 {
-        // self.name
-        //     .iter()
-        //     .map(TextScore::calculated_score)
-        //     .sum::<f32>()
-        //     * 100.
-        //     + (self
-        //         .keywords
-        //         .iter()
-        //         .map(TextScore::calculated_score)
-        //         .sum::<f32>()
-        //         * 50.)
-        //     + self
-        //         .category
-        //         .iter()
-        //         .map(TextScore::calculated_score)
-        //         .sum::<f32>()
-        //         * 50.
-        //     +
-        self.index_score.unwrap_or(0.)
+            unsafe_usage::import(&db, &sources).await?;
+        }
+        Some(command) if command == "api-keys" => api_key_command(&db, args)?,
+        Some(command) if command == "bench" => {
+            let mut args = args.collect::<Vec<_>>();
+            // The fixture is benchmarked unless the local database is
+            // requested, so that results are comparable between machines.
+            let local = take_flag(&mut args, "--local");
+            let iterations = args
+                .first()
+                .map(|iterations| iterations.parse())
+                .transpose()?
+                .unwrap_or(10);
+            let queries = bench::load_queries("benches/queries.txt")?;
+            let labels = bench::load_labels("benches/relevance.toml")?;
+            let report = if local {
+                cache.refresh_blocking()?;
+                bench::run(&queries, &labels, iterations, &db, &cache, &index)?
+            } else {
+                let (_dir, db, cache, index) = bench::import_fixture(&search)?;
+                bench::run(&queries, &labels, iterations, &db, &cache, &index)?
+            };
+            print!("{report}");
+        }
+        Some(q) => {
+            let (q, filters) = Filters::parse(&q);
+            let start = Instant::now();
+            query(&q, &filters, &db, &cache, &index)?;
+            println!("Query executed in {}us", start.elapsed().as_micros());
+        }
     }
-}
 
-#[derive(Clone, Copy, Debug)]
-enum TextScore {
-    ExactMatch,
-    StartsWith { match_percent: f32 },
-    EndsWith { match_percent: f32 },
-    Contains { match_percent: f32 },
+    Ok(())
 }
 
-impl TextScore {
-    pub fn score(needle: &str, haystack: &str) -> Option<Self> {
-        let same_length = needle.len() == haystack.len();
-        haystack
-            .find(needle)
-            .map(|offset| Self::score_offset(offset, same_length, haystack.len(), needle.len()))
-            .or_else(|| {
-                needle.find(haystack).map(|offset| {
-                    Self::score_offset(offset, same_length, needle.len(), haystack.len())
-                })
-            })
-    }
-
-    fn score_offset(
-        offset: usize,
-        same_length: bool,
-        haystack_len: usize,
-        needle_len: usize,
-    ) -> Self {
-        let match_percent = needle_len as f32 / haystack_len as f32;
-        if offset == 0 {
-            if same_length {
-                Self::ExactMatch
-            } else {
-                Self::StartsWith { match_percent }
+/// Issues, lists, and revokes API keys.
+fn api_key_command(db: &Database, mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: delve-rs api-keys create <name> [--daily-quota <requests>]
+       delve-rs api-keys list
+       delve-rs api-keys revoke <id>";
+
+    match (
+        args.next().as_deref(),
+        args.next(),
+        args.next(),
+        args.next(),
+    ) {
+        (Some("create"), Some(name), None, None) => {
+            println!("{}", api_keys::create(db, &name, None)?);
+        }
+        (Some("create"), Some(name), Some(flag), Some(quota)) if flag == "--daily-quota" => {
+            println!("{}", api_keys::create(db, &name, Some(quota.parse()?))?);
+        }
+        (Some("list"), None, None, None) => {
+            for key in api_keys::list(db)? {
+                let quota = key
+                    .daily_quota
+                    .map_or_else(|| String::from("default"), |quota| quota.to_string());
+                println!(
+                    "{} {}{}: created {}, quota {quota}, {} requests today, {} total, {} rejected",
+                    key.id,
+                    key.name,
+                    if key.revoked { " (revoked)" } else { "" },
+                    key.created_at,
+                    key.requests_today,
+                    key.requests_total,
+                    key.rejected_total,
+                );
             }
-        } else if offset == haystack_len - needle_len {
-            Self::EndsWith { match_percent }
-        } else {
-            Self::Contains { match_percent }
         }
+        (Some("revoke"), Some(id), None, None) => {
+            let name = api_keys::revoke(db, &id)?;
+            println!("Revoked the API key issued to {name}");
+        }
+        _ => anyhow::bail!(USAGE),
     }
+    Ok(())
+}
 
-    fn calculated_score(&self) -> f32 {
-        match self {
-            TextScore::ExactMatch => 100.,
-            TextScore::StartsWith { match_percent } => 10. * match_percent * match_percent,
-            TextScore::EndsWith { match_percent } => 10. * match_percent * match_percent,
-            TextScore::Contains { match_percent } => *match_percent * *match_percent,
+/// Removes `flag` from `args`, returning true if it was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
         }
+        None => false,
     }
 }
@@ -0,0 +1,207 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bonsaidb::{core::connection::Connection, local::Database};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{analytics, dump::Importer, tantivy_index};
+
+/// How long a scheduled compaction waits for a running import to finish
+/// before checking again.
+const IMPORT_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// When the database is compacted in the background.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompactionConfig {
+    /// Hours between compactions, counted from when the server starts. 0
+    /// disables scheduled compaction, leaving only the admin API and the
+    /// `compact` command.
+    pub interval_hours: u64,
+    /// Days that search result clicks, experiment events, and queries that
+    /// haven't been searched for again, are kept before each scheduled
+    /// compaction deletes them. 0 keeps them forever.
+    pub analytics_retention_days: u64,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            interval_hours: 24,
+            analytics_retention_days: 90,
+        }
+    }
+}
+
+/// The outcome of a compaction, as reported by the admin API.
+#[derive(Serialize, Debug, Clone)]
+pub struct CompactionReport {
+    /// When the compaction finished, in RFC 3339 format.
+    pub finished_at: String,
+    pub duration_ms: u128,
+    /// The size of the database on disk before compacting, in bytes.
+    pub size_before: u64,
+    pub size_after: u64,
+    /// How many bytes the compaction freed, which is 0 if the database grew
+    /// because an import was writing to it at the same time.
+    pub reclaimed: u64,
+}
+
+/// Compacts the database, ensuring that only one compaction runs at a time.
+/// Compaction rewrites the database's files without the space left behind by
+/// overwritten and deleted documents.
+#[derive(Clone, Debug)]
+pub struct Compactor {
+    database: Database,
+    path: Arc<Path>,
+    running: Arc<AtomicBool>,
+    last_report: Arc<Mutex<Option<CompactionReport>>>,
+}
+
+impl Compactor {
+    /// Returns a compactor for `database`, which is stored in `path`.
+    pub fn new(database: Database, path: impl Into<PathBuf>) -> Self {
+        Self {
+            database,
+            path: Arc::from(path.into()),
+            running: Arc::default(),
+            last_report: Arc::default(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Returns the report of the last compaction since the server started.
+    pub fn last_report(&self) -> Option<CompactionReport> {
+        self.last_report.lock().expect("poisoned").clone()
+    }
+
+    /// Compacts the database, returning an error if a compaction is already
+    /// running.
+    pub async fn run(&self) -> anyhow::Result<CompactionReport> {
+        let Some(_running) = self.claim() else { anyhow::bail!("a compaction is already running") };
+        let compactor = self.clone();
+        tokio::task::spawn_blocking(move || compactor.compact()).await?
+    }
+
+    /// Starts a compaction in the background, returning false if one is
+    /// already running.
+    pub fn start(&self) -> bool {
+        let Some(running) = self.claim() else { return false };
+        let compactor = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _running = running;
+            if let Err(err) = compactor.compact() {
+                tracing::error!("error compacting: {err}");
+            }
+        });
+        true
+    }
+
+    /// Compacts the database every `config.interval_hours`, postponing each
+    /// compaction while `importer` is running so that the two don't compete
+    /// for the disk. Search analytics older than
+    /// `config.analytics_retention_days` are deleted first, so that the
+    /// compaction reclaims their space.
+    pub async fn run_on_schedule(self, config: CompactionConfig, importer: Importer) {
+        if config.interval_hours == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(config.interval_hours.saturating_mul(60 * 60));
+        loop {
+            tokio::time::sleep(interval).await;
+            while importer.is_running() {
+                tokio::time::sleep(IMPORT_RETRY_INTERVAL).await;
+            }
+            if config.analytics_retention_days > 0 {
+                self.prune_analytics(config.analytics_retention_days).await;
+            }
+            if let Err(err) = self.run().await {
+                tracing::error!("error compacting: {err}");
+            }
+        }
+    }
+
+    /// Deletes the search analytics older than `retention_days`, logging
+    /// rather than returning errors so that the compaction still runs.
+    async fn prune_analytics(&self, retention_days: u64) {
+        let database = self.database.clone();
+        let pruned =
+            tokio::task::spawn_blocking(move || analytics::prune(&database, retention_days));
+        match pruned.await {
+            Ok(Ok(deleted)) => tracing::info!("deleted {deleted} expired analytics records"),
+            Ok(Err(err)) => tracing::error!("error pruning analytics: {err}"),
+            Err(err) => tracing::error!("error pruning analytics: {err}"),
+        }
+    }
+
+    fn claim(&self) -> Option<RunningCompaction> {
+        (!self.running.swap(true, Ordering::AcqRel))
+            .then(|| RunningCompaction(self.running.clone()))
+    }
+
+    fn compact(&self) -> anyhow::Result<CompactionReport> {
+        tracing::info!("compacting the database");
+        let size_before = disk_usage(&self.path)?;
+        let start = Instant::now();
+        self.database.compact()?;
+        let duration = start.elapsed();
+        let size_after = disk_usage(&self.path)?;
+
+        let report = CompactionReport {
+            finished_at: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            duration_ms: duration.as_millis(),
+            size_before,
+            size_after,
+            reclaimed: size_before.saturating_sub(size_after),
+        };
+        tracing::info!(
+            "compacted the database in {}ms, reclaiming {} bytes",
+            report.duration_ms,
+            report.reclaimed
+        );
+        *self.last_report.lock().expect("poisoned") = Some(report.clone());
+        Ok(report)
+    }
+}
+
+/// Marks a compaction as running until dropped, even if the compaction
+/// panics.
+struct RunningCompaction(Arc<AtomicBool>);
+
+impl Drop for RunningCompaction {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Returns the total size of the files in `path`, excluding the search index,
+/// which compaction doesn't touch.
+fn disk_usage(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(tantivy_index::DIRECTORY)
+            {
+                size += disk_usage(&entry.path())?;
+            }
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,136 @@
+//! Escaping for the Markdown templates. Most of what they show comes from
+//! crates' metadata, which could otherwise add links, images, or HTML to an
+//! exported summary, or break out of the list item or table cell it's in.
+
+use std::fmt::{self, Write};
+
+use askama_escape::Escaper;
+
+/// The escaper `askama.toml` configures for `.md` templates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Markdown;
+
+impl Escaper for Markdown {
+    fn write_escaped<W>(&self, mut fmt: W, string: &str) -> fmt::Result
+    where
+        W: Write,
+    {
+        let block_start = block_start(string);
+        for (index, ch) in string.char_indices() {
+            match ch {
+                // Values are always written within a line.
+                '\n' | '\r' => fmt.write_char(' ')?,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '&' => {
+                    fmt.write_char('\\')?;
+                    fmt.write_char(ch)?;
+                }
+                _ if Some(index) == block_start => {
+                    fmt.write_char('\\')?;
+                    fmt.write_char(ch)?;
+                }
+                _ => fmt.write_char(ch)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the index of the character that would start a heading or list if
+/// `string` began a line, such as the `#` of `# Title` or the `.` of `1. `.
+fn block_start(string: &str) -> Option<usize> {
+    let marker_ends_line = |end: usize| {
+        string[end..]
+            .chars()
+            .next()
+            .map_or(true, char::is_whitespace)
+    };
+    match string.chars().next()? {
+        '#' | '=' => Some(0),
+        '-' | '+' if marker_ends_line(1) => Some(0),
+        '0'..='9' => {
+            let digits = string.bytes().take_while(u8::is_ascii_digit).count();
+            let marker = string[digits..].chars().next()?;
+            (matches!(marker, '.' | ')') && marker_ends_line(digits + 1)).then_some(digits)
+        }
+        _ => None,
+    }
+}
+
+/// Returns `url` as a Markdown autolink, or as escaped text if it can't be
+/// one, such as when it contains spaces or has no scheme.
+pub(crate) fn autolink(url: &str) -> String {
+    let has_scheme = url.split_once(':').map_or(false, |(scheme, _)| {
+        (2..=32).contains(&scheme.len())
+            && scheme.starts_with(|ch: char| ch.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '+' | '.' | '-'))
+    });
+    if has_scheme
+        && !url
+            .chars()
+            .any(|ch| ch.is_whitespace() || ch.is_control() || matches!(ch, '<' | '>'))
+    {
+        format!("<{url}>")
+    } else {
+        let mut escaped = String::with_capacity(url.len());
+        Markdown
+            .write_escaped(&mut escaped, url)
+            .expect("writing to a String can't fail");
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(string: &str) -> String {
+        let mut escaped = String::new();
+        Markdown.write_escaped(&mut escaped, string).unwrap();
+        escaped
+    }
+
+    #[test]
+    fn inline_markup_is_escaped() {
+        assert_eq!(escape("serde_json"), "serde\\_json");
+        assert_eq!(
+            escape("![x](https://evil.example)"),
+            "!\\[x\\](https://evil.example)"
+        );
+        assert_eq!(escape("<script>"), "\\<script\\>");
+        assert_eq!(escape("a | b"), "a \\| b");
+        assert_eq!(escape("**bold** `code`"), "\\*\\*bold\\*\\* \\`code\\`");
+        assert_eq!(escape("&amp;"), "\\&amp;");
+        assert_eq!(escape("line\nbreak"), "line break");
+        assert_eq!(escape("A fast JSON parser."), "A fast JSON parser.");
+    }
+
+    #[test]
+    fn block_markers_are_escaped() {
+        assert_eq!(escape("# Title"), "\\# Title");
+        assert_eq!(escape("- item"), "\\- item");
+        assert_eq!(escape("+"), "\\+");
+        assert_eq!(escape("12. item"), "12\\. item");
+        assert_eq!(escape("1) item"), "1\\) item");
+        assert_eq!(escape("==="), "\\===");
+        // Versions and hyphenated names aren't block markers.
+        assert_eq!(escape("1.0.0"), "1.0.0");
+        assert_eq!(escape("-sys"), "-sys");
+        assert_eq!(escape("1970"), "1970");
+    }
+
+    #[test]
+    fn autolinks() {
+        assert_eq!(
+            autolink("https://github.com/serde-rs/serde_json"),
+            "<https://github.com/serde-rs/serde_json>"
+        );
+        assert_eq!(
+            autolink("https://example.com/a>b"),
+            "https://example.com/a\\>b"
+        );
+        assert_eq!(autolink("see [docs](x)"), "see \\[docs\\](x)");
+        assert_eq!(autolink("example.com"), "example.com");
+    }
+}
@@ -0,0 +1,465 @@
+//! A [`SearchBackend`] that indexes crates in an external Meilisearch
+//! instance.
+//!
+//! Searches are synchronous, so requests are sent from a thread of their own
+//! rather than from whichever runtime the search happens to be called in.
+//! Web handlers search through [`crate::spawn_query`], so waiting for a
+//! response never blocks the runtime's workers.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::{
+    schema::Crate,
+    search_backend::{Highlighter, IndexWriter, SearchBackend},
+    snippets::WordHighlighter,
+    syntax::{self, Expr},
+};
+
+/// The most crates sent to Meilisearch by each request while importing.
+const BATCH_SIZE: usize = 500;
+
+/// How long a request to Meilisearch may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a search may take. Searches are made while a visitor waits, so
+/// they give up much sooner than indexing requests.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a committed batch is checked on while waiting for Meilisearch to
+/// index it.
+const TASK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long to wait for Meilisearch to finish a task before giving up.
+const TASK_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Appended to the index's name to name the index a rebuild writes to before
+/// it's swapped with the live index.
+const REBUILD_SUFFIX: &str = "-rebuild";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeilisearchConfig {
+    /// The instance's URL, such as `http://localhost:7700`.
+    pub url: String,
+    /// The index crates are stored in, which is created if needed.
+    pub index: String,
+    /// Sent as `Authorization: Bearer <key>`. `DELVE_RS_MEILISEARCH_KEY`
+    /// overrides this setting.
+    pub api_key: Option<String>,
+}
+
+impl Default for MeilisearchConfig {
+    fn default() -> Self {
+        Self {
+            url: String::from("http://localhost:7700"),
+            index: String::from("crates"),
+            api_key: None,
+        }
+    }
+}
+
+impl MeilisearchConfig {
+    /// Returns the API key, preferring `DELVE_RS_MEILISEARCH_KEY`.
+    fn api_key(&self) -> Option<String> {
+        std::env::var("DELVE_RS_MEILISEARCH_KEY")
+            .ok()
+            .or_else(|| self.api_key.clone())
+            .filter(|key| !key.is_empty())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Meilisearch {
+    requests: flume::Sender<Request>,
+    /// The name of the index, such as `crates`.
+    uid: Arc<str>,
+    /// The path of the index, such as `/indexes/crates`.
+    index: Arc<str>,
+}
+
+impl Meilisearch {
+    /// Connects to the instance in `config`, configuring which fields of its
+    /// index are searched.
+    pub fn connect(config: &MeilisearchConfig) -> anyhow::Result<Self> {
+        let connection = Connection {
+            http: reqwest::Client::builder().build()?,
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key: config.api_key(),
+        };
+        let (requests, receiver) = flume::unbounded::<Request>();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        thread::Builder::new()
+            .name(String::from("meilisearch"))
+            .spawn(move || {
+                runtime.block_on(async move {
+                    let connection = Arc::new(connection);
+                    while let Ok(request) = receiver.recv_async().await {
+                        tokio::spawn(request.send(connection.clone()));
+                    }
+                });
+            })?;
+
+        let meilisearch = Self {
+            requests,
+            uid: Arc::from(config.index.as_str()),
+            index: Arc::from(format!("/indexes/{}", config.index)),
+        };
+        meilisearch.configure(&meilisearch.index)?;
+        Ok(meilisearch)
+    }
+
+    /// Configures which fields of the index at `index` are searched, creating
+    /// the index if it doesn't exist.
+    fn configure(&self, index: &str) -> anyhow::Result<()> {
+        let task = self.call(
+            Method::PATCH,
+            format!("{index}/settings"),
+            Some(json!({ "searchableAttributes": ["name", "description", "readme"] })),
+        )?;
+        self.wait(&task)
+    }
+
+    /// Sends a request and waits for its response.
+    fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<Value>,
+    ) -> anyhow::Result<T> {
+        self.call_with_timeout(method, path, body, REQUEST_TIMEOUT)
+    }
+
+    /// Sends a request and waits up to `timeout` for its response.
+    fn call_with_timeout<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<Value>,
+        timeout: Duration,
+    ) -> anyhow::Result<T> {
+        let (reply, response) = flume::bounded(1);
+        self.requests
+            .send(Request {
+                method,
+                path,
+                body,
+                timeout,
+                reply,
+            })
+            .map_err(|_| anyhow::anyhow!("meilisearch thread stopped"))?;
+        Ok(serde_json::from_value(response.recv()??)?)
+    }
+
+    /// Waits up to [`TASK_TIMEOUT`] for Meilisearch to finish `task`,
+    /// returning an error if the task failed.
+    fn wait(&self, task: &Task) -> anyhow::Result<()> {
+        let started = Instant::now();
+        loop {
+            let status =
+                self.call::<TaskStatus>(Method::GET, format!("/tasks/{}", task.task_uid), None)?;
+            match status.status.as_str() {
+                "succeeded" => return Ok(()),
+                "failed" | "canceled" => {
+                    let error = status.error.map_or_else(String::new, |error| error.message);
+                    anyhow::bail!(
+                        "meilisearch task {} {}: {error}",
+                        task.task_uid,
+                        status.status
+                    )
+                }
+                _ if started.elapsed() >= TASK_TIMEOUT => {
+                    anyhow::bail!(
+                        "timed out waiting for meilisearch task {}, which is {}",
+                        task.task_uid,
+                        status.status
+                    )
+                }
+                _ => thread::sleep(TASK_POLL_INTERVAL),
+            }
+        }
+    }
+
+    fn writer_for(&self, index: Arc<str>) -> MeilisearchWriter {
+        MeilisearchWriter {
+            meilisearch: self.clone(),
+            index,
+            pending: Vec::new(),
+            tasks: Vec::new(),
+        }
+    }
+}
+
+impl SearchBackend for Meilisearch {
+    /// Meilisearch is sent the query's words and phrases that aren't
+    /// excluded. Operators and field prefixes only apply to the name and
+    /// keyword matching.
+    fn search(&self, terms: &str, limit: usize) -> anyhow::Result<Vec<(f32, u64)>> {
+        let Some(expr) = syntax::parse(terms) else { return Ok(Vec::new()) };
+        let response = self.call_with_timeout::<SearchResponse>(
+            Method::POST,
+            format!("{}/search", self.index),
+            Some(json!({
+                "q": query_text(&expr),
+                "limit": limit,
+                "attributesToRetrieve": ["id"],
+                "showRankingScore": true,
+            })),
+            SEARCH_TIMEOUT,
+        )?;
+        response
+            .hits
+            .into_iter()
+            .map(|hit| {
+                let score = hit.ranking_score.ok_or_else(|| {
+                    anyhow::anyhow!("meilisearch didn't report ranking scores, which 1.3 added")
+                })?;
+                Ok((score, hit.id))
+            })
+            .collect()
+    }
+
+    fn writer(&self) -> anyhow::Result<Box<dyn IndexWriter>> {
+        Ok(Box::new(self.writer_for(self.index.clone())))
+    }
+
+    /// Indexes every crate into a temporary index, which is then swapped with
+    /// the live index, so that searches keep using the old index until the
+    /// new one is complete.
+    fn rebuild(&self, db: &Database) -> anyhow::Result<usize> {
+        let uid = format!("{}{REBUILD_SUFFIX}", self.uid);
+        let index = Arc::<str>::from(format!("/indexes/{uid}"));
+        self.configure(&index)?;
+        // A failed rebuild can leave documents behind.
+        let cleared = self.call(Method::DELETE, format!("{index}/documents"), None)?;
+        self.wait(&cleared)?;
+
+        let mut writer = self.writer_for(index.clone());
+        let mut indexed = 0;
+        for krate in Crate::all(db).query()? {
+            writer.replace(krate.header.id, &krate.contents)?;
+            indexed += 1;
+        }
+        Box::new(writer).commit()?;
+
+        let swapped = self.call(
+            Method::POST,
+            String::from("/swap-indexes"),
+            Some(json!([{ "indexes": [&*self.uid, &uid] }])),
+        )?;
+        self.wait(&swapped)?;
+        // The temporary index now holds the old documents.
+        let deleted = self.call(Method::DELETE, index.to_string(), None)?;
+        self.wait(&deleted)?;
+        Ok(indexed)
+    }
+
+    /// Checks that the instance is reachable.
+    fn warm_up(&self) -> anyhow::Result<()> {
+        self.call::<Value>(Method::GET, String::from("/health"), None)?;
+        Ok(())
+    }
+
+    /// Meilisearch doesn't report how many documents contain each word, so
+    /// the spelling dictionary is built from names and keywords alone.
+    fn common_terms(&self, _min_crates: u32) -> anyhow::Result<HashMap<String, u64>> {
+        Ok(HashMap::new())
+    }
+
+    fn num_docs(&self) -> u64 {
+        match self.call::<IndexStats>(Method::GET, format!("{}/stats", self.index), None) {
+            Ok(stats) => stats.number_of_documents,
+            Err(err) => {
+                tracing::warn!("error reading meilisearch stats: {err}");
+                0
+            }
+        }
+    }
+
+    fn highlighter(&self, terms: &str) -> anyhow::Result<Option<Box<dyn Highlighter>>> {
+        Ok(syntax::parse(terms)
+            .map(|expr| Box::new(WordHighlighter::new(&expr)) as Box<dyn Highlighter>))
+    }
+
+    /// Replicas search the same Meilisearch instance.
+    fn snapshot(&self, _destination: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the words and quoted phrases of `expr` that aren't excluded.
+fn query_text(expr: &Expr<'_>) -> String {
+    expr.terms()
+        .into_iter()
+        .filter(|(_, negated)| !negated)
+        .map(|(term, _)| {
+            if term.phrase {
+                format!("\"{}\"", term.text)
+            } else {
+                term.text.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Sends crates to Meilisearch in batches, since each request enqueues a task
+/// that Meilisearch indexes on its own time.
+struct MeilisearchWriter {
+    meilisearch: Meilisearch,
+    /// The path of the index written to.
+    index: Arc<str>,
+    pending: Vec<Value>,
+    /// The tasks enqueued so far, in the order they were enqueued.
+    tasks: Vec<Task>,
+}
+
+impl MeilisearchWriter {
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.pending.is_empty() {
+            let documents = Value::Array(std::mem::take(&mut self.pending));
+            self.tasks.push(self.meilisearch.call(
+                Method::POST,
+                format!("{}/documents?primaryKey=id", self.index),
+                Some(documents),
+            )?);
+        }
+        Ok(())
+    }
+}
+
+impl IndexWriter for MeilisearchWriter {
+    fn replace(&mut self, id: u64, krate: &Crate) -> anyhow::Result<()> {
+        self.pending.push(json!({
+            "id": id,
+            "name": krate.name,
+            "description": krate.description,
+            "readme": krate.readme,
+        }));
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Waits for Meilisearch to index every batch sent, returning an error if
+    /// any batch failed. A failed task doesn't stop later tasks from running,
+    /// so each is checked.
+    fn commit(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.flush()?;
+        for task in &self.tasks {
+            self.meilisearch.wait(task)?;
+        }
+        Ok(())
+    }
+}
+
+struct Connection {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+/// A request sent to the Meilisearch thread.
+struct Request {
+    method: Method,
+    /// The path of the request, starting with `/`.
+    path: String,
+    body: Option<Value>,
+    timeout: Duration,
+    reply: flume::Sender<anyhow::Result<Value>>,
+}
+
+impl Request {
+    async fn send(self, connection: Arc<Connection>) {
+        let mut request = connection
+            .http
+            .request(self.method, format!("{}{}", connection.url, self.path))
+            .timeout(self.timeout);
+        if let Some(api_key) = &connection.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        if let Some(body) = &self.body {
+            request = request.json(body);
+        }
+        let response =
+            async move { anyhow::Ok(request.send().await?.error_for_status()?.json().await?) };
+        // The caller stops waiting if it's dropped.
+        let _ = self.reply.send(response.await);
+    }
+}
+
+/// An enqueued indexing task.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    task_uid: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct TaskStatus {
+    status: String,
+    error: Option<TaskError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TaskError {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResponse {
+    hits: Vec<Hit>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Hit {
+    id: u64,
+    #[serde(rename = "_rankingScore")]
+    ranking_score: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct IndexStats {
+    number_of_documents: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(query: &str) -> String {
+        query_text(&syntax::parse(query).expect("has terms"))
+    }
+
+    #[test]
+    fn words_are_sent() {
+        assert_eq!(text("http server"), "http server");
+        assert_eq!(text("http OR server"), "http server");
+    }
+
+    #[test]
+    fn phrases_are_quoted() {
+        assert_eq!(text("\"zero copy\" parser"), "\"zero copy\" parser");
+    }
+
+    #[test]
+    fn excluded_terms_are_left_out() {
+        assert_eq!(text("serde NOT json"), "serde");
+        assert_eq!(text("orm OR (sql NOT \"async io\")"), "orm sql");
+        assert_eq!(text("NOT (a NOT b)"), "b");
+    }
+}
@@ -0,0 +1,84 @@
+use serde::Serialize;
+use time::Date;
+
+use crate::{
+    cache::{Cache, CachedCrate},
+    schema::Crate,
+};
+
+/// A crate whose name Cargo treats as the same as another crate's, because
+/// they only differ by `-` and `_` or by case. Depending on the wrong one is a
+/// common source of dependency confusion, especially when a private registry
+/// has a crate with the same name as a public one.
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct CollidingCrate {
+    pub name: String,
+    pub registry: String,
+    pub first_published: Option<String>,
+}
+
+impl CollidingCrate {
+    fn new(krate: &CachedCrate) -> Self {
+        Self {
+            name: krate.name().to_string(),
+            registry: krate.registry().to_string(),
+            first_published: krate.first_published().map(|date| date.to_string()),
+        }
+    }
+}
+
+/// A group of crates sharing a normalized name.
+#[derive(Serialize, Debug)]
+pub(crate) struct Collision {
+    pub normalized_name: String,
+    /// The crates sharing the name, oldest first.
+    pub crates: Vec<CollidingCrate>,
+}
+
+/// Returns the other crates whose normalized name is the same as the crate
+/// with `id`.
+pub(crate) fn for_crate(cache: &Cache, id: u64) -> anyhow::Result<Vec<CollidingCrate>> {
+    let crates = cache.crates()?;
+    let Some(krate) = crates.get(id) else { return Ok(Vec::new()) };
+    let names = cache.names()?;
+    Ok(names
+        .all(&Crate::normalized_name(krate.name()))
+        .filter(|other| *other != id)
+        .filter_map(|other| crates.get(other))
+        .map(|other| CollidingCrate::new(&other))
+        .collect())
+}
+
+/// Returns the collisions involving a crate first published on or after
+/// `since`, with the most recent collisions first.
+pub(crate) fn report(cache: &Cache, since: Date) -> anyhow::Result<Vec<Collision>> {
+    let crates = cache.crates()?;
+    let names = cache.names()?;
+    let mut collisions = Vec::new();
+    for (normalized_name, ids) in names.shared() {
+        let mut colliding = ids
+            .into_iter()
+            .filter_map(|id| crates.get(id))
+            .collect::<Vec<_>>();
+        colliding.sort_by_key(|krate| krate.first_published());
+        let newest = colliding
+            .iter()
+            .filter_map(|krate| krate.first_published())
+            .max();
+        if colliding.len() < 2 || newest.map_or(true, |newest| newest < since) {
+            continue;
+        }
+        collisions.push((
+            newest,
+            Collision {
+                normalized_name: normalized_name.to_string(),
+                crates: colliding.iter().map(CollidingCrate::new).collect(),
+            },
+        ));
+    }
+    collisions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(collisions
+        .into_iter()
+        .map(|(_, collision)| collision)
+        .collect())
+}
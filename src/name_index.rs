@@ -0,0 +1,265 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+use crate::spelling::edit_distance;
+use crate::strings::{Span, StringArena};
+
+/// The longest n-gram indexed. Shorter n-grams are indexed as well so that
+/// query words shorter than this can be looked up directly.
+const MAX_GRAM: usize = 3;
+
+type Gram = [u8; MAX_GRAM];
+
+/// The longest crate name crates.io allows. Longer substrings of a query word
+/// can't be names, so they aren't looked up.
+pub(crate) const MAX_NAME_LEN: usize = 64;
+
+/// The most names [`NameIndex::similar`] computes edit distances for. A word
+/// with few distinct bigrams, such as `sesese`, shares half of them with a
+/// large share of all names.
+const MAX_SIMILAR_CANDIDATES: usize = 1_000;
+
+/// An n-gram index of normalized crate names. This finds the names that
+/// contain a query word, or are contained by it, without scanning every crate.
+#[derive(Debug, Default)]
+pub struct NameIndex {
+    text: StringArena,
+    /// Normalized names and their crate ids, sorted by name.
+    names: Vec<(Span, u64)>,
+    /// For each n-gram, the indexes into `names` of every name containing it,
+    /// in ascending order.
+    grams: HashMap<Gram, Vec<u32>>,
+}
+
+impl NameIndex {
+    pub fn new(names: impl IntoIterator<Item = (String, u64)>) -> Self {
+        let mut names = names.into_iter().collect::<Vec<_>>();
+        names.sort_unstable();
+
+        let mut grams = HashMap::<Gram, Vec<u32>>::new();
+        for (index, (name, _)) in names.iter().enumerate() {
+            let index = u32::try_from(index).expect("too many crates");
+            for len in 1..=MAX_GRAM {
+                for window in name.as_bytes().windows(len) {
+                    let postings = grams.entry(gram(window)).or_default();
+                    // A name can contain the same n-gram more than once.
+                    if postings.last() != Some(&index) {
+                        postings.push(index);
+                    }
+                }
+            }
+        }
+
+        let mut text = StringArena::default();
+        let names = names
+            .into_iter()
+            .map(|(name, id)| (text.push(&name), id))
+            .collect();
+        text.shrink_to_fit();
+
+        Self { text, names, grams }
+    }
+
+    /// Returns the id of the crate whose normalized name is `normalized_name`.
+    pub fn get(&self, normalized_name: &str) -> Option<u64> {
+        self.position(normalized_name)
+            .map(|index| self.names[index].1)
+    }
+
+    /// Returns the ids of every crate whose normalized name is
+    /// `normalized_name`. Crates from different registries can share a name.
+    pub fn all<'a>(&'a self, normalized_name: &'a str) -> impl Iterator<Item = u64> + 'a {
+        let start = self
+            .names
+            .partition_point(|(name, _)| self.text.get(*name) < normalized_name);
+        self.names[start..]
+            .iter()
+            .take_while(move |(name, _)| self.text.get(*name) == normalized_name)
+            .map(|(_, id)| *id)
+    }
+
+    /// Returns each normalized name shared by more than one crate, along with
+    /// the ids of those crates.
+    pub fn shared(&self) -> Vec<(&str, Vec<u64>)> {
+        let mut shared = Vec::<(&str, Vec<u64>)>::new();
+        for window in self.names.windows(2) {
+            let (name, id) = (self.text.get(window[0].0), window[0].1);
+            if name != self.text.get(window[1].0) {
+                continue;
+            }
+            match shared.last_mut() {
+                Some((last, ids)) if *last == name => ids.push(window[1].1),
+                _ => shared.push((name, vec![id, window[1].1])),
+            }
+        }
+        shared
+    }
+
+    fn position(&self, normalized_name: &str) -> Option<usize> {
+        self.names
+            .binary_search_by(|(name, _)| self.text.get(*name).cmp(normalized_name))
+            .ok()
+    }
+
+    /// Returns the normalized name and crate id of every crate whose name
+    /// contains `word` or is contained within `word`.
+    pub fn candidates(&self, word: &str) -> Vec<(&str, u64)> {
+        let bytes = word.as_bytes();
+        let mut candidates = if bytes.len() <= MAX_GRAM {
+            // Every name containing a short word contains it as an n-gram.
+            self.grams
+                .get(&gram(bytes))
+                .map(|postings| postings.iter().map(|index| self.entry(*index)).collect())
+                .unwrap_or_default()
+        } else {
+            self.containing(word)
+        };
+
+        // Add the names that are substrings of the word. The word itself has
+        // already been matched above.
+        let mut seen = HashSet::new();
+        for start in 0..word.len() {
+            for end in start + 1..=word.len().min(start + MAX_NAME_LEN) {
+                let Some(substring) = word.get(start..end) else { continue };
+                if substring.len() == word.len() || !seen.insert(substring) {
+                    continue;
+                }
+                if let Some(index) = self.position(substring) {
+                    candidates.push(self.entry(index as u32));
+                }
+            }
+        }
+
+        candidates
+    }
+
+    /// Returns the edit distances, normalized names, and crate ids of up to
+    /// `limit` crates whose names are closest to `word`, closest first. Names
+    /// are only compared if they share at least half of the word's bigrams,
+    /// so misspellings and names containing the word are found without
+    /// comparing every name. Words longer than any name have no similar names.
+    pub fn similar(&self, word: &str, limit: usize) -> Vec<(usize, &str, u64)> {
+        if word.len() > MAX_NAME_LEN {
+            return Vec::new();
+        }
+        let mut shared = HashMap::<u32, usize>::new();
+        let mut bigrams = 0;
+        let mut seen = HashSet::new();
+        for window in word.as_bytes().windows(2) {
+            if !seen.insert(window) {
+                continue;
+            }
+            bigrams += 1;
+            for index in self.grams.get(&gram(window)).into_iter().flatten() {
+                *shared.entry(*index).or_default() += 1;
+            }
+        }
+
+        // Only the names sharing the most bigrams, and the shortest names
+        // containing or contained by the word, are compared. The edit
+        // distance is at least the difference in length, so names closest in
+        // length are preferred among those sharing as many bigrams.
+        let mut sharing = shared
+            .into_iter()
+            .filter(|(_, count)| *count * 2 >= bigrams)
+            .map(|(index, count)| {
+                let (name, _) = self.entry(index);
+                (Reverse(count), name.len().abs_diff(word.len()), index)
+            })
+            .collect::<Vec<_>>();
+        sharing.sort_unstable();
+        sharing.truncate(MAX_SIMILAR_CANDIDATES);
+        let mut containing = self.candidates(word);
+        containing.sort_unstable_by_key(|(name, _)| (name.len(), *name));
+        containing.truncate(MAX_SIMILAR_CANDIDATES);
+
+        let mut similar = sharing
+            .into_iter()
+            .map(|(_, _, index)| self.entry(index))
+            .chain(containing)
+            .filter(|(name, _)| *name != word)
+            .map(|(name, id)| (edit_distance(word, name), name, id))
+            .collect::<Vec<_>>();
+        similar.sort_unstable();
+        similar.dedup();
+        similar.truncate(limit);
+        similar
+    }
+
+    fn entry(&self, index: u32) -> (&str, u64) {
+        let (name, id) = self.names[index as usize];
+        (self.text.get(name), id)
+    }
+
+    /// Returns the names that contain `word`, which must be longer than
+    /// [`MAX_GRAM`].
+    fn containing(&self, word: &str) -> Vec<(&str, u64)> {
+        let mut postings = Vec::with_capacity(word.len() - MAX_GRAM + 1);
+        for window in word.as_bytes().windows(MAX_GRAM) {
+            let Some(list) = self.grams.get(&gram(window)) else { return Vec::new() };
+            postings.push(list);
+        }
+        postings.sort_unstable_by_key(|list| list.len());
+        let (smallest, rest) = postings.split_first().expect("at least one gram");
+
+        // Containing every trigram of the word doesn't guarantee containing
+        // the word itself, so each candidate is still checked.
+        smallest
+            .iter()
+            .filter(|index| rest.iter().all(|list| list.binary_search(*index).is_ok()))
+            .map(|index| self.entry(*index))
+            .filter(|(name, _)| name.contains(word))
+            .collect()
+    }
+}
+
+/// Pads `bytes` to the length of a [`Gram`]. NUL never appears in a crate
+/// name, so padded grams can't collide with longer ones.
+fn gram(bytes: &[u8]) -> Gram {
+    let mut gram = [0; MAX_GRAM];
+    gram[..bytes.len()].copy_from_slice(bytes);
+    gram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(names: impl IntoIterator<Item = String>) -> NameIndex {
+        NameIndex::new(names.into_iter().zip(0..))
+    }
+
+    fn names<'a>(similar: &[(usize, &'a str, u64)]) -> Vec<&'a str> {
+        similar.iter().map(|(_, name, _)| *name).collect()
+    }
+
+    #[test]
+    fn similar_names_are_closest_first() {
+        let index = index(["serde", "serde_json", "tokio", "clap"].map(String::from));
+        let similar = index.similar("serdr", 2);
+        assert_eq!(names(&similar), ["serde", "serde_json"]);
+        assert_eq!(similar[0].0, 1);
+    }
+
+    #[test]
+    fn words_longer_than_names_have_no_similar_names() {
+        let index = index(["se", "es", "sese"].map(String::from));
+        assert!(index.similar(&"se".repeat(MAX_NAME_LEN), 10).is_empty());
+        assert!(!index.similar(&"se".repeat(MAX_NAME_LEN / 2), 10).is_empty());
+    }
+
+    #[test]
+    fn similar_compares_a_limited_number_of_names() {
+        // Every name shares both of the bigrams of `sesese`.
+        let index = index(
+            (0..MAX_SIMILAR_CANDIDATES * 3)
+                .map(|i| format!("se{i}es"))
+                .chain(["sesesa", "seses"].map(String::from)),
+        );
+        let similar = index.similar("sesese", 2);
+        assert_eq!(names(&similar), ["seses", "sesesa"]);
+        assert!(similar.iter().all(|(distance, _, _)| *distance == 1));
+    }
+}
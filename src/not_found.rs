@@ -0,0 +1,86 @@
+//! Responses for crates that don't exist, which suggest the crates with the
+//! closest names instead of a bare error.
+
+use askama::Template;
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::{
+    cache::{Cache, CachedCrate},
+    name_index::MAX_NAME_LEN,
+    schema::Crate,
+};
+
+/// The most crates suggested in place of a missing crate.
+const MAX_SUGGESTIONS: usize = 10;
+
+/// The body of the API's error responses.
+#[derive(Serialize, Debug)]
+pub(crate) struct ApiError {
+    pub error: String,
+    /// The names of the crates with the closest names, closest first, when a
+    /// crate wasn't found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestions: Option<Vec<String>>,
+}
+
+#[derive(Template, Debug)]
+#[template(path = "crate_not_found.html")]
+struct CrateNotFound {
+    name: String,
+    suggestions: Vec<CachedCrate>,
+}
+
+/// Returns the crates whose names are closest to `name`, closest first.
+/// Crates equally close are ordered by their downloads. Names longer than any
+/// crate's name get no suggestions.
+pub(crate) fn suggestions(cache: &Cache, name: &str) -> anyhow::Result<Vec<CachedCrate>> {
+    if name.len() > MAX_NAME_LEN {
+        return Ok(Vec::new());
+    }
+    let crates = cache.crates()?;
+    let names = cache.names()?;
+    let mut suggestions = names
+        .similar(&Crate::normalized_name(name), MAX_SUGGESTIONS * 2)
+        .into_iter()
+        .filter_map(|(distance, _, id)| Some((distance, crates.get(id)?)))
+        .filter(|(_, krate)| !krate.blocked())
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| b.1.downloads().cmp(&a.1.downloads()))
+    });
+    Ok(suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, krate)| krate)
+        .collect())
+}
+
+/// Returns the page shown when no crate is named `name`, which lists the
+/// crates with the closest names.
+pub(crate) fn crate_page(cache: &Cache, name: &str) -> anyhow::Result<Response> {
+    let page = CrateNotFound {
+        name: name.to_string(),
+        suggestions: suggestions(cache, name)?,
+    };
+    Ok((StatusCode::NOT_FOUND, Html(page.render()?)).into_response())
+}
+
+/// Returns the API's response when no crate is named `name`, which lists the
+/// crates with the closest names.
+pub(crate) fn crate_json(cache: &Cache, name: &str) -> anyhow::Result<Response> {
+    let suggestions = suggestions(cache, name)?
+        .iter()
+        .map(|krate| krate.name().to_string())
+        .collect();
+    let body = ApiError {
+        error: format!("no crate is named {name}"),
+        suggestions: Some(suggestions),
+    };
+    Ok((StatusCode::NOT_FOUND, Json(body)).into_response())
+}
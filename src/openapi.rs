@@ -0,0 +1,1056 @@
+use askama::Template;
+use axum::{response::Html, routing::get, Json};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::webserver::{AppError, AppState};
+
+/// Routes describing the JSON API: the OpenAPI document and a page listing
+/// its operations.
+pub(super) fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/openapi.json", get(|| async { Json(&*DOCUMENT) }))
+        .route("/docs", get(docs))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "api_docs.html")]
+struct ApiDocs {
+    operations: Vec<Operation>,
+}
+
+/// An operation described by the OpenAPI document.
+#[derive(Debug)]
+struct Operation {
+    method: String,
+    path: String,
+    summary: String,
+    description: String,
+    parameters: Vec<Parameter>,
+    /// The status codes of the operation's responses and their descriptions.
+    responses: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+struct Parameter {
+    name: String,
+    /// Where the parameter is given, such as `query` or `path`.
+    location: String,
+    required: bool,
+    description: String,
+}
+
+async fn docs() -> Result<Html<String>, AppError> {
+    let operations = operations(&DOCUMENT);
+    Ok(Html(ApiDocs { operations }.render()?))
+}
+
+/// Returns the operations described by `document`, with their paths
+/// including the server's prefix.
+fn operations(document: &Value) -> Vec<Operation> {
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    let prefix = document["servers"][0]["url"].as_str().unwrap_or_default();
+    let mut operations = Vec::new();
+    for (path, item) in document["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in item.as_object().into_iter().flatten() {
+            // Parameters shared by the path's operations are listed with
+            // each operation.
+            if method == "parameters" {
+                continue;
+            }
+            operations.push(Operation {
+                method: method.to_ascii_uppercase(),
+                path: format!("{prefix}{path}"),
+                summary: text(&operation["summary"]),
+                description: text(&operation["description"]),
+                parameters: [&item["parameters"], &operation["parameters"]]
+                    .into_iter()
+                    .filter_map(Value::as_array)
+                    .flatten()
+                    .map(|parameter| Parameter {
+                        name: text(&parameter["name"]),
+                        location: text(&parameter["in"]),
+                        required: parameter["required"].as_bool().unwrap_or(false),
+                        description: text(&parameter["description"]),
+                    })
+                    .collect(),
+                responses: operation["responses"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(status, response)| (status.clone(), text(&response["description"])))
+                    .collect(),
+            });
+        }
+    }
+    operations
+}
+
+/// The OpenAPI 3 description of the public `/api/v1` routes. Admin routes are
+/// omitted since they aren't meant for integrators.
+static DOCUMENT: Lazy<Value> = Lazy::new(|| {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "delve-rs",
+            "description": "Search and inspect Rust crates.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": {
+            "/search": {
+                "get": {
+                    "summary": "Search for crates",
+                    "parameters": [
+                        query_parameter(
+                            "q",
+                            "string",
+                            true,
+                            "The search query, using the search box's syntax. At most 512 bytes.",
+                        ),
+                        query_parameter(
+                            "hide_placeholders",
+                            "boolean",
+                            false,
+                            "Omits crates that only reserve their name.",
+                        ),
+                        query_parameter(
+                            "ranker",
+                            "string",
+                            false,
+                            "The ranker ordering the results, such as `popularity`.",
+                        ),
+                        limit_parameter(),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "The first `limit` results.",
+                            array(schema_ref("SearchResult")),
+                        ),
+                        "400": { "description": "The ranker is invalid." },
+                    },
+                },
+            },
+            "/search/pages": {
+                "get": {
+                    "summary": "Page through a search's results",
+                    "parameters": [
+                        query_parameter(
+                            "q",
+                            "string",
+                            true,
+                            "The search query, using the search box's syntax. At most 512 bytes.",
+                        ),
+                        query_parameter(
+                            "hide_placeholders",
+                            "boolean",
+                            false,
+                            "Omits crates that only reserve their name.",
+                        ),
+                        query_parameter(
+                            "ranker",
+                            "string",
+                            false,
+                            "The ranker ordering the results, such as `popularity`.",
+                        ),
+                        cursor_parameter(),
+                        limit_parameter(),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "A page of results. `partial` is true if the search took too long, \
+                             in which case they're the search index's matches in the index's \
+                             order, or none if the search index itself was too slow.",
+                            search_page_schema(),
+                        ),
+                        "400": { "description": "The ranker or cursor is invalid." },
+                    },
+                },
+            },
+            "/quick": {
+                "get": {
+                    "summary": "Complete a search",
+                    "description": "Returns the top 5 results with only the fields needed for \
+                                    completion popups. Scoring stops after 50ms, searches that \
+                                    haven't finished after 100ms return no items, and \
+                                    placeholders are omitted.",
+                    "parameters": [query_parameter(
+                        "q",
+                        "string",
+                        true,
+                        "The search query, using the search box's syntax. At most 512 bytes.",
+                    )],
+                    "responses": {
+                        "200": json_response(
+                            "The top results. `partial` is true if scoring the results ran out \
+                             of time, in which case they're the search index's matches in the \
+                             index's order, or if the search didn't finish in time, in which \
+                             case there are no items.",
+                            object(json!({
+                                "items": array(schema_ref("QuickResult")),
+                                "partial": boolean(),
+                            })),
+                        ),
+                    },
+                },
+            },
+            "/crates/{name}": {
+                "get": {
+                    "summary": "Get a crate's details",
+                    "parameters": [name_parameter()],
+                    "responses": {
+                        "200": json_response("The crate's details.", schema_ref("CrateInfo")),
+                        "404": json_response(
+                            "No crate has this name. The crates with the closest names are \
+                             suggested.",
+                            schema_ref("ApiError"),
+                        ),
+                    },
+                },
+            },
+            "/crates/{name}/versions/resolve": {
+                "get": {
+                    "summary": "Resolve a version requirement",
+                    "description": "Returns the newest version Cargo would choose for a \
+                                    requirement, with its features and dependencies. Yanked \
+                                    versions are never chosen.",
+                    "parameters": [
+                        name_parameter(),
+                        query_parameter(
+                            "req",
+                            "string",
+                            true,
+                            "A Cargo version requirement, such as `^1.2` or `1.2`.",
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("The chosen version.", schema_ref("VersionInfo")),
+                        "400": { "description": "The requirement is invalid." },
+                        "404": {
+                            "description": "No crate has this name, or no version matches.",
+                        },
+                    },
+                },
+            },
+            "/crates/{name}/depgraph": {
+                "get": {
+                    "summary": "Get a crate's dependency graph",
+                    "parameters": [
+                        name_parameter(),
+                        query_parameter(
+                            "depth",
+                            "integer",
+                            false,
+                            "How many levels of dependencies to include, up to 5.",
+                        ),
+                        query_parameter(
+                            "format",
+                            "string",
+                            false,
+                            "`json`, the default, or `dot` for Graphviz.",
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "The dependency graph.",
+                            schema_ref("DependencyGraph"),
+                        ),
+                        "400": { "description": "The format is unknown." },
+                        "404": { "description": "No crate has this name." },
+                    },
+                },
+            },
+            "/crates/{name}/dependents": {
+                "get": {
+                    "summary": "List the crates depending on a crate",
+                    "parameters": [name_parameter(), cursor_parameter(), limit_parameter()],
+                    "responses": {
+                        "200": json_response("A page of dependents.", page_schema("Dependent")),
+                        "404": json_response(
+                            "No crate has this name. The crates with the closest names are \
+                             suggested.",
+                            schema_ref("ApiError"),
+                        ),
+                    },
+                },
+            },
+            "/categories/{slug}/crates": {
+                "get": {
+                    "summary": "List the crates in a category and its subcategories",
+                    "parameters": [
+                        {
+                            "name": "slug",
+                            "in": "path",
+                            "required": true,
+                            "schema": { "type": "string" },
+                        },
+                        cursor_parameter(),
+                        limit_parameter(),
+                    ],
+                    "responses": {
+                        "200": json_response("A page of crates.", page_schema("CategoryCrate")),
+                        "404": { "description": "No category has this slug." },
+                    },
+                },
+            },
+            "/keywords/suggest": {
+                "get": {
+                    "summary": "Suggest keywords starting with a prefix",
+                    "parameters": [
+                        query_parameter("q", "string", true, "The prefix to complete."),
+                        query_parameter(
+                            "limit",
+                            "integer",
+                            false,
+                            "The most suggestions to return, up to 50.",
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("Matching keywords.", array(string())),
+                    },
+                },
+            },
+            "/categories/suggest": {
+                "get": {
+                    "summary": "Suggest categories matching a prefix",
+                    "parameters": [
+                        query_parameter("q", "string", true, "The prefix to complete."),
+                        query_parameter(
+                            "limit",
+                            "integer",
+                            false,
+                            "The most suggestions to return, up to 50.",
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response(
+                            "Matching categories.",
+                            array(schema_ref("CategorySuggestion")),
+                        ),
+                    },
+                },
+            },
+            "/audit": {
+                "post": {
+                    "summary": "Check locked packages for yanked versions and advisories",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": schema_ref("AuditRequest") },
+                        },
+                    },
+                    "responses": {
+                        "200": json_response(
+                            "The packages with issues.",
+                            array(schema_ref("AuditFinding")),
+                        ),
+                    },
+                },
+            },
+            "/recommendations": {
+                "post": {
+                    "summary": "Recommend crates used alongside a project's dependencies",
+                    "requestBody": {
+                        "required": true,
+                        "description": "The project's Cargo.toml or Cargo.lock.",
+                        "content": {
+                            "text/plain": { "schema": string() },
+                        },
+                    },
+                    "responses": {
+                        "200": json_response(
+                            "Crates often used alongside the dependencies, and replacements \
+                             for deprecated dependencies.",
+                            schema_ref("Recommendations"),
+                        ),
+                        "400": { "description": "The body isn't a Cargo.toml or Cargo.lock." },
+                    },
+                },
+            },
+            "/report": {
+                "post": {
+                    "summary": "Generate a dependency review of a list of crates",
+                    "requestBody": {
+                        "required": true,
+                        "description": "A Cargo.lock, or a JSON object listing the crates.",
+                        "content": {
+                            "application/json": { "schema": schema_ref("ReportRequest") },
+                            "text/plain": { "schema": string() },
+                        },
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "The review, as a printable HTML page or as JSON \
+                                            when requested with `Accept: application/json`.",
+                            "content": {
+                                "text/html": { "schema": string() },
+                                "application/json": { "schema": schema_ref("ReviewReport") },
+                            },
+                        },
+                        "400": { "description": "The body isn't a lockfile or a list of crates." },
+                        "413": { "description": "The body lists more than 1,000 crates." },
+                        "429": {
+                            "description": "Too many reviews are being generated, retry after \
+                                            `Retry-After` seconds.",
+                        },
+                    },
+                },
+            },
+            "/watchlists": {
+                "post": {
+                    "summary": "Create a watchlist",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": schema_ref("Watchlist") },
+                        },
+                    },
+                    "responses": {
+                        "201": json_response(
+                            "The watchlist's random id, which is needed to view, change, or \
+                             delete it.",
+                            object(json!({ "id": string() })),
+                        ),
+                        "422": {
+                            "description": "The name is empty or longer than 100 characters, \
+                                            or more than 200 crates are listed.",
+                        },
+                        "429": {
+                            "description": "100 watchlists have already been created this \
+                                            hour. Retry after the number of seconds in the \
+                                            `Retry-After` header.",
+                        },
+                    },
+                },
+            },
+            "/watchlists/{id}": {
+                "parameters": [{
+                    "name": "id",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }],
+                "get": {
+                    "summary": "Get a watchlist",
+                    "responses": {
+                        "200": json_response("The watchlist.", schema_ref("Watchlist")),
+                        "404": { "description": "No watchlist has this id." },
+                    },
+                },
+                "put": {
+                    "summary": "Replace a watchlist's name and crates",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": { "schema": schema_ref("Watchlist") },
+                        },
+                    },
+                    "responses": {
+                        "204": { "description": "The watchlist was replaced." },
+                        "404": { "description": "No watchlist has this id." },
+                        "422": {
+                            "description": "The name is empty or longer than 100 characters, \
+                                            or more than 200 crates are listed.",
+                        },
+                    },
+                },
+                "delete": {
+                    "summary": "Delete a watchlist",
+                    "responses": {
+                        "204": { "description": "The watchlist was deleted." },
+                        "404": { "description": "No watchlist has this id." },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "QuickResult": object(json!({
+                    "name": string(),
+                    "description": string(),
+                    "version": nullable(string()),
+                })),
+                "SearchResult": object(json!({
+                    "name": string(),
+                    "description": string(),
+                    "downloads": integer(),
+                    "recent_downloads": integer(),
+                    "confidence": number(),
+                    "popularity": number(),
+                    "likely_placeholder": boolean(),
+                    "unmaintained": boolean(),
+                    "wasm": boolean(),
+                    "proc_macro": boolean(),
+                    "build_script": boolean(),
+                    "unsafe_items": nullable(integer()),
+                    "forbids_unsafe": boolean(),
+                    "reviews": integer(),
+                    "audited": boolean(),
+                    "registry": string(),
+                    "language": nullable(string()),
+                    "transitive_dependencies": nullable(integer()),
+                    "dependency_depth": nullable(integer()),
+                    "first_published": nullable(string()),
+                    "snippet": nullable(string()),
+                    "keywords": array(string()),
+                    "similar": array(string()),
+                    "successors": array(string()),
+                })),
+                "CrateInfo": object(json!({
+                    "name": string(),
+                    "description": string(),
+                    "homepage": string(),
+                    "documentation": string(),
+                    "repository": string(),
+                    "created_at": string(),
+                    "updated_at": string(),
+                    "downloads": integer(),
+                    "recent_downloads": integer(),
+                    "yearly_downloads": integer(),
+                    "recorded_downloads": integer(),
+                    "latest_version": nullable(string()),
+                    "license": nullable(string()),
+                    "install": nullable(string()),
+                    "blocked": nullable(object(json!({
+                        "reason": string(),
+                        "advisory": nullable(string()),
+                        "blocked_at": string(),
+                    }))),
+                    "links": array(object(json!({
+                        "kind": { "type": "string", "enum": ["download", "docs", "source"] },
+                        "url": string(),
+                    }))),
+                    "rust_version": nullable(string()),
+                    "deprecation": nullable(object(json!({
+                        "replacements": array(string()),
+                        "note": string(),
+                        "source": { "type": "string", "enum": ["Manual", "Heuristic"] },
+                    }))),
+                    "likely_placeholder": boolean(),
+                    "unmaintained": boolean(),
+                    "successors": array(string()),
+                    "wasm": boolean(),
+                    "platforms": object(json!({
+                        "windows": boolean(),
+                        "macos": boolean(),
+                        "linux": boolean(),
+                        "embedded": boolean(),
+                    })),
+                    "proc_macro": boolean(),
+                    "build_script": boolean(),
+                    "dependency_proc_macros": integer(),
+                    "dependency_build_scripts": integer(),
+                    "unsafe_usage": nullable(schema_ref("UnsafeUsage")),
+                    "reviews": array(object(json!({
+                        "crate_name": string(),
+                        "version": string(),
+                        "kind": string(),
+                        "source": string(),
+                        "reviewer": string(),
+                        "verdict": string(),
+                        "favorable": boolean(),
+                        "notes": nullable(string()),
+                    }))),
+                    "audited_by": array(string()),
+                    "language": nullable(string()),
+                    "name_collisions": array(object(json!({
+                        "name": string(),
+                        "registry": string(),
+                        "first_published": nullable(string()),
+                    }))),
+                    "duplicate_of": nullable(object(json!({
+                        "name": string(),
+                        "similarity": number(),
+                    }))),
+                    "versions": array(object(json!({
+                        "version": string(),
+                        "created_at": string(),
+                        "yanked": boolean(),
+                        "rust_version": nullable(string()),
+                    }))),
+                    "dependencies": array(schema_ref("DependencyGroup")),
+                    "download_split": array(object(json!({
+                        "versions": string(),
+                        "downloads": integer(),
+                        "share": number(),
+                    }))),
+                    "advisories": array(schema_ref("AdvisorySummary")),
+                    "ownership_changes": array(object(json!({
+                        "id": integer(),
+                        "name": string(),
+                        "owner": string(),
+                        "team": boolean(),
+                        "added": boolean(),
+                        "at": string(),
+                    }))),
+                    "often_used_with": array(object(json!({
+                        "name": string(),
+                        "description": string(),
+                        "shared": integer(),
+                    }))),
+                })),
+                "VersionInfo": object(json!({
+                    "name": string(),
+                    "version": string(),
+                    "latest": boolean(),
+                    "yanked": boolean(),
+                    "license": string(),
+                    "checksum": string(),
+                    "created_at": string(),
+                    "downloads": integer(),
+                    "crate_size": nullable(integer()),
+                    "rust_version": nullable(string()),
+                    "features": {
+                        "type": "object",
+                        "additionalProperties": array(string()),
+                    },
+                    "dependencies": array(schema_ref("DependencyGroup")),
+                })),
+                "DependencyGroup": object(json!({
+                    "kind": string(),
+                    "dependencies": array(schema_ref("DependencyInfo")),
+                })),
+                "DependencyInfo": object(json!({
+                    "name": string(),
+                    "req": string(),
+                    "optional": boolean(),
+                    "default_features": boolean(),
+                    "features": array(string()),
+                    "target": nullable(string()),
+                    "rename": nullable(string()),
+                    "latest": nullable(string()),
+                    "stale": boolean(),
+                })),
+                "UnsafeUsage": object(json!({
+                    "version": string(),
+                    "used": schema_ref("UnsafeCounts"),
+                    "unused": schema_ref("UnsafeCounts"),
+                    "forbids_unsafe": boolean(),
+                })),
+                "UnsafeCounts": object(json!({
+                    "functions": integer(),
+                    "exprs": integer(),
+                    "impls": integer(),
+                    "traits": integer(),
+                    "methods": integer(),
+                })),
+                "AdvisorySummary": object(json!({
+                    "id": string(),
+                    "title": string(),
+                    "date": string(),
+                    "url": nullable(string()),
+                    "informational": nullable(string()),
+                })),
+                "DependencyGraph": object(json!({
+                    "root": string(),
+                    "nodes": array(object(json!({
+                        "name": string(),
+                        "version": nullable(string()),
+                        "depth": integer(),
+                    }))),
+                    "edges": array(object(json!({
+                        "from": string(),
+                        "to": string(),
+                        "req": string(),
+                        "kind": string(),
+                        "target": nullable(string()),
+                    }))),
+                })),
+                "Dependent": object(json!({
+                    "name": string(),
+                    "versions": integer(),
+                })),
+                "CategoryCrate": object(json!({
+                    "name": string(),
+                    "description": string(),
+                    "downloads": integer(),
+                })),
+                "CategorySuggestion": object(json!({
+                    "slug": string(),
+                    "category": string(),
+                })),
+                "AuditRequest": object(json!({
+                    "packages": array(schema_ref("LockedPackage")),
+                })),
+                "LockedPackage": object(json!({
+                    "name": string(),
+                    "version": string(),
+                })),
+                "AuditFinding": object(json!({
+                    "name": string(),
+                    "version": string(),
+                    "yanked": boolean(),
+                    "advisories": array(schema_ref("AdvisorySummary")),
+                })),
+                "Recommendations": object(json!({
+                    "dependencies": array(string()),
+                    "unknown": array(string()),
+                    "complementary": array(object(json!({
+                        "name": string(),
+                        "description": string(),
+                        "downloads": integer(),
+                        "used_with": array(string()),
+                        "cooccurrences": integer(),
+                    }))),
+                    "alternatives": array(object(json!({
+                        "name": string(),
+                        "replacements": array(string()),
+                        "note": string(),
+                    }))),
+                })),
+                "ApiError": optional(
+                    object(json!({
+                        "error": string(),
+                        "suggestions": array(string()),
+                    })),
+                    "suggestions",
+                ),
+                "ReportRequest": object(json!({
+                    "crates": array(object(json!({
+                        "name": string(),
+                        "version": nullable(string()),
+                    }))),
+                })),
+                "ReviewReport": object(json!({
+                    "generated": string(),
+                    "crates": array(object(json!({
+                        "name": string(),
+                        "version": nullable(string()),
+                        "latest_version": nullable(string()),
+                        "license": nullable(string()),
+                        "yanked": boolean(),
+                        "advisories": array(schema_ref("AdvisorySummary")),
+                        "maintenance": number(),
+                        "unmaintained": boolean(),
+                        "deprecated": boolean(),
+                        "owners": array(string()),
+                        "recent_downloads": integer(),
+                        "previous_downloads": integer(),
+                    }))),
+                    "licenses": {
+                        "type": "object",
+                        "additionalProperties": integer(),
+                    },
+                    "unknown": array(string()),
+                })),
+                "Watchlist": object(json!({
+                    "name": string(),
+                    "crates": array(string()),
+                })),
+            },
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+            },
+        },
+        // Keys are optional unless the server is configured to require them.
+        "security": [{}, { "bearer": [] }, { "apiKey": [] }],
+    })
+});
+
+fn query_parameter(name: &str, kind: &str, required: bool, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "description": description,
+        "schema": { "type": kind },
+    })
+}
+
+fn name_parameter() -> Value {
+    json!({
+        "name": "name",
+        "in": "path",
+        "required": true,
+        "description": "The crate's name. Hyphens and underscores are interchangeable.",
+        "schema": { "type": "string" },
+    })
+}
+
+fn cursor_parameter() -> Value {
+    query_parameter(
+        "cursor",
+        "string",
+        false,
+        "The `next_cursor` of the previous page.",
+    )
+}
+
+fn limit_parameter() -> Value {
+    query_parameter(
+        "limit",
+        "integer",
+        false,
+        "The most items to return, up to 100.",
+    )
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+fn page_schema(item: &str) -> Value {
+    object(json!({
+        "items": array(schema_ref(item)),
+        "next_cursor": nullable(string()),
+        "stale": boolean(),
+    }))
+}
+
+fn search_page_schema() -> Value {
+    object(json!({
+        "items": array(schema_ref("SearchResult")),
+        "next_cursor": nullable(string()),
+        "stale": boolean(),
+        "partial": boolean(),
+    }))
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+/// An object schema whose properties are all required. Optional values are
+/// represented as nullable properties, matching how the API serializes them.
+fn object(properties: Value) -> Value {
+    let required = properties
+        .as_object()
+        .map(|properties| properties.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Marks the `property` of an [`object`] schema as optional, for properties
+/// left out rather than serialized as null.
+fn optional(mut schema: Value, property: &str) -> Value {
+    if let Some(required) = schema["required"].as_array_mut() {
+        required.retain(|name| name != property);
+    }
+    schema
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn nullable(mut schema: Value) -> Value {
+    schema["nullable"] = Value::Bool(true);
+    schema
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn number() -> Value {
+    json!({ "type": "number" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    /// Returns the paths routed with `.route(` in `source`, prefixed with
+    /// `prefix` and written the way OpenAPI writes them.
+    fn route_paths(source: &str, prefix: &str) -> Vec<String> {
+        source
+            .split(".route(")
+            .skip(1)
+            .filter_map(|rest| rest.trim_start().strip_prefix('"')?.split_once('"'))
+            .map(|(path, _)| {
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(parameter) => format!("{{{parameter}}}"),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                format!("{prefix}{}", path.trim_end_matches('/'))
+            })
+            .collect()
+    }
+
+    /// A field of a struct as serialized, and whether it can be null or left
+    /// out.
+    #[derive(Debug)]
+    struct Field {
+        name: String,
+        nullable: bool,
+        optional: bool,
+    }
+
+    /// Returns the serialized fields of the struct named `name` in `source`.
+    fn struct_fields(source: &str, name: &str) -> Vec<Field> {
+        let start = [
+            format!("pub struct {name} {{"),
+            format!("pub(crate) struct {name} {{"),
+            format!("\nstruct {name} {{"),
+        ]
+        .iter()
+        .find_map(|declaration| source.find(declaration.as_str()))
+        .unwrap_or_else(|| panic!("struct {name} not found"));
+        let body = &source[start..];
+        let body = &body[body.find('{').expect("struct body") + 1..body.find("\n}").expect("end")];
+
+        let mut fields = Vec::new();
+        let mut attributes = String::new();
+        for line in body.lines().map(str::trim) {
+            if line.starts_with("#[") {
+                attributes.push_str(line);
+                continue;
+            }
+            let Some((field, kind)) = line
+                .trim_start_matches("pub(crate) ")
+                .trim_start_matches("pub ")
+                .split_once(": ")
+            else {
+                continue;
+            };
+            if !line.starts_with("//") && !attributes.contains("skip)") {
+                let optional = attributes.contains("skip_serializing_if");
+                fields.push(Field {
+                    name: field.to_string(),
+                    nullable: kind.starts_with("Option<") && !optional,
+                    optional,
+                });
+            }
+            attributes.clear();
+        }
+        fields
+    }
+
+    #[test]
+    fn schemas_match_their_structs() {
+        let sources = [
+            ("api.rs", include_str!("api.rs")),
+            ("schema.rs", include_str!("schema.rs")),
+            ("version_info.rs", include_str!("version_info.rs")),
+            ("dependency_graph.rs", include_str!("dependency_graph.rs")),
+            ("recommendations.rs", include_str!("recommendations.rs")),
+            ("review_report.rs", include_str!("review_report.rs")),
+            ("not_found.rs", include_str!("not_found.rs")),
+        ];
+        let structs = [
+            ("QuickResult", "api.rs"),
+            ("SearchResult", "api.rs"),
+            ("CrateInfo", "api.rs"),
+            ("VersionInfo", "version_info.rs"),
+            ("DependencyGroup", "api.rs"),
+            ("DependencyInfo", "api.rs"),
+            ("UnsafeUsage", "schema.rs"),
+            ("UnsafeCounts", "schema.rs"),
+            ("AdvisorySummary", "api.rs"),
+            ("DependencyGraph", "dependency_graph.rs"),
+            ("Dependent", "api.rs"),
+            ("CategoryCrate", "api.rs"),
+            ("CategorySuggestion", "api.rs"),
+            ("AuditRequest", "api.rs"),
+            ("LockedPackage", "api.rs"),
+            ("AuditFinding", "api.rs"),
+            ("Recommendations", "recommendations.rs"),
+            ("ApiError", "not_found.rs"),
+            ("ReportRequest", "review_report.rs"),
+            ("ReviewReport", "review_report.rs"),
+            ("Watchlist", "schema.rs"),
+        ];
+        let schemas = DOCUMENT["components"]["schemas"]
+            .as_object()
+            .expect("schemas is an object");
+        let documented = schemas.keys().map(String::as_str).collect::<BTreeSet<_>>();
+        let checked = structs
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(documented, checked, "every schema is checked");
+
+        let mut mismatches = Vec::new();
+        for (name, file) in structs {
+            let source = sources.iter().find(|(f, _)| *f == file).expect("source").1;
+            let schema = &schemas[name];
+            let properties = schema["properties"].as_object().expect("an object schema");
+            let required = schema["required"].as_array().expect("required properties");
+            let fields = struct_fields(source, name);
+            for field in &fields {
+                let Some(property) = properties.get(&field.name) else {
+                    mismatches.push(format!("{name}.{} is undocumented", field.name));
+                    continue;
+                };
+                if property["nullable"].as_bool().unwrap_or(false) != field.nullable {
+                    mismatches.push(format!("{name}.{} nullable is wrong", field.name));
+                }
+                if required.contains(&Value::from(field.name.as_str())) == field.optional {
+                    mismatches.push(format!("{name}.{} required is wrong", field.name));
+                }
+            }
+            for property in properties.keys() {
+                if !fields.iter().any(|field| field.name == *property) {
+                    mismatches.push(format!("{name}.{property} isn't serialized"));
+                }
+            }
+        }
+        assert!(mismatches.is_empty(), "{mismatches:#?}");
+    }
+
+    #[test]
+    fn operations_are_listed() {
+        let operations = operations(&DOCUMENT);
+        let search = operations
+            .iter()
+            .find(|operation| operation.path == "/api/v1/search")
+            .expect("search is documented");
+        assert_eq!(search.method, "GET");
+        assert_eq!(search.summary, "Search for crates");
+        let q = &search.parameters[0];
+        assert_eq!(
+            (q.name.as_str(), q.location.as_str(), q.required),
+            ("q", "query", true)
+        );
+        assert!(search.responses.iter().any(|(status, _)| status == "200"));
+
+        let watchlist = operations
+            .iter()
+            .filter(|operation| operation.path == "/api/v1/watchlists/{id}")
+            .map(|operation| operation.method.as_str())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(watchlist, BTreeSet::from(["DELETE", "GET", "PUT"]));
+    }
+
+    #[test]
+    fn every_api_route_is_documented() {
+        let mut routes = route_paths(include_str!("api.rs"), "");
+        routes.extend(route_paths(include_str!("watchlists.rs"), "/watchlists"));
+        let paths = DOCUMENT["paths"].as_object().expect("paths is an object");
+
+        let undocumented = routes
+            .iter()
+            .filter(|route| !paths.contains_key(route.as_str()))
+            .collect::<Vec<_>>();
+        assert!(
+            undocumented.is_empty(),
+            "undocumented routes: {undocumented:?}"
+        );
+        let unrouted = paths
+            .keys()
+            .filter(|path| !routes.contains(path))
+            .collect::<Vec<_>>();
+        assert!(
+            unrouted.is_empty(),
+            "documented paths without routes: {unrouted:?}"
+        );
+    }
+}
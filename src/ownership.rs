@@ -0,0 +1,188 @@
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::{
+    api::advisories_for,
+    cache::Cache,
+    name_collisions,
+    schema::{
+        Owner, OwnerId, OwnershipEvent, OwnershipEventsByCrate, ReadmeDuplicatesByOriginal,
+        YankEventsByCrate,
+    },
+};
+
+/// The number of events shown on the ownership page and in its feed.
+const RECENT_EVENTS: u32 = 100;
+
+/// The number of alerts in an owner's feed.
+const OWNER_ALERTS: usize = 100;
+
+#[derive(Serialize, Debug)]
+pub(crate) struct OwnershipEntry {
+    pub id: u64,
+    pub name: String,
+    /// The owner's GitHub login, or their crates.io id if it isn't known.
+    pub owner: String,
+    pub team: bool,
+    pub added: bool,
+    pub at: String,
+}
+
+impl OwnershipEntry {
+    fn new(id: u64, name: String, event: OwnershipEvent) -> Self {
+        let (owner_id, team) = match event.owner {
+            OwnerId::User(id) => (id, false),
+            OwnerId::Team(id) => (id, true),
+        };
+        Self {
+            id,
+            name,
+            owner: event.login.unwrap_or_else(|| format!("#{owner_id}")),
+            team,
+            added: event.added,
+            at: event.at,
+        }
+    }
+}
+
+/// Returns the most recent ownership changes across all crates, newest first.
+pub(crate) fn recent(db: &Database, cache: &Cache) -> anyhow::Result<Vec<OwnershipEntry>> {
+    let crates = cache.crates()?;
+    let mut entries = Vec::new();
+    for event in OwnershipEvent::all(db)
+        .descending()
+        .limit(RECENT_EVENTS)
+        .query()?
+    {
+        let Some(krate) = crates.get(event.contents.crate_id) else { continue };
+        entries.push(OwnershipEntry::new(
+            event.header.id,
+            krate.name().to_string(),
+            event.contents,
+        ));
+    }
+    Ok(entries)
+}
+
+/// Returns every ownership change of the crate `crate_id`, newest first.
+pub(crate) fn history(
+    db: &Database,
+    crate_id: u64,
+    name: &str,
+) -> anyhow::Result<Vec<OwnershipEntry>> {
+    let mut entries = Vec::new();
+    for mapping in OwnershipEventsByCrate::entries(db)
+        .with_key(&crate_id)
+        .query()?
+    {
+        entries.push(OwnershipEntry::new(
+            mapping.source.id.deserialize::<u64>()?,
+            name.to_string(),
+            mapping.value,
+        ));
+    }
+    entries.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(entries)
+}
+
+/// Advisories, yanks, and suspected impersonations affecting the crates of a
+/// single owner, so that maintainers can monitor their own crates.
+///
+/// Impersonations are approximated by crates whose READMEs appear to be
+/// copied from the owner's, and by crates in other registries that Cargo
+/// treats as having the same name. A typosquat with a README of its own
+/// isn't reported, since similar names alone aren't checked.
+#[derive(Debug)]
+pub(crate) struct OwnerAlerts {
+    /// The owner's login, as capitalized in the dump.
+    pub login: String,
+    /// The alerts, newest first.
+    pub alerts: Vec<OwnerAlert>,
+}
+
+#[derive(Debug)]
+pub(crate) struct OwnerAlert {
+    pub title: String,
+    pub link: String,
+    pub at: String,
+    /// A unique identifier of the alert, so that feed readers don't show it
+    /// twice.
+    pub guid: String,
+}
+
+impl OwnerAlerts {
+    /// Loads the alerts of the owner whose login is `login`, ignoring case,
+    /// returning None if they don't own any crates.
+    pub fn load(db: &Database, cache: &Cache, login: &str) -> anyhow::Result<Option<Self>> {
+        let Some(owner) = Owner::get(&login.to_lowercase(), db)? else { return Ok(None) };
+        let owner = owner.contents;
+        let crates = cache.crates()?;
+
+        let mut alerts = Vec::new();
+        for &crate_id in &owner.crates {
+            let Some(krate) = crates.get(crate_id) else { continue };
+            let name = krate.name();
+
+            for (advisory_id, advisory) in advisories_for(db, name)? {
+                alerts.push(OwnerAlert {
+                    title: format!("{advisory_id} affects {name}: {}", advisory.title),
+                    link: advisory.url.unwrap_or_else(|| format!("/crates/{name}")),
+                    at: advisory.date,
+                    guid: format!("advisory-{advisory_id}-{crate_id}"),
+                });
+            }
+            for mapping in YankEventsByCrate::entries(db).with_key(&crate_id).query()? {
+                let action = if mapping.value.yanked {
+                    "yanked"
+                } else {
+                    "unyanked"
+                };
+                alerts.push(OwnerAlert {
+                    title: format!("{name} {} was {action}", mapping.value.version),
+                    link: format!("/crates/{name}"),
+                    at: mapping.value.at,
+                    guid: format!("yank-{}", mapping.source.id.deserialize::<u64>()?),
+                });
+            }
+            for mapping in ReadmeDuplicatesByOriginal::entries(db)
+                .with_key(&crate_id)
+                .query()?
+            {
+                let copy_id = mapping.source.id.deserialize::<u64>()?;
+                let Some(copy) = crates.get(copy_id) else { continue };
+                let Some(first_published) = copy.first_published() else { continue };
+                alerts.push(OwnerAlert {
+                    title: format!("{}'s README appears to be copied from {name}", copy.name()),
+                    link: format!("/crates/{}", copy.name()),
+                    at: first_published.to_string(),
+                    guid: format!("readme-copy-{copy_id}"),
+                });
+            }
+            for other in name_collisions::for_crate(cache, crate_id)? {
+                let Some(first_published) = other.first_published else { continue };
+                alerts.push(OwnerAlert {
+                    title: format!(
+                        "{} from {} has the same name as {name} to Cargo",
+                        other.name, other.registry
+                    ),
+                    link: format!("/crates/{name}"),
+                    guid: format!("collision-{crate_id}-{}-{}", other.registry, other.name),
+                    at: first_published,
+                });
+            }
+        }
+
+        // Every timestamp begins with an ISO 8601 date, so sorting them as
+        // strings orders them chronologically.
+        alerts.sort_by(|a, b| b.at.cmp(&a.at));
+        alerts.truncate(OWNER_ALERTS);
+
+        Ok(Some(Self {
+            login: owner.login,
+            alerts,
+        }))
+    }
+}
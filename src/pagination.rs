@@ -0,0 +1,203 @@
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The number of items in a page when the request doesn't specify a limit.
+const DEFAULT_LIMIT: usize = 20;
+
+/// The most items a single page can contain.
+const MAX_LIMIT: usize = 100;
+
+/// A position within a paginated list, handed to clients as an opaque string.
+///
+/// Lists are paged by the key of the last item returned rather than by
+/// offset, so crates added or removed by an import don't shift later pages.
+/// The cursor also records the generation of the crate cache the page was
+/// built from, so the next page can tell whether the data has changed since.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Cursor<K> {
+    pub generation: u64,
+    pub last: K,
+}
+
+impl<K> Cursor<K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursors always serialize"))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+impl Cursor<u64> {
+    /// Returns the first key after the cursor, responding with 400 Bad
+    /// Request if the cursor is at the largest key.
+    pub fn next_key(&self) -> Result<u64, StatusCode> {
+        self.last.checked_add(1).ok_or(StatusCode::BAD_REQUEST)
+    }
+}
+
+/// The query parameters accepted by every paginated list.
+#[derive(Deserialize, Debug)]
+pub(crate) struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    /// Decodes the requested cursor, responding with 400 Bad Request if it
+    /// wasn't produced by this list.
+    pub fn cursor<K>(&self) -> Result<Option<Cursor<K>>, StatusCode>
+    where
+        K: Serialize + DeserializeOwned,
+    {
+        self.cursor
+            .as_deref()
+            .map(|cursor| Cursor::decode(cursor).ok_or(StatusCode::BAD_REQUEST))
+            .transpose()
+    }
+}
+
+/// One page of a list.
+#[derive(Serialize, Debug)]
+pub(crate) struct Page<T> {
+    pub items: Vec<T>,
+    /// Passed as `cursor` to request the next page. Omitted on the last page.
+    pub next_cursor: Option<String>,
+    /// True if an import or cache refresh has changed the data since the
+    /// previous page was returned.
+    pub stale: bool,
+}
+
+impl<T> Page<T> {
+    /// Builds a page from the keyed items following the cursor, in key
+    /// order. At most `limit` items are included, and a cursor is only
+    /// returned if more items remain.
+    pub fn new<K>(
+        items: impl IntoIterator<Item = (K, T)>,
+        limit: usize,
+        generation: u64,
+        cursor: Option<&Cursor<K>>,
+    ) -> Self
+    where
+        K: Serialize + DeserializeOwned,
+    {
+        let mut items = items.into_iter();
+        let mut page = Vec::with_capacity(limit);
+        let mut last = None;
+        for (key, item) in items.by_ref().take(limit) {
+            page.push(item);
+            last = Some(key);
+        }
+        let next_cursor = last
+            .filter(|_| items.next().is_some())
+            .map(|last| Cursor { generation, last }.encode());
+
+        Self {
+            items: page,
+            next_cursor,
+            stale: cursor.map_or(false, |cursor| cursor.generation != generation),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            stale: self.stale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor {
+            generation: 3,
+            last: 42_u64,
+        };
+        let decoded = Cursor::<u64>::decode(&cursor.encode()).expect("valid cursor");
+        assert_eq!(decoded.generation, 3);
+        assert_eq!(decoded.last, 42);
+    }
+
+    #[test]
+    fn tampered_cursors_are_rejected() {
+        let encoded = Cursor {
+            generation: 3,
+            last: 42_u64,
+        }
+        .encode();
+        assert!(Cursor::<u64>::decode(&encoded[1..]).is_none());
+        assert!(Cursor::<u64>::decode(&format!("{encoded}!")).is_none());
+        assert!(Cursor::<u64>::decode("").is_none());
+        // Valid JSON that isn't a cursor of this list's keys.
+        let other = Cursor {
+            generation: 3,
+            last: String::from("serde"),
+        }
+        .encode();
+        assert!(Cursor::<u64>::decode(&other).is_none());
+
+        let query = PageQuery {
+            cursor: Some(String::from("not a cursor")),
+            limit: None,
+        };
+        assert_eq!(query.cursor::<u64>().unwrap_err(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn the_last_key_has_no_next_key() {
+        let cursor = Cursor {
+            generation: 0,
+            last: 41_u64,
+        };
+        assert_eq!(cursor.next_key(), Ok(42));
+        let cursor = Cursor {
+            generation: 0,
+            last: u64::MAX,
+        };
+        assert_eq!(cursor.next_key(), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        let limit = |limit| PageQuery {
+            cursor: None,
+            limit,
+        };
+        assert_eq!(limit(None).limit(), DEFAULT_LIMIT);
+        assert_eq!(limit(Some(0)).limit(), 1);
+        assert_eq!(limit(Some(1_000)).limit(), MAX_LIMIT);
+    }
+
+    #[test]
+    fn pages_continue_after_their_last_key() {
+        let items = (1..=5_u64).map(|key| (key, key * 10));
+        let page = Page::new(items, 2, 7, None);
+        assert_eq!(page.items, [10, 20]);
+        assert!(!page.stale);
+        let cursor = Cursor::<u64>::decode(page.next_cursor.as_deref().expect("more items"))
+            .expect("valid cursor");
+        assert_eq!(cursor.last, 2);
+        assert_eq!(cursor.generation, 7);
+
+        let rest = (3..=5_u64).map(|key| (key, key * 10));
+        let last = Page::new(rest, 3, 8, Some(&cursor));
+        assert_eq!(last.items, [30, 40, 50]);
+        assert!(last.next_cursor.is_none());
+        assert!(last.stale);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::schema::CrateInfo;
+
+/// Phrases found in the descriptions and READMEs of crates that were
+/// published only to reserve a name.
+const RESERVATION_PHRASES: &[&str] = &[
+    "this crate is a placeholder",
+    "placeholder crate",
+    "placeholder for a future",
+    "name is reserved",
+    "reserved for future use",
+    "reserving this name",
+    "reserve this name",
+    "reserve the name",
+    "name squat",
+    "namesquat",
+    "if you would like to use this name",
+    "if you want this name",
+];
+
+/// Returns true if `description` or `readme` contains a notice that the crate
+/// only exists to reserve its name.
+pub fn has_reservation_notice(description: &str, readme: &str) -> bool {
+    [description, readme].iter().any(|text| {
+        let text = text.to_ascii_lowercase();
+        RESERVATION_PHRASES
+            .iter()
+            .any(|phrase| text.contains(phrase))
+    })
+}
+
+/// The number of weak signals that must be present for a crate without a
+/// reservation notice to be considered a placeholder.
+const MINIMUM_SIGNALS: usize = 3;
+
+/// Returns true if the crate described by `info` appears to be a placeholder
+/// rather than a usable library.
+///
+/// `version_count` is the total number of published versions, and
+/// `latest_version` is the newest non-yanked version, if any.
+pub fn is_likely_placeholder(
+    info: &CrateInfo,
+    version_count: usize,
+    latest_version: Option<&semver::Version>,
+) -> bool {
+    if info.reservation_notice {
+        return true;
+    }
+
+    let signals = [
+        info.downloads == 0,
+        info.description.trim().is_empty(),
+        info.repository.trim().is_empty(),
+        version_count <= 1
+            && latest_version.map_or(true, |version| version.major == 0 && version.minor == 0),
+    ];
+    signals.into_iter().filter(|signal| *signal).count() >= MINIMUM_SIGNALS
+}
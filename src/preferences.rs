@@ -0,0 +1,202 @@
+//! Visitors' search preferences, which are kept in a signed cookie instead of
+//! a server-side session.
+//!
+//! The theme is the exception: it's kept in the unsigned `theme` cookie that
+//! the theme toggle also sets, since it's applied by a script before the page
+//! is painted.
+//!
+//! The settings form carries a token that must match the visitor's signed
+//! `csrf` cookie, so that other sites can't submit it on a visitor's behalf.
+
+use askama::Template;
+use axum::{
+    http::{
+        header::{LOCATION, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{Html, IntoResponse, Response},
+    Extension, Form,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    i18n, ranking,
+    signed_cookies::{self, SignedCookies},
+    webserver::AppError,
+};
+
+/// The cookie storing a visitor's [`Preferences`].
+const PREFERENCES_COOKIE: &str = "prefs";
+
+/// The cookie storing the visitor's theme, which `theme.js` reads.
+const THEME_COOKIE: &str = "theme";
+
+/// The cookie storing the token the settings form must be submitted with.
+const CSRF_COOKIE: &str = "csrf";
+
+/// How long preferences are kept after they were last saved.
+const PREFERENCES_MAX_AGE: u32 = 60 * 60 * 24 * 365;
+
+/// The page sizes visitors can choose from.
+const PAGE_SIZES: &[usize] = &[10, 20, 50, 100];
+
+/// The themes visitors can choose from, besides following the system's color
+/// scheme.
+const THEMES: &[&str] = &["light", "dark"];
+
+/// How a visitor prefers searches to be shown when the search doesn't say.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(default)]
+pub(crate) struct Preferences {
+    /// The number of results on each page of search results, or None to show
+    /// every result on one page.
+    pub per_page: Option<usize>,
+    /// The name of the ranker that orders results when the search doesn't
+    /// request one.
+    pub sort: Option<String>,
+    /// Hides placeholder crates from searches that weren't made with the
+    /// search form's checkbox.
+    pub hide_placeholders: bool,
+}
+
+impl Preferences {
+    /// Returns the preferences of the request's visitor. A missing or
+    /// tampered cookie is the default preferences.
+    pub fn read(cookies: &SignedCookies, headers: &HeaderMap) -> Self {
+        cookies
+            .read(headers, PREFERENCES_COOKIE)
+            .and_then(|payload| serde_urlencoded::from_str::<Self>(payload).ok())
+            .map(Self::validated)
+            .unwrap_or_default()
+    }
+
+    /// Returns the `Set-Cookie` header value that stores these preferences.
+    fn cookie(&self, cookies: &SignedCookies) -> Option<HeaderValue> {
+        let payload = serde_urlencoded::to_string(self).ok()?;
+        cookies.set(PREFERENCES_COOKIE, &payload, PREFERENCES_MAX_AGE)
+    }
+
+    /// Drops the preferences that aren't among the choices, such as a ranker
+    /// that has since been removed.
+    fn validated(mut self) -> Self {
+        self.per_page = self.per_page.filter(|size| PAGE_SIZES.contains(size));
+        self.sort = self.sort.filter(|sort| ranking::by_name(sort).is_some());
+        self
+    }
+}
+
+#[derive(Template, Debug)]
+#[template(path = "settings.html")]
+struct SettingsPage {
+    /// Each page size and whether it's chosen.
+    page_sizes: Vec<(usize, bool)>,
+    /// Each ranker's name and whether it's chosen.
+    sorts: Vec<(&'static str, bool)>,
+    /// Each theme, its translated name, and whether it's chosen.
+    themes: Vec<(&'static str, String, bool)>,
+    hide_placeholders: bool,
+    csrf: String,
+}
+
+/// Shows the form for changing the visitor's preferences.
+pub(crate) async fn settings(
+    Extension(cookies): Extension<SignedCookies>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let preferences = Preferences::read(&cookies, &headers);
+    let theme = signed_cookies::cookie(&headers, THEME_COOKIE);
+    let (csrf, set_csrf) = match cookies.read(&headers, CSRF_COOKIE) {
+        Some(csrf) => (csrf.to_string(), None),
+        None => {
+            let csrf = Uuid::new_v4().simple().to_string();
+            let cookie = cookies.set(CSRF_COOKIE, &csrf, PREFERENCES_MAX_AGE);
+            (csrf, cookie)
+        }
+    };
+    let page = Html(
+        SettingsPage {
+            page_sizes: PAGE_SIZES
+                .iter()
+                .map(|size| (*size, preferences.per_page == Some(*size)))
+                .collect(),
+            sorts: ranking::RANKERS
+                .iter()
+                .map(|ranker| {
+                    (
+                        ranker.name(),
+                        preferences.sort.as_deref() == Some(ranker.name()),
+                    )
+                })
+                .collect(),
+            themes: THEMES
+                .iter()
+                .map(|choice| {
+                    let label = i18n::t(&format!("settings-theme-{choice}"));
+                    (*choice, label, theme == Some(*choice))
+                })
+                .collect(),
+            hide_placeholders: preferences.hide_placeholders,
+            csrf,
+        }
+        .render()?,
+    );
+
+    let mut response_headers = HeaderMap::new();
+    if let Some(cookie) = set_csrf {
+        response_headers.insert(SET_COOKIE, cookie);
+    }
+    Ok((response_headers, page).into_response())
+}
+
+/// The settings form. Empty values choose the default.
+#[derive(Deserialize, Debug)]
+pub(crate) struct SettingsForm {
+    #[serde(default)]
+    per_page: String,
+    #[serde(default)]
+    sort: String,
+    #[serde(default)]
+    theme: String,
+    #[serde(default)]
+    hide_placeholders: bool,
+    #[serde(default)]
+    csrf: String,
+}
+
+/// Saves the visitor's preferences and returns to the settings page. Forms
+/// whose token doesn't match the visitor's `csrf` cookie are rejected with
+/// 403.
+pub(crate) async fn save_settings(
+    Extension(cookies): Extension<SignedCookies>,
+    headers: HeaderMap,
+    Form(form): Form<SettingsForm>,
+) -> Response {
+    if cookies.read(&headers, CSRF_COOKIE) != Some(form.csrf.as_str()) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let preferences = Preferences {
+        per_page: form.per_page.parse().ok(),
+        sort: Some(form.sort).filter(|sort| !sort.is_empty()),
+        hide_placeholders: form.hide_placeholders,
+    }
+    .validated();
+    let theme = if THEMES.contains(&form.theme.as_str()) {
+        let theme = &form.theme;
+        format!("{THEME_COOKIE}={theme}; Path=/; Max-Age={PREFERENCES_MAX_AGE}; SameSite=Lax")
+    } else {
+        // Follows the system's color scheme.
+        format!("{THEME_COOKIE}=; Path=/; Max-Age=0; SameSite=Lax")
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(LOCATION, HeaderValue::from_static("/settings"));
+    if let Some(cookie) = preferences.cookie(&cookies) {
+        headers.append(SET_COOKIE, cookie);
+    }
+    if let Ok(theme) = HeaderValue::from_str(&theme) {
+        headers.append(SET_COOKIE, theme);
+    }
+    (StatusCode::SEE_OTHER, headers).into_response()
+}
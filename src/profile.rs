@@ -0,0 +1,727 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bonsaidb::{
+    core::{
+        schema::{SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+
+use crate::{
+    dependency_graph::{self, Graph},
+    schema::{
+        Category, Crate, CrateProfile, CratesByCategory, CratesByNormalizedName,
+        DependenciesByVersion, Dependency, DependencyKind, Keyword, Platforms, Version,
+    },
+};
+
+/// Keywords that crates supporting WebAssembly are tagged with.
+const WASM_KEYWORDS: &[&str] = &["wasm", "webassembly", "wasm32", "wasi", "wasm-bindgen"];
+
+/// The slug of the WebAssembly category.
+const WASM_CATEGORY: &str = "wasm";
+
+/// The slugs of the categories that imply support for a platform, with the
+/// platforms they imply.
+const PLATFORM_CATEGORIES: &[(&str, &[Platform])] = &[
+    ("os::windows-apis", &[Platform::Windows]),
+    ("os::macos-apis", &[Platform::MacOs]),
+    ("os::linux-apis", &[Platform::Linux]),
+    ("os::unix-apis", &[Platform::MacOs, Platform::Linux]),
+    ("embedded", &[Platform::Embedded]),
+    ("no-std", &[Platform::Embedded]),
+];
+
+/// Keywords that imply support for a platform, with the platforms they imply.
+const PLATFORM_KEYWORDS: &[(&str, &[Platform])] = &[
+    ("windows", &[Platform::Windows]),
+    ("win32", &[Platform::Windows]),
+    ("macos", &[Platform::MacOs]),
+    ("osx", &[Platform::MacOs]),
+    ("linux", &[Platform::Linux]),
+    ("unix", &[Platform::MacOs, Platform::Linux]),
+    ("embedded", &[Platform::Embedded]),
+    ("no-std", &[Platform::Embedded]),
+    ("no_std", &[Platform::Embedded]),
+];
+
+/// Crates that only build for WebAssembly. Depending on one, even optionally
+/// or only for tests, means the crate is meant to run there.
+const WASM_CRATES: &[&str] = &[
+    "wasm-bindgen",
+    "wasm-bindgen-futures",
+    "wasm-bindgen-test",
+    "js-sys",
+    "web-sys",
+];
+
+/// The crates procedural macros are written with. Proc-macro crates can't be
+/// identified from the dump directly, so crates are recognized by depending
+/// on these.
+const PROC_MACRO_CRATES: &[&str] = &["proc-macro2", "syn", "quote"];
+
+/// Endings of the names commonly given to procedural macro crates.
+const PROC_MACRO_SUFFIXES: &[&str] = &["derive", "macro", "macros", "codegen"];
+
+/// Keywords that procedural macro crates are tagged with.
+const PROC_MACRO_KEYWORDS: &[&str] = &["proc-macro", "proc_macro", "derive", "macro", "macros"];
+
+/// Everything known about a crate's latest version while building its
+/// [`CrateProfile`].
+struct Facts<'a> {
+    name: &'a str,
+    keywords: Vec<&'a str>,
+    categories: HashSet<&'a str>,
+    dependencies: Vec<Dependency>,
+    features: Vec<String>,
+    /// The native library the crate links to, which requires a build script.
+    links: String,
+}
+
+/// Crates and categories that are looked up while building every profile.
+struct Known {
+    /// The ids of the crates in [`WASM_CRATES`].
+    wasm_crates: HashSet<u64>,
+    /// The ids of the crates in [`PROC_MACRO_CRATES`].
+    proc_macro_crates: HashSet<u64>,
+}
+
+/// Recomputes the [`CrateProfile`] of every crate from its keywords,
+/// categories, and its latest version's dependencies and features, storing
+/// the ones that changed.
+///
+/// Procedural macros and build scripts in each crate's dependencies are
+/// counted using the latest version of every dependency, like
+/// [`DependencyMetrics`](crate::schema::DependencyMetrics).
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    println!("Profiling crates");
+    let keyword_names = Keyword::all(db)
+        .query()?
+        .into_iter()
+        .map(|keyword| (keyword.header.id, keyword.contents.keyword))
+        .collect::<HashMap<_, _>>();
+    let categories = Category::all(db).query()?;
+    let mut categories_by_crate = HashMap::<u64, HashSet<&str>>::new();
+    for category in &categories {
+        let slug = category.contents.slug.as_str();
+        if slug != WASM_CATEGORY && !PLATFORM_CATEGORIES.iter().any(|(known, _)| *known == slug) {
+            continue;
+        }
+        for mapping in CratesByCategory::entries(db)
+            .with_key(&category.header.id)
+            .query()?
+        {
+            categories_by_crate
+                .entry(mapping.source.id.deserialize()?)
+                .or_default()
+                .insert(category.contents.slug.as_str());
+        }
+    }
+
+    let crates = CratesByNormalizedName::entries(db).query()?;
+    let ids_of = |names: &[&str]| {
+        crates
+            .iter()
+            .filter(|mapping| {
+                names
+                    .iter()
+                    .any(|name| mapping.key == Crate::normalized_name(name))
+            })
+            .map(|mapping| mapping.source.id.deserialize())
+            .collect::<Result<HashSet<u64>, _>>()
+    };
+    let known = Known {
+        wasm_crates: ids_of(WASM_CRATES)?,
+        proc_macro_crates: ids_of(PROC_MACRO_CRATES)?,
+    };
+    let latest_versions = dependency_graph::latest_versions(db)?;
+    let mut existing = CrateProfile::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+
+    let mut profiles = HashMap::<u64, CrateProfile>::with_capacity(crates.len());
+    let mut graph = Graph::with_capacity(crates.len());
+    for mapping in &crates {
+        let crate_id = mapping.source.id.deserialize::<u64>()?;
+        let (dependencies, version) = match latest_versions.get(&crate_id) {
+            Some(version_id) => (
+                DependenciesByVersion::entries(db)
+                    .with_key(version_id)
+                    .query()?
+                    .into_iter()
+                    .map(|mapping| mapping.value)
+                    .collect::<Vec<_>>(),
+                Version::get(version_id, db)?.map(|version| version.contents),
+            ),
+            None => (Vec::new(), None),
+        };
+        let mut required = dependencies
+            .iter()
+            .filter(|dependency| dependency_graph::is_required(dependency))
+            .map(|dependency| dependency.crate_id)
+            .collect::<Vec<_>>();
+        required.sort_unstable();
+        required.dedup();
+        graph.insert(crate_id, required);
+
+        let facts = Facts {
+            name: &mapping.value.name,
+            keywords: mapping
+                .value
+                .keywords
+                .iter()
+                .filter_map(|id| keyword_names.get(id).map(String::as_str))
+                .collect(),
+            categories: categories_by_crate.remove(&crate_id).unwrap_or_default(),
+            dependencies,
+            features: version
+                .as_ref()
+                .map(|version| feature_names(&version.features))
+                .unwrap_or_default(),
+            links: version.map(|version| version.links).unwrap_or_default(),
+        };
+        profiles.insert(crate_id, infer(&facts, &known));
+    }
+
+    let dependency_counts = profiles
+        .keys()
+        .map(|crate_id| {
+            let mut proc_macros = 0_u32;
+            let mut build_scripts = 0_u32;
+            for dependency in dependency_graph::reachable(&graph, *crate_id) {
+                let Some(dependency) = profiles.get(&dependency) else { continue };
+                proc_macros += u32::from(dependency.proc_macro);
+                build_scripts += u32::from(dependency.build_script);
+            }
+            (*crate_id, proc_macros, build_scripts)
+        })
+        .collect::<Vec<_>>();
+    for (crate_id, proc_macros, build_scripts) in dependency_counts {
+        if let Some(profile) = profiles.get_mut(&crate_id) {
+            profile.dependency_proc_macros = proc_macros;
+            profile.dependency_build_scripts = build_scripts;
+        }
+    }
+
+    let mut tx = Transaction::new();
+    for (crate_id, profile) in profiles {
+        let operation = match existing.remove(&crate_id) {
+            Some(existing) if existing.contents == profile => continue,
+            Some(existing) => {
+                Operation::update_serialized::<CrateProfile>(existing.header, &profile)?
+            }
+            None => Operation::insert_serialized::<CrateProfile>(Some(&crate_id), &profile)?,
+        };
+        tx.operations.push(operation);
+        if tx.operations.len() >= 100_000 {
+            tx.apply(db)?;
+            tx = Transaction::new();
+        }
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+fn infer(facts: &Facts<'_>, known: &Known) -> CrateProfile {
+    CrateProfile {
+        wasm: supports_wasm(facts, known),
+        platforms: platforms(facts),
+        proc_macro: is_proc_macro(facts, known),
+        build_script: has_build_script(facts),
+        dependency_proc_macros: 0,
+        dependency_build_scripts: 0,
+    }
+}
+
+/// Returns true if the crate appears to be a procedural macro: it depends on
+/// every crate in [`PROC_MACRO_CRATES`], or on one of them while being named
+/// or tagged like a macro. The crates macros are written with, and libraries
+/// that help write them, are sometimes mistaken for macros themselves, which
+/// is harmless for judging what runs at compile time.
+fn is_proc_macro(facts: &Facts<'_>, known: &Known) -> bool {
+    let macro_dependencies = facts
+        .dependencies
+        .iter()
+        .filter(|dependency| {
+            dependency.kind == DependencyKind::Normal
+                && known.proc_macro_crates.contains(&dependency.crate_id)
+        })
+        .map(|dependency| dependency.crate_id)
+        .collect::<HashSet<_>>();
+    if macro_dependencies.is_empty()
+        || PROC_MACRO_CRATES
+            .iter()
+            .any(|name| Crate::normalized_name(name) == Crate::normalized_name(facts.name))
+    {
+        return false;
+    }
+
+    let name = facts.name.to_ascii_lowercase();
+    macro_dependencies.len() == known.proc_macro_crates.len()
+        || PROC_MACRO_SUFFIXES.iter().any(|suffix| {
+            name.strip_suffix(suffix)
+                .map_or(false, |rest| rest.is_empty() || rest.ends_with(['-', '_']))
+        })
+        || facts
+            .keywords
+            .iter()
+            .any(|keyword| PROC_MACRO_KEYWORDS.contains(&keyword.to_ascii_lowercase().as_str()))
+}
+
+/// Returns true if the crate appears to have a build script: it links to a
+/// native library or has build-dependencies. Build scripts that need no
+/// dependencies can't be detected.
+fn has_build_script(facts: &Facts<'_>) -> bool {
+    !facts.links.is_empty()
+        || facts
+            .dependencies
+            .iter()
+            .any(|dependency| dependency.kind == DependencyKind::Build)
+}
+
+/// Returns true if the crate appears to support WebAssembly: it's tagged or
+/// categorized as such, it has dependencies specific to WebAssembly targets,
+/// or it has a feature for WebAssembly.
+fn supports_wasm(facts: &Facts<'_>, known: &Known) -> bool {
+    facts
+        .keywords
+        .iter()
+        .any(|keyword| WASM_KEYWORDS.contains(&keyword.to_ascii_lowercase().as_str()))
+        || facts.categories.contains(WASM_CATEGORY)
+        || facts.dependencies.iter().any(|dependency| {
+            known.wasm_crates.contains(&dependency.crate_id)
+                || dependency
+                    .target
+                    .as_deref()
+                    .map_or(false, |target| target.contains("wasm"))
+        })
+        || facts
+            .features
+            .iter()
+            .any(|feature| feature.to_ascii_lowercase().contains("wasm"))
+}
+
+/// A platform shown in the support matrix on crate pages. WebAssembly is
+/// tracked separately in [`CrateProfile::wasm`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Platform {
+    Windows,
+    MacOs,
+    Linux,
+    Wasm32,
+    Embedded,
+}
+
+impl Platform {
+    /// Parses the name of a platform as written in the `platform:` filter.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "windows" | "win" => Some(Self::Windows),
+            "macos" | "mac" | "osx" => Some(Self::MacOs),
+            "linux" => Some(Self::Linux),
+            "wasm32" | "wasm" => Some(Self::Wasm32),
+            "embedded" | "no-std" | "no_std" => Some(Self::Embedded),
+            _ => None,
+        }
+    }
+
+    /// Returns true if `profile` shows support for this platform.
+    pub fn supported_by(self, profile: &CrateProfile) -> bool {
+        let platforms = &profile.platforms;
+        match self {
+            Self::Windows => platforms.windows,
+            Self::MacOs => platforms.macos,
+            Self::Linux => platforms.linux,
+            Self::Wasm32 => profile.wasm,
+            Self::Embedded => platforms.embedded,
+        }
+    }
+
+    fn mark(self, platforms: &mut Platforms) {
+        match self {
+            Self::Windows => platforms.windows = true,
+            Self::MacOs => platforms.macos = true,
+            Self::Linux => platforms.linux = true,
+            Self::Wasm32 => {}
+            Self::Embedded => platforms.embedded = true,
+        }
+    }
+}
+
+/// Returns the platforms the crate has code specific to, judging by the
+/// targets of its dependencies, its keywords and its categories.
+fn platforms(facts: &Facts<'_>) -> Platforms {
+    let mut platforms = Platforms::default();
+    for keyword in &facts.keywords {
+        let keyword = keyword.to_ascii_lowercase();
+        for (_, implied) in PLATFORM_KEYWORDS
+            .iter()
+            .filter(|(known, _)| *known == keyword)
+        {
+            implied
+                .iter()
+                .for_each(|platform| platform.mark(&mut platforms));
+        }
+    }
+    for (_, implied) in PLATFORM_CATEGORIES
+        .iter()
+        .filter(|(slug, _)| facts.categories.contains(slug))
+    {
+        implied
+            .iter()
+            .for_each(|platform| platform.mark(&mut platforms));
+    }
+    for target in facts
+        .dependencies
+        .iter()
+        .filter_map(|dependency| dependency.target.as_deref())
+    {
+        for platform in target_platforms(target) {
+            platform.mark(&mut platforms);
+        }
+    }
+    platforms
+}
+
+/// Returns the platforms a dependency's `target` applies to. `target` is
+/// either a target triple or a `cfg(...)` expression. Predicates inside
+/// `not(...)` are ignored, since code for every platform but one says little
+/// about which platforms are supported.
+fn target_platforms(target: &str) -> Vec<Platform> {
+    let target = target.trim().to_ascii_lowercase();
+    let mut platforms = Vec::new();
+    match target
+        .strip_prefix("cfg(")
+        .and_then(|cfg| cfg.strip_suffix(')'))
+    {
+        Some(cfg) => cfg_platforms(cfg, false, &mut platforms),
+        None => triple_platforms(&target, &mut platforms),
+    }
+    platforms
+}
+
+fn triple_platforms(triple: &str, platforms: &mut Vec<Platform>) {
+    let parts = triple.split('-').collect::<Vec<_>>();
+    if parts.contains(&"windows") {
+        platforms.push(Platform::Windows);
+    }
+    if parts.contains(&"darwin") {
+        platforms.push(Platform::MacOs);
+    }
+    if parts.contains(&"linux") {
+        platforms.push(Platform::Linux);
+    }
+    if parts.contains(&"none") || triple.starts_with("thumb") {
+        platforms.push(Platform::Embedded);
+    }
+}
+
+/// Collects the platforms named by the predicates in `cfg`, the inside of a
+/// `cfg(...)` expression. `negated` is true inside an odd number of
+/// `not(...)`s.
+fn cfg_platforms(cfg: &str, negated: bool, platforms: &mut Vec<Platform>) {
+    for predicate in split_cfg_list(cfg) {
+        let predicate = predicate.trim();
+        if let Some((function, arguments)) = predicate
+            .strip_suffix(')')
+            .and_then(|predicate| predicate.split_once('('))
+        {
+            let negated = negated ^ (function.trim() == "not");
+            cfg_platforms(arguments, negated, platforms);
+        } else if !negated {
+            let (key, value) = match predicate.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().trim_matches('"')),
+                None => ("", predicate),
+            };
+            match (key, value) {
+                ("" | "target_family" | "target_os", "windows") => {
+                    platforms.push(Platform::Windows);
+                }
+                ("" | "target_family", "unix") => {
+                    platforms.extend([Platform::MacOs, Platform::Linux]);
+                }
+                ("target_os", "macos") => platforms.push(Platform::MacOs),
+                ("target_os", "linux") => platforms.push(Platform::Linux),
+                ("target_os", "none") => platforms.push(Platform::Embedded),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits a comma-separated list of `cfg` predicates, leaving the commas
+/// inside nested parentheses and strings alone.
+fn split_cfg_list(list: &str) -> Vec<&str> {
+    let mut predicates = Vec::new();
+    let mut depth = 0_usize;
+    let mut in_string = false;
+    let mut start = 0;
+    for (index, ch) in list.char_indices() {
+        match ch {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth = depth.saturating_sub(1),
+            ',' if !in_string && depth == 0 => {
+                predicates.push(&list[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    predicates.push(&list[start..]);
+    predicates
+}
+
+/// Returns the names of the features declared in `features`, the JSON object
+/// stored in the dump.
+fn feature_names(features: &str) -> Vec<String> {
+    serde_json::from_str::<BTreeMap<String, serde_json::Value>>(features)
+        .map(|features| features.into_keys().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WASM_BINDGEN: u64 = 100;
+    const PROC_MACRO2: u64 = 200;
+    const SYN: u64 = 201;
+    const QUOTE: u64 = 202;
+
+    fn known() -> Known {
+        Known {
+            wasm_crates: HashSet::from([WASM_BINDGEN]),
+            proc_macro_crates: HashSet::from([PROC_MACRO2, SYN, QUOTE]),
+        }
+    }
+
+    fn facts(name: &str) -> Facts<'_> {
+        Facts {
+            name,
+            keywords: Vec::new(),
+            categories: HashSet::new(),
+            dependencies: Vec::new(),
+            features: Vec::new(),
+            links: String::new(),
+        }
+    }
+
+    fn dependency(crate_id: u64, kind: DependencyKind, target: Option<&str>) -> Dependency {
+        Dependency {
+            version_id: 1,
+            crate_id,
+            req: String::from("^1"),
+            kind,
+            optional: false,
+            default_features: true,
+            features: Vec::new(),
+            target: target.map(String::from),
+            explicit_name: None,
+            dependent_crate_id: None,
+        }
+    }
+
+    #[test]
+    fn cfg_lists_are_split_at_the_top_level() {
+        assert_eq!(
+            split_cfg_list(r#"any(unix, windows), target_os = "a,b", feature = "x""#),
+            [
+                "any(unix, windows)",
+                r#" target_os = "a,b""#,
+                r#" feature = "x""#
+            ]
+        );
+        assert_eq!(split_cfg_list("unix"), ["unix"]);
+        assert_eq!(split_cfg_list(""), [""]);
+    }
+
+    #[test]
+    fn cfg_predicates() {
+        let platforms = |cfg: &str, negated: bool| {
+            let mut platforms = Vec::new();
+            cfg_platforms(cfg, negated, &mut platforms);
+            platforms
+        };
+        assert_eq!(platforms("windows", false), [Platform::Windows]);
+        assert!(platforms("windows", true).is_empty());
+        assert_eq!(
+            platforms(r#"target_family = "unix""#, false),
+            [Platform::MacOs, Platform::Linux]
+        );
+        assert_eq!(
+            platforms(r#"target_os="none""#, false),
+            [Platform::Embedded]
+        );
+        assert!(platforms(r#"target_arch = "x86_64""#, false).is_empty());
+        assert_eq!(platforms("not(windows)", true), [Platform::Windows]);
+    }
+
+    #[test]
+    fn target_triples_and_cfgs() {
+        assert_eq!(
+            target_platforms("x86_64-pc-windows-msvc"),
+            [Platform::Windows]
+        );
+        assert_eq!(target_platforms("aarch64-apple-darwin"), [Platform::MacOs]);
+        assert_eq!(
+            target_platforms("x86_64-unknown-linux-gnu"),
+            [Platform::Linux]
+        );
+        assert_eq!(
+            target_platforms("thumbv7em-none-eabihf"),
+            [Platform::Embedded]
+        );
+        assert!(target_platforms("wasm32-unknown-unknown").is_empty());
+        assert_eq!(target_platforms("CFG(Windows)"), [Platform::Windows]);
+        assert_eq!(
+            target_platforms("cfg(unix)"),
+            [Platform::MacOs, Platform::Linux]
+        );
+        assert_eq!(
+            target_platforms(r#"cfg(any(target_os = "linux", target_os = "none"))"#),
+            [Platform::Linux, Platform::Embedded]
+        );
+        assert!(target_platforms("cfg(not(windows))").is_empty());
+        assert_eq!(
+            target_platforms("cfg(not(not(windows)))"),
+            [Platform::Windows]
+        );
+        assert_eq!(
+            target_platforms(r#"cfg(all(unix, not(target_os = "macos")))"#),
+            [Platform::MacOs, Platform::Linux]
+        );
+    }
+
+    #[test]
+    fn platforms_from_keywords_categories_and_targets() {
+        let mut helpers = facts("winapi-helpers");
+        helpers.keywords = vec!["Linux"];
+        helpers.categories = HashSet::from(["os::windows-apis"]);
+        helpers.dependencies = vec![dependency(1, DependencyKind::Normal, Some("cfg(unix)"))];
+        assert_eq!(
+            platforms(&helpers),
+            Platforms {
+                windows: true,
+                macos: true,
+                linux: true,
+                embedded: false,
+            }
+        );
+        assert!(!platforms(&facts("plain")).any());
+    }
+
+    #[test]
+    fn proc_macros() {
+        let known = known();
+        let with = |name: &'static str, dependencies: &[(u64, DependencyKind)]| {
+            let mut facts = facts(name);
+            facts.dependencies = dependencies
+                .iter()
+                .map(|(id, kind)| dependency(*id, *kind, None))
+                .collect();
+            facts
+        };
+        let normal = DependencyKind::Normal;
+
+        // Named like a macro, or depending on every macro crate.
+        assert!(is_proc_macro(
+            &with("serde_derive", &[(SYN, normal)]),
+            &known
+        ));
+        assert!(is_proc_macro(
+            &with("tokio-macros", &[(QUOTE, normal)]),
+            &known
+        ));
+        assert!(is_proc_macro(
+            &with(
+                "anything",
+                &[(PROC_MACRO2, normal), (SYN, normal), (QUOTE, normal)]
+            ),
+            &known
+        ));
+        // Tagged like a macro.
+        let mut tagged = with("anything", &[(SYN, normal)]);
+        tagged.keywords = vec!["Derive"];
+        assert!(is_proc_macro(&tagged, &known));
+
+        assert!(!is_proc_macro(&with("anything", &[(SYN, normal)]), &known));
+        assert!(!is_proc_macro(&with("foomacros", &[(SYN, normal)]), &known));
+        assert!(!is_proc_macro(&with("some-derive", &[]), &known));
+        assert!(!is_proc_macro(
+            &with("some-derive", &[(SYN, DependencyKind::Dev)]),
+            &known
+        ));
+        // The crates macros are written with aren't macros themselves.
+        assert!(!is_proc_macro(
+            &with("syn", &[(PROC_MACRO2, normal), (QUOTE, normal)]),
+            &known
+        ));
+        assert!(!is_proc_macro(
+            &with("proc_macro2", &[(QUOTE, normal)]),
+            &known
+        ));
+    }
+
+    #[test]
+    fn wasm_support() {
+        let known = known();
+        assert!(!supports_wasm(&facts("plain"), &known));
+
+        let mut keyword = facts("a");
+        keyword.keywords = vec!["WebAssembly"];
+        assert!(supports_wasm(&keyword, &known));
+
+        let mut category = facts("a");
+        category.categories = HashSet::from([WASM_CATEGORY]);
+        assert!(supports_wasm(&category, &known));
+
+        let mut dependency_on_wasm = facts("a");
+        dependency_on_wasm.dependencies = vec![dependency(WASM_BINDGEN, DependencyKind::Dev, None)];
+        assert!(supports_wasm(&dependency_on_wasm, &known));
+
+        let mut target = facts("a");
+        target.dependencies = vec![dependency(
+            1,
+            DependencyKind::Normal,
+            Some(r#"cfg(target_arch = "wasm32")"#),
+        )];
+        assert!(supports_wasm(&target, &known));
+
+        let mut feature = facts("a");
+        feature.features = vec![String::from("Wasm-Bindgen")];
+        assert!(supports_wasm(&feature, &known));
+    }
+
+    #[test]
+    fn build_scripts() {
+        assert!(!has_build_script(&facts("plain")));
+
+        let mut links = facts("libz-sys");
+        links.links = String::from("z");
+        assert!(has_build_script(&links));
+
+        let mut build = facts("a");
+        build.dependencies = vec![dependency(1, DependencyKind::Build, None)];
+        assert!(has_build_script(&build));
+
+        let mut dev = facts("a");
+        dev.dependencies = vec![dependency(1, DependencyKind::Dev, None)];
+        assert!(!has_build_script(&dev));
+    }
+
+    #[test]
+    fn feature_names_are_parsed() {
+        assert_eq!(
+            feature_names(r#"{"std": [], "wasm": ["dep:wasm-bindgen"]}"#),
+            ["std", "wasm"]
+        );
+        assert!(feature_names("not json").is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Detection of owners who publish waves of near-identical crates, such as
+//! hundreds of name reservations in a day.
+//!
+//! Each owner's crates are ordered by when their first version was
+//! published, and the most crates published within [`WINDOW`] form the
+//! owner's largest wave. A wave is abuse when it's large and most of its
+//! crates are near-identical: their descriptions are the same apart from the
+//! crate's name, or their READMEs are copies. Organizations publishing many
+//! distinct crates at once, such as SDKs, don't describe them identically.
+//! Only the wave's crates are demoted, not the owner's other crates.
+
+use std::collections::{HashMap, HashSet};
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    dump,
+    schema::{Crate, Owner, PublishWave, ReadmeDuplicate, VersionsByCrate},
+};
+
+/// How close together the crates of a wave are first published.
+const WINDOW: Duration = Duration::days(3);
+
+/// The fewest crates a wave must have to be abuse.
+const MIN_WAVE_CRATES: usize = 100;
+
+/// The fraction of a wave's crates that must be near-identical for it to be
+/// abuse.
+const MIN_IDENTICAL_SHARE: f32 = 0.8;
+
+/// Flags the owners whose largest wave of crates is abuse, replacing the
+/// previously flagged owners.
+pub(crate) fn update(db: &Database) -> anyhow::Result<()> {
+    println!("Finding publish waves");
+    let mut first_published = HashMap::<u64, OffsetDateTime>::new();
+    for mapping in VersionsByCrate::entries(db).query()? {
+        let Ok(published) = dump::parse_timestamp(&mapping.value.created_at) else { continue };
+        first_published
+            .entry(mapping.key)
+            .and_modify(|first| *first = (*first).min(published))
+            .or_insert(published);
+    }
+    let duplicates = ReadmeDuplicate::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| d.header.id)
+        .collect::<HashSet<_>>();
+
+    let mut waves = HashMap::new();
+    for owner in Owner::all(db).query()? {
+        let Some((crates, started, ended)) = largest_wave(&owner.contents.crates, &first_published)
+        else {
+            continue;
+        };
+        if crates.len() < MIN_WAVE_CRATES {
+            continue;
+        }
+        let crates = Crate::get_multiple(&crates, db)?
+            .into_iter()
+            .map(|krate| (krate.header.id, krate.contents))
+            .collect::<Vec<_>>();
+        if identical_share(&crates, &duplicates) >= MIN_IDENTICAL_SHARE {
+            waves.insert(
+                owner.header.id,
+                PublishWave {
+                    login: owner.contents.login,
+                    crates: crates.len() as u64,
+                    started: dump::timestamp(started),
+                    ended: dump::timestamp(ended),
+                    crate_ids: crates.iter().map(|(id, _)| *id).collect(),
+                },
+            );
+        }
+    }
+
+    let mut existing = PublishWave::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id.clone(), d))
+        .collect::<HashMap<_, _>>();
+    let mut tx = Transaction::new();
+    for (login, wave) in &waves {
+        let operation = match existing.remove(login) {
+            Some(existing) if existing.contents == *wave => continue,
+            Some(existing) => Operation::update_serialized::<PublishWave>(existing.header, wave)?,
+            None => Operation::insert_serialized::<PublishWave>(Some(login), wave)?,
+        };
+        tx.operations.push(operation);
+    }
+    // Owners who are no longer flagged, such as after their crates were
+    // deleted.
+    for stale in existing.into_values() {
+        tx.operations.push(Operation::delete(
+            PublishWave::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+    if !waves.is_empty() {
+        println!("Flagged {} owners for publish waves", waves.len());
+    }
+
+    Ok(())
+}
+
+/// Returns the ids of the most of `crates` first published within
+/// [`WINDOW`], and when the first and last of them were published.
+fn largest_wave(
+    crates: &[u64],
+    first_published: &HashMap<u64, OffsetDateTime>,
+) -> Option<(Vec<u64>, OffsetDateTime, OffsetDateTime)> {
+    if crates.len() < MIN_WAVE_CRATES {
+        return None;
+    }
+    let mut published = crates
+        .iter()
+        .filter_map(|id| first_published.get(id).map(|at| (*at, *id)))
+        .collect::<Vec<_>>();
+    published.sort_unstable();
+
+    let mut largest = 0..0;
+    let mut start = 0;
+    for end in 0..published.len() {
+        while published[end].0 - published[start].0 > WINDOW {
+            start += 1;
+        }
+        if end + 1 - start > largest.len() {
+            largest = start..end + 1;
+        }
+    }
+    let wave = &published[largest];
+    Some((
+        wave.iter().map(|(_, id)| *id).collect(),
+        wave.first()?.0,
+        wave.last()?.0,
+    ))
+}
+
+/// Returns the fraction of `crates`, with their ids, that are near-identical
+/// to another of them. Crates without a description aren't near-identical to
+/// each other, since many crates omit theirs.
+fn identical_share(crates: &[(u64, Crate)], duplicates: &HashSet<u64>) -> f32 {
+    if crates.is_empty() {
+        return 0.;
+    }
+    let templates = crates
+        .iter()
+        .map(|(_, krate)| description_template(krate))
+        .collect::<Vec<_>>();
+    let mut template_counts = HashMap::<&str, usize>::new();
+    for template in templates.iter().filter(|template| !template.is_empty()) {
+        *template_counts.entry(template.as_str()).or_default() += 1;
+    }
+    let identical = crates
+        .iter()
+        .zip(&templates)
+        .filter(|((id, _), template)| {
+            template_counts
+                .get(template.as_str())
+                .map_or(false, |count| *count > 1)
+                || duplicates.contains(id)
+        })
+        .count();
+    identical as f32 / crates.len() as f32
+}
+
+/// Returns the crate's description in lowercase with its name removed, so
+/// that descriptions generated from a template compare equal.
+fn description_template(krate: &Crate) -> String {
+    let name = krate.name.to_ascii_lowercase();
+    krate
+        .description
+        .to_ascii_lowercase()
+        .replace(&name, "")
+        .replace(&name.replace('-', "_"), "")
+        .replace(&name.replace('_', "-"), "")
+        .split_ascii_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn krate(name: &str, description: &str) -> Crate {
+        Crate {
+            created_at: String::new(),
+            description: description.to_string(),
+            documentation: String::new(),
+            downloads: None,
+            homepage: String::new(),
+            max_upload_size: None,
+            name: name.to_string(),
+            readme: String::new(),
+            repository: String::new(),
+            updated_at: String::new(),
+            keywords: HashSet::new(),
+            category_ids: HashSet::new(),
+            owners: HashSet::new(),
+            registry: None,
+            language: None,
+        }
+    }
+
+    /// Returns when each crate was first published, given in minutes.
+    fn published(crates: &[(u64, i64)]) -> HashMap<u64, OffsetDateTime> {
+        let start = OffsetDateTime::UNIX_EPOCH;
+        crates
+            .iter()
+            .map(|(id, minutes)| (*id, start + Duration::minutes(*minutes)))
+            .collect()
+    }
+
+    #[test]
+    fn templates_leave_out_the_name() {
+        assert_eq!(
+            description_template(&krate("foo-bar", "Reserved  for FOO_BAR.")),
+            "reserved for ."
+        );
+        assert_eq!(
+            description_template(&krate("foo_bar", "The foo-bar crate")),
+            "the crate"
+        );
+        assert_eq!(description_template(&krate("foo", "")), "");
+    }
+
+    #[test]
+    fn waves_are_the_most_crates_within_the_window() {
+        // A wave of crates published a minute apart, ten days after another.
+        let mut times = (0..MIN_WAVE_CRATES as u64)
+            .map(|id| (id, 60 * 24 * 10 + id as i64))
+            .collect::<Vec<_>>();
+        times.push((1_000, 0));
+        let first_published = published(&times);
+        let ids = times.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+        let (wave, started, ended) = largest_wave(&ids, &first_published).expect("enough crates");
+        assert_eq!(wave.len(), MIN_WAVE_CRATES);
+        assert!(!wave.contains(&1_000));
+        assert_eq!(started, first_published[&0]);
+        assert_eq!(ended, first_published[&(MIN_WAVE_CRATES as u64 - 1)]);
+
+        assert!(largest_wave(&ids[..MIN_WAVE_CRATES - 1], &first_published).is_none());
+    }
+
+    #[test]
+    fn waves_are_limited_to_the_window() {
+        // One crate published every day.
+        let times = (0..MIN_WAVE_CRATES as u64)
+            .map(|id| (id, 60 * 24 * id as i64))
+            .collect::<Vec<_>>();
+        let ids = times.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let (wave, _, _) = largest_wave(&ids, &published(&times)).expect("enough crates");
+        assert_eq!(wave.len(), 4);
+    }
+
+    #[test]
+    fn templated_descriptions_are_identical() {
+        let crates = (0..10)
+            .map(|id| {
+                (
+                    id,
+                    krate(&format!("name{id}"), &format!("Reserved: name{id}")),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(identical_share(&crates, &HashSet::new()), 1.);
+    }
+
+    #[test]
+    fn distinct_sdk_crates_are_not_identical() {
+        let services = ["S3", "EC2", "Lambda", "DynamoDB"];
+        let crates = services
+            .iter()
+            .enumerate()
+            .map(|(id, service)| {
+                let name = format!("aws-sdk-{}", service.to_ascii_lowercase());
+                (
+                    id as u64,
+                    krate(&name, &format!("AWS SDK for Amazon {service}")),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(identical_share(&crates, &HashSet::new()), 0.);
+    }
+
+    #[test]
+    fn empty_descriptions_are_not_identical() {
+        let crates = (0..4)
+            .map(|id| (id, krate(&format!("name{id}"), "")))
+            .collect::<Vec<_>>();
+        assert_eq!(identical_share(&crates, &HashSet::new()), 0.);
+        // README copies still count.
+        assert_eq!(identical_share(&crates, &HashSet::from([0, 1])), 0.5);
+        assert_eq!(identical_share(&[], &HashSet::new()), 0.);
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::Debug;
+
+use serde::Deserialize;
+
+use crate::cache::CachedCrate;
+
+/// The signals available to a [`Ranker`] when ordering a search result.
+#[derive(Debug)]
+pub struct Signals<'a> {
+    /// The result's text relevance as a fraction of the most relevant result,
+    /// after filter adjustments such as the placeholder penalty.
+    pub relevance: f32,
+    /// The raw tantivy score of the crate's name, description, and readme.
+    pub index_score: f32,
+    /// The sum of the scores of query words matching the crate's name.
+    pub name_score: f32,
+    /// The sum of the scores of query words matching the crate's keywords.
+    pub keyword_score: f32,
+    /// A blend of the crate's download percentiles, from 0 to 1.
+    pub popularity: f32,
+    /// The number of days since the crate last published a version, if known.
+    pub days_since_release: Option<i64>,
+    /// The number of days since the crate was first published, if known.
+    pub days_since_first_release: Option<i64>,
+    /// The number of crates the latest version requires, if known.
+    pub transitive_dependencies: Option<u32>,
+    pub krate: &'a CachedCrate,
+}
+
+/// A strategy for ordering search results. Higher scores are ranked first.
+pub trait Ranker: Debug + Send + Sync {
+    /// The name used to select this ranker with `ranker=` or `ranker:`.
+    fn name(&self) -> &'static str;
+
+    fn score(&self, signals: &Signals<'_>) -> f32;
+}
+
+/// Every built-in ranker. The first is used by default.
+pub static RANKERS: &[&dyn Ranker] = &[
+    &RelevanceFirst,
+    &PopularityFirst,
+    &MaintenanceWeighted,
+    &Lightweight,
+];
+
+/// Returns the ranker named `name`.
+pub fn by_name(name: &str) -> Option<&'static dyn Ranker> {
+    RANKERS
+        .iter()
+        .copied()
+        .find(|ranker| ranker.name().eq_ignore_ascii_case(name))
+}
+
+/// Returns the ranker used when none is requested.
+pub fn default_ranker() -> &'static dyn Ranker {
+    RANKERS[0]
+}
+
+/// Weighs text relevance and popularity equally.
+#[derive(Debug)]
+pub struct RelevanceFirst;
+
+impl Ranker for RelevanceFirst {
+    fn name(&self) -> &'static str {
+        "relevance"
+    }
+
+    fn score(&self, signals: &Signals<'_>) -> f32 {
+        signals.relevance * signals.popularity
+    }
+}
+
+/// Orders results mostly by popularity, using relevance to break ties between
+/// similarly popular crates.
+#[derive(Debug)]
+pub struct PopularityFirst;
+
+impl Ranker for PopularityFirst {
+    fn name(&self) -> &'static str {
+        "popularity"
+    }
+
+    fn score(&self, signals: &Signals<'_>) -> f32 {
+        signals.popularity * signals.relevance.powf(0.25)
+    }
+}
+
+/// Demotes crates that haven't released in over a year, are deprecated, or
+/// appear to be placeholders.
+#[derive(Debug)]
+pub struct MaintenanceWeighted;
+
+/// Releases within this many days are not penalized.
+const MAINTAINED_DAYS: i64 = 365;
+
+impl MaintenanceWeighted {
+    /// Scores how well maintained `krate` appears to be, from 0.1 to 1, given
+    /// the number of days since it last published a version.
+    pub fn maintenance(krate: &CachedCrate, days_since_release: Option<i64>) -> f32 {
+        if krate.deprecation().is_some() || krate.likely_placeholder() {
+            return 0.1;
+        }
+
+        match days_since_release {
+            Some(days) if days <= MAINTAINED_DAYS => 1.,
+            // Halve the score for every additional year without a release.
+            Some(days) => 0.5_f32
+                .powf((days - MAINTAINED_DAYS) as f32 / 365.)
+                .max(0.25),
+            None => 0.5,
+        }
+    }
+}
+
+impl Ranker for MaintenanceWeighted {
+    fn name(&self) -> &'static str {
+        "maintained"
+    }
+
+    fn score(&self, signals: &Signals<'_>) -> f32 {
+        signals.relevance
+            * signals.popularity
+            * Self::maintenance(signals.krate, signals.days_since_release)
+    }
+}
+
+/// Prefers crates with fewer transitive dependencies.
+#[derive(Debug)]
+pub struct Lightweight;
+
+/// The number of transitive dependencies that halves a crate's score.
+const HALVING_DEPENDENCIES: f32 = 25.;
+
+impl Ranker for Lightweight {
+    fn name(&self) -> &'static str {
+        "lightweight"
+    }
+
+    fn score(&self, signals: &Signals<'_>) -> f32 {
+        let weight = match signals.transitive_dependencies {
+            Some(dependencies) => 1. / (1. + dependencies as f32 / HALVING_DEPENDENCIES),
+            None => 0.5,
+        };
+        signals.relevance * signals.popularity * weight
+    }
+}
+
+/// Adjusts every ranker's scores by how recently each crate was first
+/// published, boosting or penalizing new crates.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recency {
+    /// How much a crate published today is boosted. A weight of 0.5 multiplies
+    /// its score by 1.5, while -0.5 halves it. Defaults to 0, which disables
+    /// the adjustment.
+    pub weight: f32,
+    /// How many days the adjustment takes to fade out. Crates older than this
+    /// are unaffected.
+    pub days: u32,
+}
+
+impl Default for Recency {
+    fn default() -> Self {
+        Self {
+            weight: 0.,
+            days: 365,
+        }
+    }
+}
+
+impl Recency {
+    /// Returns the multiplier applied to the score of the crate described by
+    /// `signals`.
+    pub fn factor(&self, signals: &Signals<'_>) -> f32 {
+        let Some(age) = signals.days_since_first_release else { return 1. };
+        if self.weight == 0. || self.days == 0 {
+            return 1.;
+        }
+
+        let newness = (1. - age.max(0) as f32 / self.days as f32).max(0.);
+        (1. + self.weight * newness).max(0.)
+    }
+}
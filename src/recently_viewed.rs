@@ -0,0 +1,46 @@
+use axum::http::{HeaderMap, HeaderValue};
+
+use crate::signed_cookies::SignedCookies;
+
+/// The cookie listing the crates a visitor viewed most recently.
+const RECENT_COOKIE: &str = "recent";
+
+/// The most crates remembered in [`RECENT_COOKIE`].
+const MAX_RECENT: usize = 8;
+
+/// How long a visitor's history is kept after their last crate page view.
+const RECENT_MAX_AGE: u32 = 60 * 60 * 24 * 90;
+
+/// Reads and updates the signed cookie that remembers the crate pages a
+/// visitor viewed. The history is only stored in the visitor's browser.
+#[derive(Clone, Debug)]
+pub(crate) struct RecentlyViewed {
+    cookies: SignedCookies,
+}
+
+impl RecentlyViewed {
+    pub fn new(cookies: SignedCookies) -> Self {
+        Self { cookies }
+    }
+
+    /// Returns the names of the crates the request's visitor viewed, most
+    /// recent first. A missing or tampered cookie is an empty history.
+    pub fn read(&self, headers: &HeaderMap) -> Vec<String> {
+        self.cookies
+            .read(headers, RECENT_COOKIE)
+            .map(|names| names.split('.').map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the `Set-Cookie` header value that moves `name` to the front of
+    /// the request's history.
+    pub fn record(&self, headers: &HeaderMap, name: &str) -> Option<HeaderValue> {
+        let mut names = self.read(headers);
+        names.retain(|viewed| viewed != name);
+        names.insert(0, name.to_string());
+        names.truncate(MAX_RECENT);
+
+        self.cookies
+            .set(RECENT_COOKIE, &names.join("."), RECENT_MAX_AGE)
+    }
+}
@@ -0,0 +1,318 @@
+//! Recommendations of crates to use alongside a project's dependencies, based
+//! on the crates that other crates depend on together, as counted by
+//! [`cooccurrence::update`](crate::cooccurrence::update).
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{extract::State, http::StatusCode, Json};
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+use serde::Serialize;
+
+use crate::{
+    api::find_crate,
+    cache::Cache,
+    schema::CrateCooccurrence,
+    webserver::{internal_error, AppState},
+};
+
+/// The most of a manifest's dependencies that recommendations are based on.
+const MAX_DEPENDENCIES: usize = 50;
+
+const MAX_RECOMMENDATIONS: usize = 20;
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Recommendations {
+    /// The manifest's dependencies that recommendations are based on.
+    dependencies: Vec<String>,
+    /// The manifest's dependencies that aren't known crates.
+    unknown: Vec<String>,
+    /// Crates often used alongside the dependencies, most often first.
+    complementary: Vec<Complementary>,
+    /// Deprecated dependencies and the crates to consider instead.
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Serialize, Debug)]
+struct Complementary {
+    name: String,
+    description: String,
+    downloads: u64,
+    /// The dependencies the crate is used alongside.
+    used_with: Vec<String>,
+    /// How many crates use the crate alongside each of the dependencies,
+    /// summed over the dependencies.
+    cooccurrences: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct Alternative {
+    name: String,
+    replacements: Vec<String>,
+    note: String,
+}
+
+/// Recommends crates for the project described by the request body, which is
+/// the contents of its `Cargo.toml` or `Cargo.lock`.
+pub(crate) async fn recommend(
+    State((db, cache, _)): State<AppState>,
+    manifest: String,
+) -> Result<Json<Recommendations>, StatusCode> {
+    let names = dependency_names(&manifest).ok_or(StatusCode::BAD_REQUEST)?;
+    let recommendations = tokio::task::spawn_blocking(move || recommend_for(&db, &cache, names))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+    Ok(Json(recommendations))
+}
+
+/// Returns the names of the registry dependencies listed in `manifest`, a
+/// `Cargo.toml` or `Cargo.lock`, or None if it's neither. Path and git
+/// dependencies are left out, as are the dependencies of dependencies listed
+/// in a lockfile.
+fn dependency_names(manifest: &str) -> Option<Vec<String>> {
+    let manifest = toml::from_str::<toml::Table>(manifest).ok()?;
+    let mut names = Vec::new();
+    if let Some(packages) = manifest.get("package").and_then(toml::Value::as_array) {
+        // A lockfile lists every package, and only the workspace's own
+        // packages have no source. Their dependencies are named with the
+        // version when more than one version of a package is locked.
+        fn field<'a>(package: &'a toml::Value, key: &str) -> Option<&'a str> {
+            package.get(key).and_then(toml::Value::as_str)
+        }
+        let (members, registry): (Vec<_>, Vec<_>) = packages
+            .iter()
+            .partition(|package| package.get("source").is_none());
+        for member in members {
+            let dependencies = member
+                .get("dependencies")
+                .and_then(toml::Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(toml::Value::as_str);
+            for dependency in dependencies {
+                let mut parts = dependency.split_whitespace();
+                let Some(name) = parts.next() else { continue };
+                let version = parts.next();
+                let is_registry = registry.iter().any(|package| {
+                    field(package, "name") == Some(name)
+                        && version
+                            .map_or(true, |version| field(package, "version") == Some(version))
+                });
+                if is_registry {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    } else {
+        let targets = manifest
+            .get("target")
+            .and_then(toml::Value::as_table)
+            .into_iter()
+            .flat_map(|targets| targets.values().filter_map(toml::Value::as_table));
+        let workspace = manifest.get("workspace").and_then(toml::Value::as_table);
+        for table in std::iter::once(&manifest).chain(targets).chain(workspace) {
+            for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+                let Some(dependencies) = table.get(kind).and_then(toml::Value::as_table)
+                    else { continue };
+                for (name, dependency) in dependencies {
+                    if let Some(dependency) = dependency.as_table() {
+                        if dependency.contains_key("path") || dependency.contains_key("git") {
+                            continue;
+                        }
+                        if let Some(package) =
+                            dependency.get("package").and_then(toml::Value::as_str)
+                        {
+                            names.push(package.to_string());
+                            continue;
+                        }
+                    }
+                    names.push(name.clone());
+                }
+            }
+        }
+    }
+    names.sort_unstable();
+    names.dedup();
+    Some(names)
+}
+
+fn recommend_for(
+    db: &Database,
+    cache: &Cache,
+    names: Vec<String>,
+) -> anyhow::Result<Recommendations> {
+    let crates = cache.crates()?;
+    let mut dependencies = Vec::new();
+    let mut unknown = Vec::new();
+    let mut alternatives = Vec::new();
+    for name in names {
+        match find_crate(cache, &name)?.and_then(|id| crates.get(id)) {
+            Some(krate) => {
+                if let Some(alias) = krate.deprecation() {
+                    alternatives.push(Alternative {
+                        name: krate.name().to_string(),
+                        replacements: alias.replacements.clone(),
+                        note: alias.note.clone(),
+                    });
+                }
+                dependencies.push(krate);
+            }
+            None => unknown.push(name),
+        }
+    }
+    // The most popular dependencies are the most telling.
+    dependencies.sort_by(|a, b| b.recent_downloads().cmp(&a.recent_downloads()));
+    dependencies.truncate(MAX_DEPENDENCIES);
+    let dependency_ids = dependencies
+        .iter()
+        .map(|krate| krate.id())
+        .collect::<HashSet<_>>();
+
+    // Score each crate used with the dependencies by the fraction of each
+    // dependency's dependents that also use it, summed over the dependencies.
+    let mut candidates = HashMap::<u64, (f64, u64, Vec<u64>)>::new();
+    for dependency in &dependency_ids {
+        let Some(cooccurrence) = CrateCooccurrence::get(dependency, db)? else { continue };
+        let dependents = cooccurrence.contents.dependents.max(1) as f64;
+        for used_with in cooccurrence.contents.used_with {
+            if dependency_ids.contains(&used_with.crate_id) {
+                continue;
+            }
+            let (score, shared, dependencies) = candidates.entry(used_with.crate_id).or_default();
+            *score += used_with.shared as f64 / dependents;
+            *shared += used_with.shared;
+            dependencies.push(*dependency);
+        }
+    }
+
+    let mut complementary = candidates
+        .into_iter()
+        .filter_map(|(id, (score, count, used_with))| {
+            let krate = crates.get(id)?;
+            if krate.deprecation().is_some() || krate.blocked() {
+                return None;
+            }
+            let mut used_with = used_with
+                .into_iter()
+                .filter_map(|id| crates.get(id).map(|krate| krate.name().to_string()))
+                .collect::<Vec<_>>();
+            used_with.sort_unstable();
+            Some((
+                score,
+                Complementary {
+                    name: krate.name().to_string(),
+                    description: krate.description().to_string(),
+                    downloads: krate.downloads(),
+                    used_with,
+                    cooccurrences: count,
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+    complementary.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.downloads.cmp(&a.1.downloads)));
+    let complementary = complementary
+        .into_iter()
+        .take(MAX_RECOMMENDATIONS)
+        .map(|(_, complementary)| complementary)
+        .collect();
+
+    let mut dependencies = dependencies
+        .iter()
+        .map(|krate| krate.name().to_string())
+        .collect::<Vec<_>>();
+    dependencies.sort_unstable();
+    Ok(Recommendations {
+        dependencies,
+        unknown,
+        complementary,
+        alternatives,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_dependencies() {
+        let manifest = r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [dependencies]
+            serde = "1"
+            json = { package = "serde_json", version = "1" }
+            local = { path = "../local" }
+            forked = { git = "https://github.com/example/forked" }
+
+            [dev-dependencies]
+            tempfile = "3"
+
+            [target.'cfg(unix)'.build-dependencies]
+            cc = "1"
+
+            [workspace.dependencies]
+            tokio = { version = "1", features = ["full"] }
+        "#;
+        assert_eq!(
+            dependency_names(manifest).unwrap(),
+            ["cc", "serde", "serde_json", "tempfile", "tokio"]
+        );
+    }
+
+    #[test]
+    fn lockfile_dependencies() {
+        let lockfile = r#"
+            version = 3
+
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = [
+             "bitflags 1.3.2",
+             "local",
+             "serde",
+            ]
+
+            [[package]]
+            name = "local"
+            version = "0.1.0"
+            dependencies = [
+             "bitflags 2.0.0",
+            ]
+
+            [[package]]
+            name = "bitflags"
+            version = "1.3.2"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "bitflags"
+            version = "2.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "serde"
+            version = "1.0.160"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            dependencies = [
+             "serde_derive",
+            ]
+
+            [[package]]
+            name = "serde_derive"
+            version = "1.0.160"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+        "#;
+        // The workspace's members and the dependencies of its dependencies
+        // aren't included.
+        assert_eq!(dependency_names(lockfile).unwrap(), ["bitflags", "serde"]);
+    }
+
+    #[test]
+    fn invalid_manifests() {
+        assert!(dependency_names("[dependencies\nserde = 1").is_none());
+        assert_eq!(dependency_names("").unwrap(), Vec::<String>::new());
+    }
+}
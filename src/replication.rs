@@ -0,0 +1,261 @@
+//! Snapshots of the database that read-only replicas serve searches from.
+//!
+//! BonsaiDB's storage can only be opened by one process, so replicas don't
+//! share the writer's database. Instead, the writer backs up its database
+//! with BonsaiDB's backup API and copies its search index into the snapshot
+//! directory after each import. Each replica restores a private copy of the
+//! newest snapshot when it starts. Replicas don't switch to newer snapshots
+//! while running; they must be restarted to serve one.
+//!
+//! Anything a replica wrote to its copy would be lost when it's restarted, and
+//! wouldn't be seen by other processes, so replicas are read-only: they don't
+//! record clicks, searches, or experiment events, and they refuse requests
+//! that would write, such as requests metered against an API key's quota.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bonsaidb::local::Database;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::SearchIndex;
+
+/// How often a replica checks for a snapshot newer than the one it serves.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+/// The suffix of a snapshot that's still being copied.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// The directory of a snapshot containing the database's backup. Everything
+/// else in the snapshot is the search index's.
+const BACKUP_DIRECTORY: &str = "backup";
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Makes this process a read-only replica.
+pub fn enable_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if this process is a read-only replica, which mustn't write
+/// to its database.
+pub(crate) fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Responds with 421 Misdirected Request, telling the load balancer or client
+/// that only the importing process can serve the request.
+pub(crate) fn misdirected() -> Response {
+    (
+        StatusCode::MISDIRECTED_REQUEST,
+        "this server is a read-only replica, send the request to the primary",
+    )
+        .into_response()
+}
+
+/// Middleware that refuses requests on a replica whose method changes data.
+pub(crate) async fn reject_writes<B>(request: Request<B>, next: Next<B>) -> Response {
+    let reads = [Method::GET, Method::HEAD, Method::OPTIONS];
+    if is_read_only() && !reads.contains(request.method()) {
+        misdirected()
+    } else {
+        next.run(request).await
+    }
+}
+
+/// Where snapshots are published to and loaded from.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplicationConfig {
+    /// A directory shared by the writer and the replicas, such as a network
+    /// volume. The writer doesn't publish snapshots when unset.
+    pub snapshots: Option<PathBuf>,
+    /// How many snapshots the writer keeps. Older snapshots are deleted after
+    /// a new one is published, so this should leave enough time for replicas
+    /// that are starting to finish copying the snapshot they chose.
+    pub keep: usize,
+    /// The fewest minutes between snapshots. Each snapshot is a full backup
+    /// of the database, so imports finishing sooner than this after the
+    /// newest snapshot aren't published; the next snapshot includes them.
+    pub interval_minutes: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            snapshots: None,
+            keep: 3,
+            interval_minutes: 6 * 60,
+        }
+    }
+}
+
+/// Publishes a snapshot of the database and search index after each import.
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    database: Database,
+    index: SearchIndex,
+    snapshots: Arc<Path>,
+    keep: usize,
+    interval: Duration,
+}
+
+impl Publisher {
+    /// Returns a publisher of `database` and `index`, or None if `config`
+    /// doesn't configure a snapshot directory.
+    pub fn new(database: Database, index: SearchIndex, config: &ReplicationConfig) -> Option<Self> {
+        Some(Self {
+            database,
+            index,
+            snapshots: Arc::from(config.snapshots.clone()?),
+            keep: config.keep.max(1),
+            interval: Duration::from_secs(config.interval_minutes.saturating_mul(60)),
+        })
+    }
+
+    /// Backs up the database and copies the search index into a new
+    /// snapshot, then deletes the oldest snapshots. Nothing is published if
+    /// the newest snapshot was published less than the configured interval
+    /// ago.
+    ///
+    /// The backup reads every document through BonsaiDB rather than copying
+    /// its files, so each document is copied whole even though the server
+    /// keeps running. Documents that requests write during the backup, such
+    /// as clicks, may or may not be included, but everything the import wrote
+    /// is. The search index is copied as of its last commit, with imports and
+    /// rebuilds held off until the copy is done.
+    pub fn publish(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.snapshots)?;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if let Some(newest) = list(&self.snapshots)?.pop() {
+            let published = newest.parse::<i64>().unwrap_or(0);
+            let age = u64::try_from(now.saturating_sub(published)).unwrap_or(0);
+            if age < self.interval.as_secs() {
+                tracing::info!("not publishing a snapshot, {newest} is recent enough");
+                return Ok(());
+            }
+        }
+        // Only one import runs at a time, so any partial snapshot was left
+        // behind by a copy that failed.
+        for entry in std::fs::read_dir(&self.snapshots)? {
+            let entry = entry?;
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(PARTIAL_SUFFIX)
+            {
+                std::fs::remove_dir_all(entry.path())?;
+            }
+        }
+        // Zero-padded timestamps sort in the order they were published.
+        let name = format!("{now:020}");
+        let partial = self.snapshots.join(format!("{name}{PARTIAL_SUFFIX}"));
+        std::fs::create_dir_all(&partial)?;
+        self.database
+            .storage()
+            .backup(&partial.join(BACKUP_DIRECTORY))?;
+        self.index.snapshot(&partial)?;
+        // Renaming publishes the snapshot only once it's complete.
+        std::fs::rename(&partial, self.snapshots.join(&name))?;
+        tracing::info!("published snapshot {name}");
+
+        let published = list(&self.snapshots)?;
+        for old in published.iter().rev().skip(self.keep) {
+            std::fs::remove_dir_all(self.snapshots.join(old))?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the database at `destination` with one restored from the newest
+/// snapshot, returning the snapshot's name. This must be called before the
+/// database is opened.
+pub fn load_latest(config: &ReplicationConfig, destination: &Path) -> anyhow::Result<String> {
+    let Some(snapshots) = &config.snapshots
+        else { anyhow::bail!("replicas require `snapshots` to be set under [replication]") };
+    let Some(name) = list(snapshots)?.pop()
+        else { anyhow::bail!("{} doesn't contain any snapshots yet", snapshots.display()) };
+    if destination.exists() {
+        std::fs::remove_dir_all(destination)?;
+    }
+    let snapshot = snapshots.join(&name);
+    // Restoring creates the database, so the storage is opened without it.
+    crate::open_storage(destination)?.restore(&snapshot.join(BACKUP_DIRECTORY))?;
+    for entry in std::fs::read_dir(&snapshot)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if entry.file_name() == BACKUP_DIRECTORY {
+            continue;
+        } else if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    tracing::info!("loaded snapshot {name}");
+    Ok(name)
+}
+
+/// Logs when a snapshot newer than `loaded` is published. A replica keeps
+/// serving the snapshot it started with, so replicas must be restarted, one
+/// at a time to keep serving searches, to serve the newer snapshot.
+pub async fn watch(config: ReplicationConfig, loaded: String) {
+    let Some(snapshots) = config.snapshots else { return };
+    let mut newest = loaded;
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        match list(&snapshots) {
+            Ok(mut published) => {
+                if let Some(name) = published.pop().filter(|name| *name > newest) {
+                    tracing::warn!(
+                        "snapshot {name} is newer than the one served, restart this replica \
+                         to serve it"
+                    );
+                    newest = name;
+                }
+            }
+            Err(err) => tracing::error!("error listing snapshots: {err}"),
+        }
+    }
+}
+
+/// Returns the names of the complete snapshots in `snapshots`, oldest first.
+fn list(snapshots: &Path) -> anyhow::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(snapshots)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else { continue };
+        if entry.file_type()?.is_dir() && !name.ends_with(PARTIAL_SUFFIX) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Copies the directory `source` and everything in it to `destination`.
+pub(crate) fn copy_dir(source: &Path, destination: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! Dependency review reports, which summarize what a reviewer needs to know
+//! about each of a project's dependencies in one printable page: licenses,
+//! advisories, how well maintained each crate appears to be, its owners, and
+//! whether its downloads are growing.
+
+use std::collections::BTreeMap;
+
+use askama::Template;
+use axum::{
+    extract::State,
+    http::{header::RETRY_AFTER, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::{
+    api::{advisories_for, find_crate, AdvisorySummary},
+    cache::Cache,
+    ranking::MaintenanceWeighted,
+    rollup,
+    schema::{self, OwnersByCrate, VersionsByCrate},
+    webserver::{self, internal_error, AppState},
+};
+
+/// The most crates a single report covers. Lockfiles of large workspaces list
+/// several hundred.
+const MAX_REPORT_CRATES: usize = 1_000;
+
+/// The most reports generated at once. Each report reads every requested
+/// crate's versions, advisories, and downloads, so requests beyond this are
+/// asked to retry rather than queued.
+const MAX_CONCURRENT_REPORTS: usize = 2;
+
+/// The `source` of crates.io packages in a lockfile, from its git index and
+/// its sparse index.
+const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+static REPORTS: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(MAX_CONCURRENT_REPORTS));
+
+/// The length of the periods whose downloads are compared to find a crate's
+/// download trend.
+const TREND_DAYS: i64 = 90;
+
+#[derive(Deserialize, Debug)]
+struct ReportRequest {
+    crates: Vec<RequestedCrate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RequestedCrate {
+    name: String,
+    /// The version in use. Without one, the report describes the latest
+    /// version.
+    version: Option<String>,
+}
+
+#[derive(Template, Serialize, Debug)]
+#[template(path = "report.html")]
+pub(crate) struct ReviewReport {
+    /// The date the report was generated.
+    generated: String,
+    crates: Vec<ReportEntry>,
+    /// The number of crates under each license, with crates without a known
+    /// license counted under `unknown`.
+    licenses: BTreeMap<String, usize>,
+    /// The requested crates that aren't known crates.
+    unknown: Vec<String>,
+}
+
+impl ReviewReport {
+    /// Returns the number of crates with advisories affecting them.
+    pub fn with_advisories(&self) -> usize {
+        self.crates
+            .iter()
+            .filter(|entry| !entry.advisories.is_empty())
+            .count()
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ReportEntry {
+    name: String,
+    /// The version in use, or None if the request didn't say.
+    version: Option<String>,
+    latest_version: Option<String>,
+    /// The license of the version in use, or of the latest version.
+    license: Option<String>,
+    /// True if the version in use has been yanked.
+    yanked: bool,
+    /// The advisories affecting the version in use, or every open advisory
+    /// of the crate when no version was given. Informational advisories are
+    /// in `notices` instead.
+    advisories: Vec<AdvisorySummary>,
+    /// The crate's open informational advisories, such as notices that it's
+    /// unmaintained.
+    notices: Vec<AdvisorySummary>,
+    /// How well maintained the crate appears to be, from 0.1 to 1, as used by
+    /// the `maintained` ranker.
+    maintenance: f32,
+    unmaintained: bool,
+    deprecated: bool,
+    /// The logins of the crate's owners.
+    owners: Vec<String>,
+    /// Downloads in the last 90 days.
+    recent_downloads: u64,
+    /// Downloads in the 90 days before that.
+    previous_downloads: u64,
+}
+
+impl ReportEntry {
+    /// Describes the change from `previous_downloads` to `recent_downloads`,
+    /// such as `+12%`.
+    pub fn trend(&self) -> String {
+        if self.previous_downloads == 0 {
+            return String::from("new");
+        }
+        let change = self.recent_downloads as f64 / self.previous_downloads as f64 - 1.;
+        format!("{:+.0}%", change * 100.)
+    }
+
+    pub fn maintenance_percent(&self) -> String {
+        format!("{:.0}%", self.maintenance * 100.)
+    }
+}
+
+/// Generates a dependency review of the crates in the request body, which is
+/// either a JSON list of crates or a `Cargo.lock`. The review is a printable
+/// HTML page, or JSON when the request prefers it. When
+/// [`MAX_CONCURRENT_REPORTS`] reports are already being generated, responds
+/// with 429 Too Many Requests.
+pub(crate) async fn generate(
+    State((db, cache, _)): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, StatusCode> {
+    let Ok(_permit) = REPORTS.try_acquire()
+        else { return Ok((StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, "5")]).into_response()) };
+    let mut requested = requested_crates(&body).ok_or(StatusCode::BAD_REQUEST)?;
+    if requested.len() > MAX_REPORT_CRATES {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    requested.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    requested.dedup_by(|a, b| a.name == b.name && a.version == b.version);
+
+    let report = tokio::task::spawn_blocking(move || report_for(&db, &cache, requested))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+    if webserver::prefers_json(&headers) {
+        Ok(Json(report).into_response())
+    } else {
+        Ok(Html(report.render().map_err(internal_error)?).into_response())
+    }
+}
+
+/// Returns the crates listed in `body`, a JSON [`ReportRequest`] or a
+/// `Cargo.lock`, or None if it's neither. A lockfile's workspace members, git
+/// and path dependencies, and crates from other registries are left out,
+/// since other registries' crates can share names with unrelated crates.io
+/// crates.
+fn requested_crates(body: &str) -> Option<Vec<RequestedCrate>> {
+    if body.trim_start().starts_with('{') {
+        return serde_json::from_str::<ReportRequest>(body)
+            .ok()
+            .map(|request| request.crates);
+    }
+
+    let lockfile = toml::from_str::<toml::Table>(body).ok()?;
+    let packages = lockfile.get("package")?.as_array()?;
+    Some(
+        packages
+            .iter()
+            .filter(|package| {
+                package
+                    .get("source")
+                    .and_then(toml::Value::as_str)
+                    .map_or(false, |source| CRATES_IO_SOURCES.contains(&source))
+            })
+            .filter_map(|package| {
+                Some(RequestedCrate {
+                    name: package.get("name")?.as_str()?.to_string(),
+                    version: package
+                        .get("version")
+                        .and_then(toml::Value::as_str)
+                        .map(String::from),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn report_for(
+    db: &Database,
+    cache: &Cache,
+    requested: Vec<RequestedCrate>,
+) -> anyhow::Result<ReviewReport> {
+    let crates = cache.crates()?;
+    let today = time::OffsetDateTime::now_utc().date();
+    let mut found = Vec::new();
+    let mut unknown = Vec::new();
+    for request in requested {
+        match find_crate(cache, &request.name)?.and_then(|id| crates.get(id)) {
+            Some(krate) => found.push((krate, request.version)),
+            None => unknown.push(request.name),
+        }
+    }
+
+    let mut entries = Vec::with_capacity(found.len());
+    let mut licenses = BTreeMap::new();
+    for (krate, version) in found {
+        let versions = VersionsByCrate::entries(db).with_key(&krate.id()).query()?;
+        let described = version.as_deref().or(krate.latest_version());
+        let in_use = versions
+            .iter()
+            .find(|mapping| Some(mapping.value.version.as_str()) == described);
+        let license = match in_use {
+            Some(mapping) => schema::Version::get(&mapping.source.id.deserialize::<u64>()?, db)?
+                .map(|version| version.contents.license)
+                .filter(|license| !license.is_empty()),
+            None => None,
+        };
+        let parsed = version
+            .as_deref()
+            .and_then(|version| semver::Version::parse(version).ok());
+        let mut advisories = Vec::new();
+        let mut notices = Vec::new();
+        for (id, advisory) in advisories_for(db, krate.name())? {
+            if advisory.withdrawn.is_some() {
+                continue;
+            } else if advisory.is_informational() {
+                notices.push(AdvisorySummary::new(id, advisory));
+            } else if parsed
+                .as_ref()
+                .map_or(true, |version| advisory.affects(version))
+            {
+                advisories.push(AdvisorySummary::new(id, advisory));
+            }
+        }
+
+        *licenses
+            .entry(license.clone().unwrap_or_else(|| String::from("unknown")))
+            .or_default() += 1;
+        entries.push(ReportEntry {
+            name: krate.name().to_string(),
+            yanked: version.is_some() && in_use.map_or(false, |mapping| mapping.value.yanked),
+            version,
+            latest_version: krate.latest_version().map(String::from),
+            license,
+            advisories,
+            notices,
+            maintenance: MaintenanceWeighted::maintenance(
+                &krate,
+                krate
+                    .last_published()
+                    .map(|published| (today - published).whole_days()),
+            ),
+            unmaintained: krate.unmaintained(),
+            deprecated: krate.deprecation().is_some(),
+            owners: OwnersByCrate::entries(db)
+                .with_key(&krate.id())
+                .query()?
+                .into_iter()
+                .map(|mapping| mapping.value)
+                .collect(),
+            recent_downloads: rollup::crate_downloads(
+                db,
+                krate.id(),
+                today - Duration::days(TREND_DAYS - 1),
+                today,
+            )?,
+            previous_downloads: rollup::crate_downloads(
+                db,
+                krate.id(),
+                today - Duration::days(TREND_DAYS * 2 - 1),
+                today - Duration::days(TREND_DAYS),
+            )?,
+        });
+    }
+
+    Ok(ReviewReport {
+        generated: today.to_string(),
+        crates: entries,
+        licenses,
+        unknown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(requested: &[RequestedCrate]) -> Vec<(&str, Option<&str>)> {
+        requested
+            .iter()
+            .map(|krate| (krate.name.as_str(), krate.version.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn json_requests() {
+        let requested = requested_crates(
+            r#"{"crates": [{"name": "serde", "version": "1.0.160"}, {"name": "tokio"}]}"#,
+        )
+        .expect("valid request");
+        assert_eq!(
+            names(&requested),
+            [("serde", Some("1.0.160")), ("tokio", None)]
+        );
+        assert!(requested_crates(r#"{"crates": "serde"}"#).is_none());
+        assert!(requested_crates("not a lockfile").is_none());
+    }
+
+    #[test]
+    fn version_3_lockfiles() {
+        let lockfile = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde", "private", "forked", "local"]
+
+[[package]]
+name = "serde"
+version = "1.0.160"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bb2f3770c8bce3bcda7e149193a069a0f4365bda1fa5cd88e03bca26afc1216c"
+
+[[package]]
+name = "anyhow"
+version = "1.0.70"
+source = "sparse+https://index.crates.io/"
+
+[[package]]
+name = "private"
+version = "2.0.0"
+source = "registry+https://my-intranet:8080/git/index"
+
+[[package]]
+name = "forked"
+version = "0.3.0"
+source = "git+https://github.com/example/forked?branch=main#0123456789abcdef"
+
+[[package]]
+name = "local"
+version = "0.1.0"
+"#;
+        let requested = requested_crates(lockfile).expect("valid lockfile");
+        assert_eq!(
+            names(&requested),
+            [("serde", Some("1.0.160")), ("anyhow", Some("1.0.70"))]
+        );
+    }
+
+    #[test]
+    fn version_1_lockfiles() {
+        let lockfile = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "log 0.4.17 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "log"
+version = "0.4.17"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum log 0.4.17 (registry+https://github.com/rust-lang/crates.io-index)" = "abcd"
+"#;
+        let requested = requested_crates(lockfile).expect("valid lockfile");
+        assert_eq!(names(&requested), [("log", Some("0.4.17"))]);
+    }
+
+    fn entry(recent_downloads: u64, previous_downloads: u64) -> ReportEntry {
+        ReportEntry {
+            name: String::from("serde"),
+            version: None,
+            latest_version: None,
+            license: None,
+            yanked: false,
+            advisories: Vec::new(),
+            notices: Vec::new(),
+            maintenance: 1.,
+            unmaintained: false,
+            deprecated: false,
+            owners: Vec::new(),
+            recent_downloads,
+            previous_downloads,
+        }
+    }
+
+    #[test]
+    fn trends() {
+        assert_eq!(entry(112, 100).trend(), "+12%");
+        assert_eq!(entry(100, 100).trend(), "+0%");
+        assert_eq!(entry(25, 100).trend(), "-75%");
+        assert_eq!(entry(0, 100).trend(), "-100%");
+        assert_eq!(entry(10, 0).trend(), "new");
+        assert_eq!(entry(0, 0).trend(), "new");
+    }
+}
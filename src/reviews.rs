@@ -0,0 +1,604 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use bonsaidb::{
+    core::{
+        document::Header,
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Deserialize;
+
+use crate::{
+    archive,
+    schema::{Crate, Review, ReviewKind, ReviewsByCrate},
+};
+
+/// The directory within the data directory that crev proof repositories are
+/// extracted into.
+const CREV_DIRECTORY: &str = "crev-proofs";
+
+/// Where community reviews of crates are imported from after each import.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewsConfig {
+    /// The URLs of cargo-vet `audits.toml` files, keyed by the name of the
+    /// organization publishing them. Crates are shown as audited by these
+    /// names.
+    pub vet: BTreeMap<String, String>,
+    /// The URLs of crev proof repositories hosted on GitHub.
+    pub crev: Vec<String>,
+}
+
+impl Default for ReviewsConfig {
+    /// The audits listed in cargo-vet's registry of well-known audit files,
+    /// and the proofs of crev's author.
+    fn default() -> Self {
+        let vet = [
+            (
+                "bytecode-alliance",
+                "bytecodealliance/wasmtime/main/supply-chain/audits.toml",
+            ),
+            (
+                "embark-studios",
+                "EmbarkStudios/rust-ecosystem/main/audits.toml",
+            ),
+            ("google", "google/supply-chain/main/audits.toml"),
+            ("isrg", "divviup/libprio-rs/main/supply-chain/audits.toml"),
+            ("mozilla", "mozilla/supply-chain/main/audits.toml"),
+            (
+                "zcash",
+                "zcash/rust-ecosystem/main/supply-chain/audits.toml",
+            ),
+        ];
+        Self {
+            vet: vet
+                .into_iter()
+                .map(|(name, path)| {
+                    (
+                        name.to_string(),
+                        format!("https://raw.githubusercontent.com/{path}"),
+                    )
+                })
+                .collect(),
+            crev: vec![String::from("https://github.com/dpc/crev-proofs")],
+        }
+    }
+}
+
+/// Downloads every source in `config` and replaces the reviews imported from
+/// it. Crev proof repositories are extracted in `data_dir`. Sources that
+/// can't be downloaded or parsed are logged and keep their previously imported
+/// reviews.
+pub(crate) async fn import(
+    database: &Database,
+    config: &ReviewsConfig,
+    data_dir: &Path,
+) -> anyhow::Result<()> {
+    println!("Importing reviews.");
+    let mut imported = HashMap::<String, Vec<(String, Review)>>::new();
+    for (name, url) in &config.vet {
+        match download_vet_audits(name, url).await {
+            Ok(reviews) => {
+                imported.insert(name.clone(), reviews);
+            }
+            Err(err) => tracing::error!("error importing cargo-vet audits from {url}: {err:#}"),
+        }
+    }
+    for (index, url) in config.crev.iter().enumerate() {
+        let directory = data_dir.join(CREV_DIRECTORY).join(index.to_string());
+        match download_crev_proofs(url, directory).await {
+            Ok(reviews) => {
+                imported.insert(url.clone(), reviews);
+            }
+            Err(err) => tracing::error!("error importing crev proofs from {url}: {err:#}"),
+        }
+    }
+
+    let configured = config
+        .vet
+        .keys()
+        .chain(&config.crev)
+        .cloned()
+        .collect::<HashSet<_>>();
+    let database = database.clone();
+    tokio::task::spawn_blocking(move || store(&database, &configured, imported)).await?
+}
+
+/// Replaces the reviews from each source in `imported` with the reviews
+/// imported from it, and deletes the reviews from sources that are no longer
+/// `configured`.
+fn store(
+    db: &Database,
+    configured: &HashSet<String>,
+    imported: HashMap<String, Vec<(String, Review)>>,
+) -> anyhow::Result<()> {
+    let mut existing = Review::all(db)
+        .query()?
+        .into_iter()
+        .filter(|review| {
+            imported.contains_key(&review.contents.source)
+                || !configured.contains(&review.contents.source)
+        })
+        .map(|review| (review.header.id.clone(), review))
+        .collect::<HashMap<_, _>>();
+
+    let mut tx = Transaction::new();
+    let mut ids = HashSet::new();
+    for (id, review) in imported.into_values().flatten() {
+        // The same review can be listed more than once in an audit file.
+        if !ids.insert(id.clone()) {
+            continue;
+        }
+        match existing.remove(&id) {
+            Some(existing) if existing.contents == review => {}
+            _ => tx
+                .operations
+                .push(Operation::overwrite_serialized::<Review, _>(&id, &review)?),
+        }
+    }
+    for stale in existing.into_values() {
+        tx.operations.push(Operation::delete(
+            Review::collection_name(),
+            Header::try_from(stale.header)?,
+        ));
+    }
+
+    println!("Committing {} review changes", tx.operations.len());
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+    Ok(())
+}
+
+/// Returns the reviews of every version of the crate named `crate_name`,
+/// favorable reviews first.
+pub(crate) fn for_crate(db: &Database, crate_name: &str) -> anyhow::Result<Vec<Review>> {
+    let mut reviews = Vec::new();
+    for mapping in ReviewsByCrate::entries(db)
+        .with_key(&Crate::normalized_name(crate_name))
+        .query()?
+    {
+        let id = mapping.source.id.deserialize::<String>()?;
+        if let Some(review) = Review::get(&id, db)? {
+            reviews.push(review.contents);
+        }
+    }
+    reviews.sort_by(|a, b| {
+        b.favorable
+            .cmp(&a.favorable)
+            .then_with(|| a.source.cmp(&b.source))
+    });
+    Ok(reviews)
+}
+
+/// Returns the sources of the favorable reviews in `reviews`, once each.
+pub(crate) fn audited_by(reviews: &[Review]) -> Vec<String> {
+    let mut sources = reviews
+        .iter()
+        .filter(|review| review.favorable)
+        .map(|review| review.source.clone())
+        .collect::<Vec<_>>();
+    sources.sort();
+    sources.dedup();
+    sources
+}
+
+async fn download_vet_audits(name: &str, url: &str) -> anyhow::Result<Vec<(String, Review)>> {
+    let contents = reqwest::get(url).await?.error_for_status()?.text().await?;
+    parse_vet_audits(name, &contents)
+}
+
+/// Parses a cargo-vet `audits.toml` file published by the organization
+/// `name`. Violations, which mark versions that fail a criteria instead of
+/// certifying them, aren't imported.
+fn parse_vet_audits(name: &str, contents: &str) -> anyhow::Result<Vec<(String, Review)>> {
+    let file: VetAudits = toml::from_str(contents)?;
+    let mut reviews = Vec::new();
+    for (crate_name, audits) in file.audits {
+        for audit in audits {
+            let version = match (audit.version, audit.delta) {
+                (Some(version), _) => version,
+                (None, Some(delta)) => delta,
+                (None, None) => continue,
+            };
+            let criteria = match audit.criteria {
+                VetCriteria::One(criteria) => criteria,
+                VetCriteria::Many(criteria) => criteria.join(", "),
+            };
+            reviews.push((
+                format!("vet:{name}:{crate_name}:{version}:{criteria}"),
+                Review {
+                    crate_name: crate_name.clone(),
+                    version,
+                    kind: ReviewKind::Vet,
+                    source: name.to_string(),
+                    reviewer: audit.who.map(VetWho::join).unwrap_or_default(),
+                    verdict: criteria,
+                    favorable: true,
+                    notes: audit.notes,
+                },
+            ));
+        }
+    }
+    Ok(reviews)
+}
+
+#[derive(Deserialize, Debug)]
+struct VetAudits {
+    #[serde(default)]
+    audits: BTreeMap<String, Vec<VetAudit>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VetAudit {
+    who: Option<VetWho>,
+    criteria: VetCriteria,
+    version: Option<String>,
+    delta: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum VetWho {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl VetWho {
+    fn join(self) -> String {
+        match self {
+            Self::One(who) => who,
+            Self::Many(who) => who.join(", "),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum VetCriteria {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Downloads the crev proof repository at `url` from GitHub, extracting it
+/// into `directory`.
+async fn download_crev_proofs(
+    url: &str,
+    directory: PathBuf,
+) -> anyhow::Result<Vec<(String, Review)>> {
+    let archive_url = format!("{}/archive/HEAD.tar.gz", url.trim_end_matches('/'));
+    let response = reqwest::get(&archive_url).await?.error_for_status()?;
+    if directory.exists() {
+        tokio::fs::remove_dir_all(&directory).await?;
+    }
+    tokio::fs::create_dir_all(&directory).await?;
+    archive::download_and_extract(response, &directory.join("proofs.tar.gz"), &directory).await?;
+
+    let url = url.to_string();
+    tokio::task::spawn_blocking(move || {
+        let mut reviews = Vec::new();
+        read_crev_proofs(&directory, &url, &mut reviews)?;
+        Ok(reviews)
+    })
+    .await?
+}
+
+/// Reads the package reviews in every `.crev` file in `directory` and its
+/// subdirectories.
+fn read_crev_proofs(
+    directory: &Path,
+    source: &str,
+    reviews: &mut Vec<(String, Review)>,
+) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            read_crev_proofs(&path, source, reviews)?;
+        } else if path.extension().map_or(false, |ext| ext == "crev") {
+            let contents = std::fs::read_to_string(&path)?;
+            reviews.extend(parse_crev_proofs(source, &contents));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the crates.io package reviews in a file of crev proofs. Proofs
+/// that aren't valid YAML are logged and skipped.
+///
+/// Signatures aren't verified, so the reviews are only as trustworthy as the
+/// repository they're downloaded from: anyone able to push to it could add
+/// reviews attributed to any crev id.
+fn parse_crev_proofs(source: &str, contents: &str) -> Vec<(String, Review)> {
+    const BEGIN: &str = "-----BEGIN CREV PACKAGE REVIEW-----";
+    const SIGNATURE: &str = "-----BEGIN CREV PACKAGE REVIEW SIGNATURE-----";
+
+    let mut reviews = Vec::new();
+    let mut rest = contents;
+    while let Some(start) = rest.find(BEGIN) {
+        let body = &rest[start + BEGIN.len()..];
+        let end = body.find(SIGNATURE).unwrap_or(body.len());
+        rest = &body[end..];
+
+        let proof = match serde_yaml::from_str::<CrevProof>(&body[..end]) {
+            Ok(proof) => proof,
+            Err(err) => {
+                tracing::warn!("skipping malformed crev proof from {source}: {err}");
+                continue;
+            }
+        };
+        if !proof.package.source.contains("crates.io") {
+            continue;
+        }
+        let version = match proof.package.version {
+            serde_yaml::Value::String(version) => version,
+            // Versions such as `1.0` are written without quotes, so they're
+            // parsed as numbers.
+            serde_yaml::Value::Number(version) => version.to_string(),
+            _ => continue,
+        };
+        let rating = proof
+            .review
+            .map_or_else(|| String::from("neutral"), |review| review.rating);
+        reviews.push((
+            format!("crev:{}:{}:{version}", proof.from.id, proof.package.name),
+            Review {
+                crate_name: proof.package.name,
+                version,
+                kind: ReviewKind::Crev,
+                source: source.to_string(),
+                reviewer: proof.from.url.unwrap_or(proof.from.id),
+                favorable: matches!(rating.as_str(), "positive" | "strong"),
+                verdict: rating,
+                notes: proof
+                    .comment
+                    .map(|comment| comment.trim().to_string())
+                    .filter(|comment| !comment.is_empty()),
+            },
+        ));
+    }
+    reviews
+}
+
+/// The fields of a crev package review that are imported.
+#[derive(Deserialize, Debug)]
+struct CrevProof {
+    from: CrevId,
+    package: CrevPackage,
+    review: Option<CrevReview>,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrevId {
+    id: String,
+    url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrevPackage {
+    source: String,
+    name: String,
+    version: serde_yaml::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrevReview {
+    rating: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An excerpt of a cargo-vet `audits.toml`, in the format of the files
+    /// listed in cargo-vet's registry.
+    const VET_AUDITS: &str = r#"
+[criteria.does-not-implement-crypto]
+description = """
+Inspected to confirm that the crate does not implement any cryptographic
+algorithms.
+"""
+
+[[wildcard-audits.bytecodealliance]]
+who = "Nick Fitzgerald <fitzgen@gmail.com>"
+criteria = "safe-to-deploy"
+user-id = 73222
+start = "2019-04-01"
+end = "2024-04-01"
+
+[[audits.arbitrary]]
+who = "Nick Fitzgerald <fitzgen@gmail.com>"
+criteria = "safe-to-deploy"
+version = "1.1.0"
+notes = "I am the author of this crate."
+
+[[audits.arbitrary]]
+who = "Nick Fitzgerald <fitzgen@gmail.com>"
+criteria = "safe-to-deploy"
+delta = "1.1.0 -> 1.1.3"
+
+[[audits.bitflags]]
+who = ["Alex Crichton <alex@alexcrichton.com>", "Jamey Sharp <jsharp@fastly.com>"]
+criteria = ["safe-to-deploy", "does-not-implement-crypto"]
+version = "1.3.2"
+
+[[audits.openssl-src]]
+criteria = "safe-to-deploy"
+violation = "<111.0.0"
+notes = "Bundles a vulnerable OpenSSL."
+
+[[trusted.cc]]
+criteria = "safe-to-deploy"
+user-id = 55123
+start = "2022-10-29"
+end = "2024-06-26"
+"#;
+
+    /// Package reviews in the format written by `cargo crev`, the second of
+    /// which isn't from crates.io.
+    const CREV_PROOFS: &str = r#"
+-----BEGIN CREV PACKAGE REVIEW-----
+version: -1
+date: "2019-08-28T23:53:50.165419084-07:00"
+from:
+  id-type: crev
+  id: FYlr8YoYGVvDwHQxqEIs89reKKDy-oWisoO0qXXEfHE
+  url: "https://github.com/dpc/crev-proofs"
+package:
+  source: "https://crates.io"
+  name: toml
+  version: 0.5.3
+  digest: 1R3lQ5ueStcaBxdUNkmnJuRnF7bXUTx6SWXWW0Qb-1I
+review:
+  thoroughness: low
+  understanding: medium
+  rating: positive
+comment: |-
+  Reviewed the parser and serializer.
+  No unsafe code.
+-----BEGIN CREV PACKAGE REVIEW SIGNATURE-----
+4R2WjtU-avpSznmJYAl44H1lOYgETu3RSNhCDal1FWbxmhpyHVGXfUBUW7Xl3jSgQ4CQ3DSHlDUiKNDmLhtdBg
+-----END CREV PACKAGE REVIEW-----
+-----BEGIN CREV PACKAGE REVIEW-----
+version: -1
+date: "2019-09-01T10:12:31.029101283-07:00"
+from:
+  id-type: crev
+  id: FYlr8YoYGVvDwHQxqEIs89reKKDy-oWisoO0qXXEfHE
+  url: "https://github.com/dpc/crev-proofs"
+package:
+  source: "https://github.com/dpc/crev"
+  name: crev-lib
+  version: 0.8.0
+  digest: aXvM7ovR2s6gOCR1d3pYqxb9ib9IZk9kdKZN5TJQ6YI
+review:
+  thoroughness: high
+  understanding: high
+  rating: strong
+-----BEGIN CREV PACKAGE REVIEW SIGNATURE-----
+m0sMa1gzeI7Yz1cWLZ-5CY1cN3S1ZvmLfEsIRv8p8VbVJQ9Cc2uQaLd8gVq7y6jc5PtRjDl2fJKvfHeRBWYmAg
+-----END CREV PACKAGE REVIEW-----
+-----BEGIN CREV PACKAGE REVIEW-----
+version: -1
+date: "2020-01-12T08:41:09.284902126+01:00"
+from:
+  id-type: crev
+  id: YWfa4SGgcW87fIT88uCkkrsRgIbWiGOOYmBbA1AtnKA
+package:
+  source: "https://crates.io"
+  name: rand
+  version: 1.0
+  digest: y3hGmBQwrf2GdBbtt1OYjzrh3JZ4ZLmBsDSgYKHX4-A
+review:
+  thoroughness: medium
+  understanding: medium
+  rating: negative
+-----BEGIN CREV PACKAGE REVIEW SIGNATURE-----
+b6ZsPVX7RZf9Ef6pV6kGSGWZbpHgpD4YsgPeLqBm3pBq9yXx-0Jc9_4Kr6ecCbOeVYjzTbjLdcQZoD2Cr4XVBg
+-----END CREV PACKAGE REVIEW-----
+"#;
+
+    #[test]
+    fn vet_audits_are_parsed() {
+        let reviews = parse_vet_audits("bytecode-alliance", VET_AUDITS).unwrap();
+        let ids = reviews
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                "vet:bytecode-alliance:arbitrary:1.1.0:safe-to-deploy",
+                "vet:bytecode-alliance:arbitrary:1.1.0 -> 1.1.3:safe-to-deploy",
+                "vet:bytecode-alliance:bitflags:1.3.2:safe-to-deploy, does-not-implement-crypto",
+            ]
+        );
+
+        let (_, author) = &reviews[0];
+        assert_eq!(author.crate_name, "arbitrary");
+        assert_eq!(author.kind, ReviewKind::Vet);
+        assert_eq!(author.source, "bytecode-alliance");
+        assert_eq!(author.reviewer, "Nick Fitzgerald <fitzgen@gmail.com>");
+        assert!(author.favorable);
+        assert_eq!(
+            author.notes.as_deref(),
+            Some("I am the author of this crate.")
+        );
+
+        let (_, shared) = &reviews[2];
+        assert_eq!(
+            shared.reviewer,
+            "Alex Crichton <alex@alexcrichton.com>, Jamey Sharp <jsharp@fastly.com>"
+        );
+        assert_eq!(shared.notes, None);
+    }
+
+    #[test]
+    fn invalid_vet_audits_are_errors() {
+        assert!(parse_vet_audits("org", "[[audits.serde]]\nversion = \"1.0.0\"\n").is_err());
+        assert!(parse_vet_audits("org", "not toml").is_err());
+        assert!(parse_vet_audits("org", "").unwrap().is_empty());
+    }
+
+    #[test]
+    fn crev_proofs_are_parsed() {
+        let source = "https://github.com/dpc/crev-proofs";
+        let reviews = parse_crev_proofs(source, CREV_PROOFS);
+        assert_eq!(reviews.len(), 2);
+
+        let (id, positive) = &reviews[0];
+        assert_eq!(
+            id,
+            "crev:FYlr8YoYGVvDwHQxqEIs89reKKDy-oWisoO0qXXEfHE:toml:0.5.3"
+        );
+        assert_eq!(positive.crate_name, "toml");
+        assert_eq!(positive.version, "0.5.3");
+        assert_eq!(positive.kind, ReviewKind::Crev);
+        assert_eq!(positive.source, source);
+        assert_eq!(positive.reviewer, "https://github.com/dpc/crev-proofs");
+        assert_eq!(positive.verdict, "positive");
+        assert!(positive.favorable);
+        assert_eq!(
+            positive.notes.as_deref(),
+            Some("Reviewed the parser and serializer.\nNo unsafe code.")
+        );
+
+        // Without a URL, the reviewer is identified by their id. The version
+        // is unquoted, so it's parsed as a number.
+        let (_, negative) = &reviews[1];
+        assert_eq!(negative.crate_name, "rand");
+        assert_eq!(negative.version, "1.0");
+        assert_eq!(
+            negative.reviewer,
+            "YWfa4SGgcW87fIT88uCkkrsRgIbWiGOOYmBbA1AtnKA"
+        );
+        assert_eq!(negative.verdict, "negative");
+        assert!(!negative.favorable);
+        assert_eq!(negative.notes, None);
+    }
+
+    #[test]
+    fn malformed_crev_proofs_are_skipped() {
+        let malformed = "-----BEGIN CREV PACKAGE REVIEW-----\n\
+                         from:\n  id: abc\npackage: [unclosed\n\
+                         -----BEGIN CREV PACKAGE REVIEW SIGNATURE-----\n\
+                         signature\n\
+                         -----END CREV PACKAGE REVIEW-----\n";
+        let missing_package = "-----BEGIN CREV PACKAGE REVIEW-----\n\
+                               from:\n  id: abc\n\
+                               -----BEGIN CREV PACKAGE REVIEW SIGNATURE-----\n\
+                               signature\n\
+                               -----END CREV PACKAGE REVIEW-----\n";
+        let contents = format!("{malformed}{missing_package}{CREV_PROOFS}");
+        let reviews = parse_crev_proofs("source", &contents);
+        assert_eq!(reviews.len(), 2);
+        assert!(parse_crev_proofs("source", "").is_empty());
+    }
+}
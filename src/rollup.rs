@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bonsaidb::{
+    core::{
+        schema::{Collection, SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::schema::{
+    CalendarDate, DownloadSummariesByCrate, DownloadSummariesByDate, DownloadSummary,
+    DownloadSummaryKey, DownloadsByCrate, DownloadsByCrateAndDate, DownloadsByDate, ImportState,
+    VersionDownloads,
+};
+
+/// Daily version downloads older than this many days are rolled up into
+/// weekly summaries. Download statistics only read the last 90 days of daily
+/// rows, and crates.io's dump never includes days older than that.
+const RAW_RETENTION_DAYS: i64 = 180;
+
+/// Weekly summaries older than this many days are merged into monthly
+/// summaries.
+const WEEKLY_RETENTION_DAYS: i64 = 730;
+
+/// The most rows deleted in one transaction. A week of daily rows can number
+/// in the millions.
+const DELETE_BATCH: usize = 100_000;
+
+/// Rolls daily version downloads that are older than [`RAW_RETENTION_DAYS`]
+/// into per-crate weekly summaries and deletes them, then merges weekly
+/// summaries older than [`WEEKLY_RETENTION_DAYS`] into monthly summaries.
+pub(crate) fn roll_up(db: &Database) -> anyhow::Result<()> {
+    let today = OffsetDateTime::now_utc().date();
+    roll_up_daily(db, week_start(today - Duration::days(RAW_RETENTION_DAYS)))?;
+    merge_weekly(
+        db,
+        month_start(today - Duration::days(WEEKLY_RETENTION_DAYS)),
+    )
+}
+
+/// Rolls up the daily downloads before `cutoff`, one week at a time. The
+/// rows are deleted in batches, each in the same transaction that adds the
+/// batch's downloads to the summaries, so that no download is lost or counted
+/// twice if rolling up is interrupted.
+fn roll_up_daily(db: &Database, cutoff: Date) -> anyhow::Result<()> {
+    let cutoff_key = (CalendarDate::from(cutoff), 0);
+    let oldest = DownloadsByDate::entries(db)
+        .with_key_range(..cutoff_key)
+        .limit(1)
+        .query()?
+        .into_iter()
+        .next();
+    let Some(oldest) = oldest else { return Ok(()) };
+
+    println!("Rolling up daily downloads before {cutoff}");
+    let mut week = week_start(Date::from(oldest.key.0));
+    while week < cutoff {
+        let next_week = week + Duration::days(7);
+        let mappings = DownloadsByDate::entries(db)
+            .with_key_range((CalendarDate::from(week), 0)..(CalendarDate::from(next_week), 0))
+            .query()?;
+
+        for batch in mappings.chunks(DELETE_BATCH) {
+            let mut totals = BTreeMap::<DownloadSummaryKey, u64>::new();
+            let mut tx = Transaction::new();
+            for mapping in batch {
+                let (date, crate_id) = mapping.key;
+                *totals
+                    .entry(period(crate_id, Date::from(date)))
+                    .or_default() += mapping.value;
+                tx.operations.push(Operation::delete(
+                    VersionDownloads::collection_name(),
+                    mapping.source.clone(),
+                ));
+            }
+            add_to_summaries(db, totals, &mut tx)?;
+            tx.apply(db)?;
+        }
+        week = next_week;
+    }
+
+    Ok(())
+}
+
+/// Merges the summaries of each month before `cutoff` into one summary per
+/// crate, picking up from the first month that hasn't been merged yet. Each
+/// crate's summaries are replaced in a single transaction.
+fn merge_weekly(db: &Database, cutoff: Date) -> anyhow::Result<()> {
+    let mut state = ImportState::get(&(), db)?
+        .map(|d| d.contents)
+        .unwrap_or_default();
+    let mut month = match state.downloads_merged_from {
+        Some(month) => Date::from(month),
+        None => {
+            let oldest = DownloadSummariesByDate::entries(db)
+                .limit(1)
+                .query()?
+                .into_iter()
+                .next();
+            let Some(oldest) = oldest else { return Ok(()) };
+            month_start(Date::from(oldest.key.0))
+        }
+    };
+
+    while month < cutoff {
+        let next_month = month_start(month + Duration::days(31));
+        let mappings = DownloadSummariesByDate::entries(db)
+            .with_key_range((CalendarDate::from(month), 0)..(CalendarDate::from(next_month), 0))
+            .query()?;
+
+        let mut summaries = HashMap::<u64, Vec<_>>::new();
+        for mapping in mappings {
+            let (_, crate_id) = mapping.key;
+            let (_, downloads) = mapping.value;
+            summaries
+                .entry(crate_id)
+                .or_default()
+                .push((mapping.source, downloads));
+        }
+
+        let mut tx = Transaction::new();
+        let end = CalendarDate::from(next_month - Duration::days(1));
+        for (crate_id, summaries) in summaries {
+            if summaries.len() < 2 {
+                continue;
+            }
+            let mut downloads = 0;
+            for (source, summary_downloads) in summaries {
+                downloads += summary_downloads;
+                tx.operations.push(Operation::delete(
+                    DownloadSummary::collection_name(),
+                    source,
+                ));
+            }
+            let key = DownloadSummaryKey {
+                crate_id,
+                start: CalendarDate::from(month),
+                end,
+            };
+            tx.operations
+                .push(Operation::overwrite_serialized::<DownloadSummary, _>(
+                    &key,
+                    &DownloadSummary { downloads },
+                )?);
+            if tx.operations.len() >= DELETE_BATCH {
+                tx.apply(db)?;
+                tx = Transaction::new();
+            }
+        }
+        if !tx.operations.is_empty() {
+            tx.apply(db)?;
+        }
+        month = next_month;
+    }
+
+    state.downloads_merged_from = Some(CalendarDate::from(month));
+    state.overwrite_into(&(), db)?;
+    Ok(())
+}
+
+/// Adds `totals` to the existing summaries of the same periods. Summaries
+/// usually don't exist yet, but a period can be rolled up in parts if daily
+/// rows for it are imported after the rest were rolled up.
+fn add_to_summaries(
+    db: &Database,
+    totals: BTreeMap<DownloadSummaryKey, u64>,
+    tx: &mut Transaction,
+) -> anyhow::Result<()> {
+    let existing = DownloadSummary::get_multiple(totals.keys(), db)?
+        .into_iter()
+        .map(|document| (document.header.id, document.contents.downloads))
+        .collect::<BTreeMap<_, _>>();
+    for (key, downloads) in totals {
+        let downloads = downloads + existing.get(&key).copied().unwrap_or_default();
+        tx.operations
+            .push(Operation::overwrite_serialized::<DownloadSummary, _>(
+                &key,
+                &DownloadSummary { downloads },
+            )?);
+    }
+    Ok(())
+}
+
+/// Returns the total downloads of `crate_id` from `start` through `end`,
+/// combining daily rows with the summaries of rolled up periods. Summaries
+/// that only partly overlap the range are counted in proportion to the
+/// overlapping days.
+pub(crate) fn crate_downloads(
+    db: &Database,
+    crate_id: u64,
+    start: Date,
+    end: Date,
+) -> anyhow::Result<u64> {
+    let mut downloads = DownloadsByCrateAndDate::entries(db)
+        .with_key_range((crate_id, CalendarDate::from(start))..=(crate_id, CalendarDate::from(end)))
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.value.1)
+        .sum::<u64>();
+
+    // No period is longer than a month, so summaries starting a month before
+    // the range are the earliest that can overlap it.
+    let first = DownloadSummaryKey {
+        crate_id,
+        start: CalendarDate::from(start - Duration::days(31)),
+        end: CalendarDate::MIN,
+    };
+    let last = DownloadSummaryKey {
+        crate_id,
+        start: CalendarDate::from(end),
+        end: CalendarDate::from(end + Duration::days(31)),
+    };
+    for summary in DownloadSummary::list(first..=last, db).query()? {
+        let period_start = Date::from(summary.header.id.start);
+        let period_end = Date::from(summary.header.id.end);
+        let overlap = (period_end.min(end) - period_start.max(start)).whole_days() + 1;
+        if overlap <= 0 {
+            continue;
+        }
+        let days = (period_end - period_start).whole_days() + 1;
+        downloads += summary.contents.downloads * overlap as u64 / days as u64;
+    }
+
+    Ok(downloads)
+}
+
+/// Returns every download of `crate_id` recorded since daily downloads were
+/// first imported, combining daily rows with the summaries of rolled up
+/// periods.
+pub(crate) fn recorded_downloads(db: &Database, crate_id: u64) -> anyhow::Result<u64> {
+    let daily = DownloadsByCrate::entries(db)
+        .with_key(&crate_id)
+        .reduce_grouped()?;
+    let summarized = DownloadSummariesByCrate::entries(db)
+        .with_key(&crate_id)
+        .reduce_grouped()?;
+    Ok(daily
+        .into_iter()
+        .chain(summarized)
+        .map(|mapping| mapping.value)
+        .sum())
+}
+
+/// Returns the summary period containing `date`: the days of its week, from
+/// Monday through Sunday, that fall in the same month.
+fn period(crate_id: u64, date: Date) -> DownloadSummaryKey {
+    let start = week_start(date).max(month_start(date));
+    let week_end = week_start(date) + Duration::days(6);
+    let month_end = month_start(month_start(date) + Duration::days(31)) - Duration::days(1);
+    DownloadSummaryKey {
+        crate_id,
+        start: CalendarDate::from(start),
+        end: CalendarDate::from(week_end.min(month_end)),
+    }
+}
+
+fn week_start(date: Date) -> Date {
+    date - Duration::days(i64::from(date.weekday().number_days_from_monday()))
+}
+
+fn month_start(date: Date) -> Date {
+    Date::from_calendar_date(date.year(), date.month(), 1).expect("every month has a first day")
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+    use crate::schema::VersionDownloadKey;
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::from_calendar_date(year, Month::try_from(month).unwrap(), day).unwrap()
+    }
+
+    fn key(crate_id: u64, start: Date, end: Date) -> DownloadSummaryKey {
+        DownloadSummaryKey {
+            crate_id,
+            start: CalendarDate::from(start),
+            end: CalendarDate::from(end),
+        }
+    }
+
+    fn add_daily(db: &Database, version_id: u64, crate_id: u64, day: Date, downloads: u64) {
+        VersionDownloads {
+            crate_id,
+            downloads,
+        }
+        .insert_into(
+            &VersionDownloadKey {
+                version_id,
+                date: CalendarDate::from(day),
+            },
+            db,
+        )
+        .unwrap();
+    }
+
+    fn summaries(db: &Database) -> Vec<(DownloadSummaryKey, u64)> {
+        DownloadSummary::all(db)
+            .query()
+            .unwrap()
+            .into_iter()
+            .map(|summary| (summary.header.id, summary.contents.downloads))
+            .collect()
+    }
+
+    #[test]
+    fn weeks_and_months_start() {
+        assert_eq!(month_start(date(2023, 3, 15)), date(2023, 3, 1));
+        assert_eq!(month_start(date(2023, 3, 1)), date(2023, 3, 1));
+        assert_eq!(month_start(date(2024, 2, 29)), date(2024, 2, 1));
+        // 2023-03-15 was a Wednesday.
+        assert_eq!(week_start(date(2023, 3, 15)), date(2023, 3, 13));
+        assert_eq!(week_start(date(2023, 3, 13)), date(2023, 3, 13));
+        assert_eq!(week_start(date(2023, 1, 1)), date(2022, 12, 26));
+    }
+
+    #[test]
+    fn periods_are_weeks_within_a_month() {
+        assert_eq!(
+            period(1, date(2023, 3, 15)),
+            key(1, date(2023, 3, 13), date(2023, 3, 19))
+        );
+        // The week of 2023-02-27 is split between February and March.
+        assert_eq!(
+            period(1, date(2023, 2, 28)),
+            key(1, date(2023, 2, 27), date(2023, 2, 28))
+        );
+        assert_eq!(
+            period(1, date(2023, 3, 1)),
+            key(1, date(2023, 3, 1), date(2023, 3, 5))
+        );
+        assert_eq!(
+            period(1, date(2023, 1, 1)),
+            key(1, date(2023, 1, 1), date(2023, 1, 1))
+        );
+    }
+
+    #[test]
+    fn partly_overlapping_summaries_are_prorated() {
+        let (_dir, db) = crate::test_database();
+        DownloadSummary { downloads: 700 }
+            .overwrite_into(&key(1, date(2023, 3, 13), date(2023, 3, 19)), &db)
+            .unwrap();
+        DownloadSummary { downloads: 1_000 }
+            .overwrite_into(&key(2, date(2023, 3, 13), date(2023, 3, 19)), &db)
+            .unwrap();
+        add_daily(&db, 10, 1, date(2023, 3, 20), 5);
+
+        let downloads = |start, end| crate_downloads(&db, 1, start, end).unwrap();
+        assert_eq!(downloads(date(2023, 3, 17), date(2023, 3, 20)), 305);
+        assert_eq!(downloads(date(2023, 3, 1), date(2023, 3, 31)), 705);
+        assert_eq!(downloads(date(2023, 3, 20), date(2023, 3, 25)), 5);
+        assert_eq!(downloads(date(2023, 3, 13), date(2023, 3, 13)), 100);
+        assert_eq!(recorded_downloads(&db, 1).unwrap(), 705);
+    }
+
+    #[test]
+    fn daily_downloads_are_rolled_up_and_merged() {
+        let (_dir, db) = crate::test_database();
+        add_daily(&db, 10, 1, date(2023, 2, 28), 3);
+        add_daily(&db, 10, 1, date(2023, 3, 1), 4);
+        add_daily(&db, 11, 1, date(2023, 3, 2), 5);
+        add_daily(&db, 20, 2, date(2023, 3, 1), 7);
+        add_daily(&db, 10, 1, date(2023, 3, 6), 10);
+        add_daily(&db, 10, 1, date(2023, 3, 13), 1);
+
+        roll_up_daily(&db, date(2023, 3, 13)).unwrap();
+        assert_eq!(
+            summaries(&db),
+            [
+                (key(1, date(2023, 2, 27), date(2023, 2, 28)), 3),
+                (key(1, date(2023, 3, 1), date(2023, 3, 5)), 9),
+                (key(1, date(2023, 3, 6), date(2023, 3, 12)), 10),
+                (key(2, date(2023, 3, 1), date(2023, 3, 5)), 7),
+            ]
+        );
+        // Only the row on the cutoff is left.
+        assert_eq!(DownloadsByDate::entries(&db).query().unwrap().len(), 1);
+        assert_eq!(recorded_downloads(&db, 1).unwrap(), 23);
+
+        merge_weekly(&db, date(2023, 4, 1)).unwrap();
+        assert_eq!(
+            summaries(&db),
+            [
+                (key(1, date(2023, 2, 27), date(2023, 2, 28)), 3),
+                (key(1, date(2023, 3, 1), date(2023, 3, 31)), 19),
+                (key(2, date(2023, 3, 1), date(2023, 3, 5)), 7),
+            ]
+        );
+        assert_eq!(recorded_downloads(&db, 1).unwrap(), 23);
+        let state = ImportState::get(&(), &db).unwrap().unwrap().contents;
+        assert_eq!(
+            state.downloads_merged_from,
+            Some(CalendarDate::from(date(2023, 4, 1)))
+        );
+    }
+}
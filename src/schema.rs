@@ -1,7 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
 use std::iter::{Peekable, Sum};
 use std::ops::AddAssign;
-use std::str::Chars;
+use std::str::{Chars, FromStr};
 
 use bonsaidb::core::connection::RangeRef;
 use bonsaidb::core::document::{CollectionDocument, Emit};
@@ -11,8 +12,10 @@ use bonsaidb::core::schema::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{blocklist, placeholder, unmaintained};
+
 #[derive(Schema, Debug)]
-#[schema(name = "delve-rs", collections = [Crate, Keyword, Category, ImportState, Version, VersionDownloads])]
+#[schema(name = "delve-rs", collections = [Crate, Keyword, Category, ImportState, Version, VersionDownloads, DownloadSummary, Advisory, CrateAlias, Dependency, YankEvent, OwnershipEvent, Webhook, Watchlist, ExperimentEvent, ResultClick, QueryLog, DependencyMetrics, CrateProfile, UnsafeUsage, Review, ApiKey, ApiUsage, CrateEmbedding, CrateCooccurrence, ReadmeDuplicate, Owner, CrateSuccessor, Curation, CurationEvent, BlockedCrate, PublishWave, KeywordStats])]
 pub struct CrateIndex;
 
 #[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Default)]
@@ -21,10 +24,66 @@ pub struct ImportState {
     pub downloaded_last_modified: Option<String>,
     #[serde(default)]
     pub last_dump_imported: Option<String>,
+    /// The version of the dump most recently imported from each alternate
+    /// registry, keyed by the registry's name. A dump's version is its export
+    /// time, or when its crates were written for dumps without metadata.
+    #[serde(default)]
+    pub registries: HashMap<String, String>,
+    /// When each registry's version downloads were last fully reconciled with
+    /// its dump, keyed by the registry's name.
+    #[serde(default)]
+    pub downloads_reconciled: HashMap<String, String>,
+    /// The first month whose weekly [`DownloadSummary`] records haven't been
+    /// merged into a monthly summary.
+    #[serde(default)]
+    pub downloads_merged_from: Option<CalendarDate>,
+    /// The normalized names of the built-in aliases that have been seeded,
+    /// so that aliases deleted by an admin aren't seeded again.
+    #[serde(default)]
+    pub seeded_aliases: HashSet<String>,
+}
+
+/// The name of the default registry.
+pub const CRATES_IO: &str = "crates-io";
+
+/// A registry that crates are imported from.
+///
+/// Records imported from crates.io keep the ids from its dump. Other
+/// registries' ids are offset by the registry's id, so that the records of
+/// different registries never share a key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Registry {
+    pub id: u16,
+    pub name: String,
+}
+
+impl Registry {
+    /// The number of low bits of a key used for the id within a registry.
+    const ID_BITS: u32 = 48;
+
+    pub fn crates_io() -> Self {
+        Self {
+            id: 0,
+            name: String::from(CRATES_IO),
+        }
+    }
+
+    pub fn is_crates_io(&self) -> bool {
+        self.id == 0
+    }
+
+    /// Returns the key of the record with `id` in this registry's dump, or an
+    /// error if `id` is too large to be combined with the registry's id.
+    pub fn key(&self, id: u64) -> anyhow::Result<u64> {
+        if id >= 1 << Self::ID_BITS {
+            anyhow::bail!("id {id} in registry {} is too large", self.name);
+        }
+        Ok((u64::from(self.id) << Self::ID_BITS) | id)
+    }
 }
 
 #[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
-#[collection(name = "crates", primary_key = u64, views = [CratesByNormalizedName, CratesByKeyword])]
+#[collection(name = "crates", primary_key = u64, views = [CratesByNormalizedName, CratesByKeyword, CratesByCategory])]
 pub struct Crate {
     pub created_at: String,
     pub description: String,
@@ -39,6 +98,13 @@ pub struct Crate {
     pub keywords: HashSet<u64>,
     pub category_ids: HashSet<u64>,
     pub owners: HashSet<OwnerId>,
+    /// The registry the crate was imported from, or None for crates.io.
+    #[serde(default)]
+    pub registry: Option<String>,
+    /// The ISO 639-1 code of the language the description and README are
+    /// written in, if it was detected.
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 impl Crate {
@@ -63,7 +129,7 @@ impl CollectionViewSchema for CratesByNormalizedName {
     type View = Self;
 
     fn version(&self) -> u64 {
-        1
+        6
     }
 
     fn lazy(&self) -> bool {
@@ -74,6 +140,15 @@ impl CollectionViewSchema for CratesByNormalizedName {
         &self,
         document: CollectionDocument<<Self::View as View>::Collection>,
     ) -> ViewMapResult<Self::View> {
+        let reservation_notice = placeholder::has_reservation_notice(
+            &document.contents.description,
+            &document.contents.readme,
+        );
+        let archive_notice = unmaintained::has_archive_notice(
+            &document.contents.name,
+            &document.contents.description,
+            &document.contents.readme,
+        );
         document.header.emit_key_and_value(
             Crate::normalized_name(&document.contents.name),
             CrateInfo {
@@ -81,6 +156,11 @@ impl CollectionViewSchema for CratesByNormalizedName {
                 description: document.contents.description,
                 keywords: document.contents.keywords,
                 downloads: document.contents.downloads.unwrap_or(0),
+                repository: document.contents.repository,
+                reservation_notice,
+                archive_notice,
+                registry: document.contents.registry,
+                language: document.contents.language,
             },
         )
     }
@@ -92,6 +172,21 @@ pub struct CrateInfo {
     pub downloads: u64,
     pub description: String,
     pub keywords: HashSet<u64>,
+    #[serde(default)]
+    pub repository: String,
+    /// True if the description or README indicates the crate only exists to
+    /// reserve its name.
+    #[serde(default)]
+    pub reservation_notice: bool,
+    /// True if the description or README indicates the crate's repository is
+    /// archived or the crate is no longer maintained.
+    #[serde(default)]
+    pub archive_notice: bool,
+    /// The registry the crate was imported from, or None for crates.io.
+    #[serde(default)]
+    pub registry: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 #[derive(View, Clone, Debug)]
@@ -126,6 +221,42 @@ impl CollectionViewSchema for CratesByKeyword {
     }
 }
 
+#[derive(View, Clone, Debug)]
+#[view(name = "by-category", collection = Crate, key = u64, value = u64)]
+pub struct CratesByCategory;
+
+impl CollectionViewSchema for CratesByCategory {
+    type View = Self;
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .contents
+            .category_ids
+            .into_iter()
+            .map(|id| document.header.emit_key_and_value(id, 1))
+            .collect()
+    }
+
+    fn reduce(
+        &self,
+        mappings: &[ViewMappedValue<Self::View>],
+        _rereduce: bool,
+    ) -> ReduceResult<Self::View> {
+        Ok(mappings.iter().map(|m| m.value).sum())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Clone, Copy)]
 pub enum OwnerId {
     User(u64),
@@ -157,6 +288,15 @@ impl CollectionViewSchema for Keywords {
     }
 }
 
+/// The number of crates using each keyword when a dump was imported, keyed by
+/// the date of the import. Comparing these shows which topics are growing.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "keyword-stats", primary_key = String)]
+pub struct KeywordStats {
+    /// The number of crates using each keyword, keyed by the keyword.
+    pub crates: BTreeMap<String, u64>,
+}
+
 #[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[collection(name = "categories", primary_key = u64)]
 pub struct Category {
@@ -165,6 +305,29 @@ pub struct Category {
     pub description: String,
     pub path: String,
     pub slug: String,
+    /// The id of the category this is a subcategory of, as determined by
+    /// `path`.
+    #[serde(default)]
+    pub parent_id: Option<u64>,
+}
+
+impl Category {
+    /// Returns the path of this category's parent. Top-level categories' paths
+    /// only contain `root`, which isn't itself a category.
+    pub fn parent_path(&self) -> Option<&str> {
+        self.path
+            .rsplit_once('.')
+            .map(|(parent, _)| parent)
+            .filter(|parent| *parent != "root")
+    }
+
+    /// Returns the last segment of the category's name, such as `Robotics`
+    /// for `Science::Robotics`.
+    pub fn name(&self) -> &str {
+        self.category
+            .rsplit_once("::")
+            .map_or(&self.category, |(_, name)| name)
+    }
 }
 
 #[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -182,6 +345,8 @@ pub struct Version {
     pub version: String,
     pub published_by: Option<u64>,
     pub yanked: bool,
+    #[serde(default)]
+    pub rust_version: Option<String>,
 }
 
 #[derive(View, Clone, Debug)]
@@ -191,6 +356,10 @@ pub struct VersionsByCrate;
 impl CollectionViewSchema for VersionsByCrate {
     type View = Self;
 
+    fn version(&self) -> u64 {
+        2
+    }
+
     fn lazy(&self) -> bool {
         false
     }
@@ -204,6 +373,8 @@ impl CollectionViewSchema for VersionsByCrate {
             VersionSummary {
                 version: document.contents.version,
                 yanked: document.contents.yanked,
+                rust_version: document.contents.rust_version,
+                created_at: document.contents.created_at,
             },
         )
     }
@@ -213,31 +384,137 @@ impl CollectionViewSchema for VersionsByCrate {
 pub struct VersionSummary {
     pub version: String,
     pub yanked: bool,
+    #[serde(default)]
+    pub rust_version: Option<String>,
+    #[serde(default)]
+    pub created_at: String,
 }
 
+/// A version being yanked or unyanked, detected by comparing a version
+/// between two imported dumps.
 #[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
-#[collection(name = "version-downloads", primary_key = VersionDownloadKey, views = [DownloadsByDate])]
-pub struct VersionDownloads {
+#[collection(name = "yank-events", primary_key = u64, views = [YankEventsByCrate])]
+pub struct YankEvent {
     pub crate_id: u64,
-    pub downloads: u64,
+    pub version_id: u64,
+    pub version: String,
+    /// Whether the version was yanked, rather than unyanked.
+    pub yanked: bool,
+    /// The version's `updated_at` timestamp from the dump that contained the
+    /// change.
+    pub at: String,
 }
 
-#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct VersionDownloadKey {
-    pub version_id: u64,
-    pub date: CalendarDate,
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = YankEvent, key = u64, value = YankEvent)]
+pub struct YankEventsByCrate;
+
+impl CollectionViewSchema for YankEventsByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.crate_id, document.contents)
+    }
+}
+
+/// A named list of crates, whose recent activity is summarized on a page and
+/// in a feed. Its id is random, so only people who have been given the id
+/// can view or change it.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "watchlists", primary_key = String)]
+pub struct Watchlist {
+    pub name: String,
+    pub crates: Vec<String>,
+}
+
+/// The crates.io crates owned by a user or team, keyed by the owner's
+/// lowercased login. Replaced with the dump's owners on each import.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "owners", primary_key = String, views = [OwnersByCrate])]
+pub struct Owner {
+    /// The owner's GitHub login, as capitalized in the dump. Teams' logins
+    /// look like `github:org:team`.
+    pub login: String,
+    /// The ids of the owned crates, in ascending order.
+    pub crates: Vec<u64>,
 }
 
+/// The logins of each crate's owners, keyed by the crate's id.
 #[derive(View, Clone, Debug)]
-#[view(name = "by-date", collection = VersionDownloads, key = (CalendarDate, u64), value = u64)]
-pub struct DownloadsByDate;
+#[view(name = "by-crate", collection = Owner, key = u64, value = String)]
+pub struct OwnersByCrate;
 
-impl CollectionViewSchema for DownloadsByDate {
+impl CollectionViewSchema for OwnersByCrate {
     type View = Self;
 
-    fn version(&self) -> u64 {
-        1
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .contents
+            .crates
+            .iter()
+            .map(|id| {
+                document
+                    .header
+                    .emit_key_and_value(*id, document.contents.login.clone())
+            })
+            .collect()
     }
+}
+
+/// An owner who published a wave of near-identical crates in a short window,
+/// keyed by the owner's lowercased login. The wave's crates are demoted in
+/// search results. Computed after each import.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "publish-waves", primary_key = String)]
+pub struct PublishWave {
+    pub login: String,
+    /// The number of crates first published in the wave.
+    pub crates: u64,
+    /// When the wave's first crate was published.
+    pub started: String,
+    /// When the wave's last crate was published.
+    pub ended: String,
+    /// The ids of the crates first published in the wave.
+    #[serde(default)]
+    pub crate_ids: Vec<u64>,
+}
+
+/// An owner being added to or removed from a crate, detected by comparing a
+/// crate's owners between two imported dumps.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "ownership-events", primary_key = u64, views = [OwnershipEventsByCrate])]
+pub struct OwnershipEvent {
+    pub crate_id: u64,
+    pub owner: OwnerId,
+    /// The owner's GitHub login at the time of the change.
+    pub login: Option<String>,
+    /// Whether the owner was added, rather than removed.
+    pub added: bool,
+    pub at: String,
+}
+
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = OwnershipEvent, key = u64, value = OwnershipEvent)]
+pub struct OwnershipEventsByCrate;
+
+impl CollectionViewSchema for OwnershipEventsByCrate {
+    type View = Self;
 
     fn lazy(&self) -> bool {
         false
@@ -247,57 +524,1075 @@ impl CollectionViewSchema for DownloadsByDate {
         &self,
         document: CollectionDocument<<Self::View as View>::Collection>,
     ) -> ViewMapResult<Self::View> {
-        document.header.emit_key_and_value(
-            (document.header.id.date, document.contents.crate_id),
-            document.contents.downloads,
-        )
+        document
+            .header
+            .emit_key_and_value(document.contents.crate_id, document.contents)
     }
+}
 
-    fn reduce(
+/// A search results page shown to, or a result clicked by, a session assigned
+/// to one of a ranking experiment's variants.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "experiment-events", primary_key = u64, views = [ExperimentEventsByExperiment])]
+pub struct ExperimentEvent {
+    pub experiment: String,
+    /// The name of the ranker that ordered the results.
+    pub variant: String,
+    pub kind: ExperimentEventKind,
+    /// When the event happened. Empty for events recorded before this was
+    /// added, which are pruned first.
+    #[serde(default)]
+    pub at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExperimentEventKind {
+    /// A results page listing `results` crates was shown.
+    Impression { results: u32 },
+    /// The result at `position`, starting from 1, was clicked.
+    Click { position: u32 },
+}
+
+#[derive(View, Clone, Debug)]
+#[view(name = "by-experiment", collection = ExperimentEvent, key = String, value = ExperimentEvent)]
+pub struct ExperimentEventsByExperiment;
+
+impl CollectionViewSchema for ExperimentEventsByExperiment {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
         &self,
-        mappings: &[ViewMappedValue<Self::View>],
-        _rereduce: bool,
-    ) -> ReduceResult<Self::View> {
-        Ok(mappings.iter().map(|m| m.value).sum())
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.experiment.clone(), document.contents)
     }
 }
 
-#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct DateAndCrate {
-    pub date: CalendarDate,
+/// A click on a search result, recorded by the `/out/:crate` redirect.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "result-clicks", primary_key = u64, views = [ResultClicksByQuery])]
+pub struct ResultClick {
     pub crate_id: u64,
+    /// The result's position in the search results, starting from 1.
+    pub position: u32,
+    /// A hash of the search query, which is also the id of the query's
+    /// [`QueryLog`] entry.
+    pub query_hash: String,
+    pub at: String,
 }
 
-impl DateAndCrate {
-    pub fn range_for_after_date(date: CalendarDate) -> RangeRef<'static, DateAndCrate> {
-        RangeRef::from(DateAndCrate { date, crate_id: 0 }..)
+#[derive(View, Clone, Debug)]
+#[view(name = "by-query", collection = ResultClick, key = String, value = ResultClick)]
+pub struct ResultClicksByQuery;
+
+impl CollectionViewSchema for ResultClicksByQuery {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.query_hash.clone(), document.contents)
     }
 }
 
-#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct CalendarDate(u32);
+/// How often a search query has been made, keyed by the query's hash.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "query-log", primary_key = String, views = [QueriesByCount, ZeroResultQueriesByCount])]
+pub struct QueryLog {
+    /// The normalized query, truncated to a maximum length.
+    pub query: String,
+    pub count: u64,
+    /// The number of searches for this query that returned no results.
+    pub zero_results: u64,
+    pub last_searched: String,
+}
 
-impl From<time::Date> for CalendarDate {
-    fn from(value: time::Date) -> Self {
-        let year = u32::try_from(value.year()).expect("negative years are unsupported");
-        assert!(year < 2_u32.pow(22));
-        Self(year << 9 | value.ordinal() as u32)
+#[derive(View, Clone, Debug)]
+#[view(name = "by-count", collection = QueryLog, key = u64, value = QueryLog)]
+pub struct QueriesByCount;
+
+impl CollectionViewSchema for QueriesByCount {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.count, document.contents)
     }
 }
 
-impl From<CalendarDate> for time::Date {
-    fn from(value: CalendarDate) -> Self {
-        let year = i32::try_from(value.0 >> 9).expect("year out of range");
-        let ordinal = (value.0 & 0x1FF) as u16;
-        time::Date::from_ordinal_date(year, ordinal).expect("date out of range")
+/// Queries that have returned no results, keyed by how often they did.
+#[derive(View, Clone, Debug)]
+#[view(name = "zero-results-by-count", collection = QueryLog, key = u64, value = QueryLog)]
+pub struct ZeroResultQueriesByCount;
+
+impl CollectionViewSchema for ZeroResultQueriesByCount {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        let zero_results = document.contents.zero_results;
+        (zero_results > 0)
+            .then(|| {
+                document
+                    .header
+                    .emit_key_and_value(zero_results, document.contents)
+            })
+            .into_iter()
+            .collect()
     }
 }
 
-impl std::ops::Sub<u32> for CalendarDate {
-    type Output = Self;
+/// A key issued for the public API, keyed by the hex-encoded SHA-256 hash of
+/// the key so that the key itself is never stored.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "api-keys", primary_key = String)]
+pub struct ApiKey {
+    /// Who or what the key was issued to.
+    pub name: String,
+    /// The most requests the key may make per UTC day, where 0 means
+    /// unlimited. The configured default applies when None.
+    pub daily_quota: Option<u64>,
+    pub created_at: String,
+    /// Revoked keys are refused, but kept so that their usage is still
+    /// reported.
+    pub revoked: bool,
+    /// Every request made with the key, which is kept after its daily
+    /// [`ApiUsage`] is pruned.
+    #[serde(default)]
+    pub requests_total: u64,
+    /// Every request refused because the key's quota was used up.
+    #[serde(default)]
+    pub rejected_total: u64,
+}
 
-    fn sub(self, rhs: u32) -> Self::Output {
-        let date = time::Date::from(self);
-        Self::from(date - time::Duration::days(rhs as i64))
+/// The requests made with an API key on one UTC day, keyed by the key's hash
+/// and the date, separated by `/`.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "api-usage", primary_key = String)]
+pub struct ApiUsage {
+    /// The hash of the key, which is its [`ApiKey`]'s id.
+    pub key: String,
+    /// The day, formatted as `YYYY-MM-DD`.
+    pub date: String,
+    pub requests: u64,
+    /// The requests refused because the key's quota was used up, which
+    /// aren't counted in `requests`.
+    pub rejected: u64,
+}
+
+/// A URL that is notified of new crates and versions after each import.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "webhooks", primary_key = u64)]
+pub struct Webhook {
+    pub url: String,
+    /// The key used to sign each request, so that the receiver can verify
+    /// that it came from delve-rs.
+    pub secret: String,
+    /// Only notify about these crates. If both this and `keywords` are empty,
+    /// every release is sent.
+    #[serde(default)]
+    pub crates: Vec<String>,
+    /// Only notify about crates with any of these keywords.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+impl Webhook {
+    pub fn matches(&self, name: &str, keywords: &[String]) -> bool {
+        if self.crates.is_empty() && self.keywords.is_empty() {
+            return true;
+        }
+        let name = Crate::normalized_name(name);
+        self.crates
+            .iter()
+            .any(|krate| Crate::normalized_name(krate) == name)
+            || self
+                .keywords
+                .iter()
+                .any(|keyword| keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword)))
+    }
+}
+
+/// A dependency declared by a published version.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "dependencies", primary_key = u64, views = [DependenciesByVersion, DependentsByCrate])]
+pub struct Dependency {
+    /// The version declaring the dependency.
+    pub version_id: u64,
+    /// The crate being depended on.
+    pub crate_id: u64,
+    pub req: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    /// The `cfg` or target triple the dependency is limited to.
+    pub target: Option<String>,
+    /// The name the dependency is renamed to in the manifest, if any.
+    pub explicit_name: Option<String>,
+    /// The crate that published `version_id`, if its version was imported.
+    #[serde(default)]
+    pub dependent_crate_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum DependencyKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Build => "build",
+            DependencyKind::Dev => "dev",
+        })
+    }
+}
+
+/// Measurements of the dependencies required by a crate's latest version,
+/// keyed by crate id.
+#[derive(Collection, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[collection(name = "dependency-metrics", primary_key = u64)]
+pub struct DependencyMetrics {
+    /// The number of distinct crates required, directly or indirectly.
+    pub transitive: u32,
+    /// The number of steps from the crate to its furthest dependency.
+    pub depth: u32,
+}
+
+impl DependencyMetrics {
+    /// Crates requiring at most this many crates are considered lightweight.
+    pub const LIGHTWEIGHT: u32 = 10;
+    /// Crates requiring at least this many crates are considered heavyweight.
+    pub const HEAVYWEIGHT: u32 = 100;
+
+    pub fn is_lightweight(&self) -> bool {
+        self.transitive <= Self::LIGHTWEIGHT
+    }
+
+    pub fn is_heavyweight(&self) -> bool {
+        self.transitive >= Self::HEAVYWEIGHT
+    }
+}
+
+/// What a crate's latest version supports, inferred from its metadata after
+/// each import, keyed by crate id.
+#[derive(Collection, Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[collection(name = "crate-profiles", primary_key = u64)]
+pub struct CrateProfile {
+    /// True if the crate appears to support WebAssembly.
+    pub wasm: bool,
+    /// The platforms the crate has code specific to.
+    #[serde(default)]
+    pub platforms: Platforms,
+    /// True if the crate appears to be a procedural macro.
+    #[serde(default)]
+    pub proc_macro: bool,
+    /// True if the crate appears to have a build script.
+    #[serde(default)]
+    pub build_script: bool,
+    /// The number of crates the latest version requires, directly or
+    /// indirectly, that appear to be procedural macros.
+    #[serde(default)]
+    pub dependency_proc_macros: u32,
+    /// The number of crates the latest version requires, directly or
+    /// indirectly, that appear to have build scripts.
+    #[serde(default)]
+    pub dependency_build_scripts: u32,
+}
+
+impl CrateProfile {
+    /// Returns true if building the crate runs a procedural macro, because it
+    /// is one or requires one.
+    pub fn runs_proc_macros(&self) -> bool {
+        self.proc_macro || self.dependency_proc_macros > 0
+    }
+
+    /// Returns true if building the crate runs a build script, its own or one
+    /// of a crate it requires.
+    pub fn runs_build_scripts(&self) -> bool {
+        self.build_script || self.dependency_build_scripts > 0
+    }
+}
+
+/// The `unsafe` code in a version of a crate, imported from cargo-geiger
+/// reports and keyed by crate id.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "unsafe-usage", primary_key = u64)]
+pub struct UnsafeUsage {
+    /// The version that was scanned.
+    pub version: String,
+    /// Unsafe code in the files compiled by the scanned build.
+    pub used: UnsafeCounts,
+    /// Unsafe code in files that weren't compiled, such as code for other
+    /// platforms or disabled features.
+    pub unused: UnsafeCounts,
+    /// True if the crate declares `#![forbid(unsafe_code)]`.
+    pub forbids_unsafe: bool,
+}
+
+impl UnsafeUsage {
+    pub fn indicator(&self) -> UnsafeIndicator {
+        if self.forbids_unsafe {
+            UnsafeIndicator::Forbidden
+        } else if self.used.total() == 0 {
+            UnsafeIndicator::None
+        } else {
+            UnsafeIndicator::Used(self.used.total())
+        }
+    }
+}
+
+/// The number of items of each kind that are or contain `unsafe` code.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct UnsafeCounts {
+    pub functions: u64,
+    pub exprs: u64,
+    pub impls: u64,
+    pub traits: u64,
+    pub methods: u64,
+}
+
+impl UnsafeCounts {
+    pub fn total(&self) -> u64 {
+        self.functions + self.exprs + self.impls + self.traits + self.methods
+    }
+}
+
+/// A summary of a crate's [`UnsafeUsage`], shown next to the crate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnsafeIndicator {
+    /// The crate forbids unsafe code.
+    Forbidden,
+    /// The scanned build compiled no unsafe code.
+    None,
+    /// The scanned build compiled this many unsafe items.
+    Used(u64),
+}
+
+impl UnsafeIndicator {
+    /// The number of unsafe items compiled by the scanned build.
+    pub fn count(&self) -> u64 {
+        match self {
+            Self::Forbidden | Self::None => 0,
+            Self::Used(count) => *count,
+        }
+    }
+}
+
+/// Platforms a crate is known to support, because it declares dependencies
+/// for them or is tagged or categorized for them. A crate without any
+/// platform-specific code usually supports every platform its dependencies
+/// do, so a missing platform doesn't mean it's unsupported.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Platforms {
+    pub windows: bool,
+    pub macos: bool,
+    pub linux: bool,
+    /// Bare-metal targets, such as microcontrollers.
+    pub embedded: bool,
+}
+
+impl Platforms {
+    pub fn any(&self) -> bool {
+        self.windows || self.macos || self.linux || self.embedded
+    }
+}
+
+#[derive(View, Clone, Debug)]
+#[view(name = "by-version", collection = Dependency, key = u64, value = Dependency)]
+pub struct DependenciesByVersion;
+
+impl CollectionViewSchema for DependenciesByVersion {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.version_id, document.contents)
+    }
+}
+
+/// Maps each crate and a crate depending on it to the number of the
+/// dependent's versions that declare the dependency.
+#[derive(View, Clone, Debug)]
+#[view(name = "dependents", collection = Dependency, key = (u64, u64), value = u64)]
+pub struct DependentsByCrate;
+
+impl CollectionViewSchema for DependentsByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .contents
+            .dependent_crate_id
+            .into_iter()
+            .map(|dependent| {
+                document
+                    .header
+                    .emit_key_and_value((document.contents.crate_id, dependent), 1)
+            })
+            .collect()
+    }
+
+    fn reduce(
+        &self,
+        mappings: &[ViewMappedValue<Self::View>],
+        _rereduce: bool,
+    ) -> ReduceResult<Self::View> {
+        Ok(mappings.iter().map(|m| m.value).sum())
+    }
+}
+
+/// A minimum supported Rust version, as declared by `rust-version` in a
+/// crate's manifest. Omitted components are treated as 0.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RustVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl FromStr for RustVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('.');
+        let major = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("empty rust-version"))?
+            .parse()?;
+        let minor = parts.next().map(str::parse).transpose()?.unwrap_or(0);
+        let patch = parts.next().map(str::parse).transpose()?.unwrap_or(0);
+        if parts.next().is_some() {
+            anyhow::bail!("invalid rust-version: {s}");
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl Display for RustVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "version-downloads", primary_key = VersionDownloadKey, views = [DownloadsByDate, DownloadsByCrateAndDate, DownloadsByCrate])]
+pub struct VersionDownloads {
+    pub crate_id: u64,
+    pub downloads: u64,
+}
+
+#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct VersionDownloadKey {
+    pub version_id: u64,
+    pub date: CalendarDate,
+}
+
+#[derive(View, Clone, Debug)]
+#[view(name = "by-date", collection = VersionDownloads, key = (CalendarDate, u64), value = u64)]
+pub struct DownloadsByDate;
+
+impl CollectionViewSchema for DownloadsByDate {
+    type View = Self;
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document.header.emit_key_and_value(
+            (document.header.id.date, document.contents.crate_id),
+            document.contents.downloads,
+        )
+    }
+
+    fn reduce(
+        &self,
+        mappings: &[ViewMappedValue<Self::View>],
+        _rereduce: bool,
+    ) -> ReduceResult<Self::View> {
+        Ok(mappings.iter().map(|m| m.value).sum())
+    }
+}
+
+/// Each day's downloads of a crate's versions, as `(version_id, downloads)`.
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate-and-date", collection = VersionDownloads, key = (u64, CalendarDate), value = (u64, u64))]
+pub struct DownloadsByCrateAndDate;
+
+impl CollectionViewSchema for DownloadsByCrateAndDate {
+    type View = Self;
+
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document.header.emit_key_and_value(
+            (document.contents.crate_id, document.header.id.date),
+            (document.header.id.version_id, document.contents.downloads),
+        )
+    }
+}
+
+/// Every recorded download of a crate's versions. Unlike [`Crate::downloads`],
+/// which is copied from the dump, this only counts the daily downloads that
+/// have been imported and not yet rolled up into a [`DownloadSummary`].
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = VersionDownloads, key = u64, value = u64)]
+pub struct DownloadsByCrate;
+
+impl CollectionViewSchema for DownloadsByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.crate_id, document.contents.downloads)
+    }
+
+    fn reduce(
+        &self,
+        mappings: &[ViewMappedValue<Self::View>],
+        _rereduce: bool,
+    ) -> ReduceResult<Self::View> {
+        Ok(mappings.iter().map(|m| m.value).sum())
+    }
+}
+
+/// A crate's downloads over a period whose daily [`VersionDownloads`] have
+/// been rolled up and pruned. Periods never span more than one month.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "download-summaries", primary_key = DownloadSummaryKey, views = [DownloadSummariesByDate, DownloadSummariesByCrate])]
+pub struct DownloadSummary {
+    pub downloads: u64,
+}
+
+#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DownloadSummaryKey {
+    pub crate_id: u64,
+    /// The first day of the period.
+    pub start: CalendarDate,
+    /// The last day of the period.
+    pub end: CalendarDate,
+}
+
+/// Download summaries by the first day of their period, as `(end, downloads)`.
+#[derive(View, Clone, Debug)]
+#[view(name = "by-date", collection = DownloadSummary, key = (CalendarDate, u64), value = (CalendarDate, u64))]
+pub struct DownloadSummariesByDate;
+
+impl CollectionViewSchema for DownloadSummariesByDate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document.header.emit_key_and_value(
+            (document.header.id.start, document.header.id.crate_id),
+            (document.header.id.end, document.contents.downloads),
+        )
+    }
+}
+
+/// The total downloads of each crate's [`DownloadSummary`] records.
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = DownloadSummary, key = u64, value = u64)]
+pub struct DownloadSummariesByCrate;
+
+impl CollectionViewSchema for DownloadSummariesByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.header.id.crate_id, document.contents.downloads)
+    }
+
+    fn reduce(
+        &self,
+        mappings: &[ViewMappedValue<Self::View>],
+        _rereduce: bool,
+    ) -> ReduceResult<Self::View> {
+        Ok(mappings.iter().map(|m| m.value).sum())
+    }
+}
+
+#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DateAndCrate {
+    pub date: CalendarDate,
+    pub crate_id: u64,
+}
+
+impl DateAndCrate {
+    pub fn range_for_after_date(date: CalendarDate) -> RangeRef<'static, DateAndCrate> {
+        RangeRef::from(DateAndCrate { date, crate_id: 0 }..)
+    }
+}
+
+#[derive(Key, Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct CalendarDate(u32);
+
+impl CalendarDate {
+    /// A date before any other, for use as the start of a range.
+    pub const MIN: Self = Self(0);
+}
+
+impl From<time::Date> for CalendarDate {
+    fn from(value: time::Date) -> Self {
+        let year = u32::try_from(value.year()).expect("negative years are unsupported");
+        assert!(year < 2_u32.pow(22));
+        Self(year << 9 | value.ordinal() as u32)
+    }
+}
+
+impl From<CalendarDate> for time::Date {
+    fn from(value: CalendarDate) -> Self {
+        let year = i32::try_from(value.0 >> 9).expect("year out of range");
+        let ordinal = (value.0 & 0x1FF) as u16;
+        time::Date::from_ordinal_date(year, ordinal).expect("date out of range")
+    }
+}
+
+impl std::ops::Sub<u32> for CalendarDate {
+    type Output = Self;
+
+    fn sub(self, rhs: u32) -> Self::Output {
+        let date = time::Date::from(self);
+        Self::from(date - time::Duration::days(rhs as i64))
+    }
+}
+
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "advisories", primary_key = String, views = [AdvisoriesByCrate])]
+pub struct Advisory {
+    pub crate_name: String,
+    pub title: String,
+    pub date: String,
+    pub url: Option<String>,
+    pub informational: Option<String>,
+    pub withdrawn: Option<String>,
+    pub patched: Vec<String>,
+    pub unaffected: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl Advisory {
+    /// Returns true if this advisory reports the crate itself as malware,
+    /// rather than a vulnerability in it.
+    pub fn is_malicious(&self) -> bool {
+        self.withdrawn.is_none()
+            && (self.informational.as_deref() == Some("malicious")
+                || self
+                    .categories
+                    .iter()
+                    .any(|category| category == "malicious"))
+    }
+
+    /// Returns true if this advisory is informational, such as a notice that
+    /// the crate is unmaintained, rather than a vulnerability. Malware reports
+    /// aren't informational, even when RustSec marks them so.
+    pub fn is_informational(&self) -> bool {
+        self.informational.is_some() && !self.is_malicious()
+    }
+
+    /// Returns true if this advisory reports a vulnerability that `version` is
+    /// neither patched nor unaffected by. Informational advisories don't
+    /// affect any version.
+    pub fn affects(&self, version: &semver::Version) -> bool {
+        self.withdrawn.is_none()
+            && !self.is_informational()
+            && !self
+                .patched
+                .iter()
+                .chain(&self.unaffected)
+                .any(|req| semver::VersionReq::parse(req).map_or(false, |req| req.matches(version)))
+    }
+}
+
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = Advisory, key = String)]
+pub struct AdvisoriesByCrate;
+
+impl CollectionViewSchema for AdvisoriesByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document.header.emit_key(document.contents.crate_name)
+    }
+}
+
+/// A community review of a version of a crate, imported from a cargo-vet audit
+/// file or a crev proof repository.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "reviews", primary_key = String, views = [ReviewsByCrate])]
+pub struct Review {
+    /// The reviewed crate's name, as written by the reviewer.
+    pub crate_name: String,
+    /// The reviewed version, or for reviews of the changes between two
+    /// versions, both versions separated by `->`.
+    pub version: String,
+    pub kind: ReviewKind,
+    /// The source the review was imported from: the name of the organization
+    /// publishing a cargo-vet audit file, or the URL of a crev repository.
+    pub source: String,
+    pub reviewer: String,
+    /// The cargo-vet criteria the version was audited for, or the crev
+    /// rating.
+    pub verdict: String,
+    /// False for crev reviews without a positive rating.
+    pub favorable: bool,
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReviewKind {
+    Vet,
+    Crev,
+}
+
+/// Maps each review to the normalized name of the reviewed crate, with
+/// whether the review is favorable.
+#[derive(View, Clone, Debug)]
+#[view(name = "by-crate", collection = Review, key = String, value = bool)]
+pub struct ReviewsByCrate;
+
+impl CollectionViewSchema for ReviewsByCrate {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document.header.emit_key_and_value(
+            Crate::normalized_name(&document.contents.crate_name),
+            document.contents.favorable,
+        )
+    }
+}
+
+/// The embedding of a crate's name, description, and README, keyed by crate
+/// id. Crates are embedded again when their text or the configured model
+/// changes.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[collection(name = "crate-embeddings", primary_key = u64)]
+pub struct CrateEmbedding {
+    /// The model that computed the embedding.
+    pub model: String,
+    /// The SHA-256 hash of the text that was embedded.
+    pub text_hash: String,
+    pub vector: Vec<f32>,
+}
+
+/// The crates most often depended on together with a crate, keyed by crate
+/// id. Computed after each import from the dependencies of every crate's
+/// latest version.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "crate-cooccurrence", primary_key = u64)]
+pub struct CrateCooccurrence {
+    /// The number of crates whose latest version depends on this crate.
+    pub dependents: u64,
+    /// The crates depended on alongside this crate, most closely associated
+    /// first.
+    pub used_with: Vec<UsedWith>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UsedWith {
+    pub crate_id: u64,
+    /// The number of crates whose latest version depends on both crates.
+    pub shared: u64,
+}
+
+/// Marks a crate whose README is a near-exact copy of another crate's, keyed
+/// by the copy's crate id. Crates with copied READMEs form clusters, and
+/// every crate in a cluster but its earliest published one is marked as a
+/// copy of that crate. Computed after each import.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[collection(name = "readme-duplicates", primary_key = u64, views = [ReadmeDuplicatesByOriginal])]
+pub struct ReadmeDuplicate {
+    /// The crate this crate's README appears to be copied from.
+    pub original: u64,
+    /// The estimated fraction of the two READMEs' word sequences that they
+    /// share.
+    pub similarity: f32,
+}
+
+/// The similarity of each crate whose README appears to be copied from a
+/// crate, keyed by the original crate's id.
+#[derive(View, Clone, Debug)]
+#[view(name = "by-original", collection = ReadmeDuplicate, key = u64, value = f32)]
+pub struct ReadmeDuplicatesByOriginal;
+
+impl CollectionViewSchema for ReadmeDuplicatesByOriginal {
+    type View = Self;
+
+    fn lazy(&self) -> bool {
+        false
+    }
+
+    fn map(
+        &self,
+        document: CollectionDocument<<Self::View as View>::Collection>,
+    ) -> ViewMapResult<Self::View> {
+        document
+            .header
+            .emit_key_and_value(document.contents.original, document.contents.similarity)
+    }
+}
+
+/// A note that a crate has been deprecated or renamed, keyed by the crate's
+/// normalized name.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "crate-aliases", primary_key = String)]
+pub struct CrateAlias {
+    /// The crates that should be considered instead.
+    pub replacements: Vec<String>,
+    pub note: String,
+    pub source: AliasSource,
+}
+
+/// The crates suggested in place of a crate when it appears abandoned, keyed
+/// by the crate's normalized name.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "crate-successors", primary_key = String)]
+pub struct CrateSuccessor {
+    /// The names of the crates continuing the crate, such as its maintained
+    /// forks.
+    pub successors: Vec<String>,
+    pub source: AliasSource,
+}
+
+/// A crate flagged as malware, keyed by [`blocklist::key`]. Blocked crates
+/// are left out of searches and the sparse index, and their pages warn
+/// against installing them.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "blocklist", primary_key = String)]
+pub struct BlockedCrate {
+    pub reason: String,
+    /// The id of the RustSec advisory that flagged the crate, or None if an
+    /// administrator blocked it. Advisories only block crates.io crates.
+    pub advisory: Option<String>,
+    pub blocked_at: String,
+}
+
+/// An administrator's override of how a crate ranks in the results of a
+/// search.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "curations", primary_key = u64)]
+pub struct Curation {
+    /// The search terms the override applies to, ignoring case and
+    /// whitespace. Filters aren't part of a search's terms.
+    pub query: String,
+    pub crate_name: String,
+    pub action: CurationAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CurationAction {
+    /// The crate is listed first, even if it doesn't match the search.
+    /// Multiple pinned crates are listed in the order they were pinned.
+    Pin,
+    /// The crate's score is multiplied by [`CurationAction::BOOST`].
+    Boost,
+    /// The crate's score is multiplied by [`CurationAction::DEMOTE`].
+    Demote,
+    /// The crate is left out of the results.
+    Hide,
+}
+
+impl CurationAction {
+    pub const BOOST: f32 = 4.;
+    pub const DEMOTE: f32 = 0.25;
+}
+
+/// A change made to the [`Curation`]s, kept as an audit trail.
+#[derive(Collection, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[collection(name = "curation-events", primary_key = u64)]
+pub struct CurationEvent {
+    pub at: String,
+    pub change: CurationChange,
+    /// The id of the curation that was added or removed.
+    pub curation_id: u64,
+    pub curation: Curation,
+    /// Why the change was made, as given by the administrator.
+    pub reason: String,
+    /// The administrator who made the change: the username they signed in
+    /// with, or `token` if they used the bearer token. Empty for changes made
+    /// before administrators were recorded.
+    #[serde(default)]
+    pub admin: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CurationChange {
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AliasSource {
+    /// Entered by an administrator. Manual aliases are never overwritten by
+    /// the importer.
+    Manual,
+    /// Detected from the crate's metadata during import.
+    Heuristic,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_keys() {
+        let registry = Registry {
+            id: 2,
+            name: String::from("internal"),
+        };
+        assert_eq!(Registry::crates_io().key(42).unwrap(), 42);
+        assert_eq!(registry.key(42).unwrap(), (2 << 48) | 42);
+        assert_eq!(registry.key((1 << 48) - 1).unwrap(), (3 << 48) - 1);
+        assert!(registry.key(1 << 48).is_err());
+    }
+
+    fn advisory(informational: Option<&str>, categories: &[&str]) -> Advisory {
+        Advisory {
+            crate_name: String::from("evil"),
+            title: String::from("evil is malware"),
+            date: String::from("2023-03-20"),
+            url: None,
+            informational: informational.map(String::from),
+            withdrawn: None,
+            patched: Vec::new(),
+            unaffected: Vec::new(),
+            categories: categories
+                .iter()
+                .map(|category| category.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn malicious_advisories() {
+        assert!(advisory(Some("malicious"), &[]).is_malicious());
+        assert!(advisory(None, &["code-execution", "malicious"]).is_malicious());
+        assert!(!advisory(None, &["code-execution"]).is_malicious());
+        assert!(!advisory(Some("unmaintained"), &[]).is_malicious());
+
+        let mut withdrawn = advisory(Some("malicious"), &[]);
+        withdrawn.withdrawn = Some(String::from("2023-03-21"));
+        assert!(!withdrawn.is_malicious());
+    }
+
+    #[test]
+    fn informational_advisories_affect_nothing() {
+        let version = semver::Version::new(1, 2, 3);
+        assert!(advisory(None, &["memory-corruption"]).affects(&version));
+        assert!(advisory(Some("malicious"), &[]).affects(&version));
+        assert!(!advisory(Some("unmaintained"), &[]).affects(&version));
+        assert!(!advisory(Some("notice"), &[]).affects(&version));
+        assert!(advisory(Some("unmaintained"), &[]).is_informational());
+        assert!(!advisory(Some("malicious"), &[]).is_informational());
+
+        let mut patched = advisory(None, &[]);
+        patched.patched = vec![String::from(">=1.2.0")];
+        assert!(!patched.affects(&version));
+        assert!(patched.affects(&semver::Version::new(1, 1, 0)));
     }
 }
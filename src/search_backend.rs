@@ -0,0 +1,92 @@
+//! The full-text search of crates' names, descriptions, and READMEs that
+//! [`query`](crate::query) layers its name and keyword scoring on top of.
+
+use std::{collections::HashMap, path::Path};
+
+use bonsaidb::local::Database;
+use serde::Deserialize;
+
+use crate::{meilisearch::MeilisearchConfig, schema::Crate, syntax::Field};
+
+/// How crates' text is searched.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Which backend searches. `--no-tantivy` overrides this with
+    /// `descriptions`.
+    pub backend: Backend,
+    /// The instance searched when `backend` is `meilisearch`.
+    pub meilisearch: MeilisearchConfig,
+}
+
+/// Which implementation of [`SearchBackend`] to search with.
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// A tantivy index of every crate's name, description, and README, stored
+    /// on disk next to the database.
+    #[default]
+    Tantivy,
+    /// An in-memory index of crates' names and descriptions only, for
+    /// deployments without the memory to spare for tantivy. READMEs aren't
+    /// searched, and phrases match text containing all of their words rather
+    /// than only the words next to each other.
+    Descriptions,
+    /// An external Meilisearch instance, which is sent every crate's name,
+    /// description, and README as they're imported.
+    Meilisearch,
+}
+
+/// Finds the crates whose text matches a search.
+pub trait SearchBackend: std::fmt::Debug + Send + Sync {
+    /// Returns up to `limit` crates matching `terms`, most relevant first,
+    /// with the backend's score for each. Terms that can't be parsed match
+    /// nothing.
+    fn search(&self, terms: &str, limit: usize) -> anyhow::Result<Vec<(f32, u64)>>;
+
+    /// Returns a writer that adds crates to the index.
+    fn writer(&self) -> anyhow::Result<Box<dyn IndexWriter>>;
+
+    /// Replaces the index's contents with every crate in `db`, returning the
+    /// number of crates indexed.
+    fn rebuild(&self, db: &Database) -> anyhow::Result<usize>;
+
+    /// Loads whatever the first searches would otherwise wait on.
+    fn warm_up(&self) -> anyhow::Result<()>;
+
+    /// Returns the words appearing in the text of at least `min_crates`
+    /// crates, with the number of crates' texts containing each.
+    fn common_terms(&self, min_crates: u32) -> anyhow::Result<HashMap<String, u64>>;
+
+    /// Returns the number of crates in the index.
+    fn num_docs(&self) -> u64;
+
+    /// Returns a highlighter of where `terms` match, or None if the terms
+    /// can't be parsed.
+    fn highlighter(&self, terms: &str) -> anyhow::Result<Option<Box<dyn Highlighter>>>;
+
+    /// Copies the index as of its last commit into `destination`, a
+    /// replication snapshot, so that a replica opening the snapshot as its
+    /// database finds it. Backends that don't store their index next to the
+    /// database copy nothing.
+    fn snapshot(&self, destination: &Path) -> anyhow::Result<()>;
+}
+
+/// Adds crates to a [`SearchBackend`]. Searches don't see the changes until
+/// they're committed.
+pub trait IndexWriter: Send {
+    /// Adds `krate` to the index, replacing the crate with the same `id` if
+    /// it was already indexed.
+    fn replace(&mut self, id: u64, krate: &Crate) -> anyhow::Result<()>;
+
+    /// Makes every change visible to searches.
+    fn commit(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+/// Excerpts crate text around a search's matches.
+pub trait Highlighter {
+    /// Returns an HTML excerpt of `text`, which is from `field`, with the
+    /// search's terms highlighted, or None if `text` doesn't contain any of
+    /// them.
+    fn highlight(&self, field: Field, text: &str) -> Option<String>;
+}
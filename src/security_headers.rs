@@ -0,0 +1,72 @@
+//! Security headers sent with every response.
+//!
+//! The content security policy only allows scripts from the site itself,
+//! including the copy of Swagger UI served from `/assets`, and script elements
+//! carrying the current response's nonce. Templates add the nonce to their
+//! script elements with [`nonce`], so inline scripts keep working while
+//! injected ones don't.
+
+use axum::{
+    http::{
+        header::{CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS},
+        HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use uuid::Uuid;
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// A response extension that lets any site frame the response, such as the
+/// embeddable search box. Other responses can't be framed.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Framable;
+
+/// Middleware that adds the security headers to every response, generating
+/// the nonce that templates rendered while handling the request use.
+///
+/// Responses that already have a content security policy, such as proxied
+/// images, keep theirs.
+pub(crate) async fn set<B>(request: Request<B>, next: Next<B>) -> Response {
+    let nonce = STANDARD.encode(Uuid::new_v4().as_bytes());
+    let mut response = NONCE.scope(nonce.clone(), next.run(request)).await;
+    let frame_ancestors = if response.extensions().get::<Framable>().is_some() {
+        "*"
+    } else {
+        "'none'"
+    };
+    let headers = response.headers_mut();
+    if !headers.contains_key(CONTENT_SECURITY_POLICY) {
+        // Inline styles are allowed for the style attributes of the crate
+        // page's charts.
+        let policy = format!(
+            "default-src 'self'; \
+             script-src 'self' 'nonce-{nonce}'; \
+             style-src 'self' 'unsafe-inline'; \
+             img-src 'self' data:; \
+             object-src 'none'; \
+             base-uri 'self'; \
+             form-action 'self'; \
+             frame-ancestors {frame_ancestors}"
+        );
+        headers.insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_str(&policy).expect("nonces are base64"),
+        );
+    }
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(
+        REFERRER_POLICY,
+        HeaderValue::from_static("strict-origin-when-cross-origin"),
+    );
+    response
+}
+
+/// Returns the nonce of the current response's script elements.
+pub fn nonce() -> String {
+    NONCE.try_with(Clone::clone).unwrap_or_default()
+}
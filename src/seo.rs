@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+/// The most characters of a description included in a page's metadata. Link
+/// previews truncate longer descriptions anyway.
+const MAX_DESCRIPTION: usize = 200;
+
+/// The URL the public site is served at, which link previews require to
+/// resolve a page's canonical URL.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SiteUrl(Option<Arc<str>>);
+
+impl SiteUrl {
+    pub fn new(public_url: Option<&str>) -> Self {
+        Self(public_url.map(|url| Arc::from(url.trim_end_matches('/'))))
+    }
+
+    /// Returns the URL of `path`, which must start with `/`. Without a
+    /// configured public URL, the path is returned as-is.
+    pub fn absolute(&self, path: &str) -> String {
+        match &self.0 {
+            Some(base) => format!("{base}{path}"),
+            None => path.to_string(),
+        }
+    }
+}
+
+/// The title, description, and canonical URL of a page, rendered as meta
+/// tags for search engines and as OpenGraph and Twitter card data for link
+/// previews.
+#[derive(Debug)]
+pub(crate) struct PageMeta {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+}
+
+impl PageMeta {
+    pub fn new(site: &SiteUrl, path: &str, title: String, description: &str) -> Self {
+        Self {
+            title,
+            description: summarize(description),
+            url: site.absolute(path),
+        }
+    }
+}
+
+/// Collapses the whitespace in `text` and truncates it to
+/// [`MAX_DESCRIPTION`] characters, at the end of a word unless the first word
+/// is already too long.
+fn summarize(text: &str) -> String {
+    let mut summary = String::with_capacity(text.len().min(MAX_DESCRIPTION));
+    for (index, word) in text.split_whitespace().enumerate() {
+        if summary.chars().count() + word.chars().count() + 1 > MAX_DESCRIPTION {
+            if index == 0 {
+                summary.extend(word.chars().take(MAX_DESCRIPTION - 1));
+            }
+            summary.push('…');
+            break;
+        }
+        if index > 0 {
+            summary.push(' ');
+        }
+        summary.push_str(word);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summaries_collapse_whitespace() {
+        assert_eq!(
+            summarize("  A fast\n\tJSON   parser. "),
+            "A fast JSON parser."
+        );
+        assert_eq!(summarize(""), "");
+    }
+
+    #[test]
+    fn summaries_end_at_a_word() {
+        let text = "word ".repeat(100);
+        let summary = summarize(&text);
+        assert_eq!(summary.chars().count(), 200);
+        assert!(summary.ends_with("word…"));
+    }
+
+    #[test]
+    fn long_first_words_are_cut() {
+        let summary = summarize(&format!("{} more", "é".repeat(300)));
+        assert_eq!(summary, format!("{}…", "é".repeat(199)));
+    }
+}
@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use axum::http::{header::COOKIE, HeaderMap, HeaderValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Signs and verifies cookies that are only stored in visitors' browsers. The
+/// signature keeps other sites and scripts from planting values in them. Each
+/// signature covers the cookie's name, so one cookie's value can't be copied
+/// into another.
+#[derive(Clone)]
+pub(crate) struct SignedCookies {
+    secret: Arc<[u8]>,
+}
+
+impl SignedCookies {
+    /// Returns a signer using `secret`, or a random secret that lasts until
+    /// the server restarts when none is configured.
+    pub fn new(secret: Option<&str>) -> Self {
+        let secret = match secret {
+            Some(secret) => Arc::from(secret.as_bytes()),
+            None => {
+                let mut secret = Uuid::new_v4().as_bytes().to_vec();
+                secret.extend_from_slice(Uuid::new_v4().as_bytes());
+                Arc::from(secret)
+            }
+        };
+        Self { secret }
+    }
+
+    /// Returns the payload of the request's cookie named `name`, or None if
+    /// it's missing, empty, or tampered with.
+    pub fn read<'a>(&self, headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        cookie(headers, name).and_then(|value| self.verify(name, value))
+    }
+
+    /// Returns the `Set-Cookie` header value that stores `payload` in the
+    /// cookie named `name` for `max_age` seconds.
+    pub fn set(&self, name: &str, payload: &str, max_age: u32) -> Option<HeaderValue> {
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(name, payload).finalize().into_bytes());
+        let cookie = format!(
+            "{name}={payload}:{signature}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax"
+        );
+        HeaderValue::from_str(&cookie).ok()
+    }
+
+    /// Returns the payload of `value`, the value of the cookie named `name`,
+    /// if its signature is valid.
+    fn verify<'a>(&self, name: &str, value: &'a str) -> Option<&'a str> {
+        let (payload, signature) = value.rsplit_once(':')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(name, payload).verify_slice(&signature).ok()?;
+        Some(payload).filter(|payload| !payload.is_empty())
+    }
+
+    fn mac(&self, name: &str, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        // Cookie names can't contain `=`, so the name and payload can't run
+        // together ambiguously.
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+impl std::fmt::Debug for SignedCookies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignedCookies").finish_non_exhaustive()
+    }
+}
+
+/// Returns the value of the request's cookie named `name`, unverified.
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (cookie_name, value) = cookie.trim().split_once('=')?;
+            (cookie_name == name).then_some(value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_cookie(cookie: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    /// Returns request headers sending the cookie set by `set_cookie`.
+    fn request(set_cookie: &HeaderValue) -> HeaderMap {
+        with_cookie(set_cookie.to_str().unwrap().split(';').next().unwrap())
+    }
+
+    #[test]
+    fn signed_values_round_trip() {
+        let cookies = SignedCookies::new(Some("secret"));
+        let headers = request(&cookies.set("prefs", "per_page=20", 60).unwrap());
+        assert_eq!(cookies.read(&headers, "prefs"), Some("per_page=20"));
+        assert_eq!(cookies.read(&headers, "recent"), None);
+    }
+
+    #[test]
+    fn tampered_values_are_rejected() {
+        let cookies = SignedCookies::new(Some("secret"));
+        let set = cookies.set("prefs", "per_page=20", 60).unwrap();
+        let value = request(&set)
+            .get(COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .replace("per_page=20", "per_page=50");
+        assert_eq!(cookies.read(&with_cookie(&value), "prefs"), None);
+        assert_eq!(
+            cookies.read(&with_cookie("prefs=per_page=20"), "prefs"),
+            None
+        );
+        assert_eq!(
+            cookies.read(&with_cookie("prefs=per_page=20:"), "prefs"),
+            None
+        );
+        assert_eq!(
+            cookies.read(&with_cookie("prefs=per_page=20:!!"), "prefs"),
+            None
+        );
+
+        // Values signed with another secret are rejected too.
+        let other = SignedCookies::new(Some("other secret"));
+        assert_eq!(other.read(&request(&set), "prefs"), None);
+    }
+
+    #[test]
+    fn empty_values_are_missing() {
+        let cookies = SignedCookies::new(Some("secret"));
+        let headers = request(&cookies.set("prefs", "", 60).unwrap());
+        assert_eq!(cookies.read(&headers, "prefs"), None);
+        assert_eq!(cookies.read(&HeaderMap::new(), "prefs"), None);
+    }
+
+    #[test]
+    fn values_are_bound_to_their_cookie() {
+        let cookies = SignedCookies::new(Some("secret"));
+        let recent = request(&cookies.set("recent", "serde", 60).unwrap());
+        let signed = recent.get(COOKIE).unwrap().to_str().unwrap();
+        let copied = signed.replacen("recent=", "prefs=", 1);
+        assert_eq!(cookies.read(&with_cookie(&copied), "prefs"), None);
+    }
+
+    #[test]
+    fn cookies_are_found_by_name() {
+        let headers = with_cookie("theme=dark; prefs=a:b; xprefs=c");
+        assert_eq!(cookie(&headers, "prefs"), Some("a:b"));
+        assert_eq!(cookie(&headers, "theme"), Some("dark"));
+        assert_eq!(cookie(&headers, "refs"), None);
+    }
+}
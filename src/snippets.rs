@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+
+use crate::{
+    description_index::words,
+    schema::Crate,
+    search_backend::Highlighter,
+    syntax::{Expr, Field},
+    CrateResult, SearchIndex,
+};
+
+/// The number of results given snippets. Each snippet may require loading the
+/// crate's readme, so only the results likely to be read are highlighted.
+pub const SNIPPET_RESULTS: usize = 50;
+
+/// The longest snippet generated, in characters.
+pub(crate) const MAX_SNIPPET_CHARS: usize = 200;
+
+/// Fills in the [`CrateResult::snippet`] of the first [`SNIPPET_RESULTS`]
+/// results with an excerpt of the crate's description or readme, highlighting
+/// where `terms` matched. Results whose description and readme don't contain
+/// any of the terms are left without a snippet.
+pub fn highlight(
+    terms: &str,
+    db: &Database,
+    index: &SearchIndex,
+    results: &mut [CrateResult],
+) -> anyhow::Result<()> {
+    let Some(highlighter) = index.highlighter(terms)? else { return Ok(()) };
+
+    for result in results.iter_mut().take(SNIPPET_RESULTS) {
+        let snippet = highlighter.highlight(Field::Description, result.result.description());
+        if snippet.is_some() {
+            result.snippet = snippet;
+            continue;
+        }
+
+        let Some(krate) = Crate::get(&result.result.id(), db)? else { continue };
+        result.snippet = highlighter.highlight(Field::Readme, &krate.contents.readme);
+    }
+
+    Ok(())
+}
+
+/// Highlights the words of a search's terms, for search backends without a
+/// highlighter of their own.
+pub(crate) struct WordHighlighter {
+    /// The lowercased words of the terms that aren't negated.
+    words: HashSet<String>,
+}
+
+impl WordHighlighter {
+    pub fn new(expr: &Expr<'_>) -> Self {
+        let words = expr
+            .terms()
+            .into_iter()
+            .filter(|(_, negated)| !negated)
+            .flat_map(|(term, _)| words(term.text).map(|(_, word)| word.to_lowercase()))
+            .collect();
+        Self { words }
+    }
+}
+
+impl Highlighter for WordHighlighter {
+    /// Excerpts up to [`MAX_SNIPPET_CHARS`] characters, starting shortly
+    /// before the first highlighted word.
+    fn highlight(&self, _field: Field, text: &str) -> Option<String> {
+        let matches = words(text)
+            .filter(|(_, word)| self.words.contains(&word.to_lowercase()))
+            .map(|(start, word)| start..start + word.len())
+            .collect::<Vec<_>>();
+        let first = matches.first()?;
+
+        // Begin at a word within a quarter of the snippet before the match.
+        let start = words(text)
+            .map(|(start, _)| start)
+            .find(|start| first.start - *start <= MAX_SNIPPET_CHARS / 4)
+            .unwrap_or(first.start);
+        let limit = text[start..]
+            .char_indices()
+            .nth(MAX_SNIPPET_CHARS)
+            .map_or(text.len(), |(offset, _)| start + offset);
+        // End at the last word that fits, unless it would cut off the match.
+        let end = words(text)
+            .map(|(start, word)| start + word.len())
+            .take_while(|end| *end <= limit)
+            .last()
+            .filter(|end| *end >= first.end)
+            .unwrap_or(limit);
+
+        let mut html = String::new();
+        let mut position = start;
+        for range in matches.into_iter().filter(|range| range.end <= end) {
+            escape(&text[position..range.start], &mut html);
+            html.push_str("<b>");
+            escape(&text[range.clone()], &mut html);
+            html.push_str("</b>");
+            position = range.end;
+        }
+        escape(&text[position..end], &mut html);
+        Some(html)
+    }
+}
+
+fn escape(text: &str, html: &mut String) {
+    for ch in text.chars() {
+        match ch {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#x27;"),
+            ch => html.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax;
+
+    fn highlight(terms: &str, text: &str) -> Option<String> {
+        WordHighlighter::new(&syntax::parse(terms).unwrap()).highlight(Field::Description, text)
+    }
+
+    #[test]
+    fn words_are_highlighted() {
+        assert_eq!(
+            highlight(
+                "json parser",
+                "High performance JSON parser based on simdjson."
+            )
+            .as_deref(),
+            Some("High performance <b>JSON</b> <b>parser</b> based on simdjson")
+        );
+        assert_eq!(
+            highlight("größe", "Größe der Datei").as_deref(),
+            Some("<b>Größe</b> der Datei")
+        );
+        assert_eq!(highlight("yaml", "A JSON parser."), None);
+    }
+
+    #[test]
+    fn negated_words_are_not_highlighted() {
+        assert_eq!(
+            highlight("json NOT serde", "A JSON format for serde.").as_deref(),
+            Some("A <b>JSON</b> format for serde")
+        );
+        assert_eq!(highlight("json NOT serde", "A format for serde."), None);
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            highlight("html", "Escapes <html> & \"quotes\"").as_deref(),
+            Some("Escapes &lt;<b>html</b>&gt; &amp; &quot;quotes")
+        );
+    }
+
+    #[test]
+    fn long_text_is_excerpted() {
+        let text = format!("{}target {}", "word ".repeat(100), "word ".repeat(100));
+        let snippet = highlight("target", &text).unwrap();
+        // The excerpt starts a quarter of the snippet before the match and
+        // ends at the last whole word that fits.
+        assert!(snippet.starts_with(&format!("{}<b>target</b> word", "word ".repeat(10))));
+        assert!(snippet.ends_with(" word"));
+        let plain = snippet.replace("<b>", "").replace("</b>", "");
+        assert_eq!(plain, text[450..646]);
+        assert!(plain.chars().count() <= MAX_SNIPPET_CHARS);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json,
+};
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::{
+    api::find_crate,
+    cache::Cache,
+    schema::{self, DependenciesByVersion, VersionsByCrate},
+    webserver::{internal_error, AppState},
+};
+
+/// Serves the imported crates using Cargo's sparse registry protocol, so that
+/// Cargo can resolve dependencies without access to crates.io. `download_url`
+/// is where Cargo downloads the `.crate` files from.
+pub(super) fn router(download_url: String) -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/config.json",
+            get(move || {
+                let dl = download_url.clone();
+                async move { Json(RegistryConfig { dl }) }
+            }),
+        )
+        .route("/*path", get(index_file))
+}
+
+#[derive(Serialize, Debug)]
+struct RegistryConfig {
+    dl: String,
+}
+
+async fn index_file(
+    State((db, cache, _)): State<AppState>,
+    Path(path): Path<String>,
+) -> Result<Response, StatusCode> {
+    let Some((prefix, name)) = path.rsplit_once('/') else { return Err(StatusCode::NOT_FOUND) };
+    if index_prefix(name).as_deref() != Some(prefix) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let lines = index_lines(&db, &cache, name)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(([(CONTENT_TYPE, "text/plain")], lines).into_response())
+}
+
+/// Returns the directory of the index file for `name`, as defined by Cargo's
+/// index format, or None if `name` can't be a crate's name.
+fn index_prefix(name: &str) -> Option<String> {
+    // Crate names are ASCII, so the name can be sliced by bytes.
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-')
+    {
+        return None;
+    }
+    let name = name.to_ascii_lowercase();
+    Some(match name.len() {
+        1 => String::from("1"),
+        2 => String::from("2"),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    })
+}
+
+/// A line of an index file, describing one version of a crate.
+#[derive(Serialize, Debug)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    deps: Vec<IndexDependency>,
+    cksum: String,
+    features: BTreeMap<String, Vec<String>>,
+    /// Features using the `dep:` or `?` syntax, which older versions of Cargo
+    /// can't parse.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    features2: BTreeMap<String, Vec<String>>,
+    yanked: bool,
+    links: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    v: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rust_version: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct IndexDependency {
+    name: String,
+    req: String,
+    features: Vec<String>,
+    optional: bool,
+    default_features: bool,
+    target: Option<String>,
+    kind: String,
+    /// The name of the crate, when it is renamed to `name` in the manifest.
+    package: Option<String>,
+}
+
+/// Returns the contents of the index file for the crate named `name`, or None
+/// if the crate isn't found or is blocked, so that Cargo can't resolve blocked
+/// crates.
+fn index_lines(db: &Database, cache: &Cache, name: &str) -> anyhow::Result<Option<String>> {
+    let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+    let crates = cache.crates()?;
+    let Some(krate) = crates.get(id).filter(|krate| !krate.blocked()) else { return Ok(None) };
+
+    let mut version_ids = VersionsByCrate::entries(db)
+        .with_key(&id)
+        .query()?
+        .into_iter()
+        .map(|mapping| mapping.source.id.deserialize::<u64>())
+        .collect::<Result<Vec<_>, _>>()?;
+    // Versions are listed in the order they were published.
+    version_ids.sort_unstable();
+
+    let mut lines = String::new();
+    for version_id in version_ids {
+        let Some(version) = schema::Version::get(&version_id, db)? else { continue };
+        let version = version.contents;
+
+        let mut deps = Vec::new();
+        for mapping in DependenciesByVersion::entries(db)
+            .with_key(&version_id)
+            .query()?
+        {
+            let dependency = mapping.value;
+            let Some(dependency_crate) = crates.get(dependency.crate_id) else { continue };
+            let package = dependency_crate.name().to_string();
+            let (name, package) = match dependency.explicit_name {
+                Some(explicit_name) => (explicit_name, Some(package)),
+                None => (package, None),
+            };
+            deps.push(IndexDependency {
+                name,
+                req: dependency.req,
+                features: dependency.features,
+                optional: dependency.optional,
+                default_features: dependency.default_features,
+                target: dependency.target,
+                kind: dependency.kind.to_string(),
+                package,
+            });
+        }
+
+        let all_features: BTreeMap<String, Vec<String>> = if version.features.trim().is_empty() {
+            BTreeMap::new()
+        } else {
+            serde_json::from_str(&version.features)?
+        };
+        let (features2, features): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            all_features.into_iter().partition(|(_, enables)| {
+                enables
+                    .iter()
+                    .any(|enable| enable.starts_with("dep:") || enable.contains("?/"))
+            });
+
+        let entry = IndexEntry {
+            name: krate.name().to_string(),
+            vers: version.version,
+            deps,
+            cksum: version.checksum,
+            v: (!features2.is_empty()).then_some(2),
+            features,
+            features2,
+            yanked: version.yanked,
+            links: Some(version.links).filter(|links| !links.is_empty()),
+            rust_version: version.rust_version,
+        };
+        lines.push_str(&serde_json::to_string(&entry)?);
+        lines.push('\n');
+    }
+
+    Ok(Some(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_prefixes() {
+        assert_eq!(index_prefix("a").as_deref(), Some("1"));
+        assert_eq!(index_prefix("ab").as_deref(), Some("2"));
+        assert_eq!(index_prefix("abc").as_deref(), Some("3/a"));
+        assert_eq!(index_prefix("abcd").as_deref(), Some("ab/cd"));
+        assert_eq!(index_prefix("Serde_JSON").as_deref(), Some("se/rd"));
+        assert_eq!(index_prefix("pico-args").as_deref(), Some("pi/co"));
+    }
+
+    #[test]
+    fn invalid_names_have_no_prefix() {
+        for name in [
+            "",
+            "éa",
+            "aé",
+            "abé",
+            "日本語",
+            "a b",
+            "a/b",
+            "a.b",
+            "..",
+            "a%2F",
+        ] {
+            assert_eq!(index_prefix(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn blocked_crates_are_left_out() {
+        let (_dir, db) = crate::test_database();
+        crate::test_crate("fine").push_into(&db).unwrap();
+        crate::test_crate("evil").push_into(&db).unwrap();
+        schema::BlockedCrate {
+            reason: String::from("malware"),
+            advisory: None,
+            blocked_at: String::from("2023-03-20 00:00:00"),
+        }
+        .insert_into(&String::from("evil"), &db)
+        .unwrap();
+        let cache = crate::test_cache(&db);
+
+        assert_eq!(
+            index_lines(&db, &cache, "fine").unwrap().as_deref(),
+            Some("")
+        );
+        assert_eq!(index_lines(&db, &cache, "evil").unwrap(), None);
+        assert_eq!(index_lines(&db, &cache, "missing").unwrap(), None);
+    }
+}
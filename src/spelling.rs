@@ -0,0 +1,294 @@
+//! Spelling correction of search terms.
+//!
+//! Corrections are found with the symmetric delete algorithm used by
+//! SymSpell. Every dictionary word is indexed by itself and by the strings
+//! made by deleting one of its characters. Looking up a misspelling and its
+//! own deletes then finds every word within one insertion, deletion,
+//! substitution, or transposition of it, without comparing it to each word in
+//! the dictionary.
+//!
+//! Only words one edit away are suggested. Allowing two edits quadruples the
+//! size of the index and suggests a different word for many correctly spelled
+//! but uncommon words.
+
+use std::collections::HashMap;
+
+use crate::filters::Filters;
+
+/// Words shorter than this are never corrected, since most short words are
+/// one edit away from many others.
+const MIN_WORD_LENGTH: usize = 4;
+
+const MAX_WORD_LENGTH: usize = 40;
+
+/// How many times more common the best correction must be than any other
+/// word one edit away for a query to be corrected.
+const MIN_FREQUENCY_RATIO: u64 = 4;
+
+/// A dictionary of crate names, keywords, and common words used to correct
+/// misspelled search terms.
+#[derive(Debug, Default)]
+pub struct Spelling {
+    /// Normalized words, sorted, with the form shown when suggesting the
+    /// word and how common the word is.
+    words: Vec<(String, String, u64)>,
+    /// The hash of each word and of each of its deletes, with the index of
+    /// the word in `words`, sorted by hash.
+    deletes: Vec<(u32, u32)>,
+}
+
+impl Spelling {
+    /// Builds a dictionary of `words` and how common each is, such as the
+    /// number of crates using it. Words that differ only by case or by `-`
+    /// and `_` are merged, keeping the most common form.
+    pub fn new(words: impl IntoIterator<Item = (String, u64)>) -> Self {
+        let mut merged = HashMap::<String, (String, u64)>::new();
+        for (word, frequency) in words {
+            let normalized = normalize(&word);
+            if !is_word(&normalized) {
+                continue;
+            }
+            let entry = merged.entry(normalized).or_insert((word.clone(), 0));
+            if frequency > entry.1 {
+                *entry = (word, frequency);
+            }
+        }
+        let mut words = merged
+            .into_iter()
+            .map(|(normalized, (word, frequency))| (normalized, word, frequency))
+            .collect::<Vec<_>>();
+        words.sort_unstable();
+
+        let mut deletes = Vec::new();
+        for (index, (normalized, _, _)) in words.iter().enumerate() {
+            let index = u32::try_from(index).expect("too many words");
+            deletes.push((hash(normalized), index));
+            deletes.extend(single_deletes(normalized).map(|delete| (hash(&delete), index)));
+        }
+        deletes.sort_unstable();
+        deletes.dedup();
+        deletes.shrink_to_fit();
+
+        Self { words, deletes }
+    }
+
+    /// Returns the correction of `word` if it's clearly misspelled: it isn't
+    /// in the dictionary, and one word a single edit away from it is much
+    /// more common than any other.
+    pub fn correct(&self, word: &str) -> Option<&str> {
+        let normalized = normalize(word);
+        if !is_word(&normalized) || self.position(&normalized).is_some() {
+            return None;
+        }
+
+        let mut candidates = Vec::<u32>::new();
+        for delete in std::iter::once(normalized.clone()).chain(single_deletes(&normalized)) {
+            let hash = hash(&delete);
+            let start = self.deletes.partition_point(|(key, _)| *key < hash);
+            candidates.extend(
+                self.deletes[start..]
+                    .iter()
+                    .take_while(|(key, _)| *key == hash)
+                    .map(|(_, index)| *index),
+            );
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut matches = candidates
+            .into_iter()
+            .map(|index| &self.words[index as usize])
+            .filter(|(candidate, _, _)| edit_distance(&normalized, candidate) == 1)
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| b.2.cmp(&a.2));
+        let (_, best, frequency) = matches.first()?;
+        let runner_up = matches.get(1).map_or(0, |(_, _, frequency)| *frequency);
+        (*frequency >= runner_up.saturating_mul(MIN_FREQUENCY_RATIO).max(1))
+            .then_some(best.as_str())
+    }
+
+    /// Returns `query` with its search term corrected, if it has a single,
+    /// clearly misspelled term. Queries of several terms aren't corrected,
+    /// since a word that looks misspelled on its own is often meaningful
+    /// alongside the others. Filters are kept as they are.
+    pub fn correct_query(&self, query: &str) -> Option<String> {
+        let (terms, _) = Filters::parse(query);
+        let mut words = terms.split_whitespace();
+        let (Some(word), None) = (words.next(), words.next()) else { return None };
+        let corrected = self.correct(word)?;
+        Some(
+            query
+                .split_whitespace()
+                .map(|part| if part == word { corrected } else { part })
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    fn position(&self, normalized: &str) -> Option<usize> {
+        self.words
+            .binary_search_by(|(word, _, _)| word.as_str().cmp(normalized))
+            .ok()
+    }
+}
+
+/// Returns `word` in lowercase with `-` replaced by `_`, which is how crate
+/// names are compared.
+fn normalize(word: &str) -> String {
+    word.chars()
+        .map(|ch| if ch == '-' { '_' } else { ch })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Returns whether `normalized` can be corrected or suggested: a word of
+/// letters, digits, and underscores containing at least one letter.
+fn is_word(normalized: &str) -> bool {
+    let length = normalized.chars().count();
+    (MIN_WORD_LENGTH..=MAX_WORD_LENGTH).contains(&length)
+        && normalized
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_')
+        && normalized.chars().any(char::is_alphabetic)
+}
+
+/// Returns every string made by deleting one character of `word`.
+fn single_deletes(word: &str) -> impl Iterator<Item = String> + '_ {
+    word.char_indices().map(move |(start, ch)| {
+        let mut delete = String::with_capacity(word.len());
+        delete.push_str(&word[..start]);
+        delete.push_str(&word[start + ch.len_utf8()..]);
+        delete
+    })
+}
+
+/// A 32-bit FNV-1a hash. Collisions only add candidates, which are checked by
+/// their edit distance.
+fn hash(text: &str) -> u32 {
+    text.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Returns the number of insertions, deletions, substitutions, and
+/// transpositions of adjacent characters needed to turn `a` into `b`, where
+/// no character is edited more than once.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let width = b.len() + 1;
+    let mut distances = vec![0_usize; (a.len() + 1) * width];
+    for i in 0..=a.len() {
+        distances[i * width] = i;
+    }
+    for (j, distance) in distances.iter_mut().enumerate().take(width) {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[(i - 1) * width + j] + 1)
+                .min(distances[i * width + j - 1] + 1)
+                .min(distances[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[(i - 2) * width + j - 2] + 1);
+            }
+            distances[i * width + j] = distance;
+        }
+    }
+    distances[a.len() * width + b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spelling(words: &[(&str, u64)]) -> Spelling {
+        Spelling::new(
+            words
+                .iter()
+                .map(|(word, frequency)| ((*word).to_string(), *frequency)),
+        )
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("serde", "serde"), 0);
+        assert_eq!(edit_distance("serde", "serd"), 1);
+        assert_eq!(edit_distance("serd", "serde"), 1);
+        assert_eq!(edit_distance("serde", "serve"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "tokio"), 5);
+        // Transposing adjacent characters is a single edit.
+        assert_eq!(edit_distance("sedre", "serde"), 1);
+        assert_eq!(edit_distance("tokoi", "tokio"), 1);
+        // No character is edited twice, so this isn't a transposition and an
+        // insertion.
+        assert_eq!(edit_distance("ca", "abc"), 3);
+        // Distances count characters rather than bytes.
+        assert_eq!(edit_distance("naïve", "naive"), 1);
+        assert_eq!(edit_distance("日本語", "日本"), 1);
+        assert_eq!(edit_distance("日語本", "日本語"), 1);
+    }
+
+    #[test]
+    fn words_are_merged_and_filtered() {
+        let spelling = spelling(&[
+            ("Serde-Json", 5),
+            ("serde_json", 10),
+            ("SERDE_JSON", 1),
+            ("log", 100),
+            ("1234", 100),
+            ("has space", 100),
+            (&"a".repeat(MAX_WORD_LENGTH + 1), 100),
+        ]);
+        assert_eq!(
+            spelling.words,
+            vec![("serde_json".to_string(), "serde_json".to_string(), 10)]
+        );
+        assert!(spelling.position("serde_json").is_some());
+    }
+
+    #[test]
+    fn corrections() {
+        let spelling = spelling(&[("serde", 1000), ("tokio", 900), ("serve", 10)]);
+        assert_eq!(spelling.correct("sedre"), Some("serde"));
+        assert_eq!(spelling.correct("srde"), Some("serde"));
+        assert_eq!(spelling.correct("Tokoi"), Some("tokio"));
+        // Known words and short words are left alone.
+        assert_eq!(spelling.correct("serve"), None);
+        assert_eq!(spelling.correct("SERDE"), None);
+        assert_eq!(spelling.correct("sde"), None);
+        // Words two edits away aren't suggested.
+        assert_eq!(spelling.correct("srdee"), None);
+    }
+
+    #[test]
+    fn ambiguous_corrections_are_skipped() {
+        let spelling = spelling(&[("crate", 100), ("grate", 90), ("trait", 400), ("trail", 10)]);
+        assert_eq!(spelling.correct("drate"), None);
+        assert_eq!(spelling.correct("traiy"), Some("trait"));
+    }
+
+    #[test]
+    fn non_ascii_corrections() {
+        let spelling = spelling(&[("naïve", 10), ("größe", 10)]);
+        assert_eq!(spelling.correct("naive"), Some("naïve"));
+        assert_eq!(spelling.correct("GRÖßE"), None);
+        assert_eq!(spelling.correct("grösse"), None);
+        assert_eq!(spelling.correct("gröse"), Some("größe"));
+    }
+
+    #[test]
+    fn query_corrections() {
+        let spelling = spelling(&[("serde", 1000), ("tokio", 900)]);
+        assert_eq!(spelling.correct_query("sedre").as_deref(), Some("serde"));
+        assert_eq!(
+            spelling.correct_query("sedre placeholders:hide").as_deref(),
+            Some("serde placeholders:hide")
+        );
+        assert_eq!(spelling.correct_query("serde"), None);
+        assert_eq!(spelling.correct_query("async sedre"), None);
+        assert_eq!(spelling.correct_query(""), None);
+    }
+}
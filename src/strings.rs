@@ -0,0 +1,30 @@
+/// Strings packed end to end into a single buffer.
+#[derive(Debug, Default)]
+pub struct StringArena {
+    buffer: String,
+}
+
+/// The location of a string in a [`StringArena`].
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    start: u32,
+    len: u32,
+}
+
+impl StringArena {
+    pub fn push(&mut self, value: &str) -> Span {
+        let start = u32::try_from(self.buffer.len()).expect("string arena too large");
+        let len = u32::try_from(value.len()).expect("string too large");
+        self.buffer.push_str(value);
+        Span { start, len }
+    }
+
+    pub fn get(&self, span: Span) -> &str {
+        let start = span.start as usize;
+        &self.buffer[start..start + span.len as usize]
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.buffer.shrink_to_fit();
+    }
+}
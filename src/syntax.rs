@@ -0,0 +1,534 @@
+use std::fmt::Display;
+
+/// The most tokens read from a query. Anything after them is ignored.
+const MAX_TOKENS: usize = 256;
+
+/// The most distinct terms a parsed query can contain. The rest of a query
+/// from the term after them on is ignored, so that a term such as a negated
+/// one is never parsed without being matched.
+pub const MAX_TERMS: usize = 64;
+
+/// The deepest parentheses may be nested. Parentheses nested more deeply are
+/// ignored, so that parsing and dropping an expression can't overflow the
+/// stack.
+const MAX_DEPTH: usize = 32;
+
+/// A word or quoted phrase in a search query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Term<'a> {
+    /// The term's text, without quotes.
+    pub text: &'a str,
+    /// Whether the term is a phrase, whose words must appear next to each
+    /// other, in order.
+    pub phrase: bool,
+    /// The only field the term may match, when targeted with a prefix such as
+    /// `name:`.
+    pub field: Option<Field>,
+}
+
+impl<'a> Term<'a> {
+    fn new(text: &'a str, field: Option<Field>) -> Self {
+        Self {
+            text,
+            phrase: text.contains(|ch: char| ch.is_ascii_whitespace()),
+            field,
+        }
+    }
+
+    /// Returns true if the term can match crate names.
+    pub fn matches_names(&self) -> bool {
+        matches!(self.field, None | Some(Field::Name))
+    }
+
+    /// Returns true if the term can match crate keywords.
+    pub fn matches_keywords(&self) -> bool {
+        self.field.is_none()
+    }
+}
+
+impl Display for Term<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(field) = self.field {
+            write!(f, "{}:", field.name())?;
+        }
+        if self.phrase {
+            write!(f, "\"{}\"", self.text)
+        } else {
+            f.write_str(self.text)
+        }
+    }
+}
+
+/// A field of the search index that a term can be restricted to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Field {
+    Name,
+    Description,
+    Readme,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Field::Name),
+            "description" => Some(Field::Description),
+            "readme" => Some(Field::Readme),
+            _ => None,
+        }
+    }
+
+    /// The name of the field in the search index.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Name => "name",
+            Field::Description => "description",
+            Field::Readme => "readme",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operator {
+    And,
+    Or,
+    Not,
+}
+
+/// A piece of a search query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Token<'a> {
+    Term(Term<'a>),
+    /// `AND`, `OR`, or `NOT`. Operators must be uppercase, so that the
+    /// lowercase words can still be searched for.
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Term(term) => term.fmt(f),
+            Token::Operator(Operator::And) => f.write_str("AND"),
+            Token::Operator(Operator::Or) => f.write_str("OR"),
+            Token::Operator(Operator::Not) => f.write_str("NOT"),
+            Token::Open => f.write_str("("),
+            Token::Close => f.write_str(")"),
+        }
+    }
+}
+
+/// Splits `query` into words, quoted phrases, operators, and parentheses. A
+/// quote without a closing quote extends to the end of the query, and a quoted
+/// single word is treated as a word. Words and phrases prefixed with `name:`,
+/// `description:`, or `readme:` are restricted to that field. Only the first
+/// [`MAX_TOKENS`] tokens are returned.
+pub fn tokens(query: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = query;
+    // The field prefixing the quoted phrase that follows.
+    let mut phrase_field = None;
+    while tokens.len() < MAX_TOKENS {
+        rest = rest.trim_start_matches(|ch: char| ch.is_ascii_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let (phrase, after) = quoted.split_once('"').unwrap_or((quoted, ""));
+            let phrase = phrase.trim_matches(|ch: char| ch.is_ascii_whitespace());
+            let field = phrase_field.take();
+            if !phrase.is_empty() {
+                tokens.push(Token::Term(Term::new(phrase, field)));
+            }
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('(') {
+            tokens.push(Token::Open);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(')') {
+            tokens.push(Token::Close);
+            rest = after;
+        } else {
+            let end = rest
+                .find(|ch: char| ch.is_ascii_whitespace() || matches!(ch, '"' | '(' | ')'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            match word {
+                "AND" => tokens.push(Token::Operator(Operator::And)),
+                "OR" => tokens.push(Token::Operator(Operator::Or)),
+                "NOT" => tokens.push(Token::Operator(Operator::Not)),
+                word => {
+                    let targeted = word
+                        .split_once(':')
+                        .and_then(|(field, text)| Some((Field::parse(field)?, text)));
+                    match targeted {
+                        Some((field, "")) if rest.starts_with('"') => phrase_field = Some(field),
+                        Some((field, text)) if !text.is_empty() => {
+                            tokens.push(Token::Term(Term::new(text, Some(field))));
+                        }
+                        _ => tokens.push(Token::Term(Term::new(word, None))),
+                    }
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// A parsed search query.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expr<'a> {
+    /// Terms written next to each other without an operator, which are
+    /// matched the same way as a query without any operators.
+    Terms(Vec<Term<'a>>),
+    And(Vec<Expr<'a>>),
+    Or(Vec<Expr<'a>>),
+    Not(Box<Expr<'a>>),
+}
+
+impl<'a> Expr<'a> {
+    /// Returns every term in the expression, and whether each is negated.
+    pub fn terms(&self) -> Vec<(Term<'a>, bool)> {
+        let mut terms = Vec::new();
+        self.collect_terms(false, &mut terms);
+        terms
+    }
+
+    fn collect_terms(&self, negated: bool, terms: &mut Vec<(Term<'a>, bool)>) {
+        match self {
+            Expr::Terms(list) => terms.extend(list.iter().map(|term| (*term, negated))),
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_terms(negated, terms);
+                }
+            }
+            Expr::Not(expr) => expr.collect_terms(!negated, terms),
+        }
+    }
+
+    /// Returns whether the expression uses `AND` or `NOT`, which restrict the
+    /// results rather than only listing alternatives.
+    pub fn has_constraints(&self) -> bool {
+        match self {
+            Expr::Terms(_) => false,
+            Expr::Or(exprs) => exprs.iter().any(Expr::has_constraints),
+            Expr::And(_) | Expr::Not(_) => true,
+        }
+    }
+
+    /// Evaluates the expression, using `matched` to determine whether each
+    /// term matches.
+    pub fn matches(&self, matched: &impl Fn(&Term<'a>) -> bool) -> bool {
+        match self {
+            Expr::Terms(terms) => terms.iter().all(matched),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.matches(matched)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.matches(matched)),
+            Expr::Not(expr) => !expr.matches(matched),
+        }
+    }
+}
+
+/// Parses `query` into an expression, returning None if it contains no terms.
+///
+/// `OR` binds more loosely than `AND`, which binds more loosely than terms
+/// written next to each other. `NOT` applies to the term or parenthesized
+/// expression that follows it. Unbalanced parentheses, parentheses nested
+/// more than [`MAX_DEPTH`] deep, and operators missing an operand are ignored,
+/// as is everything after the first [`MAX_TERMS`] distinct terms.
+pub fn parse(query: &str) -> Option<Expr<'_>> {
+    let mut parser = Parser {
+        tokens: limit_nesting(limit_terms(tokens(query))),
+        position: 0,
+    };
+    let mut exprs = Vec::new();
+    while parser.position < parser.tokens.len() {
+        let start = parser.position;
+        exprs.extend(parser.or());
+        if parser.position == start {
+            // Skip a closing parenthesis without an opening one.
+            parser.position += 1;
+        }
+    }
+    combine(exprs, Expr::And)
+}
+
+/// Truncates `tokens` before the term that would be the [`MAX_TERMS`] + 1st
+/// distinct term.
+fn limit_terms(mut tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let mut distinct = Vec::with_capacity(MAX_TERMS);
+    for (index, token) in tokens.iter().enumerate() {
+        let Token::Term(term) = token else { continue };
+        if distinct.contains(term) {
+            continue;
+        } else if distinct.len() == MAX_TERMS {
+            tokens.truncate(index);
+            break;
+        }
+        distinct.push(*term);
+    }
+    tokens
+}
+
+/// Removes the parentheses nested more than [`MAX_DEPTH`] deep, along with
+/// the parentheses closing them.
+fn limit_nesting(tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let mut depth = 0;
+    // The number of removed parentheses that haven't been closed yet.
+    let mut removed = 0;
+    tokens
+        .into_iter()
+        .filter(|token| match token {
+            Token::Open if depth == MAX_DEPTH => {
+                removed += 1;
+                false
+            }
+            Token::Open => {
+                depth += 1;
+                true
+            }
+            Token::Close if removed > 0 => {
+                removed -= 1;
+                false
+            }
+            Token::Close => {
+                depth = depth.saturating_sub(1);
+                true
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn eat(&mut self, token: Token<'a>) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Option<Expr<'a>> {
+        let mut exprs = Vec::new();
+        loop {
+            exprs.extend(self.and());
+            if !self.eat(Token::Operator(Operator::Or)) {
+                break;
+            }
+        }
+        combine(exprs, Expr::Or)
+    }
+
+    fn and(&mut self) -> Option<Expr<'a>> {
+        let mut exprs = Vec::new();
+        loop {
+            exprs.extend(self.sequence());
+            if !self.eat(Token::Operator(Operator::And)) {
+                break;
+            }
+        }
+        combine(exprs, Expr::And)
+    }
+
+    /// Parses terms and expressions written next to each other. Adjacent
+    /// terms are grouped into a single [`Expr::Terms`].
+    fn sequence(&mut self) -> Option<Expr<'a>> {
+        let mut terms = Vec::new();
+        let mut exprs = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(
+                token,
+                Token::Close | Token::Operator(Operator::And | Operator::Or)
+            ) {
+                break;
+            }
+            match self.unary() {
+                Some(Expr::Terms(list)) => terms.extend(list),
+                Some(expr) => exprs.push(expr),
+                None => {}
+            }
+        }
+
+        if !terms.is_empty() {
+            exprs.insert(0, Expr::Terms(terms));
+        }
+        combine(exprs, Expr::And)
+    }
+
+    fn unary(&mut self) -> Option<Expr<'a>> {
+        let token = self.peek()?;
+        self.position += 1;
+        match token {
+            Token::Term(term) => Some(Expr::Terms(vec![term])),
+            Token::Operator(Operator::Not) => {
+                // A run of NOTs is read without recursing, since a double
+                // negative cancels out.
+                let mut negated = true;
+                while self.eat(Token::Operator(Operator::Not)) {
+                    negated = !negated;
+                }
+                let expr = self.unary()?;
+                Some(if negated {
+                    Expr::Not(Box::new(expr))
+                } else {
+                    expr
+                })
+            }
+            Token::Open => {
+                let expr = self.or();
+                self.eat(Token::Close);
+                expr
+            }
+            Token::Operator(_) | Token::Close => None,
+        }
+    }
+}
+
+fn combine<'a>(mut exprs: Vec<Expr<'a>>, group: fn(Vec<Expr<'a>>) -> Expr<'a>) -> Option<Expr<'a>> {
+    match exprs.len() {
+        0 => None,
+        1 => exprs.pop(),
+        _ => Some(group(exprs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Term<'_> {
+        Term::new(text, None)
+    }
+
+    #[test]
+    fn deep_parentheses_are_ignored() {
+        assert_eq!(parse(&"(".repeat(100_000)), None);
+
+        let query = format!("{}serde{}", "(".repeat(100), ")".repeat(100));
+        assert_eq!(parse(&query), Some(Expr::Terms(vec![word("serde")])));
+
+        let tokens = limit_nesting(tokens(&query));
+        assert_eq!(
+            tokens.iter().filter(|token| **token == Token::Open).count(),
+            MAX_DEPTH
+        );
+        assert_eq!(
+            tokens
+                .iter()
+                .filter(|token| **token == Token::Close)
+                .count(),
+            MAX_DEPTH
+        );
+    }
+
+    #[test]
+    fn nesting_limit_keeps_following_terms_outside_the_group() {
+        let query = format!(
+            "{}a OR b{} c",
+            "(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        assert_eq!(
+            parse(&query),
+            Some(Expr::And(vec![
+                Expr::Terms(vec![word("c")]),
+                Expr::Or(vec![
+                    Expr::Terms(vec![word("a")]),
+                    Expr::Terms(vec![word("b")])
+                ]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn repeated_nots_cancel_out() {
+        assert_eq!(parse(&"NOT ".repeat(100_000)), None);
+
+        let even = format!("{}serde", "NOT ".repeat(200));
+        assert_eq!(parse(&even), Some(Expr::Terms(vec![word("serde")])));
+        let odd = format!("{}serde", "NOT ".repeat(199));
+        assert_eq!(
+            parse(&odd),
+            Some(Expr::Not(Box::new(Expr::Terms(vec![word("serde")]))))
+        );
+    }
+
+    #[test]
+    fn alternating_nots_and_parentheses_are_limited() {
+        assert_eq!(parse(&"NOT (".repeat(100_000)), None);
+
+        // The NOTs before the ignored parentheses cancel out in pairs.
+        let query = format!("{}serde", "NOT (".repeat(MAX_DEPTH + 8));
+        let mut expr = parse(&query).expect("expected an expression");
+        let mut nots = 0;
+        while let Expr::Not(inner) = expr {
+            nots += 1;
+            expr = *inner;
+        }
+        assert_eq!(nots, MAX_DEPTH);
+        assert_eq!(expr, Expr::Terms(vec![word("serde")]));
+    }
+
+    #[test]
+    fn tokens_are_limited() {
+        let query = "serde ".repeat(MAX_TOKENS * 2);
+        assert_eq!(tokens(&query).len(), MAX_TOKENS);
+        let Some(Expr::Terms(terms)) = parse(&query) else { panic!("expected terms") };
+        assert_eq!(terms.len(), MAX_TOKENS);
+    }
+
+    #[test]
+    fn terms_are_limited() {
+        let words = (0..MAX_TERMS + 1)
+            .map(|index| format!("word{index}"))
+            .collect::<Vec<_>>();
+        let query = format!(
+            "{} serde NOT {}",
+            words[..MAX_TERMS].join(" "),
+            words[MAX_TERMS]
+        );
+        let Some(Expr::Terms(terms)) = parse(&query) else { panic!("expected terms") };
+        assert_eq!(terms.len(), MAX_TERMS);
+        assert_eq!(terms.last().unwrap().text, words[MAX_TERMS - 1]);
+
+        // Repeated terms don't count toward the limit.
+        let query = format!("{} {}", words[..MAX_TERMS].join(" "), words[0]);
+        let Some(Expr::Terms(terms)) = parse(&query) else { panic!("expected terms") };
+        assert_eq!(terms.len(), MAX_TERMS + 1);
+    }
+
+    #[test]
+    fn unbalanced_input() {
+        assert_eq!(parse("(serde"), Some(Expr::Terms(vec![word("serde")])));
+        assert_eq!(parse("serde)"), Some(Expr::Terms(vec![word("serde")])));
+        assert_eq!(
+            parse(") serde ( json"),
+            Some(Expr::Terms(vec![word("serde"), word("json")]))
+        );
+        assert_eq!(parse("serde OR"), Some(Expr::Terms(vec![word("serde")])));
+        assert_eq!(parse("AND serde"), Some(Expr::Terms(vec![word("serde")])));
+        assert_eq!(parse("NOT"), None);
+        assert_eq!(parse("(((())))"), None);
+        assert_eq!(parse(")))((("), None);
+    }
+
+    #[test]
+    fn constraints() {
+        let constrained = |query| parse(query).expect("has terms").has_constraints();
+        assert!(!constrained("http server"));
+        assert!(!constrained("http OR server"));
+        assert!(constrained("serde NOT json"));
+        assert!(constrained("http AND server"));
+        assert!(constrained("orm OR (sql NOT async)"));
+    }
+}
@@ -0,0 +1,466 @@
+//! The tantivy [`SearchBackend`], which indexes every crate's name,
+//! description, and README.
+//!
+//! Rebuilding the index writes a new generation of it to a sibling directory
+//! while searches continue to use the current one. Once the new generation is
+//! committed, a pointer file is replaced to name it, searches switch to it, and
+//! the previous generation is deleted.
+//!
+//! Writers wait for tantivy's merges to finish before releasing their claim
+//! on the index, so that the current generation's files don't change while
+//! a snapshot copies them.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    doc,
+    query::{BooleanQuery, BoostQuery, Occur, Query, QueryParser},
+    schema::{Field, Schema, Value, INDEXED, STORED, TEXT},
+    Index, IndexReader, ReloadPolicy, Searcher, SnippetGenerator, Term,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    replication::copy_dir,
+    schema::Crate,
+    search_backend::{Highlighter, IndexWriter, SearchBackend},
+    snippets::MAX_SNIPPET_CHARS,
+    syntax::{self, Expr},
+    PHRASE_BOOST,
+};
+
+/// The directory of the index's first generation, inside the database's
+/// directory. Later generations add a suffix to it.
+pub(crate) const DIRECTORY: &str = "tantivy";
+
+/// The file naming the current generation's directory. Without it, the
+/// current generation is [`DIRECTORY`].
+const CURRENT_FILE: &str = "tantivy.current";
+
+/// The memory each index writer may use before flushing to disk.
+const WRITER_MEMORY: usize = 4 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct TantivyIndex {
+    /// The directory containing every generation of the index.
+    path: Arc<Path>,
+    current: Arc<RwLock<Arc<Generation>>>,
+    /// Keeps imports from writing to a generation that a rebuild is about to
+    /// replace.
+    claims: Arc<Mutex<Claims>>,
+}
+
+impl std::fmt::Debug for TantivyIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TantivyIndex")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TantivyIndex {
+    /// Opens the current generation of the index stored in `path`, creating
+    /// the index if needed.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let directory = match std::fs::read_to_string(path.join(CURRENT_FILE)) {
+            Ok(directory) => directory.trim().to_string(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::from(DIRECTORY),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path: Arc::from(path),
+            current: Arc::new(RwLock::new(Arc::new(Generation::open(
+                path.join(directory),
+            )?))),
+            claims: Arc::default(),
+        })
+    }
+
+    /// Returns the generation searches currently use.
+    fn current(&self) -> Arc<Generation> {
+        self.current.read().expect("poisoned").clone()
+    }
+
+    /// Makes `generation` the current generation, returning the previous one.
+    fn switch_to(&self, generation: Generation) -> anyhow::Result<Arc<Generation>> {
+        let name = generation
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("invalid index directory"))?;
+        // Renaming replaces the pointer atomically, so a restart during the
+        // switch opens one generation or the other.
+        let pending = self.path.join(format!("{CURRENT_FILE}.new"));
+        std::fs::write(&pending, name)?;
+        std::fs::rename(&pending, self.path.join(CURRENT_FILE))?;
+        let mut current = self.current.write().expect("poisoned");
+        Ok(std::mem::replace(&mut *current, Arc::new(generation)))
+    }
+
+    /// Deletes every generation other than the current one, including any
+    /// left behind by a rebuild that failed.
+    fn remove_stale_generations(&self) -> anyhow::Result<()> {
+        let current = self.current();
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+            let generation = name == DIRECTORY
+                || name
+                    .strip_prefix(DIRECTORY)
+                    .map_or(false, |suffix| suffix.starts_with('-'));
+            if generation && entry.file_type()?.is_dir() && entry.path() != current.path {
+                // Searches still using a deleted generation keep its files
+                // open until they finish.
+                if let Err(err) = std::fs::remove_dir_all(entry.path()) {
+                    tracing::warn!("error deleting search index {name}: {err}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SearchBackend for TantivyIndex {
+    fn search(&self, terms: &str, limit: usize) -> anyhow::Result<Vec<(f32, u64)>> {
+        let generation = self.current();
+        let Some(query) = generation.parse_query(terms) else { return Ok(Vec::new()) };
+        let searcher = generation.searcher();
+        let mut matches = Vec::new();
+        for (score, doc) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            if let Ok(doc) = searcher.doc(doc) {
+                if let Some(Value::U64(crate_id)) = doc.get_first(generation.id) {
+                    matches.push((score, *crate_id));
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Fails while the index is being rebuilt, since the changes would be
+    /// written to the generation being replaced.
+    fn writer(&self) -> anyhow::Result<Box<dyn IndexWriter>> {
+        let claim = WriterClaim::new(&self.claims)?;
+        let generation = self.current();
+        Ok(Box::new(TantivyWriter {
+            writer: generation.index.writer(WRITER_MEMORY)?,
+            generation,
+            _claim: claim,
+        }))
+    }
+
+    /// Builds a new generation from every crate in `db`, then switches
+    /// searches to it. Fails while an import is writing to the index or it's
+    /// being copied.
+    fn rebuild(&self, db: &Database) -> anyhow::Result<usize> {
+        let _claim = ExclusiveClaim::new(&self.claims)?;
+        let directory = format!(
+            "{DIRECTORY}-{}",
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        );
+        let generation = Generation::open(self.path.join(directory))?;
+        let mut writer = generation.index.writer(WRITER_MEMORY)?;
+        let mut indexed = 0;
+        for krate in Crate::all(db).query()? {
+            writer.add_document(generation.document(krate.header.id, &krate.contents))?;
+            indexed += 1;
+        }
+        writer.commit()?;
+        writer.wait_merging_threads()?;
+        generation.reader.reload()?;
+
+        self.switch_to(generation)?;
+        self.remove_stale_generations()?;
+        Ok(indexed)
+    }
+
+    /// Opens every segment's inverted index for the searchable fields, which
+    /// otherwise happens during the first search.
+    fn warm_up(&self) -> anyhow::Result<()> {
+        let generation = self.current();
+        let searcher = generation.searcher();
+        for segment in searcher.segment_readers() {
+            for field in [generation.name, generation.description, generation.readme] {
+                segment.inverted_index(field)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the words of descriptions and READMEs, so a word appearing in
+    /// both of a crate's texts counts twice.
+    fn common_terms(&self, min_crates: u32) -> anyhow::Result<HashMap<String, u64>> {
+        let mut counts = HashMap::<String, u64>::new();
+        let generation = self.current();
+        let searcher = generation.searcher();
+        for segment in searcher.segment_readers() {
+            for field in [generation.description, generation.readme] {
+                let inverted_index = segment.inverted_index(field)?;
+                let mut terms = inverted_index.terms().stream()?;
+                while terms.advance() {
+                    let Ok(term) = std::str::from_utf8(terms.key()) else { continue };
+                    *counts.entry(term.to_string()).or_default() +=
+                        u64::from(terms.value().doc_freq);
+                }
+            }
+        }
+        counts.retain(|_, count| *count >= u64::from(min_crates));
+        Ok(counts)
+    }
+
+    fn num_docs(&self) -> u64 {
+        self.current().searcher().num_docs()
+    }
+
+    fn highlighter(&self, terms: &str) -> anyhow::Result<Option<Box<dyn Highlighter>>> {
+        let generation = self.current();
+        let Some(query) = generation.parse_query(terms) else { return Ok(None) };
+        Ok(Some(Box::new(Snippets {
+            description: generation.generator(&*query, generation.description)?,
+            readme: generation.generator(&*query, generation.readme)?,
+        })))
+    }
+
+    /// Copies the current generation to [`DIRECTORY`] in `destination`. Fails
+    /// while an import is writing to the index or it's being rebuilt.
+    fn snapshot(&self, destination: &Path) -> anyhow::Result<()> {
+        let _claim = ExclusiveClaim::new(&self.claims)?;
+        copy_dir(&self.current().path, &destination.join(DIRECTORY))
+    }
+}
+
+/// One generation of the index, stored in its own directory.
+struct Generation {
+    path: PathBuf,
+    index: Index,
+    /// Shared by every search so that segment readers stay warm between
+    /// queries. It reloads on its own when a commit lands.
+    reader: IndexReader,
+    id: Field,
+    name: Field,
+    description: Field,
+    readme: Field,
+}
+
+impl Generation {
+    /// Opens the generation stored in `path`, creating it with the current
+    /// schema if needed. An existing generation keeps the schema it was
+    /// built with, so it can be searched until a rebuild replaces it.
+    fn open(path: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        let directory = MmapDirectory::open(&path)?;
+        let index = if Index::exists(&directory)? {
+            Index::open(directory)?
+        } else {
+            Index::open_or_create(directory, schema())?
+        };
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        let schema = index.schema();
+        let field = |name: &str| {
+            schema
+                .get_field(name)
+                .ok_or_else(|| anyhow::anyhow!("the search index has no {name} field"))
+        };
+        Ok(Self {
+            id: field("id")?,
+            name: field("name")?,
+            description: field("description")?,
+            readme: field("readme")?,
+            path,
+            index,
+            reader,
+        })
+    }
+
+    /// Returns a searcher over the most recently loaded commit.
+    fn searcher(&self) -> Searcher {
+        self.reader.searcher()
+    }
+
+    /// Parses `terms` into a query of the name, description, and readme
+    /// fields, returning None if the terms can't be parsed.
+    fn parse_query(&self, terms: &str) -> Option<Box<dyn Query>> {
+        let parser =
+            QueryParser::for_index(&self.index, vec![self.name, self.description, self.readme]);
+        compile(&parser, &syntax::parse(terms)?)
+    }
+
+    fn generator(&self, query: &dyn Query, field: Field) -> anyhow::Result<SnippetGenerator> {
+        let mut generator = SnippetGenerator::create(&self.searcher(), query, field)?;
+        generator.set_max_num_chars(MAX_SNIPPET_CHARS);
+        Ok(generator)
+    }
+
+    fn document(&self, id: u64, krate: &Crate) -> tantivy::Document {
+        doc! {
+            self.id => id,
+            self.name => krate.name.clone(),
+            self.description => krate.description.clone(),
+            self.readme => krate.readme.clone(),
+        }
+    }
+}
+
+/// The schema of new generations of the index.
+fn schema() -> Schema {
+    let mut schema = Schema::builder();
+    schema.add_u64_field("id", INDEXED | STORED);
+    schema.add_text_field("name", TEXT);
+    schema.add_text_field("description", TEXT);
+    schema.add_text_field("readme", TEXT);
+    schema.build()
+}
+
+/// Whether imports are writing to the index, or it's being rebuilt or
+/// copied.
+#[derive(Debug, Default)]
+struct Claims {
+    writers: usize,
+    exclusive: bool,
+}
+
+/// Counts a writer as open until dropped.
+struct WriterClaim(Arc<Mutex<Claims>>);
+
+impl WriterClaim {
+    fn new(claims: &Arc<Mutex<Claims>>) -> anyhow::Result<Self> {
+        let mut state = claims.lock().expect("poisoned");
+        if state.exclusive {
+            anyhow::bail!("the search index is being rebuilt or copied");
+        }
+        state.writers += 1;
+        Ok(Self(claims.clone()))
+    }
+}
+
+impl Drop for WriterClaim {
+    fn drop(&mut self) {
+        self.0.lock().expect("poisoned").writers -= 1;
+    }
+}
+
+/// Keeps writers, rebuilds, and snapshots from starting until dropped, even
+/// if the rebuild or snapshot holding it fails.
+struct ExclusiveClaim(Arc<Mutex<Claims>>);
+
+impl ExclusiveClaim {
+    fn new(claims: &Arc<Mutex<Claims>>) -> anyhow::Result<Self> {
+        let mut state = claims.lock().expect("poisoned");
+        if state.exclusive {
+            anyhow::bail!("the search index is already being rebuilt or copied");
+        } else if state.writers > 0 {
+            anyhow::bail!("an import is writing to the search index");
+        }
+        state.exclusive = true;
+        Ok(Self(claims.clone()))
+    }
+}
+
+impl Drop for ExclusiveClaim {
+    fn drop(&mut self) {
+        self.0.lock().expect("poisoned").exclusive = false;
+    }
+}
+
+struct TantivyWriter {
+    writer: tantivy::IndexWriter,
+    generation: Arc<Generation>,
+    _claim: WriterClaim,
+}
+
+impl IndexWriter for TantivyWriter {
+    fn replace(&mut self, id: u64, krate: &Crate) -> anyhow::Result<()> {
+        self.writer
+            .delete_term(Term::from_field_u64(self.generation.id, id));
+        self.writer
+            .add_document(self.generation.document(id, krate))?;
+        Ok(())
+    }
+
+    fn commit(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.writer.commit()?;
+        // Load the commit immediately, rather than waiting for the reader to
+        // notice it.
+        self.generation.reader.reload()?;
+        // The claim is released once the merges started by the commit have
+        // finished.
+        let Self { writer, _claim, .. } = *self;
+        writer.wait_merging_threads()?;
+        Ok(())
+    }
+}
+
+struct Snippets {
+    description: SnippetGenerator,
+    readme: SnippetGenerator,
+}
+
+impl Highlighter for Snippets {
+    fn highlight(&self, field: syntax::Field, text: &str) -> Option<String> {
+        let generator = match field {
+            syntax::Field::Description => &self.description,
+            syntax::Field::Readme => &self.readme,
+            syntax::Field::Name => return None,
+        };
+        let snippet = generator.snippet(text);
+        (!snippet.highlighted().is_empty()).then(|| snippet.to_html())
+    }
+}
+
+/// Compiles `expr` into a tantivy query.
+///
+/// Quoted phrases must match, with their words next to each other, and are
+/// weighted above the individual words. A `NOT` is only meaningful alongside
+/// other terms, since a query of only excluded terms matches nothing.
+fn compile(parser: &QueryParser, expr: &Expr<'_>) -> Option<Box<dyn Query>> {
+    let mut clauses = Vec::<(Occur, Box<dyn Query>)>::new();
+    match expr {
+        Expr::Terms(terms) => {
+            let mut words = Vec::new();
+            for term in terms {
+                // Targeted terms keep their `field:` prefix, which restricts
+                // them to that field of the index.
+                if term.phrase {
+                    let phrase = parser.parse_query(&term.to_string()).ok()?;
+                    clauses.push((Occur::Must, Box::new(BoostQuery::new(phrase, PHRASE_BOOST))));
+                } else {
+                    words.push(term.to_string());
+                }
+            }
+            if !words.is_empty() {
+                clauses.push((Occur::Should, parser.parse_query(&words.join(" ")).ok()?));
+            }
+        }
+        Expr::And(exprs) => {
+            for expr in exprs {
+                clauses.push(match expr {
+                    Expr::Not(excluded) => (Occur::MustNot, compile(parser, excluded)?),
+                    expr => (Occur::Must, compile(parser, expr)?),
+                });
+            }
+        }
+        Expr::Or(exprs) => {
+            for expr in exprs {
+                clauses.push((Occur::Should, compile(parser, expr)?));
+            }
+        }
+        Expr::Not(excluded) => clauses.push((Occur::MustNot, compile(parser, excluded)?)),
+    }
+
+    if clauses.len() == 1 && clauses[0].0 != Occur::MustNot {
+        clauses.pop().map(|(_, query)| query)
+    } else {
+        Some(Box::new(BooleanQuery::new(clauses)))
+    }
+}
@@ -0,0 +1,297 @@
+//! Slows imports down so that they don't starve searches of disk I/O.
+//!
+//! Imports commit their changes in batches, and the throttle decides how long
+//! to wait after each batch. Besides fixed limits, the throttle can adapt to
+//! the latency of recent searches, pausing the import while searches are slow.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Deserialize;
+
+/// How long search latencies are remembered.
+const LATENCY_WINDOW: Duration = Duration::from_secs(30);
+
+/// The most search latencies remembered.
+const MAX_SAMPLES: usize = 1_000;
+
+/// How many searches must have run within [`LATENCY_WINDOW`] before their
+/// latency pauses an import, so that a single slow search doesn't.
+const MIN_SAMPLES: usize = 20;
+
+/// How often a paused import checks whether searches have sped up.
+const ADAPTIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The longest an import pauses for slow searches after each batch, so that
+/// imports always finish eventually.
+const MAX_ADAPTIVE_PAUSE: Duration = Duration::from_secs(60 * 5);
+
+static CONFIG: OnceCell<ThrottleConfig> = OnceCell::new();
+
+static LATENCIES: Lazy<Mutex<Latencies>> = Lazy::new(Mutex::default);
+
+/// How quickly imports write to the database.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleConfig {
+    /// How many changes are committed at a time. Smaller batches spread the
+    /// import's writes out more evenly.
+    pub batch_size: usize,
+    /// The most changes committed per second. 0 places no limit on imports.
+    pub max_operations_per_second: u64,
+    /// How many milliseconds to wait after committing each batch.
+    pub batch_pause_ms: u64,
+    /// When nonzero, imports pause after each batch while the 99th
+    /// percentile latency of the last 30 seconds' searches exceeds this many
+    /// milliseconds, for up to 5 minutes at a time.
+    pub max_query_p99_ms: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100_000,
+            max_operations_per_second: 0,
+            batch_pause_ms: 0,
+            max_query_p99_ms: 0,
+        }
+    }
+}
+
+/// Throttles every import started after this is called. Imports aren't
+/// throttled until it is, and only the first call has an effect.
+pub fn configure(config: ThrottleConfig) {
+    if CONFIG.set(config).is_err() {
+        tracing::warn!("import throttling was already configured");
+    }
+}
+
+/// Paces the batches committed by one import.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    config: &'static ThrottleConfig,
+    started: Instant,
+    operations: u64,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        static UNTHROTTLED: Lazy<ThrottleConfig> = Lazy::new(ThrottleConfig::default);
+        Self {
+            config: CONFIG.get().unwrap_or(&UNTHROTTLED),
+            started: Instant::now(),
+            operations: 0,
+        }
+    }
+
+    /// Returns how many changes to commit at a time.
+    pub fn batch_size(&self) -> usize {
+        self.config.batch_size.max(1)
+    }
+
+    /// Blocks after a batch of `operations` changes has been committed until
+    /// the next batch may be. This sleeps for up to several minutes, so it
+    /// must only be called where blocking is allowed.
+    pub fn pause(&mut self, operations: usize) {
+        let pause = self.rate_pause(operations, self.started.elapsed());
+        if !pause.is_zero() {
+            std::thread::sleep(pause);
+        }
+
+        let mut paused = None::<Instant>;
+        loop {
+            let p99 = query_p99();
+            let since = paused.map_or(Duration::ZERO, |since| since.elapsed());
+            if !self.keep_pausing(p99, since) {
+                if since >= MAX_ADAPTIVE_PAUSE {
+                    tracing::warn!("searches are still slow, resuming the import anyway");
+                }
+                break;
+            }
+            paused.get_or_insert_with(|| {
+                tracing::info!(
+                    "pausing the import while search p99 is {}ms",
+                    p99.unwrap_or_default().as_millis()
+                );
+                Instant::now()
+            });
+            std::thread::sleep(ADAPTIVE_INTERVAL);
+        }
+    }
+
+    /// Counts a batch of `operations` changes committed `elapsed` after the
+    /// import started, returning how long to wait before the next batch to
+    /// stay within the configured rate.
+    fn rate_pause(&mut self, operations: usize, elapsed: Duration) -> Duration {
+        self.operations += operations as u64;
+        let mut pause = Duration::from_millis(self.config.batch_pause_ms);
+        if self.config.max_operations_per_second > 0 {
+            let allowed_at = Duration::from_secs_f64(
+                self.operations as f64 / self.config.max_operations_per_second as f64,
+            );
+            pause += allowed_at.saturating_sub(elapsed);
+        }
+        pause
+    }
+
+    /// Returns whether an import that has paused for `paused` should keep
+    /// waiting for searches, whose recent p99 latency is `p99`, to speed up.
+    fn keep_pausing(&self, p99: Option<Duration>, paused: Duration) -> bool {
+        let limit = Duration::from_millis(self.config.max_query_p99_ms);
+        self.config.max_query_p99_ms > 0
+            && p99.map_or(false, |p99| p99 > limit)
+            && paused < MAX_ADAPTIVE_PAUSE
+    }
+}
+
+/// Records how long a search takes when dropped.
+pub(crate) struct QueryTimer(Instant);
+
+impl QueryTimer {
+    pub fn start() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        if let Ok(mut latencies) = LATENCIES.lock() {
+            latencies.record(now, now - self.0);
+        }
+    }
+}
+
+/// Returns the 99th percentile latency of the searches within the last
+/// [`LATENCY_WINDOW`], or None if too few searches ran.
+fn query_p99() -> Option<Duration> {
+    LATENCIES.lock().ok()?.p99(Instant::now())
+}
+
+/// The latencies of recent searches, oldest first, with when each finished.
+#[derive(Default, Debug)]
+struct Latencies(VecDeque<(Instant, Duration)>);
+
+impl Latencies {
+    fn record(&mut self, finished: Instant, latency: Duration) {
+        if self.0.len() >= MAX_SAMPLES {
+            self.0.pop_front();
+        }
+        self.0.push_back((finished, latency));
+    }
+
+    /// Forgets the searches that finished more than [`LATENCY_WINDOW`]
+    /// before `now`, then returns the 99th percentile latency of the rest, or
+    /// None if there are too few.
+    fn p99(&mut self, now: Instant) -> Option<Duration> {
+        while self.0.front().map_or(false, |(finished, _)| {
+            now.duration_since(*finished) > LATENCY_WINDOW
+        }) {
+            self.0.pop_front();
+        }
+        if self.0.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut durations = self
+            .0
+            .iter()
+            .map(|(_, duration)| *duration)
+            .collect::<Vec<_>>();
+        let index = (durations.len() * 99 / 100).min(durations.len() - 1);
+        Some(*durations.select_nth_unstable(index).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(config: ThrottleConfig) -> Throttle {
+        Throttle {
+            config: Box::leak(Box::new(config)),
+            started: Instant::now(),
+            operations: 0,
+        }
+    }
+
+    #[test]
+    fn unthrottled_imports_never_pause() {
+        let mut throttle = throttle(ThrottleConfig::default());
+        assert_eq!(
+            throttle.rate_pause(1_000_000, Duration::ZERO),
+            Duration::ZERO
+        );
+        assert!(!throttle.keep_pausing(Some(Duration::from_secs(10)), Duration::ZERO));
+    }
+
+    #[test]
+    fn rate_limits_pause_until_operations_are_allowed() {
+        let mut throttle = throttle(ThrottleConfig {
+            max_operations_per_second: 100,
+            batch_pause_ms: 10,
+            ..ThrottleConfig::default()
+        });
+        // 100 operations are allowed after one second.
+        assert_eq!(
+            throttle.rate_pause(100, Duration::from_millis(250)),
+            Duration::from_millis(760)
+        );
+        // 200 operations after two seconds, which have already passed.
+        assert_eq!(
+            throttle.rate_pause(100, Duration::from_secs(3)),
+            Duration::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn slow_searches_pause_imports_for_a_limited_time() {
+        let throttle = throttle(ThrottleConfig {
+            max_query_p99_ms: 100,
+            ..ThrottleConfig::default()
+        });
+        let slow = Some(Duration::from_millis(150));
+        assert!(throttle.keep_pausing(slow, Duration::ZERO));
+        assert!(throttle.keep_pausing(slow, MAX_ADAPTIVE_PAUSE - ADAPTIVE_INTERVAL));
+        assert!(!throttle.keep_pausing(slow, MAX_ADAPTIVE_PAUSE));
+        assert!(!throttle.keep_pausing(Some(Duration::from_millis(100)), Duration::ZERO));
+        assert!(!throttle.keep_pausing(None, Duration::ZERO));
+    }
+
+    #[test]
+    fn p99_needs_enough_recent_searches() {
+        let start = Instant::now();
+        let mut latencies = Latencies::default();
+        for _ in 1..MIN_SAMPLES {
+            latencies.record(start, Duration::from_millis(5));
+        }
+        assert_eq!(latencies.p99(start), None);
+        latencies.record(start, Duration::from_millis(5));
+        assert_eq!(latencies.p99(start), Some(Duration::from_millis(5)));
+        // Every search has left the window.
+        assert_eq!(latencies.p99(start + LATENCY_WINDOW * 2), None);
+    }
+
+    #[test]
+    fn p99_is_the_slowest_percent() {
+        let start = Instant::now();
+        let mut latencies = Latencies::default();
+        for ms in 1..=200 {
+            latencies.record(start, Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.p99(start), Some(Duration::from_millis(199)));
+
+        let mut latencies = Latencies::default();
+        for _ in 0..MAX_SAMPLES {
+            latencies.record(start, Duration::from_secs(1));
+        }
+        // The oldest samples are forgotten once there are too many.
+        for _ in 0..MAX_SAMPLES {
+            latencies.record(start, Duration::from_millis(1));
+        }
+        assert_eq!(latencies.p99(start), Some(Duration::from_millis(1)));
+    }
+}
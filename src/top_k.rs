@@ -0,0 +1,147 @@
+//! Selection of the highest-scoring items from an unordered stream.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+/// Keeps the `capacity` highest-scoring items offered to it.
+///
+/// The kept items are a min-heap, so offering `n` items takes O(n log k) time
+/// rather than the O(n·k) of inserting each into a sorted list, and most items
+/// of a broad search are rejected by a single comparison with the lowest kept
+/// score. Equal scores are broken in favor of the lesser item, so the items
+/// kept don't depend on the order they're offered in.
+#[derive(Debug)]
+pub struct TopK<T> {
+    capacity: usize,
+    heap: BinaryHeap<Reverse<Entry<T>>>,
+}
+
+impl<T: Ord> TopK<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Offers `item`, keeping it if it's among the highest-scoring items
+    /// offered so far. Items scored NaN are never kept.
+    pub fn push(&mut self, score: f32, item: T) {
+        if score.is_nan() {
+            return;
+        }
+        let entry = Entry(score, item);
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(entry));
+        } else if let Some(mut lowest) = self.heap.peek_mut() {
+            if entry > lowest.0 {
+                *lowest = Reverse(entry);
+            }
+        }
+    }
+
+    /// Returns the kept items with their scores, highest first.
+    pub fn into_sorted_vec(self) -> Vec<(f32, T)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(Entry(score, item))| (score, item))
+            .collect()
+    }
+}
+
+/// An item and its score, ordered by the score and then in reverse by the
+/// item.
+#[derive(Debug)]
+struct Entry<T>(f32, T);
+
+impl<T: Ord> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Entry<T> {}
+
+impl<T: Ord> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(other.1.cmp(&self.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_k(capacity: usize, items: &[(f32, u32)]) -> Vec<(f32, u32)> {
+        let mut top = TopK::new(capacity);
+        for &(score, item) in items {
+            top.push(score, item);
+        }
+        top.into_sorted_vec()
+    }
+
+    #[test]
+    fn keeps_the_highest_scores() {
+        let items = [(0.5, 1), (3.0, 2), (1.0, 3), (2.0, 4), (-1.0, 5)];
+        assert_eq!(top_k(3, &items), [(3.0, 2), (2.0, 4), (1.0, 3)]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut top = TopK::new(0);
+        top.push(1.0, 1);
+        top.push(f32::INFINITY, 2);
+        assert!(top.is_empty());
+        assert!(top.into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn fewer_items_than_capacity() {
+        let items = [(1.0, 1), (2.0, 2)];
+        let mut top = TopK::new(10);
+        for &(score, item) in &items {
+            top.push(score, item);
+        }
+        assert_eq!(top.len(), 2);
+        assert_eq!(top.into_sorted_vec(), [(2.0, 2), (1.0, 1)]);
+    }
+
+    #[test]
+    fn equal_scores_keep_the_least_items_in_any_order() {
+        let items = [(1.0, 4), (1.0, 2), (1.0, 5), (1.0, 1), (1.0, 3)];
+        let expected = [(1.0, 1), (1.0, 2), (1.0, 3)];
+        assert_eq!(top_k(3, &items), expected);
+
+        let mut reversed = items;
+        reversed.reverse();
+        assert_eq!(top_k(3, &reversed), expected);
+
+        let mut sorted = items;
+        sorted.sort_by_key(|&(_, item)| item);
+        assert_eq!(top_k(3, &sorted), expected);
+    }
+
+    #[test]
+    fn nan_scores_are_never_kept() {
+        let items = [(f32::NAN, 1), (1.0, 2), (-f32::NAN, 3), (0.5, 4)];
+        assert_eq!(top_k(3, &items), [(1.0, 2), (0.5, 4)]);
+        assert!(top_k(3, &[(f32::NAN, 1)]).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use time::Date;
+
+use crate::schema::Crate;
+
+/// Phrases found in the descriptions and READMEs of crates whose repository
+/// has been archived or whose authors have stopped maintaining them.
+const ARCHIVE_PHRASES: &[&str] = &[
+    "this repository has been archived",
+    "this repository is archived",
+    "this project has been archived",
+    "this project is archived",
+    "this crate has been archived",
+    "this crate is archived",
+    "no longer maintained",
+    "no longer being maintained",
+    "no longer actively maintained",
+    "not maintained anymore",
+    "not actively maintained",
+    "this crate is unmaintained",
+    "this project is unmaintained",
+];
+
+/// How many bytes from the start of a README a notice is taken to be about
+/// the crate itself, which leaves room for a title and badges.
+const README_TOP: usize = 1_000;
+
+/// How many bytes from a mention of the crate's name a notice further down its
+/// README must be. Notices elsewhere are usually about other crates, such as
+/// in a list of alternatives or dependencies.
+const NAME_DISTANCE: usize = 100;
+
+/// How long a crate must go without a release before it can be considered
+/// unmaintained.
+pub(crate) const RELEASE_GAP: time::Duration = time::Duration::days(365 * 2);
+
+/// Returns true if the description or README of the crate named `name`
+/// contains a notice that its repository is archived or that it's no longer
+/// maintained. Notices in the README only count near its top or near a
+/// mention of the crate's name.
+pub fn has_archive_notice(name: &str, description: &str, readme: &str) -> bool {
+    let description = description.to_ascii_lowercase();
+    if ARCHIVE_PHRASES
+        .iter()
+        .any(|phrase| description.contains(phrase))
+    {
+        return true;
+    }
+
+    // Names are compared like normalized names, so `foo-bar` matches
+    // `foo_bar`. None of the phrases contain `-`.
+    let readme = readme.to_ascii_lowercase().replace('-', "_");
+    let name = Crate::normalized_name(name);
+    let mentions = readme
+        .match_indices(&name)
+        .map(|(index, _)| index)
+        .filter(|index| {
+            let is_name_byte = |byte: &u8| byte.is_ascii_alphanumeric() || *byte == b'_';
+            !readme.as_bytes()[..*index]
+                .last()
+                .map_or(false, is_name_byte)
+                && !readme.as_bytes()[index + name.len()..]
+                    .first()
+                    .map_or(false, is_name_byte)
+        })
+        .collect::<Vec<_>>();
+    ARCHIVE_PHRASES.iter().any(|phrase| {
+        readme.match_indices(phrase).any(|(index, _)| {
+            index < README_TOP
+                || mentions
+                    .iter()
+                    .any(|mention| mention.abs_diff(index) <= NAME_DISTANCE)
+        })
+    })
+}
+
+/// Returns true if a crate last published on `last_published` appears to be
+/// unmaintained as of `today`.
+///
+/// A crate is only considered unmaintained when it hasn't been released in two
+/// years and either has an archive notice or an open advisory saying it's
+/// unmaintained, since finished crates often go years between releases.
+pub fn is_unmaintained(
+    last_published: Option<Date>,
+    archive_notice: bool,
+    unmaintained_advisory: bool,
+    today: Date,
+) -> bool {
+    (archive_notice || unmaintained_advisory)
+        && last_published.map_or(true, |published| today - published > RELEASE_GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_in_descriptions() {
+        assert!(has_archive_notice("foo", "No longer maintained.", ""));
+        assert!(has_archive_notice(
+            "foo",
+            "A parser. This crate is unmaintained",
+            ""
+        ));
+        assert!(!has_archive_notice("foo", "A maintained fork of bar.", ""));
+    }
+
+    #[test]
+    fn notices_at_the_top_of_readmes() {
+        let readme = "# foo\n\n**This repository has been archived.** Use bar instead.\n";
+        assert!(has_archive_notice("foo", "", readme));
+    }
+
+    #[test]
+    fn notices_further_down_readmes_must_mention_the_crate() {
+        let filler = "Lorem ipsum dolor sit amet. ".repeat(50);
+        let about_another_crate =
+            format!("# foo\n\n{filler}\n\n## Alternatives\n\n- bar: no longer maintained\n");
+        assert!(!has_archive_notice("foo", "", &about_another_crate));
+
+        let about_the_crate =
+            format!("# foo\n\n{filler}\n\n## Status\n\nfoo-core is no longer maintained.\n");
+        assert!(has_archive_notice("foo-core", "", &about_the_crate));
+        assert!(has_archive_notice("foo_core", "", &about_the_crate));
+        // Longer names that contain the crate's name don't mention it.
+        assert!(!has_archive_notice("core", "", &about_the_crate));
+    }
+
+    #[test]
+    fn unmaintained_crates() {
+        let date = |year| Date::from_calendar_date(year, time::Month::March, 20).unwrap();
+        let today = date(2023);
+        // Finished crates aren't unmaintained without a notice or advisory.
+        assert!(!is_unmaintained(Some(date(2015)), false, false, today));
+        assert!(is_unmaintained(Some(date(2020)), true, false, today));
+        assert!(is_unmaintained(Some(date(2020)), false, true, today));
+        assert!(is_unmaintained(None, true, false, today));
+        // Recent releases outweigh a notice.
+        assert!(!is_unmaintained(Some(date(2022)), true, true, today));
+    }
+}
@@ -0,0 +1,351 @@
+use std::{collections::HashMap, path::Path};
+
+use bonsaidb::{
+    core::{
+        schema::{SerializedCollection, SerializedView},
+        transaction::{Operation, Transaction},
+    },
+    local::Database,
+};
+use serde::Deserialize;
+
+use crate::schema::{Crate, CratesByNormalizedName, UnsafeCounts, UnsafeUsage};
+
+/// Imports the cargo-geiger reports in `sources` into the `UnsafeUsage`
+/// collection. Each source is the URL of a report, a report file, or a
+/// directory of `.json` reports. Sources that can't be loaded are logged and
+/// skipped.
+pub async fn import(database: &Database, sources: &[String]) -> anyhow::Result<()> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    println!("Importing unsafe usage reports.");
+    let mut reports = Vec::new();
+    for source in sources {
+        let loaded = if is_url(source) {
+            download(source).await.map(|report| vec![report])
+        } else {
+            read_reports(Path::new(source))
+        };
+        match loaded {
+            Ok(loaded) => reports.extend(loaded),
+            Err(err) => tracing::error!("error loading unsafe usage report {source}: {err:#}"),
+        }
+    }
+
+    let database = database.clone();
+    tokio::task::spawn_blocking(move || store(&database, &reports)).await?
+}
+
+/// Imports the cargo-geiger reports in `sources` like [`import`], skipping
+/// URLs so that the network isn't accessed.
+pub fn import_offline(database: &Database, sources: &[String]) -> anyhow::Result<()> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    println!("Importing unsafe usage reports.");
+    let mut reports = Vec::new();
+    for source in sources {
+        if is_url(source) {
+            println!("Skipping {source} while offline.");
+            continue;
+        }
+        match read_reports(Path::new(source)) {
+            Ok(loaded) => reports.extend(loaded),
+            Err(err) => tracing::error!("error loading unsafe usage report {source}: {err:#}"),
+        }
+    }
+
+    store(database, &reports)
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+async fn download(url: &str) -> anyhow::Result<Report> {
+    Ok(reqwest::get(url).await?.error_for_status()?.json().await?)
+}
+
+/// Reads the report at `path`, or every `.json` report in it if it's a
+/// directory. Reports in a directory that can't be read are logged and
+/// skipped, so that one bad file doesn't keep the rest from being imported.
+fn read_reports(path: &Path) -> anyhow::Result<Vec<Report>> {
+    let mut reports = Vec::new();
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                match read_report(&path) {
+                    Ok(report) => reports.push(report),
+                    Err(err) => tracing::error!("error loading unsafe usage report: {err:#}"),
+                }
+            }
+        }
+    } else {
+        reports.push(read_report(path)?);
+    }
+    Ok(reports)
+}
+
+fn read_report(path: &Path) -> anyhow::Result<Report> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("error reading {}: {err}", path.display()))?;
+    serde_json::from_str(&contents)
+        .map_err(|err| anyhow::anyhow!("error parsing {}: {err}", path.display()))
+}
+
+/// Stores the newest scanned version of each crates.io crate in `reports`.
+/// Crates missing from `reports` keep their previously imported counts, and
+/// counts are never replaced by those of an older version.
+fn store(db: &Database, reports: &[Report]) -> anyhow::Result<()> {
+    let mut crate_ids = HashMap::new();
+    for mapping in CratesByNormalizedName::entries(db).query()? {
+        if mapping.value.registry.is_none() {
+            crate_ids.insert(mapping.key, mapping.source.id.deserialize::<u64>()?);
+        }
+    }
+    let mut existing = UnsafeUsage::all(db)
+        .query()?
+        .into_iter()
+        .map(|d| (d.header.id, d))
+        .collect::<HashMap<_, _>>();
+
+    let mut scanned = HashMap::<u64, (semver::Version, UnsafeUsage)>::new();
+    for entry in reports.iter().flat_map(|report| &report.packages) {
+        let id = &entry.package.id;
+        if !id.from_crates_io() {
+            continue;
+        }
+        let Some(crate_id) = crate_ids.get(&Crate::normalized_name(&id.name)) else { continue };
+        let Ok(version) = semver::Version::parse(&id.version) else { continue };
+        let newer_stored = existing.get(crate_id).map_or(false, |stored| {
+            semver::Version::parse(&stored.contents.version)
+                .map_or(false, |stored| stored > version)
+        });
+        let newer_scanned = scanned
+            .get(crate_id)
+            .map_or(false, |(scanned, _)| *scanned >= version);
+        if newer_stored || newer_scanned {
+            continue;
+        }
+
+        let usage = UnsafeUsage {
+            version: id.version.clone(),
+            used: entry.unsafety.used.counts(),
+            unused: entry.unsafety.unused.counts(),
+            forbids_unsafe: entry.unsafety.forbids_unsafe,
+        };
+        scanned.insert(*crate_id, (version, usage));
+    }
+
+    let mut tx = Transaction::new();
+    for (crate_id, (_, usage)) in scanned {
+        let operation = match existing.remove(&crate_id) {
+            Some(existing) if existing.contents == usage => continue,
+            Some(existing) => Operation::update_serialized::<UnsafeUsage>(existing.header, &usage)?,
+            None => Operation::insert_serialized::<UnsafeUsage>(Some(&crate_id), &usage)?,
+        };
+        tx.operations.push(operation);
+    }
+    println!("Committing {} unsafe usage reports", tx.operations.len());
+    if !tx.operations.is_empty() {
+        tx.apply(db)?;
+    }
+
+    Ok(())
+}
+
+/// The JSON report written by `cargo geiger --output-format Json`, which
+/// covers the scanned package and every dependency.
+#[derive(Deserialize, Debug)]
+struct Report {
+    packages: Vec<ReportEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReportEntry {
+    package: PackageInfo,
+    unsafety: UnsafeInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageInfo {
+    id: PackageId,
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageId {
+    name: String,
+    version: String,
+    /// Where the package came from, such as a registry, git repository, or
+    /// path, in a format that varies between cargo-geiger releases.
+    #[serde(default)]
+    source: serde_json::Value,
+}
+
+impl PackageId {
+    fn from_crates_io(&self) -> bool {
+        self.source.to_string().contains("crates.io")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct UnsafeInfo {
+    used: CounterBlock,
+    unused: CounterBlock,
+    forbids_unsafe: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct CounterBlock {
+    functions: Count,
+    exprs: Count,
+    item_impls: Count,
+    item_traits: Count,
+    methods: Count,
+}
+
+impl CounterBlock {
+    fn counts(&self) -> UnsafeCounts {
+        UnsafeCounts {
+            functions: self.functions.unsafe_,
+            exprs: self.exprs.unsafe_,
+            impls: self.item_impls.unsafe_,
+            traits: self.item_traits.unsafe_,
+            methods: self.methods.unsafe_,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Count {
+    unsafe_: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const CRATES_IO: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    fn counts(unsafe_: u64) -> Value {
+        let count = json!({ "safe": 10, "unsafe_": unsafe_ });
+        json!({
+            "functions": count,
+            "exprs": count,
+            "item_impls": count,
+            "item_traits": count,
+            "methods": count,
+        })
+    }
+
+    fn entry(name: &str, version: &str, source: Value, used: u64) -> Value {
+        json!({
+            "package": {
+                "id": { "name": name, "version": version, "source": source },
+                "dependencies": [],
+            },
+            "unsafety": {
+                "used": counts(used),
+                "unused": counts(0),
+                "forbids_unsafe": used == 0,
+            },
+        })
+    }
+
+    fn report(entries: Vec<Value>) -> Report {
+        serde_json::from_value(json!({ "packages": entries })).unwrap()
+    }
+
+    #[test]
+    fn reports_are_parsed() {
+        let report = report(vec![
+            entry("serde", "1.0.0", json!(CRATES_IO), 2),
+            entry(
+                "local",
+                "0.1.0",
+                json!({ "Path": "file:///home/user/local" }),
+                0,
+            ),
+            entry("forked", "0.1.0", json!("git+https://github.com/a/b"), 0),
+        ]);
+        let [serde, local, forked] = &report.packages[..] else { panic!() };
+        assert!(serde.package.id.from_crates_io());
+        assert!(!local.package.id.from_crates_io());
+        assert!(!forked.package.id.from_crates_io());
+        assert_eq!(serde.unsafety.used.counts().functions, 2);
+        assert!(local.unsafety.forbids_unsafe);
+    }
+
+    #[test]
+    fn malformed_reports_in_a_directory_are_skipped() {
+        let directory = tempfile::tempdir().unwrap();
+        let report = json!({ "packages": [entry("serde", "1.0.0", json!(CRATES_IO), 2)] });
+        std::fs::write(directory.path().join("good.json"), report.to_string()).unwrap();
+        std::fs::write(directory.path().join("bad.json"), "{\"packages\":").unwrap();
+        std::fs::write(directory.path().join("notes.txt"), "not a report").unwrap();
+
+        let reports = read_reports(directory.path()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].packages[0].package.id.name, "serde");
+
+        assert!(read_reports(&directory.path().join("bad.json")).is_err());
+        assert!(read_reports(&directory.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn newest_versions_are_stored() {
+        let (_dir, db) = crate::test_database();
+        let serde = crate::test_crate("serde").push_into(&db).unwrap().header.id;
+        let mut internal = crate::test_crate("internal");
+        internal.registry = Some(String::from("internal"));
+        let internal = internal.push_into(&db).unwrap().header.id;
+        let stored = |id| {
+            UnsafeUsage::get(&id, &db)
+                .unwrap()
+                .map(|usage| usage.contents)
+        };
+
+        store(
+            &db,
+            &[
+                report(vec![entry("serde", "1.0.1", json!(CRATES_IO), 3)]),
+                report(vec![
+                    entry("serde", "1.0.0", json!(CRATES_IO), 2),
+                    entry("internal", "1.0.0", json!(CRATES_IO), 1),
+                    entry("unknown", "1.0.0", json!(CRATES_IO), 1),
+                ]),
+            ],
+        )
+        .unwrap();
+        let usage = stored(serde).unwrap();
+        assert_eq!(usage.version, "1.0.1");
+        assert_eq!(usage.used.functions, 3);
+        assert!(stored(internal).is_none());
+
+        // Older versions and crates from elsewhere don't replace the counts.
+        store(
+            &db,
+            &[report(vec![
+                entry("serde", "1.0.0", json!(CRATES_IO), 2),
+                entry("serde", "2.0.0", json!("git+https://github.com/a/serde"), 0),
+            ])],
+        )
+        .unwrap();
+        assert_eq!(stored(serde).unwrap().version, "1.0.1");
+
+        store(
+            &db,
+            &[report(vec![entry("Serde", "1.1.0", json!(CRATES_IO), 0)])],
+        )
+        .unwrap();
+        let usage = stored(serde).unwrap();
+        assert_eq!(usage.version, "1.1.0");
+        assert!(usage.forbids_unsafe);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use serde::Serialize;
+
+use crate::{
+    api::{dependency_groups, find_crate, DependencyGroup},
+    cache::Cache,
+    diff::parse_features,
+    schema::{self, VersionsByCrate},
+};
+
+/// The metadata of a single published version of a crate.
+#[derive(Serialize, Debug)]
+pub(crate) struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    /// True if this is the version shown on the crate's page.
+    pub latest: bool,
+    pub yanked: bool,
+    pub license: String,
+    pub checksum: String,
+    /// When the version was published.
+    pub created_at: String,
+    pub downloads: u64,
+    /// The size of the packaged crate, in bytes.
+    pub crate_size: Option<u64>,
+    pub rust_version: Option<String>,
+    pub features: BTreeMap<String, BTreeSet<String>>,
+    pub dependencies: Vec<DependencyGroup>,
+}
+
+impl VersionInfo {
+    /// Returns the version of the crate named `name` that `requested` refers
+    /// to, or None if the crate doesn't exist or no version matches.
+    ///
+    /// `requested` can be an exact version, `latest`, or a partial version
+    /// such as `1` or `1.0`, which resolves to the newest matching release.
+    /// Yanked versions are only resolved when they are requested exactly.
+    pub fn resolve(
+        db: &Database,
+        cache: &Cache,
+        name: &str,
+        requested: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+        if requested.eq_ignore_ascii_case("latest") {
+            return Ok(cache
+                .crates()?
+                .get(id)
+                .and_then(|krate| krate.latest_version().map(String::from)));
+        }
+
+        let versions = VersionsByCrate::entries(db).with_key(&id).query()?;
+        if versions
+            .iter()
+            .any(|mapping| mapping.value.version == requested)
+        {
+            return Ok(Some(requested.to_string()));
+        }
+
+        let requirement = format!("={requested}");
+        let Ok(requirement) = semver::VersionReq::parse(&requirement) else { return Ok(None) };
+        Ok(versions
+            .iter()
+            .filter(|mapping| !mapping.value.yanked)
+            .filter_map(|mapping| semver::Version::parse(&mapping.value.version).ok())
+            .filter(|version| requirement.matches(version))
+            .max()
+            .map(|version| version.to_string()))
+    }
+
+    /// Returns the newest version of the crate named `name` that Cargo would
+    /// choose for the requirement `req`, such as `^1.2` or `1.2`, or None if
+    /// the crate doesn't exist or no version matches. Like Cargo, yanked
+    /// versions are never chosen, and prereleases are only chosen when the
+    /// requirement names a prerelease.
+    pub fn resolve_requirement(
+        db: &Database,
+        cache: &Cache,
+        name: &str,
+        req: &semver::VersionReq,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+        Ok(VersionsByCrate::entries(db)
+            .with_key(&id)
+            .query()?
+            .iter()
+            .filter(|mapping| !mapping.value.yanked)
+            .filter_map(|mapping| semver::Version::parse(&mapping.value.version).ok())
+            .filter(|version| req.matches(version))
+            .max()
+            .map(|version| version.to_string()))
+    }
+
+    /// Loads version `version` of the crate named `name`, which must be an
+    /// exact version number.
+    pub fn load(
+        db: &Database,
+        cache: &Cache,
+        name: &str,
+        version: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(id) = find_crate(cache, name)? else { return Ok(None) };
+        let Some(krate) = cache.crates()?.get(id) else { return Ok(None) };
+
+        let mut version_id = None;
+        for mapping in VersionsByCrate::entries(db).with_key(&id).query()? {
+            if mapping.value.version == version {
+                version_id = Some(mapping.source.id.deserialize::<u64>()?);
+                break;
+            }
+        }
+        let Some(version_id) = version_id else { return Ok(None) };
+        let Some(contents) = schema::Version::get(&version_id, db)? else { return Ok(None) };
+        let contents = contents.contents;
+
+        Ok(Some(Self {
+            name: krate.name().to_string(),
+            latest: krate.latest_version() == Some(contents.version.as_str()),
+            version: contents.version,
+            yanked: contents.yanked,
+            license: contents.license,
+            checksum: contents.checksum,
+            created_at: contents.created_at,
+            downloads: contents.downloads,
+            crate_size: contents.crate_size,
+            rust_version: contents.rust_version,
+            features: parse_features(&contents.features)?,
+            dependencies: dependency_groups(db, cache, version_id)?,
+        }))
+    }
+}
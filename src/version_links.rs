@@ -0,0 +1,262 @@
+//! Links to a version's `.crate` file, documentation, and source on the sites
+//! that host them.
+//!
+//! The links are built from the version's name and number alone, so a link
+//! can point at something that doesn't exist, such as the documentation of a
+//! version whose docs.rs build failed. Each link is checked with a HEAD
+//! request by a background task, and links found to be broken are left out
+//! until they're checked again. The task works through a bounded queue with a
+//! single client, so a burst of page views can't start a burst of requests.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::StatusCode;
+use serde::Serialize;
+
+use crate::{i18n, schema::CRATES_IO};
+
+/// How long a link that works is trusted before it's checked again.
+const LIVE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// How long a broken link is left out before it's checked again. docs.rs
+/// builds new versions within a few hours, so their documentation links are
+/// rechecked sooner than links that work.
+const BROKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long a link whose check failed, such as by timing out, is shown
+/// before it's checked again. Also how long a queued link waits to be checked
+/// before it's queued again.
+const ERROR_TTL: Duration = Duration::from_secs(60 * 10);
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most links whose checks are remembered. Once exceeded, expired checks
+/// are forgotten, followed by the oldest until a tenth of the room is free.
+const MAX_CHECKS: usize = 50_000;
+
+/// The most links waiting to be checked. Links viewed while the queue is full
+/// are shown unchecked and queued when they're viewed again.
+const MAX_QUEUED: usize = 1_000;
+
+static CHECKS: Lazy<Mutex<HashMap<String, Check>>> = Lazy::new(Mutex::default);
+
+static QUEUE: OnceCell<flume::Sender<VersionLink>> = OnceCell::new();
+
+/// A link to something hosted for a single version of a crate.
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct VersionLink {
+    pub kind: LinkKind,
+    pub url: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LinkKind {
+    /// The `.crate` file on static.crates.io.
+    Download,
+    /// The version's documentation on docs.rs.
+    Docs,
+    /// The version's source, browsable on docs.rs.
+    Source,
+}
+
+impl LinkKind {
+    /// Returns the link's text in the current response's language.
+    pub fn label(&self) -> String {
+        i18n::t(match self {
+            LinkKind::Download => "link-download",
+            LinkKind::Docs => "link-docs",
+            LinkKind::Source => "link-source",
+        })
+    }
+}
+
+/// When a link was checked and its check expires, and whether the link was
+/// broken.
+#[derive(Debug, Clone, Copy)]
+struct Check {
+    checked: Instant,
+    expires: Instant,
+    broken: bool,
+}
+
+/// Returns the links to `version` of the crate `name` that aren't known to be
+/// broken, checking any that haven't been checked recently. Only crates.io
+/// crates are hosted by static.crates.io and docs.rs.
+pub(crate) fn for_version(name: &str, version: &str, registry: &str) -> Vec<VersionLink> {
+    if registry != CRATES_IO {
+        return Vec::new();
+    }
+    let links = [
+        VersionLink {
+            kind: LinkKind::Download,
+            url: format!("https://static.crates.io/crates/{name}/{name}-{version}.crate"),
+        },
+        VersionLink {
+            kind: LinkKind::Docs,
+            url: format!("https://docs.rs/{name}/{version}"),
+        },
+        VersionLink {
+            kind: LinkKind::Source,
+            url: format!("https://docs.rs/crate/{name}/{version}/source/"),
+        },
+    ];
+
+    let queue = queue();
+    let mut shown = Vec::new();
+    let mut checks = CHECKS.lock().expect("poisoned");
+    let now = Instant::now();
+    for link in links {
+        match checks.get(&link.url) {
+            Some(check) if check.expires > now => {
+                if !check.broken {
+                    shown.push(link);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        // A queued link is marked as checked, so that pages viewed before its
+        // check don't queue it again.
+        if queue.map_or(false, |queue| queue.try_send(link.clone()).is_ok()) {
+            checks.insert(
+                link.url.clone(),
+                Check {
+                    checked: now,
+                    expires: now + ERROR_TTL,
+                    broken: false,
+                },
+            );
+        }
+        shown.push(link);
+    }
+    evict(&mut checks, now, MAX_CHECKS);
+    shown
+}
+
+/// Returns the queue of links to check, starting the task that checks them
+/// the first time it's called. Returns None outside of a Tokio runtime.
+fn queue() -> Option<&'static flume::Sender<VersionLink>> {
+    QUEUE
+        .get_or_try_init(|| {
+            let runtime = tokio::runtime::Handle::try_current()?;
+            let client = reqwest::Client::builder().timeout(CHECK_TIMEOUT).build()?;
+            let (sender, receiver) = flume::bounded(MAX_QUEUED);
+            runtime.spawn(check_queued(client, receiver));
+            anyhow::Ok(sender)
+        })
+        .map_err(|err| tracing::error!("error starting link checker: {err}"))
+        .ok()
+}
+
+async fn check_queued(client: reqwest::Client, links: flume::Receiver<VersionLink>) {
+    while let Ok(link) = links.recv_async().await {
+        let check = match check(&client, &link).await {
+            Ok(broken) => Check {
+                checked: Instant::now(),
+                expires: Instant::now() + if broken { BROKEN_TTL } else { LIVE_TTL },
+                broken,
+            },
+            Err(err) => {
+                tracing::debug!("error checking {}: {err}", link.url);
+                Check {
+                    checked: Instant::now(),
+                    expires: Instant::now() + ERROR_TTL,
+                    broken: false,
+                }
+            }
+        };
+        let mut checks = CHECKS.lock().expect("poisoned");
+        checks.insert(link.url, check);
+        evict(&mut checks, Instant::now(), MAX_CHECKS);
+    }
+}
+
+/// Keeps `checks` to at most `max` entries by forgetting expired checks, and
+/// then the oldest checks until a tenth of the room is free, so that eviction
+/// doesn't run on every check once the limit is reached.
+fn evict(checks: &mut HashMap<String, Check>, now: Instant, max: usize) {
+    if checks.len() <= max {
+        return;
+    }
+    checks.retain(|_, check| check.expires > now);
+    let keep = max - max / 10;
+    if checks.len() <= keep {
+        return;
+    }
+    let mut checked = checks
+        .values()
+        .map(|check| check.checked)
+        .collect::<Vec<_>>();
+    let oldest_kept = checked.len() - keep;
+    let (_, &mut cutoff, _) = checked.select_nth_unstable(oldest_kept);
+    checks.retain(|_, check| check.checked >= cutoff);
+}
+
+/// Returns true if `link` is broken. Only responses saying that nothing is
+/// there count as broken, so that links aren't hidden while a site is down.
+async fn check(client: &reqwest::Client, link: &VersionLink) -> anyhow::Result<bool> {
+    let response = client.head(&link.url).send().await?;
+    if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+        return Ok(true);
+    }
+    response.error_for_status_ref()?;
+    // docs.rs redirects to the version's build information when it has no
+    // documentation to show.
+    Ok(link.kind == LinkKind::Docs && response.url().path().starts_with("/crate/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks(ages: &[u64], now: Instant) -> HashMap<String, Check> {
+        ages.iter()
+            .map(|&age| {
+                let checked = now - Duration::from_secs(age);
+                let check = Check {
+                    checked,
+                    expires: checked + LIVE_TTL,
+                    broken: false,
+                };
+                (age.to_string(), check)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn eviction_waits_for_the_limit() {
+        let now = Instant::now();
+        let mut checks = checks(&[1, 2, 3], now);
+        evict(&mut checks, now, 3);
+        assert_eq!(checks.len(), 3);
+    }
+
+    #[test]
+    fn expired_checks_are_evicted_first() {
+        let now = Instant::now() + LIVE_TTL;
+        let mut checks = checks(&[0, 10, 20, 30, 40, 50, 60, 70, 80, 90, 100], now);
+        checks.get_mut("0").unwrap().expires = now - Duration::from_secs(1);
+        evict(&mut checks, now, 10);
+        assert_eq!(checks.len(), 9);
+        assert!(!checks.contains_key("0"));
+    }
+
+    #[test]
+    fn oldest_checks_are_evicted() {
+        let now = Instant::now() + LIVE_TTL;
+        let ages = (0..21).collect::<Vec<_>>();
+        let mut checks = checks(&ages, now);
+        evict(&mut checks, now, 20);
+        assert_eq!(checks.len(), 18);
+        assert!(!checks.contains_key("20"));
+        assert!(!checks.contains_key("19"));
+        assert!(!checks.contains_key("18"));
+        assert!(checks.contains_key("17"));
+    }
+}
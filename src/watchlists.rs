@@ -0,0 +1,318 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json,
+};
+use bonsaidb::{
+    core::schema::{SerializedCollection, SerializedView},
+    local::Database,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    api::{advisories_for, find_crate},
+    cache::Cache,
+    i18n, replication,
+    schema::{VersionsByCrate, Watchlist, YankEventsByCrate},
+    webserver::{internal_error, AppState},
+};
+
+/// The most crates a watchlist can contain.
+const MAX_CRATES: usize = 200;
+
+/// The longest name a watchlist can have, in characters.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The most watchlists that can be created in each [`CREATION_WINDOW`]. Anyone
+/// can create a watchlist, so this bounds how quickly they can fill the
+/// database.
+const MAX_CREATED_PER_WINDOW: usize = 100;
+
+const CREATION_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+static CREATED: Lazy<Mutex<CreationWindow>> = Lazy::new(|| {
+    Mutex::new(CreationWindow {
+        started: Instant::now(),
+        created: 0,
+    })
+});
+
+/// The number of events shown on a watchlist's page and in its feed.
+const RECENT_EVENTS: usize = 100;
+
+/// Routes for managing watchlists. Anyone can create a watchlist, up to
+/// [`MAX_CREATED_PER_WINDOW`] each hour, and its random id is needed to view,
+/// change, or delete it.
+pub(super) fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/", post(create_watchlist))
+        .route(
+            "/:id",
+            get(get_watchlist)
+                .put(update_watchlist)
+                .delete(delete_watchlist),
+        )
+        .route_layer(middleware::from_fn(replication::reject_writes))
+}
+
+#[derive(Serialize, Debug)]
+struct CreatedWatchlist {
+    id: String,
+}
+
+/// Creates a watchlist, or responds with 429 Too Many Requests when
+/// [`MAX_CREATED_PER_WINDOW`] have already been created this hour.
+async fn create_watchlist(
+    State((db, _, _)): State<AppState>,
+    Json(watchlist): Json<Watchlist>,
+) -> Result<Response, StatusCode> {
+    validate(&watchlist)?;
+    let reserved = CREATED.lock().expect("poisoned").reserve(Instant::now());
+    if let Err(retry_after) = reserved {
+        let retry_after = retry_after.as_secs().max(1).to_string();
+        return Ok((StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, retry_after)]).into_response());
+    }
+    let id = Uuid::new_v4().simple().to_string();
+    watchlist.overwrite_into(&id, &db).map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(CreatedWatchlist { id })).into_response())
+}
+
+/// How many watchlists have been created since `started`.
+#[derive(Debug)]
+struct CreationWindow {
+    started: Instant,
+    created: usize,
+}
+
+impl CreationWindow {
+    /// Counts a watchlist being created at `now`, starting a new window if the
+    /// current one has ended. Returns how long until the window ends if it's
+    /// already full.
+    fn reserve(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.started);
+        if elapsed >= CREATION_WINDOW {
+            self.started = now;
+            self.created = 0;
+        } else if self.created >= MAX_CREATED_PER_WINDOW {
+            return Err(CREATION_WINDOW - elapsed);
+        }
+        self.created += 1;
+        Ok(())
+    }
+}
+
+async fn get_watchlist(
+    State((db, _, _)): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Watchlist>, StatusCode> {
+    Watchlist::get(&id, &db)
+        .map_err(internal_error)?
+        .map(|watchlist| Json(watchlist.contents))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn update_watchlist(
+    State((db, _, _)): State<AppState>,
+    Path(id): Path<String>,
+    Json(watchlist): Json<Watchlist>,
+) -> Result<StatusCode, StatusCode> {
+    validate(&watchlist)?;
+    let mut existing = Watchlist::get(&id, &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    existing.contents = watchlist;
+    existing.update(&db).map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_watchlist(
+    State((db, _, _)): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let watchlist = Watchlist::get(&id, &db)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    watchlist.delete(&db).map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn validate(watchlist: &Watchlist) -> Result<(), StatusCode> {
+    if watchlist.name.trim().is_empty()
+        || watchlist.name.chars().count() > MAX_NAME_LENGTH
+        || watchlist.crates.len() > MAX_CRATES
+    {
+        Err(StatusCode::UNPROCESSABLE_ENTITY)
+    } else {
+        Ok(())
+    }
+}
+
+/// The recent activity of the crates in a watchlist.
+#[derive(Debug)]
+pub(crate) struct WatchlistActivity {
+    pub id: String,
+    pub name: String,
+    pub crates: Vec<String>,
+    /// The listed crates that couldn't be found.
+    pub missing: Vec<String>,
+    /// Releases, advisories, and yanks, newest first.
+    pub events: Vec<WatchlistEvent>,
+}
+
+#[derive(Debug)]
+pub(crate) struct WatchlistEvent {
+    pub title: String,
+    pub link: String,
+    pub at: String,
+    /// A unique identifier of the event, so that feed readers don't show it
+    /// twice.
+    pub guid: String,
+}
+
+impl WatchlistActivity {
+    /// Loads the activity of the watchlist `id`, returning None if it doesn't
+    /// exist.
+    pub fn load(db: &Database, cache: &Cache, id: &str) -> anyhow::Result<Option<Self>> {
+        let Some(watchlist) = Watchlist::get(id, db)? else { return Ok(None) };
+        let watchlist = watchlist.contents;
+        let crates = cache.crates()?;
+
+        let mut found = Vec::with_capacity(watchlist.crates.len());
+        let mut missing = Vec::new();
+        let mut events = Vec::new();
+        for requested in watchlist.crates {
+            let found_crate = find_crate(cache, &requested)?
+                .and_then(|crate_id| Some((crate_id, crates.get(crate_id)?)));
+            let Some((crate_id, krate)) = found_crate else {
+                missing.push(requested);
+                continue;
+            };
+            let name = krate.name().to_string();
+
+            for mapping in VersionsByCrate::entries(db).with_key(&crate_id).query()? {
+                events.push(WatchlistEvent {
+                    title: i18n::t_args(
+                        "watchlist-published",
+                        &[
+                            ("name", name.clone()),
+                            ("version", mapping.value.version.to_string()),
+                        ],
+                    ),
+                    link: format!("/crates/{name}"),
+                    at: mapping.value.created_at,
+                    guid: format!("version-{}", mapping.source.id.deserialize::<u64>()?),
+                });
+            }
+            for mapping in YankEventsByCrate::entries(db).with_key(&crate_id).query()? {
+                let message = if mapping.value.yanked {
+                    "watchlist-yanked"
+                } else {
+                    "watchlist-unyanked"
+                };
+                events.push(WatchlistEvent {
+                    title: i18n::t_args(
+                        message,
+                        &[
+                            ("name", name.clone()),
+                            ("version", mapping.value.version.to_string()),
+                        ],
+                    ),
+                    link: format!("/crates/{name}"),
+                    at: mapping.value.at,
+                    guid: format!("yank-{}", mapping.source.id.deserialize::<u64>()?),
+                });
+            }
+            for (advisory_id, advisory) in advisories_for(db, &name)? {
+                events.push(WatchlistEvent {
+                    title: format!("{advisory_id}: {}", advisory.title),
+                    link: advisory.url.unwrap_or_else(|| format!("/crates/{name}")),
+                    at: advisory.date,
+                    guid: advisory_id,
+                });
+            }
+
+            found.push(name);
+        }
+
+        // Every timestamp begins with an ISO 8601 date, so sorting them as
+        // strings orders them chronologically.
+        events.sort_by(|a, b| b.at.cmp(&a.at));
+        events.truncate(RECENT_EVENTS);
+
+        Ok(Some(Self {
+            id: id.to_string(),
+            name: watchlist.name,
+            crates: found,
+            missing,
+            events,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watchlist(name: &str, crates: usize) -> Watchlist {
+        Watchlist {
+            name: name.to_string(),
+            crates: (0..crates).map(|index| format!("crate-{index}")).collect(),
+        }
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(validate(&watchlist("Async", MAX_CRATES)), Ok(()));
+        assert_eq!(
+            validate(&watchlist(&"é".repeat(MAX_NAME_LENGTH), 1)),
+            Ok(())
+        );
+        assert_eq!(
+            validate(&watchlist(" ", 1)),
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        );
+        assert_eq!(
+            validate(&watchlist(&"a".repeat(MAX_NAME_LENGTH + 1), 1)),
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        );
+        assert_eq!(
+            validate(&watchlist("Async", MAX_CRATES + 1)),
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        );
+    }
+
+    #[test]
+    fn creation_is_limited_per_window() {
+        let started = Instant::now();
+        let mut window = CreationWindow {
+            started,
+            created: 0,
+        };
+        for _ in 0..MAX_CREATED_PER_WINDOW {
+            assert_eq!(window.reserve(started), Ok(()));
+        }
+        let later = started + Duration::from_secs(60);
+        assert_eq!(
+            window.reserve(later),
+            Err(CREATION_WINDOW - Duration::from_secs(60))
+        );
+
+        let next_window = started + CREATION_WINDOW;
+        assert_eq!(window.reserve(next_window), Ok(()));
+        assert_eq!(window.started, next_window);
+        assert_eq!(window.created, 1);
+    }
+}
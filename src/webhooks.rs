@@ -0,0 +1,381 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
+
+use bonsaidb::{
+    core::{document::CollectionDocument, schema::SerializedCollection},
+    local::Database,
+};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::{
+    dump::NewReleases,
+    schema::{Crate, Keyword, Webhook},
+};
+
+/// The number of times a delivery is attempted before giving up.
+const ATTEMPTS: u32 = 4;
+
+/// The delay before the first retry, which doubles after each attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The number of crates, and of their keywords, loaded at a time.
+const BATCH_SIZE: usize = 1_000;
+
+/// The header containing the HMAC-SHA256 of the request body, keyed with the
+/// webhook's secret.
+const SIGNATURE_HEADER: &str = "X-Delve-Signature";
+
+/// The body of a delivery: every release of an import that matches the
+/// webhook's filters.
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    events: Vec<&'a Event>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    NewCrate {
+        #[serde(rename = "crate")]
+        name: String,
+        description: String,
+        keywords: Vec<String>,
+    },
+    NewVersion {
+        #[serde(rename = "crate")]
+        name: String,
+        version: String,
+        keywords: Vec<String>,
+    },
+}
+
+impl Event {
+    fn crate_name(&self) -> &str {
+        match self {
+            Event::NewCrate { name, .. } | Event::NewVersion { name, .. } => name,
+        }
+    }
+
+    fn keywords(&self) -> &[String] {
+        match self {
+            Event::NewCrate { keywords, .. } | Event::NewVersion { keywords, .. } => keywords,
+        }
+    }
+}
+
+/// Notifies each webhook of the new releases matching its filters, with one
+/// request per webhook containing all of them.
+///
+/// The requests are sent from a spawned task, so the import doesn't wait on
+/// slow receivers or retries. Errors are logged rather than returned so that
+/// a failing receiver can't interrupt importing.
+pub fn deliver(db: &Database, new_releases: &NewReleases) {
+    match deliveries(db, new_releases) {
+        Ok(deliveries) if deliveries.is_empty() => {}
+        Ok(deliveries) => {
+            tokio::spawn(send_all(deliveries));
+        }
+        Err(err) => tracing::error!("error preparing webhooks: {err}"),
+    }
+}
+
+/// Returns each webhook that matches any of the new releases, along with the
+/// body to send it.
+fn deliveries(
+    db: &Database,
+    new_releases: &NewReleases,
+) -> anyhow::Result<Vec<(CollectionDocument<Webhook>, Vec<u8>)>> {
+    let webhooks = Webhook::all(db).query()?;
+    if webhooks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let events = load_events(db, new_releases)?;
+    let mut deliveries = Vec::new();
+    for webhook in webhooks {
+        if let Some(body) = payload(&webhook.contents, &events)? {
+            deliveries.push((webhook, body));
+        }
+    }
+    Ok(deliveries)
+}
+
+/// Returns the body sent to `webhook`, or None if none of the events match
+/// its filters.
+fn payload(webhook: &Webhook, events: &[Event]) -> anyhow::Result<Option<Vec<u8>>> {
+    let events = events
+        .iter()
+        .filter(|event| webhook.matches(event.crate_name(), event.keywords()))
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_vec(&Payload { events })?))
+}
+
+async fn send_all(deliveries: Vec<(CollectionDocument<Webhook>, Vec<u8>)>) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::error!("error creating webhook client: {err}");
+            return;
+        }
+    };
+    for (webhook, body) in deliveries {
+        if let Err(err) = send(&client, &webhook.contents, body).await {
+            tracing::warn!(
+                webhook = webhook.header.id,
+                "error delivering webhook to {}: {err}",
+                webhook.contents.url
+            );
+        }
+    }
+}
+
+/// Loads the crates of the new releases and their keywords, in batches of
+/// [`BATCH_SIZE`].
+fn load_events(db: &Database, new_releases: &NewReleases) -> anyhow::Result<Vec<Event>> {
+    let ids = new_releases
+        .crates
+        .iter()
+        .chain(new_releases.versions.iter().map(|(id, _)| id))
+        .copied()
+        .collect::<BTreeSet<u64>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut crates = HashMap::with_capacity(ids.len());
+    for batch in ids.chunks(BATCH_SIZE) {
+        let batch = Crate::get_multiple(batch, db)?;
+        let keyword_ids = batch
+            .iter()
+            .flat_map(|krate| krate.contents.keywords.iter().copied())
+            .collect::<BTreeSet<u64>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut keywords = HashMap::with_capacity(keyword_ids.len());
+        for keyword_batch in keyword_ids.chunks(BATCH_SIZE) {
+            for keyword in Keyword::get_multiple(keyword_batch, db)? {
+                keywords.insert(keyword.header.id, keyword.contents.keyword);
+            }
+        }
+
+        for krate in batch {
+            let crate_keywords = krate
+                .contents
+                .keywords
+                .iter()
+                .filter_map(|id| keywords.get(id).cloned())
+                .collect::<Vec<_>>();
+            crates.insert(krate.header.id, (krate.contents, crate_keywords));
+        }
+    }
+
+    let mut events = Vec::with_capacity(new_releases.crates.len() + new_releases.versions.len());
+    for id in &new_releases.crates {
+        let Some((krate, keywords)) = crates.get(id) else { continue };
+        events.push(Event::NewCrate {
+            name: krate.name.clone(),
+            description: krate.description.clone(),
+            keywords: keywords.clone(),
+        });
+    }
+    for (id, version) in &new_releases.versions {
+        let Some((krate, keywords)) = crates.get(id) else { continue };
+        events.push(Event::NewVersion {
+            name: krate.name.clone(),
+            version: version.clone(),
+            keywords: keywords.clone(),
+        });
+    }
+    Ok(events)
+}
+
+/// Posts `body` to the webhook, retrying with exponential backoff if the
+/// request fails or the receiver responds with a server error.
+async fn send(client: &reqwest::Client, webhook: &Webhook, body: Vec<u8>) -> anyhow::Result<()> {
+    let signature = sign(&webhook.secret, &body);
+    let mut attempt = 1;
+    loop {
+        let result = client
+            .post(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let error = match result {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            // The same request will be rejected again.
+            Ok(response) if response.status().is_client_error() => {
+                anyhow::bail!("rejected with {}", response.status())
+            }
+            Ok(response) => anyhow::anyhow!("responded with {}", response.status()),
+            Err(err) => err.into(),
+        };
+        if attempt == ATTEMPTS {
+            return Err(error);
+        }
+
+        tokio::time::sleep(RETRY_DELAY * 2_u32.pow(attempt - 1)).await;
+        attempt += 1;
+    }
+}
+
+/// Returns the signature of `body`, formatted as `sha256=<hex digest>`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+
+    let mut signature = String::from("sha256=");
+    for byte in digest {
+        signature.push_str(&format!("{byte:02x}"));
+    }
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn webhook(crates: &[&str], keywords: &[&str]) -> Webhook {
+        Webhook {
+            url: String::from("https://example.com/hook"),
+            secret: String::from("secret"),
+            crates: crates.iter().map(ToString::to_string).collect(),
+            keywords: keywords.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::NewCrate {
+                name: String::from("serde-json"),
+                description: String::from("JSON"),
+                keywords: vec![String::from("JSON")],
+            },
+            Event::NewVersion {
+                name: String::from("tokio"),
+                version: String::from("1.0.0"),
+                keywords: vec![String::from("async")],
+            },
+        ]
+    }
+
+    fn delivered(webhook: &Webhook) -> Option<Value> {
+        payload(webhook, &events())
+            .unwrap()
+            .map(|body| serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn signature_format() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn empty_filters_match_everything() {
+        let webhook = webhook(&[], &[]);
+        assert!(webhook.matches("anything", &[]));
+        assert_eq!(
+            delivered(&webhook).unwrap()["events"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn crate_filters_match_normalized_names() {
+        let webhook = webhook(&["Serde_JSON"], &[]);
+        assert!(webhook.matches("serde-json", &[]));
+        assert!(!webhook.matches("serde", &[]));
+        assert_eq!(
+            delivered(&webhook),
+            Some(json!({
+                "events": [{
+                    "event": "new_crate",
+                    "crate": "serde-json",
+                    "description": "JSON",
+                    "keywords": ["JSON"],
+                }],
+            }))
+        );
+    }
+
+    #[test]
+    fn keyword_filters_ignore_case() {
+        let webhook = webhook(&[], &["ASYNC"]);
+        assert!(webhook.matches("tokio", &[String::from("async")]));
+        assert!(!webhook.matches("tokio", &[String::from("asynchronous")]));
+        assert_eq!(
+            delivered(&webhook),
+            Some(json!({
+                "events": [{
+                    "event": "new_version",
+                    "crate": "tokio",
+                    "version": "1.0.0",
+                    "keywords": ["async"],
+                }],
+            }))
+        );
+    }
+
+    #[test]
+    fn nothing_is_sent_without_matches() {
+        assert_eq!(delivered(&webhook(&["rand"], &["random"])), None);
+    }
+
+    #[test]
+    fn events_are_loaded_with_keywords() {
+        let (_dir, db) = crate::test_database();
+        Keyword {
+            keyword: String::from("random"),
+        }
+        .insert_into(&7, &db)
+        .unwrap();
+        let mut rand = crate::test_crate("rand");
+        rand.keywords.insert(7);
+        rand.insert_into(&1, &db).unwrap();
+        crate::test_crate("serde").insert_into(&2, &db).unwrap();
+
+        let events = load_events(
+            &db,
+            &NewReleases {
+                // Crate 3 doesn't exist, so it has no events.
+                crates: vec![2, 3],
+                versions: vec![(1, String::from("0.9.0")), (2, String::from("1.0.1"))],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&events).unwrap(),
+            json!([
+                { "event": "new_crate", "crate": "serde", "description": "", "keywords": [] },
+                {
+                    "event": "new_version",
+                    "crate": "rand",
+                    "version": "0.9.0",
+                    "keywords": ["random"],
+                },
+                { "event": "new_version", "crate": "serde", "version": "1.0.1", "keywords": [] },
+            ])
+        );
+    }
+}
@@ -1,68 +1,469 @@
+use std::sync::Arc;
+
 use askama::Template;
 use axum::{
-    extract::{RawQuery, State},
-    http::header::CONTENT_TYPE,
+    body::Body,
+    extract::{Path, Query as QueryParams, RawQuery, State},
+    http::{
+        header::{
+            ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION, SET_COOKIE, VARY,
+        },
+        HeaderMap, HeaderValue, Request, StatusCode,
+    },
+    middleware,
     response::{Html, IntoResponse, Response},
     routing::get,
+    Extension, Json,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use bonsaidb::local::Database;
 
 use serde::Deserialize;
+use time::Date;
+use tokio::task::JoinSet;
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::{Level, Span};
+
+use crate::{
+    access_log::{self, AccessLog},
+    admin, analytics,
+    api::{self, CrateInfo, CrateWarning},
+    api_keys::Meter,
+    assets,
+    cache::{Cache, CachedCrate},
+    categories::CategoryEntry,
+    config::{Config, ListenerConfig},
+    dashboard::Dashboard,
+    diff::VersionDiff,
+    discover,
+    dump::Importer,
+    embed,
+    embeddings::Embedder,
+    experiments::{self, Assignment, Experiment},
+    feeds,
+    filters::{Filters, PlaceholderMode, QueryTimeout},
+    i18n, image_proxy,
+    keyword_trends::{self, KeywordTrends},
+    maintenance::Compactor,
+    not_found, openapi,
+    ownership::{self, OwnershipEntry},
+    preferences::{self, Preferences},
+    ranking::{self, Recency},
+    recently_viewed::RecentlyViewed,
+    security_headers,
+    seo::{PageMeta, SiteUrl},
+    signed_cookies::SignedCookies,
+    snippets, sparse_index,
+    version_info::VersionInfo,
+    watchlists::WatchlistActivity,
+    yanks::{self, YankEntry},
+    CrateResult, QueryResults, SearchIndex, MAX_QUERY_LEN,
+};
 
-use crate::{cache::Cache, CrateResult, SearchIndex};
+pub(super) type AppState = (Database, Cache, SearchIndex);
 
-pub(super) async fn run(
+pub async fn run(
+    config: &Config,
     database: Database,
     cache: Cache,
     search_index: SearchIndex,
+    maintenance: Option<(Importer, Compactor)>,
 ) -> anyhow::Result<()> {
-    // build our application with a single route
+    let public = public_router(config, &database)?;
+    // Read-only replicas don't import or compact, so they don't serve the
+    // admin API.
+    let admin =
+        maintenance.map(|(importer, compactor)| admin::router(&config.admin, importer, compactor));
+    let access_log = std::env::var_os("DELVE_RS_ACCESS_LOG")
+        .map(AccessLog::open)
+        .transpose()?;
+    let state = (database, cache, search_index);
+
+    let mut servers = JoinSet::new();
+    for listener in config.listeners() {
+        let mut app = axum::Router::new();
+        if listener.public {
+            app = app.merge(public.clone());
+        }
+        if let Some(admin) = admin.as_ref().filter(|_| listener.admin) {
+            app = app.nest("/api/v1/admin", admin.clone());
+        }
+        if let Some(access_log) = &access_log {
+            app = app.layer(middleware::from_fn_with_state(
+                access_log.clone(),
+                access_log::log_access,
+            ));
+        }
+        let app = app
+            .layer(middleware::from_fn(security_headers::set))
+            .layer(
+                ServiceBuilder::new()
+                    .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                    .layer(
+                        TraceLayer::new_for_http()
+                            .make_span_with(request_span::<Body>)
+                            .on_response(
+                                DefaultOnResponse::new()
+                                    .level(Level::INFO)
+                                    .latency_unit(LatencyUnit::Micros),
+                            ),
+                    )
+                    .layer(PropagateRequestIdLayer::x_request_id()),
+            )
+            .with_state(state.clone());
+        servers.spawn(serve(listener, app));
+    }
+
+    // Every listener runs until the first one fails.
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
+
+/// Returns the routes of the site and its public API.
+fn public_router(config: &Config, database: &Database) -> anyhow::Result<axum::Router<AppState>> {
     let app = axum::Router::new()
         .route("/about", get(|| async { "Hello, World!" }))
-        .route(
-            "/style.css",
-            get(|| async {
-                (
-                    [(CONTENT_TYPE, "text/css")],
-                    include_str!("./assets/style.css"),
-                )
-            }),
+        .route("/assets/*path", get(assets::serve))
+        .nest(
+            "/api/v1",
+            api::router(
+                config.recency,
+                Meter::new(database.clone(), config.api_keys),
+            ),
         )
-        .route("/:slug", get(|| async { "Hello, Slug!" }))
-        .route("/", get(index));
-
-    // run it with hyper on localhost:3000
-    axum::Server::bind(&"0.0.0.0:3000".parse().unwrap())
-        .serve(
-            app.with_state((database, cache, search_index))
-                .into_make_service(),
+        .nest("/api", openapi::router())
+        .nest("/feed", feeds::router())
+        .nest("/index", sparse_index::router(config.download_url.clone()))
+        .route("/crates/:name", get(crate_page))
+        .route("/crates/:name/:version", get(version_page))
+        .route("/crates/:name/readme/image", get(image_proxy::serve))
+        .route("/crates/:name/:from/diff/:to", get(version_diff))
+        .route("/categories", get(categories_page))
+        .route("/random", get(random_crate))
+        .route(
+            "/settings",
+            get(preferences::settings).post(preferences::save_settings),
         )
-        .await?;
+        .route("/embed/search", get(embed::search))
+        .route("/yanks", get(yanks_page))
+        .route("/ownership", get(ownership_page))
+        .route("/trends/keywords", get(keyword_trends_page))
+        .route("/watchlists/:id", get(watchlist_page))
+        .route("/watchlists/:id/feed.xml", get(feeds::watchlist_feed))
+        .route("/users/:login/advisories.xml", get(feeds::owner_feed))
+        .route("/:slug", get(|| async { "Hello, Slug!" }));
+
+    // The routes that show search results, which are ordered by the
+    // experiment's variants when one is configured.
+    let mut search = axum::Router::new()
+        .route("/search/fragment", get(search_fragment))
+        .route("/out/:name", get(outbound))
+        .route("/", get(index))
+        .layer(Extension(config.recency));
+    if let Some(experiment) = &config.experiment {
+        search = search.route_layer(middleware::from_fn_with_state(
+            Arc::new(Experiment::new(experiment)?),
+            experiments::assign_session,
+        ));
+    }
+
+    let cookies = SignedCookies::new(config.cookie_secret.as_deref());
+    Ok(app
+        .merge(search)
+        .layer(Extension(SiteUrl::new(config.public_url.as_deref())))
+        .layer(Extension(RecentlyViewed::new(cookies.clone())))
+        .layer(Extension(cookies))
+        .layer(Extension(Embedder::new(config.embeddings.clone())?))
+        .layer(Extension(QueryTimeout::from_millis(
+            config.query_timeout_ms,
+        )))
+        .layer(middleware::from_fn(i18n::negotiate)))
+}
+
+async fn serve(listener: ListenerConfig, app: axum::Router) -> anyhow::Result<()> {
+    let app = app.into_make_service();
+    if let Some(tls) = &listener.tls {
+        let tls = RustlsConfig::from_pem_file(&tls.certificate, &tls.private_key).await?;
+        tracing::info!("listening on https://{}", listener.address);
+        axum_server::bind_rustls(listener.address, tls)
+            .serve(app)
+            .await?;
+    } else {
+        tracing::info!("listening on http://{}", listener.address);
+        axum::Server::try_bind(&listener.address)?
+            .serve(app)
+            .await?;
+    }
 
     Ok(())
 }
 
+fn request_span<B>(request: &Request<B>) -> Span {
+    tracing::info_span!(
+        "request",
+        id = %access_log::request_id(request),
+        method = %request.method(),
+        path = request.uri().path(),
+        q = %access_log::sanitized_query(request.uri()),
+    )
+}
+
 #[derive(Deserialize, Debug)]
 struct Query {
     q: String,
+    #[serde(default)]
+    hide_placeholders: bool,
+    /// The name of the [`Ranker`](crate::ranking::Ranker) to order results
+    /// with.
+    ranker: Option<String>,
+    /// Searches for the query as written, even if it appears misspelled.
+    #[serde(default)]
+    exact: bool,
+    /// True when the search was made with a search form, whose checkbox
+    /// overrides the visitor's preference for hiding placeholders.
+    #[serde(default)]
+    form: bool,
+    /// The page of results to show, starting from 1, when the visitor
+    /// prefers results split into pages.
+    page: Option<usize>,
+}
+
+impl Query {
+    fn parse(query: String) -> Result<Self, AppError> {
+        let query = match serde_urlencoded::from_str::<Self>(&query) {
+            Ok(query) => {
+                if let Some(ranker) = &query.ranker {
+                    if ranking::by_name(ranker).is_none() {
+                        return Err(AppError::BadRequest(format!("unknown ranker: {ranker}")));
+                    }
+                }
+                query
+            }
+            Err(err) if query.contains('=') => {
+                return Err(AppError::BadRequest(format!("invalid search query: {err}")));
+            }
+            // A bare query string, such as `/?serde`, is searched as-is.
+            Err(_) => Self {
+                q: query,
+                hide_placeholders: false,
+                ranker: None,
+                exact: false,
+                form: false,
+                page: None,
+            },
+        };
+        if query.q.len() > MAX_QUERY_LEN {
+            return Err(AppError::BadRequest(format!(
+                "search queries can be at most {MAX_QUERY_LEN} bytes long"
+            )));
+        }
+        Ok(query)
+    }
+
+    /// Returns true if placeholders are hidden from the results, either
+    /// because the search asks to or because the visitor prefers it.
+    fn hides_placeholders(&self, preferences: &Preferences) -> bool {
+        self.hide_placeholders || (!self.form && preferences.hide_placeholders)
+    }
+
+    /// Returns the links to the pages around the `searched` page of results.
+    fn pages(&self, searched: &Searched) -> Pages {
+        let page = self.page.unwrap_or(1).max(1);
+        Pages {
+            previous: (searched.offset > 0).then(|| self.page_url(page - 1)),
+            next: searched.more.then(|| self.page_url(page + 1)),
+        }
+    }
+
+    /// Returns the URL of page `page` of the search's results.
+    fn page_url(&self, page: usize) -> String {
+        let mut params = vec![("q", self.q.clone()), ("page", page.to_string())];
+        for (name, set) in [
+            ("hide_placeholders", self.hide_placeholders),
+            ("exact", self.exact),
+            ("form", self.form),
+        ] {
+            if set {
+                params.push((name, String::from("true")));
+            }
+        }
+        if let Some(ranker) = &self.ranker {
+            params.push(("ranker", ranker.clone()));
+        }
+        format!(
+            "/?{}",
+            serde_urlencoded::to_string(params).expect("strings always encode")
+        )
+    }
+
+    /// Searches for the query's results. Unless the query or the visitor's
+    /// preferences request a ranker, the results are ordered by the session's
+    /// experiment variant, and the assignment is returned so that the page
+    /// and its clicks can be attributed to it. A clearly misspelled query is
+    /// corrected unless the query is `exact`, and a natural-language query is
+    /// also matched by its embedding. Only the requested page of results is
+    /// returned when the visitor prefers results split into pages.
+    async fn search(
+        &self,
+        (db, cache, search_index): &AppState,
+        recency: Recency,
+        embedder: &Embedder,
+        timeout: QueryTimeout,
+        headers: &HeaderMap,
+        preferences: &Preferences,
+        assignment: Option<&Assignment>,
+    ) -> anyhow::Result<Searched> {
+        let corrected = if self.exact {
+            None
+        } else {
+            cache.spelling()?.correct_query(&self.q)
+        };
+        let (terms, mut filters) = Filters::parse(corrected.as_deref().unwrap_or(&self.q));
+        filters.semantic = embedder.matches(cache, &terms).await;
+        filters.recency = recency;
+        filters.readable_languages = i18n::readable_languages(headers);
+        if self.hides_placeholders(preferences) {
+            filters.placeholders = PlaceholderMode::Hide;
+        }
+        if let Some(ranker) = self.ranker.as_ref().or(preferences.sort.as_ref()) {
+            filters.ranker = ranking::by_name(ranker);
+        }
+        let assignment = assignment.filter(|_| filters.ranker.is_none());
+        if let Some(assignment) = assignment {
+            filters.ranker = Some(assignment.variant);
+        }
+
+        filters.deadline = timeout.deadline();
+        let QueryResults {
+            mut results,
+            partial,
+        } = super::spawn_query(
+            terms.clone(),
+            filters,
+            db.clone(),
+            cache.clone(),
+            search_index.clone(),
+        )
+        .await?;
+        let total = results.len();
+        let offset = paginate(&mut results, preferences.per_page, self.page);
+        snippets::highlight(&terms, db, search_index, &mut results)?;
+        Ok(Searched {
+            more: offset + results.len() < total,
+            results,
+            offset,
+            partial,
+            experiment: assignment.cloned(),
+            corrected,
+        })
+    }
+}
+
+/// Keeps only page `page` of `results`, which is the first page when None,
+/// if the visitor prefers pages of `per_page` results. Returns the number of
+/// results on the pages before it.
+fn paginate<T>(results: &mut Vec<T>, per_page: Option<usize>, page: Option<usize>) -> usize {
+    let Some(per_page) = per_page else { return 0 };
+    let page = page.unwrap_or(1).max(1);
+    let offset = per_page.saturating_mul(page - 1).min(results.len());
+    results.drain(..offset);
+    results.truncate(per_page);
+    offset
+}
+
+/// The results of a [`Query`].
+struct Searched {
+    results: Vec<CrateResult>,
+    /// The number of results on the pages before `results`.
+    offset: usize,
+    /// True if there are results on later pages.
+    more: bool,
+    /// True if scoring the results took too long, and only the search
+    /// index's matches are shown.
+    partial: bool,
+    /// The experiment variant that ordered the results, if any.
+    experiment: Option<Assignment>,
+    /// The query that was searched for instead, if the query appeared
+    /// misspelled.
+    corrected: Option<String>,
 }
 
 async fn index(
-    State((db, cache, search_index)): State<(Database, Cache, SearchIndex)>,
+    State(state): State<AppState>,
     RawQuery(query): RawQuery,
-) -> Response {
+    Extension(recency): Extension<Recency>,
+    Extension(embedder): Extension<Embedder>,
+    Extension(timeout): Extension<QueryTimeout>,
+    Extension(site): Extension<SiteUrl>,
+    Extension(recently_viewed): Extension<RecentlyViewed>,
+    Extension(cookies): Extension<SignedCookies>,
+    headers: HeaderMap,
+    assignment: Option<Extension<Assignment>>,
+) -> Result<Html<String>, AppError> {
+    let (db, cache, _) = &state;
+    let preferences = Preferences::read(&cookies, &headers);
     if let Some(query) = query {
-        let query = serde_urlencoded::from_str(&query).unwrap_or(Query { q: query });
-        let results = super::query(&query.q, &db, &cache, &search_index).unwrap();
-        Html(
+        let query = Query::parse(query)?;
+        let searched = query
+            .search(
+                &state,
+                recency,
+                &embedder,
+                timeout,
+                &headers,
+                &preferences,
+                assignment.as_ref().map(|Extension(assignment)| assignment),
+            )
+            .await?;
+        let pages = query.pages(&searched);
+        let Searched {
+            results,
+            offset,
+            partial,
+            experiment,
+            corrected,
+            ..
+        } = searched;
+        // Searches made while typing aren't logged, since most are
+        // incomplete.
+        analytics::record_query(db, &query.q, results.len());
+        if let Some(assignment) = &experiment {
+            assignment.record_impression(db, results.len());
+        }
+        let meta = PageMeta::new(
+            &site,
+            &format!("/?{}", serde_urlencoded::to_string([("q", &query.q)])?),
+            format!("\"{}\" on delve.rs", query.q),
+            &format!(
+                "{} Rust crate{} matching \"{}\".",
+                results.len(),
+                if results.len() == 1 { "" } else { "s" },
+                query.q
+            ),
+        );
+        Ok(Html(
             SearchResults {
                 query: query.q,
+                meta,
+                hide_placeholders: query.hides_placeholders(&preferences),
+                ranker: query.ranker,
                 results,
+                offset,
+                pages,
+                partial,
+                experiment,
+                corrected,
             }
-            .render()
-            .expect("invalid template data"),
-        )
-        .into_response()
+            .render()?,
+        ))
         // Html(format!(
         //     "<ol>{}</ol>",
         //     results
@@ -78,17 +479,654 @@ async fn index(
         // ))
         // .into_response()
     } else {
-        Html(Index.render().expect("invalid template data")).into_response()
+        let dashboard = cache.dashboard()?;
+        let crates = cache.crates()?;
+        let trending = dashboard
+            .trending
+            .iter()
+            .filter_map(|id| crates.get(*id))
+            .collect();
+        let featured = discover::crate_of_the_day(&crates);
+        let recent = recently_viewed
+            .read(&headers)
+            .iter()
+            .filter_map(|name| api::find_crate(cache, name).ok().flatten())
+            .filter_map(|id| crates.get(id))
+            .collect();
+        Ok(Html(
+            Index {
+                dashboard,
+                trending,
+                featured,
+                recent,
+                hide_placeholders: preferences.hide_placeholders,
+            }
+            .render()?,
+        ))
+    }
+}
+
+/// Renders only the results list, which the search form requests as the user
+/// types.
+async fn search_fragment(
+    State(state): State<AppState>,
+    RawQuery(query): RawQuery,
+    Extension(recency): Extension<Recency>,
+    Extension(embedder): Extension<Embedder>,
+    Extension(timeout): Extension<QueryTimeout>,
+    Extension(cookies): Extension<SignedCookies>,
+    headers: HeaderMap,
+    assignment: Option<Extension<Assignment>>,
+) -> Result<Html<String>, AppError> {
+    let query = Query::parse(query.unwrap_or_default())?;
+    if query.q.trim().is_empty() {
+        // Clear the results when the search box is emptied.
+        return Ok(Html(String::new()));
+    }
+
+    let searched = query
+        .search(
+            &state,
+            recency,
+            &embedder,
+            timeout,
+            &headers,
+            &Preferences::read(&cookies, &headers),
+            assignment.as_ref().map(|Extension(assignment)| assignment),
+        )
+        .await?;
+    let pages = query.pages(&searched);
+    let Searched {
+        results,
+        offset,
+        partial,
+        corrected,
+        ..
+    } = searched;
+    Ok(Html(
+        SearchResultsList {
+            query: query.q,
+            results,
+            offset,
+            pages,
+            partial,
+            // Results shown while typing aren't counted as impressions, so
+            // their clicks aren't attributed to the experiment either.
+            experiment: None,
+            corrected,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct Outbound {
+    /// The position of the clicked result, starting from 1.
+    pos: Option<u32>,
+    /// The search that returned the result.
+    q: Option<String>,
+    /// The experiment and variant that ordered the results.
+    experiment: Option<String>,
+    variant: Option<String>,
+}
+
+/// Redirects to a crate's page, recording the click on a search result. Result
+/// links go through this route so that clicks can be used to tune relevance.
+async fn outbound(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    QueryParams(outbound): QueryParams<Outbound>,
+    assignment: Option<Extension<Assignment>>,
+) -> Result<Response, AppError> {
+    let id = api::find_crate(&cache, &name)?.ok_or(AppError::NotFound)?;
+    let krate = cache.crates()?.get(id).ok_or(AppError::NotFound)?;
+
+    if let (Some(position), Some(q)) = (outbound.pos, &outbound.q) {
+        analytics::record_click(&db, id, position, q);
     }
+    if let (Some(Extension(assignment)), Some(experiment), Some(variant), Some(position)) = (
+        assignment,
+        outbound.experiment,
+        outbound.variant,
+        outbound.pos,
+    ) {
+        // Results shown before the experiment changed, or ordered by another
+        // session's variant, aren't attributed to this session's variant.
+        if assignment.ordered(&experiment, &variant) {
+            assignment.record_click(&db, position);
+        }
+    }
+
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, format!("/crates/{}", krate.name()))],
+    )
+        .into_response())
 }
 
 #[derive(Template, Debug)]
 #[template(path = "results.html")]
 struct SearchResults {
     query: String,
+    meta: PageMeta,
+    hide_placeholders: bool,
+    ranker: Option<String>,
     results: Vec<CrateResult>,
+    /// The number of results on the pages before `results`.
+    offset: usize,
+    pages: Pages,
+    partial: bool,
+    /// The experiment variant that ordered the results, if any.
+    experiment: Option<Assignment>,
+    /// The query searched for instead of `query`, which appeared misspelled.
+    corrected: Option<String>,
+}
+
+#[derive(Template, Debug)]
+#[template(path = "results_list.html")]
+struct SearchResultsList {
+    query: String,
+    results: Vec<CrateResult>,
+    offset: usize,
+    pages: Pages,
+    partial: bool,
+    experiment: Option<Assignment>,
+    corrected: Option<String>,
+}
+
+/// Links to the neighboring pages of search results.
+#[derive(Debug)]
+struct Pages {
+    previous: Option<String>,
+    next: Option<String>,
+}
+
+/// Describes how long ago `date` was, such as "3 years ago".
+fn ago(date: Date) -> String {
+    let days = (time::OffsetDateTime::now_utc().date() - date).whole_days();
+    let (count, unit) = match days {
+        ..=0 => return i18n::t("age-today"),
+        1..=59 => (days, "age-days"),
+        60..=729 => (days / 30, "age-months"),
+        _ => (days / 365, "age-years"),
+    };
+    i18n::t_arg(unit, "count", count)
 }
 
 #[derive(Template, Debug)]
 #[template(path = "index.html")]
-struct Index;
+struct Index {
+    dashboard: Arc<Dashboard>,
+    /// The crates of [`Dashboard::trending`].
+    trending: Vec<CachedCrate>,
+    /// The crate of the day, if any crate is eligible.
+    featured: Option<CachedCrate>,
+    /// The crates the visitor viewed most recently, newest first.
+    recent: Vec<CachedCrate>,
+    /// Whether the search form's checkbox for hiding placeholders starts
+    /// checked.
+    hide_placeholders: bool,
+}
+
+/// Redirects to a randomly picked crate, favoring the same lesser-known but
+/// active crates as the crate of the day.
+async fn random_crate(State((_, cache, _)): State<AppState>) -> Result<Response, AppError> {
+    let krate = discover::random(&cache.crates()?).ok_or(AppError::NotFound)?;
+    Ok((
+        StatusCode::FOUND,
+        [
+            (LOCATION, format!("/crates/{}", krate.name())),
+            (CACHE_CONTROL, String::from("no-store")),
+        ],
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, Debug)]
+struct CratePageQuery {
+    /// `print` for a printable summary of the crate, or `md` for the same
+    /// summary in Markdown.
+    format: Option<String>,
+}
+
+/// Shows a crate's page, or the same information as JSON when the request
+/// prefers `application/json` or the name ends with `.json`. `?format=print`
+/// and `?format=md` export a summary of the crate instead. Names spelled
+/// differently than the crate's, such as `serde-json` for `serde_json`,
+/// redirect to the crate's spelling.
+async fn crate_page(
+    State((db, cache, _)): State<AppState>,
+    Path(name): Path<String>,
+    QueryParams(query): QueryParams<CratePageQuery>,
+    RawQuery(raw_query): RawQuery,
+    Extension(site): Extension<SiteUrl>,
+    Extension(recently_viewed): Extension<RecentlyViewed>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    // Crate names can't contain periods, so the suffix is never part of one.
+    let (name, suffix) = match name.strip_suffix(".json") {
+        Some(name) => (name.to_string(), ".json"),
+        None => (name, ""),
+    };
+    if let Some(canonical) = canonical_name(&cache, &name)? {
+        let query = raw_query
+            .map(|query| format!("?{query}"))
+            .unwrap_or_default();
+        return Ok(moved_permanently(&format!(
+            "/crates/{canonical}{suffix}{query}"
+        )));
+    }
+    let json = !suffix.is_empty() || prefers_json(&headers);
+    let info = CrateInfo::load(&db, &cache, &name)?;
+
+    if let Some(format) = query.format {
+        let info = info.ok_or(AppError::NotFound)?;
+        let url = site.absolute(&format!("/crates/{}", info.name));
+        let generated = time::OffsetDateTime::now_utc().date();
+        return match format.as_str() {
+            "print" => Ok(Html(
+                CratePrint {
+                    info,
+                    url,
+                    generated,
+                }
+                .render()?,
+            )
+            .into_response()),
+            "md" => {
+                let disposition = format!("inline; filename=\"{}.md\"", info.name);
+                let summary = CrateMarkdown {
+                    info,
+                    url,
+                    generated,
+                }
+                .render()?;
+                Ok((
+                    [
+                        (CONTENT_TYPE, String::from("text/markdown; charset=utf-8")),
+                        (CONTENT_DISPOSITION, disposition),
+                    ],
+                    summary,
+                )
+                    .into_response())
+            }
+            _ => Err(AppError::BadRequest(format!("unknown format: {format}"))),
+        };
+    }
+
+    let mut response = match (info, json) {
+        (Some(info), true) => Json(info).into_response(),
+        (Some(info), false) => {
+            let meta = CratePage::meta(&site, &info);
+            let cookie = recently_viewed.record(&headers, &info.name);
+            let mut response = Html(CratePage { info, meta }.render()?).into_response();
+            if let Some(cookie) = cookie {
+                response.headers_mut().append(SET_COOKIE, cookie);
+            }
+            response
+        }
+        (None, true) => not_found::crate_json(&cache, &name)?,
+        (None, false) => not_found::crate_page(&cache, &name)?,
+    };
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
+    Ok(response)
+}
+
+/// Returns the crate's spelling of `name` if it's spelled differently, such
+/// as `serde_json` for `serde-json` or `Serde_JSON`. Names are looked up by
+/// their normalized form, as indexed by `CratesByNormalizedName`.
+fn canonical_name(cache: &Cache, name: &str) -> anyhow::Result<Option<String>> {
+    let Some(id) = api::find_crate(cache, name)? else { return Ok(None) };
+    Ok(cache
+        .crates()?
+        .get(id)
+        .map(|krate| krate.name().to_string())
+        .filter(|canonical| canonical != name))
+}
+
+/// Percent-encodes `segment` for a URL path, leaving letters, digits, and
+/// `-._~+` as they are. Path parameters arrive decoded, so they need encoding
+/// again before being used in a `Location` header.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'+') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn moved_permanently(location: &str) -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(LOCATION, location.to_string())],
+    )
+        .into_response()
+}
+
+/// Returns true if `url` is an http or https URL. Crates' homepage,
+/// documentation, and repository URLs are only linked when this is true, since
+/// they come from crate metadata and could otherwise be `javascript:` URLs.
+pub(crate) fn is_web_url(url: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        url.get(..scheme.len())
+            .map_or(false, |prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Returns true if the `Accept` header ranks JSON above HTML. Wildcards count
+/// toward HTML, so browsers and clients that accept anything get the page.
+pub(crate) fn prefers_json(headers: &HeaderMap) -> bool {
+    let mut json = 0.;
+    let mut html = 0.;
+    for value in headers.get_all(ACCEPT) {
+        let Ok(value) = value.to_str() else { continue };
+        for range in value.split(',') {
+            let mut parameters = range.split(';');
+            let media_type = parameters.next().unwrap_or_default().trim();
+            let quality = parameters
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.);
+            match media_type {
+                "application/json" => json = f32::max(json, quality),
+                "text/html" | "text/*" | "*/*" => html = f32::max(html, quality),
+                _ => {}
+            }
+        }
+    }
+    json > html
+}
+
+#[derive(Template, Debug)]
+#[template(path = "crate.html")]
+struct CratePage {
+    info: CrateInfo,
+    meta: PageMeta,
+}
+
+impl CratePage {
+    fn meta(site: &SiteUrl, info: &CrateInfo) -> PageMeta {
+        let title = match &info.latest_version {
+            Some(version) => format!("{} {version}", info.name),
+            None => info.name.clone(),
+        };
+        let description = if info.description.trim().is_empty() {
+            format!("The {} Rust crate.", info.name)
+        } else {
+            info.description.clone()
+        };
+        PageMeta::new(site, &format!("/crates/{}", info.name), title, &description)
+    }
+}
+
+/// A summary of a crate for dependency review documents, which prints
+/// without the site's navigation and scripts.
+#[derive(Template, Debug)]
+#[template(path = "crate_print.html")]
+struct CratePrint {
+    info: CrateInfo,
+    /// The crate page's URL.
+    url: String,
+    /// When the summary was exported.
+    generated: Date,
+}
+
+/// The same summary as [`CratePrint`] in Markdown.
+#[derive(Template, Debug)]
+#[template(path = "crate.md")]
+struct CrateMarkdown {
+    info: CrateInfo,
+    url: String,
+    generated: Date,
+}
+
+/// Shows a single version of a crate. Requests for `latest` or a partial
+/// version such as `1.0` are redirected to the version they resolve to.
+async fn version_page(
+    State((db, cache, _)): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    if let Some(canonical) = canonical_name(&cache, &name)? {
+        return Ok(moved_permanently(&format!(
+            "/crates/{canonical}/{}",
+            encode_path_segment(&version)
+        )));
+    }
+    let resolved = VersionInfo::resolve(&db, &cache, &name, &version)?.ok_or(AppError::NotFound)?;
+    if resolved != version {
+        return Ok((
+            StatusCode::FOUND,
+            [(
+                LOCATION,
+                format!(
+                    "/crates/{}/{}",
+                    encode_path_segment(&name),
+                    encode_path_segment(&resolved)
+                ),
+            )],
+        )
+            .into_response());
+    }
+
+    let info = VersionInfo::load(&db, &cache, &name, &version)?.ok_or(AppError::NotFound)?;
+    Ok(Html(VersionPage { info }.render()?).into_response())
+}
+
+#[derive(Template, Debug)]
+#[template(path = "version.html")]
+struct VersionPage {
+    info: VersionInfo,
+}
+
+async fn version_diff(
+    State((db, cache, _)): State<AppState>,
+    Path((name, from, to)): Path<(String, String, String)>,
+) -> Result<Html<String>, AppError> {
+    let diff = VersionDiff::load(&db, &cache, &name, &from, &to)?.ok_or(AppError::NotFound)?;
+    Ok(Html(VersionDiffPage { diff }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "diff.html")]
+struct VersionDiffPage {
+    diff: VersionDiff,
+}
+
+async fn categories_page(State((_, cache, _)): State<AppState>) -> Result<Html<String>, AppError> {
+    let categories = cache.categories()?;
+    Ok(Html(CategoriesPage { categories }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "categories.html")]
+struct CategoriesPage {
+    categories: Arc<Vec<CategoryEntry>>,
+}
+
+async fn yanks_page(State((db, cache, _)): State<AppState>) -> Result<Html<String>, AppError> {
+    let entries = yanks::recent(&db, &cache)?;
+    Ok(Html(YanksPage { entries }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "yanks.html")]
+struct YanksPage {
+    entries: Vec<YankEntry>,
+}
+
+async fn ownership_page(State((db, cache, _)): State<AppState>) -> Result<Html<String>, AppError> {
+    let entries = ownership::recent(&db, &cache)?;
+    Ok(Html(OwnershipPage { entries }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "ownership.html")]
+struct OwnershipPage {
+    entries: Vec<OwnershipEntry>,
+}
+
+async fn keyword_trends_page(State((db, _, _)): State<AppState>) -> Result<Html<String>, AppError> {
+    let trends = keyword_trends::trends(&db)?;
+    Ok(Html(KeywordTrendsPage { trends }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "keyword_trends.html")]
+struct KeywordTrendsPage {
+    trends: KeywordTrends,
+}
+
+async fn watchlist_page(
+    State((db, cache, _)): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let activity = WatchlistActivity::load(&db, &cache, &id)?.ok_or(AppError::NotFound)?;
+    Ok(Html(WatchlistPage { activity }.render()?))
+}
+
+#[derive(Template, Debug)]
+#[template(path = "watchlist.html")]
+struct WatchlistPage {
+    activity: WatchlistActivity,
+}
+
+/// An error encountered while handling a page request, which is displayed to
+/// the user as an error page.
+#[derive(Debug)]
+pub(crate) enum AppError {
+    /// The request could not be understood.
+    BadRequest(String),
+    NotFound,
+    /// An unexpected error. The details are logged rather than shown to the
+    /// user.
+    Internal(anyhow::Error),
+}
+
+impl<E> From<E> for AppError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        Self::Internal(err.into())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::NotFound => (StatusCode::NOT_FOUND, i18n::t("error-not-found")),
+            AppError::Internal(err) => {
+                tracing::error!("error handling request: {err:?}");
+                (StatusCode::INTERNAL_SERVER_ERROR, i18n::t("error-internal"))
+            }
+        };
+
+        match (ErrorPage { status, message }).render() {
+            Ok(page) => (status, Html(page)).into_response(),
+            Err(err) => {
+                tracing::error!("error rendering error page: {err}");
+                status.into_response()
+            }
+        }
+    }
+}
+
+/// Logs `err` and returns a 500 status, for handlers that respond with a
+/// bare status code rather than an error page.
+pub(crate) fn internal_error<E: std::fmt::Display>(err: E) -> StatusCode {
+    tracing::error!("error handling request: {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[derive(Template, Debug)]
+#[template(path = "error.html")]
+struct ErrorPage {
+    status: StatusCode,
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(per_page: Option<usize>, page: Option<usize>) -> (usize, Vec<u32>) {
+        let mut results = (1..=25).collect::<Vec<u32>>();
+        let offset = paginate(&mut results, per_page, page);
+        (offset, results)
+    }
+
+    #[test]
+    fn pages_of_results() {
+        assert_eq!(page(None, Some(3)), (0, (1..=25).collect()));
+        assert_eq!(page(Some(10), None), (0, (1..=10).collect()));
+        assert_eq!(page(Some(10), Some(0)), (0, (1..=10).collect()));
+        assert_eq!(page(Some(10), Some(2)), (10, (11..=20).collect()));
+        assert_eq!(page(Some(10), Some(3)), (20, (21..=25).collect()));
+        assert_eq!(page(Some(10), Some(4)), (25, Vec::new()));
+        assert_eq!(page(Some(10), Some(usize::MAX)), (25, Vec::new()));
+    }
+
+    fn searched(offset: usize, more: bool) -> Searched {
+        Searched {
+            results: Vec::new(),
+            offset,
+            more,
+            partial: false,
+            experiment: None,
+            corrected: None,
+        }
+    }
+
+    #[test]
+    fn page_links() {
+        let query = Query::parse(String::from("q=async+io&page=2&exact=true")).unwrap();
+        let pages = query.pages(&searched(10, true));
+        assert_eq!(
+            pages.previous.as_deref(),
+            Some("/?q=async+io&page=1&exact=true")
+        );
+        assert_eq!(
+            pages.next.as_deref(),
+            Some("/?q=async+io&page=3&exact=true")
+        );
+
+        let query = Query::parse(String::from("serde")).unwrap();
+        let pages = query.pages(&searched(0, false));
+        assert_eq!(pages.previous, None);
+        assert_eq!(pages.next, None);
+    }
+
+    #[test]
+    fn only_web_urls_are_linked() {
+        assert!(is_web_url("https://github.com/serde-rs/serde"));
+        assert!(is_web_url("HTTP://example.com"));
+        assert!(!is_web_url("javascript:alert(1)"));
+        assert!(!is_web_url(" https://example.com"));
+        assert!(!is_web_url("data:text/html,hi"));
+        assert!(!is_web_url("//example.com"));
+        assert!(!is_web_url("ht"));
+        assert!(!is_web_url("é"));
+    }
+
+    #[test]
+    fn path_segments_are_encoded() {
+        assert_eq!(encode_path_segment("serde_json"), "serde_json");
+        assert_eq!(
+            encode_path_segment("1.0.0-rc.1+build.5"),
+            "1.0.0-rc.1+build.5"
+        );
+        assert_eq!(
+            encode_path_segment("1.0\r\nSet-Cookie: a"),
+            "1.0%0D%0ASet-Cookie%3A%20a"
+        );
+        assert_eq!(encode_path_segment("../x?y#z"), "..%2Fx%3Fy%23z");
+        assert_eq!(encode_path_segment("é"), "%C3%A9");
+    }
+}
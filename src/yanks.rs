@@ -0,0 +1,38 @@
+use bonsaidb::{core::schema::SerializedCollection, local::Database};
+use serde::Serialize;
+
+use crate::{cache::Cache, schema::YankEvent};
+
+/// The number of events shown on the yanks page and in its feed.
+const RECENT_EVENTS: u32 = 100;
+
+#[derive(Serialize, Debug)]
+pub(crate) struct YankEntry {
+    pub id: u64,
+    pub name: String,
+    pub version: String,
+    pub yanked: bool,
+    pub at: String,
+}
+
+/// Returns the most recent yank events, newest first.
+pub(crate) fn recent(db: &Database, cache: &Cache) -> anyhow::Result<Vec<YankEntry>> {
+    let crates = cache.crates()?;
+    let mut entries = Vec::new();
+    for event in YankEvent::all(db)
+        .descending()
+        .limit(RECENT_EVENTS)
+        .query()?
+    {
+        // Crates can be deleted after their versions were yanked.
+        let Some(krate) = crates.get(event.contents.crate_id) else { continue };
+        entries.push(YankEntry {
+            id: event.header.id,
+            name: krate.name().to_string(),
+            version: event.contents.version,
+            yanked: event.contents.yanked,
+            at: event.contents.at,
+        });
+    }
+    Ok(entries)
+}
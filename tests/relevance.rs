@@ -0,0 +1,182 @@
+//! Snapshot tests of search relevance.
+//!
+//! These import the small dump in `tests/fixtures/dump` and assert the top
+//! results of a handful of canonical queries. If a ranking change
+//! intentionally alters these results, update the expectations alongside it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bonsaidb::local::Database;
+use delve_rs::{
+    bench,
+    cache::Cache,
+    embeddings::SemanticMatches,
+    filters::Filters,
+    query,
+    search_backend::{Backend, SearchConfig},
+    SearchIndex,
+};
+use tempfile::TempDir;
+
+const EXPECTED: &[(&str, [&str; 5])] = &[
+    (
+        "http server",
+        ["hyper", "axum", "actix-web", "rocket", "warp"],
+    ),
+    (
+        "json",
+        ["serde_json", "simd-json", "json", "sonic-rs", "json5"],
+    ),
+    (
+        "cli args",
+        ["clap", "pico-args", "lexopt", "argh", "gumdrop"],
+    ),
+    ("orm", ["diesel", "sea-orm", "rbatis", "ormlite", "welds"]),
+];
+
+/// Imports the fixture dump into a new database. The returned directory
+/// must be kept alive for as long as the database is in use.
+fn import_fixture() -> anyhow::Result<(TempDir, Database, Cache, SearchIndex)> {
+    bench::import_fixture(&SearchConfig::default())
+}
+
+/// Queries that the tantivy and description backends must match the same
+/// crates for. The description backend doesn't index READMEs, so these only
+/// search names and descriptions.
+const PARITY_QUERIES: &[&str] = &[
+    "name:json",
+    "name:JSON",
+    "description:json",
+    "description:web description:framework",
+    "description:\"cli args\"",
+    "description:orm NOT name:diesel",
+    "description:http OR name:rand",
+    "name:nothing",
+];
+
+#[test]
+fn backends_match_the_same_crates() -> anyhow::Result<()> {
+    let (_dir, _db, _cache, tantivy) = import_fixture()?;
+    let (_dir, _db, _cache, descriptions) = bench::import_fixture(&SearchConfig {
+        backend: Backend::Descriptions,
+        ..SearchConfig::default()
+    })?;
+
+    let mut failures = Vec::new();
+    for q in PARITY_QUERIES {
+        let matches = |index: &SearchIndex| -> anyhow::Result<HashSet<u64>> {
+            Ok(index
+                .search(q, 100)?
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect())
+        };
+        let (expected, actual) = (matches(&tantivy)?, matches(&descriptions)?);
+        if expected != actual {
+            failures.push(format!(
+                "{q:?}: tantivy matched {expected:?}, got {actual:?}"
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    Ok(())
+}
+
+#[test]
+fn canonical_queries() -> anyhow::Result<()> {
+    let (_dir, db, cache, index) = import_fixture()?;
+
+    let mut failures = Vec::new();
+    for (q, expected) in EXPECTED {
+        let (terms, filters) = Filters::parse(q);
+        let results = query(&terms, &filters, &db, &cache, &index)?.results;
+        let top = results
+            .iter()
+            .take(expected.len())
+            .map(|result| result.result.name())
+            .collect::<Vec<_>>();
+        if top != expected {
+            failures.push(format!("{q:?}: expected {expected:?}, got {top:?}"));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    Ok(())
+}
+
+#[test]
+fn forks_and_placeholders_are_not_ranked_separately() -> anyhow::Result<()> {
+    let (_dir, db, cache, index) = import_fixture()?;
+
+    let (terms, filters) = Filters::parse("http server");
+    let results = query(&terms, &filters, &db, &cache, &index)?.results;
+    let axum = results
+        .iter()
+        .find(|result| result.result.name() == "axum")
+        .expect("axum missing from results");
+    assert!(axum
+        .similar
+        .iter()
+        .any(|similar| similar.result.name() == "axum-fork"));
+    assert_eq!(
+        results.last().map(|result| result.result.name()),
+        Some("http-server")
+    );
+
+    let (terms, filters) = Filters::parse("http server placeholders:hide");
+    let results = query(&terms, &filters, &db, &cache, &index)?.results;
+    assert!(results
+        .iter()
+        .all(|result| result.result.name() != "http-server"));
+
+    Ok(())
+}
+
+#[test]
+fn searches_past_their_deadline_are_partial() -> anyhow::Result<()> {
+    let (_dir, db, cache, index) = import_fixture()?;
+
+    let (terms, mut filters) = Filters::parse("json");
+    filters.deadline = Some(Instant::now() + Duration::from_secs(60));
+    let results = query(&terms, &filters, &db, &cache, &index)?;
+    assert!(!results.partial);
+    assert!(!results.results.is_empty());
+
+    filters.deadline = Some(Instant::now());
+    let results = query(&terms, &filters, &db, &cache, &index)?;
+    assert!(results.partial);
+    assert!(results.results.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn semantic_matches_respect_constraints() -> anyhow::Result<()> {
+    let (_dir, db, cache, index) = import_fixture()?;
+    let serde_json = cache
+        .names()?
+        .get("serde_json")
+        .expect("serde_json missing");
+    let semantic = Arc::new(SemanticMatches::new(1., HashMap::from([(serde_json, 1.)])));
+    let names = |q: &str| -> anyhow::Result<Vec<String>> {
+        let (terms, mut filters) = Filters::parse(q);
+        filters.semantic = Some(semantic.clone());
+        Ok(query(&terms, &filters, &db, &cache, &index)?
+            .results
+            .iter()
+            .map(|result| result.result.name().to_string())
+            .collect())
+    };
+
+    // Semantic matches are results of queries that only list words.
+    assert!(names("nothing else matches these words")?.contains(&String::from("serde_json")));
+    // They can't be checked against NOT, so they're left out.
+    assert!(!names("serde NOT json")?.contains(&String::from("serde_json")));
+
+    Ok(())
+}